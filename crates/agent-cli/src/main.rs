@@ -10,7 +10,7 @@ use async_trait::async_trait;
 use clap::{Parser, Subcommand};
 use serde_json::json;
 use std::sync::Arc;
-use tracing::info;
+use tracing::{info, warn};
 
 #[derive(Parser)]
 #[command(name = "agent", about = "Microsoft Agent Framework (Rust Edition) CLI")]
@@ -34,6 +34,7 @@ enum Commands {
 }
 
 struct DemoAgent {
+    #[allow(dead_code)]
     model: StubModel,
     tools: Arc<ToolRegistry>,
 }
@@ -66,8 +67,14 @@ impl Agent for DemoAgent {
                             allow_tool_execution: true,
                             ..SafetyPolicy::default()
                         },
+                        timeout_ms: None,
                     },
                     chain_of_thought: None,
+                    sub_plan: None,
+                    depends_on: Vec::new(),
+                    condition: None,
+                    for_each: None,
+                    delegate: None,
                 },
                 Step {
                     id: "add".into(),
@@ -82,8 +89,14 @@ impl Agent for DemoAgent {
                             allow_tool_execution: true,
                             ..SafetyPolicy::default()
                         },
+                        timeout_ms: None,
                     },
                     chain_of_thought: None,
+                    sub_plan: None,
+                    depends_on: Vec::new(),
+                    condition: None,
+                    for_each: None,
+                    delegate: None,
                 },
             ],
             metadata: json!({}),
@@ -110,6 +123,9 @@ impl Agent for DemoAgent {
                     retries: 0,
                     fallback_used: false,
                     control_notes: vec![],
+                    fallback_applied: None,
+                    failure_kind: None,
+                    redactions: Vec::new(),
                 });
             }
         }
@@ -121,6 +137,9 @@ impl Agent for DemoAgent {
             retries: 0,
             fallback_used: false,
             control_notes: vec![],
+            fallback_applied: None,
+            failure_kind: None,
+            redactions: Vec::new(),
         })
     }
 }
@@ -134,9 +153,9 @@ async fn main() -> anyhow::Result<()> {
             println!("Scaffolded new agent project: {name}");
         }
         Commands::Run => {
-            let mut registry = ToolRegistry::new();
+            let registry = ToolRegistry::new();
             registry.register(TimeTool);
-            registry.register(LogTool);
+            registry.register(LogTool::new());
             registry.register(MathTool);
             let pwd = std::env::current_dir()?;
             registry.register(FileTool::new(pwd));
@@ -152,6 +171,7 @@ async fn main() -> anyhow::Result<()> {
                 metadata: json!({}),
                 memory: None,
                 tool_permissions: ToolPermissions::default(),
+                cancellation: None,
             };
             let agent = DemoAgent {
                 model: StubModel,
@@ -161,19 +181,30 @@ async fn main() -> anyhow::Result<()> {
                 max_iterations: 4,
                 delay: std::time::Duration::from_millis(0),
                 mode: ControlMode::Deterministic,
+                ..Default::default()
             };
+            for warning in agent.plan(&ctx).await?.lint() {
+                warn!(step = %warning.step_id, "{}", warning.message);
+            }
             let outcomes = loop_ctrl.run(&agent, &mut ctx).await?;
             for outcome in outcomes {
                 info!(step = %outcome.step_id, output = %outcome.output, "step completed");
             }
         }
         Commands::Test => {
+            let agent = DemoAgent {
+                model: StubModel,
+                tools: Arc::new(ToolRegistry::new()),
+            };
+            for warning in agent.plan(&AgentContext::default()).await?.lint() {
+                println!("plan warning: {}", warning.message);
+            }
             println!(
                 "Validated tool schemas and model stubs: built-in tools expose JSON schemas; models are ready for test replay."
             );
         }
         Commands::Tools => {
-            println!("Built-in tools: log, math, time, http_fetch, file, search (pluggable)");
+            println!("Built-in tools: log, math, time, http_fetch, http_request, file, search (pluggable)");
         }
         Commands::Models => {
             println!("Models: stub, random_reasoner");
@@ -0,0 +1,111 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Shared settings for building `reqwest` clients used by HTTP-backed
+/// models and tools.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct HttpClientConfig {
+    pub default_headers: BTreeMap<String, String>,
+    pub timeout: Option<Duration>,
+    pub user_agent: Option<String>,
+    pub proxy: Option<String>,
+    /// Maximum redirects to follow; reqwest's default policy applies when `None`.
+    pub max_redirects: Option<usize>,
+}
+
+#[derive(Debug, Error)]
+pub enum HttpClientError {
+    #[error("invalid header {0}")]
+    InvalidHeader(String),
+    #[error("invalid proxy: {0}")]
+    InvalidProxy(String),
+    #[error("failed to build client: {0}")]
+    Build(String),
+}
+
+impl HttpClientConfig {
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.default_headers.insert(name.into(), value.into());
+        self
+    }
+
+    pub fn with_bearer_token(self, token: impl AsRef<str>) -> Self {
+        self.with_header("Authorization", format!("Bearer {}", token.as_ref()))
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn with_user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+
+    pub fn with_proxy(mut self, proxy: impl Into<String>) -> Self {
+        self.proxy = Some(proxy.into());
+        self
+    }
+
+    pub fn with_max_redirects(mut self, max_redirects: usize) -> Self {
+        self.max_redirects = Some(max_redirects);
+        self
+    }
+
+    pub fn build_client(&self) -> Result<reqwest::Client, HttpClientError> {
+        self.client_builder()?
+            .build()
+            .map_err(|e| HttpClientError::Build(e.to_string()))
+    }
+
+    /// A builder with these settings applied, for callers that layer their
+    /// own on top before building.
+    pub fn client_builder(&self) -> Result<reqwest::ClientBuilder, HttpClientError> {
+        let mut headers = reqwest::header::HeaderMap::new();
+        for (name, value) in &self.default_headers {
+            let header_name = reqwest::header::HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| HttpClientError::InvalidHeader(name.clone()))?;
+            let mut header_value = reqwest::header::HeaderValue::from_str(value)
+                .map_err(|_| HttpClientError::InvalidHeader(name.clone()))?;
+            header_value.set_sensitive(true);
+            headers.insert(header_name, header_value);
+        }
+
+        let mut builder = reqwest::Client::builder().default_headers(headers);
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(user_agent) = &self.user_agent {
+            builder = builder.user_agent(user_agent.clone());
+        }
+        if let Some(proxy) = &self.proxy {
+            let proxy = reqwest::Proxy::all(proxy)
+                .map_err(|e| HttpClientError::InvalidProxy(e.to_string()))?;
+            builder = builder.proxy(proxy);
+        }
+        if let Some(max_redirects) = self.max_redirects {
+            builder = builder.redirect(reqwest::redirect::Policy::limited(max_redirects));
+        }
+        Ok(builder)
+    }
+}
+
+// Header values frequently carry credentials, so only their names are shown.
+impl std::fmt::Debug for HttpClientConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HttpClientConfig")
+            .field(
+                "default_headers",
+                &self.default_headers.keys().collect::<Vec<_>>(),
+            )
+            .field("timeout", &self.timeout)
+            .field("user_agent", &self.user_agent)
+            .field("proxy", &self.proxy)
+            .field("max_redirects", &self.max_redirects)
+            .finish()
+    }
+}
@@ -1,10 +1,16 @@
 use agent_memory::MemoryStore;
 use async_trait::async_trait;
-use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::Value;
-use std::{fmt::Debug, sync::Arc};
+use std::{collections::HashSet, fmt::Debug, sync::Arc};
 use thiserror::Error;
 
+mod http;
+
+pub use http::{HttpClientConfig, HttpClientError};
+pub use tokio_util::sync::CancellationToken;
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct AgentConfig {
     pub name: String,
@@ -31,6 +37,10 @@ pub struct AgentContext {
     pub memory: Option<Arc<dyn MemoryStore>>,
     #[serde(skip_serializing, skip_deserializing)]
     pub tool_permissions: ToolPermissions,
+    /// Set while a run is cancellable; agents pass it on to their model and
+    /// tool calls so an abort stops in-flight work.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub cancellation: Option<CancellationToken>,
 }
 
 #[derive(Debug, Error)]
@@ -51,26 +61,36 @@ pub enum AgentError {
     Validation(String),
     #[error("retry exhausted after {attempts} attempts")]
     RetryExhausted { attempts: usize },
+    #[error("tool {tool} rate limited, retry after {retry_after_ms}ms")]
+    RateLimited { tool: String, retry_after_ms: u64 },
+    #[error("model failure: {0}")]
+    Model(String),
+    #[error("model rate limited")]
+    ModelRateLimited { retry_after_ms: Option<u64> },
+    #[error("cancelled")]
+    Cancelled,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Default)]
 pub struct RetryPolicy {
     pub max_retries: usize,
     pub backoff_ms: u64,
     pub jitter: bool,
+    /// Longest wait a rate-limited tool or model may ask for between
+    /// attempts; [`RetryPolicy::DEFAULT_MAX_DELAY_MS`] when unset.
+    #[serde(default)]
+    pub max_delay_ms: Option<u64>,
 }
 
-impl Default for RetryPolicy {
-    fn default() -> Self {
-        Self {
-            max_retries: 0,
-            backoff_ms: 0,
-            jitter: false,
-        }
+impl RetryPolicy {
+    pub const DEFAULT_MAX_DELAY_MS: u64 = 60_000;
+
+    pub fn max_delay(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.max_delay_ms.unwrap_or(Self::DEFAULT_MAX_DELAY_MS))
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Plan {
     pub goal: String,
     pub steps: Vec<Step>,
@@ -84,9 +104,151 @@ impl Plan {
             current: 0,
         }
     }
+
+    /// Like [`Plan::executable`], but resolves duplicate step ids first.
+    pub fn try_executable(mut self, strategy: DedupStrategy) -> Result<ExecutablePlan, AgentError> {
+        self.dedup_step_ids(strategy)?;
+        Ok(self.executable())
+    }
+
+    pub fn dedup_step_ids(&mut self, strategy: DedupStrategy) -> Result<(), AgentError> {
+        let mut seen: HashSet<String> = HashSet::new();
+        let original: HashSet<String> = self.steps.iter().map(|s| s.id.clone()).collect();
+        for step in &mut self.steps {
+            if seen.insert(step.id.clone()) {
+                continue;
+            }
+            match strategy {
+                DedupStrategy::Error => {
+                    return Err(AgentError::Validation(format!(
+                        "duplicate step id: {}",
+                        step.id
+                    )))
+                }
+                DedupStrategy::SuffixIndex => {
+                    let mut index = 1;
+                    let mut candidate = format!("{}-{index}", step.id);
+                    while seen.contains(&candidate) || original.contains(&candidate) {
+                        index += 1;
+                        candidate = format!("{}-{index}", step.id);
+                    }
+                    step.id = candidate.clone();
+                    seen.insert(candidate);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether any step declares `depends_on`.
+    pub fn has_dependencies(&self) -> bool {
+        self.steps.iter().any(|step| !step.depends_on.is_empty())
+    }
+
+    /// Step ids grouped into layers whose steps depend only on earlier
+    /// layers, in plan order within each layer. Rejects duplicate ids,
+    /// unknown dependencies and cycles.
+    pub fn dependency_layers(&self) -> Result<Vec<Vec<String>>, AgentError> {
+        let mut remaining: Vec<&Step> = Vec::with_capacity(self.steps.len());
+        let mut ids = HashSet::new();
+        for step in &self.steps {
+            if !ids.insert(step.id.as_str()) {
+                return Err(AgentError::Validation(format!(
+                    "duplicate step id: {}",
+                    step.id
+                )));
+            }
+            remaining.push(step);
+        }
+        for step in &self.steps {
+            if let Some(missing) = step.depends_on.iter().find(|id| !ids.contains(id.as_str())) {
+                return Err(AgentError::Validation(format!(
+                    "step {} depends on unknown step {missing}",
+                    step.id
+                )));
+            }
+        }
+
+        let mut done: HashSet<&str> = HashSet::new();
+        let mut layers = Vec::new();
+        while !remaining.is_empty() {
+            let (ready, blocked): (Vec<&Step>, Vec<&Step>) = remaining
+                .into_iter()
+                .partition(|step| step.depends_on.iter().all(|id| done.contains(id.as_str())));
+            if ready.is_empty() {
+                let cycle: Vec<&str> = blocked.iter().map(|step| step.id.as_str()).collect();
+                return Err(AgentError::Validation(format!(
+                    "dependency cycle among steps: {}",
+                    cycle.join(", ")
+                )));
+            }
+            done.extend(ready.iter().map(|step| step.id.as_str()));
+            layers.push(ready.iter().map(|step| step.id.clone()).collect());
+            remaining = blocked;
+        }
+        Ok(layers)
+    }
+
+    /// Flags likely authoring mistakes, such as tool-less steps that depend on
+    /// the agent matching their id. Steps that carry a `sub_plan` are checked
+    /// through their nested steps instead; `for_each` and `delegate` steps
+    /// are skipped.
+    pub fn lint(&self) -> Vec<LintWarning> {
+        let mut warnings = Vec::new();
+        for step in &self.steps {
+            if let Some(sub_plan) = &step.sub_plan {
+                warnings.extend(sub_plan.lint());
+                continue;
+            }
+            if step.for_each.is_some() || step.delegate.is_some() {
+                continue;
+            }
+            if step.tool.is_none() {
+                warnings.push(LintWarning {
+                    step_id: step.id.clone(),
+                    kind: LintKind::MissingTool,
+                    message: format!(
+                        "step {} has no tool and relies on the agent handling its id",
+                        step.id
+                    ),
+                });
+            }
+            if step.args.as_object().is_some_and(|args| args.is_empty()) {
+                warnings.push(LintWarning {
+                    step_id: step.id.clone(),
+                    kind: LintKind::EmptyArgs,
+                    message: format!("step {} has empty args", step.id),
+                });
+            }
+        }
+        warnings
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LintWarning {
+    pub step_id: String,
+    pub kind: LintKind,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LintKind {
+    MissingTool,
+    EmptyArgs,
+}
+
+/// How [`Plan::try_executable`] treats steps sharing an id.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DedupStrategy {
+    /// Reject the plan with [`AgentError::Validation`].
+    #[default]
+    Error,
+    /// Rename later duplicates to `<id>-1`, `<id>-2`, ...
+    SuffixIndex,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Step {
     pub id: String,
     pub description: String,
@@ -96,6 +258,26 @@ pub struct Step {
     pub policies: StepPolicies,
     #[serde(skip_serializing, skip_deserializing)]
     pub chain_of_thought: Option<ChainOfThought>,
+    /// Nested plan executed in place of this step.
+    #[serde(default)]
+    pub sub_plan: Option<Box<Plan>>,
+    /// Ids of steps that must succeed before this one starts. Steps with no
+    /// path between them may run concurrently; see [`Plan::dependency_layers`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub depends_on: Vec<String>,
+    /// Expression over earlier outcomes and the context metadata, e.g.
+    /// `steps.search.success && steps.search.output.results | length > 0`;
+    /// the step is skipped when it is false. Unconditional when `None`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub condition: Option<String>,
+    /// Runs a template step once per item of an earlier step's output in
+    /// place of this step.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub for_each: Option<Box<ForEach>>,
+    /// Hands the step to another agent, whose run's final output becomes
+    /// this step's.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delegate: Option<Box<Delegate>>,
 }
 
 impl Step {
@@ -105,6 +287,32 @@ impl Step {
         self
     }
 
+    pub fn with_sub_plan(mut self, plan: Plan) -> Self {
+        self.sub_plan = Some(Box::new(plan));
+        self
+    }
+
+    /// Adds a step that must succeed before this one starts.
+    pub fn with_dependency<T: Into<String>>(mut self, step_id: T) -> Self {
+        self.depends_on.push(step_id.into());
+        self
+    }
+
+    pub fn with_condition<T: Into<String>>(mut self, condition: T) -> Self {
+        self.condition = Some(condition.into());
+        self
+    }
+
+    pub fn with_for_each(mut self, for_each: ForEach) -> Self {
+        self.for_each = Some(Box::new(for_each));
+        self
+    }
+
+    pub fn with_delegate(mut self, delegate: Delegate) -> Self {
+        self.delegate = Some(Box::new(delegate));
+        self
+    }
+
     pub fn add_cot_note<T: Into<String>>(&mut self, note: T) {
         let mut cot = self.chain_of_thought.take().unwrap_or_default();
         cot.push(note);
@@ -119,14 +327,74 @@ impl Step {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+/// Iteration over a JSON array, e.g. "summarize every search result".
+///
+/// `items` is a path in the syntax of step conditions, such as
+/// `steps.search.output.results`, and must lead to an array. Each item runs a
+/// copy of `template` with id `<step id>[<index>]`, in which strings of its
+/// description and args may embed `{{ expression }}`s over the condition
+/// scope plus `item` and `index`; a string that is a single expression takes
+/// that value's JSON type. The template's `condition` is also checked per item.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ForEach {
+    pub items: String,
+    pub template: Step,
+    /// Items run at once; one after another when `None`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_concurrency: Option<usize>,
+}
+
+impl ForEach {
+    pub fn new<T: Into<String>>(items: T, template: Step) -> Self {
+        Self {
+            items: items.into(),
+            template,
+            max_concurrency: None,
+        }
+    }
+
+    pub fn with_max_concurrency(mut self, max_concurrency: usize) -> Self {
+        self.max_concurrency = Some(max_concurrency);
+        self
+    }
+}
+
+/// Another agent, by the name it is registered under, and the input it is
+/// started with.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Delegate {
+    pub agent: String,
+    #[serde(default)]
+    pub input: Value,
+}
+
+impl Delegate {
+    pub fn new<T: Into<String>>(agent: T, input: Value) -> Self {
+        Self {
+            agent: agent.into(),
+            input,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Default)]
 pub struct StepPolicies {
     pub retry: RetryPolicy,
     pub fallback: Option<FallbackPolicy>,
     pub safety: SafetyPolicy,
+    /// Longest a single attempt may take before it fails with
+    /// [`AgentError::Timeout`]; retries get the same allowance each.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_ms: Option<u64>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl StepPolicies {
+    pub fn timeout(&self) -> Option<std::time::Duration> {
+        self.timeout_ms.map(std::time::Duration::from_millis)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Subtask {
     pub id: String,
     pub description: String,
@@ -139,6 +407,7 @@ pub struct ExecutablePlan {
 }
 
 impl ExecutablePlan {
+    #[allow(clippy::should_implement_trait)]
     pub fn next(&mut self) -> Option<Step> {
         if self.current < self.plan.steps.len() {
             let step = self.plan.steps[self.current].clone();
@@ -150,9 +419,11 @@ impl ExecutablePlan {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Default)]
 pub struct SafetyPolicy {
     pub allow_tool_execution: bool,
+    /// Applied to tool output: rules starting with `/` are JSON pointers whose
+    /// value is masked, anything else is a regex whose matches are masked.
     pub redaction_rules: Vec<String>,
     pub rbac_roles: Vec<String>,
     pub input_validation: Vec<InputValidationRule>,
@@ -163,94 +434,88 @@ pub struct SafetyPolicy {
     pub guardrails: Vec<GuardrailLLM>,
     pub prompt_filters: Vec<PromptFilter>,
     pub output_validators: Vec<OutputPolicyValidator>,
+    /// The step waits for the runtime's intervention handler to approve it
+    /// before it runs.
+    #[serde(default)]
+    pub requires_review: bool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Default)]
 pub struct InputValidationRule {
     pub name: String,
     pub description: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Default)]
 pub struct SandboxPolicy {
     pub enabled: bool,
     pub allowed_operations: Vec<String>,
     pub forbidden_paths: Vec<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Default)]
 pub struct RateLimitRule {
     pub name: String,
     pub max_calls: u64,
     pub per_seconds: u64,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Default)]
 pub struct AccessPolicy {
     pub resource: String,
     pub required_roles: Vec<String>,
     pub conditions: Vec<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Default)]
 pub struct RetryFallbackDirective {
     pub identifier: String,
     pub retryable_errors: Vec<String>,
     pub fallback_steps: Vec<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Default)]
 pub struct GuardrailLLM {
     pub model: String,
     pub purpose: GuardrailPurpose,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Default)]
 pub enum GuardrailPurpose {
+    #[default]
     InputValidation,
     OutputModeration,
     ToolGatekeeping,
 }
 
-impl Default for GuardrailPurpose {
-    fn default() -> Self {
-        GuardrailPurpose::InputValidation
-    }
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Default)]
 pub struct PromptFilter {
     pub pattern: String,
     pub action: FilterAction,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Default)]
 pub enum FilterAction {
     Reject,
     Mask,
+    #[default]
     AllowWithTag,
 }
 
-impl Default for FilterAction {
-    fn default() -> Self {
-        FilterAction::AllowWithTag
-    }
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Default)]
 pub struct OutputPolicyValidator {
     pub name: String,
     pub description: Option<String>,
     pub block_on_failure: bool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct FallbackPolicy {
     pub strategy: FallbackStrategy,
     pub reason: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(tag = "type", content = "data")]
 pub enum FallbackStrategy {
     Skip,
@@ -259,6 +524,26 @@ pub enum FallbackStrategy {
     Abort,
 }
 
+impl FallbackStrategy {
+    pub fn kind(&self) -> FallbackKind {
+        match self {
+            FallbackStrategy::Skip => FallbackKind::Skip,
+            FallbackStrategy::RetryWithLimit { .. } => FallbackKind::RetryWithLimit,
+            FallbackStrategy::AlternateTool { .. } => FallbackKind::AlternateTool,
+            FallbackStrategy::Abort => FallbackKind::Abort,
+        }
+    }
+}
+
+/// Data-free discriminant of [`FallbackStrategy`], recorded on outcomes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum FallbackKind {
+    Skip,
+    RetryWithLimit,
+    AlternateTool,
+    Abort,
+}
+
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ChainOfThought {
     notes: Vec<String>,
@@ -284,6 +569,25 @@ pub struct ToolPermissions {
     pub denied: Vec<String>,
 }
 
+impl ToolPermissions {
+    /// An empty `allowed` list permits every tool that is not `denied`.
+    /// Entries may be wildcards: `*` matches every tool and `fs/*` every
+    /// tool under the `fs` namespace.
+    pub fn permits(&self, tool: &str) -> bool {
+        !self.denied.iter().any(|p| tool_pattern_matches(p, tool))
+            && (self.allowed.is_empty()
+                || self.allowed.iter().any(|p| tool_pattern_matches(p, tool)))
+    }
+}
+
+fn tool_pattern_matches(pattern: &str, tool: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some("") => true,
+        Some(prefix) if prefix.ends_with('/') => tool.starts_with(prefix),
+        _ => pattern == tool,
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StepOutcome {
     pub step_id: String,
@@ -293,6 +597,43 @@ pub struct StepOutcome {
     pub retries: usize,
     pub fallback_used: bool,
     pub control_notes: Vec<String>,
+    /// Which fallback strategy produced this outcome, if any.
+    #[serde(default)]
+    pub fallback_applied: Option<FallbackKind>,
+    /// Structured reason for a failed outcome, when the runtime knows it.
+    #[serde(default)]
+    pub failure_kind: Option<FailureKind>,
+    /// What `SafetyPolicy::redaction_rules` removed from tool output.
+    #[serde(default)]
+    pub redactions: Vec<Redaction>,
+}
+
+/// One redaction rule that matched a tool's output.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Redaction {
+    pub tool: String,
+    /// The rule as written in `SafetyPolicy::redaction_rules`.
+    pub rule: String,
+    /// JSON pointer of the value that was changed (`""` for the whole output).
+    pub path: String,
+    /// Number of matches replaced at `path`.
+    pub count: usize,
+}
+
+/// Why the runtime failed a step, for callers that need more than the error text.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FailureKind {
+    /// The step's tool is not permitted for the running agent.
+    ToolNotPermitted { tool: String },
+    /// An `AlternateTool` fallback named a tool missing from the registry.
+    MissingAlternateTool { tool: String },
+    /// The step's `condition` was false, so it did not run.
+    ConditionNotMet { condition: String },
+    /// The step needed review and the reviewer skipped it.
+    SkippedByReviewer,
+    /// The step needed review and the reviewer stopped the run.
+    AbortedByReviewer { reason: String },
 }
 
 impl StepOutcome {
@@ -305,6 +646,9 @@ impl StepOutcome {
             retries: 0,
             fallback_used: false,
             control_notes: Vec::new(),
+            fallback_applied: None,
+            failure_kind: None,
+            redactions: Vec::new(),
         }
     }
 
@@ -317,8 +661,21 @@ impl StepOutcome {
             retries: 0,
             fallback_used: false,
             control_notes: vec!["failure".to_string()],
+            fallback_applied: None,
+            failure_kind: None,
+            redactions: Vec::new(),
         }
     }
+
+    /// Deserializes the whole output into `T`.
+    pub fn output_as<T: DeserializeOwned>(&self) -> Result<T, serde_json::Error> {
+        T::deserialize(&self.output)
+    }
+
+    /// Looks up a string in the output by JSON pointer, e.g. `"/content"`.
+    pub fn output_str(&self, pointer: &str) -> Option<&str> {
+        self.output.pointer(pointer).and_then(Value::as_str)
+    }
 }
 
 #[async_trait]
@@ -355,3 +712,174 @@ pub trait Agent: Send + Sync + Debug {
         Ok(())
     }
 }
+
+#[async_trait]
+impl<T: Agent + ?Sized> Agent for Arc<T> {
+    async fn plan(&self, ctx: &AgentContext) -> Result<Plan, AgentError> {
+        (**self).plan(ctx).await
+    }
+
+    async fn execute_step(
+        &self,
+        step: &Step,
+        ctx: &mut AgentContext,
+    ) -> Result<StepOutcome, AgentError> {
+        (**self).execute_step(step, ctx).await
+    }
+
+    async fn initialize(&self, ctx: &mut AgentContext) -> Result<(), AgentError> {
+        (**self).initialize(ctx).await
+    }
+
+    async fn think(&self, ctx: &AgentContext) -> Result<Plan, AgentError> {
+        (**self).think(ctx).await
+    }
+
+    async fn act(&self, step: &Step, ctx: &mut AgentContext) -> Result<StepOutcome, AgentError> {
+        (**self).act(step, ctx).await
+    }
+
+    async fn observe(
+        &self,
+        outcome: &StepOutcome,
+        ctx: &mut AgentContext,
+    ) -> Result<(), AgentError> {
+        (**self).observe(outcome, ctx).await
+    }
+
+    async fn reflect(&self, ctx: &mut AgentContext) -> Result<(), AgentError> {
+        (**self).reflect(ctx).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn step(id: &str) -> Step {
+        Step {
+            id: id.into(),
+            description: "noop".into(),
+            tool: None,
+            args: json!({}),
+            subtasks: vec![],
+            policies: StepPolicies::default(),
+            chain_of_thought: None,
+            sub_plan: None,
+            depends_on: Vec::new(),
+            condition: None,
+            for_each: None,
+            delegate: None,
+        }
+    }
+
+    fn plan_with_duplicates() -> Plan {
+        Plan {
+            goal: "dup".into(),
+            steps: vec![step("step"), step("step"), step("step-1")],
+            metadata: json!({}),
+        }
+    }
+
+    #[test]
+    fn tool_permissions_accept_namespace_wildcards() {
+        let permissions = ToolPermissions {
+            allowed: vec!["fs/*".into(), "math".into()],
+            denied: vec!["fs/write".into()],
+        };
+        assert!(permissions.permits("fs/read"));
+        assert!(permissions.permits("fs/dir/list"));
+        assert!(permissions.permits("math"));
+        assert!(!permissions.permits("fs/write"));
+        assert!(!permissions.permits("fsx/read"));
+        assert!(!permissions.permits("web/search"));
+
+        let everything = ToolPermissions {
+            allowed: vec!["*".into()],
+            denied: vec!["web/*".into()],
+        };
+        assert!(everything.permits("time"));
+        assert!(!everything.permits("web/search"));
+    }
+
+    #[test]
+    fn dependency_layers_order_steps_and_reject_cycles() {
+        let mut plan = Plan {
+            goal: "dag".into(),
+            steps: vec![
+                step("report")
+                    .with_dependency("fetch")
+                    .with_dependency("parse"),
+                step("fetch"),
+                step("parse").with_dependency("fetch"),
+                step("notify"),
+            ],
+            metadata: json!({}),
+        };
+        assert!(plan.has_dependencies());
+        assert_eq!(
+            plan.dependency_layers().unwrap(),
+            vec![vec!["fetch", "notify"], vec!["parse"], vec!["report"]]
+        );
+
+        plan.steps[1].depends_on.push("report".into());
+        let err = plan.dependency_layers().unwrap_err().to_string();
+        assert!(err.contains("dependency cycle among steps: report, fetch, parse"));
+        plan.steps[1].depends_on = vec!["missing".into()];
+        assert!(plan.dependency_layers().is_err());
+    }
+
+    #[test]
+    fn lint_flags_tool_less_and_empty_steps() {
+        let plan = Plan {
+            goal: "lint".into(),
+            steps: vec![
+                step("respond"),
+                step("search").with_tool("search", json!({"query": "rust"})),
+            ],
+            metadata: json!({}),
+        };
+        let warnings = plan.lint();
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings.iter().all(|w| w.step_id == "respond"));
+        assert_eq!(warnings[0].kind, LintKind::MissingTool);
+        assert_eq!(warnings[1].kind, LintKind::EmptyArgs);
+    }
+
+    #[test]
+    fn outcome_output_accessors() {
+        #[derive(Deserialize)]
+        struct Read {
+            path: String,
+            content: String,
+        }
+
+        let outcome = StepOutcome::success(
+            "read".into(),
+            json!({"path": "notes.txt", "content": "hi", "meta": {"lines": ["one"]}}),
+        );
+        let read: Read = outcome.output_as().unwrap();
+        assert_eq!(read.path, "notes.txt");
+        assert_eq!(read.content, "hi");
+        assert_eq!(outcome.output_str("/content"), Some("hi"));
+        assert_eq!(outcome.output_str("/meta/lines/0"), Some("one"));
+        assert_eq!(outcome.output_str("/missing"), None);
+        assert!(outcome.output_as::<Vec<String>>().is_err());
+    }
+
+    #[test]
+    fn duplicate_step_ids_are_rejected() {
+        let result = plan_with_duplicates().try_executable(DedupStrategy::Error);
+        assert!(matches!(result, Err(AgentError::Validation(_))));
+    }
+
+    #[test]
+    fn duplicate_step_ids_are_suffixed() {
+        let executable = plan_with_duplicates()
+            .try_executable(DedupStrategy::SuffixIndex)
+            .expect("plan deduplicated");
+        let ids: Vec<String> = executable.plan.steps.into_iter().map(|s| s.id).collect();
+        assert_eq!(ids, vec!["step", "step-2", "step-1"]);
+    }
+}
@@ -1,7 +1,13 @@
+use std::sync::Arc;
+
 use async_trait::async_trait;
 use serde_json::{json, Value};
 use thiserror::Error;
 
+mod streaming;
+
+pub use streaming::{GuardedTokenStream, StreamingGuardrail};
+
 /// Standardized result shape shared by all evaluators.
 #[derive(Debug, Clone, PartialEq)]
 pub struct EvaluationResult {
@@ -89,6 +95,8 @@ pub enum EvalError {
     Failed(String),
     #[error("invalid input: {0}")]
     InvalidInput(String),
+    #[error("guardrail violation: {0}")]
+    Violation(String),
 }
 
 #[async_trait]
@@ -244,29 +252,71 @@ impl StepEvaluator for ToolCallCorrectnessEvaluator {
 }
 
 /// Ensures hidden chain-of-thought is not leaked into the final answer.
+///
+/// Accepts the answer as a string, or a serialized
+/// [`LLMResponse`](agent_models::LLMResponse) whose separate `reasoning` is
+/// then policed too: the answer fails if it repeats any sentence or clause
+/// of it.
+/// Either way, answers with chain-of-thought markers fail.
 pub struct ChainOfThoughtGuardrail;
 
-#[async_trait]
-impl GuardrailEvaluator for ChainOfThoughtGuardrail {
-    async fn validate(&self, candidate: &Value) -> Result<EvaluationResult, EvalError> {
-        let text = candidate
-            .as_str()
-            .ok_or_else(|| EvalError::InvalidInput("candidate must be a string".into()))?;
+impl ChainOfThoughtGuardrail {
+    /// Shortest reasoning clause, in words, that counts as leaked when
+    /// repeated; shorter ones match answers by coincidence.
+    pub const MIN_LEAKED_WORDS: usize = 5;
 
-        let lowered = text.to_lowercase();
+    pub fn check(content: &str, reasoning: Option<&str>) -> EvaluationResult {
+        let lowered = content.to_lowercase();
         if lowered.contains("chain-of-thought") || lowered.contains("reasoning:") {
-            Ok(EvaluationResult::fail(
+            return EvaluationResult::fail(
                 "chain-of-thought markers should be hidden from the user",
-            ))
-        } else {
-            Ok(EvaluationResult::pass(
-                1.0,
-                "no chain-of-thought markers exposed to the user",
-            ))
+            );
+        }
+        let answer = normalize(content);
+        let leaked = reasoning
+            .into_iter()
+            .flat_map(|reasoning| reasoning.split(['.', '!', '?', ',', ';', '\n']))
+            .map(normalize)
+            .filter(|clause| clause.split(' ').count() >= Self::MIN_LEAKED_WORDS)
+            .find(|clause| answer.contains(clause.as_str()));
+        match leaked {
+            Some(clause) => EvaluationResult::fail("answer repeats the model's hidden reasoning")
+                .with_details(json!({ "leaked": clause })),
+            None => EvaluationResult::pass(1.0, "no chain-of-thought exposed to the user"),
         }
     }
 }
 
+/// Lowercases `text` and collapses its whitespace, so line wrapping and
+/// capitalisation do not hide a repeated clause.
+fn normalize(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+#[async_trait]
+impl GuardrailEvaluator for ChainOfThoughtGuardrail {
+    async fn validate(&self, candidate: &Value) -> Result<EvaluationResult, EvalError> {
+        let (content, reasoning) = match candidate {
+            Value::String(content) => (content.as_str(), None),
+            Value::Object(response) => (
+                response["content"].as_str().ok_or_else(|| {
+                    EvalError::InvalidInput("response must have string content".into())
+                })?,
+                response.get("reasoning").and_then(Value::as_str),
+            ),
+            _ => {
+                return Err(EvalError::InvalidInput(
+                    "candidate must be a string or a model response".into(),
+                ))
+            }
+        };
+        Ok(Self::check(content, reasoning))
+    }
+}
+
 /// Allows a model or agent to provide a self-scored reflection for the step.
 pub struct SelfAssessmentEvaluator;
 
@@ -328,6 +378,61 @@ impl RewardEvaluator for ScoreRewardEvaluator {
     }
 }
 
+/// Combines named reward components into a single weighted signal.
+#[derive(Default)]
+pub struct CompositeRewardEvaluator {
+    components: Vec<(String, Arc<dyn RewardEvaluator>, f32)>,
+}
+
+impl CompositeRewardEvaluator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_component(
+        mut self,
+        name: impl Into<String>,
+        evaluator: Arc<dyn RewardEvaluator>,
+        weight: f32,
+    ) -> Self {
+        self.components.push((name.into(), evaluator, weight));
+        self
+    }
+}
+
+#[async_trait]
+impl RewardEvaluator for CompositeRewardEvaluator {
+    async fn reward(&self, context: &Value) -> Result<EvaluationResult, EvalError> {
+        if self.components.is_empty() {
+            return Err(EvalError::InvalidInput(
+                "composite reward needs at least one component".into(),
+            ));
+        }
+
+        let mut total = 0.0f32;
+        let mut passed = true;
+        let mut breakdown = Vec::with_capacity(self.components.len());
+        for (name, evaluator, weight) in &self.components {
+            let result = evaluator.reward(context).await?;
+            total += result.score * weight;
+            passed &= result.passed;
+            breakdown.push(json!({
+                "name": name,
+                "weight": weight,
+                "score": result.score,
+                "passed": result.passed,
+            }));
+        }
+
+        Ok(EvaluationResult {
+            passed,
+            score: total.clamp(0.0, 1.0),
+            reason: Some("weighted sum of reward components".into()),
+            details: json!({ "components": breakdown }),
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -380,6 +485,37 @@ mod tests {
         assert!(!result.passed);
     }
 
+    #[tokio::test]
+    async fn chain_of_thought_guardrail_polices_the_reasoning_field() {
+        let response = agent_models::LLMResponse {
+            content: "Ship it Friday. The migration only touches two tables.".into(),
+            reasoning: Some(
+                "The user wants a date.\nThe migration only touches two tables, so risk is low."
+                    .into(),
+            ),
+            ..agent_models::LLMResponse::default()
+        };
+        let result = ChainOfThoughtGuardrail
+            .validate(&serde_json::to_value(&response).unwrap())
+            .await
+            .unwrap();
+        assert!(!result.passed);
+        assert_eq!(
+            result.details["leaked"],
+            "the migration only touches two tables"
+        );
+
+        let answer_only = agent_models::LLMResponse {
+            content: "Ship it Friday.".into(),
+            ..response
+        };
+        let result = ChainOfThoughtGuardrail
+            .validate(&serde_json::to_value(&answer_only).unwrap())
+            .await
+            .unwrap();
+        assert!(result.passed);
+    }
+
     #[tokio::test]
     async fn reward_evaluator_uses_score() {
         let evaluator = ScoreRewardEvaluator;
@@ -391,4 +527,27 @@ mod tests {
         assert!(result.passed);
         assert_eq!(result.score, 0.8);
     }
+
+    struct FixedReward(f32);
+
+    #[async_trait]
+    impl RewardEvaluator for FixedReward {
+        async fn reward(&self, _context: &Value) -> Result<EvaluationResult, EvalError> {
+            Ok(EvaluationResult::pass(self.0, "fixed"))
+        }
+    }
+
+    #[tokio::test]
+    async fn composite_reward_weights_components() {
+        let evaluator = CompositeRewardEvaluator::new()
+            .with_component("accuracy", Arc::new(FixedReward(1.0)), 0.5)
+            .with_component("brevity", Arc::new(FixedReward(0.4)), 0.5);
+        let result = evaluator.reward(&json!({})).await.unwrap();
+
+        assert!((result.score - 0.7).abs() < 1e-6);
+        assert_eq!(result.details["components"][1]["name"], "brevity");
+
+        let empty = CompositeRewardEvaluator::new().reward(&json!({})).await;
+        assert!(matches!(empty, Err(EvalError::InvalidInput(_))));
+    }
 }
@@ -0,0 +1,159 @@
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use agent_models::{Token, TokenStream};
+use futures::stream::{self, Stream, StreamExt};
+use serde_json::Value;
+
+use crate::{EvalError, GuardrailEvaluator};
+
+pub type GuardedTokenStream = Pin<Box<dyn Stream<Item = Result<Token, EvalError>> + Send>>;
+
+/// Runs a guardrail over a token stream while it is being generated.
+///
+/// Tokens are held back until the prefix containing them has been checked, so a
+/// violation terminates the stream before any offending token is emitted. The
+/// prefix is checked every `check_every` tokens and once more when the
+/// underlying stream ends.
+pub struct StreamingGuardrail {
+    guardrail: Arc<dyn GuardrailEvaluator>,
+    check_every: usize,
+}
+
+impl StreamingGuardrail {
+    pub fn new<G: GuardrailEvaluator + 'static>(guardrail: G, check_every: usize) -> Self {
+        Self {
+            guardrail: Arc::new(guardrail),
+            check_every: check_every.max(1),
+        }
+    }
+
+    pub fn guard(&self, tokens: TokenStream) -> GuardedTokenStream {
+        let state = GuardState {
+            tokens,
+            guardrail: self.guardrail.clone(),
+            check_every: self.check_every,
+            prefix: String::new(),
+            pending: Vec::new(),
+            ready: VecDeque::new(),
+            finished: false,
+        };
+
+        Box::pin(stream::unfold(state, |mut state| async move {
+            let item = state.next_item().await?;
+            Some((item, state))
+        }))
+    }
+}
+
+struct GuardState {
+    tokens: TokenStream,
+    guardrail: Arc<dyn GuardrailEvaluator>,
+    check_every: usize,
+    prefix: String,
+    pending: Vec<Token>,
+    ready: VecDeque<Token>,
+    finished: bool,
+}
+
+impl GuardState {
+    async fn next_item(&mut self) -> Option<Result<Token, EvalError>> {
+        loop {
+            if let Some(token) = self.ready.pop_front() {
+                return Some(Ok(token));
+            }
+            if self.finished {
+                return None;
+            }
+
+            match self.tokens.next().await {
+                Some(token) => {
+                    // Tokens are raw deltas, spacing included, so join them as-is.
+                    self.prefix.push_str(&token);
+                    self.pending.push(token);
+                    if self.pending.len() >= self.check_every {
+                        if let Err(err) = self.check().await {
+                            return Some(Err(err));
+                        }
+                    }
+                }
+                None => {
+                    self.finished = true;
+                    if !self.pending.is_empty() {
+                        if let Err(err) = self.check().await {
+                            return Some(Err(err));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    async fn check(&mut self) -> Result<(), EvalError> {
+        let verdict = self
+            .guardrail
+            .validate(&Value::String(self.prefix.clone()))
+            .await;
+        let result = match verdict {
+            Ok(result) => result,
+            Err(err) => {
+                self.finished = true;
+                return Err(err);
+            }
+        };
+
+        if result.passed {
+            self.ready.extend(self.pending.drain(..));
+            Ok(())
+        } else {
+            self.finished = true;
+            self.pending.clear();
+            Err(EvalError::Violation(result.reason.unwrap_or_else(|| {
+                "guardrail rejected streamed output".into()
+            })))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ToxicityEvaluator;
+
+    fn tokens(deltas: &[&str]) -> TokenStream {
+        let tokens: Vec<Token> = deltas.iter().map(|delta| delta.to_string()).collect();
+        Box::pin(tokio_stream::iter(tokens))
+    }
+
+    #[tokio::test]
+    async fn streaming_guardrail_cuts_off_toxic_output() {
+        let guardrail = StreamingGuardrail::new(ToxicityEvaluator::default(), 2);
+        let items: Vec<_> = guardrail
+            .guard(tokens(&[
+                "hel", "lo", " there", " we", " should", " vio", "lence", " and", " more",
+            ]))
+            .collect()
+            .await;
+
+        let emitted: String = items
+            .iter()
+            .filter_map(|item| item.as_ref().ok().map(String::as_str))
+            .collect();
+        // "vio" alone passes; the term is caught once "lence" completes it.
+        assert_eq!(emitted, "hello there we should vio");
+        assert!(matches!(items.last(), Some(Err(EvalError::Violation(_)))));
+    }
+
+    #[tokio::test]
+    async fn streaming_guardrail_passes_clean_output() {
+        let guardrail = StreamingGuardrail::new(ToxicityEvaluator::default(), 3);
+        let items: Vec<_> = guardrail
+            .guard(tokens(&["a", " calm", " reply"]))
+            .collect()
+            .await;
+
+        assert_eq!(items.len(), 3);
+        assert!(items.iter().all(Result::is_ok));
+    }
+}
@@ -0,0 +1,244 @@
+//! [`EncryptedStore`], enabled by the `encryption` cargo feature.
+
+use std::sync::Arc;
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde_json::{json, Value};
+
+use crate::{MemoryError, MemoryStore};
+
+/// Algorithm tag written into every sealed value.
+const ALGORITHM: &str = "aes-256-gcm";
+
+/// A 256-bit AES key. Its `Debug` output never shows the bytes.
+#[derive(Clone)]
+pub struct EncryptionKey([u8; 32]);
+
+impl EncryptionKey {
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    /// Reads a key given as 32 bytes of standard base64 or 64 hex digits.
+    pub fn parse(encoded: &str) -> Result<Self, MemoryError> {
+        let encoded = encoded.trim();
+        let bytes = if encoded.len() == 64 && encoded.bytes().all(|b| b.is_ascii_hexdigit()) {
+            (0..64)
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&encoded[i..i + 2], 16))
+                .collect::<Result<Vec<u8>, _>>()
+                .map_err(|e| MemoryError::Backend(e.to_string()))?
+        } else {
+            BASE64
+                .decode(encoded)
+                .map_err(|e| MemoryError::Backend(format!("invalid encryption key: {e}")))?
+        };
+        let bytes: [u8; 32] = bytes.try_into().map_err(|bytes: Vec<u8>| {
+            MemoryError::Backend(format!(
+                "encryption key must be 32 bytes, got {}",
+                bytes.len()
+            ))
+        })?;
+        Ok(Self(bytes))
+    }
+
+    /// A fresh random key.
+    pub fn generate() -> Self {
+        Self(Aes256Gcm::generate_key(&mut OsRng).into())
+    }
+
+    /// The key as standard base64, the form [`parse`](Self::parse) reads.
+    pub fn to_base64(&self) -> String {
+        BASE64.encode(self.0)
+    }
+}
+
+impl std::fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("EncryptionKey(***)")
+    }
+}
+
+/// Encrypts values with AES-256-GCM before they reach an inner store.
+///
+/// Each value is sealed under a fresh nonce into `{"enc", "nonce",
+/// "ciphertext"}`, with its key name as associated data so a sealed value
+/// copied to another key fails to open. Key names stay in plaintext, so
+/// [`search`](MemoryStore::search) only matches keys.
+pub struct EncryptedStore {
+    inner: Arc<dyn MemoryStore>,
+    cipher: Aes256Gcm,
+}
+
+impl EncryptedStore {
+    pub fn new(inner: Arc<dyn MemoryStore>, key: &EncryptionKey) -> Self {
+        Self {
+            inner,
+            cipher: Aes256Gcm::new(&key.0.into()),
+        }
+    }
+
+    pub fn inner(&self) -> &Arc<dyn MemoryStore> {
+        &self.inner
+    }
+
+    fn seal(&self, key: &str, value: &Value) -> Result<Value, MemoryError> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let plaintext =
+            serde_json::to_vec(value).map_err(|e| MemoryError::Backend(e.to_string()))?;
+        let ciphertext = self
+            .cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: &plaintext,
+                    aad: key.as_bytes(),
+                },
+            )
+            .map_err(|_| MemoryError::Backend(format!("failed to encrypt {key}")))?;
+        Ok(json!({
+            "enc": ALGORITHM,
+            "nonce": BASE64.encode(nonce),
+            "ciphertext": BASE64.encode(ciphertext),
+        }))
+    }
+
+    fn open(&self, key: &str, sealed: &Value) -> Result<Value, MemoryError> {
+        let field = |name: &str| {
+            sealed[name]
+                .as_str()
+                .and_then(|encoded| BASE64.decode(encoded).ok())
+                .ok_or_else(|| MemoryError::Backend(format!("{key} is not an encrypted value")))
+        };
+        if sealed["enc"] != ALGORITHM {
+            return Err(MemoryError::Backend(format!(
+                "{key} is not an encrypted value"
+            )));
+        }
+        let nonce = field("nonce")?;
+        if nonce.len() != 12 {
+            return Err(MemoryError::Backend(format!("{key} has a malformed nonce")));
+        }
+        let plaintext = self
+            .cipher
+            .decrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: &field("ciphertext")?,
+                    aad: key.as_bytes(),
+                },
+            )
+            .map_err(|_| MemoryError::Backend(format!("failed to decrypt {key}")))?;
+        serde_json::from_slice(&plaintext).map_err(|e| MemoryError::Backend(e.to_string()))
+    }
+}
+
+impl std::fmt::Debug for EncryptedStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EncryptedStore")
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
+impl MemoryStore for EncryptedStore {
+    fn put(&self, key: &str, value: &Value) -> Result<(), MemoryError> {
+        self.inner.put(key, &self.seal(key, value)?)
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Value>, MemoryError> {
+        self.inner
+            .get(key)?
+            .map(|sealed| self.open(key, &sealed))
+            .transpose()
+    }
+
+    /// Values whose key contains `query`; values themselves are opaque to
+    /// the inner store.
+    fn search(&self, query: &str) -> Result<Vec<Value>, MemoryError> {
+        self.inner
+            .entries()?
+            .into_iter()
+            .filter(|(key, _)| key.contains(query))
+            .map(|(key, sealed)| self.open(&key, &sealed))
+            .collect()
+    }
+
+    fn entries(&self) -> Result<Vec<(String, Value)>, MemoryError> {
+        self.inner
+            .entries()?
+            .into_iter()
+            .map(|(key, sealed)| {
+                let value = self.open(&key, &sealed)?;
+                Ok((key, value))
+            })
+            .collect()
+    }
+
+    fn delete(&self, key: &str) -> Result<bool, MemoryError> {
+        self.inner.delete(key)
+    }
+
+    fn list_keys(&self, prefix: &str) -> Result<Vec<String>, MemoryError> {
+        self.inner.list_keys(prefix)
+    }
+
+    fn clear(&self) -> Result<(), MemoryError> {
+        self.inner.clear()
+    }
+
+    fn get_versioned(&self, key: &str) -> Result<Option<(Value, u64)>, MemoryError> {
+        self.inner
+            .get_versioned(key)?
+            .map(|(sealed, version)| Ok((self.open(key, &sealed)?, version)))
+            .transpose()
+    }
+
+    fn put_if_version(
+        &self,
+        key: &str,
+        value: &Value,
+        expected_version: u64,
+    ) -> Result<u64, MemoryError> {
+        self.inner
+            .put_if_version(key, &self.seal(key, value)?, expected_version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::InMemoryStore;
+
+    #[test]
+    fn values_are_sealed_at_rest_and_bound_to_their_key() {
+        let inner = Arc::new(InMemoryStore::new());
+        let key = EncryptionKey::generate();
+        let store = EncryptedStore::new(inner.clone(), &key);
+
+        let secret = json!({"text": "the vault code is 4921"});
+        store.put("vault", &secret).unwrap();
+        let at_rest = inner.get("vault").unwrap().unwrap();
+        assert_eq!(at_rest["enc"], ALGORITHM);
+        assert!(!at_rest.to_string().contains("4921"));
+        assert_eq!(store.get("vault").unwrap(), Some(secret.clone()));
+        assert_eq!(store.search("vau").unwrap(), vec![secret.clone()]);
+        assert!(store.search("4921").unwrap().is_empty());
+
+        inner.put("copy", &at_rest).unwrap();
+        assert!(store.get("copy").is_err());
+
+        let reopened = EncryptedStore::new(
+            inner.clone(),
+            &EncryptionKey::parse(&key.to_base64()).unwrap(),
+        );
+        assert_eq!(reopened.get("vault").unwrap(), Some(secret));
+        let stranger = EncryptedStore::new(inner, &EncryptionKey::generate());
+        assert!(stranger.get("vault").is_err());
+        assert!(EncryptionKey::parse("c2hvcnQ=").is_err());
+        assert_eq!(format!("{key:?}"), "EncryptionKey(***)");
+    }
+}
@@ -0,0 +1,448 @@
+//! An in-memory HNSW (hierarchical navigable small world) index for
+//! approximate nearest-neighbour search, used by
+//! [`VectorBackend::LocalHnsw`](crate::VectorBackend::LocalHnsw).
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::MemoryError;
+
+/// How two vectors are compared; higher scores are more similar.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Similarity {
+    /// Cosine similarity; vectors are normalized when inserted.
+    #[default]
+    Cosine,
+    /// Raw dot product, for embeddings that are already normalized or whose
+    /// magnitude carries meaning.
+    Dot,
+}
+
+/// Tuning knobs for an [`HnswIndex`].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct HnswConfig {
+    pub similarity: Similarity,
+    /// Links kept per node on the upper layers; twice as many on the bottom
+    /// one.
+    pub m: usize,
+    /// Candidates considered while linking a new node. Higher builds a
+    /// better graph, more slowly.
+    pub ef_construction: usize,
+    /// Candidates considered per query. Higher trades speed for recall.
+    pub ef_search: usize,
+    /// Seeds the layer assignment, so the same inserts build the same graph.
+    pub seed: u64,
+}
+
+impl Default for HnswConfig {
+    fn default() -> Self {
+        Self {
+            similarity: Similarity::Cosine,
+            m: 16,
+            ef_construction: 100,
+            ef_search: 50,
+            seed: 0x5eed,
+        }
+    }
+}
+
+impl HnswConfig {
+    pub fn with_similarity(mut self, similarity: Similarity) -> Self {
+        self.similarity = similarity;
+        self
+    }
+
+    pub fn with_ef_search(mut self, ef_search: usize) -> Self {
+        self.ef_search = ef_search;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Node {
+    key: String,
+    vector: Vec<f32>,
+    /// Neighbour ids per layer, from layer 0 up to the node's level.
+    links: Vec<Vec<usize>>,
+    /// Replaced or removed; still routes searches but is never returned.
+    deleted: bool,
+}
+
+/// Approximate nearest-neighbour index over keyed vectors.
+///
+/// Replacing or removing a key leaves a tombstone in the graph, so heavy
+/// churn slowly grows the index; rebuild it from live entries if that
+/// matters.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HnswIndex {
+    config: HnswConfig,
+    dimensions: Option<usize>,
+    nodes: Vec<Node>,
+    /// Live node of each key.
+    keys: HashMap<String, usize>,
+    entry_point: Option<usize>,
+    rng: u64,
+}
+
+impl Default for HnswIndex {
+    fn default() -> Self {
+        Self::new(HnswConfig::default())
+    }
+}
+
+impl HnswIndex {
+    pub fn new(config: HnswConfig) -> Self {
+        Self {
+            config,
+            dimensions: None,
+            nodes: Vec::new(),
+            keys: HashMap::new(),
+            entry_point: None,
+            rng: config.seed | 1,
+        }
+    }
+
+    pub fn config(&self) -> &HnswConfig {
+        &self.config
+    }
+
+    pub fn set_ef_search(&mut self, ef_search: usize) {
+        self.config.ef_search = ef_search;
+    }
+
+    /// Number of live keys.
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.keys.contains_key(key)
+    }
+
+    /// The vector indexed under `key`.
+    pub fn vector(&self, key: &str) -> Option<&[f32]> {
+        self.keys
+            .get(key)
+            .map(|id| self.nodes[*id].vector.as_slice())
+    }
+
+    /// Indexes `vector` under `key`, replacing any earlier vector for it.
+    /// Every vector must have as many dimensions as the first one.
+    pub fn insert(&mut self, key: &str, vector: &[f32]) -> Result<(), MemoryError> {
+        let dimensions = self.dimensions.unwrap_or(vector.len());
+        if vector.is_empty() || vector.len() != dimensions {
+            return Err(MemoryError::Backend(format!(
+                "expected a {dimensions}-dimensional vector, got {}",
+                vector.len()
+            )));
+        }
+        self.dimensions = Some(dimensions);
+        self.remove(key);
+
+        let vector = match self.config.similarity {
+            Similarity::Cosine => normalized(vector),
+            Similarity::Dot => vector.to_vec(),
+        };
+        let level = self.random_level();
+        let id = self.nodes.len();
+        self.nodes.push(Node {
+            key: key.to_string(),
+            vector,
+            links: vec![Vec::new(); level + 1],
+            deleted: false,
+        });
+        self.keys.insert(key.to_string(), id);
+
+        let Some(mut entry) = self.entry_point else {
+            self.entry_point = Some(id);
+            return Ok(());
+        };
+        let query = self.nodes[id].vector.clone();
+        let top = self.level_of(entry);
+        for layer in (level + 1..=top).rev() {
+            entry = self.greedy_closest(&query, entry, layer);
+        }
+        let mut entries = vec![entry];
+        for layer in (0..=level.min(top)).rev() {
+            let candidates =
+                self.search_layer(&query, &entries, self.config.ef_construction, layer);
+            let neighbours: Vec<usize> = candidates
+                .iter()
+                .take(self.max_links(layer))
+                .map(|&(_, node)| node)
+                .collect();
+            for &neighbour in &neighbours {
+                self.nodes[neighbour].links[layer].push(id);
+                self.prune(neighbour, layer);
+            }
+            self.nodes[id].links[layer] = neighbours;
+            entries = candidates.into_iter().map(|(_, node)| node).collect();
+        }
+        if level > top {
+            self.entry_point = Some(id);
+        }
+        Ok(())
+    }
+
+    /// Drops `key` from results; returns whether it was indexed.
+    pub fn remove(&mut self, key: &str) -> bool {
+        match self.keys.remove(key) {
+            Some(id) => {
+                self.nodes[id].deleted = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The `top_k` keys most similar to `query`, best first, using the
+    /// configured `ef_search`.
+    pub fn search(&self, query: &[f32], top_k: usize) -> Vec<(String, f32)> {
+        self.search_with_ef(query, top_k, self.config.ef_search)
+    }
+
+    /// Like [`search`](Self::search) with `ef` candidates per query instead
+    /// of the configured number.
+    pub fn search_with_ef(&self, query: &[f32], top_k: usize, ef: usize) -> Vec<(String, f32)> {
+        let Some(mut entry) = self.entry_point else {
+            return Vec::new();
+        };
+        if top_k == 0 || Some(query.len()) != self.dimensions {
+            return Vec::new();
+        }
+        let query = match self.config.similarity {
+            Similarity::Cosine => normalized(query),
+            Similarity::Dot => query.to_vec(),
+        };
+        for layer in (1..=self.level_of(entry)).rev() {
+            entry = self.greedy_closest(&query, entry, layer);
+        }
+        // Tombstones still take candidate slots, so widen the search by them.
+        let tombstones = self.nodes.len() - self.keys.len();
+        let ef = ef.max(top_k) + tombstones.min(ef.max(top_k));
+        self.search_layer(&query, &[entry], ef, 0)
+            .into_iter()
+            .filter(|&(_, node)| !self.nodes[node].deleted)
+            .take(top_k)
+            .map(|(score, node)| (self.nodes[node].key.clone(), score.0))
+            .collect()
+    }
+
+    /// Writes the index to `path` as JSON.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), MemoryError> {
+        let json = serde_json::to_vec(self).map_err(|e| MemoryError::Backend(e.to_string()))?;
+        std::fs::write(path, json).map_err(|e| MemoryError::Backend(e.to_string()))
+    }
+
+    /// Reads an index written by [`save`](Self::save).
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, MemoryError> {
+        let json = std::fs::read(path).map_err(|e| MemoryError::Backend(e.to_string()))?;
+        serde_json::from_slice(&json).map_err(|e| MemoryError::Backend(e.to_string()))
+    }
+
+    fn level_of(&self, node: usize) -> usize {
+        self.nodes[node].links.len() - 1
+    }
+
+    fn max_links(&self, layer: usize) -> usize {
+        let m = self.config.m.max(2);
+        if layer == 0 {
+            m * 2
+        } else {
+            m
+        }
+    }
+
+    fn score(&self, query: &[f32], node: usize) -> Score {
+        Score(
+            query
+                .iter()
+                .zip(&self.nodes[node].vector)
+                .map(|(a, b)| a * b)
+                .sum(),
+        )
+    }
+
+    /// Draws a level with probability decaying by `1 / m` per layer.
+    fn random_level(&mut self) -> usize {
+        // xorshift64*, kept in the index so reloaded indexes stay seeded.
+        self.rng ^= self.rng >> 12;
+        self.rng ^= self.rng << 25;
+        self.rng ^= self.rng >> 27;
+        let bits = self.rng.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 11;
+        let uniform = (bits as f64 + 1.0) / (1u64 << 53) as f64;
+        let scale = 1.0 / (self.config.m.max(2) as f64).ln();
+        ((-uniform.ln() * scale) as usize).min(16)
+    }
+
+    fn greedy_closest(&self, query: &[f32], mut current: usize, layer: usize) -> usize {
+        let mut best = self.score(query, current);
+        loop {
+            let mut improved = false;
+            for &neighbour in &self.nodes[current].links[layer] {
+                let score = self.score(query, neighbour);
+                if score > best {
+                    best = score;
+                    current = neighbour;
+                    improved = true;
+                }
+            }
+            if !improved {
+                return current;
+            }
+        }
+    }
+
+    /// Best-first search of one layer, returning up to `ef` nodes best
+    /// first.
+    fn search_layer(
+        &self,
+        query: &[f32],
+        entries: &[usize],
+        ef: usize,
+        layer: usize,
+    ) -> Vec<(Score, usize)> {
+        let ef = ef.max(1);
+        let mut visited: HashSet<usize> = entries.iter().copied().collect();
+        let mut candidates = BinaryHeap::new();
+        let mut found = BinaryHeap::new();
+        for &entry in entries {
+            let score = self.score(query, entry);
+            candidates.push((score, entry));
+            found.push(Reverse((score, entry)));
+        }
+        while found.len() > ef {
+            found.pop();
+        }
+        while let Some((score, node)) = candidates.pop() {
+            let worst = found.peek().map(|Reverse((worst, _))| *worst);
+            if found.len() >= ef && worst.is_some_and(|worst| score < worst) {
+                break;
+            }
+            for &neighbour in &self.nodes[node].links[layer] {
+                if !visited.insert(neighbour) {
+                    continue;
+                }
+                let score = self.score(query, neighbour);
+                let worst = found.peek().map(|Reverse((worst, _))| *worst);
+                if found.len() < ef || worst.is_some_and(|worst| score > worst) {
+                    candidates.push((score, neighbour));
+                    found.push(Reverse((score, neighbour)));
+                    if found.len() > ef {
+                        found.pop();
+                    }
+                }
+            }
+        }
+        let mut found: Vec<(Score, usize)> = found.into_iter().map(|Reverse(pair)| pair).collect();
+        found.sort_by(|a, b| b.cmp(a));
+        found
+    }
+
+    /// Keeps `node`'s closest links on `layer` once it has too many.
+    fn prune(&mut self, node: usize, layer: usize) {
+        let max = self.max_links(layer);
+        if self.nodes[node].links[layer].len() <= max {
+            return;
+        }
+        let vector = self.nodes[node].vector.clone();
+        let mut links: Vec<(Score, usize)> = self.nodes[node].links[layer]
+            .iter()
+            .map(|&neighbour| (self.score(&vector, neighbour), neighbour))
+            .collect();
+        links.sort_by(|a, b| b.cmp(a));
+        self.nodes[node].links[layer] = links
+            .into_iter()
+            .take(max)
+            .map(|(_, neighbour)| neighbour)
+            .collect();
+    }
+}
+
+/// A similarity score ordered with [`f32::total_cmp`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Score(f32);
+
+impl Eq for Score {}
+
+impl PartialOrd for Score {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Score {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+fn normalized(vector: &[f32]) -> Vec<f32> {
+    let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm == 0.0 {
+        return vector.to_vec();
+    }
+    vector.iter().map(|x| x / norm).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Points spread around the unit circle, each keyed by its angle.
+    fn circle(points: usize) -> Vec<(String, Vec<f32>)> {
+        (0..points)
+            .map(|i| {
+                let angle = i as f32 / points as f32 * std::f32::consts::TAU;
+                (format!("p{i}"), vec![angle.cos(), angle.sin(), 0.1])
+            })
+            .collect()
+    }
+
+    #[test]
+    fn finds_nearest_neighbours_and_survives_a_round_trip() {
+        let mut index = HnswIndex::new(HnswConfig {
+            m: 4,
+            ef_construction: 32,
+            ..HnswConfig::default()
+        });
+        for (key, vector) in circle(500) {
+            index.insert(&key, &vector).unwrap();
+        }
+        assert_eq!(index.len(), 500);
+
+        let hits = index.search(&[1.0, 0.0, 0.1], 3);
+        let keys: Vec<&str> = hits.iter().map(|(key, _)| key.as_str()).collect();
+        assert_eq!(keys[0], "p0");
+        assert!(keys[1..].iter().all(|key| ["p1", "p499"].contains(key)));
+        assert!(hits[0].1 > hits[1].1 - 1e-6);
+
+        index.remove("p0");
+        index.insert("p1", &[-1.0, 0.0, 0.1]).unwrap();
+        let keys: Vec<String> = index
+            .search_with_ef(&[1.0, 0.0, 0.1], 2, 8)
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect();
+        assert_eq!(keys[0], "p499");
+        assert!(keys[1] == "p2" || keys[1] == "p498");
+        assert!(index.insert("bad", &[1.0]).is_err());
+
+        let path = std::env::temp_dir().join(format!("hnsw-{}.json", std::process::id()));
+        index.save(&path).unwrap();
+        let reloaded = HnswIndex::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            reloaded.search(&[-1.0, 0.0, 0.1], 1)[0].0,
+            index.search(&[-1.0, 0.0, 0.1], 1)[0].0
+        );
+    }
+}
@@ -1,8 +1,36 @@
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
-use std::sync::RwLock;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
 use thiserror::Error;
 
+mod hnsw;
+mod migrate;
+mod observed;
+mod quota;
+mod semantic;
+mod tiered;
+
+pub use hnsw::{HnswConfig, HnswIndex, Similarity};
+pub use migrate::{migrate, MigrationOptions, MigrationProgress, MigrationReport, ProgressFn};
+pub use observed::{MemoryEvent, MemoryObserver, MemoryOp, ObservedStore, TelemetryObserver};
+pub use quota::{EvictionPolicy, MemoryQuota};
+pub use semantic::{cosine_similarity, Embedder, MetadataFilter, SemanticMemory};
+pub use tiered::{MemoryTier, TierPolicy, TieredMatch, TieredMemory};
+
+#[cfg(feature = "encryption")]
+mod encrypted;
+#[cfg(feature = "redis")]
+mod redis_store;
+
+#[cfg(feature = "encryption")]
+pub use encrypted::{EncryptedStore, EncryptionKey};
+#[cfg(feature = "redis")]
+pub use redis_store::RedisStore;
+
 #[derive(Debug, Error)]
 pub enum MemoryError {
     #[error("key not found: {0}")]
@@ -11,6 +39,12 @@ pub enum MemoryError {
     Backend(String),
     #[error("unsupported operation: {0}")]
     Unsupported(String),
+    #[error("version conflict on {key}: expected {expected}, found {actual}")]
+    Conflict {
+        key: String,
+        expected: u64,
+        actual: u64,
+    },
 }
 
 /// Primary abstraction for storing and retrieving agent memory.
@@ -22,27 +56,237 @@ pub trait MemoryStore: Send + Sync + std::fmt::Debug {
     fn put(&self, key: &str, value: &Value) -> Result<(), MemoryError>;
     fn get(&self, key: &str) -> Result<Option<Value>, MemoryError>;
     fn search(&self, query: &str) -> Result<Vec<Value>, MemoryError>;
+
+    /// Every stored `(key, value)` pair, for consumers that rank entries
+    /// themselves (e.g. by embedding similarity).
+    fn entries(&self) -> Result<Vec<(String, Value)>, MemoryError> {
+        Err(MemoryError::Unsupported("entries".into()))
+    }
+
+    /// Removes `key`; returns whether it was stored.
+    fn delete(&self, _key: &str) -> Result<bool, MemoryError> {
+        Err(MemoryError::Unsupported("delete".into()))
+    }
+
+    /// Stored keys starting with `prefix`, sorted; all of them for an empty
+    /// prefix.
+    fn list_keys(&self, _prefix: &str) -> Result<Vec<String>, MemoryError> {
+        Err(MemoryError::Unsupported("list_keys".into()))
+    }
+
+    /// Removes everything.
+    fn clear(&self) -> Result<(), MemoryError> {
+        Err(MemoryError::Unsupported("clear".into()))
+    }
+
+    /// Stores `value` along with its embedding. Stores without a vector
+    /// index keep just the value.
+    fn put_embedding(
+        &self,
+        key: &str,
+        value: &Value,
+        _embedding: &[f32],
+    ) -> Result<(), MemoryError> {
+        self.put(key, value)
+    }
+
+    /// The `top_k` entries whose embeddings are most similar to
+    /// `embedding`, best first, for stores with a vector index.
+    fn search_embedding(
+        &self,
+        _embedding: &[f32],
+        _top_k: usize,
+    ) -> Result<Vec<VectorMatch>, MemoryError> {
+        Err(MemoryError::Unsupported("search_embedding".into()))
+    }
+
+    /// The embedding stored for `key` by
+    /// [`put_embedding`](Self::put_embedding); `None` for stores without a
+    /// vector index.
+    fn get_embedding(&self, _key: &str) -> Result<Option<Vec<f32>>, MemoryError> {
+        Ok(None)
+    }
+
+    /// The value of `key` with its version. Versions start at 1 and grow
+    /// with every write; an absent key is version 0.
+    fn get_versioned(&self, _key: &str) -> Result<Option<(Value, u64)>, MemoryError> {
+        Err(MemoryError::Unsupported("get_versioned".into()))
+    }
+
+    /// Writes `value` only if `key` is still at `expected_version` (0 for
+    /// "not stored yet"), returning the new version. Otherwise fails with
+    /// [`MemoryError::Conflict`] and leaves the stored value alone, so
+    /// agents sharing a store can read, modify and retry.
+    fn put_if_version(
+        &self,
+        _key: &str,
+        _value: &Value,
+        _expected_version: u64,
+    ) -> Result<u64, MemoryError> {
+        Err(MemoryError::Unsupported("put_if_version".into()))
+    }
 }
 
-#[derive(Default, Debug)]
+#[derive(Debug)]
+struct Slot {
+    value: Value,
+    version: u64,
+    /// Key plus serialized value, as counted against the quota.
+    size: usize,
+    /// Clock tick of the last read or write.
+    touched: AtomicU64,
+    reads: AtomicU64,
+}
+
+/// A store held in process memory.
+///
+/// Unbounded by default; [`with_quota`](Self::with_quota) caps it, evicting
+/// entries by the quota's [`EvictionPolicy`] after each write and reporting
+/// every eviction to its observers as a [`MemoryOp::Evict`] event.
+#[derive(Default)]
 pub struct InMemoryStore {
-    inner: RwLock<HashMap<String, Value>>,
+    inner: RwLock<HashMap<String, Slot>>,
+    quota: MemoryQuota,
+    bytes: AtomicUsize,
+    clock: AtomicU64,
+    evictions: AtomicU64,
+    observers: Vec<Arc<dyn MemoryObserver>>,
 }
 
 impl InMemoryStore {
     pub fn new() -> Self {
-        Self {
-            inner: RwLock::new(HashMap::new()),
+        Self::default()
+    }
+
+    pub fn with_quota(mut self, quota: MemoryQuota) -> Self {
+        self.quota = quota;
+        self
+    }
+
+    pub fn with_observer(mut self, observer: impl MemoryObserver + 'static) -> Self {
+        self.observers.push(Arc::new(observer));
+        self
+    }
+
+    pub fn quota(&self) -> &MemoryQuota {
+        &self.quota
+    }
+
+    /// Bytes currently counted against the quota.
+    pub fn used_bytes(&self) -> usize {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    /// Entries evicted since the store was created.
+    pub fn evictions(&self) -> u64 {
+        self.evictions.load(Ordering::Relaxed)
+    }
+
+    fn tick(&self) -> u64 {
+        self.clock.fetch_add(1, Ordering::Relaxed)
+    }
+
+    fn touch(&self, slot: &Slot) {
+        slot.touched.store(self.tick(), Ordering::Relaxed);
+        slot.reads.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Writes `key` at `version`, then evicts other entries until the store
+    /// is back within its quota. Returns what was evicted.
+    fn store(
+        &self,
+        inner: &mut HashMap<String, Slot>,
+        key: &str,
+        value: &Value,
+        version: u64,
+    ) -> Result<Vec<MemoryEvent>, MemoryError> {
+        let size = key.len() + value.to_string().len();
+        if let Some(max) = self.quota.max_bytes {
+            if size > max {
+                return Err(MemoryError::Backend(format!(
+                    "{key} needs {size} bytes, over the {max} byte quota"
+                )));
+            }
+        }
+        let reads = inner
+            .get(key)
+            .map_or(0, |slot| slot.reads.load(Ordering::Relaxed));
+        let previous = inner.insert(
+            key.to_string(),
+            Slot {
+                value: value.clone(),
+                version,
+                size,
+                touched: AtomicU64::new(self.tick()),
+                reads: AtomicU64::new(reads),
+            },
+        );
+        if let Some(previous) = previous {
+            self.bytes.fetch_sub(previous.size, Ordering::Relaxed);
+        }
+        self.bytes.fetch_add(size, Ordering::Relaxed);
+
+        let mut evicted = Vec::new();
+        while self.quota.exceeded(inner.len(), self.used_bytes()) {
+            let victim = inner
+                .iter()
+                .filter(|(candidate, _)| candidate.as_str() != key)
+                .min_by_key(|(_, slot)| {
+                    let touched = slot.touched.load(Ordering::Relaxed);
+                    match self.quota.policy {
+                        EvictionPolicy::Lru => (0, touched),
+                        EvictionPolicy::Lfu => (slot.reads.load(Ordering::Relaxed), touched),
+                    }
+                })
+                .map(|(candidate, _)| candidate.clone());
+            let Some(victim) = victim else { break };
+            if let Some(slot) = inner.remove(&victim) {
+                self.bytes.fetch_sub(slot.size, Ordering::Relaxed);
+                self.evictions.fetch_add(1, Ordering::Relaxed);
+                evicted.push(MemoryEvent {
+                    op: MemoryOp::Evict,
+                    key: victim,
+                    bytes: slot.size,
+                    hits: 0,
+                    latency: Duration::ZERO,
+                    error: None,
+                });
+            }
+        }
+        Ok(evicted)
+    }
+
+    fn report(&self, evicted: Vec<MemoryEvent>) {
+        for event in &evicted {
+            for observer in &self.observers {
+                observer.on_event(event);
+            }
         }
     }
 }
 
+impl std::fmt::Debug for InMemoryStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("InMemoryStore")
+            .field("inner", &self.inner)
+            .field("quota", &self.quota)
+            .field("evictions", &self.evictions())
+            .field("observers", &self.observers.len())
+            .finish()
+    }
+}
+
 impl MemoryStore for InMemoryStore {
     fn put(&self, key: &str, value: &Value) -> Result<(), MemoryError> {
-        self.inner
-            .write()
-            .map_err(|e| MemoryError::Backend(e.to_string()))?
-            .insert(key.to_string(), value.clone());
+        let evicted = {
+            let mut inner = self
+                .inner
+                .write()
+                .map_err(|e| MemoryError::Backend(e.to_string()))?;
+            let version = inner.get(key).map_or(0, |slot| slot.version) + 1;
+            self.store(&mut inner, key, value, version)?
+        };
+        self.report(evicted);
         Ok(())
     }
 
@@ -52,7 +296,10 @@ impl MemoryStore for InMemoryStore {
             .read()
             .map_err(|e| MemoryError::Backend(e.to_string()))?
             .get(key)
-            .cloned())
+            .map(|slot| {
+                self.touch(slot);
+                slot.value.clone()
+            }))
     }
 
     fn search(&self, query: &str) -> Result<Vec<Value>, MemoryError> {
@@ -61,11 +308,92 @@ impl MemoryStore for InMemoryStore {
             .read()
             .map_err(|e| MemoryError::Backend(e.to_string()))?
             .iter()
-            .filter(|(k, v)| k.contains(query) || v.to_string().contains(query))
-            .map(|(_, v)| v.clone())
+            .filter(|(k, slot)| k.contains(query) || slot.value.to_string().contains(query))
+            .map(|(_, slot)| slot.value.clone())
             .collect();
         Ok(values)
     }
+
+    fn entries(&self) -> Result<Vec<(String, Value)>, MemoryError> {
+        Ok(self
+            .inner
+            .read()
+            .map_err(|e| MemoryError::Backend(e.to_string()))?
+            .iter()
+            .map(|(k, slot)| (k.clone(), slot.value.clone()))
+            .collect())
+    }
+
+    fn delete(&self, key: &str) -> Result<bool, MemoryError> {
+        let removed = self
+            .inner
+            .write()
+            .map_err(|e| MemoryError::Backend(e.to_string()))?
+            .remove(key);
+        if let Some(slot) = &removed {
+            self.bytes.fetch_sub(slot.size, Ordering::Relaxed);
+        }
+        Ok(removed.is_some())
+    }
+
+    fn list_keys(&self, prefix: &str) -> Result<Vec<String>, MemoryError> {
+        let mut keys: Vec<String> = self
+            .inner
+            .read()
+            .map_err(|e| MemoryError::Backend(e.to_string()))?
+            .keys()
+            .filter(|k| k.starts_with(prefix))
+            .cloned()
+            .collect();
+        keys.sort();
+        Ok(keys)
+    }
+
+    fn clear(&self) -> Result<(), MemoryError> {
+        self.inner
+            .write()
+            .map_err(|e| MemoryError::Backend(e.to_string()))?
+            .clear();
+        self.bytes.store(0, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn get_versioned(&self, key: &str) -> Result<Option<(Value, u64)>, MemoryError> {
+        Ok(self
+            .inner
+            .read()
+            .map_err(|e| MemoryError::Backend(e.to_string()))?
+            .get(key)
+            .map(|slot| {
+                self.touch(slot);
+                (slot.value.clone(), slot.version)
+            }))
+    }
+
+    fn put_if_version(
+        &self,
+        key: &str,
+        value: &Value,
+        expected_version: u64,
+    ) -> Result<u64, MemoryError> {
+        let evicted = {
+            let mut inner = self
+                .inner
+                .write()
+                .map_err(|e| MemoryError::Backend(e.to_string()))?;
+            let actual = inner.get(key).map_or(0, |slot| slot.version);
+            if actual != expected_version {
+                return Err(MemoryError::Conflict {
+                    key: key.to_string(),
+                    expected: expected_version,
+                    actual,
+                });
+            }
+            self.store(&mut inner, key, value, actual + 1)?
+        };
+        self.report(evicted);
+        Ok(expected_version + 1)
+    }
 }
 
 #[derive(Debug)]
@@ -83,6 +411,22 @@ impl MemoryStore for NullStore {
     fn search(&self, _query: &str) -> Result<Vec<Value>, MemoryError> {
         Ok(vec![])
     }
+
+    fn entries(&self) -> Result<Vec<(String, Value)>, MemoryError> {
+        Ok(vec![])
+    }
+
+    fn delete(&self, _key: &str) -> Result<bool, MemoryError> {
+        Ok(false)
+    }
+
+    fn list_keys(&self, _prefix: &str) -> Result<Vec<String>, MemoryError> {
+        Ok(vec![])
+    }
+
+    fn clear(&self) -> Result<(), MemoryError> {
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -92,11 +436,29 @@ pub enum VectorBackend {
     LocalHnsw,
 }
 
+/// One hit of a similarity search.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VectorMatch {
+    pub key: String,
+    pub value: Value,
+    /// Similarity to the query under the index's [`Similarity`].
+    pub score: f32,
+}
+
 #[derive(Debug)]
 pub struct VectorStore {
     backend: VectorBackend,
     /// Minimal in-memory staging area until real vector DB integrations are wired in.
     buffer: RwLock<Vec<(String, Value)>>,
+    /// Embeddings of the staged entries, searched by `LocalHnsw`.
+    index: RwLock<HnswIndex>,
+}
+
+/// On-disk form of a `LocalHnsw` [`VectorStore`].
+#[derive(Serialize, Deserialize)]
+struct VectorSnapshot {
+    entries: Vec<(String, Value)>,
+    index: HnswIndex,
 }
 
 impl VectorStore {
@@ -104,19 +466,103 @@ impl VectorStore {
         Self {
             backend,
             buffer: RwLock::new(Vec::new()),
+            index: RwLock::new(HnswIndex::default()),
         }
     }
-}
 
-impl MemoryStore for VectorStore {
-    fn put(&self, key: &str, value: &Value) -> Result<(), MemoryError> {
-        self.buffer
+    /// A `LocalHnsw` store whose index uses `config`.
+    pub fn local(config: HnswConfig) -> Self {
+        Self {
+            index: RwLock::new(HnswIndex::new(config)),
+            ..Self::new(VectorBackend::LocalHnsw)
+        }
+    }
+
+    /// Tunes recall against speed for later
+    /// [`search_embedding`](MemoryStore::search_embedding) calls.
+    pub fn set_ef_search(&self, ef_search: usize) -> Result<(), MemoryError> {
+        self.index
             .write()
             .map_err(|e| MemoryError::Backend(e.to_string()))?
-            .push((key.to_string(), value.clone()));
+            .set_ef_search(ef_search);
         Ok(())
     }
 
+    /// Writes the entries and index of a `LocalHnsw` store to `path`.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), MemoryError> {
+        self.require_local("save")?;
+        let snapshot = VectorSnapshot {
+            entries: self
+                .buffer
+                .read()
+                .map_err(|e| MemoryError::Backend(e.to_string()))?
+                .clone(),
+            index: self
+                .index
+                .read()
+                .map_err(|e| MemoryError::Backend(e.to_string()))?
+                .clone(),
+        };
+        let json =
+            serde_json::to_vec(&snapshot).map_err(|e| MemoryError::Backend(e.to_string()))?;
+        std::fs::write(path, json).map_err(|e| MemoryError::Backend(e.to_string()))
+    }
+
+    /// Reopens a `LocalHnsw` store written by [`save`](Self::save).
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, MemoryError> {
+        let json = std::fs::read(path).map_err(|e| MemoryError::Backend(e.to_string()))?;
+        let snapshot: VectorSnapshot =
+            serde_json::from_slice(&json).map_err(|e| MemoryError::Backend(e.to_string()))?;
+        Ok(Self {
+            backend: VectorBackend::LocalHnsw,
+            buffer: RwLock::new(snapshot.entries),
+            index: RwLock::new(snapshot.index),
+        })
+    }
+
+    fn require_local(&self, operation: &str) -> Result<(), MemoryError> {
+        match self.backend {
+            VectorBackend::LocalHnsw => Ok(()),
+            _ => Err(MemoryError::Unsupported(format!(
+                "{operation} needs the LocalHnsw backend, not {:?}",
+                self.backend
+            ))),
+        }
+    }
+
+    fn stage(&self, key: &str, value: &Value) -> Result<(), MemoryError> {
+        let mut buffer = self
+            .buffer
+            .write()
+            .map_err(|e| MemoryError::Backend(e.to_string()))?;
+        match buffer.iter_mut().find(|(k, _)| k == key) {
+            Some(entry) => entry.1 = value.clone(),
+            None => buffer.push((key.to_string(), value.clone())),
+        }
+        Ok(())
+    }
+}
+
+/// The numeric `embedding` array of a stored value, if it carries one.
+fn embedding_of(value: &Value) -> Option<Vec<f32>> {
+    value
+        .get("embedding")?
+        .as_array()?
+        .iter()
+        .map(|x| x.as_f64().map(|x| x as f32))
+        .collect()
+}
+
+impl MemoryStore for VectorStore {
+    /// With `LocalHnsw`, values carrying a numeric `embedding` array are
+    /// indexed by it.
+    fn put(&self, key: &str, value: &Value) -> Result<(), MemoryError> {
+        if let (VectorBackend::LocalHnsw, Some(embedding)) = (&self.backend, embedding_of(value)) {
+            return self.put_embedding(key, value, &embedding);
+        }
+        self.stage(key, value)
+    }
+
     fn get(&self, key: &str) -> Result<Option<Value>, MemoryError> {
         Ok(self
             .buffer
@@ -137,10 +583,111 @@ impl MemoryStore for VectorStore {
             .map(|(_, v)| v.clone())
             .collect())
     }
+
+    fn entries(&self) -> Result<Vec<(String, Value)>, MemoryError> {
+        Ok(self
+            .buffer
+            .read()
+            .map_err(|e| MemoryError::Backend(e.to_string()))?
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect())
+    }
+
+    /// Only `LocalHnsw` keeps an index so far; other backends store just
+    /// the value.
+    fn put_embedding(
+        &self,
+        key: &str,
+        value: &Value,
+        embedding: &[f32],
+    ) -> Result<(), MemoryError> {
+        if let VectorBackend::LocalHnsw = self.backend {
+            self.index
+                .write()
+                .map_err(|e| MemoryError::Backend(e.to_string()))?
+                .insert(key, embedding)?;
+        }
+        self.stage(key, value)
+    }
+
+    fn delete(&self, key: &str) -> Result<bool, MemoryError> {
+        self.index
+            .write()
+            .map_err(|e| MemoryError::Backend(e.to_string()))?
+            .remove(key);
+        let mut buffer = self
+            .buffer
+            .write()
+            .map_err(|e| MemoryError::Backend(e.to_string()))?;
+        let before = buffer.len();
+        buffer.retain(|(k, _)| k != key);
+        Ok(buffer.len() < before)
+    }
+
+    fn list_keys(&self, prefix: &str) -> Result<Vec<String>, MemoryError> {
+        let mut keys: Vec<String> = self
+            .buffer
+            .read()
+            .map_err(|e| MemoryError::Backend(e.to_string()))?
+            .iter()
+            .filter(|(k, _)| k.starts_with(prefix))
+            .map(|(k, _)| k.clone())
+            .collect();
+        keys.sort();
+        Ok(keys)
+    }
+
+    fn clear(&self) -> Result<(), MemoryError> {
+        let mut index = self
+            .index
+            .write()
+            .map_err(|e| MemoryError::Backend(e.to_string()))?;
+        *index = HnswIndex::new(*index.config());
+        self.buffer
+            .write()
+            .map_err(|e| MemoryError::Backend(e.to_string()))?
+            .clear();
+        Ok(())
+    }
+
+    fn search_embedding(
+        &self,
+        embedding: &[f32],
+        top_k: usize,
+    ) -> Result<Vec<VectorMatch>, MemoryError> {
+        self.require_local("search_embedding")?;
+        let hits = self
+            .index
+            .read()
+            .map_err(|e| MemoryError::Backend(e.to_string()))?
+            .search(embedding, top_k);
+        let buffer = self
+            .buffer
+            .read()
+            .map_err(|e| MemoryError::Backend(e.to_string()))?;
+        Ok(hits
+            .into_iter()
+            .filter_map(|(key, score)| {
+                let value = buffer.iter().find(|(k, _)| *k == key)?.1.clone();
+                Some(VectorMatch { key, value, score })
+            })
+            .collect())
+    }
+
+    fn get_embedding(&self, key: &str) -> Result<Option<Vec<f32>>, MemoryError> {
+        Ok(self
+            .index
+            .read()
+            .map_err(|e| MemoryError::Backend(e.to_string()))?
+            .vector(key)
+            .map(<[f32]>::to_vec))
+    }
 }
 
 #[derive(Debug)]
 pub struct SqliteStore {
+    #[allow(dead_code)]
     connection_string: String,
     cache: RwLock<HashMap<String, Value>>,
 }
@@ -182,6 +729,46 @@ impl MemoryStore for SqliteStore {
             .map(|(_, v)| v.clone())
             .collect())
     }
+
+    fn entries(&self) -> Result<Vec<(String, Value)>, MemoryError> {
+        Ok(self
+            .cache
+            .read()
+            .map_err(|e| MemoryError::Backend(e.to_string()))?
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect())
+    }
+
+    fn delete(&self, key: &str) -> Result<bool, MemoryError> {
+        Ok(self
+            .cache
+            .write()
+            .map_err(|e| MemoryError::Backend(e.to_string()))?
+            .remove(key)
+            .is_some())
+    }
+
+    fn list_keys(&self, prefix: &str) -> Result<Vec<String>, MemoryError> {
+        let mut keys: Vec<String> = self
+            .cache
+            .read()
+            .map_err(|e| MemoryError::Backend(e.to_string()))?
+            .keys()
+            .filter(|k| k.starts_with(prefix))
+            .cloned()
+            .collect();
+        keys.sort();
+        Ok(keys)
+    }
+
+    fn clear(&self) -> Result<(), MemoryError> {
+        self.cache
+            .write()
+            .map_err(|e| MemoryError::Backend(e.to_string()))?
+            .clear();
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
@@ -218,49 +805,137 @@ impl MemoryStore for PostgresStore {
             self.connection_string
         )))
     }
-}
 
-#[derive(Debug)]
-pub struct RedisStore {
-    connection_string: String,
-    cache: RwLock<HashMap<String, Value>>,
-}
+    fn delete(&self, _key: &str) -> Result<bool, MemoryError> {
+        Err(MemoryError::Unsupported(format!(
+            "delete not implemented for Postgres store ({})",
+            self.connection_string
+        )))
+    }
 
-impl RedisStore {
-    pub fn new<T: Into<String>>(connection_string: T) -> Self {
-        Self {
-            connection_string: connection_string.into(),
-            cache: RwLock::new(HashMap::new()),
-        }
+    fn list_keys(&self, _prefix: &str) -> Result<Vec<String>, MemoryError> {
+        Err(MemoryError::Unsupported(format!(
+            "list_keys not implemented for Postgres store ({})",
+            self.connection_string
+        )))
+    }
+
+    fn clear(&self) -> Result<(), MemoryError> {
+        Err(MemoryError::Unsupported(format!(
+            "clear not implemented for Postgres store ({})",
+            self.connection_string
+        )))
     }
 }
 
-impl MemoryStore for RedisStore {
-    fn put(&self, key: &str, value: &Value) -> Result<(), MemoryError> {
-        self.cache
-            .write()
-            .map_err(|e| MemoryError::Backend(e.to_string()))?
-            .insert(key.to_string(), value.clone());
-        Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn shipped_stores_delete_list_and_clear() {
+        let stores: Vec<Box<dyn MemoryStore>> = vec![
+            Box::new(InMemoryStore::new()),
+            Box::new(SqliteStore::new("sqlite::memory:")),
+            Box::new(VectorStore::new(VectorBackend::Qdrant)),
+            Box::new(VectorStore::local(HnswConfig::default())),
+        ];
+        for store in stores {
+            store.put("note:b", &json!("second")).unwrap();
+            store.put("note:a", &json!("first")).unwrap();
+            store
+                .put("fact", &json!({"text": "tea", "embedding": [1.0, 0.0]}))
+                .unwrap();
+
+            assert_eq!(store.list_keys("note:").unwrap(), vec!["note:a", "note:b"]);
+            assert!(store.delete("note:a").unwrap(), "{store:?}");
+            assert!(!store.delete("note:a").unwrap());
+            assert_eq!(store.get("note:a").unwrap(), None);
+            assert_eq!(store.list_keys("").unwrap(), vec!["fact", "note:b"]);
+
+            store.clear().unwrap();
+            assert!(store.list_keys("").unwrap().is_empty());
+            assert!(store.entries().unwrap().is_empty());
+        }
+
+        let local = VectorStore::local(HnswConfig::default());
+        local.put_embedding("x", &json!("x"), &[1.0, 0.0]).unwrap();
+        local.delete("x").unwrap();
+        assert!(local.search_embedding(&[1.0, 0.0], 1).unwrap().is_empty());
+        assert!(PostgresStore::new("postgres://").clear().is_err());
     }
 
-    fn get(&self, key: &str) -> Result<Option<Value>, MemoryError> {
-        Ok(self
-            .cache
-            .read()
-            .map_err(|e| MemoryError::Backend(e.to_string()))?
-            .get(key)
-            .cloned())
+    #[test]
+    fn put_if_version_rejects_stale_writes() {
+        let store = InMemoryStore::new();
+        assert_eq!(store.put_if_version("plan", &json!("draft"), 0).unwrap(), 1);
+        assert!(matches!(
+            store.put_if_version("plan", &json!("again"), 0),
+            Err(MemoryError::Conflict { actual: 1, .. })
+        ));
+
+        let (value, version) = store.get_versioned("plan").unwrap().unwrap();
+        assert_eq!(value, json!("draft"));
+        assert_eq!(
+            store
+                .put_if_version("plan", &json!("final"), version)
+                .unwrap(),
+            2
+        );
+        store.put("plan", &json!("overwritten")).unwrap();
+        let stale = store.put_if_version("plan", &json!("late"), 2).unwrap_err();
+        assert_eq!(
+            stale.to_string(),
+            "version conflict on plan: expected 2, found 3"
+        );
+        assert_eq!(store.get("plan").unwrap(), Some(json!("overwritten")));
+
+        store.delete("plan").unwrap();
+        assert_eq!(store.get_versioned("plan").unwrap(), None);
+        assert_eq!(store.put_if_version("plan", &json!("new"), 0).unwrap(), 1);
     }
 
-    fn search(&self, query: &str) -> Result<Vec<Value>, MemoryError> {
-        Ok(self
-            .cache
-            .read()
-            .map_err(|e| MemoryError::Backend(e.to_string()))?
-            .iter()
-            .filter(|(k, v)| k.contains(query) || v.to_string().contains(query))
-            .map(|(_, v)| v.clone())
-            .collect())
+    #[test]
+    fn quotas_evict_by_recency_or_frequency() {
+        let lru = InMemoryStore::new().with_quota(MemoryQuota::new().with_max_entries(2));
+        lru.put("a", &json!(1)).unwrap();
+        lru.put("b", &json!(2)).unwrap();
+        lru.get("a").unwrap();
+        lru.put("c", &json!(3)).unwrap();
+        assert_eq!(lru.list_keys("").unwrap(), vec!["a", "c"]);
+
+        let lfu = InMemoryStore::new().with_quota(
+            MemoryQuota::new()
+                .with_max_entries(2)
+                .with_policy(EvictionPolicy::Lfu),
+        );
+        lfu.put("a", &json!(1)).unwrap();
+        lfu.put("b", &json!(2)).unwrap();
+        lfu.get("a").unwrap();
+        lfu.get("a").unwrap();
+        lfu.get("b").unwrap();
+        lfu.put("c", &json!(3)).unwrap();
+        lfu.put("d", &json!(4)).unwrap();
+        assert_eq!(lfu.list_keys("").unwrap(), vec!["a", "d"]);
+        assert_eq!(lfu.evictions(), 2);
+
+        let telemetry = Arc::new(agent_telemetry::Telemetry::new());
+        let sized = InMemoryStore::new()
+            .with_quota(MemoryQuota::new().with_max_bytes(10))
+            .with_observer(TelemetryObserver::new(telemetry.clone()));
+        sized.put("k1", &json!("ab")).unwrap();
+        sized.put("k2", &json!("cd")).unwrap();
+        assert_eq!(sized.list_keys("").unwrap(), vec!["k2"]);
+        assert_eq!(sized.used_bytes(), 6);
+        sized.put("k1", &json!("abcd")).unwrap();
+        assert_eq!(sized.list_keys("").unwrap(), vec!["k1"]);
+        assert_eq!(sized.used_bytes(), 8);
+        assert!(sized.put("big", &json!("0123456789")).is_err());
+        assert!(telemetry
+            .export_metrics()
+            .contains(r#"memory_ops{op="evict",outcome="ok"} 2"#));
+        sized.clear().unwrap();
+        assert_eq!(sized.used_bytes(), 0);
     }
 }
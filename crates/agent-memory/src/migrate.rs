@@ -0,0 +1,207 @@
+//! Copying entries from one memory backend to another.
+
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{MemoryError, MemoryStore};
+
+/// Where a [`migrate`] run has got to, passed to its progress callback after
+/// each entry.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MigrationProgress {
+    pub key: String,
+    /// Entries handled so far, this one included.
+    pub done: usize,
+    pub total: usize,
+}
+
+/// Called by [`migrate`] after each entry.
+pub type ProgressFn = Arc<dyn Fn(&MigrationProgress) + Send + Sync>;
+
+/// How [`migrate`] copies entries.
+#[derive(Clone, Default)]
+pub struct MigrationOptions {
+    /// Only keys starting with this are copied, e.g. one agent's namespace.
+    pub key_prefix: String,
+    /// Reads and counts everything but writes nothing.
+    pub dry_run: bool,
+    /// Leaves keys the destination already holds untouched.
+    pub skip_existing: bool,
+    pub progress: Option<ProgressFn>,
+}
+
+impl MigrationOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_key_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.key_prefix = prefix.into();
+        self
+    }
+
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    pub fn with_skip_existing(mut self, skip_existing: bool) -> Self {
+        self.skip_existing = skip_existing;
+        self
+    }
+
+    pub fn with_progress(
+        mut self,
+        progress: impl Fn(&MigrationProgress) + Send + Sync + 'static,
+    ) -> Self {
+        self.progress = Some(Arc::new(progress));
+        self
+    }
+}
+
+impl std::fmt::Debug for MigrationOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MigrationOptions")
+            .field("key_prefix", &self.key_prefix)
+            .field("dry_run", &self.dry_run)
+            .field("skip_existing", &self.skip_existing)
+            .field("progress", &self.progress.is_some())
+            .finish()
+    }
+}
+
+/// What a [`migrate`] run copied, or with `dry_run` would have copied.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MigrationReport {
+    pub copied: usize,
+    /// Copied entries that carried an embedding.
+    pub embeddings: usize,
+    /// Entries left alone because the destination already held them.
+    pub skipped: usize,
+    pub dry_run: bool,
+}
+
+/// Copies every entry under `options.key_prefix` from `source` to `dest`.
+///
+/// Values move unchanged, metadata included, and entries with an embedding
+/// in the source are written with [`put_embedding`](MemoryStore::put_embedding)
+/// so the destination can index them. Keys are listed up front and each
+/// value is read as it is copied; stores that cannot list keys are read in
+/// one go through [`entries`](MemoryStore::entries). The first failure stops
+/// the run, and entries already copied stay in `dest`.
+pub fn migrate(
+    source: &dyn MemoryStore,
+    dest: &dyn MemoryStore,
+    options: &MigrationOptions,
+) -> Result<MigrationReport, MemoryError> {
+    let mut report = MigrationReport {
+        dry_run: options.dry_run,
+        ..MigrationReport::default()
+    };
+    let (keys, mut preloaded) = match source.list_keys(&options.key_prefix) {
+        Ok(keys) => (keys, None),
+        Err(MemoryError::Unsupported(_)) => {
+            let mut entries: Vec<_> = source
+                .entries()?
+                .into_iter()
+                .filter(|(key, _)| key.starts_with(&options.key_prefix))
+                .collect();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            let keys = entries.iter().map(|(key, _)| key.clone()).collect();
+            (keys, Some(entries.into_iter().map(|(_, value)| value)))
+        }
+        Err(error) => return Err(error),
+    };
+
+    let total = keys.len();
+    for (done, key) in keys.into_iter().enumerate() {
+        let value = match preloaded.as_mut() {
+            Some(values) => values.next(),
+            None => source.get(&key)?,
+        };
+        // Gone since it was listed.
+        let Some(value) = value else { continue };
+
+        if options.skip_existing && dest.get(&key)?.is_some() {
+            report.skipped += 1;
+        } else {
+            let embedding = source.get_embedding(&key)?;
+            if !options.dry_run {
+                match &embedding {
+                    Some(embedding) => dest.put_embedding(&key, &value, embedding)?,
+                    None => dest.put(&key, &value)?,
+                }
+            }
+            report.copied += 1;
+            report.embeddings += usize::from(embedding.is_some());
+        }
+
+        if let Some(progress) = &options.progress {
+            progress(&MigrationProgress {
+                key,
+                done: done + 1,
+                total,
+            });
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{HnswConfig, InMemoryStore, VectorStore};
+    use serde_json::json;
+    use std::sync::Mutex;
+
+    #[test]
+    fn copies_values_and_embeddings_between_backends() {
+        let source = VectorStore::local(HnswConfig::default());
+        let tea = json!({"text": "likes tea", "metadata": {"owner": "ana"}});
+        source.put_embedding("ana:tea", &tea, &[1.0, 0.0]).unwrap();
+        source
+            .put_embedding("ana:cats", &json!("has two cats"), &[0.0, 1.0])
+            .unwrap();
+        source.put("ben:plan", &json!("ship friday")).unwrap();
+
+        let dest = VectorStore::local(HnswConfig::default());
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let options = MigrationOptions::new()
+            .with_key_prefix("ana:")
+            .with_dry_run(true)
+            .with_progress({
+                let seen = seen.clone();
+                move |progress| seen.lock().unwrap().push(progress.clone())
+            });
+
+        let report = migrate(&source, &dest, &options).unwrap();
+        assert_eq!((report.copied, report.embeddings), (2, 2));
+        assert!(dest.list_keys("").unwrap().is_empty());
+        assert_eq!(
+            seen.lock().unwrap().last(),
+            Some(&MigrationProgress {
+                key: "ana:tea".into(),
+                done: 2,
+                total: 2,
+            })
+        );
+
+        let report = migrate(&source, &dest, &options.with_dry_run(false)).unwrap();
+        assert!(!report.dry_run);
+        assert_eq!(dest.get("ana:tea").unwrap(), Some(tea));
+        let hits = dest.search_embedding(&[0.1, 0.9], 1).unwrap();
+        assert_eq!(hits[0].key, "ana:cats");
+
+        let flat = InMemoryStore::new();
+        flat.put("ben:plan", &json!("keep me")).unwrap();
+        let report = migrate(
+            &source,
+            &flat,
+            &MigrationOptions::new().with_skip_existing(true),
+        )
+        .unwrap();
+        assert_eq!((report.copied, report.skipped), (2, 1));
+        assert_eq!(flat.get("ben:plan").unwrap(), Some(json!("keep me")));
+    }
+}
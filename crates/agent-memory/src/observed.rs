@@ -0,0 +1,320 @@
+//! Events for every operation on a memory store.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use agent_telemetry::Telemetry;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{MemoryError, MemoryStore, VectorMatch};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MemoryOp {
+    Put,
+    Get,
+    Search,
+    Delete,
+    /// An entry dropped by a store to stay within its
+    /// [`MemoryQuota`](crate::MemoryQuota).
+    Evict,
+}
+
+impl MemoryOp {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MemoryOp::Put => "put",
+            MemoryOp::Get => "get",
+            MemoryOp::Search => "search",
+            MemoryOp::Delete => "delete",
+            MemoryOp::Evict => "evict",
+        }
+    }
+}
+
+/// One operation seen by an [`ObservedStore`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MemoryEvent {
+    pub op: MemoryOp,
+    /// The key, or the query of a search.
+    pub key: String,
+    /// Serialized size of the values written or returned; for an eviction,
+    /// the bytes freed.
+    pub bytes: usize,
+    /// Values returned: 0 or 1 for a get, any number for a search, 1 for a
+    /// delete that removed something.
+    pub hits: usize,
+    pub latency: Duration,
+    /// The error, when the operation failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Receives every [`MemoryEvent`] of an [`ObservedStore`]. Called inline, so
+/// implementations should be quick.
+pub trait MemoryObserver: Send + Sync {
+    fn on_event(&self, event: &MemoryEvent);
+}
+
+/// Feeds memory events into the `memory_*` metrics of [`Telemetry`].
+#[derive(Clone)]
+pub struct TelemetryObserver {
+    telemetry: Arc<Telemetry>,
+}
+
+impl TelemetryObserver {
+    pub fn new(telemetry: Arc<Telemetry>) -> Self {
+        Self { telemetry }
+    }
+}
+
+impl MemoryObserver for TelemetryObserver {
+    fn on_event(&self, event: &MemoryEvent) {
+        self.telemetry.record_memory_op(
+            event.op.as_str(),
+            event.error.is_none(),
+            event.bytes as u64,
+            event.latency.as_secs_f64() * 1000.0,
+        );
+    }
+}
+
+impl std::fmt::Debug for TelemetryObserver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TelemetryObserver").finish()
+    }
+}
+
+/// Reports each `put`, `get`, `search` and `delete` on an inner store to its
+/// observers; other calls pass straight through.
+pub struct ObservedStore {
+    inner: Arc<dyn MemoryStore>,
+    observers: Vec<Arc<dyn MemoryObserver>>,
+}
+
+impl ObservedStore {
+    pub fn new(inner: Arc<dyn MemoryStore>) -> Self {
+        Self {
+            inner,
+            observers: Vec::new(),
+        }
+    }
+
+    pub fn with_observer(mut self, observer: impl MemoryObserver + 'static) -> Self {
+        self.observers.push(Arc::new(observer));
+        self
+    }
+
+    pub fn inner(&self) -> &Arc<dyn MemoryStore> {
+        &self.inner
+    }
+
+    /// Runs `call`, then tells every observer how it went; `measure` gives
+    /// the bytes and hits of a success.
+    fn observe<T>(
+        &self,
+        op: MemoryOp,
+        key: &str,
+        call: impl FnOnce() -> Result<T, MemoryError>,
+        measure: impl FnOnce(&T) -> (usize, usize),
+    ) -> Result<T, MemoryError> {
+        let started = Instant::now();
+        let result = call();
+        let latency = started.elapsed();
+        let ((bytes, hits), error) = match &result {
+            Ok(output) => (measure(output), None),
+            Err(error) => ((0, 0), Some(error.to_string())),
+        };
+        let event = MemoryEvent {
+            op,
+            key: key.to_string(),
+            bytes,
+            hits,
+            latency,
+            error,
+        };
+        for observer in &self.observers {
+            observer.on_event(&event);
+        }
+        result
+    }
+}
+
+impl std::fmt::Debug for ObservedStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ObservedStore")
+            .field("inner", &self.inner)
+            .field("observers", &self.observers.len())
+            .finish()
+    }
+}
+
+fn size_of(value: &Value) -> usize {
+    value.to_string().len()
+}
+
+impl MemoryStore for ObservedStore {
+    fn put(&self, key: &str, value: &Value) -> Result<(), MemoryError> {
+        let bytes = size_of(value);
+        self.observe(
+            MemoryOp::Put,
+            key,
+            || self.inner.put(key, value),
+            |_| (bytes, 0),
+        )
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Value>, MemoryError> {
+        self.observe(
+            MemoryOp::Get,
+            key,
+            || self.inner.get(key),
+            |value| match value {
+                Some(value) => (size_of(value), 1),
+                None => (0, 0),
+            },
+        )
+    }
+
+    fn search(&self, query: &str) -> Result<Vec<Value>, MemoryError> {
+        self.observe(
+            MemoryOp::Search,
+            query,
+            || self.inner.search(query),
+            |values| (values.iter().map(size_of).sum(), values.len()),
+        )
+    }
+
+    fn entries(&self) -> Result<Vec<(String, Value)>, MemoryError> {
+        self.inner.entries()
+    }
+
+    fn delete(&self, key: &str) -> Result<bool, MemoryError> {
+        self.observe(
+            MemoryOp::Delete,
+            key,
+            || self.inner.delete(key),
+            |removed| (0, usize::from(*removed)),
+        )
+    }
+
+    fn list_keys(&self, prefix: &str) -> Result<Vec<String>, MemoryError> {
+        self.inner.list_keys(prefix)
+    }
+
+    fn clear(&self) -> Result<(), MemoryError> {
+        self.inner.clear()
+    }
+
+    fn put_embedding(
+        &self,
+        key: &str,
+        value: &Value,
+        embedding: &[f32],
+    ) -> Result<(), MemoryError> {
+        let bytes = size_of(value);
+        self.observe(
+            MemoryOp::Put,
+            key,
+            || self.inner.put_embedding(key, value, embedding),
+            |_| (bytes, 0),
+        )
+    }
+
+    fn search_embedding(
+        &self,
+        embedding: &[f32],
+        top_k: usize,
+    ) -> Result<Vec<VectorMatch>, MemoryError> {
+        self.inner.search_embedding(embedding, top_k)
+    }
+
+    fn get_embedding(&self, key: &str) -> Result<Option<Vec<f32>>, MemoryError> {
+        self.inner.get_embedding(key)
+    }
+
+    fn get_versioned(&self, key: &str) -> Result<Option<(Value, u64)>, MemoryError> {
+        self.observe(
+            MemoryOp::Get,
+            key,
+            || self.inner.get_versioned(key),
+            |value| match value {
+                Some((value, _)) => (size_of(value), 1),
+                None => (0, 0),
+            },
+        )
+    }
+
+    fn put_if_version(
+        &self,
+        key: &str,
+        value: &Value,
+        expected_version: u64,
+    ) -> Result<u64, MemoryError> {
+        let bytes = size_of(value);
+        self.observe(
+            MemoryOp::Put,
+            key,
+            || self.inner.put_if_version(key, value, expected_version),
+            |_| (bytes, 0),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{InMemoryStore, PostgresStore};
+    use serde_json::json;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<MemoryEvent>>);
+
+    impl MemoryObserver for Arc<Recorder> {
+        fn on_event(&self, event: &MemoryEvent) {
+            self.0.lock().unwrap().push(event.clone());
+        }
+    }
+
+    #[test]
+    fn reports_operations_to_observers_and_telemetry() {
+        let recorder = Arc::new(Recorder::default());
+        let telemetry = Arc::new(Telemetry::new());
+        let store = ObservedStore::new(Arc::new(InMemoryStore::new()))
+            .with_observer(recorder.clone())
+            .with_observer(TelemetryObserver::new(telemetry.clone()));
+
+        store.put("note", &json!("hello")).unwrap();
+        store.get("note").unwrap();
+        store.get("missing").unwrap();
+        store.search("hel").unwrap();
+        store.delete("note").unwrap();
+
+        let events = recorder.0.lock().unwrap().clone();
+        let summary: Vec<(MemoryOp, &str, usize, usize)> = events
+            .iter()
+            .map(|event| (event.op, event.key.as_str(), event.bytes, event.hits))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (MemoryOp::Put, "note", 7, 0),
+                (MemoryOp::Get, "note", 7, 1),
+                (MemoryOp::Get, "missing", 0, 0),
+                (MemoryOp::Search, "hel", 7, 1),
+                (MemoryOp::Delete, "note", 0, 1),
+            ]
+        );
+
+        let failing = ObservedStore::new(Arc::new(PostgresStore::new("postgres://")))
+            .with_observer(TelemetryObserver::new(telemetry.clone()));
+        assert!(failing.put("note", &json!(1)).is_err());
+
+        let metrics = telemetry.export_metrics();
+        assert!(metrics.contains(r#"memory_ops{op="get",outcome="ok"} 2"#));
+        assert!(metrics.contains(r#"memory_ops{op="put",outcome="error"} 1"#));
+        assert!(metrics.contains(r#"memory_bytes{op="put"} 7"#));
+    }
+}
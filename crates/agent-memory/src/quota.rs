@@ -0,0 +1,52 @@
+//! Size limits for [`InMemoryStore`](crate::InMemoryStore).
+
+use serde::{Deserialize, Serialize};
+
+/// Which entry goes first when a store is over its [`MemoryQuota`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EvictionPolicy {
+    /// The entry read or written longest ago.
+    #[default]
+    Lru,
+    /// The entry read least often, oldest first among equals.
+    Lfu,
+}
+
+/// Limits past which a store evicts entries. Bytes count each key plus its
+/// serialized value; unset limits are unbounded.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryQuota {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_entries: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_bytes: Option<usize>,
+    #[serde(default)]
+    pub policy: EvictionPolicy,
+}
+
+impl MemoryQuota {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = Some(max_entries);
+        self
+    }
+
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    pub fn with_policy(mut self, policy: EvictionPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub(crate) fn exceeded(&self, entries: usize, bytes: usize) -> bool {
+        self.max_entries.is_some_and(|max| entries > max)
+            || self.max_bytes.is_some_and(|max| bytes > max)
+    }
+}
@@ -0,0 +1,345 @@
+//! [`RedisStore`], enabled by the `redis` cargo feature.
+
+use std::collections::HashSet;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use redis::aio::MultiplexedConnection;
+use serde_json::Value;
+use tokio::runtime::Runtime;
+use tokio::sync::OnceCell;
+
+use crate::{MemoryError, MemoryStore};
+
+/// Keys fetched per `SCAN` round trip.
+const SCAN_BATCH: usize = 200;
+
+/// Memory kept in Redis, shared by every process pointed at the server.
+///
+/// Values are stored as JSON strings, or as native RedisJSON documents with
+/// [`with_json`](Self::with_json). Keys live under an optional prefix and can
+/// expire, either by a store-wide default TTL or per write through
+/// [`put_with_ttl`](Self::put_with_ttl). `search` and `entries` walk the
+/// prefix with `SCAN`, never `KEYS`, so they don't block the server.
+///
+/// The connection is opened on first use and multiplexed over every call.
+/// [`MemoryStore`] is synchronous, so commands run on a small runtime owned
+/// by the store, which keeps calls safe from inside or outside async code.
+pub struct RedisStore {
+    connection_string: String,
+    client: redis::Client,
+    connection: Arc<OnceCell<MultiplexedConnection>>,
+    prefix: String,
+    default_ttl: Option<Duration>,
+    json: bool,
+    // Always `Some` until dropped.
+    runtime: Option<Runtime>,
+}
+
+impl RedisStore {
+    /// A store for the server at `connection_string`, e.g.
+    /// `redis://localhost:6379/0`. Nothing is sent until the first call.
+    pub fn new<T: Into<String>>(connection_string: T) -> Result<Self, MemoryError> {
+        let connection_string = connection_string.into();
+        let client = redis::Client::open(connection_string.as_str()).map_err(backend)?;
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("redis-store")
+            .enable_all()
+            .build()
+            .map_err(|e| MemoryError::Backend(e.to_string()))?;
+        Ok(Self {
+            connection_string,
+            client,
+            connection: Arc::new(OnceCell::new()),
+            prefix: String::new(),
+            default_ttl: None,
+            json: false,
+            runtime: Some(runtime),
+        })
+    }
+
+    /// Namespaces every key, e.g. `agent:`, so several stores can share a
+    /// database and `search` only sees this one's keys.
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Expires every entry written by `put` after `ttl`.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.default_ttl = Some(ttl);
+        self
+    }
+
+    /// Stores values with `JSON.SET`; the server needs the RedisJSON module.
+    pub fn with_json(mut self, json: bool) -> Self {
+        self.json = json;
+        self
+    }
+
+    pub fn connection_string(&self) -> &str {
+        &self.connection_string
+    }
+
+    /// Writes `value` expiring after `ttl`, whatever the default.
+    pub fn put_with_ttl(&self, key: &str, value: &Value, ttl: Duration) -> Result<(), MemoryError> {
+        self.write(key, value, Some(ttl))
+    }
+
+    /// Time left before `key` expires: `None` when it is missing or never
+    /// expires.
+    pub fn ttl(&self, key: &str) -> Result<Option<Duration>, MemoryError> {
+        let key = self.key(key);
+        let millis: i64 = self.run(move |mut connection| async move {
+            redis::cmd("PTTL")
+                .arg(key)
+                .query_async(&mut connection)
+                .await
+        })?;
+        Ok(u64::try_from(millis).ok().map(Duration::from_millis))
+    }
+
+    fn key(&self, key: &str) -> String {
+        format!("{}{key}", self.prefix)
+    }
+
+    fn write(&self, key: &str, value: &Value, ttl: Option<Duration>) -> Result<(), MemoryError> {
+        let key = self.key(key);
+        let value = value.to_string();
+        // Redis rejects a zero expiry, so round sub-millisecond TTLs up.
+        let ttl_ms = ttl.map(|ttl| ttl.as_millis().max(1) as u64);
+        let json = self.json;
+        self.run(move |mut connection| async move {
+            let mut pipe = redis::pipe();
+            pipe.atomic();
+            if json {
+                pipe.cmd("JSON.SET").arg(&key).arg("$").arg(value).ignore();
+                if let Some(ttl_ms) = ttl_ms {
+                    pipe.cmd("PEXPIRE").arg(&key).arg(ttl_ms).ignore();
+                }
+            } else {
+                let set = pipe.cmd("SET").arg(&key).arg(value);
+                if let Some(ttl_ms) = ttl_ms {
+                    set.arg("PX").arg(ttl_ms);
+                }
+                set.ignore();
+            }
+            pipe.query_async::<()>(&mut connection).await
+        })
+    }
+
+    /// Every `(key, value)` under the prefix whose key or serialized value
+    /// contains `query`; all of them for an empty one.
+    fn scan(&self, query: &str) -> Result<Vec<(String, Value)>, MemoryError> {
+        let pattern = format!("{}*", escape_glob(&self.prefix));
+        let json = self.json;
+        let raw = self.run(move |mut connection| async move {
+            let keys = scan_keys(&mut connection, &pattern).await?;
+            let mut found = Vec::with_capacity(keys.len());
+            for batch in keys.chunks(SCAN_BATCH) {
+                let mut pipe = redis::pipe();
+                for key in batch {
+                    pipe.cmd(if json { "JSON.GET" } else { "GET" }).arg(key);
+                }
+                let values: Vec<Option<String>> = pipe.query_async(&mut connection).await?;
+                found.extend(batch.iter().cloned().zip(values));
+            }
+            Ok(found)
+        })?;
+
+        let mut entries = Vec::new();
+        for (key, value) in raw {
+            // Keys may expire between SCAN and GET.
+            let Some(value) = value else { continue };
+            let key = key.strip_prefix(&self.prefix).unwrap_or(&key).to_string();
+            if !key.contains(query) && !value.contains(query) {
+                continue;
+            }
+            entries.push((key, decode(&value)?));
+        }
+        Ok(entries)
+    }
+
+    /// Runs `op` with the shared connection on the store's runtime and waits
+    /// for it.
+    fn run<T, F, Fut>(&self, op: F) -> Result<T, MemoryError>
+    where
+        T: Send + 'static,
+        F: FnOnce(MultiplexedConnection) -> Fut + Send + 'static,
+        Fut: Future<Output = redis::RedisResult<T>> + Send,
+    {
+        let client = self.client.clone();
+        let cell = self.connection.clone();
+        let (done, result) = std::sync::mpsc::sync_channel(1);
+        self.runtime
+            .as_ref()
+            .expect("runtime lives as long as the store")
+            .spawn(async move {
+                let outcome = async {
+                    let connection = cell
+                        .get_or_try_init(|| client.get_multiplexed_async_connection())
+                        .await?
+                        .clone();
+                    op(connection).await
+                }
+                .await;
+                let _ = done.send(outcome);
+            });
+        result
+            .recv()
+            .map_err(|_| MemoryError::Backend("redis task was dropped".into()))?
+            .map_err(backend)
+    }
+}
+
+/// Every key matching `pattern`, walked with `SCAN` and deduplicated.
+async fn scan_keys(
+    connection: &mut MultiplexedConnection,
+    pattern: &str,
+) -> redis::RedisResult<Vec<String>> {
+    let mut keys = Vec::new();
+    let mut seen = HashSet::new();
+    let mut cursor = 0u64;
+    loop {
+        let (next, batch): (u64, Vec<String>) = redis::cmd("SCAN")
+            .arg(cursor)
+            .arg("MATCH")
+            .arg(pattern)
+            .arg("COUNT")
+            .arg(SCAN_BATCH)
+            .query_async(connection)
+            .await?;
+        // SCAN may return a key more than once.
+        keys.extend(batch.into_iter().filter(|key| seen.insert(key.clone())));
+        if next == 0 {
+            return Ok(keys);
+        }
+        cursor = next;
+    }
+}
+
+impl Drop for RedisStore {
+    fn drop(&mut self) {
+        // Dropping a runtime blocks, which panics inside async code.
+        if let Some(runtime) = self.runtime.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+impl std::fmt::Debug for RedisStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RedisStore")
+            .field("prefix", &self.prefix)
+            .field("default_ttl", &self.default_ttl)
+            .field("json", &self.json)
+            .finish()
+    }
+}
+
+impl MemoryStore for RedisStore {
+    fn put(&self, key: &str, value: &Value) -> Result<(), MemoryError> {
+        self.write(key, value, self.default_ttl)
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Value>, MemoryError> {
+        let key = self.key(key);
+        let json = self.json;
+        let value: Option<String> = self.run(move |mut connection| async move {
+            redis::cmd(if json { "JSON.GET" } else { "GET" })
+                .arg(key)
+                .query_async(&mut connection)
+                .await
+        })?;
+        value.as_deref().map(decode).transpose()
+    }
+
+    fn search(&self, query: &str) -> Result<Vec<Value>, MemoryError> {
+        Ok(self
+            .scan(query)?
+            .into_iter()
+            .map(|(_, value)| value)
+            .collect())
+    }
+
+    fn entries(&self) -> Result<Vec<(String, Value)>, MemoryError> {
+        self.scan("")
+    }
+
+    fn delete(&self, key: &str) -> Result<bool, MemoryError> {
+        let key = self.key(key);
+        let removed: u64 = self.run(move |mut connection| async move {
+            redis::cmd("DEL")
+                .arg(key)
+                .query_async(&mut connection)
+                .await
+        })?;
+        Ok(removed > 0)
+    }
+
+    fn list_keys(&self, prefix: &str) -> Result<Vec<String>, MemoryError> {
+        let pattern = format!("{}*", escape_glob(&self.key(prefix)));
+        let mut keys = self
+            .run(move |mut connection| async move { scan_keys(&mut connection, &pattern).await })?;
+        for key in &mut keys {
+            key.drain(..self.prefix.len());
+        }
+        keys.sort();
+        Ok(keys)
+    }
+
+    /// Removes every key under the store's prefix, or the whole database
+    /// when it has none.
+    fn clear(&self) -> Result<(), MemoryError> {
+        let pattern = format!("{}*", escape_glob(&self.prefix));
+        self.run(move |mut connection| async move {
+            let keys = scan_keys(&mut connection, &pattern).await?;
+            for batch in keys.chunks(SCAN_BATCH) {
+                redis::cmd("UNLINK")
+                    .arg(batch)
+                    .query_async::<()>(&mut connection)
+                    .await?;
+            }
+            Ok(())
+        })
+    }
+}
+
+fn backend(error: redis::RedisError) -> MemoryError {
+    MemoryError::Backend(error.to_string())
+}
+
+fn decode(value: &str) -> Result<Value, MemoryError> {
+    serde_json::from_str(value).map_err(|e| MemoryError::Backend(e.to_string()))
+}
+
+/// Makes `prefix` match itself literally in a `SCAN MATCH` pattern.
+fn escape_glob(prefix: &str) -> String {
+    let mut escaped = String::with_capacity(prefix.len());
+    for c in prefix.chars() {
+        if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_glob_matches_prefixes_literally() {
+        assert_eq!(escape_glob("agent:"), "agent:");
+        assert_eq!(escape_glob("a*b?[c]\\"), "a\\*b\\?\\[c\\]\\\\");
+    }
+
+    #[test]
+    fn decode_reports_malformed_values() {
+        assert_eq!(decode(r#"{"n":1}"#).unwrap(), serde_json::json!({"n": 1}));
+        assert!(matches!(decode("{"), Err(MemoryError::Backend(_))));
+    }
+}
@@ -0,0 +1,237 @@
+//! Semantic recall: ranking stored entries by embedding similarity to a
+//! query instead of by substring.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{MemoryError, MemoryStore, VectorMatch};
+
+/// Turns text into a vector whose cosine similarity tracks semantic similarity.
+#[async_trait]
+pub trait Embedder: Send + Sync {
+    async fn embed(&self, text: &str) -> Vec<f32>;
+}
+
+/// Cosine similarity of two vectors; `0.0` when either is all zeros or the
+/// lengths differ.
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
+
+/// Narrows a [`SemanticMemory::search_similar`] to matching entries.
+///
+/// Fields are looked up in the value's `metadata` object, then at its top
+/// level. Every condition must hold.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MetadataFilter {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_prefix: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub equals: Vec<(String, Value)>,
+}
+
+impl MetadataFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_key_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.key_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Requires `field` to equal `value`.
+    pub fn with_eq(mut self, field: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.equals.push((field.into(), value.into()));
+        self
+    }
+
+    pub fn matches(&self, key: &str, value: &Value) -> bool {
+        if let Some(prefix) = &self.key_prefix {
+            if !key.starts_with(prefix.as_str()) {
+                return false;
+            }
+        }
+        self.equals.iter().all(|(field, expected)| {
+            value
+                .get("metadata")
+                .and_then(|metadata| metadata.get(field))
+                .or_else(|| value.get(field))
+                == Some(expected)
+        })
+    }
+}
+
+/// A [`MemoryStore`] searched by meaning through an [`Embedder`].
+///
+/// Stores with a vector index, such as a `LocalHnsw`
+/// [`VectorStore`](crate::VectorStore), answer from it; any other store that
+/// lists its [`entries`](MemoryStore::entries) is ranked by embedding each
+/// entry at query time.
+#[derive(Clone)]
+pub struct SemanticMemory {
+    store: Arc<dyn MemoryStore>,
+    embedder: Arc<dyn Embedder>,
+}
+
+impl SemanticMemory {
+    pub fn new(store: Arc<dyn MemoryStore>, embedder: Arc<dyn Embedder>) -> Self {
+        Self { store, embedder }
+    }
+
+    pub fn store(&self) -> &Arc<dyn MemoryStore> {
+        &self.store
+    }
+
+    /// The text embedded for a value: the value itself when it is a string,
+    /// otherwise its `text` or `content` field, otherwise the serialized
+    /// JSON.
+    pub fn text_of(value: &Value) -> String {
+        value
+            .as_str()
+            .or_else(|| value.get("text").and_then(Value::as_str))
+            .or_else(|| value.get("content").and_then(Value::as_str))
+            .map(str::to_string)
+            .unwrap_or_else(|| value.to_string())
+    }
+
+    /// Stores `value` along with the embedding of its text.
+    pub async fn remember(&self, key: &str, value: &Value) -> Result<(), MemoryError> {
+        let embedding = self.embedder.embed(&Self::text_of(value)).await;
+        self.store.put_embedding(key, value, &embedding)
+    }
+
+    /// The `top_k` entries most similar to `query`, best first, among those
+    /// passing `filter`.
+    pub async fn search_similar(
+        &self,
+        query: &str,
+        top_k: usize,
+        filter: Option<MetadataFilter>,
+    ) -> Result<Vec<VectorMatch>, MemoryError> {
+        if top_k == 0 {
+            return Ok(Vec::new());
+        }
+        let embedding = self.embedder.embed(query).await;
+        let keep = |hit: &VectorMatch| {
+            filter
+                .as_ref()
+                .is_none_or(|filter| filter.matches(&hit.key, &hit.value))
+        };
+
+        // Widen indexed searches until the filter leaves enough hits or the
+        // index runs dry.
+        let mut fetch = top_k;
+        loop {
+            let hits = match self.store.search_embedding(&embedding, fetch) {
+                Err(MemoryError::Unsupported(_)) => break,
+                hits => hits?,
+            };
+            let exhausted = hits.len() < fetch;
+            let kept: Vec<VectorMatch> = hits.into_iter().filter(|hit| keep(hit)).collect();
+            if kept.len() >= top_k || exhausted {
+                return Ok(kept.into_iter().take(top_k).collect());
+            }
+            fetch *= 4;
+        }
+
+        let mut scored = Vec::new();
+        for (key, value) in self.store.entries()? {
+            let hit = VectorMatch {
+                key,
+                value,
+                score: 0.0,
+            };
+            if !keep(&hit) {
+                continue;
+            }
+            let candidate = self.embedder.embed(&Self::text_of(&hit.value)).await;
+            scored.push(VectorMatch {
+                score: cosine_similarity(&embedding, &candidate),
+                ..hit
+            });
+        }
+        scored.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.key.cmp(&b.key)));
+        scored.truncate(top_k);
+        Ok(scored)
+    }
+}
+
+impl std::fmt::Debug for SemanticMemory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SemanticMemory")
+            .field("store", &self.store)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{HnswConfig, InMemoryStore, VectorStore};
+    use serde_json::json;
+
+    /// Counts a few topic words, one dimension each.
+    struct Topics;
+
+    #[async_trait]
+    impl Embedder for Topics {
+        async fn embed(&self, text: &str) -> Vec<f32> {
+            ["flight", "cat", "budget"]
+                .iter()
+                .map(|word| text.matches(word).count() as f32 + 0.01)
+                .collect()
+        }
+    }
+
+    #[tokio::test]
+    async fn ranks_by_meaning_with_or_without_an_index() {
+        let notes = [
+            (
+                "trip",
+                json!({"text": "flight to Lisbon", "metadata": {"owner": "ana"}}),
+            ),
+            (
+                "pet",
+                json!({"text": "the cat naps", "metadata": {"owner": "ana"}}),
+            ),
+            (
+                "work",
+                json!({"text": "flight budget", "metadata": {"owner": "ben"}}),
+            ),
+        ];
+        let stores: [Arc<dyn MemoryStore>; 2] = [
+            Arc::new(InMemoryStore::new()),
+            Arc::new(VectorStore::local(HnswConfig::default())),
+        ];
+        for store in stores {
+            let memory = SemanticMemory::new(store, Arc::new(Topics));
+            for (key, value) in &notes {
+                memory.remember(key, value).await.unwrap();
+            }
+
+            let hits = memory.search_similar("flight", 2, None).await.unwrap();
+            let keys: Vec<&str> = hits.iter().map(|hit| hit.key.as_str()).collect();
+            assert_eq!(keys, vec!["trip", "work"], "{:?}", memory.store());
+            assert!(hits[0].score > hits[1].score);
+
+            let filter = MetadataFilter::new().with_eq("owner", "ben");
+            let hits = memory.search_similar("cat", 3, Some(filter)).await.unwrap();
+            assert_eq!(hits.len(), 1);
+            assert_eq!(hits[0].key, "work");
+        }
+    }
+}
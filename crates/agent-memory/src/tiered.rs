@@ -0,0 +1,397 @@
+//! [`TieredMemory`]: working, episodic and long-term stores behind one
+//! [`MemoryStore`].
+
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{InMemoryStore, MemoryError, MemoryStore};
+
+/// Where a [`TieredMemory`] entry lives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MemoryTier {
+    /// Scratch state for the task at hand.
+    Working,
+    /// What happened: turns, observations, tool results.
+    Episodic,
+    /// Durable facts and preferences.
+    LongTerm,
+}
+
+impl MemoryTier {
+    /// Lookup order, and the tiebreak between equally ranked hits.
+    pub const ALL: [MemoryTier; 3] = [
+        MemoryTier::Working,
+        MemoryTier::Episodic,
+        MemoryTier::LongTerm,
+    ];
+}
+
+/// Routing, promotion and ranking rules of a [`TieredMemory`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TierPolicy {
+    /// Tier per value `category` field.
+    pub routes: HashMap<String, MemoryTier>,
+    /// Tier of values with no routed category.
+    pub default_tier: MemoryTier,
+    /// Reads after which an episodic entry moves to long-term storage;
+    /// `0` never promotes.
+    pub promote_after: u32,
+    /// Age at which an entry's recency score halves.
+    pub recency_half_life: Duration,
+    pub recency_weight: f32,
+    /// Weight of a value's `importance` field, from 0 to 1 (0.5 when
+    /// absent).
+    pub importance_weight: f32,
+}
+
+impl Default for TierPolicy {
+    fn default() -> Self {
+        Self {
+            routes: HashMap::new(),
+            default_tier: MemoryTier::Episodic,
+            promote_after: 3,
+            recency_half_life: Duration::from_secs(3600),
+            recency_weight: 0.5,
+            importance_weight: 0.5,
+        }
+    }
+}
+
+impl TierPolicy {
+    pub fn with_route(mut self, category: impl Into<String>, tier: MemoryTier) -> Self {
+        self.routes.insert(category.into(), tier);
+        self
+    }
+
+    pub fn with_promote_after(mut self, reads: u32) -> Self {
+        self.promote_after = reads;
+        self
+    }
+
+    fn tier_for(&self, value: &Value) -> MemoryTier {
+        value
+            .get("category")
+            .and_then(Value::as_str)
+            .and_then(|category| self.routes.get(category))
+            .copied()
+            .unwrap_or(self.default_tier)
+    }
+}
+
+/// One hit of [`TieredMemory::search_ranked`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TieredMatch {
+    pub key: String,
+    pub value: Value,
+    pub tier: MemoryTier,
+    pub score: f32,
+}
+
+#[derive(Debug)]
+struct Access {
+    reads: u32,
+    touched: Instant,
+}
+
+/// Three stores acting as one: writes go to the tier their `category`
+/// routes to, episodic entries read often enough are promoted to long-term
+/// storage, and searches merge all tiers ranked by recency and importance.
+///
+/// A key lives in one tier at a time. Read counts and recency are tracked in
+/// process, so they restart with it.
+pub struct TieredMemory {
+    working: Arc<dyn MemoryStore>,
+    episodic: Arc<dyn MemoryStore>,
+    long_term: Arc<dyn MemoryStore>,
+    policy: TierPolicy,
+    access: Mutex<HashMap<String, Access>>,
+}
+
+impl Default for TieredMemory {
+    fn default() -> Self {
+        Self::new(
+            Arc::new(InMemoryStore::new()),
+            Arc::new(InMemoryStore::new()),
+            Arc::new(InMemoryStore::new()),
+        )
+    }
+}
+
+impl TieredMemory {
+    pub fn new(
+        working: Arc<dyn MemoryStore>,
+        episodic: Arc<dyn MemoryStore>,
+        long_term: Arc<dyn MemoryStore>,
+    ) -> Self {
+        Self {
+            working,
+            episodic,
+            long_term,
+            policy: TierPolicy::default(),
+            access: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn with_policy(mut self, policy: TierPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn tier(&self, tier: MemoryTier) -> &Arc<dyn MemoryStore> {
+        match tier {
+            MemoryTier::Working => &self.working,
+            MemoryTier::Episodic => &self.episodic,
+            MemoryTier::LongTerm => &self.long_term,
+        }
+    }
+
+    /// Writes `value` to `tier` whatever its category, moving `key` out of
+    /// any other tier.
+    pub fn put_in(&self, tier: MemoryTier, key: &str, value: &Value) -> Result<(), MemoryError> {
+        self.tier(tier).put(key, value)?;
+        for other in MemoryTier::ALL.into_iter().filter(|other| *other != tier) {
+            self.tier(other).delete(key)?;
+        }
+        self.access()?.insert(
+            key.to_string(),
+            Access {
+                reads: 0,
+                touched: Instant::now(),
+            },
+        );
+        Ok(())
+    }
+
+    /// The tier holding `key`, if any.
+    pub fn locate(&self, key: &str) -> Result<Option<(MemoryTier, Value)>, MemoryError> {
+        for tier in MemoryTier::ALL {
+            if let Some(value) = self.tier(tier).get(key)? {
+                return Ok(Some((tier, value)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Entries of every tier whose key or value contains `query`, best
+    /// first by recency and importance.
+    pub fn search_ranked(
+        &self,
+        query: &str,
+        top_k: usize,
+    ) -> Result<Vec<TieredMatch>, MemoryError> {
+        let access = self.access()?;
+        let now = Instant::now();
+        let half_life = self
+            .policy
+            .recency_half_life
+            .as_secs_f32()
+            .max(f32::EPSILON);
+        let mut hits = Vec::new();
+        for tier in MemoryTier::ALL {
+            for (key, value) in self.tier(tier).entries()? {
+                if !key.contains(query) && !value.to_string().contains(query) {
+                    continue;
+                }
+                let recency = access.get(&key).map_or(0.0, |access| {
+                    0.5f32.powf(now.duration_since(access.touched).as_secs_f32() / half_life)
+                });
+                let importance = value
+                    .get("importance")
+                    .and_then(Value::as_f64)
+                    .map_or(0.5, |importance| importance.clamp(0.0, 1.0) as f32);
+                hits.push(TieredMatch {
+                    key,
+                    value,
+                    tier,
+                    score: self.policy.recency_weight * recency
+                        + self.policy.importance_weight * importance,
+                });
+            }
+        }
+        // Stable, so ties keep tier order.
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+        hits.truncate(top_k);
+        Ok(hits)
+    }
+
+    fn access(&self) -> Result<std::sync::MutexGuard<'_, HashMap<String, Access>>, MemoryError> {
+        self.access
+            .lock()
+            .map_err(|e| MemoryError::Backend(e.to_string()))
+    }
+
+    /// Counts a read of an episodic entry and promotes it once it is due.
+    fn record_read(&self, key: &str, value: &Value) -> Result<(), MemoryError> {
+        let reads = {
+            let mut access = self.access()?;
+            let entry = access.entry(key.to_string()).or_insert(Access {
+                reads: 0,
+                touched: Instant::now(),
+            });
+            entry.reads += 1;
+            entry.touched = Instant::now();
+            entry.reads
+        };
+        if self.policy.promote_after > 0 && reads >= self.policy.promote_after {
+            self.put_in(MemoryTier::LongTerm, key, value)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for TieredMemory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TieredMemory")
+            .field("working", &self.working)
+            .field("episodic", &self.episodic)
+            .field("long_term", &self.long_term)
+            .field("policy", &self.policy)
+            .finish()
+    }
+}
+
+impl MemoryStore for TieredMemory {
+    fn put(&self, key: &str, value: &Value) -> Result<(), MemoryError> {
+        self.put_in(self.policy.tier_for(value), key, value)
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Value>, MemoryError> {
+        let Some((tier, value)) = self.locate(key)? else {
+            return Ok(None);
+        };
+        match tier {
+            MemoryTier::Episodic => self.record_read(key, &value)?,
+            _ => {
+                if let Some(access) = self.access()?.get_mut(key) {
+                    access.touched = Instant::now();
+                }
+            }
+        }
+        Ok(Some(value))
+    }
+
+    fn search(&self, query: &str) -> Result<Vec<Value>, MemoryError> {
+        Ok(self
+            .search_ranked(query, usize::MAX)?
+            .into_iter()
+            .map(|hit| hit.value)
+            .collect())
+    }
+
+    fn entries(&self) -> Result<Vec<(String, Value)>, MemoryError> {
+        let mut entries = Vec::new();
+        for tier in MemoryTier::ALL {
+            entries.extend(self.tier(tier).entries()?);
+        }
+        Ok(entries)
+    }
+
+    fn delete(&self, key: &str) -> Result<bool, MemoryError> {
+        let mut removed = false;
+        for tier in MemoryTier::ALL {
+            removed |= self.tier(tier).delete(key)?;
+        }
+        self.access()?.remove(key);
+        Ok(removed)
+    }
+
+    fn list_keys(&self, prefix: &str) -> Result<Vec<String>, MemoryError> {
+        let mut keys = BTreeSet::new();
+        for tier in MemoryTier::ALL {
+            keys.extend(self.tier(tier).list_keys(prefix)?);
+        }
+        Ok(keys.into_iter().collect())
+    }
+
+    fn clear(&self) -> Result<(), MemoryError> {
+        for tier in MemoryTier::ALL {
+            self.tier(tier).clear()?;
+        }
+        self.access()?.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn routes_promotes_and_ranks_across_tiers() {
+        let memory = TieredMemory::default().with_policy(
+            TierPolicy::default()
+                .with_route("scratch", MemoryTier::Working)
+                .with_route("preference", MemoryTier::LongTerm)
+                .with_promote_after(2),
+        );
+        memory
+            .put(
+                "plan",
+                &json!({"category": "scratch", "text": "tea then code"}),
+            )
+            .unwrap();
+        memory
+            .put(
+                "likes",
+                &json!({"category": "preference", "text": "likes tea", "importance": 0.9}),
+            )
+            .unwrap();
+        memory
+            .put(
+                "turn:1",
+                &json!({"text": "asked about tea", "importance": 0.1}),
+            )
+            .unwrap();
+
+        assert_eq!(
+            memory.locate("plan").unwrap().unwrap().0,
+            MemoryTier::Working
+        );
+        assert_eq!(
+            memory.locate("likes").unwrap().unwrap().0,
+            MemoryTier::LongTerm
+        );
+        assert_eq!(
+            memory.locate("turn:1").unwrap().unwrap().0,
+            MemoryTier::Episodic
+        );
+
+        let ranked: Vec<String> = memory
+            .search_ranked("tea", 3)
+            .unwrap()
+            .into_iter()
+            .map(|hit| hit.key)
+            .collect();
+        assert_eq!(ranked, vec!["likes", "plan", "turn:1"]);
+
+        memory.get("turn:1").unwrap();
+        assert_eq!(
+            memory.locate("turn:1").unwrap().unwrap().0,
+            MemoryTier::Episodic
+        );
+        memory.get("turn:1").unwrap();
+        assert_eq!(
+            memory.locate("turn:1").unwrap().unwrap().0,
+            MemoryTier::LongTerm
+        );
+        assert!(memory
+            .tier(MemoryTier::Episodic)
+            .entries()
+            .unwrap()
+            .is_empty());
+
+        assert_eq!(
+            memory.list_keys("").unwrap(),
+            vec!["likes", "plan", "turn:1"]
+        );
+        assert!(memory.delete("plan").unwrap());
+        memory.clear().unwrap();
+        assert!(memory.entries().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,385 @@
+//! Azure OpenAI backend for [`AzureOpenAIModel`](crate::AzureOpenAIModel),
+//! enabled by the `azure` cargo feature.
+//!
+//! Requests go to `{endpoint}/openai/deployments/{deployment}/chat/completions`
+//! and authenticate with either a resource API key or a Microsoft Entra ID
+//! bearer token from a [`TokenCredential`]. Content-filter verdicts are
+//! copied into [`ModelMetadata::annotations`] under `content_filter`.
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use agent_telemetry::Secret;
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::openai::{chat_body, send_completion, send_stream, streaming_body, OpenAIError};
+use crate::{
+    ChatMessage, ChatOptions, EventStream, GenerationOptions, HttpClientConfig, HttpClientError,
+    LLMResponse, ModelMetadata,
+};
+
+/// Supplies Entra ID access tokens for the Cognitive Services scope.
+#[async_trait]
+pub trait TokenCredential: Send + Sync {
+    async fn token(&self) -> Result<Secret<String>, OpenAIError>;
+}
+
+/// A pre-acquired token, used as-is until it expires.
+#[async_trait]
+impl TokenCredential for Secret<String> {
+    async fn token(&self) -> Result<Secret<String>, OpenAIError> {
+        Ok(self.clone())
+    }
+}
+
+#[derive(Clone)]
+pub enum AzureAuth {
+    /// Sent as the `api-key` header.
+    ApiKey(Secret<String>),
+    /// Sent as `Authorization: Bearer`, fetched before every request.
+    EntraId(Arc<dyn TokenCredential>),
+}
+
+impl fmt::Debug for AzureAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ApiKey(key) => f.debug_tuple("ApiKey").field(key).finish(),
+            Self::EntraId(_) => f.write_str("EntraId"),
+        }
+    }
+}
+
+/// Entra ID client-credentials flow for a service principal. Tokens are cached
+/// and refreshed shortly before they expire.
+pub struct ClientSecretCredential {
+    tenant_id: String,
+    client_id: String,
+    client_secret: Secret<String>,
+    authority_host: String,
+    scope: String,
+    client: reqwest::Client,
+    cached: Mutex<Option<(Secret<String>, Instant)>>,
+}
+
+impl ClientSecretCredential {
+    pub const DEFAULT_AUTHORITY_HOST: &'static str = "https://login.microsoftonline.com";
+    pub const COGNITIVE_SERVICES_SCOPE: &'static str =
+        "https://cognitiveservices.azure.com/.default";
+    /// Tokens this close to expiry are treated as expired.
+    const REFRESH_MARGIN: Duration = Duration::from_secs(300);
+
+    pub fn new(
+        tenant_id: impl Into<String>,
+        client_id: impl Into<String>,
+        client_secret: impl Into<Secret<String>>,
+    ) -> Self {
+        Self {
+            tenant_id: tenant_id.into(),
+            client_id: client_id.into(),
+            client_secret: client_secret.into(),
+            authority_host: Self::DEFAULT_AUTHORITY_HOST.into(),
+            scope: Self::COGNITIVE_SERVICES_SCOPE.into(),
+            client: reqwest::Client::new(),
+            cached: Mutex::new(None),
+        }
+    }
+
+    /// Targets a sovereign cloud or a test server instead of the public cloud.
+    pub fn with_authority_host(mut self, host: impl Into<String>) -> Self {
+        self.authority_host = host.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    pub fn with_scope(mut self, scope: impl Into<String>) -> Self {
+        self.scope = scope.into();
+        self
+    }
+}
+
+impl fmt::Debug for ClientSecretCredential {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClientSecretCredential")
+            .field("tenant_id", &self.tenant_id)
+            .field("client_id", &self.client_id)
+            .field("authority_host", &self.authority_host)
+            .field("scope", &self.scope)
+            .finish()
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: u64,
+}
+
+#[async_trait]
+impl TokenCredential for ClientSecretCredential {
+    async fn token(&self) -> Result<Secret<String>, OpenAIError> {
+        if let Some((token, expires_at)) = &*self.cached.lock().unwrap() {
+            if Instant::now() + Self::REFRESH_MARGIN < *expires_at {
+                return Ok(token.clone());
+            }
+        }
+
+        let response = self
+            .client
+            .post(format!(
+                "{}/{}/oauth2/v2.0/token",
+                self.authority_host, self.tenant_id
+            ))
+            .form(&[
+                ("grant_type", "client_credentials"),
+                ("client_id", self.client_id.as_str()),
+                ("client_secret", self.client_secret.expose().as_str()),
+                ("scope", self.scope.as_str()),
+            ])
+            .send()
+            .await?;
+        let status = response.status();
+        if !status.is_success() {
+            let body: Value = response.json().await.unwrap_or_default();
+            let detail = body["error_description"]
+                .as_str()
+                .or(body["error"].as_str())
+                .unwrap_or("no details");
+            return Err(OpenAIError::Auth(format!(
+                "token request returned {status}: {detail}"
+            )));
+        }
+        let issued: TokenResponse = response
+            .json()
+            .await
+            .map_err(|e| OpenAIError::Auth(format!("malformed token response: {e}")))?;
+
+        let token = Secret::new(issued.access_token);
+        *self.cached.lock().unwrap() = Some((
+            token.clone(),
+            Instant::now() + Duration::from_secs(issued.expires_in),
+        ));
+        Ok(token)
+    }
+}
+
+/// Resource endpoint, API version and credentials for Azure OpenAI requests.
+#[derive(Debug, Clone)]
+pub struct AzureOpenAIConnection {
+    /// Resource URL such as `https://my-resource.openai.azure.com`.
+    pub endpoint: String,
+    pub api_version: String,
+    pub auth: AzureAuth,
+    pub client: reqwest::Client,
+}
+
+impl AzureOpenAIConnection {
+    pub const DEFAULT_API_VERSION: &'static str = "2024-10-21";
+
+    pub fn new(endpoint: impl Into<String>, auth: AzureAuth) -> Self {
+        Self {
+            endpoint: endpoint.into().trim_end_matches('/').to_string(),
+            api_version: Self::DEFAULT_API_VERSION.into(),
+            auth,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    pub fn with_api_key(endpoint: impl Into<String>, api_key: impl Into<Secret<String>>) -> Self {
+        Self::new(endpoint, AzureAuth::ApiKey(api_key.into()))
+    }
+
+    pub fn with_credential(
+        endpoint: impl Into<String>,
+        credential: impl TokenCredential + 'static,
+    ) -> Self {
+        Self::new(endpoint, AzureAuth::EntraId(Arc::new(credential)))
+    }
+
+    pub fn with_api_version(mut self, api_version: impl Into<String>) -> Self {
+        self.api_version = api_version.into();
+        self
+    }
+
+    pub fn with_client_config(
+        mut self,
+        config: &HttpClientConfig,
+    ) -> Result<Self, HttpClientError> {
+        self.client = config.build_client()?;
+        Ok(self)
+    }
+
+    async fn request(
+        &self,
+        deployment: &str,
+        body: &Value,
+    ) -> Result<reqwest::RequestBuilder, OpenAIError> {
+        let request = self
+            .client
+            .post(format!(
+                "{}/openai/deployments/{deployment}/chat/completions",
+                self.endpoint
+            ))
+            .query(&[("api-version", &self.api_version)])
+            .json(body);
+        Ok(match &self.auth {
+            AzureAuth::ApiKey(key) => request.header("api-key", key.expose()),
+            AzureAuth::EntraId(credential) => {
+                request.bearer_auth(credential.token().await?.expose())
+            }
+        })
+    }
+
+    pub(crate) async fn complete(
+        &self,
+        deployment: &str,
+        messages: &[ChatMessage],
+        options: &ChatOptions,
+        metadata: ModelMetadata,
+    ) -> Result<LLMResponse, OpenAIError> {
+        let request = self
+            .request(deployment, &chat_body(messages, options))
+            .await?;
+        send_completion(request, metadata).await
+    }
+
+    pub(crate) async fn stream(
+        &self,
+        deployment: &str,
+        messages: &[ChatMessage],
+        generation: &GenerationOptions,
+    ) -> Result<EventStream, OpenAIError> {
+        let body = streaming_body(chat_body(
+            messages,
+            &ChatOptions::default().with_generation(generation.clone()),
+        ));
+        let request = self.request(deployment, &body).await?;
+        send_stream(request).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AzureOpenAIModel, FinishReason, LLMModel};
+    use serde_json::json;
+    use wiremock::matchers::{body_string_contains, header, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const DEPLOYMENT_PATH: &str = "/openai/deployments/gpt4o/chat/completions";
+
+    fn completion() -> Value {
+        json!({
+            "model": "gpt-4o",
+            "prompt_filter_results": [{
+                "prompt_index": 0,
+                "content_filter_results": {"hate": {"filtered": false, "severity": "safe"}}
+            }],
+            "choices": [{
+                "message": {"role": "assistant", "content": "Hi there"},
+                "finish_reason": "stop",
+                "content_filter_results": {"violence": {"filtered": false, "severity": "low"}}
+            }],
+            "usage": {"prompt_tokens": 3, "completion_tokens": 2}
+        })
+    }
+
+    #[tokio::test]
+    async fn api_key_requests_map_content_filter_annotations() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path(DEPLOYMENT_PATH))
+            .and(query_param("api-version", "2024-06-01"))
+            .and(header("api-key", "azure-key"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(completion()))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let model = AzureOpenAIModel::new("gpt4o").with_connection(
+            AzureOpenAIConnection::with_api_key(server.uri(), "azure-key".to_string())
+                .with_api_version("2024-06-01"),
+        );
+        let response = model
+            .generate("hello", &GenerationOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(response.content, "Hi there");
+        assert_eq!(response.finish_reason, Some(FinishReason::Stop));
+        assert_eq!(response.metadata.provider, "azure_openai");
+        let filter = &response.metadata.annotations["content_filter"];
+        assert_eq!(
+            filter["prompt"][0]["content_filter_results"]["hate"]["severity"],
+            "safe"
+        );
+        assert_eq!(filter["completion"]["violence"]["severity"], "low");
+    }
+
+    #[tokio::test]
+    async fn entra_id_tokens_are_fetched_once_and_cached() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/tenant-1/oauth2/v2.0/token"))
+            .and(body_string_contains("grant_type=client_credentials"))
+            .and(body_string_contains("client_id=app-1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "token_type": "Bearer",
+                "expires_in": 3600,
+                "access_token": "entra-token"
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path(DEPLOYMENT_PATH))
+            .and(header("authorization", "Bearer entra-token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(completion()))
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let credential = ClientSecretCredential::new("tenant-1", "app-1", "s3cret".to_string())
+            .with_authority_host(server.uri());
+        let model = AzureOpenAIModel::new("gpt4o").with_connection(
+            AzureOpenAIConnection::with_credential(server.uri(), credential),
+        );
+        for _ in 0..2 {
+            assert_eq!(
+                model
+                    .generate("hello", &GenerationOptions::default())
+                    .await
+                    .unwrap()
+                    .content,
+                "Hi there"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn token_failures_surface_as_auth_errors() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/tenant-1/oauth2/v2.0/token"))
+            .respond_with(ResponseTemplate::new(401).set_body_json(json!({
+                "error": "invalid_client",
+                "error_description": "AADSTS7000215: Invalid client secret provided."
+            })))
+            .mount(&server)
+            .await;
+
+        let credential = ClientSecretCredential::new("tenant-1", "app-1", "wrong".to_string())
+            .with_authority_host(server.uri());
+        let model = AzureOpenAIModel::new("gpt4o").with_connection(
+            AzureOpenAIConnection::with_credential(server.uri(), credential),
+        );
+        let err = model
+            .complete(&[ChatMessage::user("hi")], &ChatOptions::default())
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&err, OpenAIError::Auth(message) if message.contains("AADSTS7000215")),
+            "{err}"
+        );
+    }
+}
@@ -0,0 +1,429 @@
+//! Response caching for repeated prompts.
+//!
+//! [`CachedModel`] keys each request by a SHA-256 of its prompt (or
+//! messages) and options, and keeps successful responses in a pluggable
+//! [`ResponseCache`]: the in-process [`LruResponseCache`] or any
+//! [`MemoryStore`] through [`MemoryStoreCache`].
+
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use agent_memory::MemoryStore;
+use agent_telemetry::Telemetry;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    ChatMessage, ChatOptions, EventStream, GenerationOptions, LLMModel, LLMResponse, ModelError,
+    Tokenizer,
+};
+
+/// A stored response and when it was stored, in milliseconds since the Unix
+/// epoch so entries in shared stores age correctly across processes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedResponse {
+    pub response: LLMResponse,
+    pub stored_at_ms: u64,
+}
+
+/// Storage behind a [`CachedModel`]. Expiry is decided by the model, so
+/// backends only need to store and return entries.
+pub trait ResponseCache: Send + Sync {
+    fn get(&self, key: &str) -> Option<CachedResponse>;
+    fn put(&self, key: &str, entry: CachedResponse);
+}
+
+/// In-process cache that evicts the least recently used entry once it holds
+/// `max_entries`.
+#[derive(Debug)]
+pub struct LruResponseCache {
+    max_entries: usize,
+    inner: Mutex<LruState>,
+}
+
+#[derive(Debug, Default)]
+struct LruState {
+    entries: BTreeMap<String, CachedResponse>,
+    order: VecDeque<String>, // least recently used first
+}
+
+impl LruResponseCache {
+    pub fn new(max_entries: usize) -> Self {
+        Self {
+            max_entries: max_entries.max(1),
+            inner: Mutex::new(LruState::default()),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl ResponseCache for LruResponseCache {
+    fn get(&self, key: &str) -> Option<CachedResponse> {
+        let mut state = self.inner.lock().unwrap();
+        let entry = state.entries.get(key)?.clone();
+        state.order.retain(|k| k != key);
+        state.order.push_back(key.to_string());
+        Some(entry)
+    }
+
+    fn put(&self, key: &str, entry: CachedResponse) {
+        let mut state = self.inner.lock().unwrap();
+        if state.entries.insert(key.to_string(), entry).is_some() {
+            state.order.retain(|k| k != key);
+        }
+        state.order.push_back(key.to_string());
+        while state.order.len() > self.max_entries {
+            if let Some(oldest) = state.order.pop_front() {
+                state.entries.remove(&oldest);
+            }
+        }
+    }
+}
+
+/// Keeps entries in a [`MemoryStore`] under `prefix`, so a persistent or
+/// shared store can serve several processes.
+#[derive(Debug, Clone)]
+pub struct MemoryStoreCache {
+    pub store: Arc<dyn MemoryStore>,
+    pub prefix: String,
+}
+
+impl MemoryStoreCache {
+    pub const DEFAULT_PREFIX: &'static str = "model-cache:";
+
+    pub fn new(store: Arc<dyn MemoryStore>) -> Self {
+        Self {
+            store,
+            prefix: Self::DEFAULT_PREFIX.into(),
+        }
+    }
+
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+}
+
+impl ResponseCache for MemoryStoreCache {
+    fn get(&self, key: &str) -> Option<CachedResponse> {
+        match self.store.get(&format!("{}{key}", self.prefix)) {
+            Ok(value) => value.and_then(|value| serde_json::from_value(value).ok()),
+            Err(error) => {
+                tracing::warn!(%error, "model cache lookup failed");
+                None
+            }
+        }
+    }
+
+    fn put(&self, key: &str, entry: CachedResponse) {
+        let Ok(value) = serde_json::to_value(&entry) else {
+            return;
+        };
+        if let Err(error) = self.store.put(&format!("{}{key}", self.prefix), &value) {
+            tracing::warn!(%error, "model cache write failed");
+        }
+    }
+}
+
+/// Serves repeated `generate` and `chat` requests from a [`ResponseCache`].
+///
+/// Only successful responses are stored; hits carry a `cache: "hit"`
+/// annotation. Streams always go to the wrapped model. Hit and miss counts
+/// are kept on the model and, with [`CachedModel::with_telemetry`], exported
+/// as `model_cache_hits`/`model_cache_misses` labelled by `namespace`.
+pub struct CachedModel<M: LLMModel> {
+    pub model: M,
+    pub cache: Arc<dyn ResponseCache>,
+    /// Entries older than this are treated as misses; `None` keeps them
+    /// until the backend drops them.
+    pub ttl: Option<Duration>,
+    /// Mixed into every key. Requests are keyed by content alone, so models
+    /// that share a backend need distinct namespaces unless they would give
+    /// the same answers.
+    pub namespace: String,
+    pub telemetry: Option<Arc<Telemetry>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl<M: LLMModel> CachedModel<M> {
+    /// Caches `model`'s responses in `cache` under `namespace`, e.g. the
+    /// provider and model name.
+    pub fn new(model: M, cache: Arc<dyn ResponseCache>, namespace: impl Into<String>) -> Self {
+        Self {
+            model,
+            cache,
+            ttl: None,
+            namespace: namespace.into(),
+            telemetry: None,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    pub fn with_telemetry(mut self, telemetry: Arc<Telemetry>) -> Self {
+        self.telemetry = Some(telemetry);
+        self
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    fn key(&self, request: Value) -> String {
+        let body = json!({"namespace": self.namespace, "request": request});
+        Sha256::digest(body.to_string().as_bytes())
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect()
+    }
+
+    fn lookup(&self, key: &str) -> Option<LLMResponse> {
+        let now = now_ms();
+        let fresh = self.cache.get(key).filter(|entry| match self.ttl {
+            Some(ttl) => now.saturating_sub(entry.stored_at_ms) <= ttl.as_millis() as u64,
+            None => true,
+        });
+        let hit = fresh.is_some();
+        if hit {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+        }
+        if let Some(telemetry) = &self.telemetry {
+            telemetry.record_cache_lookup(&self.namespace, hit);
+        }
+        fresh.map(|entry| {
+            let mut response = entry.response;
+            response
+                .metadata
+                .annotations
+                .insert("cache".into(), json!("hit"));
+            response
+        })
+    }
+
+    fn store(&self, key: &str, response: &LLMResponse) {
+        self.cache.put(
+            key,
+            CachedResponse {
+                response: response.clone(),
+                stored_at_ms: now_ms(),
+            },
+        );
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+#[async_trait]
+impl<M: LLMModel> LLMModel for CachedModel<M> {
+    async fn generate(
+        &self,
+        prompt: &str,
+        options: &GenerationOptions,
+    ) -> Result<LLMResponse, ModelError> {
+        let key = self.key(json!({"prompt": prompt, "options": options}));
+        if let Some(response) = self.lookup(&key) {
+            return Ok(response);
+        }
+        let response = self.model.generate(prompt, options).await?;
+        self.store(&key, &response);
+        Ok(response)
+    }
+
+    async fn stream(
+        &self,
+        prompt: &str,
+        options: &GenerationOptions,
+    ) -> Result<EventStream, ModelError> {
+        self.model.stream(prompt, options).await
+    }
+
+    fn supports_tools(&self) -> bool {
+        self.model.supports_tools()
+    }
+
+    async fn chat(
+        &self,
+        messages: &[ChatMessage],
+        options: &ChatOptions,
+    ) -> Result<LLMResponse, ModelError> {
+        let key = self.key(json!({"messages": messages, "options": options}));
+        if let Some(response) = self.lookup(&key) {
+            return Ok(response);
+        }
+        let response = self.model.chat(messages, options).await?;
+        self.store(&key, &response);
+        Ok(response)
+    }
+
+    fn tokenizer(&self) -> Arc<dyn Tokenizer> {
+        self.model.tokenizer()
+    }
+}
+
+impl<M: LLMModel> std::fmt::Debug for CachedModel<M> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CachedModel")
+            .field("namespace", &self.namespace)
+            .field("ttl", &self.ttl)
+            .field("hits", &self.hits())
+            .field("misses", &self.misses())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StubModel;
+    use agent_memory::InMemoryStore;
+    use std::sync::atomic::AtomicUsize;
+
+    /// Echoes prompts and counts how often it was actually called.
+    #[derive(Default)]
+    struct Counting {
+        calls: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl LLMModel for Counting {
+        async fn generate(
+            &self,
+            prompt: &str,
+            options: &GenerationOptions,
+        ) -> Result<LLMResponse, ModelError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            StubModel.generate(prompt, options).await
+        }
+
+        async fn stream(
+            &self,
+            prompt: &str,
+            options: &GenerationOptions,
+        ) -> Result<EventStream, ModelError> {
+            StubModel.stream(prompt, options).await
+        }
+
+        fn supports_tools(&self) -> bool {
+            false
+        }
+    }
+
+    #[tokio::test]
+    async fn repeated_requests_hit_the_cache_and_are_counted() {
+        let telemetry = Arc::new(Telemetry::new());
+        let model = CachedModel::new(
+            Counting::default(),
+            Arc::new(LruResponseCache::new(8)),
+            "planner",
+        )
+        .with_telemetry(telemetry.clone());
+        let options = GenerationOptions::default();
+
+        let first = model.generate("plan a trip", &options).await.unwrap();
+        let second = model.generate("plan a trip", &options).await.unwrap();
+        assert_eq!(first.content, second.content);
+        assert!(!first.metadata.annotations.contains_key("cache"));
+        assert_eq!(second.metadata.annotations["cache"], "hit");
+
+        // Different options are a different request.
+        model
+            .generate("plan a trip", &options.clone().with_seed(1))
+            .await
+            .unwrap();
+        let messages = [ChatMessage::user("plan a trip")];
+        model
+            .chat(&messages, &ChatOptions::default())
+            .await
+            .unwrap();
+        model
+            .chat(&messages, &ChatOptions::default())
+            .await
+            .unwrap();
+
+        assert_eq!(model.model.calls.load(Ordering::SeqCst), 3);
+        assert_eq!((model.hits(), model.misses()), (2, 3));
+        let metrics = telemetry.export_metrics();
+        assert!(metrics.contains("model_cache_hits{namespace=\"planner\"} 2"));
+        assert!(metrics.contains("model_cache_misses{namespace=\"planner\"} 3"));
+    }
+
+    #[tokio::test]
+    async fn expired_entries_are_refetched() {
+        let cache = Arc::new(LruResponseCache::new(8));
+        let model = CachedModel::new(Counting::default(), cache.clone(), "echo")
+            .with_ttl(Duration::from_millis(20));
+        let options = GenerationOptions::default();
+        model.generate("hi", &options).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        model.generate("hi", &options).await.unwrap();
+        assert_eq!(model.model.calls.load(Ordering::SeqCst), 2);
+        assert_eq!(model.misses(), 2);
+    }
+
+    #[test]
+    fn lru_evicts_the_least_recently_used_entry() {
+        let cache = LruResponseCache::new(2);
+        let entry = || CachedResponse {
+            response: LLMResponse::default(),
+            stored_at_ms: 0,
+        };
+        cache.put("a", entry());
+        cache.put("b", entry());
+        assert!(cache.get("a").is_some());
+        cache.put("c", entry());
+        assert!(cache.get("b").is_none());
+        assert!(cache.get("a").is_some() && cache.get("c").is_some());
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn memory_store_backend_is_shared_within_a_namespace() {
+        let store: Arc<dyn MemoryStore> = Arc::new(InMemoryStore::new());
+        let cache = Arc::new(MemoryStoreCache::new(store.clone()));
+        let options = GenerationOptions::default();
+
+        let writer = CachedModel::new(Counting::default(), cache.clone(), "echo");
+        writer.generate("hello", &options).await.unwrap();
+        let reader = CachedModel::new(Counting::default(), cache.clone(), "echo");
+        let response = reader.generate("hello", &options).await.unwrap();
+        assert_eq!(response.content, "echo: hello");
+        assert_eq!(reader.model.calls.load(Ordering::SeqCst), 0);
+
+        // Another model on the same backend never sees these answers.
+        let other = CachedModel::new(Counting::default(), cache, "other");
+        other.generate("hello", &options).await.unwrap();
+        assert_eq!(other.model.calls.load(Ordering::SeqCst), 1);
+        let stored = store.entries().unwrap_or_default();
+        assert!(stored
+            .iter()
+            .all(|(key, _)| key.starts_with(MemoryStoreCache::DEFAULT_PREFIX)));
+    }
+}
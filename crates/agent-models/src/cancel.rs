@@ -0,0 +1,265 @@
+//! Deadlines and cancellation for streamed generations.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use tokio::time::{Instant, Sleep};
+use tokio_stream::Stream;
+use tokio_util::sync::{CancellationToken, WaitForCancellationFutureOwned};
+
+use crate::{EventStream, FinishReason, ModelError, StreamEvent};
+
+/// Bounds on one [`LLMModel::stream_with`](crate::LLMModel::stream_with)
+/// call.
+#[derive(Debug, Clone, Default)]
+pub struct StreamControl {
+    /// Time allowed for the whole call, from opening the stream to its last
+    /// event.
+    pub timeout: Option<Duration>,
+    /// Ends the stream early when cancelled, e.g. because the run was.
+    pub cancellation: Option<CancellationToken>,
+}
+
+impl StreamControl {
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+}
+
+/// Opens a stream with `opening` under `control`. Whichever of the stream,
+/// the deadline and the cancellation finishes first wins; the losing
+/// provider stream is dropped, closing its connection.
+pub(crate) async fn open_controlled<F>(
+    opening: F,
+    control: &StreamControl,
+) -> Result<EventStream, ModelError>
+where
+    F: Future<Output = Result<EventStream, ModelError>> + Send,
+{
+    let mut guard = Guard::new(control);
+    let events = tokio::select! {
+        biased;
+        reason = guard.interrupted() => return Ok(terminal(reason)),
+        events = opening => events?,
+    };
+    Ok(Box::pin(Controlled {
+        events: Some(events),
+        guard,
+    }))
+}
+
+fn terminal(reason: FinishReason) -> EventStream {
+    Box::pin(tokio_stream::once(StreamEvent::Done {
+        finish_reason: Some(reason),
+    }))
+}
+
+struct Guard {
+    cancelled: Option<Pin<Box<WaitForCancellationFutureOwned>>>,
+    deadline: Option<Pin<Box<Sleep>>>,
+}
+
+impl Guard {
+    fn new(control: &StreamControl) -> Self {
+        Self {
+            cancelled: control
+                .cancellation
+                .clone()
+                .map(|token| Box::pin(token.cancelled_owned())),
+            deadline: control
+                .timeout
+                .map(|timeout| Box::pin(tokio::time::sleep_until(Instant::now() + timeout))),
+        }
+    }
+
+    fn poll_interrupted(&mut self, cx: &mut Context<'_>) -> Poll<FinishReason> {
+        if let Some(cancelled) = &mut self.cancelled {
+            if cancelled.as_mut().poll(cx).is_ready() {
+                tracing::debug!("model stream cancelled");
+                return Poll::Ready(FinishReason::Cancelled);
+            }
+        }
+        if let Some(deadline) = &mut self.deadline {
+            if deadline.as_mut().poll(cx).is_ready() {
+                tracing::debug!("model stream timed out");
+                return Poll::Ready(FinishReason::TimedOut);
+            }
+        }
+        Poll::Pending
+    }
+
+    async fn interrupted(&mut self) -> FinishReason {
+        std::future::poll_fn(|cx| self.poll_interrupted(cx)).await
+    }
+}
+
+/// Passes events through until the provider's `Done`, or ends with a
+/// `Cancelled` or `TimedOut` one instead.
+struct Controlled {
+    // `None` once finished, so the provider stream is dropped straight away.
+    events: Option<EventStream>,
+    guard: Guard,
+}
+
+impl Stream for Controlled {
+    type Item = StreamEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<StreamEvent>> {
+        let this = &mut *self;
+        let Some(events) = &mut this.events else {
+            return Poll::Ready(None);
+        };
+        if let Poll::Ready(reason) = this.guard.poll_interrupted(cx) {
+            this.events = None;
+            return Poll::Ready(Some(StreamEvent::Done {
+                finish_reason: Some(reason),
+            }));
+        }
+        let event = std::task::ready!(events.as_mut().poll_next(cx));
+        if matches!(event, None | Some(StreamEvent::Done { .. })) {
+            this.events = None;
+        }
+        Poll::Ready(event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{GenerationOptions, LLMModel, LLMResponse, StubModel};
+    use async_trait::async_trait;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use tokio_stream::StreamExt;
+
+    /// Sends one delta and then stalls forever, flagging when its stream is
+    /// dropped.
+    #[derive(Default)]
+    struct Runaway {
+        open_delay: Duration,
+        dropped: Arc<AtomicBool>,
+    }
+
+    struct DropFlag(Arc<AtomicBool>);
+
+    impl Drop for DropFlag {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    #[async_trait]
+    impl LLMModel for Runaway {
+        async fn generate(
+            &self,
+            prompt: &str,
+            options: &GenerationOptions,
+        ) -> Result<LLMResponse, ModelError> {
+            StubModel.generate(prompt, options).await
+        }
+
+        async fn stream(
+            &self,
+            _prompt: &str,
+            _options: &GenerationOptions,
+        ) -> Result<EventStream, ModelError> {
+            tokio::time::sleep(self.open_delay).await;
+            let flag = DropFlag(self.dropped.clone());
+            let first = tokio_stream::once(StreamEvent::TextDelta {
+                text: "thinking".into(),
+            });
+            Ok(Box::pin(first.chain(tokio_stream::pending()).map(
+                move |event| {
+                    let _ = &flag;
+                    event
+                },
+            )))
+        }
+
+        fn supports_tools(&self) -> bool {
+            false
+        }
+    }
+
+    #[tokio::test]
+    async fn cancelling_ends_the_stream_and_drops_the_connection() {
+        let model = Runaway::default();
+        let token = CancellationToken::new();
+        let control = StreamControl::default().with_cancellation(token.clone());
+        let mut events = model
+            .stream_with("go", &GenerationOptions::default(), &control)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            events.next().await,
+            Some(StreamEvent::TextDelta {
+                text: "thinking".into()
+            })
+        );
+        token.cancel();
+        assert_eq!(
+            events.next().await,
+            Some(StreamEvent::Done {
+                finish_reason: Some(FinishReason::Cancelled)
+            })
+        );
+        assert!(model.dropped.load(Ordering::SeqCst));
+        assert_eq!(events.next().await, None);
+    }
+
+    #[tokio::test]
+    async fn deadlines_cover_opening_and_reading() {
+        let model = Runaway::default();
+        let control = StreamControl::default().with_timeout(Duration::from_millis(30));
+        let events: Vec<_> = model
+            .stream_with("go", &GenerationOptions::default(), &control)
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        assert_eq!(
+            events.last(),
+            Some(&StreamEvent::Done {
+                finish_reason: Some(FinishReason::TimedOut)
+            })
+        );
+        assert_eq!(events.len(), 2);
+
+        let slow = Runaway {
+            open_delay: Duration::from_secs(5),
+            ..Runaway::default()
+        };
+        let events: Vec<_> = slow
+            .stream_with("go", &GenerationOptions::default(), &control)
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        assert_eq!(
+            events,
+            vec![StreamEvent::Done {
+                finish_reason: Some(FinishReason::TimedOut)
+            }]
+        );
+
+        let events: Vec<_> = StubModel
+            .stream_with("hi", &GenerationOptions::default(), &control)
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        assert!(matches!(
+            events.last(),
+            Some(StreamEvent::Done { finish_reason }) if finish_reason != &Some(FinishReason::TimedOut)
+        ));
+    }
+}
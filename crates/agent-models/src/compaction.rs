@@ -0,0 +1,236 @@
+//! Summarizing old memory into condensed records.
+//!
+//! [`MemoryCompactor`] asks a model to summarize the oldest entries under a
+//! key prefix once there are too many, stores each summary with links back
+//! to the keys it replaced, and deletes the originals.
+
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use agent_memory::{MemoryError, MemoryStore, SemanticMemory};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use thiserror::Error;
+
+use crate::{GenerationOptions, LLMModel, ModelError};
+
+/// Marks summary records, which are never compacted again.
+pub const SUMMARY_KIND: &str = "memory_summary";
+
+#[derive(Debug, Error)]
+pub enum CompactionError {
+    #[error("summarization failed: {0}")]
+    Model(#[from] ModelError),
+    #[error("memory store failed: {0}")]
+    Memory(#[from] MemoryError),
+}
+
+/// When and how much a [`MemoryCompactor`] condenses.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CompactionPolicy {
+    /// Only entries whose keys start with this are compacted. Keys are
+    /// expected to sort oldest first, e.g. zero-padded turn numbers.
+    pub key_prefix: String,
+    /// Compaction starts once more entries than this are under the prefix.
+    pub max_entries: usize,
+    /// Newest entries always left as they are.
+    pub keep_recent: usize,
+    /// Entries condensed into each summary.
+    pub batch_size: usize,
+    /// Prepended to the last summarized key to name each summary.
+    pub summary_prefix: String,
+}
+
+impl Default for CompactionPolicy {
+    fn default() -> Self {
+        Self {
+            key_prefix: String::new(),
+            max_entries: 100,
+            keep_recent: 20,
+            batch_size: 20,
+            summary_prefix: "summary:".into(),
+        }
+    }
+}
+
+/// What one [`MemoryCompactor::compact`] pass did.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CompactionReport {
+    /// Keys of the summaries written.
+    pub summaries: Vec<String>,
+    /// Entries folded into them and deleted.
+    pub compacted: usize,
+}
+
+/// Keeps a store within budget by replacing old entries with model-written
+/// summaries.
+///
+/// Each summary is stored as `{"kind": "memory_summary", "text", "sources",
+/// "summarized_at"}`, where `sources` lists the keys it replaced. Originals
+/// are deleted only after their summary is stored, so the store must support
+/// [`MemoryStore::delete`].
+pub struct MemoryCompactor<M: LLMModel> {
+    pub model: M,
+    pub store: Arc<dyn MemoryStore>,
+    pub policy: CompactionPolicy,
+    pub options: GenerationOptions,
+}
+
+impl<M: LLMModel> MemoryCompactor<M> {
+    pub fn new(model: M, store: Arc<dyn MemoryStore>) -> Self {
+        Self {
+            model,
+            store,
+            policy: CompactionPolicy::default(),
+            options: GenerationOptions::default(),
+        }
+    }
+
+    pub fn with_policy(mut self, policy: CompactionPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn with_options(mut self, options: GenerationOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Summarizes the oldest entries if the prefix holds more than
+    /// `max_entries`, leaving the newest `keep_recent` untouched.
+    pub async fn compact(&self) -> Result<CompactionReport, CompactionError> {
+        let mut entries: Vec<(String, Value)> = self
+            .store
+            .entries()?
+            .into_iter()
+            .filter(|(key, value)| {
+                key.starts_with(&self.policy.key_prefix) && value["kind"] != SUMMARY_KIND
+            })
+            .collect();
+        let mut report = CompactionReport::default();
+        if entries.len() <= self.policy.max_entries {
+            return Ok(report);
+        }
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        entries.truncate(entries.len().saturating_sub(self.policy.keep_recent));
+
+        for batch in entries.chunks(self.policy.batch_size.max(1)) {
+            let summary = self.model.generate(&prompt(batch), &self.options).await?;
+            let sources: Vec<&str> = batch.iter().map(|(key, _)| key.as_str()).collect();
+            let key = format!(
+                "{}{}",
+                self.policy.summary_prefix,
+                sources[sources.len() - 1]
+            );
+            let record = json!({
+                "kind": SUMMARY_KIND,
+                "text": summary.content.trim(),
+                "sources": sources,
+                "summarized_at": SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs(),
+            });
+            self.store.put(&key, &record)?;
+            for source in &sources {
+                self.store.delete(source)?;
+            }
+            tracing::debug!(summary = %key, entries = sources.len(), "compacted memory");
+            report.compacted += sources.len();
+            report.summaries.push(key);
+        }
+        Ok(report)
+    }
+}
+
+impl<M: LLMModel + 'static> MemoryCompactor<M> {
+    /// Runs [`compact`](Self::compact) every `interval` until the returned
+    /// task is aborted; failures are logged and retried next time.
+    pub fn spawn(self: Arc<Self>, interval: Duration) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(interval);
+            ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticks.tick().await;
+                if let Err(error) = self.compact().await {
+                    tracing::warn!(%error, "memory compaction failed");
+                }
+            }
+        })
+    }
+}
+
+impl<M: LLMModel> std::fmt::Debug for MemoryCompactor<M> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MemoryCompactor")
+            .field("store", &self.store)
+            .field("policy", &self.policy)
+            .finish()
+    }
+}
+
+fn prompt(batch: &[(String, Value)]) -> String {
+    let mut prompt = String::from(
+        "Condense these memory entries into one short record. Keep facts, \
+         decisions, names, numbers and open tasks; drop pleasantries and \
+         repetition. Reply with the record only.\n",
+    );
+    for (key, value) in batch {
+        prompt.push_str(&format!("\n[{key}] {}", SemanticMemory::text_of(value)));
+    }
+    prompt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StubModel;
+    use agent_memory::InMemoryStore;
+
+    #[tokio::test]
+    async fn folds_old_turns_into_linked_summaries() {
+        let store = Arc::new(InMemoryStore::new());
+        for turn in 0..7 {
+            store
+                .put(
+                    &format!("turn:{turn:03}"),
+                    &json!({"text": format!("said {turn}")}),
+                )
+                .unwrap();
+        }
+        store.put("profile", &json!("likes tea")).unwrap();
+        let compactor =
+            MemoryCompactor::new(StubModel, store.clone()).with_policy(CompactionPolicy {
+                key_prefix: "turn:".into(),
+                max_entries: 5,
+                keep_recent: 2,
+                batch_size: 3,
+                ..CompactionPolicy::default()
+            });
+
+        let report = compactor.compact().await.unwrap();
+        assert_eq!(report.compacted, 5);
+        assert_eq!(
+            report.summaries,
+            vec!["summary:turn:002", "summary:turn:004"]
+        );
+
+        let summary = store.get("summary:turn:002").unwrap().unwrap();
+        assert_eq!(
+            summary["sources"],
+            json!(["turn:000", "turn:001", "turn:002"])
+        );
+        assert!(summary["text"]
+            .as_str()
+            .unwrap()
+            .contains("[turn:001] said 1"));
+        assert_eq!(store.get("turn:001").unwrap(), None);
+        assert!(store.get("turn:006").unwrap().is_some());
+        assert!(store.get("profile").unwrap().is_some());
+
+        assert_eq!(
+            compactor.compact().await.unwrap(),
+            CompactionReport::default()
+        );
+    }
+}
@@ -0,0 +1,291 @@
+//! Ordered failover across several [`LLMModel`]s.
+
+use std::future::Future;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{
+    ChatMessage, ChatOptions, EventStream, GenerationOptions, LLMModel, LLMResponse, ModelError,
+    Tokenizer,
+};
+
+/// Which failures hand a request on to the next model. Anything else, such
+/// as an auth error or a content-filter block, is returned as is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FallbackPolicy {
+    pub on_rate_limit: bool,
+    /// 5xx responses and connection failures.
+    pub on_server_error: bool,
+    pub on_timeout: bool,
+}
+
+impl Default for FallbackPolicy {
+    fn default() -> Self {
+        Self {
+            on_rate_limit: true,
+            on_server_error: true,
+            on_timeout: true,
+        }
+    }
+}
+
+impl FallbackPolicy {
+    pub fn with_rate_limit(mut self, enabled: bool) -> Self {
+        self.on_rate_limit = enabled;
+        self
+    }
+
+    pub fn with_server_error(mut self, enabled: bool) -> Self {
+        self.on_server_error = enabled;
+        self
+    }
+
+    pub fn with_timeout(mut self, enabled: bool) -> Self {
+        self.on_timeout = enabled;
+        self
+    }
+
+    pub fn should_fall_back(&self, error: &ModelError) -> bool {
+        match error {
+            ModelError::RateLimited { .. } => self.on_rate_limit,
+            ModelError::Api { status, .. } => self.on_server_error && *status >= 500,
+            ModelError::Transport(_) => self.on_server_error,
+            ModelError::Timeout => self.on_timeout,
+            ModelError::Auth(_)
+            | ModelError::ContentFilter(_)
+            | ModelError::InvalidResponse(_)
+            | ModelError::Cancelled => false,
+        }
+    }
+}
+
+/// Sends each request to the first model and, when it fails in a way the
+/// [`FallbackPolicy`] covers, to the next one in order.
+///
+/// Responses carry a `fallback` annotation in their metadata with the index
+/// of the model that `served_by` the request and the errors of any `skipped`
+/// before it.
+pub struct FallbackModel {
+    pub models: Vec<Box<dyn LLMModel>>,
+    pub policy: FallbackPolicy,
+}
+
+impl FallbackModel {
+    pub fn new(primary: impl LLMModel + 'static) -> Self {
+        Self {
+            models: vec![Box::new(primary)],
+            policy: FallbackPolicy::default(),
+        }
+    }
+
+    /// Appends `model` to the end of the chain.
+    pub fn with_fallback(mut self, model: impl LLMModel + 'static) -> Self {
+        self.models.push(Box::new(model));
+        self
+    }
+
+    pub fn with_policy(mut self, policy: FallbackPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Runs `call` against each model in turn, returning the first success
+    /// with its index and the errors that were skipped over.
+    async fn route<'a, T, F, Fut>(&'a self, call: F) -> Result<(T, usize, Vec<Value>), ModelError>
+    where
+        F: Fn(&'a dyn LLMModel) -> Fut,
+        Fut: Future<Output = Result<T, ModelError>>,
+    {
+        let mut skipped = Vec::new();
+        for (index, model) in self.models.iter().enumerate() {
+            match call(model.as_ref()).await {
+                Ok(value) => return Ok((value, index, skipped)),
+                Err(error)
+                    if index + 1 < self.models.len() && self.policy.should_fall_back(&error) =>
+                {
+                    tracing::warn!(%error, index, "model failed, falling back to the next one");
+                    skipped.push(json!({"index": index, "error": error.to_string()}));
+                }
+                Err(error) => return Err(error),
+            }
+        }
+        unreachable!("a fallback chain always holds at least one model")
+    }
+
+    fn annotate(mut response: LLMResponse, served_by: usize, skipped: Vec<Value>) -> LLMResponse {
+        response.metadata.annotations.insert(
+            "fallback".into(),
+            json!({"served_by": served_by, "skipped": skipped}),
+        );
+        response
+    }
+}
+
+#[async_trait]
+impl LLMModel for FallbackModel {
+    async fn generate(
+        &self,
+        prompt: &str,
+        options: &GenerationOptions,
+    ) -> Result<LLMResponse, ModelError> {
+        let (response, served_by, skipped) =
+            self.route(|model| model.generate(prompt, options)).await?;
+        Ok(Self::annotate(response, served_by, skipped))
+    }
+
+    /// Falls back only while opening the stream; a stream that fails midway
+    /// ends with a `Done` event instead.
+    async fn stream(
+        &self,
+        prompt: &str,
+        options: &GenerationOptions,
+    ) -> Result<EventStream, ModelError> {
+        let (events, _, _) = self.route(|model| model.stream(prompt, options)).await?;
+        Ok(events)
+    }
+
+    /// True only when every model in the chain supports tools, so a request
+    /// that needs them is never routed to one that cannot call them.
+    fn supports_tools(&self) -> bool {
+        self.models.iter().all(|model| model.supports_tools())
+    }
+
+    async fn chat(
+        &self,
+        messages: &[ChatMessage],
+        options: &ChatOptions,
+    ) -> Result<LLMResponse, ModelError> {
+        let (response, served_by, skipped) =
+            self.route(|model| model.chat(messages, options)).await?;
+        Ok(Self::annotate(response, served_by, skipped))
+    }
+
+    fn tokenizer(&self) -> Arc<dyn Tokenizer> {
+        self.models[0].tokenizer()
+    }
+}
+
+impl std::fmt::Debug for FallbackModel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FallbackModel")
+            .field("models", &self.models.len())
+            .field("policy", &self.policy)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ModelMetadata, StubModel};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Fails with `error` and counts how often it was asked.
+    struct Failing {
+        error: ModelError,
+        calls: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl LLMModel for Failing {
+        async fn generate(
+            &self,
+            _prompt: &str,
+            _options: &GenerationOptions,
+        ) -> Result<LLMResponse, ModelError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Err(self.error.clone())
+        }
+
+        async fn stream(
+            &self,
+            _prompt: &str,
+            _options: &GenerationOptions,
+        ) -> Result<EventStream, ModelError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Err(self.error.clone())
+        }
+
+        fn supports_tools(&self) -> bool {
+            true
+        }
+    }
+
+    fn failing(error: ModelError) -> (Failing, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let model = Failing {
+            error,
+            calls: calls.clone(),
+        };
+        (model, calls)
+    }
+
+    #[tokio::test]
+    async fn falls_through_covered_errors_and_records_the_serving_model() {
+        let (throttled, _) = failing(ModelError::RateLimited {
+            retry_after: None,
+            message: "slow down".into(),
+        });
+        let (overloaded, _) = failing(ModelError::Api {
+            status: 503,
+            message: "overloaded".into(),
+        });
+        let model = FallbackModel::new(throttled)
+            .with_fallback(overloaded)
+            .with_fallback(StubModel);
+
+        let response = model
+            .generate("hi", &GenerationOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(response.content, "echo: hi");
+        let ModelMetadata {
+            provider,
+            annotations,
+            ..
+        } = response.metadata;
+        assert_eq!(provider, "stub");
+        assert_eq!(annotations["fallback"]["served_by"], 2);
+        assert_eq!(
+            annotations["fallback"]["skipped"][1]["error"],
+            "provider returned 503: overloaded"
+        );
+
+        assert!(model
+            .stream("hi", &GenerationOptions::default())
+            .await
+            .is_ok());
+        assert!(!model.supports_tools());
+    }
+
+    #[tokio::test]
+    async fn returns_uncovered_errors_without_trying_the_next_model() {
+        let (denied, _) = failing(ModelError::Auth("bad key".into()));
+        let (backup, backup_calls) = failing(ModelError::Timeout);
+        let model = FallbackModel::new(denied).with_fallback(backup);
+        let err = model
+            .generate("hi", &GenerationOptions::default())
+            .await
+            .unwrap_err();
+        assert_eq!(err, ModelError::Auth("bad key".into()));
+        assert_eq!(backup_calls.load(Ordering::SeqCst), 0);
+
+        let (slow, _) = failing(ModelError::Timeout);
+        let (last, last_calls) = failing(ModelError::Timeout);
+        let model = FallbackModel::new(slow)
+            .with_fallback(last)
+            .with_policy(FallbackPolicy::default().with_timeout(false));
+        assert_eq!(
+            model
+                .chat(&[ChatMessage::user("hi")], &ChatOptions::default())
+                .await
+                .unwrap_err(),
+            ModelError::Timeout
+        );
+        assert_eq!(last_calls.load(Ordering::SeqCst), 0);
+    }
+}
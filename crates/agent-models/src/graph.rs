@@ -0,0 +1,388 @@
+//! Entity and relation memory extracted from step outputs.
+//!
+//! [`GraphMemory`] asks a model for the entities and relations in each
+//! [`StepOutcome`], stores them as nodes and edges in a [`MemoryStore`], and
+//! answers "what do we know about X" by walking the edges around X.
+
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::sync::Arc;
+
+use agent_core::StepOutcome;
+use agent_memory::{MemoryError, MemoryStore, SemanticMemory};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use thiserror::Error;
+
+use crate::{GenerationOptions, LLMModel, StructuredGeneration, StructuredOutputError};
+
+/// Marks stored entity records.
+pub const ENTITY_KIND: &str = "graph_entity";
+/// Marks stored relation records.
+pub const RELATION_KIND: &str = "graph_relation";
+
+#[derive(Debug, Error)]
+pub enum GraphMemoryError {
+    #[error("entity extraction failed: {0}")]
+    Extraction(#[from] StructuredOutputError),
+    #[error("memory store failed: {0}")]
+    Memory(#[from] MemoryError),
+}
+
+/// A named thing: a person, place, project, tool and so on.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Entity {
+    pub name: String,
+    /// What sort of thing it is, e.g. `person` or `project`.
+    #[serde(rename = "type")]
+    pub kind: String,
+}
+
+/// A directed, typed edge: `source` `relation` `target`, e.g. "Ana works_on
+/// Atlas".
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Relation {
+    pub source: String,
+    pub relation: String,
+    pub target: String,
+}
+
+/// What a model found in one piece of text.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Extraction {
+    #[serde(default)]
+    pub entities: Vec<Entity>,
+    #[serde(default)]
+    pub relations: Vec<Relation>,
+}
+
+/// The entities and relations within some hops of an entity.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Neighborhood {
+    pub entities: Vec<Entity>,
+    pub relations: Vec<Relation>,
+}
+
+impl Neighborhood {
+    /// One line per relation, for dropping into a prompt.
+    pub fn describe(&self) -> String {
+        self.relations
+            .iter()
+            .map(|r| format!("{} {} {}", r.source, r.relation, r.target))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// A typed entity graph kept in a [`MemoryStore`].
+///
+/// Entities are stored under `{prefix}entity:{name}` and relations under
+/// `{prefix}edge:{source}|{relation}|{target}`, names lowercased so "Ana"
+/// and "ana" are one node. Each record lists the steps it was seen in. The
+/// store must support [`MemoryStore::list_keys`] for queries.
+pub struct GraphMemory<M: LLMModel> {
+    pub model: M,
+    pub store: Arc<dyn MemoryStore>,
+    pub prefix: String,
+    pub options: GenerationOptions,
+}
+
+impl<M: LLMModel> GraphMemory<M> {
+    pub fn new(model: M, store: Arc<dyn MemoryStore>) -> Self {
+        Self {
+            model,
+            store,
+            prefix: "graph:".into(),
+            options: GenerationOptions::default(),
+        }
+    }
+
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    pub fn with_options(mut self, options: GenerationOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Extracts and stores the entities and relations in a step's output
+    /// and observations.
+    pub async fn ingest(&self, outcome: &StepOutcome) -> Result<Extraction, GraphMemoryError> {
+        let mut text = SemanticMemory::text_of(&outcome.output);
+        for observation in &outcome.observations {
+            text.push('\n');
+            text.push_str(observation);
+        }
+        self.ingest_text(&outcome.step_id, &text).await
+    }
+
+    /// Extracts and stores the entities and relations in `text`, crediting
+    /// them to `source`.
+    pub async fn ingest_text(
+        &self,
+        source: &str,
+        text: &str,
+    ) -> Result<Extraction, GraphMemoryError> {
+        let extraction: Extraction = self
+            .model
+            .generate_structured(&prompt(text), &self.options)
+            .await?;
+        for entity in &extraction.entities {
+            self.add_entity(entity, source)?;
+        }
+        for relation in &extraction.relations {
+            self.add_relation(relation, source)?;
+        }
+        tracing::debug!(
+            source,
+            entities = extraction.entities.len(),
+            relations = extraction.relations.len(),
+            "ingested graph memory"
+        );
+        Ok(extraction)
+    }
+
+    /// Stores `entity`, keeping the type it was first stored with.
+    pub fn add_entity(&self, entity: &Entity, source: &str) -> Result<(), MemoryError> {
+        let key = self.entity_key(&entity.name);
+        let existing = self.store.get(&key)?;
+        let kind = existing
+            .as_ref()
+            .and_then(|record| record["type"].as_str())
+            .filter(|kind| *kind != "unknown")
+            .unwrap_or(&entity.kind)
+            .to_string();
+        let record = json!({
+            "kind": ENTITY_KIND,
+            "name": existing.as_ref().map_or(entity.name.as_str(), |record| {
+                record["name"].as_str().unwrap_or(&entity.name)
+            }),
+            "type": kind,
+            "sources": with_source(existing.as_ref(), source),
+        });
+        self.store.put(&key, &record)
+    }
+
+    /// Stores `relation`, adding untyped entities for ends not seen yet.
+    pub fn add_relation(&self, relation: &Relation, source: &str) -> Result<(), MemoryError> {
+        for name in [&relation.source, &relation.target] {
+            if self.entity(name)?.is_none() {
+                let entity = Entity {
+                    name: name.clone(),
+                    kind: "unknown".into(),
+                };
+                self.add_entity(&entity, source)?;
+            }
+        }
+        let key = format!(
+            "{}edge:{}|{}|{}",
+            self.prefix,
+            normalize(&relation.source),
+            normalize(&relation.relation),
+            normalize(&relation.target)
+        );
+        let existing = self.store.get(&key)?;
+        let record = json!({
+            "kind": RELATION_KIND,
+            "source": relation.source,
+            "relation": relation.relation,
+            "target": relation.target,
+            "sources": with_source(existing.as_ref(), source),
+        });
+        self.store.put(&key, &record)
+    }
+
+    pub fn entity(&self, name: &str) -> Result<Option<Entity>, MemoryError> {
+        Ok(self
+            .store
+            .get(&self.entity_key(name))?
+            .and_then(|record| serde_json::from_value(record).ok()))
+    }
+
+    /// Everything within `depth` hops of `name`, following edges either way.
+    /// Empty when `name` is unknown.
+    pub fn neighborhood(&self, name: &str, depth: usize) -> Result<Neighborhood, MemoryError> {
+        let mut neighborhood = Neighborhood::default();
+        let Some(center) = self.entity(name)? else {
+            return Ok(neighborhood);
+        };
+
+        let edge_prefix = format!("{}edge:", self.prefix);
+        let mut edges = Vec::new();
+        for key in self.store.list_keys(&edge_prefix)? {
+            if let Some(relation) = self
+                .store
+                .get(&key)?
+                .and_then(|record| serde_json::from_value::<Relation>(record).ok())
+            {
+                edges.push(relation);
+            }
+        }
+
+        let mut seen = BTreeSet::from([normalize(&center.name)]);
+        let mut used = BTreeMap::new();
+        let mut frontier = VecDeque::from([(normalize(&center.name), 0)]);
+        while let Some((node, hops)) = frontier.pop_front() {
+            if hops == depth {
+                continue;
+            }
+            for (index, edge) in edges.iter().enumerate() {
+                let (from, to) = (normalize(&edge.source), normalize(&edge.target));
+                let other = if from == node {
+                    to
+                } else if to == node {
+                    from
+                } else {
+                    continue;
+                };
+                used.insert(index, edge.clone());
+                if seen.insert(other.clone()) {
+                    frontier.push_back((other, hops + 1));
+                }
+            }
+        }
+
+        neighborhood.relations = used.into_values().collect();
+        for name in seen {
+            if let Some(entity) = self.entity(&name)? {
+                neighborhood.entities.push(entity);
+            }
+        }
+        Ok(neighborhood)
+    }
+
+    fn entity_key(&self, name: &str) -> String {
+        format!("{}entity:{}", self.prefix, normalize(name))
+    }
+}
+
+impl<M: LLMModel> std::fmt::Debug for GraphMemory<M> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GraphMemory")
+            .field("store", &self.store)
+            .field("prefix", &self.prefix)
+            .finish()
+    }
+}
+
+fn normalize(name: &str) -> String {
+    name.trim().to_lowercase()
+}
+
+/// The `sources` of `existing` with `source` appended once.
+fn with_source(existing: Option<&Value>, source: &str) -> Vec<String> {
+    let mut sources: Vec<String> = existing
+        .and_then(|record| record["sources"].as_array())
+        .map(|sources| {
+            sources
+                .iter()
+                .filter_map(|s| s.as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default();
+    if !sources.iter().any(|s| s == source) {
+        sources.push(source.to_string());
+    }
+    sources
+}
+
+fn prompt(text: &str) -> String {
+    format!(
+        "List the entities (people, organisations, places, projects, tools, \
+         concepts) in the text below, each with a short lowercase type, and \
+         the relations between them as source, relation, target triples. Use \
+         short snake_case relation names such as works_on or located_in. \
+         Leave out anything not stated in the text.\n\n{text}"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EventStream, LLMResponse, ModelError};
+    use agent_memory::InMemoryStore;
+    use async_trait::async_trait;
+
+    /// Always replies with the same extraction.
+    struct Extractor(&'static str);
+
+    #[async_trait]
+    impl LLMModel for Extractor {
+        async fn generate(
+            &self,
+            _prompt: &str,
+            _options: &GenerationOptions,
+        ) -> Result<LLMResponse, ModelError> {
+            Ok(LLMResponse {
+                content: self.0.into(),
+                ..LLMResponse::default()
+            })
+        }
+
+        async fn stream(
+            &self,
+            _prompt: &str,
+            _options: &GenerationOptions,
+        ) -> Result<EventStream, ModelError> {
+            unreachable!("extraction does not stream")
+        }
+
+        fn supports_tools(&self) -> bool {
+            false
+        }
+    }
+
+    #[tokio::test]
+    async fn extracts_step_outputs_into_a_queryable_graph() {
+        let store = Arc::new(InMemoryStore::new());
+        let graph = GraphMemory::new(
+            Extractor(
+                r#"{"entities": [{"name": "Ana", "type": "person"},
+                                 {"name": "Atlas", "type": "project"}],
+                    "relations": [{"source": "Ana", "relation": "works_on", "target": "Atlas"},
+                                  {"source": "Atlas", "relation": "written_in", "target": "Rust"}]}"#,
+            ),
+            store.clone(),
+        );
+        let outcome = StepOutcome {
+            step_id: "step-1".into(),
+            output: json!({"text": "Ana works on Atlas, which is written in Rust."}),
+            observations: Vec::new(),
+            success: true,
+            retries: 0,
+            fallback_used: false,
+            control_notes: Vec::new(),
+            fallback_applied: None,
+            failure_kind: None,
+            redactions: Vec::new(),
+        };
+        let extraction = graph.ingest(&outcome).await.unwrap();
+        assert_eq!(extraction.relations.len(), 2);
+
+        assert_eq!(graph.entity("rust").unwrap().unwrap().kind, "unknown");
+        graph
+            .add_entity(
+                &Entity {
+                    name: "Rust".into(),
+                    kind: "language".into(),
+                },
+                "step-2",
+            )
+            .unwrap();
+        assert_eq!(graph.entity("RUST").unwrap().unwrap().kind, "language");
+        let record = store.get("graph:entity:rust").unwrap().unwrap();
+        assert_eq!(record["sources"], json!(["step-1", "step-2"]));
+
+        let near = graph.neighborhood("ana", 1).unwrap();
+        assert_eq!(near.describe(), "Ana works_on Atlas");
+        assert_eq!(near.entities.len(), 2);
+        let wider = graph.neighborhood("Ana", 2).unwrap();
+        assert_eq!(
+            wider.describe(),
+            "Ana works_on Atlas\nAtlas written_in Rust"
+        );
+        assert!(graph.neighborhood("Bob", 2).unwrap().entities.is_empty());
+    }
+}
@@ -0,0 +1,467 @@
+//! [Hugging Face](https://huggingface.co) text generation, enabled by the
+//! `huggingface` cargo feature.
+//!
+//! [`HuggingFaceModel`] speaks the text-generation wire format shared by the
+//! serverless Inference API and Text Generation Inference (TGI) servers,
+//! including dedicated Inference Endpoints. Streaming reads TGI's
+//! server-sent token events as they arrive.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use agent_telemetry::Secret;
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use thiserror::Error;
+
+use crate::streaming::line_stream;
+use crate::{
+    tokenizer_for, EventStream, FinishReason, GenerationOptions, HttpClientConfig, HttpClientError,
+    LLMModel, LLMResponse, ModelError, ModelMetadata, StreamEvent, Tokenizer, UsageMetrics,
+};
+
+#[derive(Debug, Error)]
+pub enum HuggingFaceError {
+    #[error("request failed: {0}")]
+    Transport(#[from] reqwest::Error),
+    #[error("server returned {status}: {message}")]
+    Api { status: u16, message: String },
+    /// The serverless API is still loading the model onto a worker.
+    #[error("model is loading: {message}")]
+    Loading {
+        message: String,
+        /// The server's estimate of when the model will be ready.
+        estimated_time: Option<Duration>,
+    },
+    #[error("unexpected response: {0}")]
+    Decode(String),
+}
+
+impl From<HuggingFaceError> for ModelError {
+    fn from(error: HuggingFaceError) -> Self {
+        match error {
+            HuggingFaceError::Transport(error) => error.into(),
+            HuggingFaceError::Api { status, message } => {
+                ModelError::from_status(status, message, None)
+            }
+            HuggingFaceError::Loading {
+                message,
+                estimated_time,
+            } => ModelError::RateLimited {
+                retry_after: estimated_time,
+                message,
+            },
+            HuggingFaceError::Decode(message) => ModelError::InvalidResponse(message),
+        }
+    }
+}
+
+/// What a TGI server reports about the model it serves.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct TgiInfo {
+    pub model_id: String,
+    #[serde(default)]
+    pub model_sha: Option<String>,
+    /// Longest prompt accepted, in tokens.
+    #[serde(default, alias = "max_input_length")]
+    pub max_input_tokens: Option<usize>,
+    /// Longest prompt plus completion, in tokens.
+    #[serde(default)]
+    pub max_total_tokens: Option<usize>,
+    /// TGI version.
+    #[serde(default)]
+    pub version: Option<String>,
+}
+
+/// An open-weight model served by Hugging Face.
+pub struct HuggingFaceModel {
+    /// Repository id such as `meta-llama/Llama-3.1-8B-Instruct`.
+    pub model: String,
+    /// URL that generation requests are posted to.
+    pub endpoint: String,
+    /// Hugging Face access token, sent as a bearer token. Self-hosted TGI
+    /// servers usually need none.
+    pub token: Option<Secret<String>>,
+    pub supports_tools: bool,
+    pub client: reqwest::Client,
+}
+
+impl HuggingFaceModel {
+    pub const INFERENCE_API_URL: &'static str = "https://api-inference.huggingface.co/models";
+
+    /// `model` on the serverless Inference API.
+    pub fn new(model: impl Into<String>) -> Self {
+        let model = model.into();
+        Self::tgi(format!("{}/{model}", Self::INFERENCE_API_URL), model)
+    }
+
+    /// `model` served by the TGI server or Inference Endpoint at `endpoint`;
+    /// a trailing `/` is ignored.
+    pub fn tgi(endpoint: impl Into<String>, model: impl Into<String>) -> Self {
+        Self {
+            model: model.into(),
+            endpoint: endpoint.into().trim_end_matches('/').to_string(),
+            token: None,
+            supports_tools: false,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    pub fn with_token(mut self, token: impl Into<Secret<String>>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    pub fn with_client_config(
+        mut self,
+        config: &HttpClientConfig,
+    ) -> Result<Self, HttpClientError> {
+        self.client = config.build_client()?;
+        Ok(self)
+    }
+
+    /// Fetches the served model's id and token limits from TGI's `/info`.
+    /// The serverless Inference API does not offer it.
+    pub async fn info(&self) -> Result<TgiInfo, HuggingFaceError> {
+        let response = self
+            .authorized(self.client.get(format!("{}/info", self.endpoint)))
+            .send()
+            .await?;
+        checked(response)
+            .await?
+            .json()
+            .await
+            .map_err(|e| HuggingFaceError::Decode(e.to_string()))
+    }
+
+    fn authorized(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match &self.token {
+            Some(token) => request.bearer_auth(token.expose()),
+            None => request,
+        }
+    }
+
+    fn request(
+        &self,
+        prompt: &str,
+        options: &GenerationOptions,
+        stream: bool,
+    ) -> reqwest::RequestBuilder {
+        let body = json!({
+            "inputs": prompt,
+            "parameters": parameters(options),
+            "stream": stream,
+        });
+        self.authorized(self.client.post(&self.endpoint).json(&body))
+    }
+
+    fn metadata(&self) -> ModelMetadata {
+        ModelMetadata {
+            provider: "huggingface".into(),
+            model: self.model.clone(),
+            supports_tools: self.supports_tools,
+            ..ModelMetadata::default()
+        }
+    }
+}
+
+impl std::fmt::Debug for HuggingFaceModel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HuggingFaceModel")
+            .field("model", &self.model)
+            .field("endpoint", &self.endpoint)
+            .field("token", &self.token)
+            .finish()
+    }
+}
+
+/// TGI generation parameters. TGI rejects a zero temperature, so that asks
+/// for greedy decoding instead.
+fn parameters(options: &GenerationOptions) -> Value {
+    let mut parameters = json!({
+        "details": true,
+        "return_full_text": false,
+    });
+    match options.temperature {
+        Some(temperature) if temperature <= 0.0 => parameters["do_sample"] = json!(false),
+        Some(temperature) => {
+            parameters["do_sample"] = json!(true);
+            parameters["temperature"] = json!(temperature);
+        }
+        None => {}
+    }
+    let optional = [
+        ("top_p", options.top_p.map(Value::from)),
+        ("max_new_tokens", options.max_tokens.map(Value::from)),
+        (
+            "frequency_penalty",
+            options.frequency_penalty.map(Value::from),
+        ),
+        ("seed", options.seed.map(Value::from)),
+    ];
+    for (key, value) in optional {
+        if let Some(value) = value {
+            parameters[key] = value;
+        }
+    }
+    if !options.stop.is_empty() {
+        parameters["stop"] = json!(options.stop);
+    }
+    parameters
+}
+
+fn finish_reason(reason: &str) -> FinishReason {
+    match reason {
+        "eos_token" | "stop_sequence" => FinishReason::Stop,
+        "length" => FinishReason::Length,
+        other => FinishReason::Other(other.to_string()),
+    }
+}
+
+#[derive(Deserialize)]
+struct Generation {
+    generated_text: String,
+    #[serde(default)]
+    details: Option<Details>,
+}
+
+#[derive(Deserialize)]
+struct Details {
+    finish_reason: String,
+    generated_tokens: usize,
+}
+
+#[async_trait]
+impl LLMModel for HuggingFaceModel {
+    async fn generate(
+        &self,
+        prompt: &str,
+        options: &GenerationOptions,
+    ) -> Result<LLMResponse, ModelError> {
+        let response = checked(self.request(prompt, options, false).send().await?).await?;
+        // The serverless API wraps the generation in a one-element array.
+        let body: Value = response
+            .json()
+            .await
+            .map_err(|e| HuggingFaceError::Decode(e.to_string()))?;
+        let generation = match body {
+            Value::Array(mut generations) if !generations.is_empty() => generations.remove(0),
+            body => body,
+        };
+        let generation = Generation::deserialize(generation)
+            .map_err(|e| HuggingFaceError::Decode(e.to_string()))?;
+        let tokenizer = self.tokenizer();
+        let completion_tokens = match &generation.details {
+            Some(details) => details.generated_tokens,
+            None => tokenizer.count(&generation.generated_text),
+        };
+        Ok(LLMResponse {
+            usage: UsageMetrics {
+                prompt_tokens: tokenizer.count(prompt),
+                completion_tokens,
+            },
+            finish_reason: generation
+                .details
+                .map(|details| finish_reason(&details.finish_reason)),
+            content: generation.generated_text,
+            metadata: self.metadata(),
+            ..LLMResponse::default()
+        })
+    }
+
+    async fn stream(
+        &self,
+        prompt: &str,
+        options: &GenerationOptions,
+    ) -> Result<EventStream, ModelError> {
+        let response = checked(self.request(prompt, options, true).send().await?).await?;
+        let prompt_tokens = self.tokenizer().count(prompt);
+        Ok(line_stream(response, "huggingface", move |line| {
+            let Some(data) = line.strip_prefix("data:") else {
+                return Vec::new();
+            };
+            let Ok(event) = serde_json::from_str::<Value>(data.trim()) else {
+                return Vec::new();
+            };
+            if let Some(error) = event["error"].as_str() {
+                tracing::warn!(error, "huggingface stream failed");
+                return vec![StreamEvent::Done {
+                    finish_reason: Some(FinishReason::Error),
+                }];
+            }
+            let mut events = Vec::new();
+            let token = &event["token"];
+            if !token["special"].as_bool().unwrap_or(false) {
+                if let Some(text) = token["text"].as_str() {
+                    events.push(StreamEvent::TextDelta { text: text.into() });
+                }
+            }
+            // The last event carries the whole text and the details.
+            if let Ok(details) = Details::deserialize(&event["details"]) {
+                events.push(StreamEvent::Usage {
+                    usage: UsageMetrics {
+                        prompt_tokens,
+                        completion_tokens: details.generated_tokens,
+                    },
+                });
+                events.push(StreamEvent::Done {
+                    finish_reason: Some(finish_reason(&details.finish_reason)),
+                });
+            }
+            events
+        }))
+    }
+
+    fn supports_tools(&self) -> bool {
+        self.supports_tools
+    }
+
+    fn tokenizer(&self) -> Arc<dyn Tokenizer> {
+        tokenizer_for("huggingface", &self.model)
+    }
+}
+
+async fn checked(response: reqwest::Response) -> Result<reqwest::Response, HuggingFaceError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let text = response.text().await.unwrap_or_default();
+    // Error bodies look like {"error": "...", "estimated_time": 20.0}; fall
+    // back to the raw text.
+    let body = serde_json::from_str::<Value>(&text).unwrap_or_default();
+    let message = body["error"]
+        .as_str()
+        .map(ToOwned::to_owned)
+        .unwrap_or(text);
+    if let Some(seconds) = body["estimated_time"].as_f64() {
+        return Err(HuggingFaceError::Loading {
+            message,
+            estimated_time: Some(Duration::from_secs_f64(seconds.max(0.0))),
+        });
+    }
+    Err(HuggingFaceError::Api {
+        status: status.as_u16(),
+        message,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_stream::StreamExt;
+    use wiremock::matchers::{body_partial_json, header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn model(server: &MockServer) -> HuggingFaceModel {
+        HuggingFaceModel::tgi(format!("{}/", server.uri()), "HuggingFaceH4/zephyr-7b-beta")
+            .with_token("hf_test".to_string())
+    }
+
+    #[tokio::test]
+    async fn generates_with_token_auth_and_tgi_parameters() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/"))
+            .and(header("authorization", "Bearer hf_test"))
+            .and(body_partial_json(json!({
+                "inputs": "Write a haiku",
+                "stream": false,
+                "parameters": {"do_sample": false, "max_new_tokens": 32, "details": true}
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([{
+                "generated_text": "Autumn wind whispers",
+                "details": {"finish_reason": "eos_token", "generated_tokens": 5}
+            }])))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/info"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "model_id": "HuggingFaceH4/zephyr-7b-beta",
+                "max_input_tokens": 4095,
+                "max_total_tokens": 4096,
+                "version": "2.4.0"
+            })))
+            .mount(&server)
+            .await;
+
+        let options = GenerationOptions::default()
+            .with_temperature(0.0)
+            .with_max_tokens(32);
+        let response = model(&server)
+            .generate("Write a haiku", &options)
+            .await
+            .unwrap();
+        assert_eq!(response.content, "Autumn wind whispers");
+        assert_eq!(response.usage.completion_tokens, 5);
+        assert_eq!(response.finish_reason, Some(FinishReason::Stop));
+        assert_eq!(response.metadata.provider, "huggingface");
+
+        let info = model(&server).info().await.unwrap();
+        assert_eq!(info.max_total_tokens, Some(4096));
+    }
+
+    #[tokio::test]
+    async fn streams_tokens_and_reports_loading_models() {
+        let server = MockServer::start().await;
+        let body = [
+            r#"data:{"token":{"id":1,"text":"Hello","special":false},"generated_text":null,"details":null}"#,
+            r#"data:{"token":{"id":2,"text":" there","special":false},"generated_text":null,"details":null}"#,
+            r#"data:{"token":{"id":3,"text":"</s>","special":true},"generated_text":"Hello there","details":{"finish_reason":"eos_token","generated_tokens":3}}"#,
+        ]
+        .join("\n\n");
+        Mock::given(method("POST"))
+            .and(body_partial_json(json!({"stream": true})))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(body, "text/event-stream"))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(503).set_body_json(json!({
+                "error": "Model HuggingFaceH4/zephyr-7b-beta is currently loading",
+                "estimated_time": 20.5
+            })))
+            .mount(&server)
+            .await;
+
+        let events: Vec<StreamEvent> = model(&server)
+            .stream("Hi", &GenerationOptions::default())
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        assert_eq!(
+            events,
+            vec![
+                StreamEvent::TextDelta {
+                    text: "Hello".into()
+                },
+                StreamEvent::TextDelta {
+                    text: " there".into()
+                },
+                StreamEvent::Usage {
+                    usage: UsageMetrics {
+                        prompt_tokens: model(&server).tokenizer().count("Hi"),
+                        completion_tokens: 3
+                    }
+                },
+                StreamEvent::Done {
+                    finish_reason: Some(FinishReason::Stop)
+                },
+            ]
+        );
+
+        let err = model(&server)
+            .generate("Hi", &GenerationOptions::default())
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err,
+            ModelError::RateLimited {
+                retry_after: Some(Duration::from_millis(20_500)),
+                message: "Model HuggingFaceH4/zephyr-7b-beta is currently loading".into(),
+            }
+        );
+    }
+}
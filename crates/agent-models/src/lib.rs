@@ -1,15 +1,141 @@
+use std::collections::BTreeMap;
 use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
 
+use agent_core::AgentError;
+use agent_telemetry::Secret;
 use async_trait::async_trait;
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use thiserror::Error;
 use tokio_stream::{self as stream, Stream};
 
+mod cache;
+mod cancel;
+mod compaction;
+mod fallback;
+mod graph;
+mod middleware;
+mod pricing;
+mod registry;
+mod retry;
+#[cfg(any(feature = "openai", feature = "ollama", feature = "huggingface"))]
+mod streaming;
+mod structured;
+mod throttle;
+mod tokenizer;
+mod usage;
+
+pub use agent_core::{HttpClientConfig, HttpClientError};
+pub use agent_memory::{cosine_similarity, Embedder};
+pub use cache::{CachedModel, CachedResponse, LruResponseCache, MemoryStoreCache, ResponseCache};
+pub use cancel::StreamControl;
+pub use compaction::{
+    CompactionError, CompactionPolicy, CompactionReport, MemoryCompactor, SUMMARY_KIND,
+};
+pub use fallback::{FallbackModel, FallbackPolicy};
+pub use graph::{
+    Entity, Extraction, GraphMemory, GraphMemoryError, Neighborhood, Relation, ENTITY_KIND,
+    RELATION_KIND,
+};
+pub use middleware::{LoggingMiddleware, MiddlewareModel, ModelMiddleware, ModelRequest};
+pub use pricing::{price_for, register_price, ModelPrice, PricingTable};
+pub use registry::{
+    CredentialRef, ModelConfig, ModelProvider, ModelRegistry, ModelRegistryConfig, RegistryError,
+};
+pub use retry::{ModelRetryPolicy, RetryingModel};
+pub use structured::{
+    ResponseFormat, StructuredGeneration, StructuredOutputError, STRUCTURED_OUTPUT_ATTEMPTS,
+};
+pub use throttle::{RateLimit, Throttle, ThrottleLimits, ThrottlePermit, ThrottledModel};
+pub use tokenizer::{tokenizer_for, CharEstimateTokenizer, Tokenizer, WhitespaceTokenizer};
+pub use tokio_util::sync::CancellationToken;
+pub use usage::{UsageTotals, UsageTracker};
+
+#[cfg(feature = "tiktoken")]
+pub use tokenizer::{BpeEncoding, BpeTokenizer};
+
+#[cfg(feature = "openai")]
+pub mod openai;
+
+#[cfg(feature = "openai")]
+pub use openai::{OpenAIConnection, OpenAIError};
+
+#[cfg(feature = "azure")]
+pub mod azure;
+
+#[cfg(feature = "ollama")]
+pub mod ollama;
+
+#[cfg(feature = "huggingface")]
+pub mod huggingface;
+
+#[cfg(feature = "local")]
+pub mod local;
+
+#[cfg(feature = "huggingface")]
+pub use huggingface::{HuggingFaceError, HuggingFaceModel, TgiInfo};
+
+#[cfg(feature = "local")]
+pub use local::{LocalModel, LocalModelConfig, LocalModelError};
+
+#[cfg(feature = "ollama")]
+pub use ollama::{OllamaConnection, OllamaError, OllamaOptions};
+
+#[cfg(feature = "azure")]
+pub use azure::{AzureAuth, AzureOpenAIConnection, ClientSecretCredential, TokenCredential};
+
 pub type Token = String;
 pub type TokenStream = Pin<Box<dyn Stream<Item = Token> + Send>>;
+/// Output of [`LLMModel::stream`]; always ends with [`StreamEvent::Done`].
+pub type EventStream = Pin<Box<dyn Stream<Item = StreamEvent> + Send>>;
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StreamEvent {
+    /// Next piece of the reply text.
+    TextDelta {
+        text: String,
+    },
+    /// Next piece of a reasoning model's reasoning, sent before the reply.
+    ReasoningDelta {
+        text: String,
+    },
+    /// Fragment of the tool call at `index`. `id` and `name` come with the
+    /// first fragment; `arguments` text is concatenated across fragments.
+    ToolCallDelta {
+        index: usize,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+        #[serde(default)]
+        arguments: String,
+    },
+    Usage {
+        usage: UsageMetrics,
+    },
+    Done {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        finish_reason: Option<FinishReason>,
+    },
+}
+
+/// Keeps only the text deltas of `events`, e.g. to feed a token-based
+/// consumer such as a streaming guardrail.
+pub fn text_deltas(events: EventStream) -> TokenStream {
+    use tokio_stream::StreamExt;
+
+    Box::pin(events.filter_map(|event| match event {
+        StreamEvent::TextDelta { text } => Some(text),
+        _ => None,
+    }))
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
 pub struct UsageMetrics {
     pub prompt_tokens: usize,
     pub completion_tokens: usize,
@@ -21,12 +147,19 @@ pub struct ModelMetadata {
     pub model: String,
     pub supports_tools: bool,
     pub is_reasoning: bool,
+    /// Provider-specific details about the response, such as Azure
+    /// content-filter verdicts under `content_filter`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub annotations: BTreeMap<String, Value>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
 pub struct ToolCallInfo {
     pub name: String,
     pub arguments: Value,
+    /// Provider-assigned call id, echoed back in the matching tool message.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -38,50 +171,700 @@ pub struct CompletionChunk {
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct LLMResponse {
     pub content: String,
+    /// Reasoning a reasoning model produced before its answer, kept apart
+    /// from `content` so it can be logged or policed but not shown.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning: Option<String>,
     pub usage: UsageMetrics,
     pub tool_calls: Vec<ToolCallInfo>,
     pub metadata: ModelMetadata,
+    /// Why the provider stopped generating; `None` for offline models.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finish_reason: Option<FinishReason>,
+}
+
+impl LLMResponse {
+    /// Folds one streamed event into the response, so a consumer can render
+    /// partial text and tool calls and end up with the complete reply. Tool
+    /// call arguments stay as raw text until [`StreamEvent::Done`], when
+    /// those that are valid JSON are parsed.
+    pub fn apply_event(&mut self, event: &StreamEvent) {
+        match event {
+            StreamEvent::TextDelta { text } => self.content.push_str(text),
+            StreamEvent::ReasoningDelta { text } => self
+                .reasoning
+                .get_or_insert_with(String::new)
+                .push_str(text),
+            StreamEvent::ToolCallDelta {
+                index,
+                id,
+                name,
+                arguments,
+            } => {
+                if self.tool_calls.len() <= *index {
+                    self.tool_calls.resize_with(index + 1, || ToolCallInfo {
+                        arguments: Value::String(String::new()),
+                        ..ToolCallInfo::default()
+                    });
+                }
+                let call = &mut self.tool_calls[*index];
+                if let Some(id) = id {
+                    call.id = Some(id.clone());
+                }
+                if let Some(name) = name {
+                    call.name.push_str(name);
+                }
+                if let Value::String(text) = &mut call.arguments {
+                    text.push_str(arguments);
+                }
+            }
+            StreamEvent::Usage { usage } => self.usage = usage.clone(),
+            StreamEvent::Done { finish_reason } => {
+                self.finish_reason = finish_reason.clone();
+                for call in &mut self.tool_calls {
+                    if let Value::String(text) = &call.arguments {
+                        if let Ok(parsed) = serde_json::from_str(text) {
+                            call.arguments = parsed;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FinishReason {
+    Stop,
+    Length,
+    ToolCalls,
+    ContentFilter,
+    /// The request failed before the provider produced a completion.
+    Error,
+    /// The caller cancelled the stream; see [`StreamControl`].
+    Cancelled,
+    /// The stream ran past its [`StreamControl`] deadline.
+    TimedOut,
+    Other(String),
+}
+
+impl From<String> for FinishReason {
+    fn from(reason: String) -> Self {
+        match reason.as_str() {
+            "stop" => Self::Stop,
+            "length" => Self::Length,
+            "tool_calls" | "function_call" => Self::ToolCalls,
+            "content_filter" => Self::ContentFilter,
+            _ => Self::Other(reason),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ChatRole {
+    System,
+    #[default]
+    User,
+    Assistant,
+    Tool,
+}
+
+impl ChatRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChatRole::System => "system",
+            ChatRole::User => "user",
+            ChatRole::Assistant => "assistant",
+            ChatRole::Tool => "tool",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+pub struct ChatMessage {
+    pub role: ChatRole,
+    pub content: String,
+    /// Set on [`ChatRole::Tool`] messages to link a result to its call.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+    /// Calls requested by an [`ChatRole::Assistant`] turn, replayed so the
+    /// following tool messages have something to answer.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCallInfo>,
+    /// Images and documents sent alongside `content`, for models that
+    /// accept them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub parts: Vec<ContentPart>,
+}
+
+impl ChatMessage {
+    pub fn new(role: ChatRole, content: impl Into<String>) -> Self {
+        Self {
+            role,
+            content: content.into(),
+            tool_call_id: None,
+            tool_calls: Vec::new(),
+            parts: Vec::new(),
+        }
+    }
+
+    pub fn system(content: impl Into<String>) -> Self {
+        Self::new(ChatRole::System, content)
+    }
+
+    pub fn user(content: impl Into<String>) -> Self {
+        Self::new(ChatRole::User, content)
+    }
+
+    /// The assistant turn for `response`, including any tool calls it made.
+    pub fn assistant(response: &LLMResponse) -> Self {
+        Self {
+            tool_calls: response.tool_calls.clone(),
+            ..Self::new(ChatRole::Assistant, response.content.clone())
+        }
+    }
+
+    /// The result of the tool call identified by `tool_call_id`.
+    pub fn tool(tool_call_id: impl Into<String>, content: impl Into<String>) -> Self {
+        Self {
+            tool_call_id: Some(tool_call_id.into()),
+            ..Self::new(ChatRole::Tool, content)
+        }
+    }
+
+    pub fn with_image(mut self, source: MediaSource) -> Self {
+        self.parts.push(ContentPart::Image {
+            source,
+            detail: ImageDetail::default(),
+        });
+        self
+    }
+
+    pub fn with_document(mut self, source: MediaSource, name: Option<String>) -> Self {
+        self.parts.push(ContentPart::Document { source, name });
+        self
+    }
+}
+
+/// Where the bytes of an image or document come from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum MediaSource {
+    Url { url: String },
+    Base64 { media_type: String, data: String },
+}
+
+impl MediaSource {
+    pub fn url(url: impl Into<String>) -> Self {
+        MediaSource::Url { url: url.into() }
+    }
+
+    /// Inline content, e.g. a PNG screenshot as `("image/png", data)`.
+    pub fn base64(media_type: impl Into<String>, data: impl Into<String>) -> Self {
+        MediaSource::Base64 {
+            media_type: media_type.into(),
+            data: data.into(),
+        }
+    }
+
+    /// The URL itself, or inline content as a `data:` URL.
+    pub fn to_url(&self) -> String {
+        match self {
+            MediaSource::Url { url } => url.clone(),
+            MediaSource::Base64 { media_type, data } => format!("data:{media_type};base64,{data}"),
+        }
+    }
+}
+
+/// Resolution a vision model should look at an image in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageDetail {
+    #[default]
+    Auto,
+    Low,
+    High,
+}
+
+/// Non-text content attached to a [`ChatMessage`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentPart {
+    Image {
+        source: MediaSource,
+        #[serde(default)]
+        detail: ImageDetail,
+    },
+    Document {
+        source: MediaSource,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+    },
+}
+
+/// Sampling controls for a single request. Unset fields fall back to the
+/// provider's defaults; offline models honour `max_tokens`, `stop` and `seed`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GenerationOptions {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stop: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frequency_penalty: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub presence_penalty: Option<f32>,
+    /// Requests reproducible sampling from providers that support it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+}
+
+impl GenerationOptions {
+    pub fn with_temperature(mut self, temperature: f32) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+
+    pub fn with_top_p(mut self, top_p: f32) -> Self {
+        self.top_p = Some(top_p);
+        self
+    }
+
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    pub fn with_stop(mut self, stop: impl Into<String>) -> Self {
+        self.stop.push(stop.into());
+        self
+    }
+
+    pub fn with_frequency_penalty(mut self, penalty: f32) -> Self {
+        self.frequency_penalty = Some(penalty);
+        self
+    }
+
+    pub fn with_presence_penalty(mut self, penalty: f32) -> Self {
+        self.presence_penalty = Some(penalty);
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Applies `stop` and `max_tokens` to locally produced text: cuts at the
+    /// earliest stop sequence, then keeps at most `max_tokens` words.
+    pub fn truncate(&self, content: &str) -> String {
+        let end = self
+            .stop
+            .iter()
+            .filter(|stop| !stop.is_empty())
+            .filter_map(|stop| content.find(stop.as_str()))
+            .min()
+            .unwrap_or(content.len());
+        let content = &content[..end];
+        match self.max_tokens {
+            Some(limit) if content.split_whitespace().count() > limit as usize => content
+                .split_whitespace()
+                .take(limit as usize)
+                .collect::<Vec<_>>()
+                .join(" "),
+            _ => content.to_string(),
+        }
+    }
+}
+
+/// Per-request settings for [`LLMModel::chat`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChatOptions {
+    /// Provider-format function specs, e.g. from `ToolRegistry::to_function_specs`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<Value>,
+    #[serde(default)]
+    pub generation: GenerationOptions,
+    /// Constrains the reply's format on providers that support it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<ResponseFormat>,
+}
+
+impl ChatOptions {
+    pub fn with_tools(mut self, tools: Vec<Value>) -> Self {
+        self.tools = tools;
+        self
+    }
+
+    pub fn with_generation(mut self, generation: GenerationOptions) -> Self {
+        self.generation = generation;
+        self
+    }
+}
+
+/// Renders a conversation as a single prompt, one `role: content` block per
+/// message, for models that only accept plain text.
+/// Attached images and documents are reduced to `[image]`/`[document]`
+/// placeholders.
+pub fn flatten_messages(messages: &[ChatMessage]) -> String {
+    messages
+        .iter()
+        .map(|message| {
+            let role = message.role.as_str();
+            let mut block = match &message.tool_call_id {
+                Some(id) => format!("{role} ({id}): {}", message.content),
+                None => format!("{role}: {}", message.content),
+            };
+            for call in &message.tool_calls {
+                block.push_str(&format!("\n[call {}({})]", call.name, call.arguments));
+            }
+            for part in &message.parts {
+                block.push_str(match part {
+                    ContentPart::Image { .. } => "\n[image]",
+                    ContentPart::Document { .. } => "\n[document]",
+                });
+            }
+            block
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// How much of a model's context window a conversation occupies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub struct ContextUsage {
+    pub used: usize,
+    pub limit: usize,
+    pub remaining: usize,
+}
+
+impl ContextUsage {
+    pub fn new(used: usize, limit: usize) -> Self {
+        Self {
+            used,
+            limit,
+            remaining: limit.saturating_sub(used),
+        }
+    }
+
+    pub fn is_over_limit(&self) -> bool {
+        self.used > self.limit
+    }
+}
+
+/// Why a model call failed, classified so callers can decide whether and
+/// when to retry.
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum ModelError {
+    #[error("authentication failed: {0}")]
+    Auth(String),
+    #[error("rate limited: {message}")]
+    RateLimited {
+        /// How long the provider asked callers to wait, when it said.
+        retry_after: Option<Duration>,
+        message: String,
+    },
+    #[error("blocked by content filter: {0}")]
+    ContentFilter(String),
+    #[error("model request timed out")]
+    Timeout,
+    #[error("transport failed: {0}")]
+    Transport(String),
+    #[error("provider returned {status}: {message}")]
+    Api { status: u16, message: String },
+    #[error("unexpected response: {0}")]
+    InvalidResponse(String),
+    #[error("model request cancelled")]
+    Cancelled,
+}
+
+impl ModelError {
+    /// Classifies an HTTP error status the way hosted providers use them.
+    pub fn from_status(status: u16, message: String, retry_after: Option<Duration>) -> Self {
+        match status {
+            401 | 403 => ModelError::Auth(message),
+            429 => ModelError::RateLimited {
+                retry_after,
+                message,
+            },
+            408 | 504 => ModelError::Timeout,
+            _ => ModelError::Api { status, message },
+        }
+    }
+
+    /// Whether the same request may succeed if sent again later.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ModelError::RateLimited { .. } | ModelError::Timeout | ModelError::Transport(_) => true,
+            ModelError::Api { status, .. } => *status >= 500,
+            ModelError::Auth(_)
+            | ModelError::ContentFilter(_)
+            | ModelError::InvalidResponse(_)
+            | ModelError::Cancelled => false,
+        }
+    }
+
+    /// Short label for the failure class, used in metrics.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ModelError::Auth(_) => "auth",
+            ModelError::RateLimited { .. } => "rate_limited",
+            ModelError::ContentFilter(_) => "content_filter",
+            ModelError::Timeout => "timeout",
+            ModelError::Transport(_) => "transport",
+            ModelError::Api { status, .. } if *status >= 500 => "server_error",
+            ModelError::Api { .. } => "api",
+            ModelError::InvalidResponse(_) => "invalid_response",
+            ModelError::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            ModelError::RateLimited { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for ModelError {
+    fn from(error: reqwest::Error) -> Self {
+        if error.is_timeout() {
+            ModelError::Timeout
+        } else {
+            ModelError::Transport(error.to_string())
+        }
+    }
+}
+
+impl From<ModelError> for AgentError {
+    fn from(error: ModelError) -> Self {
+        match error {
+            ModelError::RateLimited { retry_after, .. } => AgentError::ModelRateLimited {
+                retry_after_ms: retry_after.map(|delay| delay.as_millis() as u64),
+            },
+            ModelError::ContentFilter(_) => AgentError::Safety(error.to_string()),
+            ModelError::Timeout => AgentError::Timeout,
+            ModelError::Cancelled => AgentError::Cancelled,
+            other => AgentError::Model(other.to_string()),
+        }
+    }
 }
 
 #[async_trait]
 pub trait LLMModel: Send + Sync {
-    async fn generate(&self, prompt: &str) -> LLMResponse;
-    async fn stream(&self, prompt: &str) -> TokenStream;
+    async fn generate(
+        &self,
+        prompt: &str,
+        options: &GenerationOptions,
+    ) -> Result<LLMResponse, ModelError>;
+    async fn stream(
+        &self,
+        prompt: &str,
+        options: &GenerationOptions,
+    ) -> Result<EventStream, ModelError>;
     fn supports_tools(&self) -> bool;
+
+    /// Streams under a deadline and cancellation token. When either fires,
+    /// while opening or mid-stream, the provider stream is dropped, closing
+    /// its connection, and the events end with [`StreamEvent::Done`] finished
+    /// by [`FinishReason::Cancelled`] or [`FinishReason::TimedOut`].
+    async fn stream_with(
+        &self,
+        prompt: &str,
+        options: &GenerationOptions,
+        control: &StreamControl,
+    ) -> Result<EventStream, ModelError> {
+        cancel::open_controlled(self.stream(prompt, options), control).await
+    }
+
+    /// Continues a multi-turn conversation. The default flattens `messages`
+    /// with [`flatten_messages`] and ignores `options`; providers with a
+    /// native chat API override it.
+    async fn chat(
+        &self,
+        messages: &[ChatMessage],
+        options: &ChatOptions,
+    ) -> Result<LLMResponse, ModelError> {
+        self.generate(&flatten_messages(messages), &options.generation)
+            .await
+    }
+
+    /// The tokenizer behind [`count_tokens`](Self::count_tokens) and the
+    /// offline models' usage metrics. Defaults to a whitespace split.
+    fn tokenizer(&self) -> Arc<dyn Tokenizer> {
+        Arc::new(WhitespaceTokenizer)
+    }
+
+    fn count_tokens(&self, messages: &[ChatMessage]) -> usize {
+        self.tokenizer().count_tokens(messages)
+    }
+
+    fn context_usage(&self, messages: &[ChatMessage], model_limit: usize) -> ContextUsage {
+        ContextUsage::new(self.count_tokens(messages), model_limit)
+    }
 }
 
-fn build_usage(prompt: &str, completion: &str) -> UsageMetrics {
+/// Shared models, including the `Arc<dyn LLMModel>` a [`ModelRegistry`]
+/// hands out, are models themselves.
+#[async_trait]
+impl<M: LLMModel + ?Sized> LLMModel for Arc<M> {
+    async fn generate(
+        &self,
+        prompt: &str,
+        options: &GenerationOptions,
+    ) -> Result<LLMResponse, ModelError> {
+        (**self).generate(prompt, options).await
+    }
+
+    async fn stream(
+        &self,
+        prompt: &str,
+        options: &GenerationOptions,
+    ) -> Result<EventStream, ModelError> {
+        (**self).stream(prompt, options).await
+    }
+
+    fn supports_tools(&self) -> bool {
+        (**self).supports_tools()
+    }
+
+    async fn chat(
+        &self,
+        messages: &[ChatMessage],
+        options: &ChatOptions,
+    ) -> Result<LLMResponse, ModelError> {
+        (**self).chat(messages, options).await
+    }
+
+    fn tokenizer(&self) -> Arc<dyn Tokenizer> {
+        (**self).tokenizer()
+    }
+
+    fn count_tokens(&self, messages: &[ChatMessage]) -> usize {
+        (**self).count_tokens(messages)
+    }
+
+    fn context_usage(&self, messages: &[ChatMessage], model_limit: usize) -> ContextUsage {
+        (**self).context_usage(messages, model_limit)
+    }
+}
+
+fn build_usage(tokenizer: &dyn Tokenizer, prompt: &str, completion: &str) -> UsageMetrics {
     UsageMetrics {
-        prompt_tokens: prompt.split_whitespace().count(),
-        completion_tokens: completion.split_whitespace().count(),
+        prompt_tokens: tokenizer.count(prompt),
+        completion_tokens: tokenizer.count(completion),
     }
 }
 
-fn token_stream_from_content(content: &str) -> TokenStream {
-    let tokens: Vec<String> = content.split_whitespace().map(ToOwned::to_owned).collect();
-    Box::pin(stream::iter(tokens))
+/// Replays locally produced `tokens` as space-separated text deltas followed
+/// by usage and a `Stop` completion.
+fn event_stream(tokens: Vec<Token>, usage: UsageMetrics) -> EventStream {
+    let mut events: Vec<StreamEvent> = tokens
+        .into_iter()
+        .enumerate()
+        .map(|(index, token)| StreamEvent::TextDelta {
+            text: if index == 0 {
+                token
+            } else {
+                format!(" {token}")
+            },
+        })
+        .collect();
+    events.push(StreamEvent::Usage { usage });
+    events.push(StreamEvent::Done {
+        finish_reason: Some(FinishReason::Stop),
+    });
+    Box::pin(stream::iter(events))
+}
+
+fn event_stream_from_content(
+    tokenizer: &dyn Tokenizer,
+    prompt: &str,
+    content: &str,
+) -> EventStream {
+    let tokens = content.split_whitespace().map(ToOwned::to_owned).collect();
+    event_stream(tokens, build_usage(tokenizer, prompt, content))
 }
 
 pub struct OpenAIChatModel {
     pub model: String,
     pub supports_tools: bool,
     pub reasoning: bool,
+    /// Live API connection; without one the model echoes prompts offline.
+    #[cfg(feature = "openai")]
+    pub connection: Option<OpenAIConnection>,
 }
 
 impl OpenAIChatModel {
+    pub fn new(model: impl Into<String>) -> Self {
+        Self {
+            model: model.into(),
+            supports_tools: false,
+            reasoning: false,
+            #[cfg(feature = "openai")]
+            connection: None,
+        }
+    }
+
+    #[cfg(feature = "openai")]
+    pub fn with_connection(mut self, connection: OpenAIConnection) -> Self {
+        self.connection = Some(connection);
+        self
+    }
+
+    /// Sends `messages` to the Chat Completions API.
+    ///
+    /// Fails with [`OpenAIError::Decode`] when no connection is configured.
+    #[cfg(feature = "openai")]
+    pub async fn complete(
+        &self,
+        messages: &[ChatMessage],
+        options: &ChatOptions,
+    ) -> Result<LLMResponse, OpenAIError> {
+        let connection = self
+            .connection
+            .as_ref()
+            .ok_or_else(|| OpenAIError::Decode("no connection configured".into()))?;
+        connection
+            .complete(&self.model, messages, options, self.metadata())
+            .await
+    }
+
     fn metadata(&self) -> ModelMetadata {
         ModelMetadata {
             provider: "openai".into(),
             model: self.model.clone(),
             supports_tools: self.supports_tools,
             is_reasoning: self.reasoning,
+            annotations: BTreeMap::new(),
         }
     }
 }
 
 #[async_trait]
 impl LLMModel for OpenAIChatModel {
-    async fn generate(&self, prompt: &str) -> LLMResponse {
+    async fn generate(
+        &self,
+        prompt: &str,
+        options: &GenerationOptions,
+    ) -> Result<LLMResponse, ModelError> {
+        #[cfg(feature = "openai")]
+        if self.connection.is_some() {
+            return self
+                .chat(
+                    &[ChatMessage::user(prompt)],
+                    &ChatOptions::default().with_generation(options.clone()),
+                )
+                .await;
+        }
+
         let content = if self.reasoning {
             format!("[reasoning:{}] {}", self.model, prompt)
         } else {
@@ -92,54 +875,142 @@ impl LLMModel for OpenAIChatModel {
             vec![ToolCallInfo {
                 name: "auto_tool".into(),
                 arguments: serde_json::json!({"prompt": prompt}),
+                id: None,
             }]
         } else {
             Vec::new()
         };
 
-        LLMResponse {
-            usage: build_usage(prompt, &content),
+        let content = options.truncate(&content);
+        Ok(LLMResponse {
+            usage: build_usage(&*self.tokenizer(), prompt, &content),
             content,
+            reasoning: None,
             tool_calls,
             metadata: self.metadata(),
-        }
+            finish_reason: None,
+        })
     }
 
-    async fn stream(&self, prompt: &str) -> TokenStream {
+    async fn stream(
+        &self,
+        prompt: &str,
+        options: &GenerationOptions,
+    ) -> Result<EventStream, ModelError> {
+        #[cfg(feature = "openai")]
+        if let Some(connection) = &self.connection {
+            let messages = [ChatMessage::user(prompt)];
+            return Ok(connection.stream(&self.model, &messages, options).await?);
+        }
+
         let content = if self.reasoning {
             format!("reasoning {}", prompt)
         } else {
             format!("chat {}", prompt)
         };
 
-        token_stream_from_content(&content)
+        Ok(event_stream_from_content(
+            &*self.tokenizer(),
+            prompt,
+            &options.truncate(&content),
+        ))
     }
 
     fn supports_tools(&self) -> bool {
         self.supports_tools
     }
+
+    fn tokenizer(&self) -> Arc<dyn Tokenizer> {
+        let metadata = self.metadata();
+        tokenizer_for(&metadata.provider, &metadata.model)
+    }
+
+    #[cfg(feature = "openai")]
+    async fn chat(
+        &self,
+        messages: &[ChatMessage],
+        options: &ChatOptions,
+    ) -> Result<LLMResponse, ModelError> {
+        if self.connection.is_some() {
+            return Ok(self.complete(messages, options).await?);
+        }
+        self.generate(&flatten_messages(messages), &options.generation)
+            .await
+    }
 }
 
 pub struct AzureOpenAIModel {
     pub deployment: String,
     pub supports_tools: bool,
     pub reasoning: bool,
+    /// Live resource connection; without one the model echoes prompts offline.
+    #[cfg(feature = "azure")]
+    pub connection: Option<AzureOpenAIConnection>,
 }
 
 impl AzureOpenAIModel {
+    pub fn new(deployment: impl Into<String>) -> Self {
+        Self {
+            deployment: deployment.into(),
+            supports_tools: false,
+            reasoning: false,
+            #[cfg(feature = "azure")]
+            connection: None,
+        }
+    }
+
+    #[cfg(feature = "azure")]
+    pub fn with_connection(mut self, connection: AzureOpenAIConnection) -> Self {
+        self.connection = Some(connection);
+        self
+    }
+
+    /// Sends `messages` to the deployment's Chat Completions endpoint.
+    ///
+    /// Fails with [`OpenAIError::Decode`] when no connection is configured.
+    #[cfg(feature = "azure")]
+    pub async fn complete(
+        &self,
+        messages: &[ChatMessage],
+        options: &ChatOptions,
+    ) -> Result<LLMResponse, OpenAIError> {
+        let connection = self
+            .connection
+            .as_ref()
+            .ok_or_else(|| OpenAIError::Decode("no connection configured".into()))?;
+        connection
+            .complete(&self.deployment, messages, options, self.metadata())
+            .await
+    }
+
     fn metadata(&self) -> ModelMetadata {
         ModelMetadata {
             provider: "azure_openai".into(),
             model: self.deployment.clone(),
             supports_tools: self.supports_tools,
             is_reasoning: self.reasoning,
+            annotations: BTreeMap::new(),
         }
     }
 }
 
 #[async_trait]
 impl LLMModel for AzureOpenAIModel {
-    async fn generate(&self, prompt: &str) -> LLMResponse {
+    async fn generate(
+        &self,
+        prompt: &str,
+        options: &GenerationOptions,
+    ) -> Result<LLMResponse, ModelError> {
+        #[cfg(feature = "azure")]
+        if self.connection.is_some() {
+            return self
+                .chat(
+                    &[ChatMessage::user(prompt)],
+                    &ChatOptions::default().with_generation(options.clone()),
+                )
+                .await;
+        }
+
         let content = if self.reasoning {
             format!("[azure-reasoning:{}] {}", self.deployment, prompt)
         } else {
@@ -150,108 +1021,394 @@ impl LLMModel for AzureOpenAIModel {
             vec![ToolCallInfo {
                 name: "azure_tool".into(),
                 arguments: serde_json::json!({"input": prompt}),
+                id: None,
             }]
         } else {
             Vec::new()
         };
 
-        LLMResponse {
-            usage: build_usage(prompt, &content),
+        let content = options.truncate(&content);
+        Ok(LLMResponse {
+            usage: build_usage(&*self.tokenizer(), prompt, &content),
             content,
+            reasoning: None,
             tool_calls,
             metadata: self.metadata(),
-        }
+            finish_reason: None,
+        })
     }
 
-    async fn stream(&self, prompt: &str) -> TokenStream {
+    async fn stream(
+        &self,
+        prompt: &str,
+        options: &GenerationOptions,
+    ) -> Result<EventStream, ModelError> {
+        #[cfg(feature = "azure")]
+        if let Some(connection) = &self.connection {
+            let messages = [ChatMessage::user(prompt)];
+            return Ok(connection
+                .stream(&self.deployment, &messages, options)
+                .await?);
+        }
+
         let content = format!("azure {}", prompt);
-        token_stream_from_content(&content)
+        Ok(event_stream_from_content(
+            &*self.tokenizer(),
+            prompt,
+            &options.truncate(&content),
+        ))
     }
 
     fn supports_tools(&self) -> bool {
         self.supports_tools
     }
+
+    fn tokenizer(&self) -> Arc<dyn Tokenizer> {
+        let metadata = self.metadata();
+        tokenizer_for(&metadata.provider, &metadata.model)
+    }
+
+    #[cfg(feature = "azure")]
+    async fn chat(
+        &self,
+        messages: &[ChatMessage],
+        options: &ChatOptions,
+    ) -> Result<LLMResponse, ModelError> {
+        if self.connection.is_some() {
+            return Ok(self.complete(messages, options).await?);
+        }
+        self.generate(&flatten_messages(messages), &options.generation)
+            .await
+    }
 }
 
 pub struct OllamaModel {
     pub model: String,
     pub supports_tools: bool,
+    /// Live server connection; without one the model echoes prompts offline.
+    #[cfg(feature = "ollama")]
+    pub connection: Option<OllamaConnection>,
+    #[cfg(feature = "ollama")]
+    pub options: OllamaOptions,
 }
 
 impl OllamaModel {
+    pub fn new(model: impl Into<String>) -> Self {
+        Self {
+            model: model.into(),
+            supports_tools: false,
+            #[cfg(feature = "ollama")]
+            connection: None,
+            #[cfg(feature = "ollama")]
+            options: OllamaOptions::default(),
+        }
+    }
+
+    #[cfg(feature = "ollama")]
+    pub fn with_connection(mut self, connection: OllamaConnection) -> Self {
+        self.connection = Some(connection);
+        self
+    }
+
+    #[cfg(feature = "ollama")]
+    pub fn with_options(mut self, options: OllamaOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    #[cfg(feature = "ollama")]
+    fn connection(&self) -> Result<&OllamaConnection, OllamaError> {
+        self.connection
+            .as_ref()
+            .ok_or_else(|| OllamaError::Decode("no connection configured".into()))
+    }
+
+    /// Sends `messages` to `/api/chat`.
+    ///
+    /// Fails with [`OllamaError::Decode`] when no connection is configured.
+    #[cfg(feature = "ollama")]
+    pub async fn complete(
+        &self,
+        messages: &[ChatMessage],
+        options: &ChatOptions,
+    ) -> Result<LLMResponse, OllamaError> {
+        self.connection()?
+            .complete(
+                &self.model,
+                messages,
+                options,
+                &self.options,
+                self.metadata(),
+            )
+            .await
+    }
+
+    /// Checks that the model is installed on the server, pulling it when the
+    /// connection allows.
+    #[cfg(feature = "ollama")]
+    pub async fn ensure_available(&self) -> Result<(), OllamaError> {
+        self.connection()?.ensure_model(&self.model).await
+    }
+
     fn metadata(&self) -> ModelMetadata {
         ModelMetadata {
             provider: "ollama".into(),
             model: self.model.clone(),
             supports_tools: self.supports_tools,
             is_reasoning: false,
+            annotations: BTreeMap::new(),
         }
     }
 }
 
 #[async_trait]
 impl LLMModel for OllamaModel {
-    async fn generate(&self, prompt: &str) -> LLMResponse {
+    async fn generate(
+        &self,
+        prompt: &str,
+        options: &GenerationOptions,
+    ) -> Result<LLMResponse, ModelError> {
+        #[cfg(feature = "ollama")]
+        if self.connection.is_some() {
+            return self
+                .chat(
+                    &[ChatMessage::user(prompt)],
+                    &ChatOptions::default().with_generation(options.clone()),
+                )
+                .await;
+        }
+
         let content = format!("[ollama:{}] {}", self.model, prompt);
-        LLMResponse {
-            usage: build_usage(prompt, &content),
+        let content = options.truncate(&content);
+        Ok(LLMResponse {
+            usage: build_usage(&*self.tokenizer(), prompt, &content),
             content,
+            reasoning: None,
             tool_calls: Vec::new(),
             metadata: self.metadata(),
-        }
+            finish_reason: None,
+        })
     }
 
-    async fn stream(&self, prompt: &str) -> TokenStream {
-        token_stream_from_content(prompt)
+    async fn stream(
+        &self,
+        prompt: &str,
+        options: &GenerationOptions,
+    ) -> Result<EventStream, ModelError> {
+        #[cfg(feature = "ollama")]
+        if let Some(connection) = &self.connection {
+            let messages = [ChatMessage::user(prompt)];
+            return Ok(connection
+                .stream(&self.model, &messages, options, &self.options)
+                .await?);
+        }
+
+        Ok(event_stream_from_content(
+            &*self.tokenizer(),
+            prompt,
+            &options.truncate(prompt),
+        ))
     }
 
     fn supports_tools(&self) -> bool {
         self.supports_tools
     }
+
+    fn tokenizer(&self) -> Arc<dyn Tokenizer> {
+        let metadata = self.metadata();
+        tokenizer_for(&metadata.provider, &metadata.model)
+    }
+
+    #[cfg(feature = "ollama")]
+    async fn chat(
+        &self,
+        messages: &[ChatMessage],
+        options: &ChatOptions,
+    ) -> Result<LLMResponse, ModelError> {
+        if self.connection.is_some() {
+            return Ok(self.complete(messages, options).await?);
+        }
+        self.generate(&flatten_messages(messages), &options.generation)
+            .await
+    }
 }
 
-pub struct RestModel {
-    pub endpoint: String,
+/// A model behind any server that speaks the OpenAI Chat Completions wire
+/// format, such as vLLM, LM Studio, Together or Groq.
+///
+/// With the `openai` feature, requests go to `{base_url}/chat/completions`
+/// with tool calling and streaming; without it the model echoes prompts
+/// offline.
+pub struct OpenAICompatibleModel {
+    /// API root including any version segment, e.g. `http://localhost:8000/v1`.
+    pub base_url: String,
     pub model: String,
     pub supports_tools: bool,
+    /// Sent as a bearer token; local servers usually need none.
+    pub api_key: Option<Secret<String>>,
+    /// HTTP client used to reach `base_url`.
+    pub client: reqwest::Client,
 }
 
-impl RestModel {
+impl OpenAICompatibleModel {
+    /// A trailing `/` on `base_url` is ignored.
+    pub fn new(base_url: impl Into<String>, model: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            model: model.into(),
+            supports_tools: false,
+            api_key: None,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    pub fn with_client_config(
+        base_url: impl Into<String>,
+        model: impl Into<String>,
+        config: &HttpClientConfig,
+    ) -> Result<Self, HttpClientError> {
+        Ok(Self {
+            client: config.build_client()?,
+            ..Self::new(base_url, model)
+        })
+    }
+
+    pub fn with_api_key(mut self, api_key: impl Into<Secret<String>>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    #[cfg(feature = "openai")]
+    fn connection(&self) -> OpenAIConnection {
+        OpenAIConnection {
+            base_url: self.base_url.clone(),
+            api_key: self
+                .api_key
+                .clone()
+                .unwrap_or_else(|| Secret::new(String::new())),
+            organization: None,
+            client: self.client.clone(),
+        }
+    }
+
     fn metadata(&self) -> ModelMetadata {
         ModelMetadata {
-            provider: "rest".into(),
+            provider: "openai_compatible".into(),
             model: self.model.clone(),
             supports_tools: self.supports_tools,
             is_reasoning: false,
+            annotations: BTreeMap::new(),
         }
     }
 }
 
 #[async_trait]
-impl LLMModel for RestModel {
-    async fn generate(&self, prompt: &str) -> LLMResponse {
-        let content = format!("[rest:{}] {} => {}", self.model, self.endpoint, prompt);
-        LLMResponse {
-            usage: build_usage(prompt, &content),
-            content,
-            tool_calls: Vec::new(),
-            metadata: self.metadata(),
+impl LLMModel for OpenAICompatibleModel {
+    async fn generate(
+        &self,
+        prompt: &str,
+        options: &GenerationOptions,
+    ) -> Result<LLMResponse, ModelError> {
+        #[cfg(feature = "openai")]
+        {
+            self.chat(
+                &[ChatMessage::user(prompt)],
+                &ChatOptions::default().with_generation(options.clone()),
+            )
+            .await
+        }
+
+        #[cfg(not(feature = "openai"))]
+        {
+            let content = format!("[{}@{}] {}", self.model, self.base_url, prompt);
+            let content = options.truncate(&content);
+            Ok(LLMResponse {
+                usage: build_usage(&*self.tokenizer(), prompt, &content),
+                content,
+                reasoning: None,
+                tool_calls: Vec::new(),
+                metadata: self.metadata(),
+                finish_reason: None,
+            })
         }
     }
 
-    async fn stream(&self, prompt: &str) -> TokenStream {
-        token_stream_from_content(&format!("{} {}", self.model, prompt))
+    async fn stream(
+        &self,
+        prompt: &str,
+        options: &GenerationOptions,
+    ) -> Result<EventStream, ModelError> {
+        #[cfg(feature = "openai")]
+        {
+            let messages = [ChatMessage::user(prompt)];
+            Ok(self
+                .connection()
+                .stream(&self.model, &messages, options)
+                .await?)
+        }
+
+        #[cfg(not(feature = "openai"))]
+        Ok(event_stream_from_content(
+            &*self.tokenizer(),
+            prompt,
+            &options.truncate(&format!("{} {}", self.model, prompt)),
+        ))
     }
 
     fn supports_tools(&self) -> bool {
         self.supports_tools
     }
+
+    #[cfg(feature = "openai")]
+    async fn chat(
+        &self,
+        messages: &[ChatMessage],
+        options: &ChatOptions,
+    ) -> Result<LLMResponse, ModelError> {
+        Ok(self
+            .connection()
+            .complete(&self.model, messages, options, self.metadata())
+            .await?)
+    }
+
+    fn tokenizer(&self) -> Arc<dyn Tokenizer> {
+        let metadata = self.metadata();
+        tokenizer_for(&metadata.provider, &metadata.model)
+    }
 }
 
 pub struct EmbeddingModel {
     pub model: String,
 }
 
+impl EmbeddingModel {
+    /// Length of the vectors produced by the [`Embedder`] impl.
+    pub const DIMENSIONS: usize = 64;
+}
+
+/// Offline hashed bag-of-words embedding: each lowercased word bumps one of
+/// [`EmbeddingModel::DIMENSIONS`] buckets, so texts sharing words score higher.
+#[async_trait]
+impl Embedder for EmbeddingModel {
+    async fn embed(&self, text: &str) -> Vec<f32> {
+        let mut vector = vec![0.0; Self::DIMENSIONS];
+        for word in text
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+        {
+            // FNV-1a keeps buckets stable across runs and platforms.
+            let hash = word
+                .to_lowercase()
+                .bytes()
+                .fold(0xcbf29ce484222325u64, |hash, byte| {
+                    (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+                });
+            vector[(hash % Self::DIMENSIONS as u64) as usize] += 1.0;
+        }
+        vector
+    }
+}
+
 impl EmbeddingModel {
     fn metadata(&self) -> ModelMetadata {
         ModelMetadata {
@@ -259,24 +1416,39 @@ impl EmbeddingModel {
             model: self.model.clone(),
             supports_tools: false,
             is_reasoning: false,
+            annotations: BTreeMap::new(),
         }
     }
 }
 
 #[async_trait]
 impl LLMModel for EmbeddingModel {
-    async fn generate(&self, prompt: &str) -> LLMResponse {
+    async fn generate(
+        &self,
+        prompt: &str,
+        _options: &GenerationOptions,
+    ) -> Result<LLMResponse, ModelError> {
         let embedding = format!("embedding:{}", self.model);
-        LLMResponse {
-            usage: build_usage(prompt, &embedding),
+        Ok(LLMResponse {
+            usage: build_usage(&WhitespaceTokenizer, prompt, &embedding),
             content: embedding,
+            reasoning: None,
             tool_calls: Vec::new(),
             metadata: self.metadata(),
-        }
+            finish_reason: None,
+        })
     }
 
-    async fn stream(&self, prompt: &str) -> TokenStream {
-        token_stream_from_content(&format!("embedding {}", prompt))
+    async fn stream(
+        &self,
+        prompt: &str,
+        _options: &GenerationOptions,
+    ) -> Result<EventStream, ModelError> {
+        Ok(event_stream_from_content(
+            &WhitespaceTokenizer,
+            prompt,
+            &format!("embedding {}", prompt),
+        ))
     }
 
     fn supports_tools(&self) -> bool {
@@ -289,27 +1461,36 @@ pub struct StubModel;
 
 #[async_trait]
 impl LLMModel for StubModel {
-    async fn generate(&self, prompt: &str) -> LLMResponse {
+    async fn generate(
+        &self,
+        prompt: &str,
+        _options: &GenerationOptions,
+    ) -> Result<LLMResponse, ModelError> {
         let content = format!("echo: {prompt}");
-        LLMResponse {
-            content: content.clone(),
-            usage: UsageMetrics {
-                prompt_tokens: prompt.len(),
-                completion_tokens: 2,
-            },
+        Ok(LLMResponse {
+            usage: build_usage(&WhitespaceTokenizer, prompt, &content),
+            content,
+            reasoning: None,
             tool_calls: Vec::new(),
             metadata: ModelMetadata {
                 provider: "stub".into(),
                 model: "stub".into(),
                 supports_tools: false,
                 is_reasoning: false,
+                annotations: BTreeMap::new(),
             },
-        }
+            finish_reason: None,
+        })
     }
 
-    async fn stream(&self, prompt: &str) -> TokenStream {
+    async fn stream(
+        &self,
+        prompt: &str,
+        _options: &GenerationOptions,
+    ) -> Result<EventStream, ModelError> {
         let tokens = vec!["echo".to_string(), prompt.to_string()];
-        Box::pin(stream::iter(tokens))
+        let usage = build_usage(&WhitespaceTokenizer, prompt, &tokens.join(" "));
+        Ok(event_stream(tokens, usage))
     }
 
     fn supports_tools(&self) -> bool {
@@ -321,36 +1502,46 @@ pub struct RandomReasoner;
 
 #[async_trait]
 impl LLMModel for RandomReasoner {
-    async fn generate(&self, prompt: &str) -> LLMResponse {
-        let mut rng = rand::thread_rng();
+    async fn generate(
+        &self,
+        prompt: &str,
+        options: &GenerationOptions,
+    ) -> Result<LLMResponse, ModelError> {
+        let mut rng = StdRng::seed_from_u64(options.seed.unwrap_or_else(rand::random));
         let calls = if rng.gen_bool(0.2) {
             vec![ToolCallInfo {
                 name: "math".into(),
                 arguments: serde_json::json!({"expression": "1+1"}),
+                id: None,
             }]
         } else {
             Vec::new()
         };
         let content = format!("reasoned: {prompt}");
-        LLMResponse {
-            content: content.clone(),
-            usage: UsageMetrics {
-                prompt_tokens: prompt.len(),
-                completion_tokens: 3,
-            },
+        Ok(LLMResponse {
+            usage: build_usage(&WhitespaceTokenizer, prompt, &content),
+            content,
+            reasoning: None,
             tool_calls: calls,
             metadata: ModelMetadata {
                 provider: "random".into(),
                 model: "reasoner".into(),
                 supports_tools: true,
                 is_reasoning: true,
+                annotations: BTreeMap::new(),
             },
-        }
+            finish_reason: None,
+        })
     }
 
-    async fn stream(&self, prompt: &str) -> TokenStream {
+    async fn stream(
+        &self,
+        prompt: &str,
+        _options: &GenerationOptions,
+    ) -> Result<EventStream, ModelError> {
         let chunks = vec!["reasoned".to_string(), prompt.to_string()];
-        Box::pin(stream::iter(chunks))
+        let usage = build_usage(&WhitespaceTokenizer, prompt, &chunks.join(" "));
+        Ok(event_stream(chunks, usage))
     }
 
     fn supports_tools(&self) -> bool {
@@ -0,0 +1,446 @@
+//! Fully offline inference over quantized GGUF models of the Llama family
+//! (Llama, Mistral and compatible fine-tunes), run on the CPU with candle.
+//! Nothing leaves the machine: weights and tokenizer are read from disk.
+
+use std::fs::File;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use candle_core::quantized::gguf_file;
+use candle_core::{Device, Tensor};
+use candle_transformers::generation::LogitsProcessor;
+use candle_transformers::models::quantized_llama::ModelWeights;
+use candle_transformers::utils::apply_repeat_penalty;
+use thiserror::Error;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+
+use crate::{
+    EventStream, FinishReason, GenerationOptions, LLMModel, LLMResponse, ModelError, ModelMetadata,
+    StreamEvent, Tokenizer, UsageMetrics,
+};
+
+/// End-of-turn markers of common chat fine-tunes; whichever exist in the
+/// tokenizer's vocabulary end generation.
+const STOP_TOKENS: &[&str] = &[
+    "</s>",
+    "<|eot_id|>",
+    "<|end_of_text|>",
+    "<|im_end|>",
+    "<|endoftext|>",
+];
+
+/// Seed for sampling when the request does not set one.
+const DEFAULT_SEED: u64 = 299_792_458;
+
+#[derive(Debug, Clone)]
+pub struct LocalModelConfig {
+    /// Quantized weights in GGUF format.
+    pub model_path: PathBuf,
+    /// The model's `tokenizer.json`, in Hugging Face format.
+    pub tokenizer_path: PathBuf,
+    /// Reported as the model name in response metadata.
+    pub name: String,
+    /// Prompt plus completion tokens the model can attend to.
+    pub context_length: usize,
+    /// Completion cap for requests that do not set `max_tokens`.
+    pub max_tokens: usize,
+    /// Penalty applied to tokens among the last `repeat_last_n`; `1.0`
+    /// disables it.
+    pub repeat_penalty: f32,
+    pub repeat_last_n: usize,
+}
+
+impl LocalModelConfig {
+    pub fn new(model_path: impl Into<PathBuf>, tokenizer_path: impl Into<PathBuf>) -> Self {
+        let model_path = model_path.into();
+        let name = model_path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "local".into());
+        Self {
+            model_path,
+            tokenizer_path: tokenizer_path.into(),
+            name,
+            context_length: 4096,
+            max_tokens: 512,
+            repeat_penalty: 1.1,
+            repeat_last_n: 64,
+        }
+    }
+
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    pub fn with_context_length(mut self, context_length: usize) -> Self {
+        self.context_length = context_length;
+        self
+    }
+
+    pub fn with_max_tokens(mut self, max_tokens: usize) -> Self {
+        self.max_tokens = max_tokens;
+        self
+    }
+
+    pub fn with_repeat_penalty(mut self, penalty: f32, last_n: usize) -> Self {
+        self.repeat_penalty = penalty;
+        self.repeat_last_n = last_n;
+        self
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum LocalModelError {
+    #[error("failed to read model file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("inference failed: {0}")]
+    Inference(#[from] candle_core::Error),
+    #[error("tokenizer failed: {0}")]
+    Tokenizer(String),
+    #[error("prompt of {prompt} tokens does not fit a {context}-token context")]
+    ContextOverflow { prompt: usize, context: usize },
+}
+
+impl From<LocalModelError> for ModelError {
+    fn from(error: LocalModelError) -> Self {
+        match error {
+            LocalModelError::ContextOverflow { .. } => ModelError::Api {
+                status: 400,
+                message: error.to_string(),
+            },
+            error => ModelError::InvalidResponse(error.to_string()),
+        }
+    }
+}
+
+/// A GGUF model loaded into memory, implementing [`LLMModel`] without any
+/// network access.
+///
+/// Requests run one at a time on a blocking thread. `stream` yields text as
+/// each token is sampled. Prompts are passed to the model verbatim, so chat
+/// fine-tunes answer best when given their own prompt template.
+pub struct LocalModel {
+    engine: Arc<Engine>,
+}
+
+struct Engine {
+    config: LocalModelConfig,
+    weights: Mutex<ModelWeights>,
+    tokenizer: Arc<tokenizers::Tokenizer>,
+    stop_tokens: Vec<u32>,
+}
+
+struct Completion {
+    text: String,
+    usage: UsageMetrics,
+    finish_reason: FinishReason,
+}
+
+impl LocalModel {
+    /// Reads the tokenizer and weights named by `config`. Large models take
+    /// a while; call from a blocking context.
+    pub fn load(config: LocalModelConfig) -> Result<Self, LocalModelError> {
+        let tokenizer = tokenizers::Tokenizer::from_file(&config.tokenizer_path)
+            .map_err(|error| LocalModelError::Tokenizer(error.to_string()))?;
+        let mut file = File::open(&config.model_path)?;
+        let content = gguf_file::Content::read(&mut file)?;
+        let weights = ModelWeights::from_gguf(content, &mut file, &Device::Cpu)?;
+        let stop_tokens = STOP_TOKENS
+            .iter()
+            .filter_map(|token| tokenizer.token_to_id(token))
+            .collect();
+        Ok(Self {
+            engine: Arc::new(Engine {
+                config,
+                weights: Mutex::new(weights),
+                tokenizer: Arc::new(tokenizer),
+                stop_tokens,
+            }),
+        })
+    }
+
+    pub fn config(&self) -> &LocalModelConfig {
+        &self.engine.config
+    }
+
+    fn metadata(&self) -> ModelMetadata {
+        ModelMetadata {
+            provider: "local".into(),
+            model: self.engine.config.name.clone(),
+            ..ModelMetadata::default()
+        }
+    }
+}
+
+impl Engine {
+    fn encode(&self, prompt: &str) -> Result<Vec<u32>, LocalModelError> {
+        let ids = self
+            .tokenizer
+            .encode(prompt, true)
+            .map_err(|error| LocalModelError::Tokenizer(error.to_string()))?
+            .get_ids()
+            .to_vec();
+        if ids.len() >= self.config.context_length {
+            return Err(LocalModelError::ContextOverflow {
+                prompt: ids.len(),
+                context: self.config.context_length,
+            });
+        }
+        Ok(ids)
+    }
+
+    /// Samples a completion for `prompt`, handing each new piece of text to
+    /// `on_text` until it returns false.
+    fn generate(
+        &self,
+        prompt: &[u32],
+        options: &GenerationOptions,
+        mut on_text: impl FnMut(&str) -> bool,
+    ) -> Result<Completion, LocalModelError> {
+        let limit = options
+            .max_tokens
+            .map_or(self.config.max_tokens, |max| max as usize)
+            .min(self.config.context_length - prompt.len());
+        let mut sampler = LogitsProcessor::new(
+            options.seed.unwrap_or(DEFAULT_SEED),
+            options.temperature.map(f64::from),
+            options.top_p.map(f64::from),
+        );
+        let mut weights = self.weights.lock().expect("local model mutex poisoned");
+
+        let mut context = prompt.to_vec();
+        let mut decoder = TextDecoder::default();
+        let mut text = String::new();
+        let mut generated = 0;
+        let mut finish_reason = FinishReason::Length;
+        let mut input = Tensor::new(prompt, &Device::Cpu)?.unsqueeze(0)?;
+        let mut position = 0;
+        while generated < limit {
+            let mut logits = weights.forward(&input, position)?.squeeze(0)?;
+            position += input.dim(1)?;
+            if self.config.repeat_penalty != 1.0 {
+                let recent = context.len().saturating_sub(self.config.repeat_last_n);
+                logits =
+                    apply_repeat_penalty(&logits, self.config.repeat_penalty, &context[recent..])?;
+            }
+            let token = sampler.sample(&logits)?;
+            generated += 1;
+            if self.stop_tokens.contains(&token) {
+                finish_reason = FinishReason::Stop;
+                break;
+            }
+            context.push(token);
+
+            let delta = decoder.push(&self.tokenizer, token)?;
+            let emitted = text.len();
+            text.push_str(&delta);
+            let stop = options
+                .stop
+                .iter()
+                .filter(|stop| !stop.is_empty())
+                .filter_map(|stop| text.find(stop.as_str()))
+                .min();
+            if let Some(end) = stop {
+                if end > emitted {
+                    on_text(&text[emitted..end]);
+                }
+                text.truncate(end);
+                finish_reason = FinishReason::Stop;
+                break;
+            }
+            if !delta.is_empty() && !on_text(&delta) {
+                break;
+            }
+            input = Tensor::new(&[token], &Device::Cpu)?.unsqueeze(0)?;
+        }
+        Ok(Completion {
+            text,
+            usage: UsageMetrics {
+                prompt_tokens: prompt.len(),
+                completion_tokens: generated,
+            },
+            finish_reason,
+        })
+    }
+}
+
+/// Turns a growing token sequence into text increments, holding back
+/// output while the tail decodes to an incomplete UTF-8 character.
+#[derive(Default)]
+struct TextDecoder {
+    tokens: Vec<u32>,
+    emitted: usize,
+}
+
+impl TextDecoder {
+    fn push(
+        &mut self,
+        tokenizer: &tokenizers::Tokenizer,
+        token: u32,
+    ) -> Result<String, LocalModelError> {
+        self.tokens.push(token);
+        let text = tokenizer
+            .decode(&self.tokens, true)
+            .map_err(|error| LocalModelError::Tokenizer(error.to_string()))?;
+        if text.ends_with('\u{FFFD}') {
+            return Ok(String::new());
+        }
+        let delta = text.get(self.emitted..).unwrap_or_default().to_string();
+        self.emitted = self.emitted.max(text.len());
+        Ok(delta)
+    }
+}
+
+/// Counts with the model's own vocabulary.
+struct LocalTokenizer(Arc<tokenizers::Tokenizer>);
+
+impl Tokenizer for LocalTokenizer {
+    fn count(&self, text: &str) -> usize {
+        self.0
+            .encode(text, false)
+            .map(|encoding| encoding.len())
+            .unwrap_or_else(|_| text.split_whitespace().count())
+    }
+}
+
+fn join_failed(error: tokio::task::JoinError) -> ModelError {
+    ModelError::InvalidResponse(format!("local inference task failed: {error}"))
+}
+
+#[async_trait]
+impl LLMModel for LocalModel {
+    async fn generate(
+        &self,
+        prompt: &str,
+        options: &GenerationOptions,
+    ) -> Result<LLMResponse, ModelError> {
+        let engine = self.engine.clone();
+        let prompt = engine.encode(prompt)?;
+        let options = options.clone();
+        let completion =
+            tokio::task::spawn_blocking(move || engine.generate(&prompt, &options, |_| true))
+                .await
+                .map_err(join_failed)??;
+        Ok(LLMResponse {
+            content: completion.text,
+            reasoning: None,
+            usage: completion.usage,
+            tool_calls: Vec::new(),
+            metadata: self.metadata(),
+            finish_reason: Some(completion.finish_reason),
+        })
+    }
+
+    /// Yields a text delta per decoded token. Dropping the stream stops
+    /// generation at the next token.
+    async fn stream(
+        &self,
+        prompt: &str,
+        options: &GenerationOptions,
+    ) -> Result<EventStream, ModelError> {
+        let engine = self.engine.clone();
+        let prompt = engine.encode(prompt)?;
+        let options = options.clone();
+        let (events, receiver) = mpsc::channel(64);
+        tokio::task::spawn_blocking(move || {
+            let result = engine.generate(&prompt, &options, |text| {
+                events
+                    .blocking_send(StreamEvent::TextDelta { text: text.into() })
+                    .is_ok()
+            });
+            let finish_reason = match result {
+                Ok(completion) => {
+                    let _ = events.blocking_send(StreamEvent::Usage {
+                        usage: completion.usage,
+                    });
+                    completion.finish_reason
+                }
+                Err(error) => {
+                    tracing::warn!(%error, "local model stream failed");
+                    FinishReason::Error
+                }
+            };
+            let _ = events.blocking_send(StreamEvent::Done {
+                finish_reason: Some(finish_reason),
+            });
+        });
+        Ok(Box::pin(ReceiverStream::new(receiver)))
+    }
+
+    fn supports_tools(&self) -> bool {
+        false
+    }
+
+    fn tokenizer(&self) -> Arc<dyn Tokenizer> {
+        Arc::new(LocalTokenizer(self.engine.tokenizer.clone()))
+    }
+}
+
+impl std::fmt::Debug for LocalModel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LocalModel")
+            .field("config", &self.engine.config)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    /// A word-level vocabulary small enough to write inline.
+    fn tokenizer(dir: &Path) -> PathBuf {
+        let path = dir.join("tokenizer.json");
+        let spec = serde_json::json!({
+            "version": "1.0",
+            "truncation": null,
+            "padding": null,
+            "added_tokens": [],
+            "normalizer": null,
+            "pre_tokenizer": {"type": "Whitespace"},
+            "post_processor": null,
+            "decoder": null,
+            "model": {
+                "type": "WordLevel",
+                "vocab": {"[UNK]": 0, "hello": 1, "local": 2, "world": 3},
+                "unk_token": "[UNK]"
+            }
+        });
+        std::fs::write(&path, spec.to_string()).unwrap();
+        path
+    }
+
+    #[test]
+    fn decodes_tokens_into_text_increments() {
+        let dir = std::env::temp_dir().join(format!("local-model-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let tokenizer = Arc::new(tokenizers::Tokenizer::from_file(tokenizer(&dir)).unwrap());
+
+        let mut decoder = TextDecoder::default();
+        let deltas: Vec<_> = [1, 2, 3]
+            .into_iter()
+            .map(|token| decoder.push(&tokenizer, token).unwrap())
+            .collect();
+        assert_eq!(deltas, vec!["hello", " local", " world"]);
+        assert_eq!(LocalTokenizer(tokenizer).count("hello local world"), 3);
+
+        let err = LocalModel::load(LocalModelConfig::new(
+            dir.join("missing.gguf"),
+            dir.join("tokenizer.json"),
+        ))
+        .unwrap_err();
+        assert!(matches!(err, LocalModelError::Io(_)));
+        assert!(matches!(
+            ModelError::from(LocalModelError::ContextOverflow {
+                prompt: 5000,
+                context: 4096
+            }),
+            ModelError::Api { status: 400, .. }
+        ));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,338 @@
+//! Composable hooks around any [`LLMModel`].
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::{
+    flatten_messages, ChatMessage, ChatOptions, EventStream, GenerationOptions, LLMModel,
+    LLMResponse, ModelError, Tokenizer,
+};
+
+/// A request on its way to the wrapped model, open to rewriting.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ModelRequest {
+    Generate {
+        prompt: String,
+        options: GenerationOptions,
+    },
+    Stream {
+        prompt: String,
+        options: GenerationOptions,
+    },
+    Chat {
+        messages: Vec<ChatMessage>,
+        options: ChatOptions,
+    },
+}
+
+impl ModelRequest {
+    /// Every piece of text the model will read: the prompt, or each
+    /// message's content.
+    pub fn texts_mut(&mut self) -> Vec<&mut String> {
+        match self {
+            ModelRequest::Generate { prompt, .. } | ModelRequest::Stream { prompt, .. } => {
+                vec![prompt]
+            }
+            ModelRequest::Chat { messages, .. } => messages
+                .iter_mut()
+                .map(|message| &mut message.content)
+                .collect(),
+        }
+    }
+}
+
+/// Hooks run by a [`MiddlewareModel`]. Each defaults to passing things
+/// through unchanged.
+#[async_trait]
+pub trait ModelMiddleware: Send + Sync {
+    /// Inspects or rewrites a request before it is sent. An error rejects
+    /// the request without calling the model.
+    async fn before_request(&self, _request: &mut ModelRequest) -> Result<(), ModelError> {
+        Ok(())
+    }
+
+    /// Inspects or rewrites a response. An error turns the call into a
+    /// failure, e.g. a [`ModelError::ContentFilter`] from a guardrail.
+    async fn after_response(&self, _response: &mut LLMResponse) -> Result<(), ModelError> {
+        Ok(())
+    }
+
+    /// Sees a failed call; returning a response recovers from it.
+    async fn on_error(&self, error: ModelError) -> Result<LLMResponse, ModelError> {
+        Err(error)
+    }
+}
+
+/// Runs a model call through a stack of [`ModelMiddleware`].
+///
+/// Requests pass through the middleware in the order it was added, and
+/// responses and errors come back through it in reverse, so the first
+/// middleware added is outermost: it sees the request first and the final
+/// outcome last. Streams only pass through `before_request`.
+pub struct MiddlewareModel<M: LLMModel> {
+    pub model: M,
+    pub middleware: Vec<Arc<dyn ModelMiddleware>>,
+}
+
+impl<M: LLMModel> MiddlewareModel<M> {
+    pub fn new(model: M) -> Self {
+        Self {
+            model,
+            middleware: Vec::new(),
+        }
+    }
+
+    pub fn with_middleware(mut self, middleware: impl ModelMiddleware + 'static) -> Self {
+        self.middleware.push(Arc::new(middleware));
+        self
+    }
+
+    async fn prepare(&self, mut request: ModelRequest) -> Result<ModelRequest, ModelError> {
+        for middleware in &self.middleware {
+            middleware.before_request(&mut request).await?;
+        }
+        Ok(request)
+    }
+
+    async fn call(&self, request: ModelRequest) -> Result<LLMResponse, ModelError> {
+        let request = self.prepare(request).await?;
+        let mut result = match &request {
+            ModelRequest::Generate { prompt, options }
+            | ModelRequest::Stream { prompt, options } => {
+                self.model.generate(prompt, options).await
+            }
+            ModelRequest::Chat { messages, options } => self.model.chat(messages, options).await,
+        };
+        for middleware in self.middleware.iter().rev() {
+            result = match result {
+                Ok(mut response) => middleware
+                    .after_response(&mut response)
+                    .await
+                    .map(|()| response),
+                Err(error) => middleware.on_error(error).await,
+            };
+        }
+        result
+    }
+}
+
+#[async_trait]
+impl<M: LLMModel> LLMModel for MiddlewareModel<M> {
+    async fn generate(
+        &self,
+        prompt: &str,
+        options: &GenerationOptions,
+    ) -> Result<LLMResponse, ModelError> {
+        self.call(ModelRequest::Generate {
+            prompt: prompt.to_string(),
+            options: options.clone(),
+        })
+        .await
+    }
+
+    async fn stream(
+        &self,
+        prompt: &str,
+        options: &GenerationOptions,
+    ) -> Result<EventStream, ModelError> {
+        let request = ModelRequest::Stream {
+            prompt: prompt.to_string(),
+            options: options.clone(),
+        };
+        match self.prepare(request).await? {
+            ModelRequest::Stream { prompt, options }
+            | ModelRequest::Generate { prompt, options } => {
+                self.model.stream(&prompt, &options).await
+            }
+            ModelRequest::Chat { messages, options } => {
+                self.model
+                    .stream(&flatten_messages(&messages), &options.generation)
+                    .await
+            }
+        }
+    }
+
+    fn supports_tools(&self) -> bool {
+        self.model.supports_tools()
+    }
+
+    async fn chat(
+        &self,
+        messages: &[ChatMessage],
+        options: &ChatOptions,
+    ) -> Result<LLMResponse, ModelError> {
+        self.call(ModelRequest::Chat {
+            messages: messages.to_vec(),
+            options: options.clone(),
+        })
+        .await
+    }
+
+    fn tokenizer(&self) -> Arc<dyn Tokenizer> {
+        self.model.tokenizer()
+    }
+}
+
+impl<M: LLMModel> std::fmt::Debug for MiddlewareModel<M> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MiddlewareModel")
+            .field("middleware", &self.middleware.len())
+            .finish()
+    }
+}
+
+/// Traces every request, response and failure at `debug`/`warn` level.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LoggingMiddleware;
+
+#[async_trait]
+impl ModelMiddleware for LoggingMiddleware {
+    async fn before_request(&self, request: &mut ModelRequest) -> Result<(), ModelError> {
+        match request {
+            ModelRequest::Generate { prompt, .. } | ModelRequest::Stream { prompt, .. } => {
+                tracing::debug!(prompt_chars = prompt.len(), "model request")
+            }
+            ModelRequest::Chat { messages, options } => tracing::debug!(
+                messages = messages.len(),
+                tools = options.tools.len(),
+                "model chat request"
+            ),
+        }
+        Ok(())
+    }
+
+    async fn after_response(&self, response: &mut LLMResponse) -> Result<(), ModelError> {
+        tracing::debug!(
+            provider = %response.metadata.provider,
+            model = %response.metadata.model,
+            prompt_tokens = response.usage.prompt_tokens,
+            completion_tokens = response.usage.completion_tokens,
+            tool_calls = response.tool_calls.len(),
+            "model response"
+        );
+        Ok(())
+    }
+
+    async fn on_error(&self, error: ModelError) -> Result<LLMResponse, ModelError> {
+        tracing::warn!(%error, kind = error.kind(), "model call failed");
+        Err(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StubModel;
+    use std::sync::Mutex;
+
+    /// Masks a fixed secret in everything sent to the model.
+    struct Redact(&'static str);
+
+    #[async_trait]
+    impl ModelMiddleware for Redact {
+        async fn before_request(&self, request: &mut ModelRequest) -> Result<(), ModelError> {
+            for text in request.texts_mut() {
+                *text = text.replace(self.0, "[REDACTED]");
+            }
+            Ok(())
+        }
+    }
+
+    /// Blocks replies mentioning `word` and records the order hooks ran in.
+    struct Guard {
+        word: &'static str,
+        log: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[async_trait]
+    impl ModelMiddleware for Guard {
+        async fn before_request(&self, _request: &mut ModelRequest) -> Result<(), ModelError> {
+            self.log
+                .lock()
+                .unwrap()
+                .push(format!("before {}", self.word));
+            Ok(())
+        }
+
+        async fn after_response(&self, response: &mut LLMResponse) -> Result<(), ModelError> {
+            self.log
+                .lock()
+                .unwrap()
+                .push(format!("after {}", self.word));
+            if response.content.contains(self.word) {
+                return Err(ModelError::ContentFilter(format!("mentions {}", self.word)));
+            }
+            Ok(())
+        }
+    }
+
+    /// Answers with a canned reply when the call was filtered.
+    struct Fallback;
+
+    #[async_trait]
+    impl ModelMiddleware for Fallback {
+        async fn on_error(&self, error: ModelError) -> Result<LLMResponse, ModelError> {
+            match error {
+                ModelError::ContentFilter(_) => Ok(LLMResponse {
+                    content: "I can't help with that.".into(),
+                    ..LLMResponse::default()
+                }),
+                error => Err(error),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn requests_flow_in_and_outcomes_flow_back_in_reverse() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let model = MiddlewareModel::new(StubModel)
+            .with_middleware(LoggingMiddleware)
+            .with_middleware(Fallback)
+            .with_middleware(Redact("hunter2"))
+            .with_middleware(Guard {
+                word: "bomb",
+                log: log.clone(),
+            });
+
+        let response = model
+            .chat(
+                &[ChatMessage::user("my password is hunter2")],
+                &ChatOptions::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.content, "echo: user: my password is [REDACTED]");
+
+        let response = model
+            .generate("how to build a bomb", &GenerationOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(response.content, "I can't help with that.");
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec!["before bomb", "after bomb", "before bomb", "after bomb"]
+        );
+    }
+
+    #[tokio::test]
+    async fn rejected_requests_never_reach_the_model() {
+        struct Reject;
+
+        #[async_trait]
+        impl ModelMiddleware for Reject {
+            async fn before_request(&self, _request: &mut ModelRequest) -> Result<(), ModelError> {
+                Err(ModelError::ContentFilter("blocked prompt".into()))
+            }
+        }
+
+        let model = MiddlewareModel::new(StubModel).with_middleware(Reject);
+        assert_eq!(
+            model
+                .stream("anything", &GenerationOptions::default())
+                .await
+                .err(),
+            Some(ModelError::ContentFilter("blocked prompt".into()))
+        );
+    }
+}
@@ -0,0 +1,590 @@
+//! Local [Ollama](https://ollama.com) backend for
+//! [`OllamaModel`](crate::OllamaModel), enabled by the `ollama` cargo feature.
+//!
+//! Completions use `/api/chat`; streaming reads Ollama's newline-delimited
+//! JSON chunks as they arrive. [`OllamaConnection::ensure_model`] checks that
+//! a model is installed and can pull it when it is not.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use thiserror::Error;
+
+use crate::streaming::line_stream;
+use crate::{
+    ChatMessage, ChatOptions, ContentPart, EventStream, FinishReason, GenerationOptions,
+    HttpClientConfig, HttpClientError, LLMResponse, MediaSource, ModelError, ModelMetadata,
+    ResponseFormat, StreamEvent, ToolCallInfo, UsageMetrics,
+};
+
+#[derive(Debug, Error)]
+pub enum OllamaError {
+    #[error("request failed: {0}")]
+    Transport(#[from] reqwest::Error),
+    #[error("server returned {status}: {message}")]
+    Api { status: u16, message: String },
+    #[error("unexpected response: {0}")]
+    Decode(String),
+    #[error("model `{0}` is not installed")]
+    ModelNotFound(String),
+}
+
+impl From<OllamaError> for ModelError {
+    fn from(error: OllamaError) -> Self {
+        match error {
+            OllamaError::Transport(error) => error.into(),
+            OllamaError::Api { status, message } => ModelError::from_status(status, message, None),
+            OllamaError::Decode(message) => ModelError::InvalidResponse(message),
+            OllamaError::ModelNotFound(_) => ModelError::Api {
+                status: 404,
+                message: error.to_string(),
+            },
+        }
+    }
+}
+
+/// Sampling options sent with every request; unset fields use the model's
+/// Modelfile defaults.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OllamaOptions {
+    /// Context window size in tokens.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub num_ctx: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+}
+
+/// Address of an Ollama server.
+#[derive(Debug, Clone)]
+pub struct OllamaConnection {
+    pub base_url: String,
+    /// Pull missing models in [`ensure_model`](Self::ensure_model) instead of
+    /// failing with [`OllamaError::ModelNotFound`].
+    pub pull_missing: bool,
+    pub client: reqwest::Client,
+}
+
+impl Default for OllamaConnection {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_BASE_URL)
+    }
+}
+
+impl OllamaConnection {
+    pub const DEFAULT_BASE_URL: &'static str = "http://localhost:11434";
+
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            pull_missing: false,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    pub fn with_pull_missing(mut self, pull_missing: bool) -> Self {
+        self.pull_missing = pull_missing;
+        self
+    }
+
+    pub fn with_client_config(
+        mut self,
+        config: &HttpClientConfig,
+    ) -> Result<Self, HttpClientError> {
+        self.client = config.build_client()?;
+        Ok(self)
+    }
+
+    /// Lists the models installed on the server, as reported by `/api/tags`.
+    pub async fn installed_models(&self) -> Result<Vec<String>, OllamaError> {
+        let response = self
+            .client
+            .get(format!("{}/api/tags", self.base_url))
+            .send()
+            .await?;
+        let tags: Value = checked(response)
+            .await?
+            .json()
+            .await
+            .map_err(|e| OllamaError::Decode(e.to_string()))?;
+        Ok(tags["models"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|model| model["name"].as_str().map(ToOwned::to_owned))
+            .collect())
+    }
+
+    /// Succeeds when `model` is installed, pulling it first if
+    /// [`pull_missing`](Self::pull_missing) is set. A name without a tag
+    /// matches `:latest`.
+    pub async fn ensure_model(&self, model: &str) -> Result<(), OllamaError> {
+        let wanted = if model.contains(':') {
+            model.to_string()
+        } else {
+            format!("{model}:latest")
+        };
+        if self
+            .installed_models()
+            .await?
+            .iter()
+            .any(|name| *name == wanted || name == model)
+        {
+            return Ok(());
+        }
+        if !self.pull_missing {
+            return Err(OllamaError::ModelNotFound(model.to_string()));
+        }
+
+        tracing::info!(model, "pulling ollama model");
+        let response = self
+            .client
+            .post(format!("{}/api/pull", self.base_url))
+            .json(&json!({"model": model, "stream": false}))
+            .send()
+            .await?;
+        let status: Value = checked(response)
+            .await?
+            .json()
+            .await
+            .map_err(|e| OllamaError::Decode(e.to_string()))?;
+        match status["status"].as_str() {
+            Some("success") => Ok(()),
+            other => Err(OllamaError::Decode(format!(
+                "pull of `{model}` ended with status {other:?}"
+            ))),
+        }
+    }
+
+    fn request(
+        &self,
+        model: &str,
+        messages: &[ChatMessage],
+        chat: &ChatOptions,
+        options: &OllamaOptions,
+        stream: bool,
+    ) -> reqwest::RequestBuilder {
+        // Ollama takes tool-call arguments as objects and has no call ids,
+        // and accepts images only as bare base64.
+        let messages: Vec<Value> = messages
+            .iter()
+            .map(|message| {
+                let mut wire = json!({"role": message.role, "content": message.content});
+                let images: Vec<&str> = message
+                    .parts
+                    .iter()
+                    .filter_map(|part| match part {
+                        ContentPart::Image {
+                            source: MediaSource::Base64 { data, .. },
+                            ..
+                        } => Some(data.as_str()),
+                        _ => {
+                            tracing::warn!("ollama accepts only inline images; dropping a content part");
+                            None
+                        }
+                    })
+                    .collect();
+                if !images.is_empty() {
+                    wire["images"] = json!(images);
+                }
+                if !message.tool_calls.is_empty() {
+                    wire["tool_calls"] = message
+                        .tool_calls
+                        .iter()
+                        .map(|call| json!({"function": {"name": call.name, "arguments": call.arguments}}))
+                        .collect();
+                }
+                wire
+            })
+            .collect();
+        // Per-request sampling overrides the model's defaults; Ollama calls
+        // the completion cap `num_predict`.
+        let generation = &chat.generation;
+        let mut wire_options = json!(options);
+        let overrides = [
+            ("temperature", generation.temperature.map(Value::from)),
+            ("top_p", generation.top_p.map(Value::from)),
+            ("num_predict", generation.max_tokens.map(Value::from)),
+            (
+                "frequency_penalty",
+                generation.frequency_penalty.map(Value::from),
+            ),
+            (
+                "presence_penalty",
+                generation.presence_penalty.map(Value::from),
+            ),
+            ("seed", generation.seed.map(Value::from)),
+        ];
+        for (key, value) in overrides {
+            if let Some(value) = value {
+                wire_options[key] = value;
+            }
+        }
+        if !generation.stop.is_empty() {
+            wire_options["stop"] = json!(generation.stop);
+        }
+        let mut body = json!({
+            "model": model,
+            "messages": messages,
+            "options": wire_options,
+            "stream": stream,
+        });
+        if !chat.tools.is_empty() {
+            body["tools"] = json!(chat.tools);
+        }
+        match &chat.response_format {
+            Some(ResponseFormat::JsonObject) => body["format"] = json!("json"),
+            Some(ResponseFormat::JsonSchema { schema, .. }) => body["format"] = schema.clone(),
+            None => {}
+        }
+        self.client
+            .post(format!("{}/api/chat", self.base_url))
+            .json(&body)
+    }
+
+    pub(crate) async fn complete(
+        &self,
+        model: &str,
+        messages: &[ChatMessage],
+        chat: &ChatOptions,
+        options: &OllamaOptions,
+        mut metadata: ModelMetadata,
+    ) -> Result<LLMResponse, OllamaError> {
+        let response = checked(
+            self.request(model, messages, chat, options, false)
+                .send()
+                .await?,
+        )
+        .await?;
+        let chat: ChatResponse = response
+            .json()
+            .await
+            .map_err(|e| OllamaError::Decode(e.to_string()))?;
+
+        if let Some(model) = chat.model {
+            metadata.model = model;
+        }
+        Ok(LLMResponse {
+            content: chat.message.content,
+            reasoning: chat
+                .message
+                .thinking
+                .filter(|thinking| !thinking.is_empty()),
+            usage: UsageMetrics {
+                prompt_tokens: chat.prompt_eval_count,
+                completion_tokens: chat.eval_count,
+            },
+            tool_calls: chat
+                .message
+                .tool_calls
+                .into_iter()
+                .map(|call| ToolCallInfo {
+                    name: call.function.name,
+                    arguments: call.function.arguments,
+                    id: None,
+                })
+                .collect(),
+            metadata,
+            finish_reason: chat.done_reason.map(FinishReason::from),
+        })
+    }
+
+    pub(crate) async fn stream(
+        &self,
+        model: &str,
+        messages: &[ChatMessage],
+        generation: &GenerationOptions,
+        options: &OllamaOptions,
+    ) -> Result<EventStream, OllamaError> {
+        let chat = ChatOptions::default().with_generation(generation.clone());
+        let response = checked(
+            self.request(model, messages, &chat, options, true)
+                .send()
+                .await?,
+        )
+        .await?;
+        // Ollama sends each tool call whole, so every call is a single delta.
+        let mut next_call = 0;
+        Ok(line_stream(response, "ollama", move |line| {
+            let Ok(chunk) = serde_json::from_str::<Value>(line) else {
+                return Vec::new();
+            };
+            if let Some(error) = chunk["error"].as_str() {
+                tracing::warn!(error, "ollama stream failed");
+                return vec![StreamEvent::Done {
+                    finish_reason: Some(FinishReason::Error),
+                }];
+            }
+            let Ok(chunk) = ChatResponse::deserialize(chunk) else {
+                return Vec::new();
+            };
+            let mut events = Vec::new();
+            if let Some(text) = chunk.message.thinking.filter(|text| !text.is_empty()) {
+                events.push(StreamEvent::ReasoningDelta { text });
+            }
+            events.push(StreamEvent::TextDelta {
+                text: chunk.message.content,
+            });
+            for call in chunk.message.tool_calls {
+                events.push(StreamEvent::ToolCallDelta {
+                    index: next_call,
+                    id: None,
+                    name: Some(call.function.name),
+                    arguments: call.function.arguments.to_string(),
+                });
+                next_call += 1;
+            }
+            if chunk.done {
+                events.push(StreamEvent::Usage {
+                    usage: UsageMetrics {
+                        prompt_tokens: chunk.prompt_eval_count,
+                        completion_tokens: chunk.eval_count,
+                    },
+                });
+                events.push(StreamEvent::Done {
+                    finish_reason: chunk.done_reason.map(FinishReason::from),
+                });
+            }
+            events
+        }))
+    }
+}
+
+async fn checked(response: reqwest::Response) -> Result<reqwest::Response, OllamaError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let text = response.text().await.unwrap_or_default();
+    // Error bodies look like {"error": "..."}; fall back to the raw text.
+    let message = serde_json::from_str::<Value>(&text)
+        .ok()
+        .and_then(|body| body["error"].as_str().map(ToOwned::to_owned))
+        .unwrap_or(text);
+    Err(OllamaError::Api {
+        status: status.as_u16(),
+        message,
+    })
+}
+
+#[derive(Deserialize)]
+struct ChatResponse {
+    #[serde(default)]
+    model: Option<String>,
+    message: Message,
+    /// Set on the last chunk of a stream, which carries the usage counts.
+    #[serde(default)]
+    done: bool,
+    #[serde(default)]
+    done_reason: Option<String>,
+    #[serde(default)]
+    prompt_eval_count: usize,
+    #[serde(default)]
+    eval_count: usize,
+}
+
+#[derive(Deserialize)]
+struct Message {
+    #[serde(default)]
+    content: String,
+    /// Reasoning from thinking models such as `deepseek-r1` and `qwen3`.
+    #[serde(default)]
+    thinking: Option<String>,
+    #[serde(default)]
+    tool_calls: Vec<WireToolCall>,
+}
+
+#[derive(Deserialize)]
+struct WireToolCall {
+    function: WireFunction,
+}
+
+// Unlike OpenAI, Ollama sends arguments as a JSON object rather than a string.
+#[derive(Deserialize)]
+struct WireFunction {
+    name: String,
+    #[serde(default)]
+    arguments: Value,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LLMModel, OllamaModel};
+    use tokio_stream::StreamExt;
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn model(server: &MockServer) -> OllamaModel {
+        OllamaModel::new("llama3.2")
+            .with_connection(OllamaConnection::new(server.uri()))
+            .with_options(OllamaOptions {
+                num_ctx: Some(8192),
+                temperature: Some(0.5),
+            })
+    }
+
+    #[tokio::test]
+    async fn chat_sends_options_and_maps_the_reply() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/chat"))
+            .and(body_partial_json(json!({
+                "model": "llama3.2",
+                "stream": false,
+                "options": {"num_ctx": 8192, "temperature": 0.25, "num_predict": 32},
+                "messages": [{"role": "user", "content": "add 2 and 3"}]
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "model": "llama3.2",
+                "message": {
+                    "role": "assistant",
+                    "content": "",
+                    "tool_calls": [{"function": {"name": "math", "arguments": {"expression": "2+3"}}}]
+                },
+                "done": true,
+                "done_reason": "stop",
+                "prompt_eval_count": 26,
+                "eval_count": 9
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let options = GenerationOptions::default()
+            .with_temperature(0.25)
+            .with_max_tokens(32);
+        let response = model(&server)
+            .generate("add 2 and 3", &options)
+            .await
+            .unwrap();
+        assert_eq!(response.tool_calls[0].name, "math");
+        assert_eq!(
+            response.tool_calls[0].arguments,
+            json!({"expression": "2+3"})
+        );
+        assert_eq!(response.usage.prompt_tokens, 26);
+        assert_eq!(response.usage.completion_tokens, 9);
+        assert_eq!(response.finish_reason, Some(FinishReason::Stop));
+        assert_eq!(response.metadata.provider, "ollama");
+    }
+
+    #[tokio::test]
+    async fn chat_sends_inline_images() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/chat"))
+            .and(body_partial_json(json!({
+                "messages": [{"role": "user", "content": "describe this", "images": ["iVBORw0KGgo="]}]
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "message": {"role": "assistant", "content": "a login form"},
+                "done": true
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let message = ChatMessage::user("describe this")
+            .with_image(MediaSource::base64("image/png", "iVBORw0KGgo="))
+            .with_image(MediaSource::url("https://example.com/remote.png"));
+        let response = model(&server)
+            .chat(&[message], &ChatOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(response.content, "a login form");
+    }
+
+    #[tokio::test]
+    async fn streams_ndjson_chunks() {
+        let server = MockServer::start().await;
+        let body = [
+            json!({"message": {"role": "assistant", "content": "", "thinking": "Say hi."}, "done": false}),
+            json!({"message": {"role": "assistant", "content": "Hel"}, "done": false}),
+            json!({"message": {"role": "assistant", "content": "lo"}, "done": false}),
+            json!({"message": {"role": "assistant", "content": "", "tool_calls": [
+                {"function": {"name": "weather", "arguments": {"city": "Oslo"}}}
+            ]}, "done": false}),
+            json!({
+                "message": {"role": "assistant", "content": ""},
+                "done": true,
+                "done_reason": "stop",
+                "prompt_eval_count": 5,
+                "eval_count": 2
+            }),
+        ]
+        .iter()
+        .map(Value::to_string)
+        .collect::<Vec<_>>()
+        .join("\n");
+        Mock::given(method("POST"))
+            .and(path("/api/chat"))
+            .and(body_partial_json(json!({"stream": true})))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(body, "application/x-ndjson"))
+            .mount(&server)
+            .await;
+
+        let events: Vec<StreamEvent> = model(&server)
+            .stream("greet", &GenerationOptions::default())
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        assert_eq!(
+            events,
+            vec![
+                StreamEvent::ReasoningDelta {
+                    text: "Say hi.".into()
+                },
+                StreamEvent::TextDelta { text: "Hel".into() },
+                StreamEvent::TextDelta { text: "lo".into() },
+                StreamEvent::ToolCallDelta {
+                    index: 0,
+                    id: None,
+                    name: Some("weather".into()),
+                    arguments: r#"{"city":"Oslo"}"#.into(),
+                },
+                StreamEvent::Usage {
+                    usage: UsageMetrics {
+                        prompt_tokens: 5,
+                        completion_tokens: 2
+                    }
+                },
+                StreamEvent::Done {
+                    finish_reason: Some(FinishReason::Stop)
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn ensure_model_checks_tags_and_pulls_when_allowed() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/tags"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "models": [{"name": "llama3.2:latest"}]
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/pull"))
+            .and(body_partial_json(
+                json!({"model": "qwen2.5:7b", "stream": false}),
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"status": "success"})))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let connection = OllamaConnection::new(server.uri());
+        connection.ensure_model("llama3.2").await.unwrap();
+        assert!(matches!(
+            connection.ensure_model("qwen2.5:7b").await,
+            Err(OllamaError::ModelNotFound(name)) if name == "qwen2.5:7b"
+        ));
+        connection
+            .with_pull_missing(true)
+            .ensure_model("qwen2.5:7b")
+            .await
+            .unwrap();
+    }
+}
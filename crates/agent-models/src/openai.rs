@@ -0,0 +1,900 @@
+//! Chat Completions backend for [`OpenAIChatModel`](crate::OpenAIChatModel),
+//! enabled by the `openai` cargo feature.
+//!
+//! [`OpenAIConnection`] holds the base URL, API key and optional organization;
+//! attach one with `OpenAIChatModel::with_connection` and `generate`/`stream`
+//! call the API instead of echoing the prompt. The same wire format backs
+//! [`OpenAICompatibleModel`](crate::OpenAICompatibleModel) for other servers.
+
+use std::time::Duration;
+
+use agent_telemetry::Secret;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use thiserror::Error;
+
+use crate::streaming::line_stream;
+use crate::{
+    ChatMessage, ChatOptions, ContentPart, EventStream, FinishReason, GenerationOptions,
+    HttpClientConfig, HttpClientError, MediaSource, ModelError, ModelMetadata, ResponseFormat,
+    StreamEvent, ToolCallInfo, UsageMetrics,
+};
+
+#[derive(Debug, Error)]
+pub enum OpenAIError {
+    #[error("request failed: {0}")]
+    Transport(#[from] reqwest::Error),
+    #[error("API returned {status}: {message}")]
+    Api {
+        status: u16,
+        message: String,
+        /// The error body's `code`, e.g. `content_filter` on Azure.
+        code: Option<String>,
+        /// From `retry-after-ms` or `retry-after` on throttled responses.
+        retry_after: Option<Duration>,
+    },
+    #[error("unexpected response: {0}")]
+    Decode(String),
+    #[error("authentication failed: {0}")]
+    Auth(String),
+}
+
+impl From<OpenAIError> for ModelError {
+    fn from(error: OpenAIError) -> Self {
+        match error {
+            OpenAIError::Transport(error) => error.into(),
+            OpenAIError::Api { code, message, .. } if code.as_deref() == Some("content_filter") => {
+                ModelError::ContentFilter(message)
+            }
+            OpenAIError::Api {
+                status,
+                message,
+                retry_after,
+                ..
+            } => ModelError::from_status(status, message, retry_after),
+            OpenAIError::Decode(message) => ModelError::InvalidResponse(message),
+            OpenAIError::Auth(message) => ModelError::Auth(message),
+        }
+    }
+}
+
+/// Where and as whom Chat Completions requests are sent.
+#[derive(Debug, Clone)]
+pub struct OpenAIConnection {
+    pub base_url: String,
+    /// Bearer token; left out of requests when empty, for compatible servers
+    /// that take none.
+    pub api_key: Secret<String>,
+    /// Sent as `OpenAI-Organization` when set.
+    pub organization: Option<String>,
+    pub client: reqwest::Client,
+}
+
+impl OpenAIConnection {
+    pub const DEFAULT_BASE_URL: &'static str = "https://api.openai.com/v1";
+
+    pub fn new(api_key: impl Into<Secret<String>>) -> Self {
+        Self {
+            base_url: Self::DEFAULT_BASE_URL.into(),
+            api_key: api_key.into(),
+            organization: None,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Points requests at an OpenAI-compatible server; a trailing `/` is ignored.
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    pub fn with_organization(mut self, organization: impl Into<String>) -> Self {
+        self.organization = Some(organization.into());
+        self
+    }
+
+    pub fn with_client_config(
+        mut self,
+        config: &HttpClientConfig,
+    ) -> Result<Self, HttpClientError> {
+        self.client = config.build_client()?;
+        Ok(self)
+    }
+
+    fn request(&self, body: &Value) -> reqwest::RequestBuilder {
+        let mut request = self
+            .client
+            .post(format!("{}/chat/completions", self.base_url))
+            .json(body);
+        if !self.api_key.expose().is_empty() {
+            request = request.bearer_auth(self.api_key.expose());
+        }
+        if let Some(organization) = &self.organization {
+            request = request.header("OpenAI-Organization", organization);
+        }
+        request
+    }
+
+    pub(crate) async fn complete(
+        &self,
+        model: &str,
+        messages: &[ChatMessage],
+        options: &ChatOptions,
+        metadata: ModelMetadata,
+    ) -> Result<crate::LLMResponse, OpenAIError> {
+        let mut body = chat_body(messages, options);
+        body["model"] = json!(model);
+        send_completion(self.request(&body), metadata).await
+    }
+
+    pub(crate) async fn stream(
+        &self,
+        model: &str,
+        messages: &[ChatMessage],
+        generation: &GenerationOptions,
+    ) -> Result<EventStream, OpenAIError> {
+        let mut body = streaming_body(chat_body(
+            messages,
+            &ChatOptions::default().with_generation(generation.clone()),
+        ));
+        body["model"] = json!(model);
+        send_stream(self.request(&body)).await
+    }
+}
+
+/// Chat Completions request body without the `model`, which Azure takes from
+/// the deployment URL instead.
+pub(crate) fn chat_body(messages: &[ChatMessage], options: &ChatOptions) -> Value {
+    let messages: Vec<Value> = messages
+        .iter()
+        .map(|message| {
+            let mut wire = json!({"role": message.role, "content": message.content});
+            if !message.parts.is_empty() {
+                wire["content"] = content_parts(message);
+            }
+            if let Some(id) = &message.tool_call_id {
+                wire["tool_call_id"] = json!(id);
+            }
+            if !message.tool_calls.is_empty() {
+                wire["tool_calls"] = message
+                    .tool_calls
+                    .iter()
+                    .map(|call| {
+                        json!({
+                            "id": call.id,
+                            "type": "function",
+                            "function": {"name": call.name, "arguments": call.arguments.to_string()},
+                        })
+                    })
+                    .collect();
+            }
+            wire
+        })
+        .collect();
+    // Option names match the API's sampling parameters one-for-one.
+    let mut body = json!(options.generation);
+    body["messages"] = json!(messages);
+    if !options.tools.is_empty() {
+        body["tools"] = json!(options.tools);
+    }
+    match &options.response_format {
+        Some(ResponseFormat::JsonObject) => {
+            body["response_format"] = json!({"type": "json_object"})
+        }
+        Some(ResponseFormat::JsonSchema {
+            name,
+            schema,
+            strict,
+        }) => {
+            body["response_format"] = json!({
+                "type": "json_schema",
+                "json_schema": {"name": name, "schema": schema, "strict": strict},
+            })
+        }
+        None => {}
+    }
+    body
+}
+
+/// Content as an array of typed parts, which vision models require once a
+/// message carries images or files. Files must be inline, so a document
+/// given by URL is referenced in a text part instead.
+fn content_parts(message: &ChatMessage) -> Value {
+    let mut parts = Vec::new();
+    if !message.content.is_empty() {
+        parts.push(json!({"type": "text", "text": message.content}));
+    }
+    for part in &message.parts {
+        parts.push(match part {
+            ContentPart::Image { source, detail } => json!({
+                "type": "image_url",
+                "image_url": {"url": source.to_url(), "detail": detail},
+            }),
+            ContentPart::Document {
+                source: source @ MediaSource::Base64 { .. },
+                name,
+            } => json!({
+                "type": "file",
+                "file": {"filename": name.as_deref().unwrap_or("document"), "file_data": source.to_url()},
+            }),
+            ContentPart::Document {
+                source: MediaSource::Url { url },
+                ..
+            } => json!({"type": "text", "text": format!("[document: {url}]")}),
+        });
+    }
+    json!(parts)
+}
+
+/// Sends a Chat Completions request and maps the first choice.
+pub(crate) async fn send_completion(
+    request: reqwest::RequestBuilder,
+    metadata: ModelMetadata,
+) -> Result<crate::LLMResponse, OpenAIError> {
+    let response = checked(request.send().await?).await?;
+    let completion: Completion = response
+        .json()
+        .await
+        .map_err(|e| OpenAIError::Decode(e.to_string()))?;
+    completion.into_response(metadata)
+}
+
+/// Turns a request `body` into a streaming one that also reports usage.
+pub(crate) fn streaming_body(mut body: Value) -> Value {
+    body["stream"] = json!(true);
+    body["stream_options"] = json!({"include_usage": true});
+    body
+}
+
+/// Sends a streaming request and yields its events as they arrive.
+pub(crate) async fn send_stream(
+    request: reqwest::RequestBuilder,
+) -> Result<EventStream, OpenAIError> {
+    let response = checked(request.send().await?).await?;
+    Ok(sse_deltas(response))
+}
+
+async fn checked(response: reqwest::Response) -> Result<reqwest::Response, OpenAIError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let retry_after = retry_after(response.headers());
+    let text = response.text().await.unwrap_or_default();
+    // Error bodies look like {"error": {"message": ..., "code": ...}}; fall
+    // back to the raw text.
+    let body = serde_json::from_str::<Value>(&text).unwrap_or_default();
+    let message = body["error"]["message"]
+        .as_str()
+        .map(ToOwned::to_owned)
+        .unwrap_or(text);
+    Err(OpenAIError::Api {
+        status: status.as_u16(),
+        message,
+        code: body["error"]["code"].as_str().map(ToOwned::to_owned),
+        retry_after,
+    })
+}
+
+/// Reads the throttling delay, preferring OpenAI's millisecond header over
+/// the standard whole-second `retry-after`.
+fn retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    let header = |name: &str| {
+        headers
+            .get(name)?
+            .to_str()
+            .ok()?
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|value| value.is_finite() && *value >= 0.0)
+    };
+    header("retry-after-ms")
+        .map(|ms| Duration::from_secs_f64(ms / 1000.0))
+        .or_else(|| header("retry-after").map(Duration::from_secs_f64))
+}
+
+#[derive(Deserialize)]
+struct Completion {
+    #[serde(default)]
+    model: Option<String>,
+    choices: Vec<Choice>,
+    #[serde(default)]
+    usage: Option<Usage>,
+    /// Azure-only: moderation verdicts for each prompt.
+    #[serde(default)]
+    prompt_filter_results: Option<Value>,
+}
+
+#[derive(Deserialize)]
+struct Choice {
+    message: Message,
+    #[serde(default)]
+    finish_reason: Option<String>,
+    /// Azure-only: moderation verdicts for the completion.
+    #[serde(default)]
+    content_filter_results: Option<Value>,
+}
+
+#[derive(Deserialize)]
+struct Message {
+    #[serde(default)]
+    content: Option<String>,
+    /// Reasoning trace from DeepSeek-style servers; some compatible servers
+    /// call it `reasoning`.
+    #[serde(default, alias = "reasoning")]
+    reasoning_content: Option<String>,
+    #[serde(default)]
+    tool_calls: Vec<WireToolCall>,
+}
+
+#[derive(Deserialize)]
+struct WireToolCall {
+    id: String,
+    function: WireFunction,
+}
+
+#[derive(Deserialize)]
+struct WireFunction {
+    name: String,
+    arguments: String,
+}
+
+#[derive(Deserialize)]
+struct Usage {
+    prompt_tokens: usize,
+    completion_tokens: usize,
+}
+
+impl Completion {
+    fn into_response(self, mut metadata: ModelMetadata) -> Result<crate::LLMResponse, OpenAIError> {
+        let choice = self
+            .choices
+            .into_iter()
+            .next()
+            .ok_or_else(|| OpenAIError::Decode("response has no choices".into()))?;
+        if let Some(model) = self.model {
+            metadata.model = model;
+        }
+        let mut content_filter = serde_json::Map::new();
+        if let Some(prompt) = self.prompt_filter_results {
+            content_filter.insert("prompt".into(), prompt);
+        }
+        if let Some(completion) = choice.content_filter_results {
+            content_filter.insert("completion".into(), completion);
+        }
+        if !content_filter.is_empty() {
+            metadata
+                .annotations
+                .insert("content_filter".into(), Value::Object(content_filter));
+        }
+        let tool_calls = choice
+            .message
+            .tool_calls
+            .into_iter()
+            .map(|call| ToolCallInfo {
+                // Models occasionally emit malformed JSON; keep the raw text
+                // rather than dropping the call.
+                arguments: serde_json::from_str(&call.function.arguments)
+                    .unwrap_or(Value::String(call.function.arguments)),
+                name: call.function.name,
+                id: Some(call.id),
+            })
+            .collect();
+        Ok(crate::LLMResponse {
+            content: choice.message.content.unwrap_or_default(),
+            reasoning: choice
+                .message
+                .reasoning_content
+                .filter(|reasoning| !reasoning.is_empty()),
+            usage: self
+                .usage
+                .map(|usage| UsageMetrics {
+                    prompt_tokens: usage.prompt_tokens,
+                    completion_tokens: usage.completion_tokens,
+                })
+                .unwrap_or_default(),
+            tool_calls,
+            metadata,
+            finish_reason: choice.finish_reason.map(FinishReason::from),
+        })
+    }
+}
+
+/// Maps each server-sent event to content and tool-call deltas, the usage
+/// chunk to [`StreamEvent::Usage`], and `[DONE]` to [`StreamEvent::Done`]
+/// with the finish reason seen along the way.
+fn sse_deltas(response: reqwest::Response) -> EventStream {
+    let mut finish_reason = None;
+    line_stream(response, "openai", move |line| {
+        let Some(data) = line.strip_prefix("data:") else {
+            return Vec::new();
+        };
+        let data = data.trim();
+        if data == "[DONE]" {
+            return vec![StreamEvent::Done {
+                finish_reason: finish_reason.take(),
+            }];
+        }
+        let Ok(chunk) = serde_json::from_str::<Value>(data) else {
+            return Vec::new();
+        };
+        let mut events = Vec::new();
+        let choice = &chunk["choices"][0];
+        if let Some(text) = choice["delta"]["reasoning_content"]
+            .as_str()
+            .or_else(|| choice["delta"]["reasoning"].as_str())
+            .filter(|text| !text.is_empty())
+        {
+            events.push(StreamEvent::ReasoningDelta { text: text.into() });
+        }
+        if let Some(text) = choice["delta"]["content"].as_str() {
+            events.push(StreamEvent::TextDelta { text: text.into() });
+        }
+        for (position, call) in choice["delta"]["tool_calls"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .enumerate()
+        {
+            events.push(StreamEvent::ToolCallDelta {
+                index: call["index"]
+                    .as_u64()
+                    .map_or(position, |index| index as usize),
+                id: call["id"].as_str().map(ToOwned::to_owned),
+                name: call["function"]["name"].as_str().map(ToOwned::to_owned),
+                arguments: call["function"]["arguments"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string(),
+            });
+        }
+        if let Some(reason) = choice["finish_reason"].as_str() {
+            finish_reason = Some(FinishReason::from(reason.to_string()));
+        }
+        if let Ok(usage) = Usage::deserialize(&chunk["usage"]) {
+            events.push(StreamEvent::Usage {
+                usage: UsageMetrics {
+                    prompt_tokens: usage.prompt_tokens,
+                    completion_tokens: usage.completion_tokens,
+                },
+            });
+        }
+        events
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{text_deltas, LLMModel, LLMResponse, OpenAIChatModel, Token};
+    use tokio_stream::StreamExt;
+    use wiremock::matchers::{body_partial_json, header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn model(server: &MockServer) -> OpenAIChatModel {
+        OpenAIChatModel::new("gpt-4o-mini").with_connection(
+            OpenAIConnection::new("sk-test".to_string())
+                .with_base_url(format!("{}/v1/", server.uri()))
+                .with_organization("org-1"),
+        )
+    }
+
+    #[tokio::test]
+    async fn maps_completion_with_tool_calls_and_usage() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .and(header("authorization", "Bearer sk-test"))
+            .and(header("openai-organization", "org-1"))
+            .and(body_partial_json(json!({
+                "model": "gpt-4o-mini",
+                "messages": [{"role": "user", "content": "weather in Oslo?"}]
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "model": "gpt-4o-mini-2024-07-18",
+                "choices": [{
+                    "message": {
+                        "role": "assistant",
+                        "content": null,
+                        "tool_calls": [{
+                            "id": "call_1",
+                            "type": "function",
+                            "function": {"name": "weather", "arguments": "{\"city\":\"Oslo\"}"}
+                        }]
+                    },
+                    "finish_reason": "tool_calls"
+                }],
+                "usage": {"prompt_tokens": 12, "completion_tokens": 7, "total_tokens": 19}
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let response = model(&server)
+            .generate("weather in Oslo?", &GenerationOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(response.content, "");
+        assert_eq!(
+            response.tool_calls,
+            vec![ToolCallInfo {
+                name: "weather".into(),
+                arguments: json!({"city": "Oslo"}),
+                id: Some("call_1".into()),
+            }]
+        );
+        assert_eq!(response.usage.prompt_tokens, 12);
+        assert_eq!(response.usage.completion_tokens, 7);
+        assert_eq!(response.finish_reason, Some(FinishReason::ToolCalls));
+        assert_eq!(response.metadata.model, "gpt-4o-mini-2024-07-18");
+        assert_eq!(response.metadata.provider, "openai");
+    }
+
+    #[tokio::test]
+    async fn surfaces_api_errors() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(401).set_body_json(json!({
+                "error": {"message": "Incorrect API key provided", "type": "invalid_request_error"}
+            })))
+            .mount(&server)
+            .await;
+
+        let err = model(&server)
+            .complete(&[ChatMessage::user("hi")], &ChatOptions::default())
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&err, OpenAIError::Api { status: 401, message, .. } if message == "Incorrect API key provided"),
+            "{err}"
+        );
+
+        let err = model(&server)
+            .generate("hi", &GenerationOptions::default())
+            .await
+            .unwrap_err();
+        assert_eq!(err, ModelError::Auth("Incorrect API key provided".into()));
+        assert!(!err.is_retryable());
+    }
+
+    async fn model_error(response: ResponseTemplate) -> ModelError {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(response)
+            .mount(&server)
+            .await;
+        model(&server)
+            .generate("hi", &GenerationOptions::default())
+            .await
+            .unwrap_err()
+    }
+
+    #[tokio::test]
+    async fn classifies_throttling_and_content_filtering() {
+        let throttled = model_error(
+            ResponseTemplate::new(429)
+                .insert_header("retry-after-ms", "1500")
+                .insert_header("retry-after", "2")
+                .set_body_json(json!({"error": {"message": "Rate limit reached"}})),
+        )
+        .await;
+        assert_eq!(throttled.retry_after(), Some(Duration::from_millis(1500)));
+        assert!(throttled.is_retryable());
+
+        let throttled =
+            model_error(ResponseTemplate::new(429).insert_header("retry-after", "3")).await;
+        assert_eq!(throttled.retry_after(), Some(Duration::from_secs(3)));
+
+        let filtered = model_error(ResponseTemplate::new(400).set_body_json(json!({
+            "error": {"message": "The prompt was filtered", "code": "content_filter"}
+        })))
+        .await;
+        assert_eq!(
+            filtered,
+            ModelError::ContentFilter("The prompt was filtered".into())
+        );
+
+        let overloaded = model_error(ResponseTemplate::new(503)).await;
+        assert!(matches!(overloaded, ModelError::Api { status: 503, .. }));
+        assert!(overloaded.is_retryable());
+    }
+
+    #[tokio::test]
+    async fn chat_replays_tool_turns_and_sends_function_specs() {
+        let server = MockServer::start().await;
+        let weather =
+            json!({"type": "function", "function": {"name": "weather", "parameters": {}}});
+        Mock::given(method("POST"))
+            .and(body_partial_json(json!({
+                "tools": [weather],
+                "response_format": {"type": "json_object"},
+                "messages": [
+                    {"role": "system", "content": "be brief"},
+                    {"role": "user", "content": "weather in Oslo?"},
+                    {"role": "assistant", "tool_calls": [{
+                        "id": "call_1",
+                        "type": "function",
+                        "function": {"name": "weather", "arguments": "{\"city\":\"Oslo\"}"}
+                    }]},
+                    {"role": "tool", "tool_call_id": "call_1", "content": "4°C, rain"}
+                ]
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "choices": [{"message": {"content": "4°C and raining."}, "finish_reason": "stop"}]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let call = LLMResponse {
+            tool_calls: vec![ToolCallInfo {
+                name: "weather".into(),
+                arguments: json!({"city": "Oslo"}),
+                id: Some("call_1".into()),
+            }],
+            ..LLMResponse::default()
+        };
+        let messages = [
+            ChatMessage::system("be brief"),
+            ChatMessage::user("weather in Oslo?"),
+            ChatMessage::assistant(&call),
+            ChatMessage::tool("call_1", "4°C, rain"),
+        ];
+        let response = model(&server)
+            .chat(
+                &messages,
+                &ChatOptions {
+                    response_format: Some(ResponseFormat::JsonObject),
+                    ..ChatOptions::default().with_tools(vec![weather])
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.content, "4°C and raining.");
+    }
+
+    #[tokio::test]
+    async fn streams_content_deltas_usage_and_done() {
+        let server = MockServer::start().await;
+        let body = [
+            r#"data: {"choices":[{"delta":{"role":"assistant"}}]}"#,
+            r#"data: {"choices":[{"delta":{"content":"Hello"}}]}"#,
+            r#"data: {"choices":[{"delta":{"content":" world"}}]}"#,
+            r#"data: {"choices":[{"delta":{},"finish_reason":"stop"}]}"#,
+            r#"data: {"choices":[],"usage":{"prompt_tokens":3,"completion_tokens":2}}"#,
+            "data: [DONE]",
+        ]
+        .join("\n\n");
+        Mock::given(method("POST"))
+            .and(body_partial_json(json!({
+                "stream": true,
+                "stream_options": {"include_usage": true},
+                "temperature": 0.5,
+                "max_tokens": 64,
+                "stop": ["\n\n"],
+                "seed": 7
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(body, "text/event-stream"))
+            .mount(&server)
+            .await;
+
+        let options = GenerationOptions::default()
+            .with_temperature(0.5)
+            .with_max_tokens(64)
+            .with_stop("\n\n")
+            .with_seed(7);
+        let events: Vec<StreamEvent> = model(&server)
+            .stream("greet", &options)
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        assert_eq!(
+            &events[2..],
+            [
+                StreamEvent::Usage {
+                    usage: UsageMetrics {
+                        prompt_tokens: 3,
+                        completion_tokens: 2
+                    }
+                },
+                StreamEvent::Done {
+                    finish_reason: Some(FinishReason::Stop)
+                },
+            ]
+        );
+
+        let tokens: Vec<Token> =
+            text_deltas(model(&server).stream("greet", &options).await.unwrap())
+                .collect()
+                .await;
+        assert_eq!(tokens, vec!["Hello".to_string(), " world".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn keeps_reasoning_apart_from_content() {
+        let server = MockServer::start().await;
+        let body = [
+            r#"data: {"choices":[{"delta":{"reasoning_content":"Compare the "}}]}"#,
+            r#"data: {"choices":[{"delta":{"reasoning_content":"decimals.","content":null}}]}"#,
+            r#"data: {"choices":[{"delta":{"reasoning_content":null,"content":"9.9"}}]}"#,
+            r#"data: {"choices":[{"delta":{},"finish_reason":"stop"}]}"#,
+            "data: [DONE]",
+        ]
+        .join("\n\n");
+        Mock::given(method("POST"))
+            .and(body_partial_json(json!({"stream": true})))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(body, "text/event-stream"))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "choices": [{
+                    "message": {
+                        "role": "assistant",
+                        "reasoning_content": "9.11 < 9.9 because 11 hundredths < 90.",
+                        "content": "9.9 is larger."
+                    },
+                    "finish_reason": "stop"
+                }]
+            })))
+            .mount(&server)
+            .await;
+
+        let options = GenerationOptions::default();
+        let response = model(&server)
+            .generate("which is larger?", &options)
+            .await
+            .unwrap();
+        assert_eq!(response.content, "9.9 is larger.");
+        assert_eq!(
+            response.reasoning.as_deref(),
+            Some("9.11 < 9.9 because 11 hundredths < 90.")
+        );
+
+        let mut streamed = LLMResponse::default();
+        let mut events = model(&server)
+            .stream("which is larger?", &options)
+            .await
+            .unwrap();
+        while let Some(event) = events.next().await {
+            streamed.apply_event(&event);
+        }
+        assert_eq!(streamed.content, "9.9");
+        assert_eq!(streamed.reasoning.as_deref(), Some("Compare the decimals."));
+    }
+
+    #[tokio::test]
+    async fn streams_tool_call_deltas() {
+        let server = MockServer::start().await;
+        let body = [
+            r#"data: {"choices":[{"delta":{"tool_calls":[{"index":0,"id":"call_1","type":"function","function":{"name":"weather","arguments":""}}]}}]}"#,
+            r#"data: {"choices":[{"delta":{"tool_calls":[{"index":0,"function":{"arguments":"{\"city\":"}}]}}]}"#,
+            r#"data: {"choices":[{"delta":{"tool_calls":[{"index":0,"function":{"arguments":"\"Oslo\"}"}}]}}]}"#,
+            r#"data: {"choices":[{"delta":{},"finish_reason":"tool_calls"}]}"#,
+            "data: [DONE]",
+        ]
+        .join("\n\n");
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(body, "text/event-stream"))
+            .mount(&server)
+            .await;
+
+        let mut events = model(&server)
+            .stream("weather in Oslo?", &GenerationOptions::default())
+            .await
+            .unwrap();
+        let mut response = LLMResponse::default();
+        let mut deltas = 0;
+        while let Some(event) = events.next().await {
+            if matches!(event, StreamEvent::ToolCallDelta { .. }) {
+                deltas += 1;
+            }
+            response.apply_event(&event);
+        }
+        assert_eq!(deltas, 3);
+        assert_eq!(
+            response.tool_calls,
+            vec![ToolCallInfo {
+                name: "weather".into(),
+                arguments: json!({"city": "Oslo"}),
+                id: Some("call_1".into()),
+            }]
+        );
+        assert_eq!(response.finish_reason, Some(FinishReason::ToolCalls));
+    }
+
+    #[tokio::test]
+    async fn compatible_servers_get_tool_calls_and_streams_without_a_key() {
+        let server = MockServer::start().await;
+        let no_auth = |request: &wiremock::Request| !request.headers.contains_key("authorization");
+        let body = [
+            r#"data: {"choices":[{"delta":{"content":"Hi"}}]}"#,
+            r#"data: {"choices":[{"delta":{},"finish_reason":"stop"}]}"#,
+            "data: [DONE]",
+        ]
+        .join("\n\n");
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .and(no_auth)
+            .and(body_partial_json(json!({"stream": true})))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(body, "text/event-stream"))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .and(no_auth)
+            .and(body_partial_json(json!({
+                "model": "qwen2.5-7b-instruct",
+                "tools": [{"type": "function", "function": {"name": "weather"}}]
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "choices": [{
+                    "message": {
+                        "role": "assistant",
+                        "content": null,
+                        "tool_calls": [{
+                            "id": "call_9",
+                            "type": "function",
+                            "function": {"name": "weather", "arguments": "{\"city\":\"Oslo\"}"}
+                        }]
+                    },
+                    "finish_reason": "tool_calls"
+                }]
+            })))
+            .mount(&server)
+            .await;
+
+        let mut model = crate::OpenAICompatibleModel::new(
+            format!("{}/v1/", server.uri()),
+            "qwen2.5-7b-instruct",
+        );
+        model.supports_tools = true;
+        let weather = json!({"type": "function", "function": {"name": "weather"}});
+        let response = model
+            .chat(
+                &[ChatMessage::user("weather in Oslo?")],
+                &ChatOptions::default().with_tools(vec![weather]),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.metadata.provider, "openai_compatible");
+        assert_eq!(response.tool_calls[0].arguments, json!({"city": "Oslo"}));
+
+        let tokens: Vec<Token> = text_deltas(
+            model
+                .stream("greet", &GenerationOptions::default())
+                .await
+                .unwrap(),
+        )
+        .collect()
+        .await;
+        assert_eq!(tokens, vec!["Hi".to_string()]);
+    }
+
+    #[test]
+    fn vision_messages_send_typed_content_parts() {
+        let screenshot = ChatMessage::user("what is on screen?")
+            .with_image(MediaSource::base64("image/png", "iVBORw0KGgo="))
+            .with_image(MediaSource::url("https://example.com/chart.png"))
+            .with_document(
+                MediaSource::base64("application/pdf", "JVBERi0x"),
+                Some("report.pdf".into()),
+            );
+        let body = chat_body(
+            &[ChatMessage::system("be brief"), screenshot],
+            &ChatOptions::default(),
+        );
+        assert_eq!(body["messages"][0]["content"], "be brief");
+        assert_eq!(
+            body["messages"][1]["content"],
+            json!([
+                {"type": "text", "text": "what is on screen?"},
+                {"type": "image_url", "image_url": {"url": "data:image/png;base64,iVBORw0KGgo=", "detail": "auto"}},
+                {"type": "image_url", "image_url": {"url": "https://example.com/chart.png", "detail": "auto"}},
+                {"type": "file", "file": {"filename": "report.pdf", "file_data": "data:application/pdf;base64,JVBERi0x"}},
+            ])
+        );
+    }
+}
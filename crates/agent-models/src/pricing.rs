@@ -0,0 +1,228 @@
+//! Per-token prices for estimating what model calls cost.
+//!
+//! The built-in table holds published list prices for OpenAI models (also
+//! used for Azure OpenAI deployments named after them) and treats Ollama and
+//! local inference as free. Prices change, so register current or
+//! negotiated ones with [`register_price`].
+
+use std::collections::BTreeMap;
+use std::sync::{OnceLock, RwLock};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{LLMResponse, UsageMetrics};
+
+/// USD per million prompt and completion tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ModelPrice {
+    pub input_per_million: f64,
+    pub output_per_million: f64,
+}
+
+impl ModelPrice {
+    pub const FREE: Self = Self::new(0.0, 0.0);
+
+    pub const fn new(input_per_million: f64, output_per_million: f64) -> Self {
+        Self {
+            input_per_million,
+            output_per_million,
+        }
+    }
+
+    pub fn cost(&self, usage: &UsageMetrics) -> f64 {
+        (usage.prompt_tokens as f64 * self.input_per_million
+            + usage.completion_tokens as f64 * self.output_per_million)
+            / 1_000_000.0
+    }
+}
+
+const OPENAI_PRICES: &[(&str, ModelPrice)] = &[
+    ("gpt-4o", ModelPrice::new(2.50, 10.00)),
+    ("gpt-4o-mini", ModelPrice::new(0.15, 0.60)),
+    ("gpt-4.1", ModelPrice::new(2.00, 8.00)),
+    ("gpt-4.1-mini", ModelPrice::new(0.40, 1.60)),
+    ("gpt-4.1-nano", ModelPrice::new(0.10, 0.40)),
+    ("gpt-4-turbo", ModelPrice::new(10.00, 30.00)),
+    ("gpt-4", ModelPrice::new(30.00, 60.00)),
+    ("gpt-3.5-turbo", ModelPrice::new(0.50, 1.50)),
+    ("o1", ModelPrice::new(15.00, 60.00)),
+    ("o1-mini", ModelPrice::new(1.10, 4.40)),
+    ("o3", ModelPrice::new(2.00, 8.00)),
+    ("o3-mini", ModelPrice::new(1.10, 4.40)),
+    ("o4-mini", ModelPrice::new(1.10, 4.40)),
+];
+
+/// Prices keyed by provider and model name.
+///
+/// A model name also matches entries for its base name, so dated snapshots
+/// such as `gpt-4o-2024-08-06` are priced as `gpt-4o`; the longest match
+/// wins. An entry with an empty model name prices every model of its
+/// provider that has no more specific entry.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PricingTable {
+    prices: BTreeMap<String, BTreeMap<String, ModelPrice>>,
+}
+
+impl PricingTable {
+    /// A table with no prices.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The built-in list prices.
+    pub fn builtin() -> Self {
+        let mut table = Self::new();
+        for (model, price) in OPENAI_PRICES {
+            table.set_price("openai", *model, *price);
+            table.set_price("azure_openai", *model, *price);
+        }
+        table.set_price("ollama", "", ModelPrice::FREE);
+        table.set_price("local", "", ModelPrice::FREE);
+        table
+    }
+
+    pub fn with_price(
+        mut self,
+        provider: impl Into<String>,
+        model: impl Into<String>,
+        price: ModelPrice,
+    ) -> Self {
+        self.set_price(provider, model, price);
+        self
+    }
+
+    pub fn set_price(
+        &mut self,
+        provider: impl Into<String>,
+        model: impl Into<String>,
+        price: ModelPrice,
+    ) {
+        self.prices
+            .entry(provider.into())
+            .or_default()
+            .insert(model.into(), price);
+    }
+
+    pub fn price(&self, provider: &str, model: &str) -> Option<ModelPrice> {
+        self.prices
+            .get(provider)?
+            .iter()
+            .filter(|(name, _)| {
+                name.is_empty()
+                    || model == name.as_str()
+                    || model
+                        .strip_prefix(name.as_str())
+                        .is_some_and(|rest| rest.starts_with('-'))
+            })
+            .max_by_key(|(name, _)| name.len())
+            .map(|(_, price)| *price)
+    }
+
+    /// Cost of `response` from its usage and metadata; `None` when its model
+    /// has no price. Responses served from a [`CachedModel`](crate::CachedModel)
+    /// cost nothing.
+    pub fn estimate(&self, response: &LLMResponse) -> Option<f64> {
+        let price = self.price(&response.metadata.provider, &response.metadata.model)?;
+        if response
+            .metadata
+            .annotations
+            .get("cache")
+            .is_some_and(|value| value == "hit")
+        {
+            return Some(0.0);
+        }
+        Some(price.cost(&response.usage))
+    }
+}
+
+fn global() -> &'static RwLock<PricingTable> {
+    static PRICES: OnceLock<RwLock<PricingTable>> = OnceLock::new();
+    PRICES.get_or_init(|| RwLock::new(PricingTable::builtin()))
+}
+
+/// Adds or replaces the price [`LLMResponse::estimated_cost_usd`] uses for
+/// `model` on `provider`. An empty `model` prices the whole provider.
+pub fn register_price(provider: impl Into<String>, model: impl Into<String>, price: ModelPrice) {
+    global()
+        .write()
+        .expect("pricing table poisoned")
+        .set_price(provider, model, price);
+}
+
+/// The registered price for `model` on `provider`, if any.
+pub fn price_for(provider: &str, model: &str) -> Option<ModelPrice> {
+    global()
+        .read()
+        .expect("pricing table poisoned")
+        .price(provider, model)
+}
+
+impl LLMResponse {
+    /// Estimated spend in USD for this response, from the registered prices.
+    pub fn estimated_cost_usd(&self) -> Option<f64> {
+        global()
+            .read()
+            .expect("pricing table poisoned")
+            .estimate(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ModelMetadata;
+    use serde_json::json;
+
+    fn response(provider: &str, model: &str) -> LLMResponse {
+        LLMResponse {
+            usage: UsageMetrics {
+                prompt_tokens: 1_000,
+                completion_tokens: 500,
+            },
+            metadata: ModelMetadata {
+                provider: provider.into(),
+                model: model.into(),
+                ..ModelMetadata::default()
+            },
+            ..LLMResponse::default()
+        }
+    }
+
+    #[test]
+    fn snapshots_use_the_longest_matching_base_price() {
+        let table = PricingTable::builtin();
+        assert_eq!(
+            table.price("openai", "gpt-4o-mini-2024-07-18"),
+            Some(ModelPrice::new(0.15, 0.60))
+        );
+        assert_eq!(
+            table.price("openai", "gpt-4o-2024-08-06"),
+            Some(ModelPrice::new(2.50, 10.00))
+        );
+        assert_eq!(table.price("openai", "gpt-4o1"), None);
+        assert_eq!(table.price("ollama", "llama3.2"), Some(ModelPrice::FREE));
+
+        let cost = table.estimate(&response("openai", "gpt-4o")).unwrap();
+        assert!((cost - 0.0075).abs() < 1e-12);
+        let mut cached = response("openai", "gpt-4o");
+        cached
+            .metadata
+            .annotations
+            .insert("cache".into(), json!("hit"));
+        assert_eq!(table.estimate(&cached), Some(0.0));
+        assert_eq!(table.estimate(&response("rest", "custom")), None);
+    }
+
+    #[test]
+    fn registered_prices_apply_to_responses() {
+        let custom = response("pricing-test", "house-model-v2");
+        assert_eq!(custom.estimated_cost_usd(), None);
+
+        register_price("pricing-test", "house-model", ModelPrice::new(1.0, 2.0));
+        assert_eq!(custom.estimated_cost_usd(), Some(0.002));
+        assert_eq!(
+            price_for("pricing-test", "house-model"),
+            Some(ModelPrice::new(1.0, 2.0))
+        );
+    }
+}
@@ -0,0 +1,580 @@
+//! Models built from configuration and looked up by role.
+//!
+//! A registry config maps logical names such as `planner` or `summarizer`
+//! to a provider, model and credentials, so agents ask for a role instead of
+//! constructing concrete model types:
+//!
+//! ```toml
+//! [models.planner]
+//! provider = "openai"
+//! model = "gpt-4o"
+//! credential = { env = "OPENAI_API_KEY" }
+//! options = { temperature = 0.2 }
+//!
+//! [models.summarizer]
+//! provider = "ollama"
+//! model = "llama3.2"
+//! ```
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use agent_telemetry::Secret;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    ChatMessage, ChatOptions, EventStream, GenerationOptions, LLMModel, LLMResponse, ModelError,
+    StubModel, Tokenizer,
+};
+
+/// Backends a [`ModelConfig`] can name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModelProvider {
+    /// OpenAI or a compatible server at `endpoint`; needs the `openai` feature.
+    #[serde(rename = "openai")]
+    OpenAI,
+    /// An Azure OpenAI deployment named by `model` on the resource at
+    /// `endpoint`; needs the `azure` feature.
+    #[serde(rename = "azure_openai")]
+    AzureOpenAI,
+    /// An Ollama server, local by default; needs the `ollama` feature.
+    Ollama,
+    /// A Hugging Face model, on the serverless Inference API unless
+    /// `endpoint` names a TGI server; needs the `huggingface` feature.
+    #[serde(rename = "huggingface")]
+    HuggingFace,
+    /// Any other server speaking the Chat Completions format at `endpoint`,
+    /// through [`OpenAICompatibleModel`](crate::OpenAICompatibleModel);
+    /// needs the `openai` feature.
+    #[serde(rename = "openai_compatible")]
+    OpenAICompatible,
+    /// The offline [`StubModel`], for tests and dry runs.
+    Stub,
+}
+
+impl ModelProvider {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ModelProvider::OpenAI => "openai",
+            ModelProvider::AzureOpenAI => "azure_openai",
+            ModelProvider::Ollama => "ollama",
+            ModelProvider::HuggingFace => "huggingface",
+            ModelProvider::OpenAICompatible => "openai_compatible",
+            ModelProvider::Stub => "stub",
+        }
+    }
+}
+
+/// Where a credential is read from when the model is built; secrets never
+/// live in the config itself.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CredentialRef {
+    /// An environment variable.
+    Env(String),
+    /// A file holding only the secret; surrounding whitespace is ignored.
+    File(PathBuf),
+}
+
+impl CredentialRef {
+    pub fn resolve(&self) -> Result<Secret<String>, RegistryError> {
+        match self {
+            CredentialRef::Env(var) => std::env::var(var)
+                .ok()
+                .filter(|value| !value.is_empty())
+                .map(Secret::new)
+                .ok_or_else(|| RegistryError::MissingCredential(format!("env {var}"))),
+            CredentialRef::File(path) => std::fs::read_to_string(path)
+                .map(|value| Secret::new(value.trim().to_string()))
+                .map_err(|_| RegistryError::MissingCredential(format!("file {}", path.display()))),
+        }
+    }
+}
+
+/// How to build one model.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelConfig {
+    pub provider: ModelProvider,
+    /// Model name, or the deployment name for Azure OpenAI.
+    pub model: String,
+    /// Base URL of the provider; required for Azure OpenAI and
+    /// OpenAI-compatible servers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub credential: Option<CredentialRef>,
+    /// Azure OpenAI API version; the connection's default when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_version: Option<String>,
+    /// OpenAI organization sent with every request.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub organization: Option<String>,
+    #[serde(default)]
+    pub supports_tools: bool,
+    #[serde(default)]
+    pub reasoning: bool,
+    /// Defaults for requests that leave a sampling option unset.
+    #[serde(default)]
+    pub options: GenerationOptions,
+}
+
+impl ModelConfig {
+    pub fn new(provider: ModelProvider, model: impl Into<String>) -> Self {
+        Self {
+            provider,
+            model: model.into(),
+            endpoint: None,
+            credential: None,
+            api_version: None,
+            organization: None,
+            supports_tools: false,
+            reasoning: false,
+            options: GenerationOptions::default(),
+        }
+    }
+
+    pub fn with_endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.endpoint = Some(endpoint.into());
+        self
+    }
+
+    pub fn with_credential(mut self, credential: CredentialRef) -> Self {
+        self.credential = Some(credential);
+        self
+    }
+
+    pub fn with_options(mut self, options: GenerationOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Builds the configured model, resolving its credential.
+    pub fn build(&self) -> Result<Arc<dyn LLMModel>, RegistryError> {
+        let model: Arc<dyn LLMModel> = match self.provider {
+            ModelProvider::OpenAI => self.build_openai()?,
+            ModelProvider::AzureOpenAI => self.build_azure()?,
+            ModelProvider::Ollama => self.build_ollama()?,
+            ModelProvider::HuggingFace => self.build_huggingface()?,
+            ModelProvider::OpenAICompatible => self.build_openai_compatible()?,
+            ModelProvider::Stub => Arc::new(StubModel),
+        };
+        if self.options == GenerationOptions::default() {
+            return Ok(model);
+        }
+        Ok(Arc::new(WithDefaults {
+            model,
+            defaults: self.options.clone(),
+        }))
+    }
+
+    #[cfg(feature = "openai")]
+    fn required_endpoint(&self) -> Result<&str, RegistryError> {
+        self.endpoint.as_deref().ok_or_else(|| {
+            RegistryError::Invalid(format!(
+                "{} models need an endpoint",
+                self.provider.as_str()
+            ))
+        })
+    }
+
+    #[cfg(feature = "openai")]
+    fn required_credential(&self) -> Result<Secret<String>, RegistryError> {
+        self.credential
+            .as_ref()
+            .ok_or_else(|| {
+                RegistryError::Invalid(format!(
+                    "{} models need a credential",
+                    self.provider.as_str()
+                ))
+            })?
+            .resolve()
+    }
+
+    #[cfg(feature = "openai")]
+    fn build_openai(&self) -> Result<Arc<dyn LLMModel>, RegistryError> {
+        let mut connection = crate::OpenAIConnection::new(self.required_credential()?);
+        if let Some(endpoint) = &self.endpoint {
+            connection = connection.with_base_url(endpoint);
+        }
+        if let Some(organization) = &self.organization {
+            connection = connection.with_organization(organization);
+        }
+        let mut model = crate::OpenAIChatModel::new(&self.model).with_connection(connection);
+        model.supports_tools = self.supports_tools;
+        model.reasoning = self.reasoning;
+        Ok(Arc::new(model))
+    }
+
+    #[cfg(not(feature = "openai"))]
+    fn build_openai(&self) -> Result<Arc<dyn LLMModel>, RegistryError> {
+        Err(RegistryError::FeatureDisabled {
+            provider: self.provider,
+            feature: "openai",
+        })
+    }
+
+    #[cfg(feature = "openai")]
+    fn build_openai_compatible(&self) -> Result<Arc<dyn LLMModel>, RegistryError> {
+        let mut model = crate::OpenAICompatibleModel::new(self.required_endpoint()?, &self.model);
+        if let Some(credential) = &self.credential {
+            model = model.with_api_key(credential.resolve()?);
+        }
+        model.supports_tools = self.supports_tools;
+        Ok(Arc::new(model))
+    }
+
+    #[cfg(not(feature = "openai"))]
+    fn build_openai_compatible(&self) -> Result<Arc<dyn LLMModel>, RegistryError> {
+        Err(RegistryError::FeatureDisabled {
+            provider: self.provider,
+            feature: "openai",
+        })
+    }
+
+    #[cfg(feature = "azure")]
+    fn build_azure(&self) -> Result<Arc<dyn LLMModel>, RegistryError> {
+        let mut connection = crate::AzureOpenAIConnection::with_api_key(
+            self.required_endpoint()?,
+            self.required_credential()?,
+        );
+        if let Some(api_version) = &self.api_version {
+            connection = connection.with_api_version(api_version);
+        }
+        let mut model = crate::AzureOpenAIModel::new(&self.model).with_connection(connection);
+        model.supports_tools = self.supports_tools;
+        model.reasoning = self.reasoning;
+        Ok(Arc::new(model))
+    }
+
+    #[cfg(not(feature = "azure"))]
+    fn build_azure(&self) -> Result<Arc<dyn LLMModel>, RegistryError> {
+        Err(RegistryError::FeatureDisabled {
+            provider: self.provider,
+            feature: "azure",
+        })
+    }
+
+    #[cfg(feature = "ollama")]
+    fn build_ollama(&self) -> Result<Arc<dyn LLMModel>, RegistryError> {
+        let endpoint = self
+            .endpoint
+            .as_deref()
+            .unwrap_or(crate::OllamaConnection::DEFAULT_BASE_URL);
+        let mut model = crate::OllamaModel::new(&self.model)
+            .with_connection(crate::OllamaConnection::new(endpoint));
+        model.supports_tools = self.supports_tools;
+        Ok(Arc::new(model))
+    }
+
+    #[cfg(not(feature = "ollama"))]
+    fn build_ollama(&self) -> Result<Arc<dyn LLMModel>, RegistryError> {
+        Err(RegistryError::FeatureDisabled {
+            provider: self.provider,
+            feature: "ollama",
+        })
+    }
+
+    #[cfg(feature = "huggingface")]
+    fn build_huggingface(&self) -> Result<Arc<dyn LLMModel>, RegistryError> {
+        let mut model = match &self.endpoint {
+            Some(endpoint) => crate::HuggingFaceModel::tgi(endpoint, &self.model),
+            None => crate::HuggingFaceModel::new(&self.model),
+        };
+        if let Some(credential) = &self.credential {
+            model = model.with_token(credential.resolve()?);
+        }
+        model.supports_tools = self.supports_tools;
+        Ok(Arc::new(model))
+    }
+
+    #[cfg(not(feature = "huggingface"))]
+    fn build_huggingface(&self) -> Result<Arc<dyn LLMModel>, RegistryError> {
+        Err(RegistryError::FeatureDisabled {
+            provider: self.provider,
+            feature: "huggingface",
+        })
+    }
+}
+
+/// Models by logical name, as read from a TOML or JSON file.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModelRegistryConfig {
+    #[serde(default)]
+    pub models: BTreeMap<String, ModelConfig>,
+}
+
+impl ModelRegistryConfig {
+    pub fn from_toml(source: &str) -> Result<Self, RegistryError> {
+        toml::from_str(source).map_err(|error| RegistryError::Parse(error.to_string()))
+    }
+
+    pub fn from_json(source: &str) -> Result<Self, RegistryError> {
+        serde_json::from_str(source).map_err(|error| RegistryError::Parse(error.to_string()))
+    }
+
+    /// Reads a `.json` file as JSON and anything else as TOML.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, RegistryError> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path)
+            .map_err(|error| RegistryError::Parse(format!("{}: {error}", path.display())))?;
+        if path
+            .extension()
+            .is_some_and(|extension| extension == "json")
+        {
+            Self::from_json(&source)
+        } else {
+            Self::from_toml(&source)
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum RegistryError {
+    #[error("failed to parse model config: {0}")]
+    Parse(String),
+    #[error("invalid model config: {0}")]
+    Invalid(String),
+    #[error("credential not found: {0}")]
+    MissingCredential(String),
+    #[error("{} models need the `{feature}` feature", provider.as_str())]
+    FeatureDisabled {
+        provider: ModelProvider,
+        feature: &'static str,
+    },
+    #[error("failed to build model `{name}`: {source}")]
+    Model {
+        name: String,
+        source: Box<RegistryError>,
+    },
+    #[error("no model registered as `{0}`")]
+    UnknownModel(String),
+}
+
+/// Models shared by logical name.
+///
+/// Resolved models are `Arc<dyn LLMModel>`, which is itself an
+/// [`LLMModel`], so they plug into anything generic over a model.
+#[derive(Clone, Default)]
+pub struct ModelRegistry {
+    models: BTreeMap<String, Arc<dyn LLMModel>>,
+}
+
+impl ModelRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builds every model in `config`, failing on the first that cannot be
+    /// built.
+    pub fn from_config(config: &ModelRegistryConfig) -> Result<Self, RegistryError> {
+        let mut registry = Self::new();
+        for (name, model) in &config.models {
+            let built = model.build().map_err(|source| RegistryError::Model {
+                name: name.clone(),
+                source: Box::new(source),
+            })?;
+            registry.models.insert(name.clone(), built);
+        }
+        Ok(registry)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, RegistryError> {
+        Self::from_config(&ModelRegistryConfig::load(path)?)
+    }
+
+    pub fn with_model(mut self, name: impl Into<String>, model: impl LLMModel + 'static) -> Self {
+        self.register(name, model);
+        self
+    }
+
+    /// Adds `model` under `name`, replacing any model already there.
+    pub fn register(&mut self, name: impl Into<String>, model: impl LLMModel + 'static) {
+        self.models.insert(name.into(), Arc::new(model));
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn LLMModel>> {
+        self.models.get(name).cloned()
+    }
+
+    /// Like [`get`](Self::get), but failing with
+    /// [`RegistryError::UnknownModel`].
+    pub fn resolve(&self, name: &str) -> Result<Arc<dyn LLMModel>, RegistryError> {
+        self.get(name)
+            .ok_or_else(|| RegistryError::UnknownModel(name.to_string()))
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.models.keys().map(String::as_str)
+    }
+}
+
+impl std::fmt::Debug for ModelRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ModelRegistry")
+            .field("models", &self.models.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+/// Fills in sampling options a request leaves unset from the config.
+struct WithDefaults {
+    model: Arc<dyn LLMModel>,
+    defaults: GenerationOptions,
+}
+
+impl WithDefaults {
+    fn merge(&self, options: &GenerationOptions) -> GenerationOptions {
+        let defaults = &self.defaults;
+        GenerationOptions {
+            temperature: options.temperature.or(defaults.temperature),
+            top_p: options.top_p.or(defaults.top_p),
+            max_tokens: options.max_tokens.or(defaults.max_tokens),
+            stop: if options.stop.is_empty() {
+                defaults.stop.clone()
+            } else {
+                options.stop.clone()
+            },
+            frequency_penalty: options.frequency_penalty.or(defaults.frequency_penalty),
+            presence_penalty: options.presence_penalty.or(defaults.presence_penalty),
+            seed: options.seed.or(defaults.seed),
+        }
+    }
+}
+
+#[async_trait]
+impl LLMModel for WithDefaults {
+    async fn generate(
+        &self,
+        prompt: &str,
+        options: &GenerationOptions,
+    ) -> Result<LLMResponse, ModelError> {
+        self.model.generate(prompt, &self.merge(options)).await
+    }
+
+    async fn stream(
+        &self,
+        prompt: &str,
+        options: &GenerationOptions,
+    ) -> Result<EventStream, ModelError> {
+        self.model.stream(prompt, &self.merge(options)).await
+    }
+
+    fn supports_tools(&self) -> bool {
+        self.model.supports_tools()
+    }
+
+    async fn chat(
+        &self,
+        messages: &[ChatMessage],
+        options: &ChatOptions,
+    ) -> Result<LLMResponse, ModelError> {
+        let options = ChatOptions {
+            generation: self.merge(&options.generation),
+            ..options.clone()
+        };
+        self.model.chat(messages, &options).await
+    }
+
+    fn tokenizer(&self) -> Arc<dyn Tokenizer> {
+        self.model.tokenizer()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+        [models.planner]
+        provider = "stub"
+        model = "echo"
+        options = { max_tokens = 2 }
+
+        [models.summarizer]
+        provider = "stub"
+        model = "echo"
+    "#;
+
+    #[tokio::test]
+    async fn builds_models_from_toml_and_resolves_them_by_role() {
+        let config = ModelRegistryConfig::from_toml(CONFIG).unwrap();
+        let registry = ModelRegistry::from_config(&config).unwrap();
+        assert_eq!(
+            registry.names().collect::<Vec<_>>(),
+            vec!["planner", "summarizer"]
+        );
+
+        let planner = registry.resolve("planner").unwrap();
+        let response = planner
+            .generate("plan the release", &GenerationOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(response.content, "echo: plan the release");
+
+        let defaults = WithDefaults {
+            model: planner,
+            defaults: config.models["planner"].options.clone(),
+        };
+        let request = GenerationOptions::default().with_temperature(0.5);
+        assert_eq!(defaults.merge(&request), request.clone().with_max_tokens(2));
+        assert_eq!(
+            defaults
+                .merge(&request.clone().with_max_tokens(8))
+                .max_tokens,
+            Some(8)
+        );
+
+        assert!(matches!(
+            registry.resolve("critic"),
+            Err(RegistryError::UnknownModel(name)) if name == "critic"
+        ));
+    }
+
+    #[test]
+    fn reports_missing_credentials_and_disabled_providers() {
+        let config = ModelRegistryConfig::from_json(
+            r#"{"models": {"writer": {
+                "provider": "openai_compatible",
+                "model": "house-writer",
+                "endpoint": "http://localhost:8000/v1",
+                "credential": {"env": "REGISTRY_TEST_UNSET_TOKEN"}
+            }}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.models["writer"].credential,
+            Some(CredentialRef::Env("REGISTRY_TEST_UNSET_TOKEN".into()))
+        );
+        let err = ModelRegistry::from_config(&config).unwrap_err();
+
+        #[cfg(feature = "openai")]
+        {
+            assert_eq!(
+                err.to_string(),
+                "failed to build model `writer`: credential not found: env REGISTRY_TEST_UNSET_TOKEN"
+            );
+            assert!(matches!(
+                ModelConfig::new(ModelProvider::OpenAICompatible, "house-writer").build(),
+                Err(RegistryError::Invalid(_))
+            ));
+        }
+        #[cfg(not(feature = "openai"))]
+        assert_eq!(
+            err.to_string(),
+            "failed to build model `writer`: openai_compatible models need the `openai` feature"
+        );
+
+        #[cfg(not(feature = "ollama"))]
+        assert!(matches!(
+            ModelConfig::new(ModelProvider::Ollama, "llama3.2").build(),
+            Err(RegistryError::FeatureDisabled {
+                feature: "ollama",
+                ..
+            })
+        ));
+    }
+}
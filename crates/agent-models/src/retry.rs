@@ -0,0 +1,370 @@
+//! Retries of transient model failures with exponential backoff.
+
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use agent_telemetry::Telemetry;
+use async_trait::async_trait;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    ChatMessage, ChatOptions, EventStream, GenerationOptions, LLMModel, LLMResponse, ModelError,
+    Tokenizer,
+};
+
+/// How a [`RetryingModel`] spaces out and bounds its retries.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ModelRetryPolicy {
+    pub max_retries: usize,
+    /// Wait before the first retry; doubled for each one after.
+    pub base_delay: Duration,
+    /// Cap on any single backoff wait. A provider's `Retry-After` is
+    /// honoured even when longer.
+    pub max_delay: Duration,
+    /// Randomises each backoff wait between half and all of its length, so
+    /// callers that failed together do not retry together.
+    pub jitter: bool,
+    /// Total time to spend waiting across all retries of one request. A
+    /// retry whose wait would overrun it is not attempted.
+    pub budget: Option<Duration>,
+}
+
+impl Default for ModelRetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            jitter: true,
+            budget: Some(Duration::from_secs(60)),
+        }
+    }
+}
+
+impl ModelRetryPolicy {
+    pub fn with_max_retries(mut self, max_retries: usize) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    pub fn with_base_delay(mut self, base_delay: Duration) -> Self {
+        self.base_delay = base_delay;
+        self
+    }
+
+    pub fn with_max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    pub fn with_jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    pub fn with_budget(mut self, budget: Option<Duration>) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    /// Wait before retry number `retry` (zero-based) after `error`: the
+    /// provider's `Retry-After` when it sent one, otherwise backoff.
+    pub fn delay_for(&self, retry: usize, error: &ModelError) -> Duration {
+        if let Some(retry_after) = error.retry_after() {
+            return retry_after;
+        }
+        let factor = 2u32.saturating_pow(retry.min(31) as u32);
+        let delay = self.base_delay.saturating_mul(factor).min(self.max_delay);
+        if self.jitter && !delay.is_zero() {
+            let half = delay / 2;
+            half + rand::thread_rng().gen_range(Duration::ZERO..=delay - half)
+        } else {
+            delay
+        }
+    }
+}
+
+/// Re-sends requests that fail with a [retryable](ModelError::is_retryable)
+/// error (rate limits, timeouts, transport failures and 5xx responses) under
+/// a [`ModelRetryPolicy`].
+///
+/// Responses that needed retries carry a `retries` annotation with the
+/// count. Streams are retried only while opening. With
+/// [`RetryingModel::with_telemetry`], each retry is counted in
+/// `model_retries` labelled by the error's [`kind`](ModelError::kind).
+pub struct RetryingModel<M: LLMModel> {
+    pub model: M,
+    pub policy: ModelRetryPolicy,
+    pub telemetry: Option<Arc<Telemetry>>,
+    retries: AtomicU64,
+}
+
+impl<M: LLMModel> RetryingModel<M> {
+    pub fn new(model: M) -> Self {
+        Self {
+            model,
+            policy: ModelRetryPolicy::default(),
+            telemetry: None,
+            retries: AtomicU64::new(0),
+        }
+    }
+
+    pub fn with_policy(mut self, policy: ModelRetryPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn with_telemetry(mut self, telemetry: Arc<Telemetry>) -> Self {
+        self.telemetry = Some(telemetry);
+        self
+    }
+
+    /// Retries made across all requests so far.
+    pub fn retries(&self) -> u64 {
+        self.retries.load(Ordering::Relaxed)
+    }
+
+    /// Runs `call` until it succeeds, fails for good, or the policy is
+    /// exhausted, returning the result with the number of retries made.
+    async fn retry<'a, T, F, Fut>(&'a self, call: F) -> Result<(T, usize), ModelError>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T, ModelError>> + 'a,
+    {
+        let mut retries = 0;
+        let mut waited = Duration::ZERO;
+        loop {
+            let error = match call().await {
+                Ok(value) => return Ok((value, retries)),
+                Err(error) => error,
+            };
+            if !error.is_retryable() || retries >= self.policy.max_retries {
+                return Err(error);
+            }
+            let delay = self.policy.delay_for(retries, &error);
+            if self
+                .policy
+                .budget
+                .is_some_and(|budget| waited + delay > budget)
+            {
+                tracing::warn!(%error, retries, "model retry budget exhausted");
+                return Err(error);
+            }
+            tracing::warn!(
+                %error,
+                retry = retries + 1,
+                delay_ms = delay.as_millis() as u64,
+                "retrying model call"
+            );
+            self.retries.fetch_add(1, Ordering::Relaxed);
+            if let Some(telemetry) = &self.telemetry {
+                telemetry.record_model_retry(error.kind());
+            }
+            tokio::time::sleep(delay).await;
+            waited += delay;
+            retries += 1;
+        }
+    }
+
+    fn annotate(mut response: LLMResponse, retries: usize) -> LLMResponse {
+        if retries > 0 {
+            response
+                .metadata
+                .annotations
+                .insert("retries".into(), json!(retries));
+        }
+        response
+    }
+}
+
+#[async_trait]
+impl<M: LLMModel> LLMModel for RetryingModel<M> {
+    async fn generate(
+        &self,
+        prompt: &str,
+        options: &GenerationOptions,
+    ) -> Result<LLMResponse, ModelError> {
+        let (response, retries) = self.retry(|| self.model.generate(prompt, options)).await?;
+        Ok(Self::annotate(response, retries))
+    }
+
+    async fn stream(
+        &self,
+        prompt: &str,
+        options: &GenerationOptions,
+    ) -> Result<EventStream, ModelError> {
+        let (events, _) = self.retry(|| self.model.stream(prompt, options)).await?;
+        Ok(events)
+    }
+
+    fn supports_tools(&self) -> bool {
+        self.model.supports_tools()
+    }
+
+    async fn chat(
+        &self,
+        messages: &[ChatMessage],
+        options: &ChatOptions,
+    ) -> Result<LLMResponse, ModelError> {
+        let (response, retries) = self.retry(|| self.model.chat(messages, options)).await?;
+        Ok(Self::annotate(response, retries))
+    }
+
+    fn tokenizer(&self) -> Arc<dyn Tokenizer> {
+        self.model.tokenizer()
+    }
+}
+
+impl<M: LLMModel> std::fmt::Debug for RetryingModel<M> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RetryingModel")
+            .field("policy", &self.policy)
+            .field("retries", &self.retries())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StubModel;
+    use std::sync::Mutex;
+    use tokio::time::Instant;
+
+    /// Returns the scripted errors in order, then succeeds.
+    struct Flaky {
+        errors: Mutex<Vec<ModelError>>,
+        calls: AtomicU64,
+    }
+
+    impl Flaky {
+        fn new(mut errors: Vec<ModelError>) -> Self {
+            errors.reverse();
+            Self {
+                errors: Mutex::new(errors),
+                calls: AtomicU64::new(0),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl LLMModel for Flaky {
+        async fn generate(
+            &self,
+            prompt: &str,
+            options: &GenerationOptions,
+        ) -> Result<LLMResponse, ModelError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            let scripted = self.errors.lock().unwrap().pop();
+            match scripted {
+                Some(error) => Err(error),
+                None => StubModel.generate(prompt, options).await,
+            }
+        }
+
+        async fn stream(
+            &self,
+            prompt: &str,
+            options: &GenerationOptions,
+        ) -> Result<EventStream, ModelError> {
+            StubModel.stream(prompt, options).await
+        }
+
+        fn supports_tools(&self) -> bool {
+            false
+        }
+    }
+
+    fn immediate() -> ModelRetryPolicy {
+        ModelRetryPolicy::default().with_base_delay(Duration::ZERO)
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap_and_defers_to_retry_after() {
+        let policy = ModelRetryPolicy::default()
+            .with_base_delay(Duration::from_millis(100))
+            .with_max_delay(Duration::from_millis(350))
+            .with_jitter(false);
+        let delays: Vec<_> = (0..4)
+            .map(|retry| policy.delay_for(retry, &ModelError::Timeout).as_millis())
+            .collect();
+        assert_eq!(delays, vec![100, 200, 350, 350]);
+
+        let throttled = ModelError::RateLimited {
+            retry_after: Some(Duration::from_secs(2)),
+            message: "slow down".into(),
+        };
+        assert_eq!(policy.delay_for(0, &throttled), Duration::from_secs(2));
+
+        let jittered = policy.with_jitter(true).delay_for(1, &ModelError::Timeout);
+        assert!(jittered >= Duration::from_millis(100) && jittered <= Duration::from_millis(200));
+    }
+
+    #[tokio::test]
+    async fn retries_transient_failures_and_honours_retry_after() {
+        let telemetry = Arc::new(Telemetry::new());
+        let model = RetryingModel::new(Flaky::new(vec![
+            ModelError::Api {
+                status: 503,
+                message: "overloaded".into(),
+            },
+            ModelError::RateLimited {
+                retry_after: Some(Duration::from_millis(50)),
+                message: "slow down".into(),
+            },
+        ]))
+        .with_policy(immediate())
+        .with_telemetry(telemetry.clone());
+
+        let started = Instant::now();
+        let response = model
+            .generate("hi", &GenerationOptions::default())
+            .await
+            .unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(50));
+        assert_eq!(response.content, "echo: hi");
+        assert_eq!(response.metadata.annotations["retries"], 2);
+        assert_eq!(model.retries(), 2);
+        let metrics = telemetry.export_metrics();
+        assert!(metrics.contains(r#"model_retries{reason="rate_limited"} 1"#));
+        assert!(metrics.contains(r#"model_retries{reason="server_error"} 1"#));
+    }
+
+    #[tokio::test]
+    async fn gives_up_on_permanent_errors_and_exhausted_budgets() {
+        let model = RetryingModel::new(Flaky::new(vec![ModelError::Auth("bad key".into())]))
+            .with_policy(immediate());
+        let err = model
+            .generate("hi", &GenerationOptions::default())
+            .await
+            .unwrap_err();
+        assert_eq!(err, ModelError::Auth("bad key".into()));
+        assert_eq!(model.model.calls.load(Ordering::SeqCst), 1);
+
+        let throttled = ModelError::RateLimited {
+            retry_after: Some(Duration::from_secs(120)),
+            message: "come back later".into(),
+        };
+        let model =
+            RetryingModel::new(Flaky::new(vec![throttled.clone()])).with_policy(immediate());
+        let err = model
+            .generate("hi", &GenerationOptions::default())
+            .await
+            .unwrap_err();
+        assert_eq!(err, throttled);
+        assert_eq!(model.retries(), 0);
+
+        let model = RetryingModel::new(Flaky::new(vec![ModelError::Timeout; 3]))
+            .with_policy(immediate().with_max_retries(2));
+        let err = model
+            .generate("hi", &GenerationOptions::default())
+            .await
+            .unwrap_err();
+        assert_eq!(err, ModelError::Timeout);
+        assert_eq!(model.model.calls.load(Ordering::SeqCst), 3);
+    }
+}
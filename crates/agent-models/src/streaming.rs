@@ -0,0 +1,82 @@
+//! Line-oriented readers for streamed HTTP response bodies.
+
+use std::collections::VecDeque;
+
+use futures::stream;
+
+use crate::{EventStream, FinishReason, StreamEvent};
+
+struct LineState<F> {
+    response: reqwest::Response,
+    // Raw bytes, so a UTF-8 character split across chunks decodes intact.
+    buffer: Vec<u8>,
+    pending: VecDeque<StreamEvent>,
+    done: bool,
+    parse: F,
+}
+
+/// Splits `response` into lines as chunks arrive and yields the events
+/// `parse` extracts from each, ending after the first [`StreamEvent::Done`].
+/// A body that ends without one gets `Done` with no finish reason, and a
+/// transport error mid-body ends the stream with [`FinishReason::Error`].
+pub(crate) fn line_stream<F>(
+    response: reqwest::Response,
+    provider: &'static str,
+    parse: F,
+) -> EventStream
+where
+    F: FnMut(&str) -> Vec<StreamEvent> + Send + 'static,
+{
+    let state = LineState {
+        response,
+        buffer: Vec::new(),
+        pending: VecDeque::new(),
+        done: false,
+        parse,
+    };
+    Box::pin(stream::unfold(state, move |mut state| async move {
+        loop {
+            if let Some(event) = state.pending.pop_front() {
+                return Some((event, state));
+            }
+            if state.done {
+                return None;
+            }
+            let mut finish = None;
+            match state.response.chunk().await {
+                Ok(Some(bytes)) => state.buffer.extend_from_slice(&bytes),
+                Ok(None) => {
+                    // Flush a final line that lacks its trailing newline.
+                    state.buffer.push(b'\n');
+                    finish = Some(None);
+                }
+                Err(error) => {
+                    tracing::warn!(%error, provider, "model stream interrupted");
+                    state.buffer.clear();
+                    finish = Some(Some(FinishReason::Error));
+                }
+            }
+            while let Some(end) = state.buffer.iter().position(|&byte| byte == b'\n') {
+                let line: Vec<u8> = state.buffer.drain(..=end).collect();
+                for event in (state.parse)(String::from_utf8_lossy(&line).trim()) {
+                    match event {
+                        StreamEvent::TextDelta { ref text } if text.is_empty() => {}
+                        StreamEvent::Done { .. } => {
+                            state.pending.push_back(event);
+                            state.done = true;
+                            break;
+                        }
+                        event => state.pending.push_back(event),
+                    }
+                }
+                if state.done {
+                    break;
+                }
+            }
+            if let (Some(finish_reason), false) = (finish, state.done) {
+                state.pending.push_back(StreamEvent::Done { finish_reason });
+                state.done = true;
+            }
+        }
+    }))
+}
@@ -0,0 +1,306 @@
+//! Typed, schema-constrained generation on top of [`LLMModel::chat`].
+//!
+//! The JSON schema of the target type is sent both in the system prompt and
+//! as a [`ResponseFormat::JsonSchema`] for providers that enforce it. Replies
+//! that fail to parse are sent back with the error for repair, up to
+//! [`STRUCTURED_OUTPUT_ATTEMPTS`] attempts in total.
+
+use async_trait::async_trait;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::{ChatMessage, ChatOptions, GenerationOptions, LLMModel, LLMResponse, ModelError};
+
+/// Attempts made by [`StructuredGeneration::generate_structured`], including
+/// the first.
+pub const STRUCTURED_OUTPUT_ATTEMPTS: usize = 3;
+
+/// Output format requested through [`ChatOptions::response_format`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResponseFormat {
+    /// Any syntactically valid JSON object.
+    JsonObject,
+    /// JSON matching `schema`; `name` identifies it to the provider.
+    JsonSchema {
+        name: String,
+        schema: Value,
+        #[serde(default)]
+        strict: bool,
+    },
+}
+
+#[derive(Debug, Error)]
+pub enum StructuredOutputError {
+    /// The model call itself failed; no reply was available to repair.
+    #[error(transparent)]
+    Model(#[from] ModelError),
+    #[error(
+        "model output did not match the `{type_name}` schema after {attempts} attempts: {message}"
+    )]
+    Invalid {
+        type_name: String,
+        attempts: usize,
+        /// Parse error for the final reply.
+        message: String,
+        /// The final reply, verbatim.
+        last_output: String,
+    },
+}
+
+#[async_trait]
+pub trait StructuredGeneration: LLMModel {
+    /// Asks for a reply matching `T`'s JSON schema and deserializes it,
+    /// feeding parse errors back to the model for repair.
+    async fn generate_structured<T>(
+        &self,
+        prompt: &str,
+        options: &GenerationOptions,
+    ) -> Result<T, StructuredOutputError>
+    where
+        T: DeserializeOwned + JsonSchema + Send;
+}
+
+#[async_trait]
+impl<M: LLMModel + ?Sized> StructuredGeneration for M {
+    async fn generate_structured<T>(
+        &self,
+        prompt: &str,
+        options: &GenerationOptions,
+    ) -> Result<T, StructuredOutputError>
+    where
+        T: DeserializeOwned + JsonSchema + Send,
+    {
+        let type_name = T::schema_name().into_owned();
+        let schema = serde_json::to_value(schemars::schema_for!(T)).unwrap_or_default();
+        let chat_options = ChatOptions {
+            response_format: Some(ResponseFormat::JsonSchema {
+                name: schema_identifier(&type_name),
+                schema: schema.clone(),
+                strict: false,
+            }),
+            generation: options.clone(),
+            ..ChatOptions::default()
+        };
+        let mut messages = vec![
+            ChatMessage::system(format!(
+                "Respond only with JSON that matches this JSON schema, without commentary:\n{schema}"
+            )),
+            ChatMessage::user(prompt),
+        ];
+
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let response = self.chat(&messages, &chat_options).await?;
+            let error = match parse_json::<T>(&response.content) {
+                Ok(value) => return Ok(value),
+                Err(error) => error,
+            };
+            if attempts >= STRUCTURED_OUTPUT_ATTEMPTS {
+                return Err(StructuredOutputError::Invalid {
+                    type_name,
+                    attempts,
+                    message: error,
+                    last_output: response.content,
+                });
+            }
+            tracing::debug!(attempt = attempts, %error, "repairing structured output");
+            messages.push(ChatMessage::assistant(&LLMResponse {
+                tool_calls: Vec::new(),
+                ..response
+            }));
+            messages.push(ChatMessage::user(format!(
+                "That reply could not be parsed: {error}. Reply again with only the corrected JSON."
+            )));
+        }
+    }
+}
+
+/// Provider schema names allow only ASCII alphanumerics, `_` and `-`.
+fn schema_identifier(type_name: &str) -> String {
+    type_name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Parses `content` as `T`, tolerating Markdown code fences and prose around
+/// the JSON value.
+fn parse_json<T: DeserializeOwned>(content: &str) -> Result<T, String> {
+    let trimmed = content.trim();
+    let unfenced = trimmed
+        .strip_prefix("```json")
+        .or_else(|| trimmed.strip_prefix("```"))
+        .and_then(|rest| rest.strip_suffix("```"))
+        .map(str::trim)
+        .unwrap_or(trimmed);
+    let error = match serde_json::from_str(unfenced) {
+        Ok(value) => return Ok(value),
+        Err(error) => error.to_string(),
+    };
+    let start = unfenced.find(['{', '[']);
+    let end = unfenced.rfind(['}', ']']);
+    match (start, end) {
+        (Some(start), Some(end)) if start < end => {
+            serde_json::from_str(&unfenced[start..=end]).map_err(|_| error)
+        }
+        _ => Err(error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EventStream;
+    use std::sync::Mutex;
+
+    #[derive(Debug, Deserialize, JsonSchema, PartialEq)]
+    struct Forecast {
+        city: String,
+        high_c: i32,
+    }
+
+    /// Replays canned replies and records each conversation it receives.
+    struct Scripted {
+        replies: Mutex<Vec<&'static str>>,
+        seen: Mutex<Vec<(Vec<ChatMessage>, ChatOptions)>>,
+    }
+
+    impl Scripted {
+        fn new(replies: &[&'static str]) -> Self {
+            Self {
+                replies: Mutex::new(replies.iter().rev().copied().collect()),
+                seen: Mutex::new(Vec::new()),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl LLMModel for Scripted {
+        async fn generate(
+            &self,
+            _prompt: &str,
+            _options: &GenerationOptions,
+        ) -> Result<LLMResponse, ModelError> {
+            unreachable!("structured generation goes through chat")
+        }
+
+        async fn stream(
+            &self,
+            _prompt: &str,
+            _options: &GenerationOptions,
+        ) -> Result<EventStream, ModelError> {
+            unreachable!("structured generation does not stream")
+        }
+
+        fn supports_tools(&self) -> bool {
+            false
+        }
+
+        async fn chat(
+            &self,
+            messages: &[ChatMessage],
+            options: &ChatOptions,
+        ) -> Result<LLMResponse, ModelError> {
+            self.seen
+                .lock()
+                .unwrap()
+                .push((messages.to_vec(), options.clone()));
+            let reply = self.replies.lock().unwrap().pop();
+            match reply {
+                Some(content) => Ok(LLMResponse {
+                    content: content.into(),
+                    ..LLMResponse::default()
+                }),
+                None => Err(ModelError::Timeout),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn parses_fenced_json_and_sends_the_schema() {
+        let model = Scripted::new(&["```json\n{\"city\": \"Oslo\", \"high_c\": 4}\n```"]);
+        let forecast: Forecast = model
+            .generate_structured("forecast for Oslo", &GenerationOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            forecast,
+            Forecast {
+                city: "Oslo".into(),
+                high_c: 4
+            }
+        );
+
+        let seen = model.seen.lock().unwrap();
+        let Some(ResponseFormat::JsonSchema { name, schema, .. }) = &seen[0].1.response_format
+        else {
+            panic!("expected a json_schema response format");
+        };
+        assert_eq!(name, "Forecast");
+        assert_eq!(schema["required"], serde_json::json!(["city", "high_c"]));
+        assert!(seen[0].0[0].content.contains("\"high_c\""));
+    }
+
+    #[tokio::test]
+    async fn repairs_invalid_replies_then_gives_up() {
+        let model = Scripted::new(&[
+            "{\"city\": \"Oslo\"}",
+            "Sure! {\"city\": \"Oslo\", \"high_c\": 4} Enjoy.",
+        ]);
+        let forecast: Forecast = model
+            .generate_structured("forecast", &GenerationOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(forecast.high_c, 4);
+        {
+            let seen = model.seen.lock().unwrap();
+            assert_eq!(seen.len(), 2);
+            let repair = &seen[1].0;
+            assert_eq!(repair[2].content, "{\"city\": \"Oslo\"}");
+            assert!(repair[3].content.contains("missing field `high_c`"));
+        }
+
+        let stubborn = Scripted::new(&["nope", "still no", "never"]);
+        let err = stubborn
+            .generate_structured::<Forecast>("forecast", &GenerationOptions::default())
+            .await
+            .unwrap_err();
+        let StructuredOutputError::Invalid {
+            type_name,
+            attempts,
+            last_output,
+            ..
+        } = err
+        else {
+            panic!("expected an invalid-output error, got {err}");
+        };
+        assert_eq!(attempts, STRUCTURED_OUTPUT_ATTEMPTS);
+        assert_eq!(last_output, "never");
+        assert_eq!(type_name, "Forecast");
+    }
+
+    #[tokio::test]
+    async fn model_failures_are_not_retried() {
+        let model = Scripted::new(&[]);
+        let err = model
+            .generate_structured::<Forecast>("forecast", &GenerationOptions::default())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            StructuredOutputError::Model(ModelError::Timeout)
+        ));
+        assert_eq!(model.seen.lock().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,294 @@
+//! Concurrency and request-rate limits for model calls.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Mutex, OwnedSemaphorePermit, Semaphore};
+use tokio::time::Instant;
+use tokio_stream::StreamExt;
+
+use crate::{
+    ChatMessage, ChatOptions, EventStream, GenerationOptions, LLMModel, LLMResponse, ModelError,
+    Tokenizer,
+};
+
+/// At most `requests` started within any `per` window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RateLimit {
+    pub requests: u32,
+    pub per: Duration,
+}
+
+impl RateLimit {
+    pub fn per_minute(requests: u32) -> Self {
+        Self {
+            requests,
+            per: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Limits a [`Throttle`] enforces; `None` leaves that dimension unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ThrottleLimits {
+    pub max_in_flight: Option<usize>,
+    pub rate: Option<RateLimit>,
+}
+
+impl ThrottleLimits {
+    pub fn with_max_in_flight(mut self, max_in_flight: usize) -> Self {
+        self.max_in_flight = Some(max_in_flight.max(1));
+        self
+    }
+
+    pub fn with_requests_per_minute(mut self, requests: u32) -> Self {
+        self.rate = Some(RateLimit::per_minute(requests.max(1)));
+        self
+    }
+
+    pub fn with_rate(mut self, rate: RateLimit) -> Self {
+        self.rate = Some(RateLimit {
+            requests: rate.requests.max(1),
+            ..rate
+        });
+        self
+    }
+}
+
+/// Admission control shared by every [`ThrottledModel`] holding a clone, so
+/// several agents calling one deployment stay within its limits together.
+///
+/// Waiting callers are admitted first come, first served: rate-limited
+/// callers queue on a fair lock while the head of the queue sleeps until the
+/// window has room, then take a concurrency slot from a fair semaphore.
+#[derive(Clone)]
+pub struct Throttle {
+    limits: ThrottleLimits,
+    slots: Option<Arc<Semaphore>>,
+    // Start times of the requests in the current rate window.
+    window: Arc<Mutex<VecDeque<Instant>>>,
+}
+
+/// Holds a concurrency slot until dropped.
+#[derive(Debug)]
+pub struct ThrottlePermit {
+    _slot: Option<OwnedSemaphorePermit>,
+}
+
+impl Throttle {
+    pub fn new(limits: ThrottleLimits) -> Self {
+        Self {
+            limits,
+            slots: limits
+                .max_in_flight
+                .map(|max| Arc::new(Semaphore::new(max))),
+            window: Arc::new(Mutex::new(VecDeque::new())),
+        }
+    }
+
+    pub fn limits(&self) -> ThrottleLimits {
+        self.limits
+    }
+
+    /// Requests currently holding a concurrency slot.
+    pub fn in_flight(&self) -> usize {
+        match (&self.slots, self.limits.max_in_flight) {
+            (Some(slots), Some(max)) => max - slots.available_permits(),
+            _ => 0,
+        }
+    }
+
+    /// Waits for room under both limits.
+    pub async fn acquire(&self) -> ThrottlePermit {
+        if let Some(rate) = self.limits.rate {
+            let mut window = self.window.lock().await;
+            loop {
+                let now = Instant::now();
+                while window
+                    .front()
+                    .is_some_and(|start| now.duration_since(*start) >= rate.per)
+                {
+                    window.pop_front();
+                }
+                if window.len() < rate.requests as usize {
+                    window.push_back(now);
+                    break;
+                }
+                let reopens = window[0] + rate.per;
+                tracing::debug!(
+                    wait_ms = reopens.duration_since(now).as_millis() as u64,
+                    "model request rate limit reached, queuing"
+                );
+                tokio::time::sleep_until(reopens).await;
+            }
+        }
+        let slot = match &self.slots {
+            Some(slots) => Some(
+                slots
+                    .clone()
+                    .acquire_owned()
+                    .await
+                    .expect("throttle semaphore is never closed"),
+            ),
+            None => None,
+        };
+        ThrottlePermit { _slot: slot }
+    }
+}
+
+impl std::fmt::Debug for Throttle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Throttle")
+            .field("limits", &self.limits)
+            .field("in_flight", &self.in_flight())
+            .finish()
+    }
+}
+
+/// Admits calls to the wrapped model through a [`Throttle`].
+///
+/// A stream counts as in flight until it is dropped.
+#[derive(Debug)]
+pub struct ThrottledModel<M: LLMModel> {
+    pub model: M,
+    pub throttle: Throttle,
+}
+
+impl<M: LLMModel> ThrottledModel<M> {
+    pub fn new(model: M, limits: ThrottleLimits) -> Self {
+        Self::shared(model, Throttle::new(limits))
+    }
+
+    /// Wraps `model` with an existing, possibly shared, throttle.
+    pub fn shared(model: M, throttle: Throttle) -> Self {
+        Self { model, throttle }
+    }
+}
+
+#[async_trait]
+impl<M: LLMModel> LLMModel for ThrottledModel<M> {
+    async fn generate(
+        &self,
+        prompt: &str,
+        options: &GenerationOptions,
+    ) -> Result<LLMResponse, ModelError> {
+        let _permit = self.throttle.acquire().await;
+        self.model.generate(prompt, options).await
+    }
+
+    async fn stream(
+        &self,
+        prompt: &str,
+        options: &GenerationOptions,
+    ) -> Result<EventStream, ModelError> {
+        let permit = self.throttle.acquire().await;
+        let events = self.model.stream(prompt, options).await?;
+        Ok(Box::pin(events.map(move |event| {
+            let _ = &permit;
+            event
+        })))
+    }
+
+    fn supports_tools(&self) -> bool {
+        self.model.supports_tools()
+    }
+
+    async fn chat(
+        &self,
+        messages: &[ChatMessage],
+        options: &ChatOptions,
+    ) -> Result<LLMResponse, ModelError> {
+        let _permit = self.throttle.acquire().await;
+        self.model.chat(messages, options).await
+    }
+
+    fn tokenizer(&self) -> Arc<dyn Tokenizer> {
+        self.model.tokenizer()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StubModel;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Sleeps through each call and tracks the most calls seen at once.
+    #[derive(Default)]
+    struct Slow {
+        running: AtomicUsize,
+        peak: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl LLMModel for Slow {
+        async fn generate(
+            &self,
+            prompt: &str,
+            options: &GenerationOptions,
+        ) -> Result<LLMResponse, ModelError> {
+            let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(running, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            self.running.fetch_sub(1, Ordering::SeqCst);
+            StubModel.generate(prompt, options).await
+        }
+
+        async fn stream(
+            &self,
+            prompt: &str,
+            options: &GenerationOptions,
+        ) -> Result<EventStream, ModelError> {
+            StubModel.stream(prompt, options).await
+        }
+
+        fn supports_tools(&self) -> bool {
+            false
+        }
+    }
+
+    #[tokio::test]
+    async fn caps_concurrent_requests() {
+        let model = Arc::new(ThrottledModel::new(
+            Slow::default(),
+            ThrottleLimits::default().with_max_in_flight(2),
+        ));
+        let mut calls = tokio::task::JoinSet::new();
+        for _ in 0..5 {
+            let model = model.clone();
+            calls.spawn(async move { model.generate("hi", &GenerationOptions::default()).await });
+        }
+        while let Some(result) = calls.join_next().await {
+            assert!(result.unwrap().is_ok());
+        }
+        assert_eq!(model.model.peak.load(Ordering::SeqCst), 2);
+
+        let options = GenerationOptions::default();
+
+        let events = model.stream("hi", &options).await.unwrap();
+        assert_eq!(model.throttle.in_flight(), 1);
+        drop(events);
+        assert_eq!(model.throttle.in_flight(), 0);
+    }
+
+    #[tokio::test]
+    async fn queues_requests_beyond_the_rate_window() {
+        let throttle = Throttle::new(ThrottleLimits::default().with_rate(RateLimit {
+            requests: 2,
+            per: Duration::from_millis(150),
+        }));
+        let first = ThrottledModel::shared(StubModel, throttle.clone());
+        let second = ThrottledModel::shared(StubModel, throttle);
+        let options = GenerationOptions::default();
+
+        let started = Instant::now();
+        first.generate("a", &options).await.unwrap();
+        second.generate("b", &options).await.unwrap();
+        assert!(started.elapsed() < Duration::from_millis(150));
+        first.generate("c", &options).await.unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(150));
+    }
+}
@@ -0,0 +1,193 @@
+//! Token counting for usage metrics and context-window budgeting.
+//!
+//! With the `tiktoken` cargo feature, OpenAI and Azure models count with
+//! the BPE vocabulary their model family uses, and other hosted models with
+//! `cl100k_base` as a close approximation. Without it, counts fall back to a
+//! characters-per-token estimate.
+
+use std::sync::Arc;
+
+use crate::ChatMessage;
+
+pub trait Tokenizer: Send + Sync {
+    /// Tokens `text` encodes to.
+    fn count(&self, text: &str) -> usize;
+
+    /// Tokens for a whole conversation: message contents plus any tool-call
+    /// names and arguments.
+    fn count_tokens(&self, messages: &[ChatMessage]) -> usize {
+        messages
+            .iter()
+            .map(|message| message_tokens(self, message))
+            .sum()
+    }
+}
+
+fn message_tokens<T: Tokenizer + ?Sized>(tokenizer: &T, message: &ChatMessage) -> usize {
+    let calls: usize = message
+        .tool_calls
+        .iter()
+        .map(|call| tokenizer.count(&call.name) + tokenizer.count(&call.arguments.to_string()))
+        .sum();
+    tokenizer.count(&message.content) + calls
+}
+
+/// One token per whitespace-separated word; what the offline stub models
+/// have always reported.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WhitespaceTokenizer;
+
+impl Tokenizer for WhitespaceTokenizer {
+    fn count(&self, text: &str) -> usize {
+        text.split_whitespace().count()
+    }
+}
+
+/// Rounds `chars / chars_per_token` up, for models whose vocabulary is not
+/// available locally.
+#[derive(Debug, Clone, Copy)]
+pub struct CharEstimateTokenizer {
+    pub chars_per_token: usize,
+}
+
+impl Default for CharEstimateTokenizer {
+    /// About four characters per token, typical of BPE vocabularies on
+    /// English text.
+    fn default() -> Self {
+        Self { chars_per_token: 4 }
+    }
+}
+
+impl Tokenizer for CharEstimateTokenizer {
+    fn count(&self, text: &str) -> usize {
+        text.chars().count().div_ceil(self.chars_per_token.max(1))
+    }
+}
+
+#[cfg(feature = "tiktoken")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BpeEncoding {
+    /// GPT-4 and GPT-3.5 models.
+    Cl100kBase,
+    /// GPT-4o, GPT-4.1 and the o-series.
+    O200kBase,
+}
+
+/// Exact token counts from OpenAI's published BPE vocabularies.
+#[cfg(feature = "tiktoken")]
+#[derive(Debug, Clone, Copy)]
+pub struct BpeTokenizer {
+    pub encoding: BpeEncoding,
+}
+
+#[cfg(feature = "tiktoken")]
+impl BpeTokenizer {
+    /// Framing tokens the Chat Completions format adds around each message,
+    /// and once more to prime the reply.
+    const TOKENS_PER_MESSAGE: usize = 3;
+
+    pub fn new(encoding: BpeEncoding) -> Self {
+        Self { encoding }
+    }
+
+    /// The encoding an OpenAI model name uses, if it is a known family.
+    pub fn for_model(model: &str) -> Option<Self> {
+        use tiktoken_rs::tokenizer::{get_tokenizer, Tokenizer as Encoding};
+
+        match get_tokenizer(model)? {
+            Encoding::O200kBase => Some(Self::new(BpeEncoding::O200kBase)),
+            Encoding::Cl100kBase => Some(Self::new(BpeEncoding::Cl100kBase)),
+            _ => None,
+        }
+    }
+
+    fn bpe(&self) -> &'static tiktoken_rs::CoreBPE {
+        match self.encoding {
+            BpeEncoding::Cl100kBase => tiktoken_rs::cl100k_base_singleton(),
+            BpeEncoding::O200kBase => tiktoken_rs::o200k_base_singleton(),
+        }
+    }
+}
+
+#[cfg(feature = "tiktoken")]
+impl Tokenizer for BpeTokenizer {
+    fn count(&self, text: &str) -> usize {
+        self.bpe().encode_with_special_tokens(text).len()
+    }
+
+    fn count_tokens(&self, messages: &[ChatMessage]) -> usize {
+        let framing = Self::TOKENS_PER_MESSAGE * (messages.len() + 1);
+        let contents: usize = messages
+            .iter()
+            .map(|message| self.count(message.role.as_str()) + message_tokens(self, message))
+            .sum();
+        framing + contents
+    }
+}
+
+/// The closest available tokenizer for `model` on `provider` (as reported in
+/// [`ModelMetadata::provider`](crate::ModelMetadata)).
+pub fn tokenizer_for(provider: &str, model: &str) -> Arc<dyn Tokenizer> {
+    #[cfg(feature = "tiktoken")]
+    {
+        let tokenizer = match provider {
+            "openai" | "azure_openai" => {
+                BpeTokenizer::for_model(model).unwrap_or(BpeTokenizer::new(BpeEncoding::O200kBase))
+            }
+            _ => BpeTokenizer::new(BpeEncoding::Cl100kBase),
+        };
+        Arc::new(tokenizer)
+    }
+    #[cfg(not(feature = "tiktoken"))]
+    {
+        let _ = (provider, model);
+        Arc::new(CharEstimateTokenizer::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LLMResponse, ToolCallInfo};
+    use serde_json::json;
+
+    #[test]
+    fn simple_tokenizers_count_words_and_characters() {
+        assert_eq!(WhitespaceTokenizer.count("plan  a\ttrip"), 3);
+        assert_eq!(CharEstimateTokenizer::default().count("abcdefghi"), 3);
+        assert_eq!(CharEstimateTokenizer::default().count(""), 0);
+
+        let call = LLMResponse {
+            tool_calls: vec![ToolCallInfo {
+                name: "math".into(),
+                arguments: json!("1 + 1"),
+                id: None,
+            }],
+            ..LLMResponse::default()
+        };
+        let messages = [
+            ChatMessage::user("what is 1 + 1"),
+            ChatMessage::assistant(&call),
+        ];
+        assert_eq!(WhitespaceTokenizer.count_tokens(&messages), 5 + 1 + 3);
+    }
+
+    #[cfg(feature = "tiktoken")]
+    #[test]
+    fn bpe_counts_match_the_model_vocabulary() {
+        let gpt4o = BpeTokenizer::for_model("gpt-4o-mini").unwrap();
+        assert_eq!(gpt4o.encoding, BpeEncoding::O200kBase);
+        assert_eq!(
+            BpeTokenizer::for_model("gpt-4").unwrap().encoding,
+            BpeEncoding::Cl100kBase
+        );
+        assert_eq!(gpt4o.count("hello world"), 2);
+        // 3 framing tokens per message and for the reply, plus one token
+        // each for the role and the greeting.
+        assert_eq!(
+            gpt4o.count_tokens(&[ChatMessage::user("hello")]),
+            3 + 3 + 1 + 1
+        );
+        assert!(BpeTokenizer::for_model("llama3.2").is_none());
+    }
+}
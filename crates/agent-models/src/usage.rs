@@ -0,0 +1,210 @@
+//! Running totals of token usage and spend across model calls.
+
+use std::sync::{Arc, Mutex};
+
+use agent_telemetry::Telemetry;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::{ContextUsage, LLMResponse, ModelError, ModelMiddleware, UsageMetrics};
+
+/// Tokens and estimated spend of a group of model calls.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UsageTotals {
+    pub calls: usize,
+    pub prompt_tokens: usize,
+    pub completion_tokens: usize,
+    /// Sum of [`LLMResponse::estimated_cost_usd`] over calls whose model has
+    /// a price.
+    pub cost_usd: f64,
+    /// Fullest context window reported with [`record_context`](Self::record_context).
+    #[serde(default)]
+    pub context_usage: Option<ContextUsage>,
+    #[serde(default)]
+    pub context_reports: usize,
+}
+
+impl UsageTotals {
+    pub fn record(&mut self, response: &LLMResponse) {
+        self.calls += 1;
+        self.prompt_tokens += response.usage.prompt_tokens;
+        self.completion_tokens += response.usage.completion_tokens;
+        self.cost_usd += response.estimated_cost_usd().unwrap_or_default();
+    }
+
+    /// Keeps the fuller of `usage` and the context window seen so far.
+    pub fn record_context(&mut self, usage: ContextUsage) {
+        self.context_reports += 1;
+        if self
+            .context_usage
+            .is_none_or(|peak| usage.used >= peak.used)
+        {
+            self.context_usage = Some(usage);
+        }
+    }
+
+    pub fn add(&mut self, other: &UsageTotals) {
+        self.calls += other.calls;
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.cost_usd += other.cost_usd;
+        self.context_reports += other.context_reports;
+        if let Some(usage) = other.context_usage {
+            if self
+                .context_usage
+                .is_none_or(|peak| usage.used >= peak.used)
+            {
+                self.context_usage = Some(usage);
+            }
+        }
+    }
+
+    /// What was added since `earlier`, a previous reading of the same
+    /// totals. A peak cannot be split, so the context usage is the fullest
+    /// seen overall, and only kept when some was reported since `earlier`.
+    pub fn since(&self, earlier: &UsageTotals) -> UsageTotals {
+        let context_reports = self.context_reports.saturating_sub(earlier.context_reports);
+        UsageTotals {
+            calls: self.calls.saturating_sub(earlier.calls),
+            prompt_tokens: self.prompt_tokens.saturating_sub(earlier.prompt_tokens),
+            completion_tokens: self
+                .completion_tokens
+                .saturating_sub(earlier.completion_tokens),
+            cost_usd: (self.cost_usd - earlier.cost_usd).max(0.0),
+            context_usage: self.context_usage.filter(|_| context_reports > 0),
+            context_reports,
+        }
+    }
+
+    pub fn usage(&self) -> UsageMetrics {
+        UsageMetrics {
+            prompt_tokens: self.prompt_tokens,
+            completion_tokens: self.completion_tokens,
+        }
+    }
+}
+
+/// Accumulates the usage of every response reported into it. Clones share
+/// one ledger, so a tracker can be handed to several models and to the
+/// runtime, which reads it per step and per run.
+///
+/// As a [`ModelMiddleware`] it records each response passing through a
+/// [`MiddlewareModel`](crate::MiddlewareModel); streamed replies bypass
+/// middleware, so record them with [`UsageTracker::record`] once folded
+/// into a response. With [`UsageTracker::with_telemetry`], each call also
+/// feeds the `llm_*` token and cost metrics.
+#[derive(Clone, Default)]
+pub struct UsageTracker {
+    totals: Arc<Mutex<UsageTotals>>,
+    telemetry: Option<Arc<Telemetry>>,
+}
+
+impl UsageTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_telemetry(mut self, telemetry: Arc<Telemetry>) -> Self {
+        self.telemetry = Some(telemetry);
+        self
+    }
+
+    pub fn record(&self, response: &LLMResponse) {
+        self.totals
+            .lock()
+            .expect("usage tracker poisoned")
+            .record(response);
+        if let Some(telemetry) = &self.telemetry {
+            let model = &response.metadata.model;
+            telemetry.record_llm_call(
+                model,
+                response.usage.prompt_tokens as u64,
+                response.usage.completion_tokens as u64,
+                None,
+            );
+            if let Some(cost) = response.estimated_cost_usd() {
+                telemetry.record_llm_cost(model, cost);
+            }
+        }
+    }
+
+    /// Notes how full a request's context window was, e.g. from
+    /// [`LLMModel::context_usage`](crate::LLMModel::context_usage).
+    pub fn record_context(&self, usage: ContextUsage) {
+        self.totals
+            .lock()
+            .expect("usage tracker poisoned")
+            .record_context(usage);
+    }
+
+    /// Everything recorded so far.
+    pub fn totals(&self) -> UsageTotals {
+        self.totals.lock().expect("usage tracker poisoned").clone()
+    }
+}
+
+impl std::fmt::Debug for UsageTracker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UsageTracker")
+            .field("totals", &self.totals())
+            .finish()
+    }
+}
+
+#[async_trait]
+impl ModelMiddleware for UsageTracker {
+    async fn after_response(&self, response: &mut LLMResponse) -> Result<(), ModelError> {
+        self.record(response);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{GenerationOptions, LLMModel, MiddlewareModel, ModelMetadata, StubModel};
+
+    #[tokio::test]
+    async fn records_responses_through_middleware_and_telemetry() {
+        let telemetry = Arc::new(Telemetry::new());
+        let tracker = UsageTracker::new().with_telemetry(telemetry.clone());
+        let model = MiddlewareModel::new(StubModel).with_middleware(tracker.clone());
+
+        model
+            .generate("one two", &GenerationOptions::default())
+            .await
+            .unwrap();
+        let before = tracker.totals();
+        tracker.record(&LLMResponse {
+            usage: UsageMetrics {
+                prompt_tokens: 1_000,
+                completion_tokens: 500,
+            },
+            metadata: ModelMetadata {
+                provider: "openai".into(),
+                model: "gpt-4o".into(),
+                ..ModelMetadata::default()
+            },
+            ..LLMResponse::default()
+        });
+
+        assert_eq!(
+            before,
+            UsageTotals {
+                calls: 1,
+                prompt_tokens: 2,
+                completion_tokens: 3,
+                ..UsageTotals::default()
+            }
+        );
+        let step = tracker.totals().since(&before);
+        assert_eq!(step.calls, 1);
+        assert_eq!(step.usage().prompt_tokens, 1_000);
+        assert!((step.cost_usd - 0.0075).abs() < 1e-12);
+
+        let metrics = telemetry.export_metrics();
+        assert!(metrics.contains(r#"llm_calls{model="stub"} 1"#));
+        assert!(metrics.contains(r#"llm_input_tokens{model="gpt-4o"} 1000"#));
+        assert!(metrics.contains(r#"llm_cost_usd{model="gpt-4o"} 0.0075"#));
+    }
+}
@@ -0,0 +1,132 @@
+use std::time::Duration;
+
+use agent_models::{UsageTotals, UsageTracker};
+use serde::{Deserialize, Serialize};
+use tokio::time::Instant;
+
+/// Spending limits for one run. Unset limits are unbounded.
+///
+/// Limits are checked before each step starts, so the step that crosses one
+/// still finishes. Token and cost limits read the run's
+/// [`UsageTracker`](agent_models::UsageTracker) and never trip without one.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RunBudget {
+    /// Prompt plus completion tokens.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_cost_usd: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_duration: Option<Duration>,
+}
+
+/// Which [`RunBudget`] limit a run hit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetLimit {
+    Tokens,
+    Cost,
+    Duration,
+}
+
+impl std::fmt::Display for BudgetLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            BudgetLimit::Tokens => "tokens",
+            BudgetLimit::Cost => "cost",
+            BudgetLimit::Duration => "duration",
+        })
+    }
+}
+
+impl RunBudget {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_max_tokens(mut self, max_tokens: usize) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    pub fn with_max_cost_usd(mut self, max_cost_usd: f64) -> Self {
+        self.max_cost_usd = Some(max_cost_usd);
+        self
+    }
+
+    pub fn with_max_duration(mut self, max_duration: Duration) -> Self {
+        self.max_duration = Some(max_duration);
+        self
+    }
+
+    /// Whether the token or cost limits need a usage tracker to be checked.
+    pub fn tracks_usage(&self) -> bool {
+        self.max_tokens.is_some() || self.max_cost_usd.is_some()
+    }
+
+    /// The first limit that `used` and `elapsed` have reached, if any.
+    pub fn exceeded(&self, used: &UsageTotals, elapsed: Duration) -> Option<BudgetLimit> {
+        if self
+            .max_tokens
+            .is_some_and(|max| used.prompt_tokens + used.completion_tokens >= max)
+        {
+            Some(BudgetLimit::Tokens)
+        } else if self.max_cost_usd.is_some_and(|max| used.cost_usd >= max) {
+            Some(BudgetLimit::Cost)
+        } else if self.max_duration.is_some_and(|max| elapsed >= max) {
+            Some(BudgetLimit::Duration)
+        } else {
+            None
+        }
+    }
+
+    /// What is left of this budget after `used` and `elapsed`.
+    pub fn remaining(&self, used: &UsageTotals, elapsed: Duration) -> RunBudget {
+        RunBudget {
+            max_tokens: self
+                .max_tokens
+                .map(|max| max.saturating_sub(used.prompt_tokens + used.completion_tokens)),
+            max_cost_usd: self.max_cost_usd.map(|max| (max - used.cost_usd).max(0.0)),
+            max_duration: self.max_duration.map(|max| max.saturating_sub(elapsed)),
+        }
+    }
+}
+
+/// A [`RunBudget`] being spent from the moment it was started.
+#[derive(Clone)]
+pub(crate) struct BudgetMeter {
+    budget: RunBudget,
+    usage: Option<UsageTracker>,
+    start: UsageTotals,
+    started: Instant,
+}
+
+impl BudgetMeter {
+    pub(crate) fn start(budget: RunBudget, usage: Option<UsageTracker>) -> Self {
+        if budget.tracks_usage() && usage.is_none() {
+            tracing::warn!("run budget limits tokens or cost but there is no usage tracker");
+        }
+        let start = usage.as_ref().map(UsageTracker::totals).unwrap_or_default();
+        Self {
+            budget,
+            usage,
+            start,
+            started: Instant::now(),
+        }
+    }
+
+    fn used(&self) -> UsageTotals {
+        self.usage
+            .as_ref()
+            .map(|usage| usage.totals().since(&self.start))
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn exceeded(&self) -> Option<BudgetLimit> {
+        self.budget.exceeded(&self.used(), self.started.elapsed())
+    }
+
+    pub(crate) fn remaining(&self) -> RunBudget {
+        self.budget.remaining(&self.used(), self.started.elapsed())
+    }
+}
@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use agent_core::AgentError;
+use async_trait::async_trait;
+use futures::stream::{self, Stream};
+use serde_json::Value;
+use tokio::sync::{broadcast, mpsc};
+
+/// Messages published to a topic, in publishing order.
+pub type MessageStream = Pin<Box<dyn Stream<Item = Value> + Send>>;
+
+/// Topic subscribers an [`InMemoryBus`] lets fall this many messages behind
+/// unless told otherwise.
+pub const DEFAULT_TOPIC_CAPACITY: usize = 256;
+
+/// Messages an [`InMemoryBus`] inbox holds before `send` waits, unless told
+/// otherwise.
+pub const DEFAULT_INBOX_CAPACITY: usize = 1024;
+
+/// Carries messages between agents, either point to point, where each message
+/// sent to a recipient is received once, or by topic, where every subscriber
+/// of a topic gets every message published to it.
+#[async_trait]
+pub trait MessageBus {
+    async fn send(&self, recipient: &str, message: Value) -> Result<(), AgentError>;
+    async fn recv(&self, recipient: &str) -> Result<Option<Value>, AgentError>;
+
+    /// Delivers `message` to every current subscriber of `topic` and returns
+    /// how many there were, or 0 when the broker behind the bus does not say.
+    /// Nobody sees messages published before they subscribed.
+    async fn publish(&self, topic: &str, message: Value) -> Result<usize, AgentError>;
+
+    /// The messages published to `topic` from now on; dropping the stream
+    /// unsubscribes.
+    async fn subscribe(&self, topic: &str) -> Result<MessageStream, AgentError>;
+}
+
+/// Turns messages into the bytes a networked [`MessageBus`] puts on the wire
+/// and back.
+pub trait MessageCodec: Send + Sync {
+    fn encode(&self, message: &Value) -> Result<Vec<u8>, AgentError>;
+    fn decode(&self, bytes: &[u8]) -> Result<Value, AgentError>;
+}
+
+/// Messages as JSON text.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonCodec;
+
+impl MessageCodec for JsonCodec {
+    fn encode(&self, message: &Value) -> Result<Vec<u8>, AgentError> {
+        serde_json::to_vec(message).map_err(|e| AgentError::Validation(e.to_string()))
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<Value, AgentError> {
+        serde_json::from_slice(bytes).map_err(|e| AgentError::Validation(e.to_string()))
+    }
+}
+
+/// Consumer group a networked [`MessageBus`] reads inboxes with unless told
+/// otherwise.
+pub const DEFAULT_CONSUMER_GROUP: &str = "agents";
+
+/// Header naming the recipient of a message moved to a dead-letter
+/// destination.
+pub const DEAD_LETTER_RECIPIENT_HEADER: &str = "x-dead-letter-recipient";
+
+/// Header saying why a message was moved to a dead-letter destination.
+pub const DEAD_LETTER_REASON_HEADER: &str = "x-dead-letter-reason";
+
+/// A [`MessageBus`] within one process.
+///
+/// Each recipient has an inbox holding up to `inbox_capacity` messages;
+/// `send` waits for room in a full one. Messages can be taken without
+/// waiting through `recv`, waited for through [`recv_wait`](Self::recv_wait),
+/// or taken as they come through [`subscribe_inbox`](Self::subscribe_inbox).
+///
+/// Publishing never blocks: a subscriber that falls more than
+/// `topic_capacity` messages behind loses the oldest ones, with a warning
+/// logged.
+pub struct InMemoryBus {
+    inboxes: Mutex<HashMap<String, Arc<Inbox>>>,
+    topics: Mutex<HashMap<String, broadcast::Sender<Value>>>,
+    inbox_capacity: usize,
+    topic_capacity: usize,
+}
+
+struct Inbox {
+    sender: mpsc::Sender<Value>,
+    receiver: tokio::sync::Mutex<mpsc::Receiver<Value>>,
+}
+
+impl Default for InMemoryBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InMemoryBus {
+    pub fn new() -> Self {
+        Self {
+            inboxes: Mutex::new(HashMap::new()),
+            topics: Mutex::new(HashMap::new()),
+            inbox_capacity: DEFAULT_INBOX_CAPACITY,
+            topic_capacity: DEFAULT_TOPIC_CAPACITY,
+        }
+    }
+
+    /// Messages each recipient's inbox holds before `send` waits; applies to
+    /// inboxes not used yet.
+    pub fn with_inbox_capacity(mut self, capacity: usize) -> Self {
+        self.inbox_capacity = capacity.max(1);
+        self
+    }
+
+    pub fn with_topic_capacity(mut self, capacity: usize) -> Self {
+        self.topic_capacity = capacity.max(1);
+        self
+    }
+
+    /// Waits up to `timeout` for a message for `recipient`, returning `None`
+    /// if none arrives in time.
+    pub async fn recv_wait(&self, recipient: &str, timeout: Duration) -> Option<Value> {
+        let inbox = self.inbox(recipient);
+        tokio::time::timeout(timeout, async { inbox.receiver.lock().await.recv().await })
+            .await
+            .ok()
+            .flatten()
+    }
+
+    /// The messages for `recipient` as they arrive. While the stream waits
+    /// for one, `recv` finds the inbox empty and `recv_wait` waits behind it.
+    pub fn subscribe_inbox(&self, recipient: &str) -> MessageStream {
+        Box::pin(stream::unfold(self.inbox(recipient), |inbox| async move {
+            let message = inbox.receiver.lock().await.recv().await?;
+            Some((message, inbox))
+        }))
+    }
+
+    fn inbox(&self, recipient: &str) -> Arc<Inbox> {
+        lock(&self.inboxes)
+            .entry(recipient.to_string())
+            .or_insert_with(|| {
+                let (sender, receiver) = mpsc::channel(self.inbox_capacity);
+                Arc::new(Inbox {
+                    sender,
+                    receiver: tokio::sync::Mutex::new(receiver),
+                })
+            })
+            .clone()
+    }
+}
+
+#[async_trait]
+impl MessageBus for InMemoryBus {
+    async fn send(&self, recipient: &str, message: Value) -> Result<(), AgentError> {
+        let inbox = self.inbox(recipient);
+        inbox
+            .sender
+            .send(message)
+            .await
+            .map_err(|_| AgentError::Execution(format!("inbox of {recipient} is closed")))
+    }
+
+    async fn recv(&self, recipient: &str) -> Result<Option<Value>, AgentError> {
+        let inbox = self.inbox(recipient);
+        // Someone already waiting on the inbox takes whatever arrives.
+        let Ok(mut receiver) = inbox.receiver.try_lock() else {
+            return Ok(None);
+        };
+        Ok(receiver.try_recv().ok())
+    }
+
+    async fn publish(&self, topic: &str, message: Value) -> Result<usize, AgentError> {
+        let mut topics = lock(&self.topics);
+        let Some(sender) = topics.get(topic) else {
+            return Ok(0);
+        };
+        match sender.send(message) {
+            Ok(subscribers) => Ok(subscribers),
+            Err(_) => {
+                // Every subscriber has gone away.
+                topics.remove(topic);
+                Ok(0)
+            }
+        }
+    }
+
+    async fn subscribe(&self, topic: &str) -> Result<MessageStream, AgentError> {
+        let receiver = lock(&self.topics)
+            .entry(topic.to_string())
+            .or_insert_with(|| broadcast::channel(self.topic_capacity).0)
+            .subscribe();
+        let topic = topic.to_string();
+        Ok(Box::pin(stream::unfold(receiver, move |mut receiver| {
+            let topic = topic.clone();
+            async move {
+                loop {
+                    match receiver.recv().await {
+                        Ok(message) => return Some((message, receiver)),
+                        Err(broadcast::error::RecvError::Lagged(missed)) => {
+                            tracing::warn!(%topic, missed, "topic subscriber fell behind");
+                        }
+                        Err(broadcast::error::RecvError::Closed) => return None,
+                    }
+                }
+            }
+        })))
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
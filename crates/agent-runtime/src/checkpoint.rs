@@ -0,0 +1,82 @@
+use std::sync::Arc;
+
+use agent_core::{AgentError, AgentState, ExecutablePlan, Plan, StepOutcome};
+use agent_memory::MemoryStore;
+use agent_models::ToolCallInfo;
+use serde::{Deserialize, Serialize};
+
+/// Where a control loop run had got to after its last finished step, enough
+/// to carry on with [`ControlLoop::resume`](crate::ControlLoop::resume).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RunCheckpoint {
+    /// Most recent plan produced by the agent.
+    pub plan: Option<Plan>,
+    /// The plan being worked through and the cursor into it; `None` in
+    /// [`ControlMode::Reactive`](crate::ControlMode::Reactive).
+    pub executable: Option<ExecutablePlan>,
+    pub state: AgentState,
+    pub outcomes: Vec<StepOutcome>,
+    pub tool_calls: Vec<ToolCallInfo>,
+    /// The iteration to run next.
+    pub iteration: usize,
+}
+
+/// Keeps a run's latest [`RunCheckpoint`] under one key of a [`MemoryStore`].
+///
+/// Give each run its own key; saving overwrites the previous checkpoint.
+#[derive(Clone)]
+pub struct CheckpointStore {
+    store: Arc<dyn MemoryStore>,
+    key: String,
+}
+
+impl CheckpointStore {
+    pub fn new(store: Arc<dyn MemoryStore>, key: impl Into<String>) -> Self {
+        Self {
+            store,
+            key: key.into(),
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn save(&self, checkpoint: &RunCheckpoint) -> Result<(), AgentError> {
+        let value = serde_json::to_value(checkpoint)
+            .map_err(|e| AgentError::Memory(format!("cannot serialize checkpoint: {e}")))?;
+        self.store
+            .put(&self.key, &value)
+            .map_err(|e| AgentError::Memory(e.to_string()))
+    }
+
+    /// The saved checkpoint, or `None` when the run has not saved one.
+    pub fn load(&self) -> Result<Option<RunCheckpoint>, AgentError> {
+        let Some(value) = self
+            .store
+            .get(&self.key)
+            .map_err(|e| AgentError::Memory(e.to_string()))?
+        else {
+            return Ok(None);
+        };
+        serde_json::from_value(value)
+            .map(Some)
+            .map_err(|e| AgentError::Memory(format!("invalid checkpoint {}: {e}", self.key)))
+    }
+
+    /// Drops the saved checkpoint, returning whether there was one.
+    pub fn clear(&self) -> Result<bool, AgentError> {
+        self.store
+            .delete(&self.key)
+            .map_err(|e| AgentError::Memory(e.to_string()))
+    }
+}
+
+impl std::fmt::Debug for CheckpointStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CheckpointStore")
+            .field("store", &self.store)
+            .field("key", &self.key)
+            .finish()
+    }
+}
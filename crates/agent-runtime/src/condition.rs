@@ -0,0 +1,419 @@
+//! Step conditions: small boolean expressions over a run's earlier outcomes.
+//!
+//! A condition reads values by path from a scope holding `steps` (each
+//! earlier [`StepOutcome`] by step id), `metadata` (the context metadata) and
+//! `iteration`. It supports `&&`, `||`, `!`, parentheses, the comparisons
+//! `== != < <= > >=`, string, number, `true`, `false` and `null` literals,
+//! and the `length` filter, as in
+//! `steps.search.success && steps.search.output.results | length > 0`.
+//!
+//! Path segments are names (`-` allowed after the first character), array
+//! indexes (`results.0` or `results[0]`) or quoted keys (`steps["my step"]`).
+//! A path that leads nowhere is `null`. Values are truthy unless they are
+//! `null`, `false`, zero or empty, and comparing values of different types
+//! is false.
+
+use std::cmp::Ordering;
+
+use agent_core::{AgentContext, AgentError, FailureKind, Step, StepOutcome};
+use serde_json::{json, Map, Value};
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ConditionError {
+    #[error("invalid condition `{condition}`: {message}")]
+    Parse { condition: String, message: String },
+}
+
+/// A parsed step condition.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Condition {
+    expr: Expr,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Literal(Value),
+    Path(Vec<Segment>),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Compare(Box<Expr>, CompareOp, Box<Expr>),
+    Length(Box<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Condition {
+    pub fn parse(condition: &str) -> Result<Self, ConditionError> {
+        let error = |message: String| ConditionError::Parse {
+            condition: condition.to_string(),
+            message,
+        };
+        let tokens = tokenize(condition).map_err(error)?;
+        let mut parser = Parser { tokens, next: 0 };
+        let expr = parser.or().map_err(error)?;
+        if let Some(token) = parser.peek() {
+            return Err(error(format!("unexpected {token:?}")));
+        }
+        Ok(Self { expr })
+    }
+
+    /// Whether the condition holds in `scope`.
+    pub fn evaluate(&self, scope: &Value) -> bool {
+        truthy(&self.value(scope))
+    }
+
+    /// The value the expression evaluates to in `scope`, e.g. the array
+    /// `steps.search.output.results` leads to.
+    pub fn value(&self, scope: &Value) -> Value {
+        self.expr.evaluate(scope)
+    }
+}
+
+/// The scope conditions are evaluated in: `outcomes` by step id (the latest
+/// wins), the context metadata and the current iteration.
+pub fn condition_scope(outcomes: &[StepOutcome], ctx: &AgentContext) -> Value {
+    let steps: Map<String, Value> = outcomes
+        .iter()
+        .map(|outcome| {
+            let value = serde_json::to_value(outcome).unwrap_or(Value::Null);
+            (outcome.step_id.clone(), value)
+        })
+        .collect();
+    json!({
+        "steps": steps,
+        "metadata": ctx.metadata,
+        "iteration": ctx.state.iteration,
+    })
+}
+
+/// The outcome of `step` when its condition keeps it from running: skipped
+/// when false, failed when it does not parse.
+pub(crate) fn check(
+    step: &Step,
+    outcomes: &[StepOutcome],
+    ctx: &AgentContext,
+) -> Option<StepOutcome> {
+    step.condition.as_ref()?;
+    check_in(step, &condition_scope(outcomes, ctx))
+}
+
+/// Like [`check`], in a scope the caller built.
+pub(crate) fn check_in(step: &Step, scope: &Value) -> Option<StepOutcome> {
+    let source = step.condition.as_deref()?;
+    let condition = match Condition::parse(source) {
+        Ok(condition) => condition,
+        Err(error) => {
+            return Some(StepOutcome::failure(
+                step.id.clone(),
+                AgentError::Validation(error.to_string()),
+            ))
+        }
+    };
+    if condition.evaluate(scope) {
+        return None;
+    }
+    tracing::debug!(step = %step.id, condition = source, "step condition is false");
+    let mut outcome = StepOutcome::success(step.id.clone(), Value::Null);
+    outcome.success = false;
+    outcome
+        .control_notes
+        .push(format!("skipped: condition `{source}` is false"));
+    outcome.failure_kind = Some(FailureKind::ConditionNotMet {
+        condition: source.to_string(),
+    });
+    Some(outcome)
+}
+
+impl Expr {
+    fn evaluate(&self, scope: &Value) -> Value {
+        match self {
+            Expr::Literal(value) => value.clone(),
+            Expr::Path(segments) => {
+                let mut current = scope;
+                for segment in segments {
+                    let next = match segment {
+                        Segment::Key(key) => current.get(key),
+                        Segment::Index(index) => current.get(index),
+                    };
+                    match next {
+                        Some(next) => current = next,
+                        None => return Value::Null,
+                    }
+                }
+                current.clone()
+            }
+            Expr::Not(inner) => Value::Bool(!truthy(&inner.evaluate(scope))),
+            Expr::And(left, right) => {
+                Value::Bool(truthy(&left.evaluate(scope)) && truthy(&right.evaluate(scope)))
+            }
+            Expr::Or(left, right) => {
+                Value::Bool(truthy(&left.evaluate(scope)) || truthy(&right.evaluate(scope)))
+            }
+            Expr::Compare(left, op, right) => {
+                Value::Bool(compare(&left.evaluate(scope), *op, &right.evaluate(scope)))
+            }
+            Expr::Length(inner) => match inner.evaluate(scope) {
+                Value::Array(items) => json!(items.len()),
+                Value::Object(fields) => json!(fields.len()),
+                Value::String(text) => json!(text.chars().count()),
+                _ => json!(0),
+            },
+        }
+    }
+}
+
+fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(value) => *value,
+        Value::Number(number) => number.as_f64().is_some_and(|n| n != 0.0),
+        Value::String(text) => !text.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        Value::Object(fields) => !fields.is_empty(),
+    }
+}
+
+fn compare(left: &Value, op: CompareOp, right: &Value) -> bool {
+    let ordering = match (left, right) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64().partial_cmp(&b.as_f64()),
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        _ if matches!(op, CompareOp::Eq | CompareOp::Ne) => {
+            return (left == right) == matches!(op, CompareOp::Eq)
+        }
+        _ => None,
+    };
+    let Some(ordering) = ordering else {
+        return false;
+    };
+    match op {
+        CompareOp::Eq => ordering == Ordering::Equal,
+        CompareOp::Ne => ordering != Ordering::Equal,
+        CompareOp::Lt => ordering == Ordering::Less,
+        CompareOp::Le => ordering != Ordering::Greater,
+        CompareOp::Gt => ordering == Ordering::Greater,
+        CompareOp::Ge => ordering != Ordering::Less,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Name(String),
+    Number(f64),
+    Text(String),
+    Dot,
+    OpenBracket,
+    CloseBracket,
+    OpenParen,
+    CloseParen,
+    Pipe,
+    And,
+    Or,
+    Not,
+    Compare(CompareOp),
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let pair = chars.get(i + 1).map(|next| (c, *next));
+        let (token, width) = match (c, pair) {
+            (c, _) if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            (_, Some(('&', '&'))) => (Token::And, 2),
+            (_, Some(('|', '|'))) => (Token::Or, 2),
+            (_, Some(('=', '='))) => (Token::Compare(CompareOp::Eq), 2),
+            (_, Some(('!', '='))) => (Token::Compare(CompareOp::Ne), 2),
+            (_, Some(('<', '='))) => (Token::Compare(CompareOp::Le), 2),
+            (_, Some(('>', '='))) => (Token::Compare(CompareOp::Ge), 2),
+            ('<', _) => (Token::Compare(CompareOp::Lt), 1),
+            ('>', _) => (Token::Compare(CompareOp::Gt), 1),
+            ('!', _) => (Token::Not, 1),
+            ('|', _) => (Token::Pipe, 1),
+            ('.', _) => (Token::Dot, 1),
+            ('[', _) => (Token::OpenBracket, 1),
+            (']', _) => (Token::CloseBracket, 1),
+            ('(', _) => (Token::OpenParen, 1),
+            (')', _) => (Token::CloseParen, 1),
+            ('"' | '\'', _) => {
+                let end = chars[i + 1..]
+                    .iter()
+                    .position(|d| *d == c)
+                    .ok_or("unterminated string")?;
+                let text = chars[i + 1..i + 1 + end].iter().collect();
+                (Token::Text(text), end + 2)
+            }
+            (c, _) if c.is_ascii_digit() => {
+                // After a `.` this is an index, so `results.0.1` is two of them.
+                let index = tokens.last() == Some(&Token::Dot);
+                let len = chars[i..]
+                    .iter()
+                    .take_while(|d| d.is_ascii_digit() || (!index && **d == '.'))
+                    .count();
+                let text: String = chars[i..i + len].iter().collect();
+                // `results.0.title` lexes `0.` greedily; give the dot back.
+                let text = text.trim_end_matches('.');
+                let number = text
+                    .parse()
+                    .map_err(|_| format!("invalid number `{text}`"))?;
+                (Token::Number(number), text.len())
+            }
+            (c, _) if c.is_alphabetic() || c == '_' => {
+                let len = chars[i..]
+                    .iter()
+                    .take_while(|d| d.is_alphanumeric() || **d == '_' || **d == '-')
+                    .count();
+                (Token::Name(chars[i..i + len].iter().collect()), len)
+            }
+            (c, _) => return Err(format!("unexpected character `{c}`")),
+        };
+        tokens.push(token);
+        i += width;
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    next: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next)
+    }
+
+    fn advance(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.next).cloned();
+        self.next += 1;
+        token
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.peek() == Some(token) {
+            self.next += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.and()?;
+        while self.eat(&Token::Or) {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.not()?;
+        while self.eat(&Token::And) {
+            expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> Result<Expr, String> {
+        if self.eat(&Token::Not) {
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Expr, String> {
+        let left = self.filtered()?;
+        if let Some(Token::Compare(op)) = self.peek().cloned() {
+            self.next += 1;
+            let right = self.filtered()?;
+            return Ok(Expr::Compare(Box::new(left), op, Box::new(right)));
+        }
+        Ok(left)
+    }
+
+    fn filtered(&mut self) -> Result<Expr, String> {
+        let mut expr = self.primary()?;
+        while self.eat(&Token::Pipe) {
+            match self.advance() {
+                Some(Token::Name(name)) if name == "length" => expr = Expr::Length(Box::new(expr)),
+                Some(Token::Name(name)) => return Err(format!("unknown filter `{name}`")),
+                other => return Err(format!("expected a filter, found {other:?}")),
+            }
+        }
+        Ok(expr)
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        match self.advance() {
+            Some(Token::Number(number)) => Ok(Expr::Literal(json!(number))),
+            Some(Token::Text(text)) => Ok(Expr::Literal(Value::String(text))),
+            Some(Token::OpenParen) => {
+                let expr = self.or()?;
+                if !self.eat(&Token::CloseParen) {
+                    return Err("expected `)`".into());
+                }
+                Ok(expr)
+            }
+            Some(Token::Name(name)) => match name.as_str() {
+                "true" => Ok(Expr::Literal(Value::Bool(true))),
+                "false" => Ok(Expr::Literal(Value::Bool(false))),
+                "null" => Ok(Expr::Literal(Value::Null)),
+                _ => self.path(name),
+            },
+            Some(token) => Err(format!("unexpected {token:?}")),
+            None => Err("unexpected end of condition".into()),
+        }
+    }
+
+    fn path(&mut self, root: String) -> Result<Expr, String> {
+        let mut segments = vec![Segment::Key(root)];
+        loop {
+            if self.eat(&Token::Dot) {
+                segments.push(match self.advance() {
+                    Some(Token::Name(name)) => Segment::Key(name),
+                    Some(Token::Number(number)) => Segment::Index(index(number)?),
+                    other => return Err(format!("expected a name after `.`, found {other:?}")),
+                });
+            } else if self.eat(&Token::OpenBracket) {
+                segments.push(match self.advance() {
+                    Some(Token::Text(key)) => Segment::Key(key),
+                    Some(Token::Number(number)) => Segment::Index(index(number)?),
+                    other => return Err(format!("expected an index or key, found {other:?}")),
+                });
+                if !self.eat(&Token::CloseBracket) {
+                    return Err("expected `]`".into());
+                }
+            } else {
+                return Ok(Expr::Path(segments));
+            }
+        }
+    }
+}
+
+fn index(number: f64) -> Result<usize, String> {
+    if number >= 0.0 && number.fract() == 0.0 {
+        Ok(number as usize)
+    } else {
+        Err(format!("invalid index {number}"))
+    }
+}
@@ -0,0 +1,340 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use agent_core::{Agent, AgentContext, AgentError, Plan, Step, StepOutcome};
+use agent_models::{ToolCallInfo, UsageTracker};
+use agent_tools::ToolRegistry;
+use futures::stream::{FuturesUnordered, StreamExt};
+use tokio::time::Instant;
+
+use crate::budget::BudgetMeter;
+use crate::condition;
+use crate::intervention::{self, Reviewed};
+use crate::{
+    delegate, hooks, AgentEvent, Delegator, EventBus, InterventionHandler, PauseHandle, RunBudget,
+    RunHook, StepExecutor,
+};
+
+/// Steps a [`DagExecutor`] runs at once unless told otherwise.
+pub const DEFAULT_MAX_CONCURRENCY: usize = 4;
+
+/// Runs a plan's steps as soon as their `depends_on` steps have succeeded,
+/// up to `max_concurrency` at a time.
+///
+/// Each running step acts on its own clone of the context, so memory and
+/// other shared handles are shared but per-step state changes are not;
+/// [`Agent::observe`] is then called on the caller's context in completion
+/// order. Steps whose dependency failed are not run and get a failed outcome
+/// instead. Steps carrying a `sub_plan` or `for_each` are rejected. Once the
+/// context's [`cancellation`](AgentContext::cancellation) token fires no more
+/// steps start, and running ones end with [`AgentError::Cancelled`]. A step
+/// whose `condition` is false when it becomes ready counts as failed, so its
+/// dependents are skipped too. Steps that need review are put to the
+/// `intervention` handler as they become ready; an abort stops new steps
+/// from starting, as does reaching a limit of the `budget`, measured with
+/// `usage` from the start of [`DagExecutor::run`].
+#[derive(Clone)]
+pub struct DagExecutor {
+    pub max_concurrency: usize,
+    /// Stop starting new steps after this many; unlimited when `None`.
+    pub max_steps: Option<usize>,
+    pub tools: Option<Arc<ToolRegistry>>,
+    pub events: Option<EventBus>,
+    /// Checked before each step starts.
+    pub pause: Option<PauseHandle>,
+    /// Steps still running at this instant fail with
+    /// [`AgentError::Timeout`], and no more are started.
+    pub deadline: Option<Instant>,
+    pub intervention: Option<Arc<dyn InterventionHandler>>,
+    pub budget: Option<RunBudget>,
+    pub usage: Option<UsageTracker>,
+    /// Their `before_step` runs as each step is started and `after_step` as
+    /// it finishes; the other callbacks are left to the caller.
+    pub hooks: Vec<Arc<dyn RunHook>>,
+    /// Runs the agents that steps with a `delegate` hand over to.
+    pub delegates: Option<Arc<dyn Delegator>>,
+}
+
+impl Default for DagExecutor {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_CONCURRENCY)
+    }
+}
+
+impl DagExecutor {
+    pub fn new(max_concurrency: usize) -> Self {
+        Self {
+            max_concurrency,
+            max_steps: None,
+            tools: None,
+            events: None,
+            pause: None,
+            deadline: None,
+            intervention: None,
+            budget: None,
+            usage: None,
+            hooks: Vec::new(),
+            delegates: None,
+        }
+    }
+
+    pub fn with_max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = Some(max_steps);
+        self
+    }
+
+    pub fn with_tools(mut self, tools: Arc<ToolRegistry>) -> Self {
+        self.tools = Some(tools);
+        self
+    }
+
+    pub fn with_events(mut self, events: EventBus) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    pub fn with_pause(mut self, pause: PauseHandle) -> Self {
+        self.pause = Some(pause);
+        self
+    }
+
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    pub fn with_intervention(mut self, handler: Arc<dyn InterventionHandler>) -> Self {
+        self.intervention = Some(handler);
+        self
+    }
+
+    pub fn with_budget(mut self, budget: RunBudget) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    pub fn with_usage_tracker(mut self, tracker: UsageTracker) -> Self {
+        self.usage = Some(tracker);
+        self
+    }
+
+    pub fn with_hook(mut self, hook: Arc<dyn RunHook>) -> Self {
+        self.hooks.push(hook);
+        self
+    }
+
+    pub fn with_delegates(mut self, delegates: Arc<dyn Delegator>) -> Self {
+        self.delegates = Some(delegates);
+        self
+    }
+
+    fn expired(&self) -> bool {
+        self.deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    fn emit(&self, event: AgentEvent) {
+        if let Some(events) = &self.events {
+            events.publish(event);
+        }
+    }
+
+    /// Runs `plan` and returns its outcomes in the order steps finished.
+    pub async fn run<A: Agent>(
+        &self,
+        plan: &Plan,
+        agent: &A,
+        ctx: &mut AgentContext,
+    ) -> Result<Vec<StepOutcome>, AgentError> {
+        plan.dependency_layers()?;
+        if let Some(step) = plan.steps.iter().find(|step| step.sub_plan.is_some()) {
+            return Err(AgentError::Validation(format!(
+                "step {} has a sub-plan, which dependency graphs do not support",
+                step.id
+            )));
+        }
+        if let Some(step) = plan.steps.iter().find(|step| step.for_each.is_some()) {
+            return Err(AgentError::Validation(format!(
+                "step {} has a for_each, which dependency graphs do not support",
+                step.id
+            )));
+        }
+
+        let limit = self.max_concurrency.max(1);
+        let budget = self.max_steps.unwrap_or(usize::MAX);
+        let mut pending: Vec<&Step> = plan.steps.iter().collect();
+        let mut succeeded: HashSet<String> = HashSet::new();
+        let mut failed: HashSet<String> = HashSet::new();
+        let mut outcomes = Vec::new();
+        let mut running = FuturesUnordered::new();
+        let mut started = 0usize;
+        let mut aborted = false;
+        let meter = self
+            .budget
+            .clone()
+            .map(|budget| BudgetMeter::start(budget, self.usage.clone()));
+        let over_budget = || {
+            meter
+                .as_ref()
+                .is_some_and(|meter| meter.exceeded().is_some())
+        };
+        let cancellation = ctx.cancellation.clone();
+        let cancelled = || {
+            cancellation
+                .as_ref()
+                .is_some_and(|token| token.is_cancelled())
+        };
+
+        loop {
+            // Failures cascade to everything downstream before anything new starts.
+            while let Some(index) = pending
+                .iter()
+                .position(|step| step.depends_on.iter().any(|id| failed.contains(id)))
+            {
+                let step = pending.remove(index);
+                let dependency = step
+                    .depends_on
+                    .iter()
+                    .find(|id| failed.contains(*id))
+                    .cloned()
+                    .unwrap_or_default();
+                let mut outcome = StepOutcome::failure(
+                    step.id.clone(),
+                    AgentError::Validation(format!("dependency {dependency} failed")),
+                );
+                outcome
+                    .control_notes
+                    .push(format!("skipped: dependency {dependency} failed"));
+                failed.insert(step.id.clone());
+                self.emit(AgentEvent::StepCompleted {
+                    iteration: started,
+                    outcome: outcome.clone(),
+                });
+                outcomes.push(outcome);
+            }
+
+            while running.len() < limit
+                && started < budget
+                && !aborted
+                && !over_budget()
+                && !self.expired()
+                && !cancelled()
+            {
+                let Some(index) = pending
+                    .iter()
+                    .position(|step| step.depends_on.iter().all(|id| succeeded.contains(id)))
+                else {
+                    break;
+                };
+                if let Some(pause) = &self.pause {
+                    match &cancellation {
+                        Some(token) => {
+                            tokio::select! {
+                                _ = token.cancelled() => break,
+                                _ = pause.wait_while_paused() => {}
+                            }
+                        }
+                        None => pause.wait_while_paused().await,
+                    }
+                }
+                let step = pending.remove(index).clone();
+                let reviewed = match condition::check(&step, &outcomes, ctx) {
+                    Some(skipped) => Reviewed::Done(skipped),
+                    None => {
+                        match intervention::review(self.intervention.as_deref(), step, ctx).await {
+                            Reviewed::Run { mut step, note } => {
+                                match hooks::before_step(&self.hooks, &mut step, ctx).await {
+                                    Some(refused) => Reviewed::Done(refused),
+                                    None => Reviewed::Run { step, note },
+                                }
+                            }
+                            done => done,
+                        }
+                    }
+                };
+                let (step, note) = match reviewed {
+                    Reviewed::Run { step, note } => (*step, note),
+                    Reviewed::Done(outcome) => {
+                        aborted |= intervention::abort_reason(&outcome).is_some();
+                        failed.insert(outcome.step_id.clone());
+                        self.emit(AgentEvent::StepCompleted {
+                            iteration: started,
+                            outcome: outcome.clone(),
+                        });
+                        outcomes.push(outcome);
+                        continue;
+                    }
+                };
+                let iteration = started;
+                started += 1;
+                self.emit(AgentEvent::StepStarted {
+                    iteration,
+                    step_id: step.id.clone(),
+                });
+                if let Some(tool) = &step.tool {
+                    self.emit(AgentEvent::ToolCalled {
+                        iteration,
+                        step_id: step.id.clone(),
+                        call: ToolCallInfo {
+                            name: tool.clone(),
+                            arguments: step.args.clone(),
+                            id: None,
+                        },
+                    });
+                }
+                let mut step_ctx = ctx.clone();
+                step_ctx.state.iteration = iteration;
+                let tools = self.tools.as_deref();
+                let deadline = self.deadline;
+                let delegates = self.delegates.clone();
+                running.push(async move {
+                    let step_id = step.id.clone();
+                    let execution = async {
+                        match &step.delegate {
+                            Some(delegate) => {
+                                delegate::run(delegates.as_deref(), &step, delegate, &step_ctx)
+                                    .await
+                            }
+                            None => {
+                                StepExecutor::run_step_with_tools(step, agent, &mut step_ctx, tools)
+                                    .await
+                            }
+                        }
+                    };
+                    let mut outcome = match deadline {
+                        Some(deadline) => tokio::time::timeout_at(deadline, execution)
+                            .await
+                            .unwrap_or_else(|_| StepOutcome::failure(step_id, AgentError::Timeout)),
+                        None => execution.await,
+                    };
+                    outcome.control_notes.extend(note);
+                    (iteration, outcome)
+                });
+            }
+
+            let Some((iteration, mut outcome)) = running.next().await else {
+                // Steps skipped or turned down may have left dependents to skip.
+                if pending
+                    .iter()
+                    .any(|step| step.depends_on.iter().any(|id| failed.contains(id)))
+                {
+                    continue;
+                }
+                break;
+            };
+            hooks::after_step(&self.hooks, &mut outcome, ctx).await?;
+            if outcome.success {
+                succeeded.insert(outcome.step_id.clone());
+            } else {
+                failed.insert(outcome.step_id.clone());
+            }
+            agent.observe(&outcome, ctx).await?;
+            self.emit(AgentEvent::StepCompleted {
+                iteration,
+                outcome: outcome.clone(),
+            });
+            outcomes.push(outcome);
+        }
+        Ok(outcomes)
+    }
+}
@@ -0,0 +1,80 @@
+use agent_core::{AgentContext, AgentError, Delegate, Step, StepOutcome};
+use async_trait::async_trait;
+use serde_json::Value;
+
+use crate::{RunReport, StopReason};
+
+/// Runs another agent, by name, for steps that carry a `delegate`.
+///
+/// Implemented by [`MultiAgentOrchestrator`](crate::MultiAgentOrchestrator)
+/// for the agents registered with
+/// [`register_delegate`](crate::MultiAgentOrchestrator::register_delegate).
+#[async_trait]
+pub trait Delegator: Send + Sync {
+    /// Runs `agent`'s own control loop on `input` and returns its report.
+    /// `parent` is the delegating step's context, e.g. for its cancellation
+    /// token.
+    async fn delegate(
+        &self,
+        agent: &str,
+        input: Value,
+        parent: &AgentContext,
+    ) -> Result<RunReport, AgentError>;
+}
+
+/// Runs `step` on the agent `delegate` names and folds that run into the
+/// step's outcome. The step's timeout covers the whole delegated run.
+pub(crate) async fn run(
+    delegator: Option<&dyn Delegator>,
+    step: &Step,
+    delegate: &Delegate,
+    ctx: &AgentContext,
+) -> StepOutcome {
+    let Some(delegator) = delegator else {
+        return StepOutcome::failure(
+            step.id.clone(),
+            AgentError::Validation(format!(
+                "step {} delegates to {} but no delegator is set",
+                step.id, delegate.agent
+            )),
+        );
+    };
+    let run = delegator.delegate(&delegate.agent, delegate.input.clone(), ctx);
+    let result = match step.policies.timeout() {
+        Some(limit) => tokio::time::timeout(limit, run)
+            .await
+            .unwrap_or(Err(AgentError::Timeout)),
+        None => run.await,
+    };
+    match result {
+        Ok(report) => fold(step.id.clone(), &delegate.agent, report),
+        Err(error) => {
+            let mut outcome = StepOutcome::failure(step.id.clone(), error);
+            outcome
+                .control_notes
+                .push(format!("delegate: {}", delegate.agent));
+            outcome
+        }
+    }
+}
+
+/// The delegating step's outcome: the output of the delegated run's last
+/// step, successful when that run completed and its last step succeeded.
+pub(crate) fn fold(step_id: String, agent: &str, report: RunReport) -> StepOutcome {
+    let last = report.outcomes.last();
+    let succeeded =
+        report.stop_reason == StopReason::Completed && last.is_none_or(|outcome| outcome.success);
+    let output = last
+        .map(|outcome| outcome.output.clone())
+        .unwrap_or_default();
+    let mut outcome = StepOutcome::success(step_id, output);
+    outcome.success = succeeded;
+    outcome.retries = report.outcomes.iter().map(|outcome| outcome.retries).sum();
+    outcome.observations.push(format!(
+        "{agent} ran {} steps and stopped: {:?}",
+        report.outcomes.len(),
+        report.stop_reason
+    ));
+    outcome.control_notes.push(format!("delegate: {agent}"));
+    outcome
+}
@@ -0,0 +1,99 @@
+use agent_core::{Plan, StepOutcome};
+use agent_models::ToolCallInfo;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{broadcast, mpsc};
+
+/// Lifecycle events published by [`crate::ControlLoop`] while it runs.
+///
+/// The loop publishes everything but [`AgentEvent::TokenDelta`], which
+/// agents that stream model output publish themselves through the same
+/// [`EventBus`], as [`crate::ModelPlannerAgent::with_events`] does.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", content = "data")]
+pub enum AgentEvent {
+    RunStarted {
+        agent: String,
+    },
+    PlanCreated {
+        plan: Plan,
+    },
+    StepStarted {
+        iteration: usize,
+        step_id: String,
+    },
+    /// A step is about to invoke `call`.
+    ToolCalled {
+        iteration: usize,
+        step_id: String,
+        call: ToolCallInfo,
+    },
+    /// Next piece of model output while a step runs.
+    TokenDelta {
+        step_id: String,
+        text: String,
+    },
+    StepCompleted {
+        iteration: usize,
+        outcome: StepOutcome,
+    },
+    RunFinished {
+        steps: usize,
+    },
+}
+
+/// In-process pub/sub for [`AgentEvent`]s.
+///
+/// Every subscriber sees every event. Publishing never blocks: a subscriber
+/// that falls more than `capacity` events behind loses the oldest ones and
+/// observes [`broadcast::error::RecvError::Lagged`] on its next receive.
+///
+/// Events are also forwarded, in order, to any bounded mpsc channels added
+/// with [`EventBus::with_channel`], for a single consumer such as a server
+/// streaming a run's progress to its client. Publishing never waits on a
+/// channel either: while one is full, new events for it are dropped with a
+/// warning, so size it for the bursts its consumer has to absorb.
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<AgentEvent>,
+    channels: Vec<mpsc::Sender<AgentEvent>>,
+}
+
+impl EventBus {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity.max(1));
+        Self {
+            sender,
+            channels: Vec::new(),
+        }
+    }
+
+    pub fn with_channel(mut self, channel: mpsc::Sender<AgentEvent>) -> Self {
+        self.channels.push(channel);
+        self
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<AgentEvent> {
+        self.sender.subscribe()
+    }
+
+    pub fn publish(&self, event: AgentEvent) {
+        // A closed channel or no subscribers is not an error; the event is
+        // simply dropped.
+        for channel in &self.channels {
+            if let Err(mpsc::error::TrySendError::Full(dropped)) = channel.try_send(event.clone()) {
+                tracing::warn!(event = ?dropped, "event channel full, dropped event");
+            }
+        }
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        self.sender.receiver_count()
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new(256)
+    }
+}
@@ -0,0 +1,141 @@
+//! `for_each` steps: a template step run once per item of a JSON array.
+
+use agent_core::{AgentError, FailureKind, ForEach, Step, StepOutcome};
+use serde_json::{json, Map, Value};
+
+use crate::condition::{self, Condition, ConditionError};
+use crate::intervention;
+
+/// One item of a `for_each` step.
+pub(crate) enum Item {
+    /// Run this step for the item.
+    Run(Box<Step>),
+    /// The template's condition was false for the item; this is its outcome.
+    Done(StepOutcome),
+}
+
+/// Builds the item steps of `step` from the array `for_each.items` leads to
+/// in `scope`, which is extended with `item` and `index` for each of them.
+pub(crate) fn expand(
+    step: &Step,
+    for_each: &ForEach,
+    scope: &Value,
+) -> Result<Vec<Item>, AgentError> {
+    let invalid = |error: ConditionError| AgentError::Validation(error.to_string());
+    let items = Condition::parse(&for_each.items)
+        .map_err(invalid)?
+        .value(scope);
+    let Value::Array(items) = items else {
+        return Err(AgentError::Validation(format!(
+            "for_each items `{}` of step {} is not an array",
+            for_each.items, step.id
+        )));
+    };
+
+    let mut expanded = Vec::with_capacity(items.len());
+    for (index, item) in items.into_iter().enumerate() {
+        let mut scope = scope.clone();
+        scope["item"] = item;
+        scope["index"] = json!(index);
+        let mut template = for_each.template.clone();
+        template.id = format!("{}[{index}]", step.id);
+        template.description = render_text(&template.description, &scope).map_err(invalid)?;
+        template.args = render(template.args, &scope).map_err(invalid)?;
+        expanded.push(match condition::check_in(&template, &scope) {
+            Some(outcome) => Item::Done(outcome),
+            None => {
+                template.condition = None;
+                Item::Run(Box::new(template))
+            }
+        });
+    }
+    Ok(expanded)
+}
+
+/// The outcome of the `for_each` step itself, from its item outcomes in item
+/// order (`None` for items that never ran).
+///
+/// The output holds each item's output under `items`, `null` for items that
+/// did not run. It succeeds when no item failed, not counting those whose
+/// condition was false, and carries a reviewer's abort on to the run.
+pub(crate) fn aggregate(step_id: String, items: Vec<Option<StepOutcome>>) -> StepOutcome {
+    let total = items.len();
+    let mut failed = 0;
+    let mut aborted = None;
+    let mut outputs = Vec::with_capacity(total);
+    for item in items {
+        let Some(item) = item else {
+            failed += 1;
+            outputs.push(Value::Null);
+            continue;
+        };
+        let filtered = matches!(item.failure_kind, Some(FailureKind::ConditionNotMet { .. }));
+        if !item.success && !filtered {
+            failed += 1;
+        }
+        if aborted.is_none() {
+            aborted = intervention::abort_reason(&item).map(str::to_string);
+        }
+        outputs.push(item.output);
+    }
+
+    let mut outcome = StepOutcome::success(step_id, json!({ "items": outputs }));
+    outcome.success = failed == 0;
+    outcome
+        .control_notes
+        .push(format!("for_each: {total} items, {failed} failed"));
+    if let Some(reason) = aborted {
+        outcome.failure_kind = Some(FailureKind::AbortedByReviewer { reason });
+    }
+    outcome
+}
+
+/// Renders the `{{ expression }}`s in the strings of `value`.
+fn render(value: Value, scope: &Value) -> Result<Value, ConditionError> {
+    Ok(match value {
+        Value::String(text) => {
+            let single = text
+                .trim()
+                .strip_prefix("{{")
+                .and_then(|rest| rest.strip_suffix("}}"))
+                .filter(|inner| !inner.contains("{{") && !inner.contains("}}"));
+            match single {
+                Some(expression) => Condition::parse(expression)?.value(scope),
+                None => Value::String(render_text(&text, scope)?),
+            }
+        }
+        Value::Array(items) => Value::Array(
+            items
+                .into_iter()
+                .map(|item| render(item, scope))
+                .collect::<Result<_, _>>()?,
+        ),
+        Value::Object(fields) => Value::Object(
+            fields
+                .into_iter()
+                .map(|(key, field)| Ok((key, render(field, scope)?)))
+                .collect::<Result<Map<_, _>, ConditionError>>()?,
+        ),
+        other => other,
+    })
+}
+
+/// Replaces each `{{ expression }}` in `text` with its value; strings are
+/// inserted as they are and anything else as JSON.
+fn render_text(text: &str, scope: &Value) -> Result<String, ConditionError> {
+    let mut rendered = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start..].find("}}") else {
+            break;
+        };
+        rendered.push_str(&rest[..start]);
+        match Condition::parse(&rest[start + 2..start + len])?.value(scope) {
+            Value::String(value) => rendered.push_str(&value),
+            value => rendered.push_str(&value.to_string()),
+        }
+        rest = &rest[start + len + 2..];
+    }
+    rendered.push_str(rest);
+    Ok(rendered)
+}
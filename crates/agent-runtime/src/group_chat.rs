@@ -0,0 +1,269 @@
+//! Group chats: several agents taking turns in one shared conversation.
+
+use std::sync::Arc;
+
+use agent_core::{AgentContext, AgentError};
+use agent_evals::OutputEvaluator;
+use agent_models::{GenerationOptions, LLMModel};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{Delegator, RunReport};
+
+/// Speaker of the opening message, which holds the chat's task.
+pub const GROUP_CHAT_USER: &str = "user";
+
+/// One message of a [`GroupChat`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GroupMessage {
+    pub speaker: String,
+    pub content: String,
+}
+
+/// Picks the next speaker from the messages so far and the participants;
+/// `None` ends the chat.
+pub type SpeakerFn = Arc<dyn Fn(&[GroupMessage], &[String]) -> Option<String> + Send + Sync>;
+
+/// How a [`GroupChat`] picks who speaks next.
+#[derive(Clone, Default)]
+pub enum SpeakerSelection {
+    /// Participants take turns in the order they were added.
+    #[default]
+    RoundRobin,
+    /// The model reads the transcript and names the next speaker.
+    Model(Arc<dyn LLMModel>),
+    Function(SpeakerFn),
+}
+
+/// Ends a [`GroupChat`] early, checked after every message.
+#[derive(Clone)]
+pub enum Termination {
+    /// A message matching the pattern, e.g. `(?i)\bTERMINATE\b`.
+    Regex(Regex),
+    /// A message the evaluator passes.
+    Evaluator(Arc<dyn OutputEvaluator>),
+}
+
+/// Why a [`GroupChat`] ended.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GroupChatStop {
+    /// `max_turns` messages were exchanged.
+    #[default]
+    MaxTurns,
+    /// The last message matched this pattern.
+    Matched { pattern: String },
+    /// The evaluator passed the last message.
+    EvaluatorPassed,
+    /// The selection function returned no speaker.
+    NoSpeaker,
+    /// The context's cancellation token fired.
+    Cancelled,
+}
+
+/// The messages of a [`GroupChat`], starting with the task.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GroupChatReport {
+    pub messages: Vec<GroupMessage>,
+    pub stop_reason: GroupChatStop,
+}
+
+impl GroupChatReport {
+    /// The last participant message, if anyone spoke.
+    pub fn last_reply(&self) -> Option<&GroupMessage> {
+        self.messages.iter().skip(1).last()
+    }
+}
+
+/// Runs several agents in one conversation, each turn handing the whole
+/// transcript to the speaker picked by `selection`.
+///
+/// A turn runs the speaker through the [`Delegator`] with the input
+/// `{"goal": <task>, "conversation": [<messages>]}`; the output of its last
+/// step becomes its message, taking the `message` or `content` field of an
+/// object when there is one. With a
+/// [`MultiAgentOrchestrator`](crate::MultiAgentOrchestrator) as delegator,
+/// participants are the names given to
+/// [`register_delegate`](crate::MultiAgentOrchestrator::register_delegate).
+#[derive(Clone)]
+pub struct GroupChat {
+    pub participants: Vec<String>,
+    pub delegator: Arc<dyn Delegator>,
+    pub selection: SpeakerSelection,
+    /// Participant messages after which the chat ends.
+    pub max_turns: usize,
+    pub terminations: Vec<Termination>,
+}
+
+/// Turns a [`GroupChat`] takes unless told otherwise.
+pub const DEFAULT_MAX_TURNS: usize = 10;
+
+impl GroupChat {
+    pub fn new(delegator: Arc<dyn Delegator>, participants: Vec<String>) -> Self {
+        Self {
+            participants,
+            delegator,
+            selection: SpeakerSelection::default(),
+            max_turns: DEFAULT_MAX_TURNS,
+            terminations: Vec::new(),
+        }
+    }
+
+    pub fn with_selection(mut self, selection: SpeakerSelection) -> Self {
+        self.selection = selection;
+        self
+    }
+
+    pub fn with_max_turns(mut self, max_turns: usize) -> Self {
+        self.max_turns = max_turns;
+        self
+    }
+
+    pub fn with_termination(mut self, termination: Termination) -> Self {
+        self.terminations.push(termination);
+        self
+    }
+
+    /// Holds the conversation on `task`. `ctx` is passed to each turn as the
+    /// delegating context, so its cancellation token stops the chat.
+    pub async fn run(&self, task: &str, ctx: &AgentContext) -> Result<GroupChatReport, AgentError> {
+        if self.participants.is_empty() {
+            return Err(AgentError::Validation(
+                "a group chat needs at least one participant".into(),
+            ));
+        }
+        let mut report = GroupChatReport {
+            messages: vec![GroupMessage {
+                speaker: GROUP_CHAT_USER.into(),
+                content: task.to_string(),
+            }],
+            stop_reason: GroupChatStop::MaxTurns,
+        };
+
+        for turn in 0..self.max_turns {
+            if ctx
+                .cancellation
+                .as_ref()
+                .is_some_and(|token| token.is_cancelled())
+            {
+                report.stop_reason = GroupChatStop::Cancelled;
+                break;
+            }
+            let Some(speaker) = self.next_speaker(turn, &report.messages).await? else {
+                report.stop_reason = GroupChatStop::NoSpeaker;
+                break;
+            };
+            let input = json!({"goal": task, "conversation": report.messages});
+            let run = self.delegator.delegate(&speaker, input, ctx).await?;
+            tracing::debug!(%speaker, turn, "group chat turn");
+            let message = GroupMessage {
+                speaker,
+                content: reply(&run),
+            };
+            let stop = self.terminated(&message).await?;
+            report.messages.push(message);
+            if let Some(stop) = stop {
+                report.stop_reason = stop;
+                break;
+            }
+        }
+        Ok(report)
+    }
+
+    async fn next_speaker(
+        &self,
+        turn: usize,
+        messages: &[GroupMessage],
+    ) -> Result<Option<String>, AgentError> {
+        let speaker = match &self.selection {
+            SpeakerSelection::RoundRobin => {
+                return Ok(Some(
+                    self.participants[turn % self.participants.len()].clone(),
+                ))
+            }
+            SpeakerSelection::Function(select) => match select(messages, &self.participants) {
+                Some(speaker) => speaker,
+                None => return Ok(None),
+            },
+            SpeakerSelection::Model(model) => {
+                let prompt = selection_prompt(&self.participants, messages);
+                let response = model
+                    .generate(&prompt, &GenerationOptions::default())
+                    .await?;
+                let choice = response
+                    .content
+                    .trim()
+                    .trim_matches(|c: char| !c.is_alphanumeric());
+                self.participants
+                    .iter()
+                    .find(|name| name.as_str() == choice)
+                    .or_else(|| {
+                        self.participants
+                            .iter()
+                            .find(|name| response.content.contains(name.as_str()))
+                    })
+                    .cloned()
+                    .unwrap_or_else(|| response.content.trim().to_string())
+            }
+        };
+        if !self.participants.contains(&speaker) {
+            return Err(AgentError::Validation(format!(
+                "{speaker} is not a participant of the group chat"
+            )));
+        }
+        Ok(Some(speaker))
+    }
+
+    async fn terminated(
+        &self,
+        message: &GroupMessage,
+    ) -> Result<Option<GroupChatStop>, AgentError> {
+        for termination in &self.terminations {
+            match termination {
+                Termination::Regex(pattern) if pattern.is_match(&message.content) => {
+                    return Ok(Some(GroupChatStop::Matched {
+                        pattern: pattern.as_str().to_string(),
+                    }))
+                }
+                Termination::Regex(_) => {}
+                Termination::Evaluator(evaluator) => {
+                    let result = evaluator
+                        .evaluate(&Value::String(message.content.clone()))
+                        .await
+                        .map_err(|error| AgentError::Execution(error.to_string()))?;
+                    if result.passed {
+                        return Ok(Some(GroupChatStop::EvaluatorPassed));
+                    }
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// The text a participant's run contributes to the conversation.
+fn reply(run: &RunReport) -> String {
+    let Some(output) = run.outcomes.last().map(|outcome| &outcome.output) else {
+        return String::new();
+    };
+    let text = output
+        .as_str()
+        .or_else(|| output.get("message").and_then(Value::as_str))
+        .or_else(|| output.get("content").and_then(Value::as_str));
+    match text {
+        Some(text) => text.to_string(),
+        None => output.to_string(),
+    }
+}
+
+fn selection_prompt(participants: &[String], messages: &[GroupMessage]) -> String {
+    let mut prompt = format!(
+        "You are moderating a conversation between: {}.\n\nConversation:\n",
+        participants.join(", ")
+    );
+    for message in messages {
+        prompt.push_str(&format!("{}: {}\n", message.speaker, message.content));
+    }
+    prompt.push_str("\nReply with only the name of the participant who should speak next.");
+    prompt
+}
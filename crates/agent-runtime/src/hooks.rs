@@ -0,0 +1,67 @@
+use std::sync::Arc;
+
+use agent_core::{AgentContext, AgentError, Plan, Step, StepOutcome};
+use async_trait::async_trait;
+
+use crate::RunReport;
+
+/// Callbacks around a [`ControlLoop`](crate::ControlLoop) run, for concerns
+/// such as telemetry, guardrails or persistence that apply to every agent.
+///
+/// Every method does nothing by default. Hooks run in the order they were
+/// added. `before_step` and `after_step` bracket each step that runs and may
+/// change it: an error from `before_step` fails the step without running it,
+/// while errors from `on_plan` and `after_step` fail the run.
+#[async_trait]
+pub trait RunHook: Send + Sync {
+    async fn on_plan(&self, _plan: &Plan, _ctx: &AgentContext) -> Result<(), AgentError> {
+        Ok(())
+    }
+
+    async fn before_step(&self, _step: &mut Step, _ctx: &AgentContext) -> Result<(), AgentError> {
+        Ok(())
+    }
+
+    async fn after_step(
+        &self,
+        _outcome: &mut StepOutcome,
+        _ctx: &AgentContext,
+    ) -> Result<(), AgentError> {
+        Ok(())
+    }
+
+    /// The run failed with `error`.
+    async fn on_error(&self, _error: &AgentError, _ctx: &AgentContext) {}
+
+    /// The run ended, for whatever [`StopReason`](crate::StopReason).
+    async fn on_finish(&self, _report: &RunReport, _ctx: &AgentContext) {}
+}
+
+/// Runs each hook's `before_step`, returning the failed outcome of a step a
+/// hook refused.
+pub(crate) async fn before_step(
+    hooks: &[Arc<dyn RunHook>],
+    step: &mut Step,
+    ctx: &AgentContext,
+) -> Option<StepOutcome> {
+    for hook in hooks {
+        if let Err(error) = hook.before_step(step, ctx).await {
+            tracing::warn!(step = %step.id, %error, "run hook refused step");
+            let mut outcome = StepOutcome::failure(step.id.clone(), error);
+            outcome.control_notes.push("refused by run hook".into());
+            return Some(outcome);
+        }
+    }
+    None
+}
+
+pub(crate) async fn after_step(
+    hooks: &[Arc<dyn RunHook>],
+    outcome: &mut StepOutcome,
+    ctx: &AgentContext,
+) -> Result<(), AgentError> {
+    for hook in hooks {
+        hook.after_step(outcome, ctx).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,107 @@
+use agent_core::{AgentContext, AgentError, FailureKind, Step, StepOutcome};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// A reviewer's answer for a step whose `SafetyPolicy::requires_review` is
+/// set.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "decision", rename_all = "snake_case")]
+pub enum InterventionDecision {
+    /// Run the step as planned.
+    Approve,
+    /// Run the step with these arguments instead.
+    EditArgs { args: Value },
+    /// Leave the step out and carry on with the run.
+    Skip,
+    /// Stop the whole run.
+    Abort { reason: String },
+}
+
+/// Asks a human, or anything standing in for one, about steps that need
+/// review before they run.
+///
+/// Decisions are recorded in the step outcome's `control_notes` as
+/// `intervention: approved`, `intervention: args edited`,
+/// `intervention: skipped` or `intervention: aborted: <reason>`.
+#[async_trait]
+pub trait InterventionHandler: Send + Sync {
+    async fn review(&self, step: &Step, ctx: &AgentContext) -> InterventionDecision;
+}
+
+/// What reviewing a step left to do.
+pub(crate) enum Reviewed {
+    /// Run `step`, adding `note` to its outcome.
+    Run {
+        step: Box<Step>,
+        note: Option<String>,
+    },
+    /// Do not run the step; this is its outcome.
+    Done(StepOutcome),
+}
+
+/// Puts `step` to `handler` when its safety policy asks for review. Steps
+/// needing review fail when there is no handler to ask.
+pub(crate) async fn review(
+    handler: Option<&dyn InterventionHandler>,
+    mut step: Step,
+    ctx: &AgentContext,
+) -> Reviewed {
+    if !step.policies.safety.requires_review {
+        return Reviewed::Run {
+            step: Box::new(step),
+            note: None,
+        };
+    }
+    let Some(handler) = handler else {
+        return Reviewed::Done(StepOutcome::failure(
+            step.id.clone(),
+            AgentError::Safety(format!(
+                "step {} requires review but no intervention handler is set",
+                step.id
+            )),
+        ));
+    };
+
+    let decision = handler.review(&step, ctx).await;
+    tracing::info!(step = %step.id, ?decision, "step reviewed");
+    let (error, kind, note) = match decision {
+        InterventionDecision::Approve => {
+            return Reviewed::Run {
+                step: Box::new(step),
+                note: Some("intervention: approved".into()),
+            }
+        }
+        InterventionDecision::EditArgs { args } => {
+            step.args = args;
+            return Reviewed::Run {
+                step: Box::new(step),
+                note: Some("intervention: args edited".into()),
+            };
+        }
+        InterventionDecision::Skip => (
+            AgentError::Safety("skipped by reviewer".into()),
+            FailureKind::SkippedByReviewer,
+            "intervention: skipped".to_string(),
+        ),
+        InterventionDecision::Abort { reason } => (
+            AgentError::Safety(format!("aborted by reviewer: {reason}")),
+            FailureKind::AbortedByReviewer {
+                reason: reason.clone(),
+            },
+            format!("intervention: aborted: {reason}"),
+        ),
+    };
+    let mut outcome = StepOutcome::failure(step.id, error);
+    outcome.failure_kind = Some(kind);
+    outcome.control_notes.push(note);
+    Reviewed::Done(outcome)
+}
+
+/// The reviewer's reason when `outcome` ended the run.
+pub(crate) fn abort_reason(outcome: &StepOutcome) -> Option<&str> {
+    match &outcome.failure_kind {
+        Some(FailureKind::AbortedByReviewer { reason }) => Some(reason),
+        _ => None,
+    }
+}
@@ -0,0 +1,332 @@
+//! [`KafkaBus`], enabled by the `kafka` cargo feature.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use agent_core::AgentError;
+use async_trait::async_trait;
+use futures::stream;
+use rdkafka::config::ClientConfig;
+use rdkafka::consumer::{Consumer, StreamConsumer};
+use rdkafka::message::{Header, OwnedHeaders};
+use rdkafka::producer::{FutureProducer, FutureRecord};
+use rdkafka::Message;
+use serde_json::Value;
+use tokio::sync::OnceCell;
+use tokio::time::Instant;
+
+use crate::{
+    JsonCodec, MessageBus, MessageCodec, MessageStream, DEAD_LETTER_REASON_HEADER,
+    DEAD_LETTER_RECIPIENT_HEADER, DEFAULT_CONSUMER_GROUP,
+};
+
+/// Kafka topic a [`KafkaBus`] keeps inboxes in unless told otherwise.
+pub const DEFAULT_KAFKA_INBOX_TOPIC: &str = "agent-inbox";
+
+/// How long a [`KafkaBus`] `recv` waits for a message before returning
+/// `None`, unless told otherwise.
+pub const DEFAULT_POLL_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// A [`MessageBus`] over a Kafka cluster.
+///
+/// Every inbox shares one topic, keyed by recipient, so a recipient's
+/// messages land in one partition and keep their order. Each recipient is
+/// read by its own consumer, in the group `<group>.<recipient>`, which steps
+/// over other recipients' messages. The message `recv` returns is committed
+/// once the next `recv` for that recipient, or an explicit
+/// [`ack`](Self::ack), acknowledges it, so a consumer that stops before then
+/// gets it again when it comes back. Messages the codec cannot read are
+/// moved to the dead-letter topic with [`DEAD_LETTER_RECIPIENT_HEADER`] and
+/// [`DEAD_LETTER_REASON_HEADER`] set, or dropped with a warning when there is
+/// none.
+///
+/// Topics map to Kafka topics named `<topic prefix><topic>`. Each
+/// subscription is a consumer group of its own reading from the end of the
+/// topic, so messages published while it is still joining can be missed.
+/// Kafka does not count subscribers, so `publish` returns 0.
+pub struct KafkaBus {
+    config: ClientConfig,
+    producer: OnceCell<FutureProducer>,
+    codec: Arc<dyn MessageCodec>,
+    inbox_topic: String,
+    topic_prefix: String,
+    group: String,
+    poll_timeout: Duration,
+    send_timeout: Duration,
+    dead_letter: Option<String>,
+    consumers: tokio::sync::Mutex<HashMap<String, Arc<StreamConsumer>>>,
+    // The (topic, partition, offset) last received per recipient, until
+    // acknowledged.
+    unacked: Mutex<HashMap<String, (String, i32, i64)>>,
+}
+
+impl KafkaBus {
+    /// A bus for the cluster at `brokers`, e.g. `localhost:9092`. Nothing is
+    /// sent until the first call.
+    pub fn new(brokers: impl Into<String>) -> Self {
+        let mut config = ClientConfig::new();
+        config.set("bootstrap.servers", brokers.into());
+        Self {
+            config,
+            producer: OnceCell::new(),
+            codec: Arc::new(JsonCodec),
+            inbox_topic: DEFAULT_KAFKA_INBOX_TOPIC.into(),
+            topic_prefix: String::new(),
+            group: DEFAULT_CONSUMER_GROUP.into(),
+            poll_timeout: DEFAULT_POLL_TIMEOUT,
+            send_timeout: Duration::from_secs(5),
+            dead_letter: None,
+            consumers: tokio::sync::Mutex::new(HashMap::new()),
+            unacked: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Sets a librdkafka property, e.g. `security.protocol`, on every
+    /// producer and consumer the bus creates.
+    pub fn with_client_option(mut self, key: &str, value: impl Into<String>) -> Self {
+        self.config.set(key, value);
+        self
+    }
+
+    pub fn with_codec(mut self, codec: Arc<dyn MessageCodec>) -> Self {
+        self.codec = codec;
+        self
+    }
+
+    pub fn with_inbox_topic(mut self, topic: impl Into<String>) -> Self {
+        self.inbox_topic = topic.into();
+        self
+    }
+
+    /// Starts the Kafka topic of every [`publish`](MessageBus::publish)ed
+    /// topic, e.g. `agents.`.
+    pub fn with_topic_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.topic_prefix = prefix.into();
+        self
+    }
+
+    /// Starts each recipient's consumer group. Buses in the same group
+    /// share out an inbox's messages; separate groups each get all of them.
+    pub fn with_group(mut self, group: impl Into<String>) -> Self {
+        self.group = group.into();
+        self
+    }
+
+    pub fn with_poll_timeout(mut self, poll_timeout: Duration) -> Self {
+        self.poll_timeout = poll_timeout;
+        self
+    }
+
+    /// How long `send` and `publish` wait for the producer's queue to have
+    /// room.
+    pub fn with_send_timeout(mut self, send_timeout: Duration) -> Self {
+        self.send_timeout = send_timeout;
+        self
+    }
+
+    /// Moves undeliverable messages to this topic.
+    pub fn with_dead_letter(mut self, topic: impl Into<String>) -> Self {
+        self.dead_letter = Some(topic.into());
+        self
+    }
+
+    /// Acknowledges the message `recv` last returned for `recipient`, so it
+    /// is never delivered again. Does nothing when there is none.
+    pub async fn ack(&self, recipient: &str) -> Result<(), AgentError> {
+        let Some((topic, partition, offset)) = lock(&self.unacked).remove(recipient) else {
+            return Ok(());
+        };
+        let consumer = self.consumer(recipient).await?;
+        consumer
+            .store_offset(&topic, partition, offset)
+            .map_err(backend)
+    }
+
+    async fn producer(&self) -> Result<&FutureProducer, AgentError> {
+        self.producer
+            .get_or_try_init(|| async { self.config.create().map_err(backend) })
+            .await
+    }
+
+    async fn produce(
+        &self,
+        topic: &str,
+        key: Option<&str>,
+        payload: &[u8],
+        headers: Option<OwnedHeaders>,
+    ) -> Result<(), AgentError> {
+        let mut record = FutureRecord::<str, [u8]>::to(topic).payload(payload);
+        if let Some(key) = key {
+            record = record.key(key);
+        }
+        if let Some(headers) = headers {
+            record = record.headers(headers);
+        }
+        self.producer()
+            .await?
+            .send(record, self.send_timeout)
+            .await
+            .map(|_| ())
+            .map_err(|(error, _)| backend(error))
+    }
+
+    /// The consumer reading `recipient`'s messages, created and subscribed
+    /// on first use. Offsets are committed in the background, but only up
+    /// to the last one acknowledged.
+    async fn consumer(&self, recipient: &str) -> Result<Arc<StreamConsumer>, AgentError> {
+        let mut consumers = self.consumers.lock().await;
+        if let Some(consumer) = consumers.get(recipient) {
+            return Ok(consumer.clone());
+        }
+        let consumer: StreamConsumer = self
+            .config
+            .clone()
+            .set("group.id", format!("{}.{recipient}", self.group))
+            .set("enable.auto.offset.store", "false")
+            .set("auto.offset.reset", "earliest")
+            .create()
+            .map_err(backend)?;
+        consumer
+            .subscribe(&[self.inbox_topic.as_str()])
+            .map_err(backend)?;
+        let consumer = Arc::new(consumer);
+        consumers.insert(recipient.to_string(), consumer.clone());
+        Ok(consumer)
+    }
+
+    async fn dead_letter(
+        &self,
+        recipient: &str,
+        reason: &str,
+        payload: &[u8],
+    ) -> Result<(), AgentError> {
+        let Some(topic) = &self.dead_letter else {
+            tracing::warn!(%recipient, %reason, "dropped undeliverable message");
+            return Ok(());
+        };
+        let headers = OwnedHeaders::new()
+            .insert(Header {
+                key: DEAD_LETTER_RECIPIENT_HEADER,
+                value: Some(recipient),
+            })
+            .insert(Header {
+                key: DEAD_LETTER_REASON_HEADER,
+                value: Some(reason),
+            });
+        self.produce(topic, Some(recipient), payload, Some(headers))
+            .await
+    }
+}
+
+impl std::fmt::Debug for KafkaBus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("KafkaBus")
+            .field("inbox_topic", &self.inbox_topic)
+            .field("topic_prefix", &self.topic_prefix)
+            .field("group", &self.group)
+            .field("poll_timeout", &self.poll_timeout)
+            .field("dead_letter", &self.dead_letter)
+            .finish()
+    }
+}
+
+#[async_trait]
+impl MessageBus for KafkaBus {
+    async fn send(&self, recipient: &str, message: Value) -> Result<(), AgentError> {
+        let payload = self.codec.encode(&message)?;
+        self.produce(&self.inbox_topic, Some(recipient), &payload, None)
+            .await
+    }
+
+    async fn recv(&self, recipient: &str) -> Result<Option<Value>, AgentError> {
+        self.ack(recipient).await?;
+        let consumer = self.consumer(recipient).await?;
+        let deadline = Instant::now() + self.poll_timeout;
+        loop {
+            let Ok(received) = tokio::time::timeout_at(deadline, consumer.recv()).await else {
+                return Ok(None);
+            };
+            let message = received.map_err(backend)?;
+            let position = (
+                message.topic().to_string(),
+                message.partition(),
+                message.offset(),
+            );
+            if message.key() == Some(recipient.as_bytes()) {
+                let payload = message.payload().unwrap_or_default();
+                match self.codec.decode(payload) {
+                    Ok(value) => {
+                        lock(&self.unacked).insert(recipient.to_string(), position);
+                        return Ok(Some(value));
+                    }
+                    Err(error) => {
+                        let payload = payload.to_vec();
+                        drop(message);
+                        self.dead_letter(recipient, &error.to_string(), &payload)
+                            .await?;
+                    }
+                }
+            }
+            let (topic, partition, offset) = position;
+            consumer
+                .store_offset(&topic, partition, offset)
+                .map_err(backend)?;
+        }
+    }
+
+    async fn publish(&self, topic: &str, message: Value) -> Result<usize, AgentError> {
+        let payload = self.codec.encode(&message)?;
+        let topic = format!("{}{topic}", self.topic_prefix);
+        self.produce(&topic, None, &payload, None).await?;
+        Ok(0)
+    }
+
+    async fn subscribe(&self, topic: &str) -> Result<MessageStream, AgentError> {
+        let topic = format!("{}{topic}", self.topic_prefix);
+        let consumer: StreamConsumer = self
+            .config
+            .clone()
+            .set(
+                "group.id",
+                format!("{}.subscriber.{:016x}", self.group, rand::random::<u64>()),
+            )
+            .set("enable.auto.commit", "false")
+            .set("auto.offset.reset", "latest")
+            .create()
+            .map_err(backend)?;
+        consumer.subscribe(&[topic.as_str()]).map_err(backend)?;
+        let codec = self.codec.clone();
+        Ok(Box::pin(stream::unfold(consumer, move |consumer| {
+            let codec = codec.clone();
+            let topic = topic.clone();
+            async move {
+                loop {
+                    let decoded = match consumer.recv().await {
+                        Ok(message) => codec.decode(message.payload().unwrap_or_default()),
+                        Err(error) => {
+                            tracing::warn!(%topic, %error, "topic subscriber failed to receive");
+                            continue;
+                        }
+                    };
+                    match decoded {
+                        Ok(value) => return Some((value, consumer)),
+                        Err(error) => {
+                            tracing::warn!(%topic, %error, "dropped unreadable topic message");
+                        }
+                    }
+                }
+            }
+        })))
+    }
+}
+
+fn backend(error: impl std::fmt::Display) -> AgentError {
+    AgentError::Execution(format!("kafka: {error}"))
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
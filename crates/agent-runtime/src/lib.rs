@@ -1,47 +1,195 @@
 use agent_core::{
-    Agent, AgentContext, AgentError, ExecutablePlan, Plan, RetryPolicy, Step, StepOutcome,
+    Agent, AgentContext, AgentError, CancellationToken, DedupStrategy, ExecutablePlan, FailureKind,
+    FallbackKind, ForEach, Plan, RetryPolicy, Step, StepOutcome,
 };
 use async_trait::async_trait;
 use rand::Rng;
-use std::{collections::HashMap, sync::Arc};
-use tokio::time::{sleep, Duration};
+use std::{
+    collections::{BTreeMap, HashMap},
+    future::Future,
+    hash::{Hash, Hasher},
+    pin::Pin,
+    sync::Arc,
+};
+use tokio::sync::mpsc;
+use tokio::time::{sleep, Duration, Instant};
 use tracing::instrument;
 
 use agent_memory::MemoryStore;
+use agent_models::{
+    ContextUsage, GenerationOptions, LLMModel, LLMResponse, StreamEvent, StructuredGeneration,
+    StructuredOutputError, ToolCallInfo, UsageMetrics, UsageTotals, UsageTracker,
+};
+use agent_tools::{OutputRedactor, ToolRegistry};
+use serde::{Deserialize, Serialize};
+
+mod budget;
+mod bus;
+mod checkpoint;
+mod condition;
+mod dag;
+mod delegate;
+mod events;
+mod foreach;
+mod group_chat;
+mod hooks;
+mod intervention;
+#[cfg(feature = "kafka")]
+mod kafka_bus;
+#[cfg(feature = "nats")]
+mod nats_bus;
+mod pause;
+#[cfg(feature = "redis")]
+mod redis_bus;
+mod snapshot;
+mod tool_calling;
+mod workflow;
+
+use budget::BudgetMeter;
+pub use budget::{BudgetLimit, RunBudget};
+pub use bus::{
+    InMemoryBus, JsonCodec, MessageBus, MessageCodec, MessageStream, DEAD_LETTER_REASON_HEADER,
+    DEAD_LETTER_RECIPIENT_HEADER, DEFAULT_CONSUMER_GROUP, DEFAULT_INBOX_CAPACITY,
+    DEFAULT_TOPIC_CAPACITY,
+};
+pub use checkpoint::{CheckpointStore, RunCheckpoint};
+pub use condition::{condition_scope, Condition, ConditionError};
+pub use dag::{DagExecutor, DEFAULT_MAX_CONCURRENCY};
+pub use delegate::Delegator;
+pub use events::{AgentEvent, EventBus};
+pub use group_chat::{
+    GroupChat, GroupChatReport, GroupChatStop, GroupMessage, SpeakerFn, SpeakerSelection,
+    Termination, DEFAULT_MAX_TURNS, GROUP_CHAT_USER,
+};
+pub use hooks::RunHook;
+pub use intervention::{InterventionDecision, InterventionHandler};
+#[cfg(feature = "kafka")]
+pub use kafka_bus::{KafkaBus, DEFAULT_KAFKA_INBOX_TOPIC, DEFAULT_POLL_TIMEOUT};
+#[cfg(feature = "nats")]
+pub use nats_bus::{NatsBus, DEFAULT_MAX_DELIVERIES, DEFAULT_NATS_STREAM};
+pub use pause::PauseHandle;
+#[cfg(feature = "redis")]
+pub use redis_bus::{RedisBus, DEFAULT_CLAIM_IDLE};
+pub use snapshot::{RunDiff, RunSnapshot, StepDiff, StepSnapshot};
+pub use tool_calling::{ToolCallRecord, ToolCallingExecutor, ToolCallingOutcome};
+pub use workflow::{AgentRef, AggregateFn, Aggregator, Workflow};
 
 pub struct StepExecutor;
 
 impl StepExecutor {
     pub async fn run_step<A: Agent>(step: Step, agent: &A, ctx: &mut AgentContext) -> StepOutcome {
-        let retry_policy = resolve_retry_policy(&step, &ctx.config.retry_policy);
+        Self::run_step_with_tools(step, agent, ctx, None).await
+    }
+
+    /// Like [`StepExecutor::run_step`], but consults `tools` to validate
+    /// fallbacks before handing them to the agent.
+    pub async fn run_step_with_tools<A: Agent>(
+        step: Step,
+        agent: &A,
+        ctx: &mut AgentContext,
+        tools: Option<&ToolRegistry>,
+    ) -> StepOutcome {
+        if let Some(tool) = &step.tool {
+            if !ctx.tool_permissions.permits(tool) {
+                let mut outcome = StepOutcome::failure(
+                    step.id,
+                    AgentError::Safety(format!("tool {tool} is not permitted for this agent")),
+                );
+                outcome.failure_kind = Some(FailureKind::ToolNotPermitted { tool: tool.clone() });
+                return outcome;
+            }
+        }
+
+        // Reject bad redaction rules before the step gets a chance to act.
+        let redactor = if step.policies.safety.redaction_rules.is_empty() {
+            None
+        } else {
+            match OutputRedactor::from_policy(&step.policies.safety) {
+                Ok(redactor) => Some(redactor),
+                Err(err) => {
+                    return StepOutcome::failure(step.id, AgentError::Validation(err.to_string()))
+                }
+            }
+        };
+
+        let mut retry_policy = resolve_retry_policy(&step, &ctx.config.retry_policy);
+        let mut retry_note = None;
+        // Tools that declare themselves non-idempotent are never retried automatically.
+        if let (Some(tool), Some(registry)) = (&step.tool, tools) {
+            let idempotent = registry
+                .side_effects_for(tool, &step.args)
+                .is_none_or(|effects| effects.idempotent);
+            if !idempotent && retry_policy.max_retries > 0 {
+                retry_policy.max_retries = 0;
+                retry_note = Some(format!("retries disabled: tool {tool} is not idempotent"));
+            }
+        }
         let mut retries = 0usize;
+        let cancellation = ctx.cancellation.clone();
 
-        loop {
-            match agent.act(&step, ctx).await {
+        let mut outcome = loop {
+            match attempt(&step, agent, ctx).await {
                 Ok(mut outcome) => {
                     outcome.retries = retries;
-                    return outcome;
+                    break outcome;
+                }
+                // A cancelled run neither retries nor falls back.
+                Err(AgentError::Cancelled) => {
+                    let mut outcome = StepOutcome::failure(step.id.clone(), AgentError::Cancelled);
+                    outcome.retries = retries;
+                    break outcome;
                 }
                 Err(err) => {
                     if retries < retry_policy.max_retries {
-                        let delay = backoff_delay(&retry_policy, retries);
+                        // Honor the upstream hint rather than guessing with backoff,
+                        // but never wait longer than the policy allows for it.
+                        let delay = match &err {
+                            AgentError::RateLimited { retry_after_ms, .. } => {
+                                Duration::from_millis(*retry_after_ms).min(retry_policy.max_delay())
+                            }
+                            AgentError::ModelRateLimited {
+                                retry_after_ms: Some(retry_after_ms),
+                            } => {
+                                Duration::from_millis(*retry_after_ms).min(retry_policy.max_delay())
+                            }
+                            _ => backoff_delay(&retry_policy, retries),
+                        };
                         retries += 1;
                         if delay > Duration::from_millis(0) {
-                            sleep(delay).await;
+                            let wait = async {
+                                sleep(delay).await;
+                                Ok(())
+                            };
+                            if cancellable(cancellation.as_ref(), wait).await.is_err() {
+                                break StepOutcome::failure(step.id.clone(), AgentError::Cancelled);
+                            }
                         }
                         continue;
                     }
 
-                    return Self::apply_fallback(step.clone(), agent, ctx, err, retries).await;
+                    break Self::apply_fallback(step.clone(), agent, ctx, tools, err, retries)
+                        .await;
                 }
             }
+        };
+
+        if let Some(note) = retry_note {
+            outcome.control_notes.push(note);
+        }
+        // Outputs the agent built without a redacting registry still get masked here.
+        if let Some(redactor) = redactor {
+            let tool = step.tool.as_deref().unwrap_or_default();
+            let report = redactor.redact(tool, &mut outcome.output);
+            outcome.redactions.extend(report);
         }
+        outcome
     }
 
     async fn apply_fallback<A: Agent>(
         step: Step,
         agent: &A,
         ctx: &mut AgentContext,
+        tools: Option<&ToolRegistry>,
         error: AgentError,
         retries: usize,
     ) -> StepOutcome {
@@ -55,6 +203,9 @@ impl StepExecutor {
                     retries,
                     fallback_used: true,
                     control_notes: vec!["fallback: skip".to_string()],
+                    fallback_applied: Some(FallbackKind::Skip),
+                    failure_kind: None,
+                    redactions: Vec::new(),
                 },
                 agent_core::FallbackStrategy::Abort => StepOutcome {
                     step_id: step.id,
@@ -64,25 +215,35 @@ impl StepExecutor {
                     retries,
                     fallback_used: true,
                     control_notes: vec!["fallback: abort".to_string()],
+                    fallback_applied: Some(FallbackKind::Abort),
+                    failure_kind: None,
+                    redactions: Vec::new(),
                 },
                 agent_core::FallbackStrategy::RetryWithLimit {
                     max_additional_retries,
                 } => {
                     let mut total_retries = retries;
-                    for attempt in 0..=*max_additional_retries {
-                        if attempt > 0 {
+                    for retry in 0..=*max_additional_retries {
+                        if retry > 0 {
                             total_retries += 1;
                         }
 
-                        match agent.act(&step, ctx).await {
+                        match attempt(&step, agent, ctx).await {
                             Ok(mut outcome) => {
                                 outcome.retries = total_retries;
                                 outcome.fallback_used = true;
                                 outcome.control_notes.push("fallback: retry".to_string());
+                                outcome.fallback_applied = Some(FallbackKind::RetryWithLimit);
+                                return outcome;
+                            }
+                            Err(AgentError::Cancelled) => {
+                                let mut outcome =
+                                    StepOutcome::failure(step.id.clone(), AgentError::Cancelled);
+                                outcome.retries = total_retries;
                                 return outcome;
                             }
                             Err(err) => {
-                                if attempt == *max_additional_retries {
+                                if retry == *max_additional_retries {
                                     return StepOutcome {
                                         step_id: step.id.clone(),
                                         output: serde_json::json!({"error": err.to_string()}),
@@ -91,6 +252,9 @@ impl StepExecutor {
                                         retries: total_retries,
                                         fallback_used: true,
                                         control_notes: vec!["fallback: retry exhausted".to_string()],
+                                        fallback_applied: Some(FallbackKind::RetryWithLimit),
+                                        failure_kind: None,
+                                        redactions: Vec::new(),
                                     };
                                 }
                             }
@@ -100,10 +264,38 @@ impl StepExecutor {
                     StepOutcome::failure(step.id, error)
                 }
                 agent_core::FallbackStrategy::AlternateTool { tool } => {
+                    if tools.is_some_and(|registry| registry.get(tool).is_none()) {
+                        return StepOutcome {
+                            step_id: step.id,
+                            output: serde_json::json!({
+                                "error": error.to_string(),
+                                "missing_tool": tool,
+                            }),
+                            observations: vec!["alternate tool is not registered".to_string()],
+                            success: false,
+                            retries,
+                            fallback_used: true,
+                            control_notes: vec![format!(
+                                "fallback: alternate tool {tool} is not registered"
+                            )],
+                            fallback_applied: Some(FallbackKind::AlternateTool),
+                            failure_kind: Some(FailureKind::MissingAlternateTool {
+                                tool: tool.clone(),
+                            }),
+                            redactions: Vec::new(),
+                        };
+                    }
+
                     let mut alternate = step.clone();
                     alternate.tool = Some(tool.clone());
-                    let mut outcome = match agent.act(&alternate, ctx).await {
+                    let mut outcome = match attempt(&alternate, agent, ctx).await {
                         Ok(outcome) => outcome,
+                        Err(AgentError::Cancelled) => {
+                            let mut outcome =
+                                StepOutcome::failure(alternate.id, AgentError::Cancelled);
+                            outcome.retries = retries;
+                            return outcome;
+                        }
                         Err(err) => {
                             return StepOutcome {
                                 step_id: alternate.id,
@@ -113,12 +305,16 @@ impl StepExecutor {
                                 retries,
                                 fallback_used: true,
                                 control_notes: vec!["fallback: alternate tool".to_string()],
+                                fallback_applied: Some(FallbackKind::AlternateTool),
+                                failure_kind: None,
+                                redactions: Vec::new(),
                             }
                         }
                     };
 
                     outcome.retries = retries;
                     outcome.fallback_used = true;
+                    outcome.fallback_applied = Some(FallbackKind::AlternateTool);
                     outcome
                         .control_notes
                         .push("fallback: alternate tool".to_string());
@@ -130,10 +326,75 @@ impl StepExecutor {
     }
 }
 
+/// Runs `step` once, bounded by its timeout policy and the run's cancellation.
+async fn attempt<A: Agent>(
+    step: &Step,
+    agent: &A,
+    ctx: &mut AgentContext,
+) -> Result<StepOutcome, AgentError> {
+    let cancellation = ctx.cancellation.clone();
+    let limit = step.policies.timeout();
+    let act = async {
+        match limit {
+            Some(limit) => tokio::time::timeout(limit, agent.act(step, ctx))
+                .await
+                .unwrap_or(Err(AgentError::Timeout)),
+            None => agent.act(step, ctx).await,
+        }
+    };
+    cancellable(cancellation.as_ref(), act).await
+}
+
+/// Awaits `future`, failing with [`AgentError::Timeout`] once `deadline`
+/// passes or with [`AgentError::Cancelled`] once `token` is cancelled.
+async fn within<T>(
+    deadline: Option<Instant>,
+    token: Option<&CancellationToken>,
+    future: impl Future<Output = Result<T, AgentError>>,
+) -> Result<T, AgentError> {
+    let future = cancellable(token, future);
+    match deadline {
+        Some(deadline) => tokio::time::timeout_at(deadline, future)
+            .await
+            .unwrap_or(Err(AgentError::Timeout)),
+        None => future.await,
+    }
+}
+
+/// Awaits `future`, dropping it and failing with [`AgentError::Cancelled`]
+/// once `token` is cancelled.
+async fn cancellable<T>(
+    token: Option<&CancellationToken>,
+    future: impl Future<Output = Result<T, AgentError>>,
+) -> Result<T, AgentError> {
+    match token {
+        Some(token) => tokio::select! {
+            biased;
+            _ = token.cancelled() => Err(AgentError::Cancelled),
+            result = future => result,
+        },
+        None => future.await,
+    }
+}
+
+fn is_cancelled(token: Option<&CancellationToken>) -> bool {
+    token.is_some_and(CancellationToken::is_cancelled)
+}
+
+fn step_fingerprint(step: &Step) -> u64 {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    step.id.hash(&mut hasher);
+    step.description.hash(&mut hasher);
+    step.tool.hash(&mut hasher);
+    step.args.to_string().hash(&mut hasher);
+    hasher.finish()
+}
+
 fn resolve_retry_policy(step: &Step, default_policy: &RetryPolicy) -> RetryPolicy {
     if step.policies.retry.max_retries > 0
         || step.policies.retry.backoff_ms > 0
         || step.policies.retry.jitter
+        || step.policies.retry.max_delay_ms.is_some()
     {
         step.policies.retry.clone()
     } else {
@@ -155,11 +416,77 @@ fn backoff_delay(policy: &RetryPolicy, retry_count: usize) -> Duration {
     }
 }
 
-#[derive(Default)]
 pub struct ControlLoop {
     pub max_iterations: usize,
     pub delay: Duration,
     pub mode: ControlMode,
+    /// Optional broadcast bus that receives [`AgentEvent`]s as the loop runs.
+    pub events: Option<EventBus>,
+    /// Maximum nesting of `Step::sub_plan` before the run is rejected.
+    pub max_plan_depth: usize,
+    /// What to do with steps sharing an id in a plan without dependencies;
+    /// such plans are rejected by default.
+    pub dedup: DedupStrategy,
+    /// Stop once the agent produces this many identical steps in a row.
+    /// Disabled when `None`; mainly useful in [`ControlMode::Reactive`].
+    pub loop_detection: Option<usize>,
+    /// Registry used to validate tool fallbacks before they run.
+    pub tools: Option<Arc<ToolRegistry>>,
+    /// Checked before every iteration; the loop waits while it is paused.
+    pub pause: Option<PauseHandle>,
+    /// Ledger the agent's models report into; read around each step and the
+    /// whole run to fill in the report's usage. Give each concurrently
+    /// running loop its own tracker, or their usage will mix.
+    pub usage: Option<UsageTracker>,
+    /// Steps run at once when a plan declares `depends_on`; such plans go
+    /// through a [`DagExecutor`] instead of step by step.
+    pub max_concurrency: usize,
+    /// Wall-clock budget for the whole run. A step still running when it
+    /// expires is abandoned with [`AgentError::Timeout`] and the run stops
+    /// with [`StopReason::DeadlineExceeded`]; planning that overruns it fails
+    /// the run with the same error.
+    pub deadline: Option<Duration>,
+    /// Receives a [`RunCheckpoint`] after every finished step, so the run can
+    /// be picked up with [`ControlLoop::resume`] after a crash. A dependency
+    /// graph is checkpointed once, when all its steps are done.
+    pub checkpoints: Option<CheckpointStore>,
+    /// Asked about steps whose safety policy `requires_review` before they
+    /// run. Such steps fail when this is unset.
+    pub intervention: Option<Arc<dyn InterventionHandler>>,
+    /// Token, cost and time limits; the run stops with
+    /// [`StopReason::BudgetExceeded`] once one is reached.
+    pub budget: Option<RunBudget>,
+    /// Called around planning, each step and the end of the run.
+    pub hooks: Vec<Arc<dyn RunHook>>,
+    /// Runs the agents that steps with a `delegate` hand over to. Such steps
+    /// fail when this is unset.
+    pub delegates: Option<Arc<dyn Delegator>>,
+}
+
+pub const DEFAULT_MAX_PLAN_DEPTH: usize = 8;
+
+impl Default for ControlLoop {
+    fn default() -> Self {
+        Self {
+            max_iterations: 0,
+            delay: Duration::default(),
+            mode: ControlMode::default(),
+            events: None,
+            max_plan_depth: DEFAULT_MAX_PLAN_DEPTH,
+            dedup: DedupStrategy::default(),
+            loop_detection: None,
+            tools: None,
+            pause: None,
+            usage: None,
+            max_concurrency: DEFAULT_MAX_CONCURRENCY,
+            deadline: None,
+            checkpoints: None,
+            intervention: None,
+            budget: None,
+            hooks: Vec::new(),
+            delegates: None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
@@ -171,25 +498,356 @@ pub enum ControlMode {
     ReflectionEnabled,
 }
 
+/// Everything captured from a single control loop run.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RunReport {
+    /// Most recent plan produced by the agent during the run.
+    pub plan: Option<Plan>,
+    pub outcomes: Vec<StepOutcome>,
+    pub usage: UsageMetrics,
+    pub tool_calls: Vec<ToolCallInfo>,
+    pub stop_reason: StopReason,
+    /// Fullest context window observed during the run, when the loop has a
+    /// [`UsageTracker`] that model calls report their context usage to.
+    #[serde(default)]
+    pub context_usage: Option<ContextUsage>,
+    /// Spend of the responses recorded with
+    /// [`record_response`](Self::record_response), for those whose model has
+    /// a registered price.
+    #[serde(default)]
+    pub estimated_cost_usd: f64,
+    /// Model usage of each step, by step id, when the loop has a
+    /// [`UsageTracker`]. Planning calls count towards the run's `usage` only.
+    #[serde(default)]
+    pub step_usage: BTreeMap<String, UsageTotals>,
+}
+
+/// Why a control loop run ended.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StopReason {
+    /// The agent ran out of steps to execute.
+    #[default]
+    Completed,
+    /// The loop hit `max_iterations` with work still pending.
+    MaxIterations,
+    /// The agent kept producing the same step; see [`ControlLoop::loop_detection`].
+    LoopDetected { step_id: String, repeats: usize },
+    /// The run outlasted [`ControlLoop::deadline`].
+    DeadlineExceeded,
+    /// The run's cancellation token fired; see
+    /// [`ControlLoop::run_with_cancellation`].
+    Cancelled,
+    /// The [`InterventionHandler`] aborted the run at this step.
+    Aborted { step_id: String, reason: String },
+    /// The run reached a limit of its [`ControlLoop::budget`].
+    BudgetExceeded { limit: BudgetLimit },
+}
+
+impl RunReport {
+    pub fn record_usage(&mut self, usage: &UsageMetrics) {
+        self.usage.prompt_tokens += usage.prompt_tokens;
+        self.usage.completion_tokens += usage.completion_tokens;
+    }
+
+    /// Records `response`'s usage and adds its estimated cost.
+    pub fn record_response(&mut self, response: &LLMResponse) {
+        self.record_usage(&response.usage);
+        self.estimated_cost_usd += response.estimated_cost_usd().unwrap_or_default();
+    }
+
+    /// Adds the usage of model calls reported to a [`UsageTracker`],
+    /// including the context window they filled.
+    pub fn record_totals(&mut self, totals: &UsageTotals) {
+        self.record_usage(&totals.usage());
+        self.estimated_cost_usd += totals.cost_usd;
+        if let Some(usage) = totals.context_usage {
+            self.record_context_usage(usage);
+        }
+    }
+
+    /// Keeps the measurement with the highest token count.
+    pub fn record_context_usage(&mut self, usage: ContextUsage) {
+        if self
+            .context_usage
+            .is_none_or(|current| usage.used >= current.used)
+        {
+            self.context_usage = Some(usage);
+        }
+    }
+
+    pub fn snapshot(&self) -> RunSnapshot {
+        RunSnapshot::from(self)
+    }
+}
+
 impl ControlLoop {
-    #[instrument(skip_all)]
+    pub fn with_events(mut self, events: EventBus) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    /// Also sends the run's [`AgentEvent`]s to `channel`, adding an
+    /// [`EventBus`] if the loop has none. Events that find the channel full
+    /// are dropped; see [`EventBus`].
+    pub fn with_event_channel(mut self, channel: mpsc::Sender<AgentEvent>) -> Self {
+        self.events = Some(self.events.take().unwrap_or_default().with_channel(channel));
+        self
+    }
+
+    pub fn with_tools(mut self, tools: Arc<ToolRegistry>) -> Self {
+        self.tools = Some(tools);
+        self
+    }
+
+    pub fn with_pause(mut self, pause: PauseHandle) -> Self {
+        self.pause = Some(pause);
+        self
+    }
+
+    pub fn with_usage_tracker(mut self, tracker: UsageTracker) -> Self {
+        self.usage = Some(tracker);
+        self
+    }
+
+    pub fn with_max_concurrency(mut self, max_concurrency: usize) -> Self {
+        self.max_concurrency = max_concurrency;
+        self
+    }
+
+    pub fn with_deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    pub fn with_checkpoints(mut self, checkpoints: CheckpointStore) -> Self {
+        self.checkpoints = Some(checkpoints);
+        self
+    }
+
+    pub fn with_intervention(mut self, handler: Arc<dyn InterventionHandler>) -> Self {
+        self.intervention = Some(handler);
+        self
+    }
+
+    pub fn with_budget(mut self, budget: RunBudget) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    pub fn with_hook(mut self, hook: Arc<dyn RunHook>) -> Self {
+        self.hooks.push(hook);
+        self
+    }
+
+    pub fn with_delegates(mut self, delegates: Arc<dyn Delegator>) -> Self {
+        self.delegates = Some(delegates);
+        self
+    }
+
+    fn usage_totals(&self) -> UsageTotals {
+        self.usage
+            .as_ref()
+            .map(UsageTracker::totals)
+            .unwrap_or_default()
+    }
+
+    fn emit(&self, event: AgentEvent) {
+        if let Some(events) = &self.events {
+            events.publish(event);
+        }
+    }
+
+    pub fn with_dedup_strategy(mut self, dedup: DedupStrategy) -> Self {
+        self.dedup = dedup;
+        self
+    }
+
     pub async fn run<A: Agent>(
         &self,
         agent: &A,
         ctx: &mut AgentContext,
     ) -> Result<Vec<StepOutcome>, AgentError> {
+        Ok(self.run_with_report(agent, ctx).await?.outcomes)
+    }
+
+    #[instrument(skip_all)]
+    pub async fn run_with_report<A: Agent>(
+        &self,
+        agent: &A,
+        ctx: &mut AgentContext,
+    ) -> Result<RunReport, AgentError> {
+        self.run_from(agent, ctx, None).await
+    }
+
+    /// Carries on a run from `checkpoint`, typically loaded from a
+    /// [`CheckpointStore`] after a crash or redeploy.
+    ///
+    /// The agent is initialized again, then `ctx.state` is replaced by the
+    /// checkpoint's and steps continue after the last one it recorded,
+    /// without planning again. The report starts with the checkpoint's
+    /// outcomes and tool calls; usage only covers the resumed part.
+    pub async fn resume<A: Agent>(
+        &self,
+        checkpoint: RunCheckpoint,
+        agent: &A,
+        ctx: &mut AgentContext,
+    ) -> Result<RunReport, AgentError> {
+        self.run_from(agent, ctx, Some(checkpoint)).await
+    }
+
+    async fn run_from<A: Agent>(
+        &self,
+        agent: &A,
+        ctx: &mut AgentContext,
+        checkpoint: Option<RunCheckpoint>,
+    ) -> Result<RunReport, AgentError> {
+        let result = self.run_hooked(agent, ctx, checkpoint).await;
+        match &result {
+            Ok(report) => {
+                for hook in &self.hooks {
+                    hook.on_finish(report, ctx).await;
+                }
+            }
+            Err(error) => {
+                for hook in &self.hooks {
+                    hook.on_error(error, ctx).await;
+                }
+            }
+        }
+        result
+    }
+
+    async fn run_hooked<A: Agent>(
+        &self,
+        agent: &A,
+        ctx: &mut AgentContext,
+        checkpoint: Option<RunCheckpoint>,
+    ) -> Result<RunReport, AgentError> {
         agent.initialize(ctx).await?;
+        let run_start = self.usage_totals();
+        self.emit(AgentEvent::RunStarted {
+            agent: ctx.config.name.clone(),
+        });
+        let mut report = RunReport::default();
+        match self.drive(agent, ctx, &mut report, checkpoint).await {
+            Err(AgentError::Cancelled) => report.stop_reason = StopReason::Cancelled,
+            result => result?,
+        }
+
+        // Reflecting calls the model again, which a cancelled run must not.
+        if report.stop_reason != StopReason::Cancelled {
+            if matches!(self.mode, ControlMode::ReflectionEnabled) {
+                agent.reflect(ctx).await?;
+            }
+
+            if !matches!(self.mode, ControlMode::ReflectionEnabled) {
+                agent.reflect(ctx).await?;
+            }
+        }
+        if self.usage.is_some() {
+            report.record_totals(&self.usage_totals().since(&run_start));
+        }
+        self.emit(AgentEvent::RunFinished {
+            steps: report.outcomes.len(),
+        });
+        Ok(report)
+    }
+
+    /// Like [`ControlLoop::run_with_report`], but stops once `token` is
+    /// cancelled. The token is handed to the agent through
+    /// [`AgentContext::cancellation`] and aborts the planning call or step in
+    /// flight; the report keeps the outcomes gathered so far and ends with
+    /// [`StopReason::Cancelled`].
+    pub async fn run_with_cancellation<A: Agent>(
+        &self,
+        agent: &A,
+        ctx: &mut AgentContext,
+        token: CancellationToken,
+    ) -> Result<RunReport, AgentError> {
+        let previous = ctx.cancellation.replace(token);
+        let result = self.run_with_report(agent, ctx).await;
+        ctx.cancellation = previous;
+        result
+    }
+
+    /// Plans and executes steps into `report`, up to the final reflection.
+    async fn drive<A: Agent>(
+        &self,
+        agent: &A,
+        ctx: &mut AgentContext,
+        report: &mut RunReport,
+        checkpoint: Option<RunCheckpoint>,
+    ) -> Result<(), AgentError> {
+        let cancellation = ctx.cancellation.clone();
+        let deadline = self.deadline.map(|limit| Instant::now() + limit);
+        let meter = self
+            .budget
+            .clone()
+            .map(|budget| BudgetMeter::start(budget, self.usage.clone()));
         let mut executable: Option<ExecutablePlan> = None;
-        if matches!(
+        let mut graph: Option<Plan> = None;
+        let mut first_iteration = 0;
+        if let Some(checkpoint) = checkpoint {
+            ctx.state = checkpoint.state;
+            report.plan = checkpoint.plan;
+            report.outcomes = checkpoint.outcomes;
+            report.tool_calls = checkpoint.tool_calls;
+            executable = checkpoint.executable;
+            first_iteration = checkpoint.iteration;
+        } else if matches!(
             self.mode,
             ControlMode::Deterministic | ControlMode::ReflectionEnabled
         ) {
-            let plan: Plan = agent.think(ctx).await?;
-            executable = Some(plan.executable());
+            let plan: Plan = within(deadline, cancellation.as_ref(), agent.think(ctx)).await?;
+            if plan.has_dependencies() {
+                self.plan_created(&plan, ctx).await?;
+                report.plan = Some(plan.clone());
+                graph = Some(plan);
+            } else {
+                let plan = plan.try_executable(self.dedup)?;
+                self.plan_created(&plan.plan, ctx).await?;
+                report.plan = Some(plan.plan.clone());
+                executable = Some(plan);
+            }
+        }
+
+        let mut last_fingerprint: Option<u64> = None;
+        let mut repeats = 0usize;
+        report.stop_reason = StopReason::MaxIterations;
+
+        // A dependency graph runs in one go; its steps count as iterations.
+        let mut iterations = self.max_iterations;
+        if let Some(plan) = &graph {
+            self.execute_graph(plan, agent, ctx, report, deadline, meter.as_ref())
+                .await?;
+            self.checkpoint(None, ctx, report, report.outcomes.len())?;
+            iterations = 0;
         }
-        let mut results = Vec::new();
 
-        for iteration in 0..self.max_iterations {
+        for iteration in first_iteration..iterations {
+            if let Some(pause) = &self.pause {
+                let paused = async {
+                    pause.wait_while_paused().await;
+                    Ok(())
+                };
+                cancellable(cancellation.as_ref(), paused).await?;
+            }
+            if is_cancelled(cancellation.as_ref()) {
+                report.stop_reason = StopReason::Cancelled;
+                break;
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                report.stop_reason = StopReason::DeadlineExceeded;
+                break;
+            }
+            if let Some(limit) = meter.as_ref().and_then(BudgetMeter::exceeded) {
+                tracing::warn!(%limit, "run budget exceeded");
+                if let Some(last) = report.outcomes.last_mut() {
+                    last.control_notes.push(format!("budget exceeded: {limit}"));
+                }
+                report.stop_reason = StopReason::BudgetExceeded { limit };
+                break;
+            }
             ctx.state.iteration = iteration;
 
             let next_step = match self.mode {
@@ -197,30 +855,92 @@ impl ControlLoop {
                     executable.as_mut().and_then(|plan| plan.next())
                 }
                 ControlMode::Reactive => {
-                    let plan: Plan = agent.think(ctx).await?;
-                    let mut plan_exec = plan.executable();
+                    let plan: Plan =
+                        within(deadline, cancellation.as_ref(), agent.think(ctx)).await?;
+                    let mut plan_exec = plan.try_executable(self.dedup)?;
+                    self.plan_created(&plan_exec.plan, ctx).await?;
+                    report.plan = Some(plan_exec.plan.clone());
                     plan_exec.next()
                 }
                 ControlMode::Procedural => {
                     if let Some(step) = executable.as_mut().and_then(|plan| plan.next()) {
                         Some(step)
                     } else {
-                        let plan: Plan = agent.think(ctx).await?;
-                        executable = Some(plan.executable());
+                        let plan: Plan =
+                            within(deadline, cancellation.as_ref(), agent.think(ctx)).await?;
+                        let plan = plan.try_executable(self.dedup)?;
+                        self.plan_created(&plan.plan, ctx).await?;
+                        report.plan = Some(plan.plan.clone());
+                        executable = Some(plan);
                         executable.as_mut().and_then(|plan| plan.next())
                     }
                 }
             };
 
             if let Some(step) = next_step {
-                let outcome = StepExecutor::run_step(step.clone(), agent, ctx).await;
-                agent.observe(&outcome, ctx).await?;
-                results.push(outcome);
+                if let Some(threshold) = self.loop_detection {
+                    let fingerprint = step_fingerprint(&step);
+                    repeats = if last_fingerprint == Some(fingerprint) {
+                        repeats + 1
+                    } else {
+                        1
+                    };
+                    last_fingerprint = Some(fingerprint);
+                    if repeats >= threshold.max(1) {
+                        tracing::warn!(step = %step.id, repeats, "reactive loop detected");
+                        if let Some(last) = report.outcomes.last_mut() {
+                            last.control_notes.push(format!(
+                                "loop detected: step {} produced {repeats} times in a row",
+                                step.id
+                            ));
+                        }
+                        report.stop_reason = StopReason::LoopDetected {
+                            step_id: step.id,
+                            repeats,
+                        };
+                        break;
+                    }
+                }
+
+                let step_id = step.id.clone();
+                let execution = self.execute_step(step, agent, ctx, report, iteration, 0);
+                let finished = match deadline {
+                    Some(deadline) => tokio::time::timeout_at(deadline, execution).await.ok(),
+                    None => Some(execution.await),
+                };
+                match finished {
+                    Some(result) => result?,
+                    None => {
+                        let outcome = StepOutcome::failure(step_id, AgentError::Timeout);
+                        self.emit(AgentEvent::StepCompleted {
+                            iteration,
+                            outcome: outcome.clone(),
+                        });
+                        report.outcomes.push(outcome);
+                        report.stop_reason = StopReason::DeadlineExceeded;
+                        break;
+                    }
+                }
+                if is_cancelled(cancellation.as_ref()) {
+                    report.stop_reason = StopReason::Cancelled;
+                    break;
+                }
+                if let Some(outcome) = report.outcomes.last() {
+                    if let Some(reason) = intervention::abort_reason(outcome) {
+                        report.stop_reason = StopReason::Aborted {
+                            step_id: outcome.step_id.clone(),
+                            reason: reason.to_string(),
+                        };
+                        break;
+                    }
+                }
 
                 if matches!(self.mode, ControlMode::ReflectionEnabled) {
                     agent.reflect(ctx).await?;
                 }
+                self.checkpoint(executable.as_ref(), ctx, report, iteration + 1)?;
             } else {
+                report.stop_reason = StopReason::Completed;
                 break;
             }
             if self.delay > Duration::from_millis(0) {
@@ -228,52 +948,471 @@ impl ControlLoop {
             }
         }
 
-        if matches!(self.mode, ControlMode::ReflectionEnabled) {
-            agent.reflect(ctx).await?;
+        let plan_exhausted = executable
+            .as_ref()
+            .is_some_and(|plan| plan.current >= plan.plan.steps.len());
+        if report.stop_reason == StopReason::MaxIterations
+            && plan_exhausted
+            && matches!(
+                self.mode,
+                ControlMode::Deterministic | ControlMode::ReflectionEnabled
+            )
+        {
+            report.stop_reason = StopReason::Completed;
         }
+        Ok(())
+    }
 
-        if !matches!(self.mode, ControlMode::ReflectionEnabled) {
-            agent.reflect(ctx).await?;
+    async fn plan_created(&self, plan: &Plan, ctx: &AgentContext) -> Result<(), AgentError> {
+        self.emit(AgentEvent::PlanCreated { plan: plan.clone() });
+        for hook in &self.hooks {
+            hook.on_plan(plan, ctx).await?;
         }
-        Ok(results)
+        Ok(())
     }
-}
 
-#[async_trait]
-pub trait MessageBus {
-    async fn send(&self, recipient: &str, message: serde_json::Value) -> Result<(), AgentError>;
-    async fn recv(&self, recipient: &str) -> Result<Option<serde_json::Value>, AgentError>;
+    /// Saves where the run has got to, when the loop has a [`CheckpointStore`].
+    fn checkpoint(
+        &self,
+        executable: Option<&ExecutablePlan>,
+        ctx: &AgentContext,
+        report: &RunReport,
+        iteration: usize,
+    ) -> Result<(), AgentError> {
+        let Some(checkpoints) = &self.checkpoints else {
+            return Ok(());
+        };
+        checkpoints.save(&RunCheckpoint {
+            plan: report.plan.clone(),
+            executable: executable.cloned(),
+            state: ctx.state.clone(),
+            outcomes: report.outcomes.clone(),
+            tool_calls: report.tool_calls.clone(),
+            iteration,
+        })
+    }
+
+    /// Runs a plan with dependencies through a [`DagExecutor`], counting
+    /// each step against `max_iterations`.
+    async fn execute_graph<A: Agent>(
+        &self,
+        plan: &Plan,
+        agent: &A,
+        ctx: &mut AgentContext,
+        report: &mut RunReport,
+        deadline: Option<Instant>,
+        meter: Option<&BudgetMeter>,
+    ) -> Result<(), AgentError> {
+        let executor = DagExecutor {
+            max_concurrency: self.max_concurrency,
+            max_steps: Some(self.max_iterations),
+            tools: self.tools.clone(),
+            events: self.events.clone(),
+            pause: self.pause.clone(),
+            deadline,
+            intervention: self.intervention.clone(),
+            budget: meter.map(BudgetMeter::remaining),
+            usage: self.usage.clone(),
+            hooks: self.hooks.clone(),
+            delegates: self.delegates.clone(),
+        };
+        let cancellation = ctx.cancellation.clone();
+        let outcomes = executor.run(plan, agent, ctx).await?;
+        for outcome in &outcomes {
+            let step = plan.steps.iter().find(|step| step.id == outcome.step_id);
+            if let Some((tool, step)) = step.and_then(|step| Some((step.tool.clone()?, step))) {
+                report.tool_calls.push(ToolCallInfo {
+                    name: tool,
+                    arguments: step.args.clone(),
+                    id: None,
+                });
+            }
+        }
+        let aborted = outcomes.iter().find_map(|outcome| {
+            intervention::abort_reason(outcome).map(|reason| (outcome.step_id.clone(), reason))
+        });
+        if is_cancelled(cancellation.as_ref()) {
+            report.stop_reason = StopReason::Cancelled;
+        } else if let Some((step_id, reason)) = aborted {
+            report.stop_reason = StopReason::Aborted {
+                step_id,
+                reason: reason.to_string(),
+            };
+        } else if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            report.stop_reason = StopReason::DeadlineExceeded;
+        } else if outcomes.len() == plan.steps.len() {
+            report.stop_reason = StopReason::Completed;
+        } else if let Some(limit) = meter.and_then(BudgetMeter::exceeded) {
+            report.stop_reason = StopReason::BudgetExceeded { limit };
+        }
+        report.outcomes.extend(outcomes);
+        Ok(())
+    }
+
+    /// Runs a single step, expanding any `sub_plan` or `for_each`
+    /// recursively up to `max_plan_depth` levels deep.
+    fn execute_step<'a, A: Agent>(
+        &'a self,
+        step: Step,
+        agent: &'a A,
+        ctx: &'a mut AgentContext,
+        report: &'a mut RunReport,
+        iteration: usize,
+        depth: usize,
+    ) -> Pin<Box<dyn Future<Output = Result<(), AgentError>> + Send + 'a>> {
+        Box::pin(async move {
+            if let Some(sub_plan) = &step.sub_plan {
+                if depth >= self.max_plan_depth {
+                    return Err(AgentError::Validation(format!(
+                        "step {} exceeds the maximum plan depth of {}",
+                        step.id, self.max_plan_depth
+                    )));
+                }
+                for sub_step in sub_plan.steps.iter().cloned() {
+                    self.execute_step(sub_step, agent, ctx, report, iteration, depth + 1)
+                        .await?;
+                    if report
+                        .outcomes
+                        .last()
+                        .is_some_and(|outcome| intervention::abort_reason(outcome).is_some())
+                    {
+                        break;
+                    }
+                }
+                return Ok(());
+            }
+            if let Some(for_each) = &step.for_each {
+                if depth >= self.max_plan_depth {
+                    return Err(AgentError::Validation(format!(
+                        "step {} exceeds the maximum plan depth of {}",
+                        step.id, self.max_plan_depth
+                    )));
+                }
+                return Box::pin(
+                    self.execute_for_each(&step, for_each, agent, ctx, report, iteration, depth),
+                )
+                .await;
+            }
+            // Boxed so each level of sub-plan recursion stays small.
+            Box::pin(self.execute_leaf(step, agent, ctx, report, iteration)).await
+        })
+    }
+
+    /// Runs the items of a `for_each` step, then records the step's own
+    /// outcome. Items run in order as steps of their own, or through a
+    /// [`DagExecutor`] when `max_concurrency` allows more than one at a time.
+    #[allow(clippy::too_many_arguments)]
+    async fn execute_for_each<A: Agent>(
+        &self,
+        step: &Step,
+        for_each: &ForEach,
+        agent: &A,
+        ctx: &mut AgentContext,
+        report: &mut RunReport,
+        iteration: usize,
+        depth: usize,
+    ) -> Result<(), AgentError> {
+        let scope = condition_scope(&report.outcomes, ctx);
+        let items = match foreach::expand(step, for_each, &scope) {
+            Ok(items) => items,
+            Err(error) => {
+                let outcome = StepOutcome::failure(step.id.clone(), error);
+                self.emit(AgentEvent::StepCompleted {
+                    iteration,
+                    outcome: outcome.clone(),
+                });
+                report.outcomes.push(outcome);
+                return Ok(());
+            }
+        };
+
+        let mut results: Vec<Option<StepOutcome>> = vec![None; items.len()];
+        let mut concurrent = Vec::new();
+        let limit = for_each.max_concurrency.unwrap_or(1);
+        for (index, item) in items.into_iter().enumerate() {
+            let item = match item {
+                foreach::Item::Run(item) => *item,
+                foreach::Item::Done(outcome) => {
+                    self.emit(AgentEvent::StepCompleted {
+                        iteration,
+                        outcome: outcome.clone(),
+                    });
+                    report.outcomes.push(outcome.clone());
+                    results[index] = Some(outcome);
+                    continue;
+                }
+            };
+            if limit > 1 {
+                concurrent.push((index, item));
+                continue;
+            }
+            self.execute_step(item, agent, ctx, report, iteration, depth + 1)
+                .await?;
+            results[index] = report.outcomes.last().cloned();
+            if results[index]
+                .as_ref()
+                .is_some_and(|outcome| intervention::abort_reason(outcome).is_some())
+            {
+                break;
+            }
+        }
+
+        if !concurrent.is_empty() {
+            let indexes: HashMap<String, usize> = concurrent
+                .iter()
+                .map(|(index, item)| (item.id.clone(), *index))
+                .collect();
+            let plan = Plan {
+                goal: step.description.clone(),
+                steps: concurrent.into_iter().map(|(_, item)| item).collect(),
+                metadata: serde_json::json!({}),
+            };
+            let executor = DagExecutor {
+                max_concurrency: limit,
+                max_steps: None,
+                tools: self.tools.clone(),
+                events: self.events.clone(),
+                pause: self.pause.clone(),
+                deadline: None,
+                intervention: self.intervention.clone(),
+                budget: None,
+                usage: self.usage.clone(),
+                hooks: self.hooks.clone(),
+                delegates: self.delegates.clone(),
+            };
+            for outcome in executor.run(&plan, agent, ctx).await? {
+                let item = plan.steps.iter().find(|item| item.id == outcome.step_id);
+                if let Some((tool, item)) = item.and_then(|item| Some((item.tool.clone()?, item))) {
+                    report.tool_calls.push(ToolCallInfo {
+                        name: tool,
+                        arguments: item.args.clone(),
+                        id: None,
+                    });
+                }
+                if let Some(&index) = indexes.get(&outcome.step_id) {
+                    results[index] = Some(outcome.clone());
+                }
+                report.outcomes.push(outcome);
+            }
+        }
+
+        let outcome = foreach::aggregate(step.id.clone(), results);
+        self.emit(AgentEvent::StepCompleted {
+            iteration,
+            outcome: outcome.clone(),
+        });
+        report.outcomes.push(outcome);
+        Ok(())
+    }
+
+    /// Runs a step without a `sub_plan`, after review and the `before_step`
+    /// hooks; a `delegate` step runs on its [`Delegator`] instead of `agent`.
+    async fn execute_leaf<A: Agent>(
+        &self,
+        step: Step,
+        agent: &A,
+        ctx: &mut AgentContext,
+        report: &mut RunReport,
+        iteration: usize,
+    ) -> Result<(), AgentError> {
+        if let Some(outcome) = condition::check(&step, &report.outcomes, ctx) {
+            self.emit(AgentEvent::StepCompleted {
+                iteration,
+                outcome: outcome.clone(),
+            });
+            report.outcomes.push(outcome);
+            return Ok(());
+        }
+        let reviewed = intervention::review(self.intervention.as_deref(), step, ctx).await;
+        let (mut step, note) = match reviewed {
+            intervention::Reviewed::Run { step, note } => (*step, note),
+            intervention::Reviewed::Done(outcome) => {
+                self.emit(AgentEvent::StepCompleted {
+                    iteration,
+                    outcome: outcome.clone(),
+                });
+                report.outcomes.push(outcome);
+                return Ok(());
+            }
+        };
+        if let Some(outcome) = hooks::before_step(&self.hooks, &mut step, ctx).await {
+            self.emit(AgentEvent::StepCompleted {
+                iteration,
+                outcome: outcome.clone(),
+            });
+            report.outcomes.push(outcome);
+            return Ok(());
+        }
+
+        self.emit(AgentEvent::StepStarted {
+            iteration,
+            step_id: step.id.clone(),
+        });
+        if let Some(tool) = &step.tool {
+            let call = ToolCallInfo {
+                name: tool.clone(),
+                arguments: step.args.clone(),
+                id: None,
+            };
+            self.emit(AgentEvent::ToolCalled {
+                iteration,
+                step_id: step.id.clone(),
+                call: call.clone(),
+            });
+            report.tool_calls.push(call);
+        }
+        let step_start = self.usage_totals();
+        let mut outcome = match &step.delegate {
+            Some(delegate) => delegate::run(self.delegates.as_deref(), &step, delegate, ctx).await,
+            None => {
+                StepExecutor::run_step_with_tools(step, agent, ctx, self.tools.as_deref()).await
+            }
+        };
+        outcome.control_notes.extend(note);
+        hooks::after_step(&self.hooks, &mut outcome, ctx).await?;
+        if self.usage.is_some() {
+            report
+                .step_usage
+                .entry(outcome.step_id.clone())
+                .or_default()
+                .add(&self.usage_totals().since(&step_start));
+        }
+        agent.observe(&outcome, ctx).await?;
+        self.emit(AgentEvent::StepCompleted {
+            iteration,
+            outcome: outcome.clone(),
+        });
+        report.outcomes.push(outcome);
+        Ok(())
+    }
 }
 
-pub struct InMemoryBus {
-    messages: tokio::sync::Mutex<Vec<(String, serde_json::Value)>>,
+/// Agent that asks an [`LLMModel`] to produce its plan.
+///
+/// The planning prompt embeds any few-shot `examples` (goal → plan pairs)
+/// ahead of the actual goal, and the model is asked for a JSON-encoded
+/// [`Plan`] through [`StructuredGeneration::generate_structured`], which
+/// sends the plan schema and retries replies that fail to parse.
+///
+/// `generation` applies to every call; a step can override it for its own
+/// call with a `"generation"` object in its args.
+///
+/// With `events` set, steps stream the model's reply and publish each text
+/// delta as an [`AgentEvent::TokenDelta`]; hand it the loop's bus.
+pub struct ModelPlannerAgent<M: LLMModel> {
+    pub model: M,
+    pub examples: Vec<(String, Plan)>,
+    pub generation: GenerationOptions,
+    pub events: Option<EventBus>,
 }
 
-impl InMemoryBus {
-    pub fn new() -> Self {
+impl<M: LLMModel> ModelPlannerAgent<M> {
+    pub fn new(model: M) -> Self {
         Self {
-            messages: tokio::sync::Mutex::new(Vec::new()),
+            model,
+            examples: Vec::new(),
+            generation: GenerationOptions::default(),
+            events: None,
+        }
+    }
+
+    pub fn with_events(mut self, events: EventBus) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    pub fn with_generation_options(mut self, generation: GenerationOptions) -> Self {
+        self.generation = generation;
+        self
+    }
+
+    pub fn with_examples(mut self, examples: Vec<(String, Plan)>) -> Self {
+        self.examples = examples;
+        self
+    }
+
+    pub fn add_example<T: Into<String>>(&mut self, goal: T, plan: Plan) {
+        self.examples.push((goal.into(), plan));
+    }
+
+    pub fn planning_prompt(&self, goal: &str) -> String {
+        let mut prompt =
+            String::from("Produce an execution plan as JSON matching the examples below.\n\n");
+        for (example_goal, example_plan) in &self.examples {
+            let rendered = serde_json::to_string(example_plan).unwrap_or_default();
+            prompt.push_str(&format!("Goal: {example_goal}\nPlan: {rendered}\n\n"));
         }
+        prompt.push_str(&format!("Goal: {goal}\nPlan:"));
+        prompt
+    }
+
+    fn goal(ctx: &AgentContext) -> String {
+        ctx.metadata
+            .get("goal")
+            .and_then(|v| v.as_str())
+            .map(ToOwned::to_owned)
+            .or_else(|| ctx.config.description.clone())
+            .unwrap_or_else(|| ctx.config.name.clone())
+    }
+}
+
+impl<M: LLMModel> std::fmt::Debug for ModelPlannerAgent<M> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ModelPlannerAgent")
+            .field("examples", &self.examples.len())
+            .finish()
     }
 }
 
 #[async_trait]
-impl MessageBus for InMemoryBus {
-    async fn send(&self, recipient: &str, message: serde_json::Value) -> Result<(), AgentError> {
-        self.messages
-            .lock()
+impl<M: LLMModel> Agent for ModelPlannerAgent<M> {
+    async fn plan(&self, ctx: &AgentContext) -> Result<Plan, AgentError> {
+        let prompt = self.planning_prompt(&Self::goal(ctx));
+        self.model
+            .generate_structured::<Plan>(&prompt, &self.generation)
             .await
-            .push((recipient.to_string(), message));
-        Ok(())
+            .map_err(|e| match e {
+                StructuredOutputError::Model(error) => error.into(),
+                invalid => {
+                    AgentError::Planning(format!("model returned an invalid plan: {invalid}"))
+                }
+            })
     }
 
-    async fn recv(&self, recipient: &str) -> Result<Option<serde_json::Value>, AgentError> {
-        let mut messages = self.messages.lock().await;
-        if let Some(pos) = messages.iter().position(|(r, _)| r == recipient) {
-            Ok(Some(messages.remove(pos).1))
-        } else {
-            Ok(None)
-        }
+    async fn execute_step(
+        &self,
+        step: &Step,
+        _ctx: &mut AgentContext,
+    ) -> Result<StepOutcome, AgentError> {
+        let generation = match step.args.get("generation") {
+            Some(options) => serde_json::from_value(options.clone())
+                .map_err(|e| AgentError::Validation(format!("invalid generation options: {e}")))?,
+            None => self.generation.clone(),
+        };
+        let prompt = format!("{}\n{}", step.description, step.args);
+        let content = match &self.events {
+            Some(events) => {
+                use futures::StreamExt;
+
+                let mut stream = self.model.stream(&prompt, &generation).await?;
+                let mut content = String::new();
+                while let Some(event) = stream.next().await {
+                    if let StreamEvent::TextDelta { text } = event {
+                        content.push_str(&text);
+                        events.publish(AgentEvent::TokenDelta {
+                            step_id: step.id.clone(),
+                            text,
+                        });
+                    }
+                }
+                content
+            }
+            None => self.model.generate(&prompt, &generation).await?.content,
+        };
+        Ok(StepOutcome::success(
+            step.id.clone(),
+            serde_json::json!({"message": content}),
+        ))
     }
 }
 
@@ -285,7 +1424,14 @@ pub enum MemoryTopology {
 pub struct MultiAgentOrchestrator<B: MessageBus> {
     bus: Arc<B>,
     memory_topology: MemoryTopology,
-    agents: HashMap<String, AgentContext>,
+    agents: HashMap<String, RegisteredAgent>,
+}
+
+struct RegisteredAgent {
+    ctx: AgentContext,
+    allowed_tools: Option<Vec<String>>,
+    /// The agent and loop `delegate` steps naming this agent run with.
+    runner: Option<(Arc<dyn Agent>, Arc<ControlLoop>)>,
 }
 
 impl<B: MessageBus> MultiAgentOrchestrator<B> {
@@ -298,7 +1444,49 @@ impl<B: MessageBus> MultiAgentOrchestrator<B> {
     }
 
     pub fn register_agent<T: Into<String>>(&mut self, name: T, ctx: AgentContext) {
-        self.agents.insert(name.into(), ctx);
+        self.register_agent_with_tools(name, ctx, None);
+    }
+
+    /// Registers an agent that may only use `allowed_tools` (all tools when `None`).
+    pub fn register_agent_with_tools<T: Into<String>>(
+        &mut self,
+        name: T,
+        ctx: AgentContext,
+        allowed_tools: Option<Vec<String>>,
+    ) {
+        self.agents.insert(
+            name.into(),
+            RegisteredAgent {
+                ctx,
+                allowed_tools,
+                runner: None,
+            },
+        );
+    }
+
+    /// Registers `agent` so that steps delegating to `name` run it with
+    /// `control`, starting from `ctx`; see the [`Delegator`] implementation.
+    pub fn register_delegate<T: Into<String>>(
+        &mut self,
+        name: T,
+        agent: Arc<dyn Agent>,
+        ctx: AgentContext,
+        control: ControlLoop,
+    ) {
+        self.agents.insert(
+            name.into(),
+            RegisteredAgent {
+                ctx,
+                allowed_tools: None,
+                runner: Some((agent, Arc::new(control))),
+            },
+        );
+    }
+
+    pub fn allowed_tools(&self, name: &str) -> Option<&[String]> {
+        self.agents
+            .get(name)
+            .and_then(|agent| agent.allowed_tools.as_deref())
     }
 
     pub fn prepare_context(&self, ctx: &mut AgentContext) {
@@ -307,6 +1495,14 @@ impl<B: MessageBus> MultiAgentOrchestrator<B> {
         }
     }
 
+    /// Applies shared memory plus the tool subset registered for `name`.
+    pub fn prepare_agent_context(&self, name: &str, ctx: &mut AgentContext) {
+        self.prepare_context(ctx);
+        if let Some(allowed) = self.allowed_tools(name) {
+            ctx.tool_permissions.allowed = allowed.to_vec();
+        }
+    }
+
     pub async fn call_agent<A: Agent>(
         &self,
         name: &str,
@@ -316,15 +1512,16 @@ impl<B: MessageBus> MultiAgentOrchestrator<B> {
         let mut ctx = self
             .agents
             .get(name)
-            .cloned()
+            .map(|agent| agent.ctx.clone())
             .unwrap_or_else(|| AgentContext {
                 config: agent_core::AgentConfig::default(),
                 state: agent_core::AgentState::default(),
                 metadata: serde_json::json!({}),
                 memory: None,
                 tool_permissions: agent_core::ToolPermissions::default(),
+                cancellation: None,
             });
-        self.prepare_context(&mut ctx);
+        self.prepare_agent_context(name, &mut ctx);
         control.run(agent, &mut ctx).await
     }
 
@@ -342,4 +1539,58 @@ impl<B: MessageBus> MultiAgentOrchestrator<B> {
     ) -> Result<Option<serde_json::Value>, AgentError> {
         self.bus.recv(recipient).await
     }
+
+    /// Broadcasts `message` to the subscribers of `topic`; see
+    /// [`MessageBus::publish`].
+    pub async fn publish(
+        &self,
+        topic: &str,
+        message: serde_json::Value,
+    ) -> Result<usize, AgentError> {
+        self.bus.publish(topic, message).await
+    }
+
+    pub async fn subscribe(&self, topic: &str) -> Result<MessageStream, AgentError> {
+        self.bus.subscribe(topic).await
+    }
+}
+
+/// Runs agents added with [`MultiAgentOrchestrator::register_delegate`] on a
+/// copy of their registered context, prepared like
+/// [`call_agent`](MultiAgentOrchestrator::call_agent). The fields of an
+/// object `input` are merged into its metadata, and any other input is set as
+/// `metadata.input`. The run inherits the parent's cancellation token.
+#[async_trait]
+impl<B: MessageBus + Send + Sync> Delegator for MultiAgentOrchestrator<B> {
+    async fn delegate(
+        &self,
+        agent: &str,
+        input: serde_json::Value,
+        parent: &AgentContext,
+    ) -> Result<RunReport, AgentError> {
+        let registered = self.agents.get(agent).ok_or_else(|| {
+            AgentError::Validation(format!("no agent named {agent} is registered"))
+        })?;
+        let Some((runner, control)) = &registered.runner else {
+            return Err(AgentError::Validation(format!(
+                "agent {agent} was not registered for delegation"
+            )));
+        };
+        let mut ctx = registered.ctx.clone();
+        self.prepare_agent_context(agent, &mut ctx);
+        if !ctx.metadata.is_object() {
+            ctx.metadata = serde_json::json!({});
+        }
+        match input {
+            serde_json::Value::Object(fields) => {
+                for (key, value) in fields {
+                    ctx.metadata[key] = value;
+                }
+            }
+            serde_json::Value::Null => {}
+            input => ctx.metadata["input"] = input,
+        }
+        ctx.cancellation = parent.cancellation.clone();
+        control.run_with_report(runner, &mut ctx).await
+    }
 }
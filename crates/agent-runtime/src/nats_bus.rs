@@ -0,0 +1,307 @@
+//! [`NatsBus`], enabled by the `nats` cargo feature.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use agent_core::AgentError;
+use async_nats::jetstream::{self, consumer::PullConsumer, message::Acker};
+use async_trait::async_trait;
+use futures::StreamExt;
+use serde_json::Value;
+use tokio::sync::OnceCell;
+
+use crate::{
+    JsonCodec, MessageBus, MessageCodec, MessageStream, DEAD_LETTER_REASON_HEADER,
+    DEAD_LETTER_RECIPIENT_HEADER, DEFAULT_CONSUMER_GROUP,
+};
+
+/// JetStream stream a [`NatsBus`] keeps inboxes in unless told otherwise.
+pub const DEFAULT_NATS_STREAM: &str = "AGENT_INBOXES";
+
+/// Deliveries after which a message nobody acknowledges is dead-lettered,
+/// unless told otherwise.
+pub const DEFAULT_MAX_DELIVERIES: i64 = 5;
+
+/// A [`MessageBus`] over a NATS server with JetStream enabled.
+///
+/// Each recipient's inbox is the subject `<prefix>inbox.<recipient>` of one
+/// JetStream stream, read through a durable consumer per recipient, so
+/// messages wait for recipients that are not listening yet and arrive in
+/// order. The message `recv` returns is acknowledged by the next `recv` for
+/// that recipient, or an explicit [`ack`](Self::ack); until then the server
+/// redelivers it once the ack wait runs out. Messages the codec cannot read,
+/// and messages delivered more than `max_deliveries` times, are moved to the
+/// dead-letter subject with [`DEAD_LETTER_RECIPIENT_HEADER`] and
+/// [`DEAD_LETTER_REASON_HEADER`] set, or dropped with a warning when there is
+/// none.
+///
+/// Topics are plain NATS subjects, `<prefix>topic.<topic>`; NATS does not
+/// count subscribers, so `publish` returns 0.
+pub struct NatsBus {
+    url: String,
+    connection: OnceCell<(async_nats::Client, jetstream::Context)>,
+    codec: Arc<dyn MessageCodec>,
+    prefix: String,
+    stream: String,
+    group: String,
+    ack_wait: Duration,
+    max_deliveries: i64,
+    dead_letter: Option<String>,
+    consumers: tokio::sync::Mutex<HashMap<String, PullConsumer>>,
+    // The message last received per recipient, until acknowledged.
+    unacked: Mutex<HashMap<String, Acker>>,
+}
+
+impl NatsBus {
+    /// A bus for the server at `url`, e.g. `nats://localhost:4222`. Nothing
+    /// is sent until the first call.
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            connection: OnceCell::new(),
+            codec: Arc::new(JsonCodec),
+            prefix: "agents.".into(),
+            stream: DEFAULT_NATS_STREAM.into(),
+            group: DEFAULT_CONSUMER_GROUP.into(),
+            ack_wait: Duration::from_secs(60),
+            max_deliveries: DEFAULT_MAX_DELIVERIES,
+            dead_letter: None,
+            consumers: tokio::sync::Mutex::new(HashMap::new()),
+            unacked: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn with_codec(mut self, codec: Arc<dyn MessageCodec>) -> Self {
+        self.codec = codec;
+        self
+    }
+
+    /// Starts every subject, e.g. `orchestration-1.`; `agents.` by default.
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Keeps inboxes in this JetStream stream, created on first use.
+    pub fn with_stream(mut self, stream: impl Into<String>) -> Self {
+        self.stream = stream.into();
+        self
+    }
+
+    /// Names the durable consumers, one per recipient. Buses in the same
+    /// group share out an inbox's messages; separate groups each get all of
+    /// them.
+    pub fn with_group(mut self, group: impl Into<String>) -> Self {
+        self.group = group.into();
+        self
+    }
+
+    /// How long a received message may go unacknowledged before the server
+    /// delivers it again.
+    pub fn with_ack_wait(mut self, ack_wait: Duration) -> Self {
+        self.ack_wait = ack_wait;
+        self
+    }
+
+    pub fn with_max_deliveries(mut self, max_deliveries: i64) -> Self {
+        self.max_deliveries = max_deliveries;
+        self
+    }
+
+    /// Moves undeliverable messages to this subject.
+    pub fn with_dead_letter(mut self, subject: impl Into<String>) -> Self {
+        self.dead_letter = Some(subject.into());
+        self
+    }
+
+    /// Acknowledges the message `recv` last returned for `recipient`, so it
+    /// is never delivered again. Does nothing when there is none.
+    pub async fn ack(&self, recipient: &str) -> Result<(), AgentError> {
+        let acker = lock(&self.unacked).remove(recipient);
+        match acker {
+            Some(acker) => acker.ack().await.map_err(backend),
+            None => Ok(()),
+        }
+    }
+
+    fn inbox(&self, recipient: &str) -> String {
+        format!("{}inbox.{recipient}", self.prefix)
+    }
+
+    fn subject(&self, topic: &str) -> String {
+        format!("{}topic.{topic}", self.prefix)
+    }
+
+    /// The client and JetStream context, connecting and creating the inbox
+    /// stream on first use.
+    async fn connection(&self) -> Result<&(async_nats::Client, jetstream::Context), AgentError> {
+        self.connection
+            .get_or_try_init(|| async {
+                let client = async_nats::connect(self.url.as_str())
+                    .await
+                    .map_err(backend)?;
+                let context = jetstream::new(client.clone());
+                context
+                    .get_or_create_stream(jetstream::stream::Config {
+                        name: self.stream.clone(),
+                        subjects: vec![format!("{}inbox.>", self.prefix)],
+                        ..Default::default()
+                    })
+                    .await
+                    .map_err(backend)?;
+                Ok((client, context))
+            })
+            .await
+    }
+
+    async fn consumer(&self, recipient: &str) -> Result<PullConsumer, AgentError> {
+        let mut consumers = self.consumers.lock().await;
+        if let Some(consumer) = consumers.get(recipient) {
+            return Ok(consumer.clone());
+        }
+        let (_, context) = self.connection().await?;
+        // Durable names may not hold subject separators or wildcards.
+        let durable: String = format!("{}-{recipient}", self.group)
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        let consumer = context
+            .get_stream(&self.stream)
+            .await
+            .map_err(backend)?
+            .get_or_create_consumer(
+                &durable,
+                jetstream::consumer::pull::Config {
+                    durable_name: Some(durable.clone()),
+                    filter_subject: self.inbox(recipient),
+                    ack_wait: self.ack_wait,
+                    ..Default::default()
+                },
+            )
+            .await
+            .map_err(backend)?;
+        consumers.insert(recipient.to_string(), consumer.clone());
+        Ok(consumer)
+    }
+
+    async fn dead_letter(
+        &self,
+        recipient: &str,
+        reason: &str,
+        payload: Vec<u8>,
+    ) -> Result<(), AgentError> {
+        let Some(subject) = &self.dead_letter else {
+            tracing::warn!(%recipient, %reason, "dropped undeliverable message");
+            return Ok(());
+        };
+        let mut headers = async_nats::HeaderMap::new();
+        headers.insert(DEAD_LETTER_RECIPIENT_HEADER, recipient);
+        headers.insert(DEAD_LETTER_REASON_HEADER, reason);
+        let (client, _) = self.connection().await?;
+        client
+            .publish_with_headers(subject.clone(), headers, payload.into())
+            .await
+            .map_err(backend)
+    }
+}
+
+impl std::fmt::Debug for NatsBus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NatsBus")
+            .field("url", &self.url)
+            .field("prefix", &self.prefix)
+            .field("stream", &self.stream)
+            .field("group", &self.group)
+            .field("max_deliveries", &self.max_deliveries)
+            .field("dead_letter", &self.dead_letter)
+            .finish()
+    }
+}
+
+#[async_trait]
+impl MessageBus for NatsBus {
+    async fn send(&self, recipient: &str, message: Value) -> Result<(), AgentError> {
+        let payload = self.codec.encode(&message)?;
+        let (_, context) = self.connection().await?;
+        context
+            .publish(self.inbox(recipient), payload.into())
+            .await
+            .map_err(backend)?
+            .await
+            .map_err(backend)?;
+        Ok(())
+    }
+
+    async fn recv(&self, recipient: &str) -> Result<Option<Value>, AgentError> {
+        self.ack(recipient).await?;
+        let consumer = self.consumer(recipient).await?;
+        loop {
+            let mut batch = consumer
+                .fetch()
+                .max_messages(1)
+                .messages()
+                .await
+                .map_err(backend)?;
+            let Some(message) = batch.next().await.transpose().map_err(backend)? else {
+                return Ok(None);
+            };
+            let delivered = message.info().map_or(1, |info| info.delivered);
+            let (message, acker) = message.split();
+            let reason = if delivered > self.max_deliveries {
+                format!("delivered {delivered} times without being acknowledged")
+            } else {
+                match self.codec.decode(&message.payload) {
+                    Ok(value) => {
+                        lock(&self.unacked).insert(recipient.to_string(), acker);
+                        return Ok(Some(value));
+                    }
+                    Err(error) => error.to_string(),
+                }
+            };
+            self.dead_letter(recipient, &reason, message.payload.to_vec())
+                .await?;
+            acker.ack().await.map_err(backend)?;
+        }
+    }
+
+    async fn publish(&self, topic: &str, message: Value) -> Result<usize, AgentError> {
+        let payload = self.codec.encode(&message)?;
+        let (client, _) = self.connection().await?;
+        client
+            .publish(self.subject(topic), payload.into())
+            .await
+            .map_err(backend)?;
+        Ok(0)
+    }
+
+    async fn subscribe(&self, topic: &str) -> Result<MessageStream, AgentError> {
+        let subject = self.subject(topic);
+        let (client, _) = self.connection().await?;
+        let subscriber = client.subscribe(subject.clone()).await.map_err(backend)?;
+        let codec = self.codec.clone();
+        Ok(Box::pin(subscriber.filter_map(move |message| {
+            let decoded = codec.decode(&message.payload);
+            if let Err(error) = &decoded {
+                tracing::warn!(%subject, %error, "dropped unreadable topic message");
+            }
+            futures::future::ready(decoded.ok())
+        })))
+    }
+}
+
+fn backend(error: impl std::fmt::Display) -> AgentError {
+    AgentError::Execution(format!("nats: {error}"))
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
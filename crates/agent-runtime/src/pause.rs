@@ -0,0 +1,50 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use tokio::sync::Notify;
+
+/// Cloneable switch that suspends a [`crate::ControlLoop`] between iterations.
+///
+/// The loop checks the handle before each iteration and, while paused, waits
+/// for [`PauseHandle::resume`] without dropping any run or context state.
+#[derive(Debug, Clone, Default)]
+pub struct PauseHandle {
+    inner: Arc<PauseState>,
+}
+
+#[derive(Debug, Default)]
+struct PauseState {
+    paused: AtomicBool,
+    resumed: Notify,
+}
+
+impl PauseHandle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn pause(&self) {
+        self.inner.paused.store(true, Ordering::SeqCst);
+    }
+
+    pub fn resume(&self) {
+        self.inner.paused.store(false, Ordering::SeqCst);
+        self.inner.resumed.notify_waiters();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.inner.paused.load(Ordering::SeqCst)
+    }
+
+    /// Returns immediately unless paused; otherwise waits for a resume.
+    pub async fn wait_while_paused(&self) {
+        loop {
+            // Register before checking so a resume in between is not missed.
+            let resumed = self.inner.resumed.notified();
+            if !self.is_paused() {
+                return;
+            }
+            resumed.await;
+        }
+    }
+}
@@ -0,0 +1,341 @@
+//! [`RedisBus`], enabled by the `redis` cargo feature.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use agent_core::AgentError;
+use async_trait::async_trait;
+use futures::StreamExt;
+use redis::aio::MultiplexedConnection;
+use redis::streams::{StreamAutoClaimReply, StreamId, StreamReadOptions, StreamReadReply};
+use serde_json::Value;
+use tokio::sync::OnceCell;
+
+use crate::{MessageBus, MessageStream, DEFAULT_CONSUMER_GROUP};
+
+/// How long a received message may go unacknowledged before another
+/// consumer of the group takes it over, unless told otherwise.
+pub const DEFAULT_CLAIM_IDLE: Duration = Duration::from_secs(60);
+
+/// Stream field holding a message's JSON.
+const PAYLOAD_FIELD: &str = "payload";
+
+/// A [`MessageBus`] over a Redis server, so agents in separate processes can
+/// talk to each other.
+///
+/// Each recipient's inbox is a Redis stream read through a consumer group,
+/// which gives at-least-once delivery: the message `recv` returns stays
+/// pending until the next `recv` for that recipient, or an explicit
+/// [`ack`](Self::ack), acknowledges it. A message left pending for longer
+/// than the claim idle time, say because its consumer crashed, is handed out
+/// again, to this consumer or any other in the group, so receivers should
+/// tolerate the odd duplicate. Every process receiving for a recipient needs
+/// its own consumer name.
+///
+/// Acknowledged entries stay in the stream, which other groups may still
+/// need, so inboxes grow until trimmed. [`with_max_len`](Self::with_max_len)
+/// caps them as messages are sent, at the cost of dropping the oldest
+/// entries whether or not every group has read and acknowledged them.
+///
+/// Topics use Redis pub/sub: a subscription holds a connection of its own
+/// and, as with any [`MessageBus`], only sees what is published after it.
+pub struct RedisBus {
+    client: redis::Client,
+    connection: OnceCell<MultiplexedConnection>,
+    prefix: String,
+    group: String,
+    consumer: String,
+    claim_idle: Duration,
+    max_len: Option<usize>,
+    // Inboxes known to have the consumer group.
+    groups: Mutex<HashSet<String>>,
+    // The entry id last received per recipient, until acknowledged.
+    unacked: Mutex<HashMap<String, String>>,
+}
+
+impl RedisBus {
+    /// A bus for the server at `connection_string`, e.g.
+    /// `redis://localhost:6379/0`. Nothing is sent until the first call.
+    pub fn new<T: Into<String>>(connection_string: T) -> Result<Self, AgentError> {
+        let client = redis::Client::open(connection_string.into()).map_err(backend)?;
+        Ok(Self {
+            client,
+            connection: OnceCell::new(),
+            prefix: String::new(),
+            group: DEFAULT_CONSUMER_GROUP.into(),
+            consumer: format!("consumer-{}", std::process::id()),
+            claim_idle: DEFAULT_CLAIM_IDLE,
+            max_len: None,
+            groups: Mutex::new(HashSet::new()),
+            unacked: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Namespaces every stream and channel, e.g. `orchestration-1:`, so
+    /// several buses can share a server.
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Reads inboxes as this consumer group. Buses in the same group share
+    /// out an inbox's messages; separate groups each get all of them.
+    pub fn with_group(mut self, group: impl Into<String>) -> Self {
+        self.group = group.into();
+        self
+    }
+
+    /// Names this bus within its group; defaults to one derived from the
+    /// process id.
+    pub fn with_consumer(mut self, consumer: impl Into<String>) -> Self {
+        self.consumer = consumer.into();
+        self
+    }
+
+    pub fn with_claim_idle(mut self, claim_idle: Duration) -> Self {
+        self.claim_idle = claim_idle;
+        self
+    }
+
+    /// Trims each inbox to about `max_len` entries (`XADD MAXLEN ~`) as
+    /// messages are sent. Entries beyond the cap are dropped even when
+    /// unread or unacknowledged, so set it well above the backlog any
+    /// recipient may fall behind by.
+    pub fn with_max_len(mut self, max_len: usize) -> Self {
+        self.max_len = Some(max_len);
+        self
+    }
+
+    /// Acknowledges the message `recv` last returned for `recipient`, so it
+    /// is never delivered again. Does nothing when there is none.
+    pub async fn ack(&self, recipient: &str) -> Result<(), AgentError> {
+        let Some(id) = lock(&self.unacked).remove(recipient) else {
+            return Ok(());
+        };
+        let mut connection = self.connection().await?;
+        redis::cmd("XACK")
+            .arg(self.inbox(recipient))
+            .arg(&self.group)
+            .arg(&id)
+            .query_async::<()>(&mut connection)
+            .await
+            .map_err(backend)
+    }
+
+    fn inbox(&self, recipient: &str) -> String {
+        format!("{}inbox:{recipient}", self.prefix)
+    }
+
+    fn channel(&self, topic: &str) -> String {
+        format!("{}topic:{topic}", self.prefix)
+    }
+
+    async fn connection(&self) -> Result<MultiplexedConnection, AgentError> {
+        self.connection
+            .get_or_try_init(|| self.client.get_multiplexed_async_connection())
+            .await
+            .cloned()
+            .map_err(backend)
+    }
+
+    /// Creates the consumer group on `inbox` the first time this bus reads
+    /// it. The group starts from the beginning of the stream, so messages
+    /// sent before anyone listened are still delivered.
+    async fn ensure_group(
+        &self,
+        connection: &mut MultiplexedConnection,
+        inbox: &str,
+    ) -> Result<(), AgentError> {
+        if lock(&self.groups).contains(inbox) {
+            return Ok(());
+        }
+        let created = redis::cmd("XGROUP")
+            .arg("CREATE")
+            .arg(inbox)
+            .arg(&self.group)
+            .arg("0")
+            .arg("MKSTREAM")
+            .query_async::<()>(connection)
+            .await;
+        match created {
+            Ok(()) => {}
+            // Another consumer got there first.
+            Err(error) if error.code() == Some("BUSYGROUP") => {}
+            Err(error) => return Err(backend(error)),
+        }
+        lock(&self.groups).insert(inbox.to_string());
+        Ok(())
+    }
+
+    /// The next entry of `inbox` for this consumer: one left pending too
+    /// long by any consumer of the group first, then a new one.
+    async fn next_entry(
+        &self,
+        connection: &mut MultiplexedConnection,
+        inbox: &str,
+    ) -> Result<Option<StreamId>, AgentError> {
+        let claimed: StreamAutoClaimReply = redis::cmd("XAUTOCLAIM")
+            .arg(inbox)
+            .arg(&self.group)
+            .arg(&self.consumer)
+            .arg(self.claim_idle.as_millis() as u64)
+            .arg("0-0")
+            .arg("COUNT")
+            .arg(1)
+            .query_async(connection)
+            .await
+            .map_err(backend)?;
+        if let Some(entry) = claimed.claimed.into_iter().next() {
+            tracing::debug!(%inbox, id = %entry.id, "redeliver unacknowledged message");
+            return Ok(Some(entry));
+        }
+        let options = StreamReadOptions::default()
+            .group(&self.group, &self.consumer)
+            .count(1);
+        let read: Option<StreamReadReply> = redis::cmd("XREADGROUP")
+            .arg(&options)
+            .arg("STREAMS")
+            .arg(inbox)
+            .arg(">")
+            .query_async(connection)
+            .await
+            .map_err(backend)?;
+        Ok(read
+            .and_then(|read| read.keys.into_iter().next())
+            .and_then(|key| key.ids.into_iter().next()))
+    }
+}
+
+impl std::fmt::Debug for RedisBus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RedisBus")
+            .field("prefix", &self.prefix)
+            .field("group", &self.group)
+            .field("consumer", &self.consumer)
+            .field("claim_idle", &self.claim_idle)
+            .field("max_len", &self.max_len)
+            .finish()
+    }
+}
+
+#[async_trait]
+impl MessageBus for RedisBus {
+    async fn send(&self, recipient: &str, message: Value) -> Result<(), AgentError> {
+        let mut connection = self.connection().await?;
+        let mut command = redis::cmd("XADD");
+        command.arg(self.inbox(recipient));
+        if let Some(max_len) = self.max_len {
+            command.arg("MAXLEN").arg("~").arg(max_len);
+        }
+        command
+            .arg("*")
+            .arg(PAYLOAD_FIELD)
+            .arg(message.to_string())
+            .query_async::<()>(&mut connection)
+            .await
+            .map_err(backend)
+    }
+
+    async fn recv(&self, recipient: &str) -> Result<Option<Value>, AgentError> {
+        self.ack(recipient).await?;
+        let inbox = self.inbox(recipient);
+        let mut connection = self.connection().await?;
+        self.ensure_group(&mut connection, &inbox).await?;
+        let Some(entry) = self.next_entry(&mut connection, &inbox).await? else {
+            return Ok(None);
+        };
+        lock(&self.unacked).insert(recipient.to_string(), entry.id.clone());
+        match decode_entry(&entry) {
+            Ok(message) => Ok(Some(message)),
+            Err(reason) => {
+                // Redelivering a message nobody can read would only fail again.
+                self.ack(recipient).await?;
+                Err(AgentError::Validation(format!(
+                    "unreadable message in {inbox}: {reason}"
+                )))
+            }
+        }
+    }
+
+    async fn publish(&self, topic: &str, message: Value) -> Result<usize, AgentError> {
+        let mut connection = self.connection().await?;
+        redis::cmd("PUBLISH")
+            .arg(self.channel(topic))
+            .arg(message.to_string())
+            .query_async(&mut connection)
+            .await
+            .map_err(backend)
+    }
+
+    async fn subscribe(&self, topic: &str) -> Result<MessageStream, AgentError> {
+        let channel = self.channel(topic);
+        let mut pubsub = self.client.get_async_pubsub().await.map_err(backend)?;
+        pubsub.subscribe(&channel).await.map_err(backend)?;
+        Ok(Box::pin(pubsub.into_on_message().filter_map(move |msg| {
+            let message = msg
+                .get_payload::<String>()
+                .map_err(|e| e.to_string())
+                .and_then(|payload| serde_json::from_str(&payload).map_err(|e| e.to_string()));
+            if let Err(reason) = &message {
+                tracing::warn!(%channel, %reason, "dropped unreadable topic message");
+            }
+            futures::future::ready(message.ok())
+        })))
+    }
+}
+
+/// The JSON message carried in an inbox entry's payload field.
+fn decode_entry(entry: &StreamId) -> Result<Value, String> {
+    entry
+        .get::<String>(PAYLOAD_FIELD)
+        .ok_or_else(|| format!("message {} has no {PAYLOAD_FIELD} field", entry.id))
+        .and_then(|payload| serde_json::from_str(&payload).map_err(|e| e.to_string()))
+}
+
+fn backend(error: redis::RedisError) -> AgentError {
+    AgentError::Execution(format!("redis: {error}"))
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(fields: &[(&str, &str)]) -> StreamId {
+        StreamId {
+            id: "1-0".into(),
+            map: fields
+                .iter()
+                .map(|(name, value)| {
+                    (
+                        name.to_string(),
+                        redis::Value::BulkString(value.as_bytes().to_vec()),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn entries_decode_their_json_payload() {
+        let message = decode_entry(&entry(&[(PAYLOAD_FIELD, r#"{"task":"summarize"}"#)]));
+        assert_eq!(message, Ok(serde_json::json!({"task": "summarize"})));
+
+        let missing = decode_entry(&entry(&[("other", "{}")])).unwrap_err();
+        assert!(missing.contains("no payload field"), "{missing}");
+        assert!(decode_entry(&entry(&[(PAYLOAD_FIELD, "not json")])).is_err());
+    }
+
+    #[test]
+    fn inboxes_are_uncapped_unless_configured() {
+        let bus = RedisBus::new("redis://localhost:6379").unwrap();
+        assert_eq!(bus.max_len, None);
+        assert_eq!(bus.with_max_len(500).max_len, Some(500));
+    }
+}
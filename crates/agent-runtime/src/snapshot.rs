@@ -0,0 +1,136 @@
+use agent_models::{ToolCallInfo, UsageMetrics};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::RunReport;
+
+/// Serializable capture of a run, suitable for golden-file comparisons.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct RunSnapshot {
+    pub goal: Option<String>,
+    pub steps: Vec<StepSnapshot>,
+    pub usage: UsageMetrics,
+    pub tool_calls: Vec<ToolCallInfo>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StepSnapshot {
+    pub step_id: String,
+    pub output: Value,
+    pub success: bool,
+    pub retries: usize,
+    pub fallback_used: bool,
+}
+
+/// Step-level difference between a run and its baseline.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum StepDiff {
+    /// The run executed a step at this position that the baseline did not.
+    Added { index: usize, step: StepSnapshot },
+    /// The baseline executed a step at this position that the run did not.
+    Removed { index: usize, step: StepSnapshot },
+    /// Both runs executed a step at this position but the named fields differ.
+    Changed {
+        index: usize,
+        fields: Vec<String>,
+        baseline: StepSnapshot,
+        current: StepSnapshot,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct RunDiff {
+    pub goal_changed: bool,
+    pub steps: Vec<StepDiff>,
+    pub usage_changed: bool,
+    pub tool_calls_changed: bool,
+}
+
+impl RunDiff {
+    pub fn is_empty(&self) -> bool {
+        !self.goal_changed
+            && self.steps.is_empty()
+            && !self.usage_changed
+            && !self.tool_calls_changed
+    }
+}
+
+impl From<&RunReport> for RunSnapshot {
+    fn from(report: &RunReport) -> Self {
+        Self {
+            goal: report.plan.as_ref().map(|plan| plan.goal.clone()),
+            steps: report
+                .outcomes
+                .iter()
+                .map(|outcome| StepSnapshot {
+                    step_id: outcome.step_id.clone(),
+                    output: outcome.output.clone(),
+                    success: outcome.success,
+                    retries: outcome.retries,
+                    fallback_used: outcome.fallback_used,
+                })
+                .collect(),
+            usage: report.usage.clone(),
+            tool_calls: report.tool_calls.clone(),
+        }
+    }
+}
+
+impl RunSnapshot {
+    /// Compares this run against `baseline`, pairing steps by position.
+    pub fn diff(&self, baseline: &RunSnapshot) -> RunDiff {
+        let mut steps = Vec::new();
+        let len = self.steps.len().max(baseline.steps.len());
+        for index in 0..len {
+            match (baseline.steps.get(index), self.steps.get(index)) {
+                (Some(before), Some(after)) => {
+                    let fields = changed_fields(before, after);
+                    if !fields.is_empty() {
+                        steps.push(StepDiff::Changed {
+                            index,
+                            fields,
+                            baseline: before.clone(),
+                            current: after.clone(),
+                        });
+                    }
+                }
+                (None, Some(after)) => steps.push(StepDiff::Added {
+                    index,
+                    step: after.clone(),
+                }),
+                (Some(before), None) => steps.push(StepDiff::Removed {
+                    index,
+                    step: before.clone(),
+                }),
+                (None, None) => {}
+            }
+        }
+
+        RunDiff {
+            goal_changed: self.goal != baseline.goal,
+            steps,
+            usage_changed: self.usage != baseline.usage,
+            tool_calls_changed: self.tool_calls != baseline.tool_calls,
+        }
+    }
+}
+
+fn changed_fields(before: &StepSnapshot, after: &StepSnapshot) -> Vec<String> {
+    let mut fields = Vec::new();
+    if before.step_id != after.step_id {
+        fields.push("step_id".to_string());
+    }
+    if before.output != after.output {
+        fields.push("output".to_string());
+    }
+    if before.success != after.success {
+        fields.push("success".to_string());
+    }
+    if before.retries != after.retries {
+        fields.push("retries".to_string());
+    }
+    if before.fallback_used != after.fallback_used {
+        fields.push("fallback_used".to_string());
+    }
+    fields
+}
@@ -0,0 +1,282 @@
+use std::borrow::Cow;
+use std::sync::Arc;
+
+use agent_models::{
+    ChatMessage, ChatOptions, ChatRole, ContextUsage, GenerationOptions, LLMModel, LLMResponse,
+    ModelError, UsageTracker,
+};
+use agent_tools::{CancellationToken, FunctionSpecFormat, InvocationContext, ToolRegistry};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+/// Drives a model's native tool calling against a [`ToolRegistry`].
+///
+/// Each turn sends the conversation plus the registry's function specs; any
+/// `tool_calls` in the reply are invoked through the registry and their
+/// results appended as tool messages. Tool failures are reported back to the
+/// model as `{"error": ...}` so it can recover, while a failed model call
+/// ends the run. The loop ends when a reply makes no calls or after
+/// `max_iterations` model turns.
+///
+/// With a `context_limit`, each request is fitted to the model's context
+/// window by leaving out the oldest non-system turns (an assistant tool-call
+/// message together with its tool results) until the model's
+/// [`count_tokens`](LLMModel::count_tokens) fits. The latest message is
+/// always sent, and the returned transcript stays complete. The usage of
+/// each request is also reported to the `usage` tracker, if any, which is
+/// how a [`ControlLoop`](crate::ControlLoop) sharing it fills in
+/// [`RunReport::context_usage`](crate::RunReport::context_usage).
+///
+/// With a `cancellation` token, cancelling it drops the model call in flight
+/// and aborts running tools, ending the run with [`ModelError::Cancelled`].
+pub struct ToolCallingExecutor<M: LLMModel> {
+    pub model: M,
+    pub registry: Arc<ToolRegistry>,
+    pub format: FunctionSpecFormat,
+    pub max_iterations: usize,
+    pub generation: GenerationOptions,
+    /// Roles presented to the registry's permission checks.
+    pub caller_roles: Vec<String>,
+    /// Context window size in tokens; unlimited when unset.
+    pub context_limit: Option<usize>,
+    pub cancellation: Option<CancellationToken>,
+    pub usage: Option<UsageTracker>,
+}
+
+/// One tool call made during a [`ToolCallingExecutor`] run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolCallRecord {
+    pub id: String,
+    /// Function name chosen by the model.
+    pub function: String,
+    /// Registry name it resolved to, if any.
+    pub tool: Option<String>,
+    pub arguments: Value,
+    pub result: Result<Value, String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ToolCallingOutcome {
+    /// The last model reply: the final answer when `finished`.
+    pub response: LLMResponse,
+    /// Full transcript, including the assistant and tool turns.
+    pub messages: Vec<ChatMessage>,
+    pub calls: Vec<ToolCallRecord>,
+    pub iterations: usize,
+    /// False when the iteration cap stopped the loop mid-conversation.
+    pub finished: bool,
+    /// Fullest context window sent, when a `context_limit` is set.
+    pub context_usage: Option<ContextUsage>,
+}
+
+impl<M: LLMModel> ToolCallingExecutor<M> {
+    pub const DEFAULT_MAX_ITERATIONS: usize = 8;
+
+    pub fn new(model: M, registry: Arc<ToolRegistry>) -> Self {
+        Self {
+            model,
+            registry,
+            format: FunctionSpecFormat::OpenAi,
+            max_iterations: Self::DEFAULT_MAX_ITERATIONS,
+            generation: GenerationOptions::default(),
+            caller_roles: Vec::new(),
+            context_limit: None,
+            cancellation: None,
+            usage: None,
+        }
+    }
+
+    pub fn with_format(mut self, format: FunctionSpecFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn with_max_iterations(mut self, max_iterations: usize) -> Self {
+        self.max_iterations = max_iterations.max(1);
+        self
+    }
+
+    pub fn with_generation_options(mut self, generation: GenerationOptions) -> Self {
+        self.generation = generation;
+        self
+    }
+
+    pub fn with_caller_roles(mut self, roles: Vec<String>) -> Self {
+        self.caller_roles = roles;
+        self
+    }
+
+    pub fn with_context_limit(mut self, limit: usize) -> Self {
+        self.context_limit = Some(limit);
+        self
+    }
+
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    pub fn with_usage_tracker(mut self, tracker: UsageTracker) -> Self {
+        self.usage = Some(tracker);
+        self
+    }
+
+    pub async fn run(&self, prompt: &str) -> Result<ToolCallingOutcome, ModelError> {
+        self.run_messages(vec![ChatMessage::user(prompt)]).await
+    }
+
+    /// Continues `messages` until the model answers without calling tools.
+    pub async fn run_messages(
+        &self,
+        mut messages: Vec<ChatMessage>,
+    ) -> Result<ToolCallingOutcome, ModelError> {
+        let options = ChatOptions::default()
+            .with_tools(self.registry.to_function_specs(self.format))
+            .with_generation(self.generation.clone());
+        let mut calls = Vec::new();
+        let mut iterations = 0;
+        let mut context_usage: Option<ContextUsage> = None;
+        loop {
+            iterations += 1;
+            let (window, usage) = self.fit_context(&messages);
+            if let Some(usage) = usage {
+                if let Some(tracker) = &self.usage {
+                    tracker.record_context(usage);
+                }
+                if context_usage.is_none_or(|peak| usage.used > peak.used) {
+                    context_usage = Some(usage);
+                }
+            }
+            let chat = self.model.chat(&window, &options);
+            let mut response = match &self.cancellation {
+                Some(token) => tokio::select! {
+                    biased;
+                    _ = token.cancelled() => return Err(ModelError::Cancelled),
+                    response = chat => response?,
+                },
+                None => chat.await?,
+            };
+            if response.tool_calls.is_empty() {
+                messages.push(ChatMessage::assistant(&response));
+                return Ok(ToolCallingOutcome {
+                    response,
+                    messages,
+                    calls,
+                    iterations,
+                    finished: true,
+                    context_usage,
+                });
+            }
+
+            // Tool messages must reference their call, so fill in ids for
+            // providers that do not assign them.
+            for (index, call) in response.tool_calls.iter_mut().enumerate() {
+                call.id
+                    .get_or_insert_with(|| format!("call_{iterations}_{index}"));
+            }
+            messages.push(ChatMessage::assistant(&response));
+            for call in &response.tool_calls {
+                if self
+                    .cancellation
+                    .as_ref()
+                    .is_some_and(|token| token.is_cancelled())
+                {
+                    return Err(ModelError::Cancelled);
+                }
+                let record = self.execute(call).await;
+                let content = match &record.result {
+                    Ok(output) => output.to_string(),
+                    Err(error) => json!({ "error": error }).to_string(),
+                };
+                messages.push(ChatMessage::tool(record.id.clone(), content));
+                calls.push(record);
+            }
+
+            if iterations >= self.max_iterations {
+                tracing::warn!(iterations, "tool-calling loop hit its iteration cap");
+                return Ok(ToolCallingOutcome {
+                    response,
+                    messages,
+                    calls,
+                    iterations,
+                    finished: false,
+                    context_usage,
+                });
+            }
+        }
+    }
+
+    /// The part of `messages` that fits `context_limit`, with its usage.
+    fn fit_context<'a>(
+        &self,
+        messages: &'a [ChatMessage],
+    ) -> (Cow<'a, [ChatMessage]>, Option<ContextUsage>) {
+        let Some(limit) = self.context_limit else {
+            return (Cow::Borrowed(messages), None);
+        };
+        let mut window = Cow::Borrowed(messages);
+        loop {
+            let usage = self.model.context_usage(&window, limit);
+            if !usage.is_over_limit() {
+                return (window, Some(usage));
+            }
+            let oldest = window
+                .iter()
+                .position(|message| message.role != ChatRole::System);
+            let Some(start) = oldest.filter(|start| start + 1 < window.len()) else {
+                tracing::warn!(
+                    used = usage.used,
+                    limit,
+                    "conversation exceeds the context limit even after trimming"
+                );
+                return (window, Some(usage));
+            };
+            let mut end = start + 1;
+            while end + 1 < window.len() && window[end].role == ChatRole::Tool {
+                end += 1;
+            }
+            window.to_mut().drain(start..end);
+        }
+    }
+
+    fn invocation_context(&self) -> InvocationContext {
+        let ctx = InvocationContext::new(&self.caller_roles);
+        match &self.cancellation {
+            Some(token) => ctx.with_cancellation(token.clone()),
+            None => ctx,
+        }
+    }
+
+    async fn execute(&self, call: &agent_models::ToolCallInfo) -> ToolCallRecord {
+        let id = call.id.clone().unwrap_or_default();
+        let tool = self.registry.tool_for_function(&call.name);
+        let result = match &tool {
+            Some(tool) => self
+                .registry
+                .invoke_with(tool, call.arguments.clone(), &self.invocation_context())
+                .await
+                .map_err(|error| error.to_string()),
+            None => Err(format!("unknown tool `{}`", call.name)),
+        };
+        ToolCallRecord {
+            id,
+            function: call.name.clone(),
+            tool,
+            arguments: call.arguments.clone(),
+            result,
+        }
+    }
+}
+
+impl<M: LLMModel> std::fmt::Debug for ToolCallingExecutor<M> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ToolCallingExecutor")
+            .field("format", &self.format)
+            .field("max_iterations", &self.max_iterations)
+            .field("caller_roles", &self.caller_roles)
+            .field("context_limit", &self.context_limit)
+            .field("cancellation", &self.cancellation.is_some())
+            .field("usage", &self.usage)
+            .finish()
+    }
+}
@@ -0,0 +1,140 @@
+//! Workflow combinators piping agents into one another or fanning a task out.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use agent_core::{AgentContext, AgentError};
+use futures::future::try_join_all;
+use serde_json::{Map, Value};
+
+use crate::{delegate, Delegator};
+
+/// Combines the outputs of a [`Workflow::Concurrent`]'s branches, in
+/// branch order.
+pub type AggregateFn = Arc<dyn Fn(Vec<Value>) -> Value + Send + Sync>;
+
+/// How a [`Workflow::Concurrent`] merges its branches' outputs.
+#[derive(Clone, Default)]
+pub enum Aggregator {
+    /// An array of the outputs, in branch order.
+    #[default]
+    Collect,
+    /// The fields of every object output in one object, later branches
+    /// winning; other outputs are left out.
+    Merge,
+    Function(AggregateFn),
+}
+
+impl Aggregator {
+    fn aggregate(&self, outputs: Vec<Value>) -> Value {
+        match self {
+            Aggregator::Collect => Value::Array(outputs),
+            Aggregator::Merge => {
+                let mut merged = Map::new();
+                for output in outputs {
+                    if let Value::Object(fields) = output {
+                        merged.extend(fields);
+                    }
+                }
+                Value::Object(merged)
+            }
+            Aggregator::Function(aggregate) => aggregate(outputs),
+        }
+    }
+}
+
+/// One part of a [`Workflow`].
+#[derive(Clone)]
+pub enum AgentRef {
+    /// An agent the [`Delegator`] knows by this name.
+    Named(String),
+    Workflow(Box<Workflow>),
+}
+
+impl From<&str> for AgentRef {
+    fn from(name: &str) -> Self {
+        AgentRef::Named(name.to_string())
+    }
+}
+
+impl From<String> for AgentRef {
+    fn from(name: String) -> Self {
+        AgentRef::Named(name)
+    }
+}
+
+impl From<Workflow> for AgentRef {
+    fn from(workflow: Workflow) -> Self {
+        AgentRef::Workflow(Box::new(workflow))
+    }
+}
+
+/// Agents run in order or side by side, nesting through
+/// [`AgentRef::Workflow`].
+///
+/// A named agent runs through the [`Delegator`] with the workflow's current
+/// value as its input, and its run's last step output becomes the next
+/// value; a run that does not complete fails the workflow with
+/// [`AgentError::Execution`].
+#[derive(Clone)]
+pub enum Workflow {
+    /// Each agent's output is the input of the next.
+    Sequential(Vec<AgentRef>),
+    /// Every agent gets the same input at once; the aggregator merges their
+    /// outputs.
+    Concurrent(Vec<AgentRef>, Aggregator),
+}
+
+impl Workflow {
+    /// Runs the workflow on `input` and returns its final output. `ctx` is
+    /// the delegating context each agent run is started from.
+    pub fn run<'a>(
+        &'a self,
+        delegator: &'a dyn Delegator,
+        input: Value,
+        ctx: &'a AgentContext,
+    ) -> Pin<Box<dyn Future<Output = Result<Value, AgentError>> + Send + 'a>> {
+        Box::pin(async move {
+            match self {
+                Workflow::Sequential(agents) => {
+                    let mut value = input;
+                    for agent in agents {
+                        value = agent.run(delegator, value, ctx).await?;
+                    }
+                    Ok(value)
+                }
+                Workflow::Concurrent(agents, aggregator) => {
+                    let branches = agents
+                        .iter()
+                        .map(|agent| agent.run(delegator, input.clone(), ctx));
+                    Ok(aggregator.aggregate(try_join_all(branches).await?))
+                }
+            }
+        })
+    }
+}
+
+impl AgentRef {
+    async fn run(
+        &self,
+        delegator: &dyn Delegator,
+        input: Value,
+        ctx: &AgentContext,
+    ) -> Result<Value, AgentError> {
+        match self {
+            AgentRef::Named(name) => {
+                let report = delegator.delegate(name, input, ctx).await?;
+                let outcome = delegate::fold(name.clone(), name, report);
+                if !outcome.success {
+                    return Err(AgentError::Execution(format!(
+                        "agent {name} did not complete: {}",
+                        outcome.output
+                    )));
+                }
+                Ok(outcome.output)
+            }
+            AgentRef::Workflow(workflow) => workflow.run(delegator, input, ctx).await,
+        }
+    }
+}
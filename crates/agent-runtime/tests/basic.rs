@@ -1,9 +1,16 @@
 use agent_core::{
-    Agent, AgentConfig, AgentContext, AgentError, AgentState, Plan, RetryPolicy, Step, StepOutcome,
-    StepPolicies, ToolPermissions,
+    Agent, AgentConfig, AgentContext, AgentError, AgentState, Delegate, FailureKind, ForEach, Plan,
+    RetryPolicy, Step, StepOutcome, StepPolicies, ToolPermissions,
 };
 use agent_runtime::{
-    ControlLoop, ControlMode, InMemoryBus, MemoryTopology, MultiAgentOrchestrator, StepExecutor,
+    AgentEvent, BudgetLimit, CheckpointStore, ControlLoop, ControlMode, EventBus, GroupChat,
+    GroupChatStop, InMemoryBus, InterventionDecision, InterventionHandler, MemoryTopology,
+    ModelPlannerAgent, MultiAgentOrchestrator, PauseHandle, RunBudget, RunHook, RunReport,
+    SpeakerSelection, StepDiff, StepExecutor, StopReason, Termination, Workflow,
+};
+use agent_tools::{
+    builtins::{FileTool, MathTool, TimeTool},
+    ToolInvocationError, ToolRegistry,
 };
 use serde_json::json;
 use std::sync::Arc;
@@ -25,6 +32,11 @@ impl Agent for TestAgent {
                 subtasks: vec![],
                 policies: StepPolicies::default(),
                 chain_of_thought: None,
+                sub_plan: None,
+                depends_on: Vec::new(),
+                condition: None,
+                for_each: None,
+                delegate: None,
             }],
             metadata: json!({}),
         })
@@ -43,6 +55,9 @@ impl Agent for TestAgent {
             retries: 0,
             fallback_used: false,
             control_notes: vec![],
+            fallback_applied: None,
+            failure_kind: None,
+            redactions: Vec::new(),
         })
     }
 }
@@ -61,17 +76,69 @@ async fn runs_control_loop() {
         metadata: json!({}),
         memory: None,
         tool_permissions: ToolPermissions::default(),
+        cancellation: None,
     };
     let loop_ctrl = ControlLoop {
         max_iterations: 2,
         delay: std::time::Duration::from_millis(0),
         mode: ControlMode::Deterministic,
+        ..Default::default()
     };
     let outcomes = loop_ctrl.run(&agent, &mut ctx).await.expect("loop to run");
     assert_eq!(outcomes.len(), 1);
     assert!(outcomes[0].success);
 }
 
+/// Plans two steps that share an id.
+#[derive(Debug)]
+struct DuplicateIdsAgent;
+
+#[async_trait::async_trait]
+impl Agent for DuplicateIdsAgent {
+    async fn plan(&self, ctx: &AgentContext) -> Result<Plan, AgentError> {
+        let mut plan = TestAgent.plan(ctx).await?;
+        plan.steps.push(plan.steps[0].clone());
+        Ok(plan)
+    }
+
+    async fn execute_step(
+        &self,
+        step: &Step,
+        ctx: &mut AgentContext,
+    ) -> Result<StepOutcome, AgentError> {
+        TestAgent.execute_step(step, ctx).await
+    }
+}
+
+#[tokio::test]
+async fn control_loop_applies_its_dedup_strategy() {
+    let control = ControlLoop {
+        max_iterations: 5,
+        ..Default::default()
+    };
+    let rejected = control
+        .run_with_report(&DuplicateIdsAgent, &mut AgentContext::default())
+        .await;
+    assert!(matches!(rejected, Err(AgentError::Validation(_))));
+
+    let report = control
+        .with_dedup_strategy(agent_core::DedupStrategy::SuffixIndex)
+        .run_with_report(&DuplicateIdsAgent, &mut AgentContext::default())
+        .await
+        .unwrap();
+    let ids: Vec<&str> = report.outcomes.iter().map(|o| o.step_id.as_str()).collect();
+    assert_eq!(ids, vec!["one", "one-1"]);
+    let planned: Vec<&str> = report
+        .plan
+        .as_ref()
+        .unwrap()
+        .steps
+        .iter()
+        .map(|s| s.id.as_str())
+        .collect();
+    assert_eq!(planned, ids);
+}
+
 #[derive(Debug)]
 struct FlakyAgent {
     attempts: Arc<Mutex<usize>>,
@@ -93,10 +160,16 @@ impl Agent for FlakyAgent {
                         max_retries: 1,
                         backoff_ms: 0,
                         jitter: false,
+                        max_delay_ms: None,
                     },
                     ..Default::default()
                 },
                 chain_of_thought: None,
+                sub_plan: None,
+                depends_on: Vec::new(),
+                condition: None,
+                for_each: None,
+                delegate: None,
             }],
             metadata: json!({}),
         })
@@ -127,6 +200,7 @@ async fn step_executor_retries_and_records_counts() {
         metadata: json!({}),
         memory: None,
         tool_permissions: ToolPermissions::default(),
+        cancellation: None,
     };
     let plan = agent.plan(&ctx).await.expect("plan available");
     let step = plan.steps.first().cloned().expect("step present");
@@ -135,6 +209,147 @@ async fn step_executor_retries_and_records_counts() {
     assert_eq!(outcome.retries, 1);
 }
 
+#[tokio::test]
+async fn max_delay_caps_only_rate_limit_waits() {
+    let agent = FlakyAgent {
+        attempts: Arc::new(Mutex::new(0)),
+    };
+    let mut ctx = AgentContext::default();
+    let plan = agent.plan(&ctx).await.expect("plan available");
+    let mut step = plan.steps.first().cloned().expect("step present");
+    step.policies.retry.backoff_ms = 100;
+    step.policies.retry.max_delay_ms = Some(10);
+
+    let started = std::time::Instant::now();
+    let outcome = StepExecutor::run_step(step, &agent, &mut ctx).await;
+    let elapsed = started.elapsed();
+
+    assert!(outcome.success);
+    assert!(
+        elapsed >= std::time::Duration::from_millis(100),
+        "{elapsed:?}"
+    );
+}
+
+#[tokio::test]
+async fn non_idempotent_tools_are_not_retried() {
+    let registry = ToolRegistry::new();
+    registry.register(FileTool::new(std::env::temp_dir()));
+    registry.register(MathTool);
+
+    for (tool, args, expect_success) in [
+        ("file", json!({"operation": "write"}), false),
+        ("file", json!({"operation": "read"}), true),
+        ("math", json!({}), true),
+    ] {
+        let agent = FlakyAgent {
+            attempts: Arc::new(Mutex::new(0)),
+        };
+        let mut ctx = AgentContext::default();
+        let plan = agent.plan(&ctx).await.expect("plan available");
+        let mut step = plan.steps.first().cloned().expect("step present");
+        step.tool = Some(tool.into());
+        step.args = args;
+
+        let outcome =
+            StepExecutor::run_step_with_tools(step, &agent, &mut ctx, Some(&registry)).await;
+        assert_eq!(outcome.success, expect_success, "tool {tool}");
+    }
+}
+
+#[derive(Debug)]
+struct RateLimitedAgent {
+    attempts: Arc<Mutex<usize>>,
+    retry_after_ms: u64,
+}
+
+#[async_trait::async_trait]
+impl Agent for RateLimitedAgent {
+    async fn plan(&self, _ctx: &AgentContext) -> Result<Plan, AgentError> {
+        Ok(Plan {
+            goal: "rate limited".into(),
+            steps: vec![],
+            metadata: json!({}),
+        })
+    }
+
+    async fn execute_step(
+        &self,
+        step: &Step,
+        _ctx: &mut AgentContext,
+    ) -> Result<StepOutcome, AgentError> {
+        let mut attempts = self.attempts.lock().unwrap();
+        *attempts += 1;
+        if *attempts == 1 {
+            return Err(ToolInvocationError::RateLimited {
+                tool: "search".into(),
+                retry_after_ms: self.retry_after_ms,
+            }
+            .into());
+        }
+        Ok(StepOutcome::success(step.id.clone(), json!({"ok": true})))
+    }
+}
+
+#[tokio::test]
+async fn rate_limited_retries_wait_for_retry_after() {
+    let lookup = |max_delay_ms| Step {
+        id: "lookup".into(),
+        description: "rate limited lookup".into(),
+        tool: None,
+        args: json!({}),
+        subtasks: vec![],
+        policies: StepPolicies {
+            retry: RetryPolicy {
+                max_retries: 2,
+                backoff_ms: 1,
+                jitter: false,
+                max_delay_ms,
+            },
+            ..Default::default()
+        },
+        chain_of_thought: None,
+        sub_plan: None,
+        depends_on: Vec::new(),
+        condition: None,
+        for_each: None,
+        delegate: None,
+    };
+    let agent = RateLimitedAgent {
+        attempts: Arc::new(Mutex::new(0)),
+        retry_after_ms: 120,
+    };
+
+    let started = std::time::Instant::now();
+    let outcome = StepExecutor::run_step(lookup(None), &agent, &mut AgentContext::default()).await;
+    let elapsed = started.elapsed();
+
+    assert!(outcome.success);
+    assert_eq!(outcome.retries, 1);
+    assert!(
+        elapsed >= std::time::Duration::from_millis(120),
+        "{elapsed:?}"
+    );
+    assert!(elapsed < std::time::Duration::from_secs(2), "{elapsed:?}");
+
+    // A tool asking for an hour only gets the policy's maximum delay.
+    let agent = RateLimitedAgent {
+        attempts: Arc::new(Mutex::new(0)),
+        retry_after_ms: 3_600_000,
+    };
+    let started = std::time::Instant::now();
+    let outcome =
+        StepExecutor::run_step(lookup(Some(50)), &agent, &mut AgentContext::default()).await;
+    let elapsed = started.elapsed();
+
+    assert!(outcome.success);
+    assert!(
+        elapsed >= std::time::Duration::from_millis(50),
+        "{elapsed:?}"
+    );
+    assert!(elapsed < std::time::Duration::from_secs(2), "{elapsed:?}");
+}
+
 #[derive(Debug)]
 struct AlternateToolAgent;
 
@@ -159,6 +374,11 @@ impl Agent for AlternateToolAgent {
                     ..Default::default()
                 },
                 chain_of_thought: None,
+                sub_plan: None,
+                depends_on: Vec::new(),
+                condition: None,
+                for_each: None,
+                delegate: None,
             }],
             metadata: json!({}),
         })
@@ -186,15 +406,92 @@ async fn fallback_switches_tool() {
         metadata: json!({}),
         memory: None,
         tool_permissions: ToolPermissions::default(),
+        cancellation: None,
     };
     let plan = agent.plan(&ctx).await.expect("plan available");
     let step = plan.steps.first().cloned().expect("step present");
     let outcome = StepExecutor::run_step(step, &agent, &mut ctx).await;
     assert!(outcome.success);
     assert!(outcome.fallback_used);
+    assert_eq!(
+        outcome.fallback_applied,
+        Some(agent_core::FallbackKind::AlternateTool)
+    );
     assert_eq!(outcome.output["alt"], json!(true));
 }
 
+#[tokio::test]
+async fn missing_alternate_tool_is_reported() {
+    let agent = AlternateToolAgent;
+    let mut ctx = AgentContext::default();
+    let registry = ToolRegistry::new();
+    registry.register(TimeTool);
+    let plan = agent.plan(&ctx).await.expect("plan available");
+    let step = plan.steps.first().cloned().expect("step present");
+
+    let outcome = StepExecutor::run_step_with_tools(step, &agent, &mut ctx, Some(&registry)).await;
+    assert!(!outcome.success);
+    assert_eq!(
+        outcome.failure_kind,
+        Some(FailureKind::MissingAlternateTool { tool: "alt".into() })
+    );
+    assert!(outcome.control_notes[0].contains("alt is not registered"));
+}
+
+#[derive(Debug)]
+struct AlwaysFailingAgent;
+
+#[async_trait::async_trait]
+impl Agent for AlwaysFailingAgent {
+    async fn plan(&self, _ctx: &agent_core::AgentContext) -> Result<Plan, AgentError> {
+        Ok(Plan {
+            goal: "skip".into(),
+            steps: vec![],
+            metadata: json!({}),
+        })
+    }
+
+    async fn execute_step(
+        &self,
+        _step: &Step,
+        _ctx: &mut AgentContext,
+    ) -> Result<StepOutcome, AgentError> {
+        Err(AgentError::Execution("always fails".into()))
+    }
+}
+
+#[tokio::test]
+async fn skip_fallback_is_recorded_structurally() {
+    let step = Step {
+        id: "optional".into(),
+        description: "non-critical".into(),
+        tool: None,
+        args: json!({}),
+        subtasks: vec![],
+        policies: StepPolicies {
+            fallback: Some(agent_core::FallbackPolicy {
+                strategy: agent_core::FallbackStrategy::Skip,
+                reason: None,
+            }),
+            ..Default::default()
+        },
+        chain_of_thought: None,
+        sub_plan: None,
+        depends_on: Vec::new(),
+        condition: None,
+        for_each: None,
+        delegate: None,
+    };
+    let mut ctx = AgentContext::default();
+    let outcome = StepExecutor::run_step(step, &AlwaysFailingAgent, &mut ctx).await;
+    assert!(!outcome.success);
+    assert_eq!(
+        outcome.fallback_applied,
+        Some(agent_core::FallbackKind::Skip)
+    );
+    assert_eq!(outcome.control_notes, vec!["fallback: skip".to_string()]);
+}
+
 #[derive(Debug)]
 struct ModeAwareAgent;
 
@@ -211,6 +508,11 @@ impl Agent for ModeAwareAgent {
                 subtasks: vec![],
                 policies: StepPolicies::default(),
                 chain_of_thought: None,
+                sub_plan: None,
+                depends_on: Vec::new(),
+                condition: None,
+                for_each: None,
+                delegate: None,
             }],
             metadata: json!({}),
         })
@@ -239,11 +541,13 @@ async fn reactive_mode_replans_each_iteration() {
         metadata: json!({}),
         memory: None,
         tool_permissions: ToolPermissions::default(),
+        cancellation: None,
     };
     let loop_ctrl = ControlLoop {
         max_iterations: 2,
         delay: std::time::Duration::from_millis(0),
         mode: ControlMode::Reactive,
+        ..Default::default()
     };
     let outcomes = loop_ctrl.run(&agent, &mut ctx).await.expect("loop to run");
     assert_eq!(outcomes.len(), 2);
@@ -269,6 +573,11 @@ impl Agent for ReflectiveAgent {
                 subtasks: vec![],
                 policies: StepPolicies::default(),
                 chain_of_thought: None,
+                sub_plan: None,
+                depends_on: Vec::new(),
+                condition: None,
+                for_each: None,
+                delegate: None,
             }],
             metadata: json!({}),
         })
@@ -300,11 +609,13 @@ async fn reflection_enabled_mode_reflects_per_step() {
         metadata: json!({}),
         memory: None,
         tool_permissions: ToolPermissions::default(),
+        cancellation: None,
     };
     let loop_ctrl = ControlLoop {
         max_iterations: 1,
         delay: std::time::Duration::from_millis(0),
         mode: ControlMode::ReflectionEnabled,
+        ..Default::default()
     };
     loop_ctrl.run(&agent, &mut ctx).await.expect("loop to run");
     assert_eq!(*agent.reflections.lock().unwrap(), 2);
@@ -324,6 +635,7 @@ async fn orchestrator_shares_memory_and_routes_messages() {
         metadata: json!({}),
         memory: None,
         tool_permissions: ToolPermissions::default(),
+        cancellation: None,
     };
 
     orchestrator.register_agent("alpha", base_ctx.clone());
@@ -345,3 +657,2649 @@ async fn orchestrator_shares_memory_and_routes_messages() {
         .expect("message received");
     assert_eq!(received.unwrap()["ping"], json!(true));
 }
+
+#[tokio::test]
+async fn topics_reach_every_subscriber() {
+    use agent_runtime::MessageBus;
+    use tokio_stream::StreamExt;
+
+    let bus = InMemoryBus::new();
+    assert_eq!(bus.publish("news", json!("unheard")).await.unwrap(), 0);
+
+    let mut first = bus.subscribe("news").await.unwrap();
+    let mut second = bus.subscribe("news").await.unwrap();
+    let mut other = bus.subscribe("weather").await.unwrap();
+    assert_eq!(bus.publish("news", json!({"n": 1})).await.unwrap(), 2);
+    assert_eq!(first.next().await, Some(json!({"n": 1})));
+    assert_eq!(second.next().await, Some(json!({"n": 1})));
+
+    drop(second);
+    assert_eq!(bus.publish("news", json!({"n": 2})).await.unwrap(), 1);
+    assert_eq!(first.next().await, Some(json!({"n": 2})));
+
+    // Topics and point-to-point messages do not mix.
+    bus.send("news", json!("direct")).await.unwrap();
+    assert_eq!(bus.recv("news").await.unwrap(), Some(json!("direct")));
+    bus.publish("weather", json!("rain")).await.unwrap();
+    assert_eq!(other.next().await, Some(json!("rain")));
+    assert_eq!(bus.recv("weather").await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn inboxes_wait_for_messages_and_push_back_when_full() {
+    use agent_runtime::MessageBus;
+    use std::time::Duration;
+    use tokio_stream::StreamExt;
+
+    let bus = Arc::new(InMemoryBus::new().with_inbox_capacity(1));
+    assert_eq!(bus.recv_wait("beta", Duration::from_millis(20)).await, None);
+
+    let sender = bus.clone();
+    let sending = tokio::spawn(async move {
+        sender.send("beta", json!(1)).await.unwrap();
+        // Waits until the first message is taken.
+        sender.send("beta", json!(2)).await.unwrap();
+        sender.send("beta", json!(3)).await.unwrap();
+    });
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert!(!sending.is_finished());
+
+    assert_eq!(
+        bus.recv_wait("beta", Duration::from_secs(1)).await,
+        Some(json!(1))
+    );
+    let mut inbox = bus.subscribe_inbox("beta");
+    assert_eq!(inbox.next().await, Some(json!(2)));
+    assert_eq!(inbox.next().await, Some(json!(3)));
+    sending.await.unwrap();
+    drop(inbox);
+    assert_eq!(bus.recv("beta").await.unwrap(), None);
+}
+
+#[test]
+fn json_codec_round_trips_and_rejects_garbage() {
+    use agent_runtime::{JsonCodec, MessageCodec};
+
+    let message = json!({"task": "summarise", "ids": [1, 2]});
+    let bytes = JsonCodec.encode(&message).unwrap();
+    assert_eq!(JsonCodec.decode(&bytes).unwrap(), message);
+    assert!(matches!(
+        JsonCodec.decode(b"{not json"),
+        Err(AgentError::Validation(_))
+    ));
+}
+
+#[test]
+fn shared_memory_rejects_stale_versioned_writes() {
+    let shared: Arc<dyn agent_memory::MemoryStore> = Arc::new(agent_memory::InMemoryStore::new());
+    let orchestrator =
+        MultiAgentOrchestrator::new(InMemoryBus::new(), MemoryTopology::Shared(shared));
+    let base_ctx = AgentContext {
+        config: AgentConfig::default(),
+        state: AgentState::default(),
+        metadata: json!({}),
+        memory: None,
+        tool_permissions: ToolPermissions::default(),
+        cancellation: None,
+    };
+    let (mut alpha, mut beta) = (base_ctx.clone(), base_ctx);
+    orchestrator.prepare_context(&mut alpha);
+    orchestrator.prepare_context(&mut beta);
+    let (alpha, beta) = (alpha.memory.unwrap(), beta.memory.unwrap());
+
+    alpha.put("tally", &json!(0)).unwrap();
+    let (_, seen_by_alpha) = alpha.get_versioned("tally").unwrap().unwrap();
+    let (_, seen_by_beta) = beta.get_versioned("tally").unwrap().unwrap();
+    alpha
+        .put_if_version("tally", &json!(1), seen_by_alpha)
+        .unwrap();
+    let conflict = beta.put_if_version("tally", &json!(1), seen_by_beta);
+    assert!(matches!(
+        conflict,
+        Err(agent_memory::MemoryError::Conflict { .. })
+    ));
+
+    let (value, version) = beta.get_versioned("tally").unwrap().unwrap();
+    beta.put_if_version("tally", &json!(value.as_i64().unwrap() + 1), version)
+        .unwrap();
+    assert_eq!(alpha.get("tally").unwrap(), Some(json!(2)));
+}
+
+struct CapturingModel {
+    prompts: Arc<Mutex<Vec<String>>>,
+    reply: String,
+}
+
+#[async_trait::async_trait]
+impl agent_models::LLMModel for CapturingModel {
+    async fn generate(
+        &self,
+        prompt: &str,
+        _options: &agent_models::GenerationOptions,
+    ) -> Result<agent_models::LLMResponse, agent_models::ModelError> {
+        self.prompts.lock().unwrap().push(prompt.to_string());
+        Ok(agent_models::LLMResponse {
+            content: self.reply.clone(),
+            ..Default::default()
+        })
+    }
+
+    async fn stream(
+        &self,
+        _prompt: &str,
+        _options: &agent_models::GenerationOptions,
+    ) -> Result<agent_models::EventStream, agent_models::ModelError> {
+        Ok(Box::pin(tokio_stream::iter(Vec::new())))
+    }
+
+    fn supports_tools(&self) -> bool {
+        false
+    }
+}
+
+#[tokio::test]
+async fn planner_renders_few_shot_examples() {
+    let example = Plan {
+        goal: "greet the user".into(),
+        steps: vec![Step {
+            id: "say-hello".into(),
+            description: "log a greeting".into(),
+            tool: Some("log".into()),
+            args: json!({"message": "hello"}),
+            subtasks: vec![],
+            policies: StepPolicies::default(),
+            chain_of_thought: None,
+            sub_plan: None,
+            depends_on: Vec::new(),
+            condition: None,
+            for_each: None,
+            delegate: None,
+        }],
+        metadata: json!({}),
+    };
+    let prompts = Arc::new(Mutex::new(Vec::new()));
+    let model = CapturingModel {
+        prompts: prompts.clone(),
+        reply: serde_json::to_string(&example).unwrap(),
+    };
+    let agent = ModelPlannerAgent::new(model)
+        .with_examples(vec![("greet the user".to_string(), example.clone())]);
+    let ctx = AgentContext {
+        config: AgentConfig::default(),
+        state: AgentState::default(),
+        metadata: json!({"goal": "welcome a new teammate"}),
+        memory: None,
+        tool_permissions: ToolPermissions::default(),
+        cancellation: None,
+    };
+
+    let plan = agent.plan(&ctx).await.expect("plan parsed");
+    assert_eq!(plan.steps[0].id, "say-hello");
+
+    let prompts = prompts.lock().unwrap();
+    assert_eq!(prompts.len(), 1);
+    assert!(prompts[0].contains("Goal: greet the user"));
+    assert!(prompts[0].contains("say-hello"));
+    assert!(prompts[0].contains("Goal: welcome a new teammate"));
+}
+
+#[tokio::test]
+async fn generation_options_apply_per_step() {
+    use agent_models::{GenerationOptions, OpenAIChatModel};
+
+    let agent = ModelPlannerAgent::new(OpenAIChatModel::new("gpt-4o"))
+        .with_generation_options(GenerationOptions::default().with_stop("|"));
+    let mut ctx = AgentContext {
+        config: AgentConfig::default(),
+        state: AgentState::default(),
+        metadata: json!({}),
+        memory: None,
+        tool_permissions: ToolPermissions::default(),
+        cancellation: None,
+    };
+    let step = |args| Step {
+        id: "answer".into(),
+        description: "list the planets | then stop".into(),
+        tool: None,
+        args,
+        subtasks: vec![],
+        policies: StepPolicies::default(),
+        chain_of_thought: None,
+        sub_plan: None,
+        depends_on: Vec::new(),
+        condition: None,
+        for_each: None,
+        delegate: None,
+    };
+
+    let outcome = agent
+        .execute_step(&step(json!({})), &mut ctx)
+        .await
+        .unwrap();
+    assert_eq!(outcome.output["message"], "[chat:gpt-4o] list the planets ");
+
+    let capped = step(json!({"generation": {"max_tokens": 2}}));
+    let outcome = agent.execute_step(&capped, &mut ctx).await.unwrap();
+    assert_eq!(outcome.output["message"], "[chat:gpt-4o] list");
+
+    let invalid = step(json!({"generation": {"max_tokens": "many"}}));
+    assert!(matches!(
+        agent.execute_step(&invalid, &mut ctx).await,
+        Err(AgentError::Validation(_))
+    ));
+}
+
+#[tokio::test]
+async fn seeded_generation_is_reproducible() {
+    use agent_models::{GenerationOptions, LLMModel, RandomReasoner};
+
+    let mut runs = Vec::new();
+    for _ in 0..2 {
+        let mut calls = Vec::new();
+        for seed in 0..32 {
+            let options = GenerationOptions::default().with_seed(seed);
+            calls.push(
+                RandomReasoner
+                    .generate("go", &options)
+                    .await
+                    .unwrap()
+                    .tool_calls
+                    .len(),
+            );
+        }
+        runs.push(calls);
+    }
+    assert_eq!(runs[0], runs[1]);
+    assert!(runs[0].contains(&0) && runs[0].contains(&1));
+}
+
+/// Calls `function` until a tool result arrives, then answers with it.
+struct ToolCallingScript {
+    function: &'static str,
+    always_call: bool,
+    saw_specs: Mutex<Vec<usize>>,
+    saw_messages: Mutex<Vec<usize>>,
+}
+
+#[async_trait::async_trait]
+impl agent_models::LLMModel for ToolCallingScript {
+    async fn generate(
+        &self,
+        _prompt: &str,
+        _options: &agent_models::GenerationOptions,
+    ) -> Result<agent_models::LLMResponse, agent_models::ModelError> {
+        unreachable!("the executor uses chat")
+    }
+
+    async fn stream(
+        &self,
+        _prompt: &str,
+        _options: &agent_models::GenerationOptions,
+    ) -> Result<agent_models::EventStream, agent_models::ModelError> {
+        unreachable!("the executor uses chat")
+    }
+
+    fn supports_tools(&self) -> bool {
+        true
+    }
+
+    async fn chat(
+        &self,
+        messages: &[agent_models::ChatMessage],
+        options: &agent_models::ChatOptions,
+    ) -> Result<agent_models::LLMResponse, agent_models::ModelError> {
+        self.saw_specs.lock().unwrap().push(options.tools.len());
+        self.saw_messages.lock().unwrap().push(messages.len());
+        let last = messages.last().unwrap();
+        if last.role == agent_models::ChatRole::Tool && !self.always_call {
+            return Ok(agent_models::LLMResponse {
+                content: format!("the answer is {}", last.content),
+                ..Default::default()
+            });
+        }
+        Ok(agent_models::LLMResponse {
+            tool_calls: vec![agent_models::ToolCallInfo {
+                name: self.function.into(),
+                arguments: json!({"expression": "2+3"}),
+                id: None,
+            }],
+            ..Default::default()
+        })
+    }
+}
+
+#[tokio::test]
+async fn tool_calling_executor_runs_calls_until_a_final_answer() {
+    use agent_runtime::ToolCallingExecutor;
+
+    let registry = ToolRegistry::new();
+    registry.register(MathTool);
+    let model = ToolCallingScript {
+        function: "math",
+        always_call: false,
+        saw_specs: Mutex::new(Vec::new()),
+        saw_messages: Mutex::new(Vec::new()),
+    };
+    let executor = ToolCallingExecutor::new(model, Arc::new(registry));
+
+    let outcome = executor.run("what is 2+3?").await.unwrap();
+    assert!(outcome.finished);
+    assert_eq!(outcome.iterations, 2);
+    assert_eq!(outcome.calls.len(), 1);
+    let call = &outcome.calls[0];
+    assert_eq!(call.id, "call_1_0");
+    assert_eq!(call.tool.as_deref(), Some("math"));
+    let output = call.result.clone().expect("math succeeds");
+    assert_eq!(outcome.response.content, format!("the answer is {output}"));
+
+    let roles: Vec<_> = outcome.messages.iter().map(|m| m.role).collect();
+    use agent_models::ChatRole::*;
+    assert_eq!(roles, vec![User, Assistant, Tool, Assistant]);
+    assert_eq!(
+        outcome.messages[1].tool_calls[0].id.as_deref(),
+        Some("call_1_0")
+    );
+    assert_eq!(
+        outcome.messages[2].tool_call_id.as_deref(),
+        Some("call_1_0")
+    );
+    assert_eq!(*executor.model.saw_specs.lock().unwrap(), vec![1, 1]);
+}
+
+#[tokio::test]
+async fn tool_calling_executor_reports_unknown_tools_and_stops_at_the_cap() {
+    use agent_runtime::ToolCallingExecutor;
+
+    let model = ToolCallingScript {
+        function: "missing",
+        always_call: true,
+        saw_specs: Mutex::new(Vec::new()),
+        saw_messages: Mutex::new(Vec::new()),
+    };
+    let executor =
+        ToolCallingExecutor::new(model, Arc::new(ToolRegistry::new())).with_max_iterations(3);
+
+    let outcome = executor.run("loop forever").await.unwrap();
+    assert!(!outcome.finished);
+    assert_eq!(outcome.iterations, 3);
+    assert_eq!(outcome.calls.len(), 3);
+    assert_eq!(
+        outcome.calls[0].result,
+        Err("unknown tool `missing`".to_string())
+    );
+    assert_eq!(
+        outcome.messages.last().unwrap().content,
+        json!({"error": "unknown tool `missing`"}).to_string()
+    );
+}
+
+#[tokio::test]
+async fn tool_calling_executor_trims_old_turns_to_the_context_limit() {
+    use agent_models::{ChatMessage, ContextUsage};
+    use agent_runtime::ToolCallingExecutor;
+
+    let model = ToolCallingScript {
+        function: "missing",
+        always_call: true,
+        saw_specs: Mutex::new(Vec::new()),
+        saw_messages: Mutex::new(Vec::new()),
+    };
+    let executor = ToolCallingExecutor::new(model, Arc::new(ToolRegistry::new()))
+        .with_max_iterations(3)
+        .with_context_limit(10);
+
+    // Whitespace tokens: 2 for each prompt, 2 per call and 3 per error.
+    let outcome = executor
+        .run_messages(vec![
+            ChatMessage::system("be brief"),
+            ChatMessage::user("loop forever"),
+        ])
+        .await
+        .unwrap();
+    // The third turn (14 tokens) drops the user prompt and the first call.
+    assert_eq!(*executor.model.saw_messages.lock().unwrap(), vec![2, 4, 3]);
+    assert_eq!(outcome.messages.len(), 8);
+    assert_eq!(outcome.context_usage, Some(ContextUsage::new(9, 10)));
+}
+
+#[tokio::test]
+async fn snapshot_diff_reports_changed_step_output() {
+    let agent = TestAgent;
+    let mut ctx = AgentContext {
+        config: AgentConfig::default(),
+        state: AgentState::default(),
+        metadata: json!({}),
+        memory: None,
+        tool_permissions: ToolPermissions::default(),
+        cancellation: None,
+    };
+    let loop_ctrl = ControlLoop {
+        max_iterations: 2,
+        delay: std::time::Duration::from_millis(0),
+        mode: ControlMode::Deterministic,
+        ..Default::default()
+    };
+    let report = loop_ctrl
+        .run_with_report(&agent, &mut ctx)
+        .await
+        .expect("loop to run");
+    let baseline = report.snapshot();
+    assert!(baseline.diff(&baseline).is_empty());
+
+    let mut changed = report.clone();
+    changed.outcomes[0].output = json!({"ok": false});
+    let diff = changed.snapshot().diff(&baseline);
+
+    assert_eq!(diff.steps.len(), 1);
+    match &diff.steps[0] {
+        StepDiff::Changed { index, fields, .. } => {
+            assert_eq!(*index, 0);
+            assert_eq!(fields, &vec!["output".to_string()]);
+        }
+        other => panic!("unexpected diff: {other:?}"),
+    }
+    assert!(!diff.goal_changed);
+
+    let encoded = serde_json::to_string(&baseline).expect("snapshot serializes");
+    let decoded: agent_runtime::RunSnapshot = serde_json::from_str(&encoded).unwrap();
+    assert_eq!(decoded, baseline);
+}
+
+#[tokio::test]
+async fn event_bus_fans_out_to_every_subscriber() {
+    let bus = EventBus::new(16);
+    let mut ui = bus.subscribe();
+    let mut metrics = bus.subscribe();
+
+    let agent = TestAgent;
+    let mut ctx = AgentContext {
+        config: AgentConfig::default(),
+        state: AgentState::default(),
+        metadata: json!({}),
+        memory: None,
+        tool_permissions: ToolPermissions::default(),
+        cancellation: None,
+    };
+    let loop_ctrl = ControlLoop {
+        max_iterations: 2,
+        mode: ControlMode::Deterministic,
+        ..Default::default()
+    }
+    .with_events(bus.clone());
+    loop_ctrl.run(&agent, &mut ctx).await.expect("loop to run");
+
+    for receiver in [&mut ui, &mut metrics] {
+        let mut kinds = Vec::new();
+        while let Ok(event) = receiver.try_recv() {
+            kinds.push(match event {
+                AgentEvent::RunStarted { .. } => "run_started",
+                AgentEvent::PlanCreated { .. } => "plan_created",
+                AgentEvent::StepStarted { .. } => "step_started",
+                AgentEvent::ToolCalled { .. } => "tool_called",
+                AgentEvent::TokenDelta { .. } => "token_delta",
+                AgentEvent::StepCompleted { .. } => "step_completed",
+                AgentEvent::RunFinished { .. } => "run_finished",
+            });
+        }
+        assert_eq!(
+            kinds,
+            vec![
+                "run_started",
+                "plan_created",
+                "step_started",
+                "step_completed",
+                "run_finished"
+            ]
+        );
+    }
+}
+
+#[tokio::test]
+async fn event_bus_drops_oldest_events_for_lagging_subscribers() {
+    let bus = EventBus::new(2);
+    let mut slow = bus.subscribe();
+    for steps in 0..5 {
+        bus.publish(AgentEvent::RunFinished { steps });
+    }
+
+    assert!(matches!(
+        slow.recv().await,
+        Err(tokio::sync::broadcast::error::RecvError::Lagged(3))
+    ));
+    assert!(matches!(
+        slow.recv().await,
+        Ok(AgentEvent::RunFinished { steps: 3 })
+    ));
+}
+
+/// Streams its answer as token deltas on the loop's bus.
+#[derive(Debug)]
+struct StreamingAgent {
+    events: EventBus,
+}
+
+#[async_trait::async_trait]
+impl Agent for StreamingAgent {
+    async fn plan(&self, _ctx: &AgentContext) -> Result<Plan, AgentError> {
+        Ok(Plan {
+            goal: "answer".into(),
+            steps: vec![Step {
+                id: "lookup".into(),
+                description: "look it up".into(),
+                tool: Some("search".into()),
+                args: json!({"query": "rust"}),
+                subtasks: vec![],
+                policies: StepPolicies::default(),
+                chain_of_thought: None,
+                sub_plan: None,
+                depends_on: Vec::new(),
+                condition: None,
+                for_each: None,
+                delegate: None,
+            }],
+            metadata: json!({}),
+        })
+    }
+
+    async fn execute_step(
+        &self,
+        step: &Step,
+        _ctx: &mut AgentContext,
+    ) -> Result<StepOutcome, AgentError> {
+        for text in ["Ru", "st"] {
+            self.events.publish(AgentEvent::TokenDelta {
+                step_id: step.id.clone(),
+                text: text.into(),
+            });
+        }
+        Ok(StepOutcome::success(step.id.clone(), json!("Rust")))
+    }
+}
+
+#[tokio::test]
+async fn event_channels_stream_a_run_as_it_happens() {
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(32);
+    let loop_ctrl = ControlLoop {
+        max_iterations: 2,
+        ..Default::default()
+    }
+    .with_event_channel(sender);
+    let agent = StreamingAgent {
+        events: loop_ctrl.events.clone().unwrap(),
+    };
+    let mut ctx = AgentContext::default();
+    loop_ctrl.run(&agent, &mut ctx).await.unwrap();
+    drop(loop_ctrl);
+    drop(agent);
+
+    let mut events = Vec::new();
+    while let Some(event) = receiver.recv().await {
+        events.push(event);
+    }
+    let kinds: Vec<String> = events
+        .iter()
+        .map(|event| {
+            serde_json::to_value(event).unwrap()["type"]
+                .as_str()
+                .unwrap()
+                .to_string()
+        })
+        .collect();
+    assert_eq!(
+        kinds,
+        vec![
+            "RunStarted",
+            "PlanCreated",
+            "StepStarted",
+            "ToolCalled",
+            "TokenDelta",
+            "TokenDelta",
+            "StepCompleted",
+            "RunFinished"
+        ]
+    );
+    assert!(matches!(
+        &events[3],
+        AgentEvent::ToolCalled { call, .. } if call.name == "search"
+    ));
+    assert!(matches!(
+        &events[5],
+        AgentEvent::TokenDelta { text, .. } if text == "st"
+    ));
+}
+
+#[tokio::test]
+async fn event_channels_drop_events_while_full() {
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(2);
+    let bus = EventBus::new(16).with_channel(sender);
+    for steps in 0..4 {
+        bus.publish(AgentEvent::RunFinished { steps });
+    }
+    drop(bus);
+
+    let mut kept = Vec::new();
+    while let Some(AgentEvent::RunFinished { steps }) = receiver.recv().await {
+        kept.push(steps);
+    }
+    assert_eq!(kept, vec![0, 1]);
+}
+
+#[tokio::test]
+async fn model_planner_streams_token_deltas_when_given_a_bus() {
+    let bus = EventBus::new(16);
+    let mut receiver = bus.subscribe();
+    let agent = ModelPlannerAgent::new(agent_models::StubModel).with_events(bus);
+    let step = Step {
+        id: "answer".into(),
+        description: "say hi".into(),
+        tool: None,
+        args: json!({}),
+        subtasks: vec![],
+        policies: StepPolicies::default(),
+        chain_of_thought: None,
+        sub_plan: None,
+        depends_on: Vec::new(),
+        condition: None,
+        for_each: None,
+        delegate: None,
+    };
+    let outcome = agent
+        .execute_step(&step, &mut AgentContext::default())
+        .await
+        .unwrap();
+
+    let mut streamed = String::new();
+    while let Ok(event) = receiver.try_recv() {
+        match event {
+            AgentEvent::TokenDelta { step_id, text } => {
+                assert_eq!(step_id, "answer");
+                streamed.push_str(&text);
+            }
+            other => panic!("unexpected event: {other:?}"),
+        }
+    }
+    assert!(streamed.starts_with("echo"));
+    assert_eq!(outcome.output, json!({"message": streamed}));
+}
+
+fn nested_plan(levels: usize) -> Plan {
+    let mut step = Step {
+        id: "leaf".into(),
+        description: "innermost step".into(),
+        tool: None,
+        args: json!({}),
+        subtasks: vec![],
+        policies: StepPolicies::default(),
+        chain_of_thought: None,
+        sub_plan: None,
+        depends_on: Vec::new(),
+        condition: None,
+        for_each: None,
+        delegate: None,
+    };
+    for level in 0..levels {
+        let inner = Plan {
+            goal: format!("level {level}"),
+            steps: vec![step],
+            metadata: json!({}),
+        };
+        step = Step {
+            id: format!("wrapper-{level}"),
+            description: "delegates to a sub-plan".into(),
+            tool: None,
+            args: json!({}),
+            subtasks: vec![],
+            policies: StepPolicies::default(),
+            chain_of_thought: None,
+            sub_plan: None,
+            depends_on: Vec::new(),
+            condition: None,
+            for_each: None,
+            delegate: None,
+        }
+        .with_sub_plan(inner);
+    }
+    Plan {
+        goal: "nested".into(),
+        steps: vec![step],
+        metadata: json!({}),
+    }
+}
+
+#[derive(Debug)]
+struct NestedPlanAgent {
+    levels: usize,
+}
+
+#[async_trait::async_trait]
+impl Agent for NestedPlanAgent {
+    async fn plan(&self, _ctx: &agent_core::AgentContext) -> Result<Plan, AgentError> {
+        Ok(nested_plan(self.levels))
+    }
+
+    async fn execute_step(
+        &self,
+        step: &Step,
+        _ctx: &mut AgentContext,
+    ) -> Result<StepOutcome, AgentError> {
+        Ok(StepOutcome::success(step.id.clone(), json!({"ok": true})))
+    }
+}
+
+#[tokio::test]
+async fn sub_plans_run_within_depth_limit() {
+    let agent = NestedPlanAgent { levels: 3 };
+    let mut ctx = AgentContext::default();
+    let loop_ctrl = ControlLoop {
+        max_iterations: 1,
+        ..Default::default()
+    };
+    let outcomes = loop_ctrl.run(&agent, &mut ctx).await.expect("loop to run");
+    assert_eq!(outcomes.len(), 1);
+    assert_eq!(outcomes[0].step_id, "leaf");
+}
+
+#[tokio::test]
+async fn runaway_sub_plan_nesting_is_rejected() {
+    let agent = NestedPlanAgent { levels: 100 };
+    let mut ctx = AgentContext::default();
+    let loop_ctrl = ControlLoop {
+        max_iterations: 1,
+        max_plan_depth: 4,
+        ..Default::default()
+    };
+    let err = loop_ctrl.run(&agent, &mut ctx).await.unwrap_err();
+    assert!(matches!(err, AgentError::Validation(_)));
+}
+
+#[derive(Debug)]
+struct StuckAgent;
+
+#[async_trait::async_trait]
+impl Agent for StuckAgent {
+    async fn plan(&self, _ctx: &agent_core::AgentContext) -> Result<Plan, AgentError> {
+        Ok(Plan {
+            goal: "stuck".into(),
+            steps: vec![Step {
+                id: "search".into(),
+                description: "search again".into(),
+                tool: None,
+                args: json!({"query": "same"}),
+                subtasks: vec![],
+                policies: StepPolicies::default(),
+                chain_of_thought: None,
+                sub_plan: None,
+                depends_on: Vec::new(),
+                condition: None,
+                for_each: None,
+                delegate: None,
+            }],
+            metadata: json!({}),
+        })
+    }
+
+    async fn execute_step(
+        &self,
+        step: &Step,
+        _ctx: &mut AgentContext,
+    ) -> Result<StepOutcome, AgentError> {
+        Ok(StepOutcome::success(step.id.clone(), json!({"ok": true})))
+    }
+}
+
+#[tokio::test]
+async fn reactive_loop_detection_stops_repeated_steps() {
+    let mut ctx = AgentContext::default();
+    let loop_ctrl = ControlLoop {
+        max_iterations: 10,
+        mode: ControlMode::Reactive,
+        loop_detection: Some(3),
+        ..Default::default()
+    };
+    let report = loop_ctrl
+        .run_with_report(&StuckAgent, &mut ctx)
+        .await
+        .expect("loop to run");
+
+    assert_eq!(report.outcomes.len(), 2);
+    assert_eq!(
+        report.stop_reason,
+        StopReason::LoopDetected {
+            step_id: "search".into(),
+            repeats: 3
+        }
+    );
+    assert!(report.outcomes[1]
+        .control_notes
+        .iter()
+        .any(|note| note.starts_with("loop detected")));
+
+    let unguarded = ControlLoop {
+        max_iterations: 4,
+        mode: ControlMode::Reactive,
+        ..Default::default()
+    };
+    let report = unguarded
+        .run_with_report(&StuckAgent, &mut ctx)
+        .await
+        .expect("loop to run");
+    assert_eq!(report.outcomes.len(), 4);
+    assert_eq!(report.stop_reason, StopReason::MaxIterations);
+}
+
+#[derive(Debug)]
+struct ToolUsingAgent;
+
+#[async_trait::async_trait]
+impl Agent for ToolUsingAgent {
+    async fn plan(&self, _ctx: &agent_core::AgentContext) -> Result<Plan, AgentError> {
+        let step = |id: &str, tool: &str| Step {
+            id: id.into(),
+            description: format!("use {tool}"),
+            tool: Some(tool.into()),
+            args: json!({}),
+            subtasks: vec![],
+            policies: StepPolicies::default(),
+            chain_of_thought: None,
+            sub_plan: None,
+            depends_on: Vec::new(),
+            condition: None,
+            for_each: None,
+            delegate: None,
+        };
+        Ok(Plan {
+            goal: "tools".into(),
+            steps: vec![step("lookup", "search"), step("save", "file")],
+            metadata: json!({}),
+        })
+    }
+
+    async fn execute_step(
+        &self,
+        step: &Step,
+        _ctx: &mut AgentContext,
+    ) -> Result<StepOutcome, AgentError> {
+        Ok(StepOutcome::success(step.id.clone(), json!({"ok": true})))
+    }
+}
+
+#[tokio::test]
+async fn orchestrator_limits_agents_to_their_tool_subset() {
+    let mut orchestrator =
+        MultiAgentOrchestrator::new(InMemoryBus::new(), MemoryTopology::Isolated);
+    orchestrator.register_agent_with_tools(
+        "researcher",
+        AgentContext::default(),
+        Some(vec!["search".into()]),
+    );
+    orchestrator.register_agent("admin", AgentContext::default());
+    let control = ControlLoop {
+        max_iterations: 4,
+        ..Default::default()
+    };
+
+    let outcomes = orchestrator
+        .call_agent("researcher", &ToolUsingAgent, &control)
+        .await
+        .expect("loop to run");
+    assert!(outcomes[0].success);
+    assert!(!outcomes[1].success);
+    assert!(outcomes[1].output["error"]
+        .as_str()
+        .unwrap()
+        .contains("not permitted"));
+
+    let outcomes = orchestrator
+        .call_agent("admin", &ToolUsingAgent, &control)
+        .await
+        .expect("loop to run");
+    assert!(outcomes.iter().all(|o| o.success));
+}
+
+/// Chats with the model once per step, with prompts of different lengths.
+#[derive(Debug)]
+struct ChattyAgent {
+    tracker: agent_models::UsageTracker,
+    prompts: Vec<&'static str>,
+}
+
+#[async_trait::async_trait]
+impl Agent for ChattyAgent {
+    async fn plan(&self, ctx: &AgentContext) -> Result<Plan, AgentError> {
+        let mut plan = TestAgent.plan(ctx).await?;
+        let template = plan.steps.remove(0);
+        plan.steps = (0..self.prompts.len())
+            .map(|i| Step {
+                id: i.to_string(),
+                ..template.clone()
+            })
+            .collect();
+        Ok(plan)
+    }
+
+    async fn execute_step(
+        &self,
+        step: &Step,
+        _ctx: &mut AgentContext,
+    ) -> Result<StepOutcome, AgentError> {
+        let prompt = self.prompts[step.id.parse::<usize>().unwrap()];
+        let outcome = agent_runtime::ToolCallingExecutor::new(
+            agent_models::StubModel,
+            Arc::new(ToolRegistry::new()),
+        )
+        .with_context_limit(10)
+        .with_usage_tracker(self.tracker.clone())
+        .run(prompt)
+        .await
+        .map_err(|e| AgentError::Model(e.to_string()))?;
+        Ok(StepOutcome::success(
+            step.id.clone(),
+            json!(outcome.response.content),
+        ))
+    }
+}
+
+#[tokio::test]
+async fn run_report_tracks_peak_context_usage() {
+    use agent_models::ContextUsage;
+
+    let tracker = agent_models::UsageTracker::new();
+    let agent = ChattyAgent {
+        tracker: tracker.clone(),
+        prompts: vec![
+            "summarize the report",
+            "summarize the q3 revenue report for the board",
+            "thanks",
+        ],
+    };
+    let control = ControlLoop {
+        max_iterations: 5,
+        ..Default::default()
+    }
+    .with_usage_tracker(tracker);
+
+    let report = control
+        .run_with_report(&agent, &mut AgentContext::default())
+        .await
+        .unwrap();
+    assert_eq!(report.outcomes.len(), 3);
+    assert_eq!(report.context_usage, Some(ContextUsage::new(8, 10)));
+
+    let untracked = ControlLoop {
+        max_iterations: 5,
+        ..Default::default()
+    };
+    let report = untracked
+        .run_with_report(&agent, &mut AgentContext::default())
+        .await
+        .unwrap();
+    assert_eq!(report.context_usage, None);
+}
+
+#[test]
+fn run_report_accumulates_estimated_cost() {
+    use agent_models::{LLMResponse, ModelMetadata, UsageMetrics};
+
+    let priced = LLMResponse {
+        usage: UsageMetrics {
+            prompt_tokens: 2_000,
+            completion_tokens: 1_000,
+        },
+        metadata: ModelMetadata {
+            provider: "openai".into(),
+            model: "gpt-4o-mini".into(),
+            ..ModelMetadata::default()
+        },
+        ..LLMResponse::default()
+    };
+    let unpriced = LLMResponse {
+        metadata: ModelMetadata {
+            provider: "stub".into(),
+            ..ModelMetadata::default()
+        },
+        ..priced.clone()
+    };
+
+    let mut report = RunReport::default();
+    report.record_response(&priced);
+    report.record_response(&unpriced);
+    assert_eq!(report.usage.prompt_tokens, 4_000);
+    assert!((report.estimated_cost_usd - 0.0009).abs() < 1e-12);
+}
+
+/// Asks its model once to plan and once per step.
+#[derive(Debug)]
+struct MeteredAgent {
+    model: agent_models::MiddlewareModel<agent_models::StubModel>,
+}
+
+#[async_trait::async_trait]
+impl Agent for MeteredAgent {
+    async fn plan(&self, _ctx: &AgentContext) -> Result<Plan, AgentError> {
+        use agent_models::{GenerationOptions, LLMModel};
+
+        self.model
+            .generate("plan", &GenerationOptions::default())
+            .await?;
+        let step = |id: &str, description: &str| Step {
+            id: id.into(),
+            description: description.into(),
+            tool: None,
+            args: json!({}),
+            subtasks: vec![],
+            policies: StepPolicies::default(),
+            chain_of_thought: None,
+            sub_plan: None,
+            depends_on: Vec::new(),
+            condition: None,
+            for_each: None,
+            delegate: None,
+        };
+        Ok(Plan {
+            goal: "metered".into(),
+            steps: vec![step("short", "hi"), step("long", "a much longer prompt")],
+            metadata: json!({}),
+        })
+    }
+
+    async fn execute_step(
+        &self,
+        step: &Step,
+        _ctx: &mut AgentContext,
+    ) -> Result<StepOutcome, AgentError> {
+        use agent_models::{GenerationOptions, LLMModel};
+
+        let response = self
+            .model
+            .generate(&step.description, &GenerationOptions::default())
+            .await?;
+        Ok(StepOutcome {
+            step_id: step.id.clone(),
+            output: json!(response.content),
+            observations: vec![],
+            success: true,
+            retries: 0,
+            fallback_used: false,
+            control_notes: vec![],
+            fallback_applied: None,
+            failure_kind: None,
+            redactions: Vec::new(),
+        })
+    }
+}
+
+#[tokio::test]
+async fn usage_tracker_totals_each_step_and_the_run() {
+    use agent_models::{MiddlewareModel, StubModel, UsageTracker};
+
+    let tracker = UsageTracker::new();
+    let agent = MeteredAgent {
+        model: MiddlewareModel::new(StubModel).with_middleware(tracker.clone()),
+    };
+    let mut ctx = AgentContext {
+        config: AgentConfig {
+            name: "metered".into(),
+            description: None,
+            max_iterations: 5,
+            retry_policy: RetryPolicy::default(),
+        },
+        state: AgentState::default(),
+        metadata: json!({}),
+        memory: None,
+        tool_permissions: ToolPermissions::default(),
+        cancellation: None,
+    };
+    let loop_ctrl = ControlLoop {
+        max_iterations: 5,
+        ..Default::default()
+    }
+    .with_usage_tracker(tracker.clone());
+
+    let report = loop_ctrl.run_with_report(&agent, &mut ctx).await.unwrap();
+    // StubModel counts whitespace tokens and answers "echo: <prompt>".
+    assert_eq!(report.step_usage["short"].prompt_tokens, 1);
+    assert_eq!(report.step_usage["long"].prompt_tokens, 4);
+    assert_eq!(report.step_usage["long"].completion_tokens, 5);
+    assert_eq!(report.usage.prompt_tokens, 6);
+    assert_eq!(tracker.totals().calls, 3);
+
+    let report = loop_ctrl.run_with_report(&agent, &mut ctx).await.unwrap();
+    assert_eq!(report.usage.prompt_tokens, 6);
+    assert_eq!(tracker.totals().calls, 6);
+}
+
+#[tokio::test]
+async fn default_chat_flattens_roles_into_the_prompt() {
+    use agent_models::{ChatMessage, ChatOptions, LLMModel, LLMResponse, StubModel, ToolCallInfo};
+
+    let call = LLMResponse {
+        tool_calls: vec![ToolCallInfo {
+            name: "math".into(),
+            arguments: json!({"expression": "2+2"}),
+            id: Some("call_7".into()),
+        }],
+        ..LLMResponse::default()
+    };
+    let messages = [
+        ChatMessage::system("answer tersely"),
+        ChatMessage::user("what is 2+2?"),
+        ChatMessage::assistant(&call),
+        ChatMessage::tool("call_7", "4"),
+    ];
+
+    let response = StubModel
+        .chat(&messages, &ChatOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(
+        response.content,
+        "echo: system: answer tersely\nuser: what is 2+2?\nassistant: \n[call math({\"expression\":\"2+2\"})]\ntool (call_7): 4"
+    );
+}
+
+/// Fails every call with the same error.
+struct FailingModel(agent_models::ModelError);
+
+#[async_trait::async_trait]
+impl agent_models::LLMModel for FailingModel {
+    async fn generate(
+        &self,
+        _prompt: &str,
+        _options: &agent_models::GenerationOptions,
+    ) -> Result<agent_models::LLMResponse, agent_models::ModelError> {
+        Err(self.0.clone())
+    }
+
+    async fn stream(
+        &self,
+        _prompt: &str,
+        _options: &agent_models::GenerationOptions,
+    ) -> Result<agent_models::EventStream, agent_models::ModelError> {
+        Err(self.0.clone())
+    }
+
+    fn supports_tools(&self) -> bool {
+        false
+    }
+}
+
+#[tokio::test]
+async fn model_errors_map_into_agent_errors() {
+    use agent_models::ModelError;
+    use std::time::Duration;
+
+    let mut ctx = AgentContext::default();
+    let planner = ModelPlannerAgent::new(FailingModel(ModelError::Auth("bad key".into())));
+    assert!(matches!(
+        planner.plan(&ctx).await,
+        Err(AgentError::Model(message)) if message.contains("bad key")
+    ));
+
+    let step = Step {
+        id: "ask".into(),
+        description: "ask".into(),
+        tool: None,
+        args: json!({}),
+        subtasks: vec![],
+        policies: StepPolicies::default(),
+        chain_of_thought: None,
+        sub_plan: None,
+        depends_on: Vec::new(),
+        condition: None,
+        for_each: None,
+        delegate: None,
+    };
+    let throttled = ModelPlannerAgent::new(FailingModel(ModelError::RateLimited {
+        retry_after: Some(Duration::from_secs(2)),
+        message: "slow down".into(),
+    }));
+    assert!(matches!(
+        throttled.execute_step(&step, &mut ctx).await,
+        Err(AgentError::ModelRateLimited {
+            retry_after_ms: Some(2000)
+        })
+    ));
+
+    let filtered = ModelPlannerAgent::new(FailingModel(ModelError::ContentFilter("hate".into())));
+    assert!(matches!(
+        filtered.execute_step(&step, &mut ctx).await,
+        Err(AgentError::Safety(_))
+    ));
+    let slow = ModelPlannerAgent::new(FailingModel(ModelError::Timeout));
+    assert!(matches!(
+        slow.execute_step(&step, &mut ctx).await,
+        Err(AgentError::Timeout)
+    ));
+}
+
+#[tokio::test]
+async fn offline_stream_ends_with_usage_and_done() {
+    use agent_models::{
+        text_deltas, FinishReason, GenerationOptions, LLMModel, LLMResponse, OpenAIChatModel,
+        StreamEvent,
+    };
+    use tokio_stream::StreamExt;
+
+    let model = OpenAIChatModel::new("gpt-4o-mini");
+    let options = GenerationOptions::default();
+    let events: Vec<StreamEvent> = model
+        .stream("hello there", &options)
+        .await
+        .unwrap()
+        .collect()
+        .await;
+    let mut response = LLMResponse::default();
+    for event in &events {
+        response.apply_event(event);
+    }
+    assert!(matches!(
+        events[events.len() - 2],
+        StreamEvent::Usage { .. }
+    ));
+    assert_eq!(response.finish_reason, Some(FinishReason::Stop));
+    assert!(response.usage.completion_tokens > 0);
+
+    let tokens: Vec<String> = text_deltas(model.stream("hello there", &options).await.unwrap())
+        .collect()
+        .await;
+    assert_eq!(tokens.concat(), response.content);
+}
+
+#[derive(Debug)]
+struct PausingAgent {
+    pause: PauseHandle,
+    executed: Arc<Mutex<Vec<String>>>,
+}
+
+#[async_trait::async_trait]
+impl Agent for PausingAgent {
+    async fn plan(&self, _ctx: &AgentContext) -> Result<Plan, AgentError> {
+        let step = |id: &str| Step {
+            id: id.into(),
+            description: id.into(),
+            tool: None,
+            args: json!({}),
+            subtasks: vec![],
+            policies: StepPolicies::default(),
+            chain_of_thought: None,
+            sub_plan: None,
+            depends_on: Vec::new(),
+            condition: None,
+            for_each: None,
+            delegate: None,
+        };
+        Ok(Plan {
+            goal: "pause".into(),
+            steps: vec![step("ask"), step("answer"), step("close")],
+            metadata: json!({}),
+        })
+    }
+
+    async fn execute_step(
+        &self,
+        step: &Step,
+        _ctx: &mut AgentContext,
+    ) -> Result<StepOutcome, AgentError> {
+        self.executed.lock().unwrap().push(step.id.clone());
+        if step.id == "ask" {
+            // Wait for external input before continuing.
+            self.pause.pause();
+        }
+        Ok(StepOutcome::success(step.id.clone(), json!({})))
+    }
+}
+
+#[tokio::test]
+async fn paused_loop_resumes_from_the_same_iteration() {
+    let pause = PauseHandle::new();
+    let executed = Arc::new(Mutex::new(Vec::new()));
+    let agent = PausingAgent {
+        pause: pause.clone(),
+        executed: executed.clone(),
+    };
+    let control = ControlLoop {
+        max_iterations: 3,
+        ..Default::default()
+    }
+    .with_pause(pause.clone());
+
+    let run = tokio::spawn(async move {
+        let mut ctx = AgentContext::default();
+        let report = control.run_with_report(&agent, &mut ctx).await;
+        (report, ctx.state.iteration)
+    });
+
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    assert!(pause.is_paused());
+    assert_eq!(*executed.lock().unwrap(), vec!["ask"]);
+
+    pause.resume();
+    let (report, last_iteration) = run.await.unwrap();
+    let report = report.expect("run completes");
+    assert_eq!(*executed.lock().unwrap(), vec!["ask", "answer", "close"]);
+    assert_eq!(report.outcomes.len(), 3);
+    assert_eq!(last_iteration, 2);
+}
+
+#[derive(Debug)]
+struct LeakyAgent;
+
+#[async_trait::async_trait]
+impl Agent for LeakyAgent {
+    async fn plan(&self, _ctx: &agent_core::AgentContext) -> Result<Plan, AgentError> {
+        Ok(Plan {
+            goal: "leak".into(),
+            steps: vec![],
+            metadata: json!({}),
+        })
+    }
+
+    async fn execute_step(
+        &self,
+        step: &Step,
+        _ctx: &mut AgentContext,
+    ) -> Result<StepOutcome, AgentError> {
+        Ok(StepOutcome::success(
+            step.id.clone(),
+            json!({"token": "tok_live_1", "note": "call 555-1234"}),
+        ))
+    }
+}
+
+#[tokio::test]
+async fn step_safety_policy_redacts_outputs() {
+    let mut step = Step {
+        id: "lookup".into(),
+        description: "look up a contact".into(),
+        tool: Some("crm".into()),
+        args: json!({}),
+        subtasks: vec![],
+        policies: StepPolicies::default(),
+        chain_of_thought: None,
+        sub_plan: None,
+        depends_on: Vec::new(),
+        condition: None,
+        for_each: None,
+        delegate: None,
+    };
+    step.policies.safety.redaction_rules = vec!["/token".into(), r"\d{3}-\d{4}".into()];
+    let mut ctx = AgentContext::default();
+
+    let outcome = StepExecutor::run_step(step.clone(), &LeakyAgent, &mut ctx).await;
+    assert!(outcome.success);
+    assert_eq!(outcome.output["token"], "[REDACTED]");
+    assert_eq!(outcome.output["note"], "call [REDACTED]");
+    let paths: Vec<&str> = outcome.redactions.iter().map(|r| r.path.as_str()).collect();
+    assert_eq!(paths, vec!["/token", "/note"]);
+    assert!(outcome.redactions.iter().all(|r| r.tool == "crm"));
+
+    // Invalid rules fail the step before the agent acts.
+    step.policies.safety.redaction_rules = vec!["(".into()];
+    let agent = FlakyAgent {
+        attempts: Arc::new(Mutex::new(0)),
+    };
+    let outcome = StepExecutor::run_step(step, &agent, &mut ctx).await;
+    assert!(!outcome.success);
+    assert!(outcome.output["error"]
+        .as_str()
+        .unwrap()
+        .contains("invalid redaction rule"));
+    assert_eq!(*agent.attempts.lock().unwrap(), 0);
+}
+
+/// Sleeps in every step and tracks how many steps were in flight at once.
+#[derive(Debug, Default)]
+struct GraphAgent {
+    in_flight: std::sync::atomic::AtomicUsize,
+    peak: std::sync::atomic::AtomicUsize,
+    finished: Mutex<Vec<String>>,
+}
+
+#[async_trait::async_trait]
+impl Agent for GraphAgent {
+    async fn plan(&self, _ctx: &AgentContext) -> Result<Plan, AgentError> {
+        let step = |id: &str| Step {
+            id: id.into(),
+            description: id.into(),
+            tool: None,
+            args: json!({}),
+            subtasks: vec![],
+            policies: StepPolicies::default(),
+            chain_of_thought: None,
+            sub_plan: None,
+            depends_on: Vec::new(),
+            condition: None,
+            for_each: None,
+            delegate: None,
+        };
+        Ok(Plan {
+            goal: "graph".into(),
+            steps: vec![
+                step("report")
+                    .with_dependency("fetch-a")
+                    .with_dependency("fetch-b")
+                    .with_dependency("fetch-c"),
+                step("fetch-a"),
+                step("fetch-b"),
+                step("fetch-c"),
+                step("broken"),
+                step("after-broken").with_dependency("broken"),
+                step("after-that").with_dependency("after-broken"),
+            ],
+            metadata: json!({}),
+        })
+    }
+
+    async fn execute_step(
+        &self,
+        step: &Step,
+        _ctx: &mut AgentContext,
+    ) -> Result<StepOutcome, AgentError> {
+        use std::sync::atomic::Ordering;
+        let now = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        self.peak.fetch_max(now, Ordering::SeqCst);
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        self.in_flight.fetch_sub(1, Ordering::SeqCst);
+        self.finished.lock().unwrap().push(step.id.clone());
+        if step.id == "broken" {
+            return Ok(StepOutcome::failure(
+                step.id.clone(),
+                AgentError::Validation("no".into()),
+            ));
+        }
+        Ok(StepOutcome::success(step.id.clone(), json!({"ok": true})))
+    }
+}
+
+#[tokio::test]
+async fn dependency_graphs_run_independent_steps_concurrently() {
+    let agent = GraphAgent::default();
+    let mut ctx = AgentContext {
+        config: AgentConfig::default(),
+        state: AgentState::default(),
+        metadata: json!({}),
+        memory: None,
+        tool_permissions: ToolPermissions::default(),
+        cancellation: None,
+    };
+    let control = ControlLoop {
+        max_iterations: 10,
+        mode: ControlMode::Deterministic,
+        ..Default::default()
+    }
+    .with_max_concurrency(2);
+    let report = control.run_with_report(&agent, &mut ctx).await.unwrap();
+
+    assert_eq!(report.stop_reason, StopReason::Completed);
+    assert_eq!(report.outcomes.len(), 7);
+    assert_eq!(agent.peak.load(std::sync::atomic::Ordering::SeqCst), 2);
+
+    let finished = agent.finished.lock().unwrap().clone();
+    let position = |id: &str| finished.iter().position(|done| done == id).unwrap();
+    assert!(["fetch-a", "fetch-b", "fetch-c"]
+        .iter()
+        .all(|fetch| position(fetch) < position("report")));
+    assert!(!finished.iter().any(|id| id.starts_with("after-")));
+
+    let skipped: Vec<&StepOutcome> = report
+        .outcomes
+        .iter()
+        .filter(|outcome| outcome.step_id.starts_with("after-"))
+        .collect();
+    assert_eq!(skipped.len(), 2);
+    assert!(skipped.iter().all(|outcome| !outcome.success));
+    assert!(skipped[0]
+        .control_notes
+        .contains(&"skipped: dependency broken failed".to_string()));
+
+    let limited = ControlLoop {
+        max_iterations: 3,
+        mode: ControlMode::Deterministic,
+        ..Default::default()
+    };
+    let report = limited.run_with_report(&agent, &mut ctx).await.unwrap();
+    assert_eq!(report.stop_reason, StopReason::MaxIterations);
+    assert_eq!(report.outcomes.len(), 3);
+}
+
+/// Each step sleeps for the milliseconds in its `sleep_ms` arg.
+#[derive(Debug)]
+struct SleepyAgent {
+    steps: Vec<(String, u64, Option<u64>)>,
+}
+
+#[async_trait::async_trait]
+impl Agent for SleepyAgent {
+    async fn plan(&self, _ctx: &AgentContext) -> Result<Plan, AgentError> {
+        let steps = self
+            .steps
+            .iter()
+            .map(|(id, sleep_ms, timeout_ms)| Step {
+                id: id.clone(),
+                description: "sleep".into(),
+                tool: None,
+                args: json!({"sleep_ms": sleep_ms}),
+                subtasks: vec![],
+                policies: StepPolicies {
+                    retry: RetryPolicy {
+                        max_retries: 1,
+                        backoff_ms: 0,
+                        jitter: false,
+                        max_delay_ms: None,
+                    },
+                    timeout_ms: *timeout_ms,
+                    ..Default::default()
+                },
+                chain_of_thought: None,
+                sub_plan: None,
+                depends_on: Vec::new(),
+                condition: None,
+                for_each: None,
+                delegate: None,
+            })
+            .collect();
+        Ok(Plan {
+            goal: "sleep".into(),
+            steps,
+            metadata: json!({}),
+        })
+    }
+
+    async fn execute_step(
+        &self,
+        step: &Step,
+        _ctx: &mut AgentContext,
+    ) -> Result<StepOutcome, AgentError> {
+        let sleep_ms = step.args["sleep_ms"].as_u64().unwrap_or_default();
+        tokio::time::sleep(std::time::Duration::from_millis(sleep_ms)).await;
+        Ok(StepOutcome::success(
+            step.id.clone(),
+            json!({"slept": sleep_ms}),
+        ))
+    }
+}
+
+#[tokio::test]
+async fn step_timeouts_and_run_deadlines_stop_hung_work() {
+    let mut ctx = AgentContext {
+        config: AgentConfig::default(),
+        state: AgentState::default(),
+        metadata: json!({}),
+        memory: None,
+        tool_permissions: ToolPermissions::default(),
+        cancellation: None,
+    };
+    let agent = SleepyAgent {
+        steps: vec![
+            ("hung".into(), 10_000, Some(20)),
+            ("quick".into(), 1, Some(1_000)),
+        ],
+    };
+    let control = ControlLoop {
+        max_iterations: 5,
+        ..Default::default()
+    };
+    let started = std::time::Instant::now();
+    let report = control.run_with_report(&agent, &mut ctx).await.unwrap();
+    assert!(started.elapsed() < std::time::Duration::from_secs(5));
+    assert_eq!(report.stop_reason, StopReason::Completed);
+    assert!(!report.outcomes[0].success);
+    assert_eq!(report.outcomes[0].output["error"], "timeout");
+    assert!(report.outcomes[1].success);
+
+    // Fallback retries are bounded by the same step timeout.
+    let agent = SleepyAgent {
+        steps: vec![("hung".into(), 10_000, Some(20))],
+    };
+    let mut step = agent.plan(&ctx).await.unwrap().steps.remove(0);
+    step.policies.retry.max_retries = 0;
+    step.policies.fallback = Some(agent_core::FallbackPolicy {
+        strategy: agent_core::FallbackStrategy::RetryWithLimit {
+            max_additional_retries: 1,
+        },
+        reason: None,
+    });
+    let started = std::time::Instant::now();
+    let outcome = StepExecutor::run_step(step, &agent, &mut ctx).await;
+    assert!(started.elapsed() < std::time::Duration::from_secs(5));
+    assert!(!outcome.success);
+    assert_eq!(outcome.output["error"], "timeout");
+    assert_eq!(
+        outcome.fallback_applied,
+        Some(agent_core::FallbackKind::RetryWithLimit)
+    );
+
+    let agent = SleepyAgent {
+        steps: vec![("hung".into(), 10_000, Some(20))],
+    };
+    let mut step = agent.plan(&ctx).await.unwrap().steps.remove(0);
+    step.policies.retry.max_retries = 0;
+    step.policies.fallback = Some(agent_core::FallbackPolicy {
+        strategy: agent_core::FallbackStrategy::AlternateTool { tool: "alt".into() },
+        reason: None,
+    });
+    let started = std::time::Instant::now();
+    let outcome = StepExecutor::run_step(step, &agent, &mut ctx).await;
+    assert!(started.elapsed() < std::time::Duration::from_secs(5));
+    assert!(!outcome.success);
+    assert_eq!(outcome.output["error"], "timeout");
+    assert_eq!(
+        outcome.fallback_applied,
+        Some(agent_core::FallbackKind::AlternateTool)
+    );
+
+    let agent = SleepyAgent {
+        steps: vec![
+            ("first".into(), 1, None),
+            ("stuck".into(), 10_000, None),
+            ("never".into(), 1, None),
+        ],
+    };
+    let control = ControlLoop {
+        max_iterations: 5,
+        ..Default::default()
+    }
+    .with_deadline(std::time::Duration::from_millis(200));
+    let report = control.run_with_report(&agent, &mut ctx).await.unwrap();
+    assert_eq!(report.stop_reason, StopReason::DeadlineExceeded);
+    let ids: Vec<&str> = report.outcomes.iter().map(|o| o.step_id.as_str()).collect();
+    assert_eq!(ids, vec!["first", "stuck"]);
+    assert_eq!(report.outcomes[1].output["error"], "timeout");
+}
+
+#[tokio::test]
+async fn cancelling_a_run_keeps_the_outcomes_so_far() {
+    let mut ctx = AgentContext {
+        config: AgentConfig::default(),
+        state: AgentState::default(),
+        metadata: json!({}),
+        memory: None,
+        tool_permissions: ToolPermissions::default(),
+        cancellation: None,
+    };
+    let agent = SleepyAgent {
+        steps: vec![
+            ("first".into(), 1, None),
+            ("stuck".into(), 10_000, None),
+            ("never".into(), 1, None),
+        ],
+    };
+    let control = ControlLoop {
+        max_iterations: 5,
+        ..Default::default()
+    };
+    let token = agent_core::CancellationToken::new();
+    tokio::spawn({
+        let token = token.clone();
+        async move {
+            tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+            token.cancel();
+        }
+    });
+    let started = std::time::Instant::now();
+    let report = control
+        .run_with_cancellation(&agent, &mut ctx, token)
+        .await
+        .unwrap();
+    assert!(started.elapsed() < std::time::Duration::from_secs(5));
+    assert_eq!(report.stop_reason, StopReason::Cancelled);
+    let ids: Vec<&str> = report.outcomes.iter().map(|o| o.step_id.as_str()).collect();
+    assert_eq!(ids, vec!["first", "stuck"]);
+    assert!(report.outcomes[0].success);
+    assert_eq!(report.outcomes[1].output["error"], "cancelled");
+    assert!(ctx.cancellation.is_none());
+}
+
+#[tokio::test]
+async fn checkpointed_runs_resume_after_the_last_finished_step() {
+    let mut ctx = AgentContext {
+        config: AgentConfig::default(),
+        state: AgentState::default(),
+        metadata: json!({}),
+        memory: None,
+        tool_permissions: ToolPermissions::default(),
+        cancellation: None,
+    };
+    let agent = SleepyAgent {
+        steps: vec![
+            ("a".into(), 1, None),
+            ("b".into(), 1, None),
+            ("c".into(), 1, None),
+        ],
+    };
+    let checkpoints = CheckpointStore::new(Arc::new(agent_memory::InMemoryStore::new()), "run-1");
+    // Stopping after two iterations stands in for the process going away.
+    let interrupted = ControlLoop {
+        max_iterations: 2,
+        ..Default::default()
+    }
+    .with_checkpoints(checkpoints.clone());
+    let report = interrupted.run_with_report(&agent, &mut ctx).await.unwrap();
+    assert_eq!(report.stop_reason, StopReason::MaxIterations);
+
+    let checkpoint = checkpoints.load().unwrap().unwrap();
+    assert_eq!(checkpoint.iteration, 2);
+    assert_eq!(checkpoint.executable.as_ref().unwrap().current, 2);
+    assert_eq!(checkpoint.outcomes.len(), 2);
+
+    let mut fresh = AgentContext::default();
+    let control = ControlLoop {
+        max_iterations: 5,
+        ..Default::default()
+    }
+    .with_checkpoints(checkpoints.clone());
+    let report = control
+        .resume(checkpoint, &agent, &mut fresh)
+        .await
+        .unwrap();
+    assert_eq!(report.stop_reason, StopReason::Completed);
+    let ids: Vec<&str> = report.outcomes.iter().map(|o| o.step_id.as_str()).collect();
+    assert_eq!(ids, vec!["a", "b", "c"]);
+    assert_eq!(fresh.state.iteration, 3);
+    assert_eq!(checkpoints.load().unwrap().unwrap().outcomes.len(), 3);
+    assert!(checkpoints.clear().unwrap());
+    assert!(checkpoints.load().unwrap().is_none());
+}
+
+/// Plans one step per id, the ones listed in `reviewed` needing review, and
+/// echoes each step's args.
+#[derive(Debug)]
+struct ReviewedAgent {
+    steps: Vec<&'static str>,
+    reviewed: Vec<&'static str>,
+}
+
+#[async_trait::async_trait]
+impl Agent for ReviewedAgent {
+    async fn plan(&self, _ctx: &AgentContext) -> Result<Plan, AgentError> {
+        let steps = self
+            .steps
+            .iter()
+            .map(|id| {
+                let mut policies = StepPolicies::default();
+                policies.safety.requires_review = self.reviewed.contains(id);
+                Step {
+                    id: id.to_string(),
+                    description: format!("{id} things"),
+                    tool: None,
+                    args: json!({"target": "prod"}),
+                    subtasks: vec![],
+                    policies,
+                    chain_of_thought: None,
+                    sub_plan: None,
+                    depends_on: Vec::new(),
+                    condition: None,
+                    for_each: None,
+                    delegate: None,
+                }
+            })
+            .collect();
+        Ok(Plan {
+            goal: "tidy up".into(),
+            steps,
+            metadata: json!({}),
+        })
+    }
+
+    async fn execute_step(
+        &self,
+        step: &Step,
+        _ctx: &mut AgentContext,
+    ) -> Result<StepOutcome, AgentError> {
+        Ok(StepOutcome::success(step.id.clone(), step.args.clone()))
+    }
+}
+
+/// Answers by step id.
+struct ScriptedReviewer;
+
+#[async_trait::async_trait]
+impl InterventionHandler for ScriptedReviewer {
+    async fn review(&self, step: &Step, _ctx: &AgentContext) -> InterventionDecision {
+        match step.id.as_str() {
+            "archive" => InterventionDecision::Approve,
+            "delete" => InterventionDecision::EditArgs {
+                args: json!({"target": "staging"}),
+            },
+            "email" => InterventionDecision::Skip,
+            _ => InterventionDecision::Abort {
+                reason: "not during a freeze".into(),
+            },
+        }
+    }
+}
+
+#[tokio::test]
+async fn reviewers_approve_edit_skip_or_abort_steps() {
+    let agent = ReviewedAgent {
+        steps: vec!["list", "archive", "delete", "email", "deploy", "notify"],
+        reviewed: vec!["archive", "delete", "email", "deploy"],
+    };
+    let control = ControlLoop {
+        max_iterations: 10,
+        ..Default::default()
+    }
+    .with_intervention(Arc::new(ScriptedReviewer));
+    let mut ctx = AgentContext::default();
+    let report = control.run_with_report(&agent, &mut ctx).await.unwrap();
+
+    assert_eq!(
+        report.stop_reason,
+        StopReason::Aborted {
+            step_id: "deploy".into(),
+            reason: "not during a freeze".into(),
+        }
+    );
+    let ids: Vec<&str> = report.outcomes.iter().map(|o| o.step_id.as_str()).collect();
+    assert_eq!(ids, vec!["list", "archive", "delete", "email", "deploy"]);
+    assert!(report.outcomes[0].control_notes.is_empty());
+    assert!(report.outcomes[1]
+        .control_notes
+        .contains(&"intervention: approved".to_string()));
+    assert_eq!(report.outcomes[2].output, json!({"target": "staging"}));
+    assert!(report.outcomes[2]
+        .control_notes
+        .contains(&"intervention: args edited".to_string()));
+    assert!(!report.outcomes[3].success);
+    assert_eq!(
+        report.outcomes[3].failure_kind,
+        Some(FailureKind::SkippedByReviewer)
+    );
+    assert!(report.outcomes[4]
+        .control_notes
+        .contains(&"intervention: aborted: not during a freeze".to_string()));
+
+    // Without a handler, steps needing review are refused rather than run.
+    let unattended = ControlLoop {
+        max_iterations: 10,
+        ..Default::default()
+    };
+    let report = unattended.run_with_report(&agent, &mut ctx).await.unwrap();
+    assert_eq!(report.stop_reason, StopReason::Completed);
+    let ran: Vec<bool> = report.outcomes.iter().map(|o| o.success).collect();
+    assert_eq!(ran, vec![true, false, false, false, false, true]);
+}
+
+/// Spends 40 tokens of model usage per step.
+#[derive(Debug)]
+struct SpendingAgent {
+    tracker: agent_models::UsageTracker,
+}
+
+#[async_trait::async_trait]
+impl Agent for SpendingAgent {
+    async fn plan(&self, ctx: &AgentContext) -> Result<Plan, AgentError> {
+        SleepyAgent {
+            steps: (0..5).map(|i| (format!("step-{i}"), 0, None)).collect(),
+        }
+        .plan(ctx)
+        .await
+    }
+
+    async fn execute_step(
+        &self,
+        step: &Step,
+        _ctx: &mut AgentContext,
+    ) -> Result<StepOutcome, AgentError> {
+        self.tracker.record(&agent_models::LLMResponse {
+            usage: agent_models::UsageMetrics {
+                prompt_tokens: 30,
+                completion_tokens: 10,
+            },
+            ..Default::default()
+        });
+        Ok(StepOutcome::success(step.id.clone(), json!("spent")))
+    }
+}
+
+#[tokio::test]
+async fn run_budgets_stop_the_loop_once_a_limit_is_reached() {
+    let tracker = agent_models::UsageTracker::new();
+    let agent = SpendingAgent {
+        tracker: tracker.clone(),
+    };
+    let control = ControlLoop {
+        max_iterations: 10,
+        ..Default::default()
+    }
+    .with_usage_tracker(tracker.clone())
+    .with_budget(RunBudget::new().with_max_tokens(100));
+    let mut ctx = AgentContext::default();
+    let report = control.run_with_report(&agent, &mut ctx).await.unwrap();
+    assert_eq!(
+        report.stop_reason,
+        StopReason::BudgetExceeded {
+            limit: BudgetLimit::Tokens
+        }
+    );
+    assert_eq!(report.outcomes.len(), 3);
+    assert_eq!(
+        report.outcomes[2].control_notes.last().map(String::as_str),
+        Some("budget exceeded: tokens")
+    );
+    assert_eq!(
+        report.usage.prompt_tokens + report.usage.completion_tokens,
+        120
+    );
+
+    let agent = SleepyAgent {
+        steps: (0..5).map(|i| (format!("nap-{i}"), 30, None)).collect(),
+    };
+    let control = ControlLoop {
+        max_iterations: 10,
+        ..Default::default()
+    }
+    .with_budget(RunBudget::new().with_max_duration(std::time::Duration::from_millis(50)));
+    let report = control.run_with_report(&agent, &mut ctx).await.unwrap();
+    assert_eq!(
+        report.stop_reason,
+        StopReason::BudgetExceeded {
+            limit: BudgetLimit::Duration
+        }
+    );
+    assert_eq!(report.outcomes.len(), 2);
+}
+
+/// Logs every callback, refuses step `b`, rewrites step `c` and can veto
+/// plans.
+#[derive(Default)]
+struct RecordingHook {
+    calls: Mutex<Vec<String>>,
+    veto_plans: bool,
+}
+
+#[async_trait::async_trait]
+impl RunHook for RecordingHook {
+    async fn on_plan(&self, plan: &Plan, _ctx: &AgentContext) -> Result<(), AgentError> {
+        self.calls
+            .lock()
+            .unwrap()
+            .push(format!("plan:{}", plan.steps.len()));
+        if self.veto_plans {
+            return Err(AgentError::Safety("plan vetoed".into()));
+        }
+        Ok(())
+    }
+
+    async fn before_step(&self, step: &mut Step, _ctx: &AgentContext) -> Result<(), AgentError> {
+        self.calls
+            .lock()
+            .unwrap()
+            .push(format!("before:{}", step.id));
+        match step.id.as_str() {
+            "b" => Err(AgentError::Safety("b is off limits".into())),
+            "c" => {
+                step.args = json!({"target": "dev"});
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    async fn after_step(
+        &self,
+        outcome: &mut StepOutcome,
+        _ctx: &AgentContext,
+    ) -> Result<(), AgentError> {
+        self.calls
+            .lock()
+            .unwrap()
+            .push(format!("after:{}", outcome.step_id));
+        outcome.control_notes.push("audited".into());
+        Ok(())
+    }
+
+    async fn on_error(&self, error: &AgentError, _ctx: &AgentContext) {
+        self.calls.lock().unwrap().push(format!("error:{error}"));
+    }
+
+    async fn on_finish(&self, report: &RunReport, _ctx: &AgentContext) {
+        self.calls
+            .lock()
+            .unwrap()
+            .push(format!("finish:{}", report.outcomes.len()));
+    }
+}
+
+#[tokio::test]
+async fn run_hooks_wrap_planning_steps_and_the_end_of_a_run() {
+    let agent = ReviewedAgent {
+        steps: vec!["a", "b", "c"],
+        reviewed: vec![],
+    };
+    let hook = Arc::new(RecordingHook::default());
+    let control = ControlLoop {
+        max_iterations: 5,
+        ..Default::default()
+    }
+    .with_hook(hook.clone());
+    let mut ctx = AgentContext::default();
+    let report = control.run_with_report(&agent, &mut ctx).await.unwrap();
+
+    assert_eq!(
+        *hook.calls.lock().unwrap(),
+        vec!["plan:3", "before:a", "after:a", "before:b", "before:c", "after:c", "finish:3"]
+    );
+    assert!(report.outcomes[0]
+        .control_notes
+        .contains(&"audited".to_string()));
+    assert!(!report.outcomes[1].success);
+    assert!(report.outcomes[1]
+        .control_notes
+        .contains(&"refused by run hook".to_string()));
+    assert_eq!(report.outcomes[2].output, json!({"target": "dev"}));
+
+    let veto = Arc::new(RecordingHook {
+        veto_plans: true,
+        ..Default::default()
+    });
+    let control = ControlLoop {
+        max_iterations: 5,
+        ..Default::default()
+    }
+    .with_hook(veto.clone());
+    let error = control.run_with_report(&agent, &mut ctx).await.unwrap_err();
+    assert!(matches!(error, AgentError::Safety(_)));
+    assert_eq!(
+        *veto.calls.lock().unwrap(),
+        vec!["plan:3", "error:safety violation: plan vetoed"]
+    );
+}
+
+/// Searches, then runs follow-up steps depending on what the search found.
+#[derive(Debug)]
+struct ConditionalAgent;
+
+#[async_trait::async_trait]
+impl Agent for ConditionalAgent {
+    async fn plan(&self, _ctx: &AgentContext) -> Result<Plan, AgentError> {
+        let step = |id: &str, args: serde_json::Value| Step {
+            id: id.into(),
+            description: format!("{id} step"),
+            tool: None,
+            args,
+            subtasks: vec![],
+            policies: StepPolicies::default(),
+            chain_of_thought: None,
+            sub_plan: None,
+            depends_on: Vec::new(),
+            condition: None,
+            for_each: None,
+            delegate: None,
+        };
+        Ok(Plan {
+            goal: "answer".into(),
+            steps: vec![
+                step("search", json!({"results": ["a", "b"]})),
+                step("summarize", json!({})).with_condition(
+                    "steps.search.success && steps.search.output.results | length > 0",
+                ),
+                step("apologize", json!({})).with_condition("!steps.search.output.results[0]"),
+                step("cite", json!({}))
+                    .with_condition("steps.apologize.success || metadata.always_cite == true"),
+                step("broken", json!({})).with_condition("steps.search.output. =="),
+            ],
+            metadata: json!({}),
+        })
+    }
+
+    async fn execute_step(
+        &self,
+        step: &Step,
+        _ctx: &mut AgentContext,
+    ) -> Result<StepOutcome, AgentError> {
+        Ok(StepOutcome::success(step.id.clone(), step.args.clone()))
+    }
+}
+
+#[tokio::test]
+async fn steps_run_only_when_their_condition_holds() {
+    let control = ControlLoop {
+        max_iterations: 10,
+        ..Default::default()
+    };
+    let mut ctx = AgentContext::default();
+    let report = control
+        .run_with_report(&ConditionalAgent, &mut ctx)
+        .await
+        .unwrap();
+
+    let ran: Vec<bool> = report.outcomes.iter().map(|o| o.success).collect();
+    assert_eq!(ran, vec![true, true, false, false, false]);
+    assert_eq!(
+        report.outcomes[2].failure_kind,
+        Some(FailureKind::ConditionNotMet {
+            condition: "!steps.search.output.results[0]".into()
+        })
+    );
+    assert!(report.outcomes[2]
+        .control_notes
+        .contains(&"skipped: condition `!steps.search.output.results[0]` is false".to_string()));
+    assert!(report.outcomes[4].output["error"]
+        .as_str()
+        .unwrap()
+        .contains("invalid condition `steps.search.output. ==`"));
+
+    // Context metadata is in scope too.
+    ctx.metadata = json!({"always_cite": true});
+    let report = control
+        .run_with_report(&ConditionalAgent, &mut ctx)
+        .await
+        .unwrap();
+    assert!(report.outcomes[3].success);
+
+    // In a graph, a skipped step's dependents are skipped with it.
+    let mut plan = ConditionalAgent.plan(&ctx).await.unwrap();
+    plan.steps.truncate(3);
+    plan.steps[1].depends_on = vec!["search".into()];
+    plan.steps[2].depends_on = vec!["search".into()];
+    let mut retry = plan.steps[0].clone();
+    retry.id = "retry".into();
+    retry.depends_on = vec!["apologize".into()];
+    plan.steps.push(retry);
+    let outcomes = agent_runtime::DagExecutor::default()
+        .run(&plan, &ConditionalAgent, &mut ctx)
+        .await
+        .unwrap();
+    let ran: Vec<(&str, bool)> = outcomes
+        .iter()
+        .map(|o| (o.step_id.as_str(), o.success))
+        .collect();
+    assert!(ran.contains(&("summarize", true)));
+    assert!(ran.contains(&("apologize", false)));
+    assert!(ran.contains(&("retry", false)));
+}
+
+/// Searches, then summarizes every result that has a url.
+#[derive(Debug)]
+struct SummarizeEachAgent {
+    max_concurrency: Option<usize>,
+}
+
+#[async_trait::async_trait]
+impl Agent for SummarizeEachAgent {
+    async fn plan(&self, _ctx: &AgentContext) -> Result<Plan, AgentError> {
+        let step = |id: &str, args: serde_json::Value| Step {
+            id: id.into(),
+            description: format!("{id} step"),
+            tool: None,
+            args,
+            subtasks: vec![],
+            policies: StepPolicies::default(),
+            chain_of_thought: None,
+            sub_plan: None,
+            depends_on: Vec::new(),
+            condition: None,
+            for_each: None,
+            delegate: None,
+        };
+        let template = step(
+            "summary",
+            json!({"title": "{{ item.title }}", "label": "#{{index}}: {{item.title}}"}),
+        )
+        .with_condition("item.url != null");
+        let mut for_each = ForEach::new("steps.search.output.results", template);
+        for_each.max_concurrency = self.max_concurrency;
+        Ok(Plan {
+            goal: "summarize".into(),
+            steps: vec![
+                step(
+                    "search",
+                    json!({"results": [
+                        {"title": "rust", "url": "https://rust-lang.org"},
+                        {"title": "ferris"},
+                        {"title": 42, "url": "https://example.com"},
+                    ]}),
+                ),
+                step("summarize", json!({})).with_for_each(for_each),
+                step("missing", json!({})).with_for_each(ForEach::new(
+                    "steps.search.output.hits",
+                    step("x", json!({})),
+                )),
+            ],
+            metadata: json!({}),
+        })
+    }
+
+    async fn execute_step(
+        &self,
+        step: &Step,
+        _ctx: &mut AgentContext,
+    ) -> Result<StepOutcome, AgentError> {
+        Ok(StepOutcome::success(step.id.clone(), step.args.clone()))
+    }
+}
+
+#[tokio::test]
+async fn for_each_steps_run_their_template_per_item() {
+    for max_concurrency in [None, Some(2)] {
+        let control = ControlLoop {
+            max_iterations: 10,
+            ..Default::default()
+        };
+        let mut ctx = AgentContext::default();
+        let report = control
+            .run_with_report(&SummarizeEachAgent { max_concurrency }, &mut ctx)
+            .await
+            .unwrap();
+
+        let summarize = report
+            .outcomes
+            .iter()
+            .find(|o| o.step_id == "summarize")
+            .unwrap();
+        assert!(summarize.success);
+        assert_eq!(
+            summarize.output,
+            json!({"items": [
+                {"title": "rust", "label": "#0: rust"},
+                null,
+                {"title": 42, "label": "#2: 42"},
+            ]})
+        );
+        assert!(summarize
+            .control_notes
+            .contains(&"for_each: 3 items, 0 failed".to_string()));
+        let skipped = report
+            .outcomes
+            .iter()
+            .find(|o| o.step_id == "summarize[1]")
+            .unwrap();
+        assert_eq!(
+            skipped.failure_kind,
+            Some(FailureKind::ConditionNotMet {
+                condition: "item.url != null".into()
+            })
+        );
+
+        let missing = report.outcomes.last().unwrap();
+        assert_eq!(missing.step_id, "missing");
+        assert!(!missing.success);
+        assert!(missing.output["error"]
+            .as_str()
+            .unwrap()
+            .contains("is not an array"));
+    }
+}
+
+/// Answers from the topic it was given, in a single step.
+#[derive(Debug)]
+struct ResearcherAgent;
+
+#[async_trait::async_trait]
+impl Agent for ResearcherAgent {
+    async fn plan(&self, _ctx: &AgentContext) -> Result<Plan, AgentError> {
+        Ok(Plan {
+            goal: "research".into(),
+            steps: vec![Step {
+                id: "answer".into(),
+                description: "answer".into(),
+                tool: None,
+                args: json!({}),
+                subtasks: vec![],
+                policies: StepPolicies::default(),
+                chain_of_thought: None,
+                sub_plan: None,
+                depends_on: Vec::new(),
+                condition: None,
+                for_each: None,
+                delegate: None,
+            }],
+            metadata: json!({}),
+        })
+    }
+
+    async fn execute_step(
+        &self,
+        step: &Step,
+        ctx: &mut AgentContext,
+    ) -> Result<StepOutcome, AgentError> {
+        let topic = ctx.metadata["topic"].as_str().unwrap_or("nothing");
+        Ok(StepOutcome::success(
+            step.id.clone(),
+            json!({"answer": format!("notes on {topic}")}),
+        ))
+    }
+}
+
+/// Hands its work to other agents.
+#[derive(Debug)]
+struct DelegatingAgent;
+
+#[async_trait::async_trait]
+impl Agent for DelegatingAgent {
+    async fn plan(&self, _ctx: &AgentContext) -> Result<Plan, AgentError> {
+        let step = |id: &str, agent: &str| {
+            Step {
+                id: id.into(),
+                description: format!("ask {agent}"),
+                tool: None,
+                args: json!({}),
+                subtasks: vec![],
+                policies: StepPolicies::default(),
+                chain_of_thought: None,
+                sub_plan: None,
+                depends_on: Vec::new(),
+                condition: None,
+                for_each: None,
+                delegate: None,
+            }
+            .with_delegate(Delegate::new(agent, json!({"topic": "rust"})))
+        };
+        Ok(Plan {
+            goal: "delegate".into(),
+            steps: vec![step("research", "researcher"), step("unknown", "nobody")],
+            metadata: json!({}),
+        })
+    }
+
+    async fn execute_step(
+        &self,
+        _step: &Step,
+        _ctx: &mut AgentContext,
+    ) -> Result<StepOutcome, AgentError> {
+        panic!("delegated steps do not run on the delegating agent")
+    }
+}
+
+#[tokio::test]
+async fn delegate_steps_run_the_named_agents_control_loop() {
+    let mut orchestrator =
+        MultiAgentOrchestrator::new(InMemoryBus::new(), MemoryTopology::Isolated);
+    orchestrator.register_delegate(
+        "researcher",
+        Arc::new(ResearcherAgent),
+        AgentContext::default(),
+        ControlLoop {
+            max_iterations: 5,
+            ..Default::default()
+        },
+    );
+    let control = ControlLoop {
+        max_iterations: 5,
+        ..Default::default()
+    }
+    .with_delegates(Arc::new(orchestrator));
+    let mut ctx = AgentContext::default();
+    let outcomes = control.run(&DelegatingAgent, &mut ctx).await.unwrap();
+
+    assert!(outcomes[0].success);
+    assert_eq!(outcomes[0].output, json!({"answer": "notes on rust"}));
+    assert!(outcomes[0]
+        .control_notes
+        .contains(&"delegate: researcher".to_string()));
+    assert!(!outcomes[1].success);
+    assert!(outcomes[1].output["error"]
+        .as_str()
+        .unwrap()
+        .contains("no agent named nobody is registered"));
+
+    // Without a delegator the steps fail instead of reaching the agent.
+    let control = ControlLoop {
+        max_iterations: 5,
+        ..Default::default()
+    };
+    let outcomes = control.run(&DelegatingAgent, &mut ctx).await.unwrap();
+    assert!(outcomes.iter().all(|outcome| !outcome.success));
+}
+
+/// Takes part in a group chat: a writer drafts, a critic approves the
+/// second draft.
+#[derive(Debug)]
+struct ChatAgent {
+    critic: bool,
+}
+
+#[async_trait::async_trait]
+impl Agent for ChatAgent {
+    async fn plan(&self, _ctx: &AgentContext) -> Result<Plan, AgentError> {
+        Ok(Plan {
+            goal: "chat".into(),
+            steps: vec![Step {
+                id: "reply".into(),
+                description: "reply".into(),
+                tool: None,
+                args: json!({}),
+                subtasks: vec![],
+                policies: StepPolicies::default(),
+                chain_of_thought: None,
+                sub_plan: None,
+                depends_on: Vec::new(),
+                condition: None,
+                for_each: None,
+                delegate: None,
+            }],
+            metadata: json!({}),
+        })
+    }
+
+    async fn execute_step(
+        &self,
+        step: &Step,
+        ctx: &mut AgentContext,
+    ) -> Result<StepOutcome, AgentError> {
+        let seen = ctx.metadata["conversation"].as_array().unwrap().len();
+        let reply = match (self.critic, seen >= 4) {
+            (true, true) => "APPROVED".to_string(),
+            (true, false) => "revise".to_string(),
+            (false, _) => format!("draft {seen} of {}", ctx.metadata["goal"].as_str().unwrap()),
+        };
+        Ok(StepOutcome::success(
+            step.id.clone(),
+            json!({"message": reply}),
+        ))
+    }
+}
+
+/// Passes drafts that have been revised at least once.
+struct RevisedDraft;
+
+#[async_trait::async_trait]
+impl agent_evals::OutputEvaluator for RevisedDraft {
+    async fn evaluate(
+        &self,
+        final_output: &serde_json::Value,
+    ) -> Result<agent_evals::EvaluationResult, agent_evals::EvalError> {
+        Ok(match final_output.as_str() {
+            Some(text) if text.starts_with("draft 3") => {
+                agent_evals::EvaluationResult::pass(1.0, "revised")
+            }
+            _ => agent_evals::EvaluationResult::fail("first draft"),
+        })
+    }
+}
+
+#[tokio::test]
+async fn group_chats_select_speakers_and_stop_on_their_conditions() {
+    let mut orchestrator =
+        MultiAgentOrchestrator::new(InMemoryBus::new(), MemoryTopology::Isolated);
+    for (name, critic) in [("writer", false), ("critic", true)] {
+        orchestrator.register_delegate(
+            name,
+            Arc::new(ChatAgent { critic }),
+            AgentContext::default(),
+            ControlLoop {
+                max_iterations: 1,
+                ..Default::default()
+            },
+        );
+    }
+    let chat = GroupChat::new(
+        Arc::new(orchestrator),
+        vec!["writer".into(), "critic".into()],
+    );
+    let ctx = AgentContext::default();
+
+    let approved = chat
+        .clone()
+        .with_termination(Termination::Regex(
+            regex::Regex::new(r"\bAPPROVED\b").unwrap(),
+        ))
+        .run("a haiku", &ctx)
+        .await
+        .unwrap();
+    let transcript: Vec<(&str, &str)> = approved
+        .messages
+        .iter()
+        .map(|m| (m.speaker.as_str(), m.content.as_str()))
+        .collect();
+    assert_eq!(
+        transcript,
+        vec![
+            ("user", "a haiku"),
+            ("writer", "draft 1 of a haiku"),
+            ("critic", "revise"),
+            ("writer", "draft 3 of a haiku"),
+            ("critic", "APPROVED"),
+        ]
+    );
+    assert_eq!(
+        approved.stop_reason,
+        GroupChatStop::Matched {
+            pattern: r"\bAPPROVED\b".into()
+        }
+    );
+
+    let evaluated = chat
+        .clone()
+        .with_termination(Termination::Evaluator(Arc::new(RevisedDraft)))
+        .run("a haiku", &ctx)
+        .await
+        .unwrap();
+    assert_eq!(evaluated.stop_reason, GroupChatStop::EvaluatorPassed);
+    assert_eq!(evaluated.last_reply().unwrap().speaker, "writer");
+
+    let writer_only = chat
+        .clone()
+        .with_selection(SpeakerSelection::Function(Arc::new(|messages, _| {
+            (messages.len() < 3).then(|| "writer".to_string())
+        })))
+        .run("a haiku", &ctx)
+        .await
+        .unwrap();
+    assert_eq!(writer_only.messages.len(), 3);
+    assert_eq!(writer_only.stop_reason, GroupChatStop::NoSpeaker);
+
+    let prompts = Arc::new(Mutex::new(Vec::new()));
+    let moderated = chat
+        .clone()
+        .with_selection(SpeakerSelection::Model(Arc::new(CapturingModel {
+            prompts: prompts.clone(),
+            reply: "critic.".into(),
+        })))
+        .with_max_turns(1)
+        .run("a haiku", &ctx)
+        .await
+        .unwrap();
+    assert_eq!(moderated.last_reply().unwrap().speaker, "critic");
+    assert_eq!(moderated.stop_reason, GroupChatStop::MaxTurns);
+    assert!(prompts.lock().unwrap()[0].contains("between: writer, critic"));
+
+    let stranger = chat
+        .with_selection(SpeakerSelection::Function(Arc::new(|_, _| {
+            Some("stranger".to_string())
+        })))
+        .run("a haiku", &ctx)
+        .await;
+    assert!(matches!(stranger, Err(AgentError::Validation(_))));
+}
+
+type Transform = fn(&str) -> serde_json::Value;
+
+/// Applies a text transform to its input in a single step.
+#[derive(Debug)]
+struct TransformAgent(Transform);
+
+#[async_trait::async_trait]
+impl Agent for TransformAgent {
+    async fn plan(&self, _ctx: &AgentContext) -> Result<Plan, AgentError> {
+        Ok(Plan {
+            goal: "transform".into(),
+            steps: vec![Step {
+                id: "transform".into(),
+                description: "transform".into(),
+                tool: None,
+                args: json!({}),
+                subtasks: vec![],
+                policies: StepPolicies::default(),
+                chain_of_thought: None,
+                sub_plan: None,
+                depends_on: Vec::new(),
+                condition: None,
+                for_each: None,
+                delegate: None,
+            }],
+            metadata: json!({}),
+        })
+    }
+
+    async fn execute_step(
+        &self,
+        step: &Step,
+        ctx: &mut AgentContext,
+    ) -> Result<StepOutcome, AgentError> {
+        let input = ctx.metadata["input"]
+            .as_str()
+            .ok_or_else(|| AgentError::Validation("input is not text".into()))?;
+        Ok(StepOutcome::success(step.id.clone(), (self.0)(input)))
+    }
+}
+
+#[tokio::test]
+async fn workflows_pipe_and_fan_out_between_agents() {
+    use agent_runtime::{AgentRef, Aggregator};
+
+    let mut orchestrator =
+        MultiAgentOrchestrator::new(InMemoryBus::new(), MemoryTopology::Isolated);
+    let agents: [(&str, Transform); 3] = [
+        ("upper", |text| json!(text.to_uppercase())),
+        ("exclaim", |text| json!(format!("{text}!"))),
+        ("count", |text| json!({"length": text.len()})),
+    ];
+    for (name, transform) in agents {
+        orchestrator.register_delegate(
+            name,
+            Arc::new(TransformAgent(transform)),
+            AgentContext::default(),
+            ControlLoop {
+                max_iterations: 1,
+                ..Default::default()
+            },
+        );
+    }
+    let ctx = AgentContext::default();
+
+    let shout = Workflow::Sequential(vec!["upper".into(), "exclaim".into()]);
+    assert_eq!(
+        shout.run(&orchestrator, json!("hi"), &ctx).await.unwrap(),
+        json!("HI!")
+    );
+
+    let fan_out = Workflow::Concurrent(vec!["upper".into(), "exclaim".into()], Aggregator::Collect);
+    assert_eq!(
+        fan_out.run(&orchestrator, json!("hi"), &ctx).await.unwrap(),
+        json!(["HI", "hi!"])
+    );
+
+    // Workflows nest: join the branches, then pipe the result on.
+    let joined = Workflow::Concurrent(
+        vec![shout.clone().into(), "exclaim".into()],
+        Aggregator::Function(Arc::new(|outputs| {
+            let texts: Vec<&str> = outputs.iter().filter_map(|o| o.as_str()).collect();
+            json!(texts.join(" "))
+        })),
+    );
+    let nested = Workflow::Sequential(vec![joined.into(), "count".into()]);
+    assert_eq!(
+        nested.run(&orchestrator, json!("hi"), &ctx).await.unwrap(),
+        json!({"length": 7})
+    );
+
+    // An object output is not text, so the agent after it fails the workflow.
+    let broken = Workflow::Sequential(vec![AgentRef::from("count"), "upper".into()]);
+    let error = broken
+        .run(&orchestrator, json!("hi"), &ctx)
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("agent upper did not complete"));
+}
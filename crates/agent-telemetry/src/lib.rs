@@ -3,10 +3,11 @@ use opentelemetry::trace::{TraceContextExt, Tracer, TracerProvider as OtelTracer
 use opentelemetry::{Context, KeyValue};
 use opentelemetry_sdk::trace::{self, TracerProvider as SdkTracerProvider};
 use prometheus::{
-    Encoder, HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry, TextEncoder,
+    CounterVec, Encoder, HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry, TextEncoder,
 };
 use serde_json::Value;
 use std::borrow::Cow;
+use std::fmt;
 use std::fs::OpenOptions;
 use std::io::Write;
 use std::path::Path;
@@ -20,8 +21,15 @@ pub struct Telemetry {
     tool_calls: IntCounterVec,
     llm_input_tokens: IntCounterVec,
     llm_output_tokens: IntCounterVec,
+    llm_cost_usd: CounterVec,
     llm_latency_ms: HistogramVec,
     tool_latency_ms: HistogramVec,
+    model_cache_hits: IntCounterVec,
+    model_cache_misses: IntCounterVec,
+    model_retries: IntCounterVec,
+    memory_ops: IntCounterVec,
+    memory_bytes: IntCounterVec,
+    memory_latency_ms: HistogramVec,
 }
 
 impl Telemetry {
@@ -47,6 +55,11 @@ impl Telemetry {
             &["model"],
         )
         .expect("metric");
+        let llm_cost_usd = CounterVec::new(
+            Opts::new("llm_cost_usd", "Estimated LLM spend in USD"),
+            &["model"],
+        )
+        .expect("metric");
         let llm_latency_ms = HistogramVec::new(
             HistogramOpts::new(
                 "llm_call_latency_ms",
@@ -63,6 +76,21 @@ impl Telemetry {
             &["tool"],
         )
         .expect("metric");
+        let model_cache_hits = IntCounterVec::new(
+            Opts::new("model_cache_hits", "Model responses served from cache"),
+            &["namespace"],
+        )
+        .expect("metric");
+        let model_cache_misses = IntCounterVec::new(
+            Opts::new("model_cache_misses", "Model requests not found in cache"),
+            &["namespace"],
+        )
+        .expect("metric");
+        let model_retries = IntCounterVec::new(
+            Opts::new("model_retries", "Model calls retried after a failure"),
+            &["reason"],
+        )
+        .expect("metric");
         registry.register(Box::new(llm_calls.clone())).unwrap();
         registry.register(Box::new(tool_calls.clone())).unwrap();
         registry
@@ -71,10 +99,44 @@ impl Telemetry {
         registry
             .register(Box::new(llm_output_tokens.clone()))
             .unwrap();
+        registry.register(Box::new(llm_cost_usd.clone())).unwrap();
         registry.register(Box::new(llm_latency_ms.clone())).unwrap();
         registry
             .register(Box::new(tool_latency_ms.clone()))
             .unwrap();
+        registry
+            .register(Box::new(model_cache_hits.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(model_cache_misses.clone()))
+            .unwrap();
+        let memory_ops = IntCounterVec::new(
+            Opts::new("memory_ops", "Memory store operations"),
+            &["op", "outcome"],
+        )
+        .expect("metric");
+        let memory_bytes = IntCounterVec::new(
+            Opts::new(
+                "memory_bytes",
+                "Bytes of values written to or read from memory",
+            ),
+            &["op"],
+        )
+        .expect("metric");
+        let memory_latency_ms = HistogramVec::new(
+            HistogramOpts::new(
+                "memory_op_latency_ms",
+                "Memory store operation latency distribution (milliseconds)",
+            ),
+            &["op"],
+        )
+        .expect("metric");
+        registry.register(Box::new(model_retries.clone())).unwrap();
+        registry.register(Box::new(memory_ops.clone())).unwrap();
+        registry.register(Box::new(memory_bytes.clone())).unwrap();
+        registry
+            .register(Box::new(memory_latency_ms.clone()))
+            .unwrap();
 
         Self {
             tracer,
@@ -83,8 +145,15 @@ impl Telemetry {
             tool_calls,
             llm_input_tokens,
             llm_output_tokens,
+            llm_cost_usd,
             llm_latency_ms,
             tool_latency_ms,
+            model_cache_hits,
+            model_cache_misses,
+            model_retries,
+            memory_ops,
+            memory_bytes,
+            memory_latency_ms,
         }
     }
 
@@ -117,6 +186,11 @@ impl Telemetry {
         );
     }
 
+    /// Adds the estimated spend of an LLM call to `model`'s total.
+    pub fn record_llm_cost(&self, model: &str, usd: f64) {
+        self.llm_cost_usd.with_label_values(&[model]).inc_by(usd);
+    }
+
     pub fn record_tool_call(&self, tool: &str, duration_ms: Option<f64>) {
         self.tool_calls.with_label_values(&[tool]).inc();
         if let Some(value) = duration_ms {
@@ -127,6 +201,31 @@ impl Telemetry {
         event!(Level::INFO, %tool, duration_ms = duration_ms.unwrap_or_default(), "tool call recorded");
     }
 
+    pub fn record_cache_lookup(&self, namespace: &str, hit: bool) {
+        let counter = if hit {
+            &self.model_cache_hits
+        } else {
+            &self.model_cache_misses
+        };
+        counter.with_label_values(&[namespace]).inc();
+    }
+
+    /// Counts a retried model call, labelled by why the attempt failed.
+    pub fn record_model_retry(&self, reason: &str) {
+        self.model_retries.with_label_values(&[reason]).inc();
+    }
+
+    /// Counts a memory store operation such as `put` or `search`, with the
+    /// bytes of value data it moved.
+    pub fn record_memory_op(&self, op: &str, ok: bool, bytes: u64, duration_ms: f64) {
+        let outcome = if ok { "ok" } else { "error" };
+        self.memory_ops.with_label_values(&[op, outcome]).inc();
+        self.memory_bytes.with_label_values(&[op]).inc_by(bytes);
+        self.memory_latency_ms
+            .with_label_values(&[op])
+            .observe(duration_ms);
+    }
+
     pub fn log_tool_step(&self, tool: &str, step: &str, summary: &str, payload: Option<&Value>) {
         if let Some(payload) = payload {
             event!(
@@ -158,6 +257,11 @@ impl Telemetry {
         event!(Level::INFO, %event_name, payload = %payload, "audit event");
     }
 
+    /// Emits an audit event after masking the `sensitive_paths` in `payload`.
+    pub fn audit_redacted(&self, event_name: &str, payload: &Value, sensitive_paths: &[String]) {
+        self.audit(event_name, &redact_paths(payload, sensitive_paths));
+    }
+
     pub fn record_step_summary(
         &self,
         step_name: &str,
@@ -180,6 +284,12 @@ impl Telemetry {
     }
 }
 
+impl Default for Telemetry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 pub struct AuditLogWriter {
     file: Mutex<std::fs::File>,
 }
@@ -200,7 +310,17 @@ impl AuditLogWriter {
             "event_name": event_name,
             "payload": payload,
         });
-        writeln!(file, "{}", record.to_string())
+        writeln!(file, "{}", record)
+    }
+
+    /// Writes an audit record after masking the `sensitive_paths` in `payload`.
+    pub fn write_event_redacted(
+        &self,
+        event_name: &str,
+        payload: &Value,
+        sensitive_paths: &[String],
+    ) -> std::io::Result<()> {
+        self.write_event(event_name, &redact_paths(payload, sensitive_paths))
     }
 
     pub fn flush(&self) -> std::io::Result<()> {
@@ -208,3 +328,54 @@ impl AuditLogWriter {
         file.flush()
     }
 }
+
+/// Placeholder written in place of redacted values.
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Wrapper that keeps a sensitive value out of `Debug` and `Display` output.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Secret<T>(T);
+
+impl<T> Secret<T> {
+    pub fn new(value: T) -> Self {
+        Self(value)
+    }
+
+    pub fn expose(&self) -> &T {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> From<T> for Secret<T> {
+    fn from(value: T) -> Self {
+        Self(value)
+    }
+}
+
+impl<T> fmt::Debug for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl<T> fmt::Display for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+/// Returns a copy of `value` with every field addressed by the given JSON
+/// pointers replaced by [`REDACTED`]. Pointers that do not resolve are ignored.
+pub fn redact_paths<S: AsRef<str>>(value: &Value, pointers: &[S]) -> Value {
+    let mut redacted = value.clone();
+    for pointer in pointers {
+        if let Some(field) = redacted.pointer_mut(pointer.as_ref()) {
+            *field = Value::String(REDACTED.to_string());
+        }
+    }
+    redacted
+}
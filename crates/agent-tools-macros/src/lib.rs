@@ -0,0 +1,206 @@
+//! `#[tool]` attribute for defining agent tools from plain functions.
+//!
+//! Use it through the `agent_tools::tool` re-export; the generated code refers
+//! to `::agent_tools` paths.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{
+    parse_macro_input, FnArg, GenericArgument, ItemFn, LitStr, Pat, PathArguments, ReturnType, Type,
+};
+
+/// Turns a function into a tool.
+///
+/// ```ignore
+/// #[tool(description = "Looks up the forecast for a city")]
+/// async fn weather(city: String) -> Result<Forecast, WeatherError> { ... }
+/// ```
+///
+/// keeps `weather` as is and generates a unit struct `WeatherTool` implementing
+/// `agent_tools::Tool`. The arguments become the properties of the input
+/// object, and both schemas are derived with `schemars`, so argument types must
+/// implement `Deserialize + JsonSchema` and the output `Serialize + JsonSchema`.
+/// When the function returns `Result<T, E>`, `T` is the output and `E`'s
+/// `Display` text becomes a `ToolError::Execution`. Attributes on arguments
+/// (e.g. `#[serde(default)]`) are moved onto the generated input fields.
+///
+/// Options: `description = "..."` and `name = "..."` (defaults to the function
+/// name).
+#[proc_macro_attribute]
+pub fn tool(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut options = ToolOptions::default();
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("description") {
+            options.description = Some(meta.value()?.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("name") {
+            options.name = Some(meta.value()?.parse()?);
+            Ok(())
+        } else {
+            Err(meta.error("expected `description` or `name`"))
+        }
+    });
+    parse_macro_input!(attr with parser);
+    let function = parse_macro_input!(item as ItemFn);
+    expand(options, function)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+#[derive(Default)]
+struct ToolOptions {
+    description: Option<LitStr>,
+    name: Option<LitStr>,
+}
+
+fn expand(options: ToolOptions, mut function: ItemFn) -> syn::Result<TokenStream2> {
+    if !function.sig.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &function.sig.generics,
+            "#[tool] functions cannot be generic",
+        ));
+    }
+
+    let fn_name = function.sig.ident.clone();
+    let vis = function.vis.clone();
+    let struct_name = format_ident!("{}Tool", pascal_case(&fn_name.to_string()));
+    let args_name = format_ident!("{}Args", struct_name);
+    let tool_name = options
+        .name
+        .map(|name| name.value())
+        .unwrap_or_else(|| fn_name.to_string());
+    let description = match options.description {
+        Some(description) => quote!(::core::option::Option::Some(#description)),
+        None => quote!(::core::option::Option::None),
+    };
+
+    let mut fields = Vec::new();
+    let mut arg_names = Vec::new();
+    for input in function.sig.inputs.iter_mut() {
+        let FnArg::Typed(arg) = input else {
+            return Err(syn::Error::new_spanned(
+                input,
+                "#[tool] functions cannot take `self`",
+            ));
+        };
+        let Pat::Ident(pat) = &*arg.pat else {
+            return Err(syn::Error::new_spanned(
+                &arg.pat,
+                "#[tool] arguments must be plain identifiers",
+            ));
+        };
+        let ident = pat.ident.clone();
+        let ty = &arg.ty;
+        let attrs = std::mem::take(&mut arg.attrs);
+        fields.push(quote!(#(#attrs)* #ident: #ty));
+        arg_names.push(ident);
+    }
+
+    let (output_ty, fallible) = match &function.sig.output {
+        ReturnType::Default => (quote!(()), false),
+        ReturnType::Type(_, ty) => match result_ok_type(ty) {
+            Some(ok) => (quote!(#ok), true),
+            None => (quote!(#ty), false),
+        },
+    };
+    let mut call = quote!(#fn_name(#(args.#arg_names),*));
+    if function.sig.asyncness.is_some() {
+        call = quote!(#call.await);
+    }
+    if fallible {
+        call = quote! {
+            #call.map_err(|err| ::agent_tools::ToolError::Execution(err.to_string()))?
+        };
+    }
+
+    Ok(quote! {
+        #function
+
+        #[derive(Debug, Clone, Copy, Default)]
+        #vis struct #struct_name;
+
+        #[derive(
+            ::agent_tools::__private::serde::Deserialize,
+            ::agent_tools::__private::schemars::JsonSchema
+        )]
+        #[serde(crate = "::agent_tools::__private::serde")]
+        #[schemars(crate = "::agent_tools::__private::schemars")]
+        struct #args_name {
+            #(#fields,)*
+        }
+
+        impl #struct_name {
+            pub const DESCRIPTION: ::core::option::Option<&'static str> = #description;
+
+            /// Registry metadata carrying the tool description.
+            pub fn metadata() -> ::agent_tools::ToolMetadata {
+                ::agent_tools::ToolMetadata {
+                    description: Self::DESCRIPTION.map(str::to_string),
+                    ..::core::default::Default::default()
+                }
+            }
+        }
+
+        #[::agent_tools::__private::async_trait]
+        impl ::agent_tools::Tool for #struct_name {
+            fn name(&self) -> &'static str {
+                #tool_name
+            }
+
+            fn input_schema(&self) -> ::agent_tools::__private::serde_json::Value {
+                ::agent_tools::__private::schema_of::<#args_name>(Self::DESCRIPTION)
+            }
+
+            fn output_schema(&self) -> ::agent_tools::__private::serde_json::Value {
+                ::agent_tools::__private::schema_of::<#output_ty>(::core::option::Option::None)
+            }
+
+            async fn execute(
+                &self,
+                args: ::agent_tools::__private::serde_json::Value,
+            ) -> ::core::result::Result<
+                ::agent_tools::__private::serde_json::Value,
+                ::agent_tools::ToolError,
+            > {
+                let args: #args_name = ::agent_tools::__private::serde_json::from_value(args)
+                    .map_err(|err| ::agent_tools::ToolError::InvalidArgs(err.to_string()))?;
+                let output = #call;
+                ::agent_tools::__private::serde_json::to_value(output)
+                    .map_err(|err| ::agent_tools::ToolError::Execution(err.to_string()))
+            }
+        }
+    })
+}
+
+/// `T` for return types spelled `Result<T, ..>` (including aliases such as
+/// `anyhow::Result<T>`).
+fn result_ok_type(ty: &Type) -> Option<&Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    if segment.ident != "Result" {
+        return None;
+    }
+    let PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+    match args.args.first()? {
+        GenericArgument::Type(ok) => Some(ok),
+        _ => None,
+    }
+}
+
+fn pascal_case(name: &str) -> String {
+    name.split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect::<String>())
+                .unwrap_or_default()
+        })
+        .collect()
+}
@@ -0,0 +1,60 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use agent_telemetry::AuditLogWriter;
+use async_trait::async_trait;
+use serde_json::Value;
+
+use crate::ToolInvocationError;
+
+/// One finished [`crate::ToolRegistry`] invocation.
+///
+/// `args` are the arguments after middlewares ran, with the tool's
+/// `sensitive_arg_paths` already masked.
+#[derive(Debug)]
+pub struct ToolAuditRecord<'a> {
+    pub tool: &'a str,
+    pub args: &'a Value,
+    pub result: Result<&'a Value, &'a ToolInvocationError>,
+    pub duration: Duration,
+    pub caller_id: Option<&'a str>,
+    pub caller_roles: &'a [String],
+}
+
+/// Receives a [`ToolAuditRecord`] for every invocation, including denials.
+#[async_trait]
+pub trait ToolAuditSink: Send + Sync {
+    async fn record(&self, record: &ToolAuditRecord<'_>);
+}
+
+/// Writes each invocation as a `tool_invocation` event to an [`AuditLogWriter`].
+pub struct AuditLogSink {
+    writer: Arc<AuditLogWriter>,
+}
+
+impl AuditLogSink {
+    pub fn new(writer: Arc<AuditLogWriter>) -> Self {
+        Self { writer }
+    }
+}
+
+#[async_trait]
+impl ToolAuditSink for AuditLogSink {
+    async fn record(&self, record: &ToolAuditRecord<'_>) {
+        let mut payload = serde_json::json!({
+            "tool": record.tool,
+            "args": record.args,
+            "success": record.result.is_ok(),
+            "duration_ms": record.duration.as_millis() as u64,
+            "caller_id": record.caller_id,
+            "caller_roles": record.caller_roles,
+        });
+        match record.result {
+            Ok(output) => payload["output"] = output.clone(),
+            Err(error) => payload["error"] = Value::String(error.to_string()),
+        }
+        if let Err(error) = self.writer.write_event("tool_invocation", &payload) {
+            tracing::warn!(target: "agent-tools::audit", %error, "failed to write tool audit event");
+        }
+    }
+}
@@ -0,0 +1,582 @@
+//! Tools served out-of-process over gRPC.
+//!
+//! The contract lives in `proto/tool_service.proto`. [`GrpcToolProvider`]
+//! discovers a server's tools with `ListTools` and registers each one in a
+//! [`ToolRegistry`] as a [`GrpcTool`]; [`ToolServiceServer`] is the other half,
+//! serving a local registry to remote callers.
+//!
+//! Invocations carry the caller's deadline in the `grpc-timeout` header, and
+//! registry failures travel as gRPC status codes so the client can rebuild a
+//! [`ToolError`] from them.
+
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use thiserror::Error;
+use tonic::codegen::{http, Body, BoxFuture, Bytes, Service, StdError};
+use tonic::transport::{Channel, Endpoint};
+use tonic::{Code, Request, Response, Status};
+
+use crate::{InvocationContext, Tool, ToolError, ToolInvocationError, ToolMetadata, ToolRegistry};
+
+pub const SERVICE_NAME: &str = "agent.tools.v1.ToolService";
+const LIST_TOOLS_PATH: &str = "/agent.tools.v1.ToolService/ListTools";
+const INVOKE_PATH: &str = "/agent.tools.v1.ToolService/Invoke";
+
+/// Messages of `proto/tool_service.proto`.
+pub mod proto {
+    #[derive(Clone, Copy, PartialEq, Eq, Hash, ::prost::Message)]
+    pub struct ListToolsRequest {}
+
+    #[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+    pub struct ToolDescriptor {
+        #[prost(string, tag = "1")]
+        pub name: String,
+        #[prost(string, tag = "2")]
+        pub description: String,
+        #[prost(string, tag = "3")]
+        pub input_schema_json: String,
+        #[prost(string, tag = "4")]
+        pub output_schema_json: String,
+        #[prost(string, repeated, tag = "5")]
+        pub tags: Vec<String>,
+    }
+
+    #[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+    pub struct ListToolsResponse {
+        #[prost(message, repeated, tag = "1")]
+        pub tools: Vec<ToolDescriptor>,
+    }
+
+    #[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+    pub struct InvokeRequest {
+        #[prost(string, tag = "1")]
+        pub tool: String,
+        #[prost(string, tag = "2")]
+        pub args_json: String,
+    }
+
+    #[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+    pub struct InvokeResponse {
+        #[prost(string, tag = "1")]
+        pub output_json: String,
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum GrpcError {
+    #[error("transport failed: {0}")]
+    Transport(String),
+    #[error("protocol error: {0}")]
+    Protocol(String),
+    #[error("server returned {code:?}: {message}")]
+    Status { code: Code, message: String },
+}
+
+impl From<Status> for GrpcError {
+    fn from(status: Status) -> Self {
+        GrpcError::Status {
+            code: status.code(),
+            message: status.message().to_string(),
+        }
+    }
+}
+
+impl From<GrpcError> for ToolError {
+    fn from(err: GrpcError) -> Self {
+        match err {
+            GrpcError::Status {
+                code: Code::InvalidArgument | Code::FailedPrecondition | Code::OutOfRange,
+                message,
+            } => ToolError::InvalidArgs(message),
+            other => ToolError::Execution(other.to_string()),
+        }
+    }
+}
+
+/// Status reported to remote callers for a failed local invocation.
+fn invocation_status(err: ToolInvocationError) -> Status {
+    let message = err.to_string();
+    match err {
+        ToolInvocationError::NotFound(_) => Status::not_found(message),
+        ToolInvocationError::AccessDenied { .. } => Status::permission_denied(message),
+        ToolInvocationError::CoolingDown { .. }
+        | ToolInvocationError::RateLimited { .. }
+        | ToolInvocationError::BudgetExceeded { .. } => Status::resource_exhausted(message),
+        ToolInvocationError::TimedOut { .. } => Status::deadline_exceeded(message),
+        ToolInvocationError::Cancelled { .. } => Status::cancelled(message),
+        ToolInvocationError::Tool(ToolError::InvalidArgs(message)) => {
+            Status::invalid_argument(message)
+        }
+        ToolInvocationError::Tool(ToolError::Execution(message)) => Status::internal(message),
+    }
+}
+
+/// Thin unary client for `ToolService`, equivalent to what `tonic-build`
+/// would generate for the two methods.
+#[derive(Debug, Clone)]
+struct ToolServiceClient {
+    channel: Channel,
+}
+
+impl ToolServiceClient {
+    fn new(channel: Channel) -> Self {
+        Self { channel }
+    }
+
+    async fn unary<Req, Resp>(
+        &self,
+        path: &'static str,
+        request: Request<Req>,
+    ) -> Result<Response<Resp>, Status>
+    where
+        Req: prost::Message + Send + Sync + 'static,
+        Resp: prost::Message + Default + Send + Sync + 'static,
+    {
+        let mut inner = tonic::client::Grpc::new(self.channel.clone());
+        inner
+            .ready()
+            .await
+            .map_err(|e| Status::unknown(format!("service was not ready: {e}")))?;
+        let codec = tonic_prost::ProstCodec::default();
+        inner
+            .unary(request, http::uri::PathAndQuery::from_static(path), codec)
+            .await
+    }
+}
+
+/// Discovers tools on a `ToolService` server and exposes them through
+/// [`ToolRegistry`].
+pub struct GrpcToolProvider {
+    client: ToolServiceClient,
+    deadline: Option<Duration>,
+}
+
+impl GrpcToolProvider {
+    /// Connects to `endpoint`, e.g. `http://127.0.0.1:50051`.
+    pub async fn connect(endpoint: impl Into<String>) -> Result<Self, GrpcError> {
+        let channel = Endpoint::from_shared(endpoint.into())
+            .map_err(|e| GrpcError::Transport(e.to_string()))?
+            .connect()
+            .await
+            .map_err(|e| GrpcError::Transport(e.to_string()))?;
+        Ok(Self::from_channel(channel))
+    }
+
+    /// Uses an already configured channel (TLS, keep-alive, lazy connect...).
+    pub fn from_channel(channel: Channel) -> Self {
+        Self {
+            client: ToolServiceClient::new(channel),
+            deadline: None,
+        }
+    }
+
+    /// Deadline given to every tool registered by [`register_all`](Self::register_all).
+    pub fn with_deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    pub async fn list_tools(&self) -> Result<Vec<proto::ToolDescriptor>, GrpcError> {
+        let response = self
+            .client
+            .unary::<_, proto::ListToolsResponse>(
+                LIST_TOOLS_PATH,
+                Request::new(proto::ListToolsRequest {}),
+            )
+            .await?;
+        Ok(response.into_inner().tools)
+    }
+
+    /// Registers every discovered tool and returns their names.
+    pub async fn register_all(&self, registry: &ToolRegistry) -> Result<Vec<String>, GrpcError> {
+        let mut names = Vec::new();
+        for descriptor in self.list_tools().await? {
+            let mut tags = descriptor.tags.clone();
+            tags.push("grpc".into());
+            let metadata = ToolMetadata {
+                description: (!descriptor.description.is_empty())
+                    .then(|| descriptor.description.clone()),
+                tags,
+                ..Default::default()
+            };
+            names.push(descriptor.name.clone());
+            let mut tool = GrpcTool::new(self.client.channel.clone(), descriptor)?;
+            tool.deadline = self.deadline;
+            registry.register_with_metadata(tool, metadata);
+        }
+        Ok(names)
+    }
+}
+
+/// A single remote tool, invoked with `Invoke`.
+pub struct GrpcTool {
+    client: ToolServiceClient,
+    name: String,
+    input_schema: Value,
+    output_schema: Value,
+    deadline: Option<Duration>,
+}
+
+impl GrpcTool {
+    pub fn new(channel: Channel, descriptor: proto::ToolDescriptor) -> Result<Self, GrpcError> {
+        let schema = |text: &str| -> Result<Value, GrpcError> {
+            if text.is_empty() {
+                return Ok(json!({"type": "object"}));
+            }
+            serde_json::from_str(text).map_err(|e| {
+                GrpcError::Protocol(format!("invalid schema for {}: {e}", descriptor.name))
+            })
+        };
+        let input_schema = schema(&descriptor.input_schema_json)?;
+        let output_schema = schema(&descriptor.output_schema_json)?;
+        Ok(Self {
+            client: ToolServiceClient::new(channel),
+            name: descriptor.name,
+            input_schema,
+            output_schema,
+            deadline: None,
+        })
+    }
+
+    /// Sends `deadline` as `grpc-timeout` and gives up locally once it passes.
+    pub fn with_deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+}
+
+#[async_trait]
+impl Tool for GrpcTool {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn input_schema(&self) -> Value {
+        self.input_schema.clone()
+    }
+
+    fn output_schema(&self) -> Value {
+        self.output_schema.clone()
+    }
+
+    async fn execute(&self, args: Value) -> Result<Value, ToolError> {
+        let mut request = Request::new(proto::InvokeRequest {
+            tool: self.name.clone(),
+            args_json: args.to_string(),
+        });
+        if let Some(deadline) = self.deadline {
+            request.set_timeout(deadline);
+        }
+        let call = self
+            .client
+            .unary::<_, proto::InvokeResponse>(INVOKE_PATH, request);
+        let response = match self.deadline {
+            Some(deadline) => tokio::time::timeout(deadline, call).await.map_err(|_| {
+                ToolError::Execution(format!("deadline of {}ms exceeded", deadline.as_millis()))
+            })?,
+            None => call.await,
+        }
+        .map_err(GrpcError::from)?;
+        serde_json::from_str(&response.into_inner().output_json).map_err(|e| {
+            ToolError::Execution(GrpcError::Protocol(format!("invalid output: {e}")).to_string())
+        })
+    }
+}
+
+/// Serves a [`ToolRegistry`] as `ToolService`, e.g. with
+/// `tonic::transport::Server::builder().add_service(server)`.
+///
+/// Invocations go through [`ToolRegistry::invoke_with`], so permissions, rate
+/// limits, timeouts and audit sinks apply exactly as for local callers. The
+/// client's `grpc-timeout` is enforced by tonic, which drops the in-flight tool
+/// once it passes.
+#[derive(Clone)]
+pub struct ToolServiceServer {
+    registry: Arc<ToolRegistry>,
+    caller_roles: Vec<String>,
+}
+
+impl ToolServiceServer {
+    pub fn new(registry: Arc<ToolRegistry>) -> Self {
+        Self {
+            registry,
+            caller_roles: Vec::new(),
+        }
+    }
+
+    /// Roles remote callers are granted when checking `allowed_roles`.
+    pub fn with_caller_roles(mut self, roles: Vec<String>) -> Self {
+        self.caller_roles = roles;
+        self
+    }
+
+    fn list_tools(&self) -> proto::ListToolsResponse {
+        let tools = self
+            .registry
+            .list_with_metadata()
+            .into_iter()
+            .filter_map(|(name, metadata)| {
+                let tool = self.registry.get(&name)?;
+                Some(proto::ToolDescriptor {
+                    description: metadata.description.unwrap_or_default(),
+                    input_schema_json: tool.input_schema().to_string(),
+                    output_schema_json: tool.output_schema().to_string(),
+                    tags: metadata.tags,
+                    name,
+                })
+            })
+            .collect();
+        proto::ListToolsResponse { tools }
+    }
+
+    async fn invoke(&self, request: proto::InvokeRequest) -> Result<proto::InvokeResponse, Status> {
+        let args: Value = if request.args_json.is_empty() {
+            json!({})
+        } else {
+            serde_json::from_str(&request.args_json)
+                .map_err(|e| Status::invalid_argument(format!("args_json is not JSON: {e}")))?
+        };
+        let ctx = InvocationContext::new(&self.caller_roles);
+        let output = self
+            .registry
+            .invoke_with(&request.tool, args, &ctx)
+            .await
+            .map_err(invocation_status)?;
+        Ok(proto::InvokeResponse {
+            output_json: output.to_string(),
+        })
+    }
+}
+
+/// Adapts one `ToolService` method to tonic's unary handler.
+struct Method<F>(ToolServiceServer, F);
+
+impl<Req, Resp, F, Fut> tonic::server::UnaryService<Req> for Method<F>
+where
+    F: Fn(ToolServiceServer, Req) -> Fut,
+    Fut: std::future::Future<Output = Result<Resp, Status>> + Send + 'static,
+{
+    type Response = Resp;
+    type Future = BoxFuture<Response<Resp>, Status>;
+
+    fn call(&mut self, request: Request<Req>) -> Self::Future {
+        let fut = (self.1)(self.0.clone(), request.into_inner());
+        Box::pin(async move { fut.await.map(Response::new) })
+    }
+}
+
+async fn serve_unary<B, Req, Resp, F, Fut>(
+    method: Method<F>,
+    request: http::Request<B>,
+) -> http::Response<tonic::body::Body>
+where
+    B: Body + Send + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+    Req: prost::Message + Default + Send + 'static,
+    Resp: prost::Message + Send + 'static,
+    F: Fn(ToolServiceServer, Req) -> Fut,
+    Fut: std::future::Future<Output = Result<Resp, Status>> + Send + 'static,
+{
+    let codec = tonic_prost::ProstCodec::<Resp, Req>::default();
+    tonic::server::Grpc::new(codec).unary(method, request).await
+}
+
+impl<B> Service<http::Request<B>> for ToolServiceServer
+where
+    B: Body<Data = Bytes> + Send + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+{
+    type Response = http::Response<tonic::body::Body>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: http::Request<B>) -> Self::Future {
+        let server = self.clone();
+        match request.uri().path() {
+            LIST_TOOLS_PATH => Box::pin(async move {
+                let method = Method(
+                    server,
+                    |server: ToolServiceServer, _: proto::ListToolsRequest| {
+                        let response = server.list_tools();
+                        async move { Ok(response) }
+                    },
+                );
+                Ok(serve_unary(method, request).await)
+            }),
+            INVOKE_PATH => Box::pin(async move {
+                let method = Method(
+                    server,
+                    |server: ToolServiceServer, req: proto::InvokeRequest| async move {
+                        server.invoke(req).await
+                    },
+                );
+                Ok(serve_unary(method, request).await)
+            }),
+            _ => Box::pin(async move { Ok(Status::unimplemented("unknown method").into_http()) }),
+        }
+    }
+}
+
+impl tonic::server::NamedService for ToolServiceServer {
+    const NAME: &'static str = SERVICE_NAME;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::SocketAddr;
+    use tonic::transport::server::TcpIncoming;
+
+    struct Echo;
+
+    #[async_trait]
+    impl Tool for Echo {
+        fn name(&self) -> &'static str {
+            "echo"
+        }
+
+        fn input_schema(&self) -> Value {
+            json!({"type": "object", "properties": {"text": {"type": "string"}}, "required": ["text"]})
+        }
+
+        fn output_schema(&self) -> Value {
+            json!({"type": "object", "properties": {"echo": {"type": "string"}}})
+        }
+
+        async fn execute(&self, args: Value) -> Result<Value, ToolError> {
+            let text = args
+                .get("text")
+                .and_then(Value::as_str)
+                .ok_or_else(|| ToolError::InvalidArgs("text is required".into()))?;
+            Ok(json!({"echo": text}))
+        }
+    }
+
+    struct Sleepy;
+
+    #[async_trait]
+    impl Tool for Sleepy {
+        fn name(&self) -> &'static str {
+            "sleepy"
+        }
+
+        fn input_schema(&self) -> Value {
+            json!({"type": "object"})
+        }
+
+        fn output_schema(&self) -> Value {
+            json!({"type": "object"})
+        }
+
+        async fn execute(&self, _args: Value) -> Result<Value, ToolError> {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            Ok(json!({}))
+        }
+    }
+
+    async fn spawn_server() -> SocketAddr {
+        let registry = ToolRegistry::new();
+        registry.register_with_metadata(
+            Echo,
+            ToolMetadata {
+                description: Some("Echoes its input".into()),
+                tags: vec!["text".into()],
+                ..Default::default()
+            },
+        );
+        registry.register_with_metadata(
+            Sleepy,
+            ToolMetadata {
+                allowed_roles: vec!["ops".into()],
+                ..Default::default()
+            },
+        );
+        let incoming = TcpIncoming::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let addr = incoming.local_addr().unwrap();
+        let service =
+            ToolServiceServer::new(Arc::new(registry)).with_caller_roles(vec!["ops".into()]);
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(service)
+                .serve_with_incoming(incoming),
+        );
+        addr
+    }
+
+    #[tokio::test]
+    async fn registers_and_invokes_remote_tools() {
+        let addr = spawn_server().await;
+        let provider = GrpcToolProvider::connect(format!("http://{addr}"))
+            .await
+            .unwrap();
+        let registry = ToolRegistry::new();
+        let names = provider.register_all(&registry).await.unwrap();
+        assert_eq!(names, vec!["echo".to_string(), "sleepy".to_string()]);
+
+        let echo = registry.get("echo").unwrap();
+        assert_eq!(echo.input_schema()["required"], json!(["text"]));
+        let metadata = registry.get_metadata("echo").unwrap();
+        assert_eq!(metadata.tags, vec!["text".to_string(), "grpc".to_string()]);
+
+        let output = registry
+            .invoke("echo", json!({"text": "hi"}), &[])
+            .await
+            .unwrap();
+        assert_eq!(output, json!({"echo": "hi"}));
+    }
+
+    #[tokio::test]
+    async fn maps_remote_failures_to_tool_errors() {
+        let addr = spawn_server().await;
+        let provider = GrpcToolProvider::connect(format!("http://{addr}"))
+            .await
+            .unwrap()
+            .with_deadline(Duration::from_millis(100));
+        let registry = ToolRegistry::new();
+        provider.register_all(&registry).await.unwrap();
+
+        let err = registry.invoke("echo", json!({}), &[]).await.unwrap_err();
+        assert!(
+            matches!(err, ToolInvocationError::Tool(ToolError::InvalidArgs(ref message)) if message.contains("text is required")),
+            "{err}"
+        );
+
+        let started = std::time::Instant::now();
+        let err = registry.invoke("sleepy", json!({}), &[]).await.unwrap_err();
+        assert!(
+            matches!(err, ToolInvocationError::Tool(ToolError::Execution(_))),
+            "{err}"
+        );
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn invocation_errors_become_matching_statuses() {
+        let status = invocation_status(ToolInvocationError::NotFound("x".into()));
+        assert_eq!(status.code(), Code::NotFound);
+        let status = invocation_status(ToolInvocationError::RateLimited {
+            tool: "x".into(),
+            retry_after_ms: 10,
+        });
+        assert_eq!(status.code(), Code::ResourceExhausted);
+        let status = invocation_status(ToolError::InvalidArgs("bad".into()).into());
+        assert_eq!(status.code(), Code::InvalidArgument);
+        assert!(matches!(
+            ToolError::from(GrpcError::from(status)),
+            ToolError::InvalidArgs(message) if message == "bad"
+        ));
+        let err = ToolError::from(GrpcError::from(Status::permission_denied("no")));
+        assert!(
+            matches!(err, ToolError::Execution(message) if message.contains("PermissionDenied"))
+        );
+    }
+}
@@ -1,10 +1,66 @@
+use agent_core::{AgentError, Redaction};
+use agent_telemetry::redact_paths;
 use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::collections::BTreeMap;
-use std::sync::{Arc, Mutex};
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard};
 use std::time::{Duration, Instant};
 use thiserror::Error;
 
+mod audit;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod mcp;
+mod middleware;
+pub mod openapi;
+mod pipeline;
+mod redaction;
+#[cfg(any(feature = "bing", feature = "tavily", feature = "serpapi"))]
+mod search_providers;
+mod secrets;
+
+// Lets `#[tool]` expansions, which name `::agent_tools`, compile in this crate.
+extern crate self as agent_tools;
+
+pub use agent_tools_macros::tool;
+pub use audit::{AuditLogSink, ToolAuditRecord, ToolAuditSink};
+pub use middleware::ToolMiddleware;
+pub use pipeline::{PipelineStage, PipelineTool};
+pub use redaction::OutputRedactor;
+pub use secrets::{
+    AzureKeyVaultSecrets, EnvSecrets, FileSecrets, RedactingSecrets, SecretRedactor,
+    SecretsProvider,
+};
+
+#[cfg(feature = "encryption")]
+pub use secrets::encrypted_store;
+pub use tokio_util::sync::CancellationToken;
+
+/// Support code for [`tool`] expansions; not a stable API.
+#[doc(hidden)]
+pub mod __private {
+    pub use async_trait::async_trait;
+    pub use schemars;
+    pub use serde;
+    pub use serde_json;
+
+    /// JSON schema of `T` without the `$schema` and `title` keys.
+    pub fn schema_of<T: schemars::JsonSchema>(description: Option<&str>) -> serde_json::Value {
+        let mut schema = serde_json::to_value(schemars::schema_for!(T)).unwrap_or_default();
+        if let Some(object) = schema.as_object_mut() {
+            object.remove("$schema");
+            object.remove("title");
+            if let Some(description) = description {
+                object.insert("description".into(), description.into());
+            }
+        }
+        schema
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum ToolError {
     #[error("invalid arguments: {0}")]
@@ -15,10 +71,63 @@ pub enum ToolError {
 
 #[async_trait]
 pub trait Tool: Send + Sync {
-    fn name(&self) -> &'static str;
+    fn name(&self) -> &str;
     fn input_schema(&self) -> Value;
     fn output_schema(&self) -> Value;
     async fn execute(&self, args: Value) -> Result<Value, ToolError>;
+
+    /// Runs the tool for the registry with the caller's context, for tools
+    /// that invoke other tools on the caller's behalf and may fail with any
+    /// [`ToolInvocationError`]. Defaults to [`Tool::execute`].
+    async fn execute_with(
+        &self,
+        args: Value,
+        _ctx: &InvocationContext,
+    ) -> Result<Value, ToolInvocationError> {
+        Ok(self.execute(args).await?)
+    }
+
+    /// Declares whether the tool is safe to retry and what it touches.
+    /// Defaults to the conservative [`SideEffects::default`].
+    fn side_effects(&self) -> SideEffects {
+        SideEffects::default()
+    }
+
+    /// What a call with `args` touches, for tools whose operations differ,
+    /// e.g. reads and writes. Defaults to [`Tool::side_effects`].
+    fn side_effects_for(&self, _args: &Value) -> SideEffects {
+        self.side_effects()
+    }
+}
+
+/// Retry-safety metadata a tool declares about itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SideEffects {
+    /// Repeating a call with the same arguments yields the same result and state.
+    pub idempotent: bool,
+    pub reads_external: bool,
+    pub writes_external: bool,
+}
+
+impl SideEffects {
+    /// Pure computation with no external reads or writes.
+    pub const fn pure() -> Self {
+        Self {
+            idempotent: true,
+            reads_external: false,
+            writes_external: false,
+        }
+    }
+}
+
+impl Default for SideEffects {
+    fn default() -> Self {
+        Self {
+            idempotent: false,
+            reads_external: true,
+            writes_external: true,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Default)]
@@ -29,6 +138,121 @@ pub struct ToolMetadata {
     pub cooldown: Option<Duration>,
     pub access_controller: Option<AccessController>,
     pub rate_limit: Option<RateLimitPolicy>,
+    /// JSON pointers into the args (e.g. `/api_key`) masked when invocations are logged.
+    pub sensitive_arg_paths: Vec<String>,
+    /// Reuse results of identical calls; intended for idempotent tools.
+    pub cache: Option<CachePolicy>,
+    /// Abort the tool if a single execution runs longer than this.
+    pub timeout: Option<Duration>,
+    /// Charged against the registry's [`ToolBudget`] for every executed call.
+    pub cost_per_call: Option<f64>,
+    /// Sample calls rendered into the description of exported function specs.
+    /// Not inherited from namespace defaults.
+    pub examples: Vec<ToolExample>,
+}
+
+/// A sample call shown to models alongside a tool's description.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolExample {
+    /// When to make a call like this one.
+    pub description: String,
+    pub args: Value,
+    pub output: Value,
+}
+
+impl ToolExample {
+    pub fn new(description: impl Into<String>, args: Value, output: Value) -> Self {
+        Self {
+            description: description.into(),
+            args,
+            output,
+        }
+    }
+}
+
+impl ToolMetadata {
+    /// Fills every field left unset with the value from `defaults`.
+    fn inherit(&mut self, defaults: &ToolMetadata) {
+        fn fill<T: Clone>(field: &mut Option<T>, default: &Option<T>) {
+            if field.is_none() {
+                field.clone_from(default);
+            }
+        }
+        fn fill_vec<T: Clone>(field: &mut Vec<T>, default: &[T]) {
+            if field.is_empty() {
+                field.extend_from_slice(default);
+            }
+        }
+        fill(&mut self.description, &defaults.description);
+        fill_vec(&mut self.tags, &defaults.tags);
+        fill_vec(&mut self.allowed_roles, &defaults.allowed_roles);
+        fill(&mut self.cooldown, &defaults.cooldown);
+        fill(&mut self.access_controller, &defaults.access_controller);
+        fill(&mut self.rate_limit, &defaults.rate_limit);
+        fill_vec(&mut self.sensitive_arg_paths, &defaults.sensitive_arg_paths);
+        fill(&mut self.cache, &defaults.cache);
+        fill(&mut self.timeout, &defaults.timeout);
+        fill(&mut self.cost_per_call, &defaults.cost_per_call);
+    }
+}
+
+/// The description followed by an `Examples:` section listing each
+/// [`ToolExample`] with its arguments and expected output.
+fn spec_description(metadata: &ToolMetadata) -> Option<String> {
+    if metadata.examples.is_empty() {
+        return metadata.description.clone();
+    }
+    let mut text = metadata.description.clone().unwrap_or_default();
+    if !text.is_empty() {
+        text.push_str("\n\n");
+    }
+    text.push_str("Examples:");
+    for example in &metadata.examples {
+        text.push_str(&format!(
+            "\n- {}\n  args: {}\n  output: {}",
+            example.description, example.args, example.output
+        ));
+    }
+    Some(text)
+}
+
+/// Provider payload shape produced by [`ToolRegistry::to_function_specs`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FunctionSpecFormat {
+    /// `{"type": "function", "function": {name, description, parameters}}`.
+    OpenAi,
+    /// Same shape as [`FunctionSpecFormat::OpenAi`].
+    AzureOpenAi,
+    /// `{name, description, input_schema}`.
+    Anthropic,
+}
+
+/// Maps a tool name onto the `^[a-zA-Z0-9_-]{1,64}$` names providers accept:
+/// namespace separators become `__` and other characters `_`.
+pub fn function_name(tool: &str) -> String {
+    let mut name = String::with_capacity(tool.len());
+    for c in tool.chars() {
+        match c {
+            '/' => name.push_str("__"),
+            c if c.is_ascii_alphanumeric() || c == '_' || c == '-' => name.push(c),
+            _ => name.push('_'),
+        }
+    }
+    name.truncate(64);
+    name
+}
+
+/// Spending cap for tool calls, tracked separately for every
+/// `(run_id, caller_id)` pair of the [`InvocationContext`].
+#[derive(Debug, Clone, Copy)]
+pub struct ToolBudget {
+    pub limit: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct CachePolicy {
+    pub ttl: Duration,
+    pub max_entries: usize,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -41,18 +265,167 @@ pub struct AccessController {
 pub struct RateLimitPolicy {
     pub max_calls: u64,
     pub per: Duration,
+    /// Keep a separate window per [`InvocationContext::caller_id`] instead of
+    /// one window shared by every caller.
+    pub per_caller: bool,
+}
+
+/// Who is invoking a tool, used for access control and per-caller limits.
+#[derive(Debug, Clone, Default)]
+pub struct InvocationContext {
+    pub caller_roles: Vec<String>,
+    /// Agent name or tenant id identifying the caller.
+    pub caller_id: Option<String>,
+    /// Aborts the in-flight tool when cancelled, e.g. because the run was cancelled.
+    pub cancellation: Option<CancellationToken>,
+    /// Run the call belongs to; scopes [`ToolBudget`] spending.
+    pub run_id: Option<String>,
+    redactions: Arc<Mutex<Vec<Redaction>>>,
+}
+
+impl InvocationContext {
+    pub fn new(caller_roles: &[String]) -> Self {
+        Self {
+            caller_roles: caller_roles.to_vec(),
+            caller_id: None,
+            cancellation: None,
+            run_id: None,
+            redactions: Arc::default(),
+        }
+    }
+
+    pub fn with_caller_id(mut self, caller_id: impl Into<String>) -> Self {
+        self.caller_id = Some(caller_id.into());
+        self
+    }
+
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    pub fn with_run_id(mut self, run_id: impl Into<String>) -> Self {
+        self.run_id = Some(run_id.into());
+        self
+    }
+
+    /// Drains what the registry's [`OutputRedactor`] masked in calls made with
+    /// this context, e.g. to attach it to `StepOutcome::redactions`.
+    pub fn take_redactions(&self) -> Vec<Redaction> {
+        std::mem::take(&mut *self.redactions.lock().expect("redaction log poisoned"))
+    }
+
+    fn budget_key(&self) -> String {
+        format!(
+            "{}\u{0}{}",
+            self.run_id.as_deref().unwrap_or_default(),
+            self.caller_id.as_deref().unwrap_or_default()
+        )
+    }
 }
 
+#[derive(Clone)]
 struct ToolEntry {
     tool: Arc<dyn Tool>,
-    metadata: ToolMetadata,
+    metadata: Arc<ToolMetadata>,
 }
 
 #[derive(Default)]
 pub struct ToolRegistry {
-    tools: BTreeMap<String, ToolEntry>, // deterministic ordering
+    tools: RwLock<BTreeMap<String, ToolEntry>>, // deterministic ordering
     last_invoked: Mutex<BTreeMap<String, Instant>>, // cooldown tracking
     rate_windows: Mutex<BTreeMap<String, RateWindow>>, // rate limiter
+    provenance: bool,                           // attach provenance in invoke_result
+    middlewares: Vec<Arc<dyn ToolMiddleware>>,
+    audit_sinks: Vec<Arc<dyn ToolAuditSink>>,
+    redactor: Option<SecretRedactor>,
+    output_redactor: Option<OutputRedactor>,
+    caches: Mutex<BTreeMap<String, ToolCache>>, // per-tool result cache
+    namespace_defaults: BTreeMap<String, ToolMetadata>,
+    tag_rate_limits: BTreeMap<String, RateLimitPolicy>,
+    global_rate_limit: Option<RateLimitPolicy>,
+    budget: Option<ToolBudget>,
+    spent: Mutex<BTreeMap<String, f64>>, // budget spending per run/caller
+}
+
+#[derive(Default)]
+struct ToolCache {
+    entries: BTreeMap<String, (Instant, Value)>,
+    order: VecDeque<String>, // insertion order for eviction
+    ttl: Duration,
+}
+
+impl ToolCache {
+    fn get(&mut self, key: &str) -> Option<Value> {
+        let (stored_at, value) = self.entries.get(key)?;
+        if stored_at.elapsed() <= self.ttl {
+            return Some(value.clone());
+        }
+        self.entries.remove(key);
+        self.order.retain(|k| k != key);
+        None
+    }
+
+    fn insert(&mut self, key: String, value: Value, policy: &CachePolicy) {
+        self.ttl = policy.ttl;
+        if self
+            .entries
+            .insert(key.clone(), (Instant::now(), value))
+            .is_some()
+        {
+            self.order.retain(|k| k != &key);
+        }
+        self.order.push_back(key);
+        while self.order.len() > policy.max_entries.max(1) {
+            if let Some(oldest) = self.order.pop_front() {
+                self.entries.remove(&oldest);
+            }
+        }
+    }
+}
+
+/// Serializes `value` with object keys sorted so equal args share a cache key.
+fn canonical_json(value: &Value) -> String {
+    match value {
+        Value::Object(map) => {
+            let mut keys: Vec<&String> = map.keys().collect();
+            keys.sort();
+            let fields: Vec<String> = keys
+                .into_iter()
+                .map(|key| {
+                    format!(
+                        "{}:{}",
+                        Value::String(key.clone()),
+                        canonical_json(&map[key])
+                    )
+                })
+                .collect();
+            format!("{{{}}}", fields.join(","))
+        }
+        Value::Array(items) => {
+            let items: Vec<String> = items.iter().map(canonical_json).collect();
+            format!("[{}]", items.join(","))
+        }
+        other => other.to_string(),
+    }
+}
+
+/// Tool output together with where it came from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolResult {
+    pub value: Value,
+    /// Present when the registry was built with [`ToolRegistry::with_provenance`].
+    pub provenance: Option<Provenance>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Provenance {
+    pub tool: String,
+    /// Hex-encoded SHA-256 of the serialized args.
+    pub args_hash: String,
+    /// RFC 3339 time the invocation started.
+    pub timestamp: String,
+    pub duration_ms: u64,
 }
 
 #[derive(Debug, Clone)]
@@ -66,55 +439,499 @@ impl ToolRegistry {
         Self::default()
     }
 
-    pub fn register<T: Tool + 'static>(&mut self, tool: T) {
+    /// Makes [`ToolRegistry::invoke_result`] record [`Provenance`] for each call.
+    pub fn with_provenance(mut self) -> Self {
+        self.provenance = true;
+        self
+    }
+
+    /// Appends a middleware; see [`ToolMiddleware`] for ordering.
+    pub fn add_middleware<M: ToolMiddleware + 'static>(&mut self, middleware: M) {
+        self.middlewares.push(Arc::new(middleware));
+    }
+
+    pub fn with_middleware<M: ToolMiddleware + 'static>(mut self, middleware: M) -> Self {
+        self.add_middleware(middleware);
+        self
+    }
+
+    /// Masks secrets known to `redactor` in returned errors, logged args and
+    /// audit records. Pair it with providers wrapped by [`SecretRedactor::track`].
+    pub fn with_secret_redactor(mut self, redactor: SecretRedactor) -> Self {
+        self.redactor = Some(redactor);
+        self
+    }
+
+    /// Masks tool output before it reaches callers, middlewares' callers and
+    /// audit sinks; see [`InvocationContext::take_redactions`] for the report.
+    pub fn with_output_redactor(mut self, redactor: OutputRedactor) -> Self {
+        self.output_redactor = Some(redactor);
+        self
+    }
+
+    fn redact_error(&self, error: ToolInvocationError) -> ToolInvocationError {
+        let Some(redactor) = &self.redactor else {
+            return error;
+        };
+        match error {
+            ToolInvocationError::Tool(ToolError::Execution(message)) => {
+                ToolInvocationError::Tool(ToolError::Execution(redactor.redact_str(&message)))
+            }
+            ToolInvocationError::Tool(ToolError::InvalidArgs(message)) => {
+                ToolInvocationError::Tool(ToolError::InvalidArgs(redactor.redact_str(&message)))
+            }
+            other => other,
+        }
+    }
+
+    fn redact_value(&self, value: Value) -> Value {
+        match &self.redactor {
+            Some(redactor) => redactor.redact_value(&value),
+            None => value,
+        }
+    }
+
+    /// Reports every invocation, successful or not, to `sink`.
+    pub fn add_audit_sink<S: ToolAuditSink + 'static>(&mut self, sink: S) {
+        self.audit_sinks.push(Arc::new(sink));
+    }
+
+    pub fn with_audit_sink<S: ToolAuditSink + 'static>(mut self, sink: S) -> Self {
+        self.add_audit_sink(sink);
+        self
+    }
+
+    /// Registers `tool`, replacing any tool with the same name. Invocations
+    /// already in flight finish against the tool they started with.
+    pub fn register<T: Tool + 'static>(&self, tool: T) {
         self.register_with_metadata(tool, ToolMetadata::default());
     }
 
-    pub fn register_with_metadata<T: Tool + 'static>(&mut self, tool: T, metadata: ToolMetadata) {
-        self.tools.insert(
-            tool.name().to_string(),
-            ToolEntry {
-                tool: Arc::new(tool),
-                metadata,
-            },
-        );
+    pub fn register_with_metadata<T: Tool + 'static>(&self, tool: T, metadata: ToolMetadata) {
+        let name = tool.name().to_string();
+        let replaced = self
+            .tools
+            .write()
+            .expect("tool registry lock poisoned")
+            .insert(
+                name.clone(),
+                ToolEntry {
+                    tool: Arc::new(tool),
+                    metadata: Arc::new(metadata),
+                },
+            );
+        if replaced.is_some() {
+            self.forget_state(&name);
+        }
+    }
+
+    /// Removes `name` from the registry, returning the removed tool.
+    pub fn unregister(&self, name: &str) -> Option<Arc<dyn Tool>> {
+        let removed = self
+            .tools
+            .write()
+            .expect("tool registry lock poisoned")
+            .remove(name)?;
+        self.forget_state(name);
+        Some(removed.tool)
+    }
+
+    /// Drops cached results and cooldown/rate state of a replaced or removed tool.
+    fn forget_state(&self, name: &str) {
+        self.caches
+            .lock()
+            .expect("tool cache mutex poisoned")
+            .remove(name);
+        self.last_invoked
+            .lock()
+            .expect("cooldown mutex poisoned")
+            .remove(name);
+        let caller_prefix = format!("{name}\u{0}");
+        self.rate_windows
+            .lock()
+            .expect("rate limiter mutex poisoned")
+            .retain(|key, _| key != name && !key.starts_with(&caller_prefix));
+    }
+
+    fn entries(&self) -> RwLockReadGuard<'_, BTreeMap<String, ToolEntry>> {
+        self.tools.read().expect("tool registry lock poisoned")
+    }
+
+    fn entry(&self, name: &str) -> Option<ToolEntry> {
+        self.entries().get(name).cloned()
     }
 
     pub fn get(&self, name: &str) -> Option<Arc<dyn Tool>> {
-        self.tools.get(name).map(|entry| entry.tool.clone())
+        self.entry(name).map(|entry| entry.tool)
+    }
+
+    pub fn side_effects(&self, name: &str) -> Option<SideEffects> {
+        self.entry(name).map(|entry| entry.tool.side_effects())
+    }
+
+    /// The side effects of calling `name` with `args`.
+    pub fn side_effects_for(&self, name: &str, args: &Value) -> Option<SideEffects> {
+        self.entry(name)
+            .map(|entry| entry.tool.side_effects_for(args))
     }
 
     pub fn get_metadata(&self, name: &str) -> Option<ToolMetadata> {
-        self.tools.get(name).map(|entry| entry.metadata.clone())
+        self.entry(name)
+            .map(|entry| self.effective_metadata(name, &entry.metadata).into_owned())
     }
 
     pub fn list(&self) -> Vec<String> {
-        self.tools.keys().cloned().collect()
+        self.entries().keys().cloned().collect()
+    }
+
+    /// Names of the tools under `namespace`, including nested namespaces.
+    pub fn list_namespace(&self, namespace: &str) -> Vec<String> {
+        let prefix = format!("{}/", namespace.trim_end_matches('/'));
+        self.entries()
+            .keys()
+            .filter(|name| name.starts_with(&prefix))
+            .cloned()
+            .collect()
     }
 
     pub fn list_with_metadata(&self) -> Vec<(String, ToolMetadata)> {
-        self.tools
+        self.entries()
+            .iter()
+            .map(|(name, entry)| {
+                let metadata = self.effective_metadata(name, &entry.metadata);
+                (name.clone(), metadata.into_owned())
+            })
+            .collect()
+    }
+
+    /// Renders every registered tool as a provider `tools` entry, in name order.
+    /// Names pass through [`function_name`]; map a model's call back to a tool
+    /// with [`ToolRegistry::tool_for_function`].
+    pub fn to_function_specs(&self, format: FunctionSpecFormat) -> Vec<Value> {
+        self.entries()
             .iter()
-            .map(|(name, entry)| (name.clone(), entry.metadata.clone()))
+            .map(|(name, entry)| {
+                let metadata = self.effective_metadata(name, &entry.metadata);
+                let mut spec = serde_json::Map::new();
+                spec.insert("name".into(), Value::String(function_name(name)));
+                if let Some(description) = spec_description(&metadata) {
+                    spec.insert("description".into(), Value::String(description));
+                }
+                let schema = entry.tool.input_schema();
+                match format {
+                    FunctionSpecFormat::OpenAi | FunctionSpecFormat::AzureOpenAi => {
+                        spec.insert("parameters".into(), schema);
+                        serde_json::json!({"type": "function", "function": spec})
+                    }
+                    FunctionSpecFormat::Anthropic => {
+                        spec.insert("input_schema".into(), schema);
+                        Value::Object(spec)
+                    }
+                }
+            })
             .collect()
     }
 
+    /// Registered tool whose [`function_name`] is `function`.
+    pub fn tool_for_function(&self, function: &str) -> Option<String> {
+        self.entries()
+            .keys()
+            .find(|name| function_name(name) == function)
+            .cloned()
+    }
+
+    /// Sets metadata inherited by tools under `namespace` (`fs` for `fs/read`)
+    /// for every field the tool leaves unset. Nearer namespaces take precedence;
+    /// rate limits still apply per tool.
+    pub fn set_namespace_defaults(&mut self, namespace: impl Into<String>, defaults: ToolMetadata) {
+        self.namespace_defaults.insert(namespace.into(), defaults);
+    }
+
+    pub fn with_namespace_defaults(
+        mut self,
+        namespace: impl Into<String>,
+        defaults: ToolMetadata,
+    ) -> Self {
+        self.set_namespace_defaults(namespace, defaults);
+        self
+    }
+
+    /// Shares one rate window across every tool tagged `tag`, on top of the
+    /// tools' own limits, e.g. to cap calls to a downstream provider.
+    pub fn set_tag_rate_limit(&mut self, tag: impl Into<String>, policy: RateLimitPolicy) {
+        self.tag_rate_limits.insert(tag.into(), policy);
+    }
+
+    pub fn with_tag_rate_limit(mut self, tag: impl Into<String>, policy: RateLimitPolicy) -> Self {
+        self.set_tag_rate_limit(tag, policy);
+        self
+    }
+
+    /// Shares one rate window across every tool in the registry.
+    pub fn with_global_rate_limit(mut self, policy: RateLimitPolicy) -> Self {
+        self.global_rate_limit = Some(policy);
+        self
+    }
+
+    fn effective_metadata<'a>(
+        &self,
+        name: &str,
+        metadata: &'a ToolMetadata,
+    ) -> Cow<'a, ToolMetadata> {
+        let mut effective = Cow::Borrowed(metadata);
+        for (slash, _) in name.rmatch_indices('/') {
+            if let Some(defaults) = self.namespace_defaults.get(&name[..slash]) {
+                effective.to_mut().inherit(defaults);
+            }
+        }
+        effective
+    }
+
     pub async fn invoke(
         &self,
         name: &str,
         args: Value,
         caller_roles: &[String],
+    ) -> Result<Value, ToolInvocationError> {
+        self.invoke_with(name, args, &InvocationContext::new(caller_roles))
+            .await
+    }
+
+    /// Like [`ToolRegistry::invoke`], with the caller described by `ctx`.
+    pub async fn invoke_with(
+        &self,
+        name: &str,
+        args: Value,
+        ctx: &InvocationContext,
+    ) -> Result<Value, ToolInvocationError> {
+        let mut args = args;
+        let started = Instant::now();
+        let result = self
+            .run_pipeline(name, &mut args, ctx)
+            .await
+            .map_err(|error| self.redact_error(error));
+        if let Err(error) = &result {
+            for middleware in &self.middlewares {
+                middleware.on_error(name, &args, error).await;
+            }
+        }
+        if !self.audit_sinks.is_empty() {
+            let sensitive = self
+                .get_metadata(name)
+                .map(|metadata| metadata.sensitive_arg_paths)
+                .unwrap_or_default();
+            let args = self.redact_value(redact_paths(&args, &sensitive));
+            let output = result
+                .as_ref()
+                .map(|output| self.redact_value(output.clone()));
+            let record = ToolAuditRecord {
+                tool: name,
+                args: &args,
+                result: output.as_ref().map_err(|error| *error),
+                duration: started.elapsed(),
+                caller_id: ctx.caller_id.as_deref(),
+                caller_roles: &ctx.caller_roles,
+            };
+            for sink in &self.audit_sinks {
+                sink.record(&record).await;
+            }
+        }
+        result
+    }
+
+    /// Enforces policies, then executes the tool wrapped in the middlewares.
+    async fn run_pipeline(
+        &self,
+        name: &str,
+        args: &mut Value,
+        ctx: &InvocationContext,
     ) -> Result<Value, ToolInvocationError> {
         let entry = self
-            .tools
-            .get(name)
+            .entry(name)
             .ok_or_else(|| ToolInvocationError::NotFound(name.to_string()))?;
 
-        self.enforce_access(name, &entry.metadata, caller_roles)?;
-        self.enforce_cooldown(name, &entry.metadata)?;
-        self.enforce_rate_limit(name, &entry.metadata)?;
+        let metadata = self.effective_metadata(name, &entry.metadata);
+        self.enforce_access(name, &metadata, &ctx.caller_roles)?;
+
+        let mut ran = 0;
+        let mut short_circuit = None;
+        for middleware in &self.middlewares {
+            ran += 1;
+            if let Some(value) = middleware.before_invoke(name, args).await? {
+                short_circuit = Some(value);
+                break;
+            }
+        }
+
+        let mut output = match short_circuit {
+            Some(value) => value,
+            None => {
+                self.execute_entry(name, &entry.tool, &metadata, args, ctx)
+                    .await?
+            }
+        };
+        // Redact first so middlewares never see, log or cache unmasked output.
+        if let Some(redactor) = &self.output_redactor {
+            let report = redactor.redact(name, &mut output);
+            if !report.is_empty() {
+                ctx.redactions
+                    .lock()
+                    .expect("redaction log poisoned")
+                    .extend(report);
+            }
+        }
+        for middleware in self.middlewares[..ran].iter().rev() {
+            middleware.after_invoke(name, args, &mut output).await?;
+        }
+        Ok(output)
+    }
+
+    /// Serves cached output when available; otherwise applies cooldown and rate
+    /// limits, executes the tool and caches the result.
+    async fn execute_entry(
+        &self,
+        name: &str,
+        tool: &Arc<dyn Tool>,
+        metadata: &ToolMetadata,
+        args: &mut Value,
+        ctx: &InvocationContext,
+    ) -> Result<Value, ToolInvocationError> {
+        let cache_key = metadata.cache.as_ref().map(|_| canonical_json(args));
+        if let Some(key) = &cache_key {
+            let mut caches = self.caches.lock().expect("tool cache mutex poisoned");
+            if let Some(value) = caches.get_mut(name).and_then(|cache| cache.get(key)) {
+                tracing::debug!(target: "agent-tools::registry", tool = name, "tool cache hit");
+                return Ok(value);
+            }
+        }
+
+        // A call the budget rejects takes no cooldown or rate-limit slot, and
+        // one they reject gets its charge back.
+        self.charge_budget(name, metadata, ctx)?;
+        if let Err(error) = self
+            .enforce_cooldown(name, metadata)
+            .and_then(|()| self.enforce_rate_limit(name, metadata, ctx.caller_id.as_deref()))
+        {
+            self.refund_budget(metadata, ctx);
+            return Err(error);
+        }
+
+        tracing::debug!(
+            target: "agent-tools::registry",
+            tool = name,
+            args = %self.redact_value(redact_paths(args, &metadata.sensitive_arg_paths)),
+            "invoking tool"
+        );
+        // Without middlewares or audit sinks nothing needs the args afterwards.
+        let call_args = if self.middlewares.is_empty() && self.audit_sinks.is_empty() {
+            std::mem::take(args)
+        } else {
+            args.clone()
+        };
+        let execution = async {
+            match metadata.timeout {
+                Some(limit) => tokio::time::timeout(limit, tool.execute_with(call_args, ctx))
+                    .await
+                    .map_err(|_| ToolInvocationError::TimedOut {
+                        tool: name.to_string(),
+                        timeout_ms: limit.as_millis() as u64,
+                    })?,
+                None => tool.execute_with(call_args, ctx).await,
+            }
+        };
+        let output = match &ctx.cancellation {
+            Some(token) => tokio::select! {
+                biased;
+                _ = token.cancelled() => {
+                    return Err(ToolInvocationError::Cancelled {
+                        tool: name.to_string(),
+                    })
+                }
+                result = execution => result?,
+            },
+            None => execution.await?,
+        };
+
+        if let (Some(key), Some(policy)) = (cache_key, &metadata.cache) {
+            self.caches
+                .lock()
+                .expect("tool cache mutex poisoned")
+                .entry(name.to_string())
+                .or_default()
+                .insert(key, output.clone(), policy);
+        }
+        Ok(output)
+    }
 
-        Ok(entry.tool.execute(args).await?)
+    /// Caps the cumulative `cost_per_call` of executed calls; see [`ToolBudget`].
+    pub fn with_budget(mut self, budget: ToolBudget) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// Total cost charged so far for the run/caller of `ctx`.
+    pub fn spent(&self, ctx: &InvocationContext) -> f64 {
+        self.spent
+            .lock()
+            .expect("budget mutex poisoned")
+            .get(&ctx.budget_key())
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Allowance left for the run/caller of `ctx`, if a budget is set.
+    pub fn remaining_budget(&self, ctx: &InvocationContext) -> Option<f64> {
+        self.budget
+            .map(|budget| (budget.limit - self.spent(ctx)).max(0.0))
+    }
+
+    /// Forgets the spending of the run/caller of `ctx`, e.g. when a run ends.
+    pub fn reset_budget(&self, ctx: &InvocationContext) {
+        self.spent
+            .lock()
+            .expect("budget mutex poisoned")
+            .remove(&ctx.budget_key());
+    }
+
+    /// Drops every cached tool result.
+    pub fn clear_cache(&self) {
+        self.caches
+            .lock()
+            .expect("tool cache mutex poisoned")
+            .clear();
+    }
+
+    /// Like [`ToolRegistry::invoke`], but wraps the output in a [`ToolResult`].
+    pub async fn invoke_result(
+        &self,
+        name: &str,
+        args: Value,
+        caller_roles: &[String],
+    ) -> Result<ToolResult, ToolInvocationError> {
+        if !self.provenance {
+            let value = self.invoke(name, args, caller_roles).await?;
+            return Ok(ToolResult {
+                value,
+                provenance: None,
+            });
+        }
+
+        let args_hash = Sha256::digest(args.to_string().as_bytes())
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+        let timestamp = chrono::Utc::now().to_rfc3339();
+        let started = Instant::now();
+        let value = self.invoke(name, args, caller_roles).await?;
+        Ok(ToolResult {
+            value,
+            provenance: Some(Provenance {
+                tool: name.to_string(),
+                args_hash,
+                timestamp,
+                duration_ms: started.elapsed().as_millis() as u64,
+            }),
+        })
     }
 
     fn enforce_access(
@@ -155,43 +972,102 @@ impl ToolRegistry {
         }
     }
 
+    /// Takes a slot in the tool's own window and in every tag and global
+    /// window that applies, or in none of them when any is exhausted.
     fn enforce_rate_limit(
         &self,
         name: &str,
         metadata: &ToolMetadata,
+        caller_id: Option<&str>,
     ) -> Result<(), ToolInvocationError> {
-        let Some(policy) = &metadata.rate_limit else {
-            return Ok(());
+        let window_key = |scope: &str, policy: &RateLimitPolicy| match caller_id {
+            Some(caller) if policy.per_caller => format!("{scope}\u{0}{caller}"),
+            _ => scope.to_string(),
         };
+        // Group windows are keyed with a leading \u{1} so they never clash with tool names.
+        let mut scopes = BTreeMap::new();
+        if let Some(policy) = &metadata.rate_limit {
+            scopes.insert(window_key(name, policy), policy);
+        }
+        for tag in &metadata.tags {
+            if let Some(policy) = self.tag_rate_limits.get(tag) {
+                scopes.insert(window_key(&format!("\u{1}tag:{tag}"), policy), policy);
+            }
+        }
+        if let Some(policy) = &self.global_rate_limit {
+            scopes.insert(window_key("\u{1}global", policy), policy);
+        }
+        if scopes.is_empty() {
+            return Ok(());
+        }
 
         let mut guard = self
             .rate_windows
             .lock()
             .expect("rate limiter mutex poisoned");
-        let window = guard.entry(name.to_string()).or_insert_with(|| RateWindow {
-            started_at: Instant::now(),
-            calls: 0,
-        });
-
-        if window.started_at.elapsed() > policy.per {
-            window.started_at = Instant::now();
-            window.calls = 0;
-        }
-
-        if window.calls >= policy.max_calls {
+        let mut retry_after = None;
+        for (key, policy) in &scopes {
+            let window = guard.entry(key.clone()).or_insert_with(|| RateWindow {
+                started_at: Instant::now(),
+                calls: 0,
+            });
+            if window.started_at.elapsed() > policy.per {
+                window.started_at = Instant::now();
+                window.calls = 0;
+            }
+            if window.calls >= policy.max_calls {
+                let wait = policy.per.saturating_sub(window.started_at.elapsed());
+                retry_after = retry_after.max(Some(wait));
+            }
+        }
+        if let Some(wait) = retry_after {
             return Err(ToolInvocationError::RateLimited {
                 tool: name.to_string(),
-                retry_after_ms: policy
-                    .per
-                    .saturating_sub(window.started_at.elapsed())
-                    .as_millis() as u64,
+                retry_after_ms: wait.as_millis() as u64,
             });
         }
+        for key in scopes.keys() {
+            if let Some(window) = guard.get_mut(key) {
+                window.calls += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Charges `cost_per_call` up front; failed calls still count as spent.
+    fn charge_budget(
+        &self,
+        name: &str,
+        metadata: &ToolMetadata,
+        ctx: &InvocationContext,
+    ) -> Result<(), ToolInvocationError> {
+        let (Some(budget), Some(cost)) = (self.budget, metadata.cost_per_call) else {
+            return Ok(());
+        };
 
-        window.calls += 1;
+        let mut guard = self.spent.lock().expect("budget mutex poisoned");
+        let spent = guard.entry(ctx.budget_key()).or_default();
+        if *spent + cost > budget.limit {
+            return Err(ToolInvocationError::BudgetExceeded {
+                tool: name.to_string(),
+                cost,
+                remaining: (budget.limit - *spent).max(0.0),
+            });
+        }
+        *spent += cost;
         Ok(())
     }
 
+    fn refund_budget(&self, metadata: &ToolMetadata, ctx: &InvocationContext) {
+        let (Some(_), Some(cost)) = (self.budget, metadata.cost_per_call) else {
+            return;
+        };
+        let mut guard = self.spent.lock().expect("budget mutex poisoned");
+        if let Some(spent) = guard.get_mut(&ctx.budget_key()) {
+            *spent = (*spent - cost).max(0.0);
+        }
+    }
+
     fn enforce_cooldown(
         &self,
         name: &str,
@@ -224,16 +1100,57 @@ pub enum ToolInvocationError {
     CoolingDown { tool: String, remaining_ms: u64 },
     #[error("tool {tool} rate limited, retry after {retry_after_ms}ms")]
     RateLimited { tool: String, retry_after_ms: u64 },
+    #[error("tool {tool} timed out after {timeout_ms}ms")]
+    TimedOut { tool: String, timeout_ms: u64 },
+    #[error("tool {tool} cancelled")]
+    Cancelled { tool: String },
+    #[error("tool {tool} costs {cost} but only {remaining} of the budget remains")]
+    BudgetExceeded {
+        tool: String,
+        cost: f64,
+        remaining: f64,
+    },
     #[error(transparent)]
     Tool(#[from] ToolError),
 }
 
+impl From<ToolInvocationError> for AgentError {
+    fn from(err: ToolInvocationError) -> Self {
+        match err {
+            ToolInvocationError::RateLimited {
+                tool,
+                retry_after_ms,
+            } => AgentError::RateLimited {
+                tool,
+                retry_after_ms,
+            },
+            ToolInvocationError::CoolingDown { tool, remaining_ms } => AgentError::RateLimited {
+                tool,
+                retry_after_ms: remaining_ms,
+            },
+            ToolInvocationError::AccessDenied { .. } => AgentError::Safety(err.to_string()),
+            ToolInvocationError::TimedOut { .. } => AgentError::Timeout,
+            ToolInvocationError::Cancelled { .. } => AgentError::Cancelled,
+            other => AgentError::Tool(other.to_string()),
+        }
+    }
+}
+
 pub mod builtins {
-    use super::{Tool, ToolError};
+    use super::{SecretRedactor, SecretsProvider, SideEffects, Tool, ToolError};
+    use agent_core::HttpClientConfig;
+    use agent_memory::Embedder;
+    use agent_memory::{MemoryStore, SemanticMemory};
     use async_trait::async_trait;
     use serde::{Deserialize, Serialize};
     use serde_json::Value;
+    use std::collections::BTreeMap;
+    use std::process::Stdio;
+    use std::sync::Arc;
+    use std::time::Duration;
     use tokio::fs;
+    use tokio::io::{AsyncRead, AsyncReadExt};
+    use tokio::process::Command;
 
     use std::fs as stdfs;
     use std::path::PathBuf;
@@ -254,23 +1171,68 @@ pub mod builtins {
             serde_json::json!({"type": "string", "description": "ISO-8601 timestamp"})
         }
 
+        fn side_effects(&self) -> SideEffects {
+            SideEffects::pure()
+        }
+
         async fn execute(&self, _args: Value) -> Result<Value, ToolError> {
             let now = chrono::Utc::now().to_rfc3339();
             Ok(Value::String(now))
         }
     }
 
+    /// Size caps applied by [`FileTool`] operations.
+    #[derive(Debug, Clone)]
+    pub struct FileLimits {
+        pub max_read_bytes: u64,
+        /// Applies to the content of a single `write` or `append`.
+        pub max_write_bytes: usize,
+        /// Caps the entries returned by `list` and `glob`.
+        pub max_entries: usize,
+    }
+
+    impl Default for FileLimits {
+        fn default() -> Self {
+            Self {
+                max_read_bytes: 1024 * 1024,
+                max_write_bytes: 1024 * 1024,
+                max_entries: 1000,
+            }
+        }
+    }
+
+    /// File access confined to a root directory.
+    ///
+    /// Operations: `read`, `write`, `append`, `list`, `delete` (set `recursive`
+    /// for non-empty directories), `mkdir`, `stat` and `glob` (`pattern` such as
+    /// `**/*.md`, matched below `path`). Paths are resolved inside the root and
+    /// symlinks cannot escape it.
     pub struct FileTool {
         root: PathBuf,
+        read_only: bool,
+        limits: FileLimits,
     }
 
     impl FileTool {
         pub fn new(root: impl AsRef<std::path::Path>) -> Self {
             Self {
                 root: root.as_ref().to_path_buf(),
+                read_only: false,
+                limits: FileLimits::default(),
             }
         }
 
+        /// Rejects `write`, `append`, `delete` and `mkdir`.
+        pub fn with_read_only(mut self, read_only: bool) -> Self {
+            self.read_only = read_only;
+            self
+        }
+
+        pub fn with_limits(mut self, limits: FileLimits) -> Self {
+            self.limits = limits;
+            self
+        }
+
         fn canonical_root(&self) -> Result<PathBuf, ToolError> {
             stdfs::create_dir_all(&self.root)
                 .map_err(|e| ToolError::Execution(format!("failed to create root: {e}")))?;
@@ -313,6 +1275,91 @@ pub mod builtins {
             }
             Ok(resolved)
         }
+
+        fn relative(&self, path: &std::path::Path) -> String {
+            let root = self.canonical_root().unwrap_or_else(|_| self.root.clone());
+            path.strip_prefix(&root)
+                .unwrap_or(path)
+                .to_string_lossy()
+                .replace('\\', "/")
+        }
+
+        fn content_arg<'a>(&self, args: &'a Value, op: &str) -> Result<&'a str, ToolError> {
+            let content = args
+                .get("content")
+                .and_then(|v| v.as_str())
+                .ok_or_else(|| ToolError::InvalidArgs(format!("content missing for {op}")))?;
+            if content.len() > self.limits.max_write_bytes {
+                return Err(ToolError::InvalidArgs(format!(
+                    "content exceeds {} byte limit",
+                    self.limits.max_write_bytes
+                )));
+            }
+            Ok(content)
+        }
+
+        /// Depth-first walk below `dir` collecting files and directories whose
+        /// root-relative path matches `pattern`. Symlinks are not followed.
+        fn glob(
+            &self,
+            dir: &std::path::Path,
+            pattern: &[&str],
+        ) -> Result<(Vec<String>, bool), ToolError> {
+            let base = self.relative(dir);
+            let mut matches = Vec::new();
+            let mut stack = vec![dir.to_path_buf()];
+            while let Some(current) = stack.pop() {
+                let mut entries: Vec<_> = stdfs::read_dir(&current)
+                    .map_err(|e| ToolError::Execution(format!("glob failed: {e}")))?
+                    .filter_map(Result::ok)
+                    .collect();
+                entries.sort_by_key(|entry| std::cmp::Reverse(entry.file_name()));
+                for entry in entries {
+                    let path = entry.path();
+                    let relative = self.relative(&path);
+                    let below = relative
+                        .strip_prefix(&base)
+                        .unwrap_or(&relative)
+                        .trim_start_matches('/')
+                        .to_string();
+                    let segments: Vec<&str> = below.split('/').collect();
+                    if glob_matches(pattern, &segments) {
+                        if matches.len() == self.limits.max_entries {
+                            return Ok((matches, true));
+                        }
+                        matches.push(relative);
+                    }
+                    if entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+                        stack.push(path);
+                    }
+                }
+            }
+            matches.sort();
+            Ok((matches, false))
+        }
+    }
+
+    /// Matches path segments against glob segments: `**` spans any number of
+    /// segments, `*` any run of characters and `?` one character.
+    fn glob_matches(pattern: &[&str], path: &[&str]) -> bool {
+        match pattern.split_first() {
+            None => path.is_empty(),
+            Some((&"**", rest)) => (0..=path.len()).any(|skip| glob_matches(rest, &path[skip..])),
+            Some((segment, rest)) => {
+                !path.is_empty()
+                    && segment_matches(segment.as_bytes(), path[0].as_bytes())
+                    && glob_matches(rest, &path[1..])
+            }
+        }
+    }
+
+    fn segment_matches(pattern: &[u8], text: &[u8]) -> bool {
+        match pattern.split_first() {
+            None => text.is_empty(),
+            Some((b'*', rest)) => (0..=text.len()).any(|skip| segment_matches(rest, &text[skip..])),
+            Some((b'?', rest)) => !text.is_empty() && segment_matches(rest, &text[1..]),
+            Some((c, rest)) => text.first() == Some(c) && segment_matches(rest, &text[1..]),
+        }
     }
 
     #[async_trait]
@@ -326,8 +1373,13 @@ pub mod builtins {
                 "type": "object",
                 "properties": {
                     "path": {"type": "string"},
-                    "operation": {"type": "string", "enum": ["read", "write"]},
-                    "content": {"type": "string"}
+                    "operation": {
+                        "type": "string",
+                        "enum": ["read", "write", "append", "list", "delete", "mkdir", "stat", "glob"]
+                    },
+                    "content": {"type": "string"},
+                    "pattern": {"type": "string"},
+                    "recursive": {"type": "boolean"}
                 },
                 "required": ["path", "operation"],
                 "additionalProperties": false
@@ -341,13 +1393,44 @@ pub mod builtins {
                     "path": {"type": "string"},
                     "operation": {"type": "string"},
                     "content": {"type": "string"},
-                    "bytes": {"type": "integer"}
+                    "bytes": {"type": "integer"},
+                    "entries": {"type": "array"},
+                    "matches": {"type": "array", "items": {"type": "string"}},
+                    "truncated": {"type": "boolean"},
+                    "is_dir": {"type": "boolean"},
+                    "size": {"type": "integer"},
+                    "modified": {"type": "string"}
                 },
                 "required": ["path", "operation"],
                 "additionalProperties": false
             })
         }
 
+        // Writes make repeated calls unsafe to retry blindly.
+        fn side_effects(&self) -> SideEffects {
+            SideEffects {
+                idempotent: self.read_only,
+                reads_external: true,
+                writes_external: !self.read_only,
+            }
+        }
+
+        fn side_effects_for(&self, args: &Value) -> SideEffects {
+            match args.get("operation").and_then(Value::as_str) {
+                Some("read" | "list" | "stat" | "glob") => SideEffects {
+                    idempotent: true,
+                    reads_external: true,
+                    writes_external: false,
+                },
+                Some("write" | "append" | "delete" | "mkdir") => SideEffects {
+                    idempotent: false,
+                    reads_external: true,
+                    writes_external: true,
+                },
+                _ => self.side_effects(),
+            }
+        }
+
         async fn execute(&self, args: Value) -> Result<Value, ToolError> {
             let path = args
                 .get("path")
@@ -357,45 +1440,631 @@ pub mod builtins {
                 .get("operation")
                 .and_then(|v| v.as_str())
                 .ok_or_else(|| ToolError::InvalidArgs("operation missing".into()))?;
-            let resolved = self.resolve(path, op == "write")?;
+            let mutating = matches!(op, "write" | "append" | "delete" | "mkdir");
+            if mutating && self.read_only {
+                return Err(ToolError::InvalidArgs(format!(
+                    "{op} not allowed: file tool is read-only"
+                )));
+            }
+            let resolved = self.resolve(path, matches!(op, "write" | "append" | "mkdir"))?;
+            let display = resolved.display().to_string();
 
             match op {
                 "read" => {
+                    let metadata = fs::metadata(&resolved)
+                        .await
+                        .map_err(|e| ToolError::Execution(format!("read failed: {e}")))?;
+                    if metadata.len() > self.limits.max_read_bytes {
+                        return Err(ToolError::InvalidArgs(format!(
+                            "file exceeds {} byte read limit",
+                            self.limits.max_read_bytes
+                        )));
+                    }
                     let content = fs::read_to_string(&resolved)
                         .await
                         .map_err(|e| ToolError::Execution(format!("read failed: {e}")))?;
                     Ok(serde_json::json!({
-                        "path": resolved.display().to_string(),
+                        "path": display,
                         "operation": "read",
                         "content": content
                     }))
                 }
-                "write" => {
-                    let content =
-                        args.get("content")
-                            .and_then(|v| v.as_str())
-                            .ok_or_else(|| {
-                                ToolError::InvalidArgs("content missing for write".into())
-                            })?;
+                "write" | "append" => {
+                    let content = self.content_arg(&args, op)?;
                     if let Some(parent) = resolved.parent() {
                         fs::create_dir_all(parent).await.map_err(|e| {
                             ToolError::Execution(format!("failed to create directories: {e}"))
                         })?;
                     }
-                    fs::write(&resolved, content)
-                        .await
-                        .map_err(|e| ToolError::Execution(format!("write failed: {e}")))?;
+                    if op == "write" {
+                        fs::write(&resolved, content)
+                            .await
+                            .map_err(|e| ToolError::Execution(format!("write failed: {e}")))?;
+                    } else {
+                        use tokio::io::AsyncWriteExt;
+                        let mut file = fs::OpenOptions::new()
+                            .create(true)
+                            .append(true)
+                            .open(&resolved)
+                            .await
+                            .map_err(|e| ToolError::Execution(format!("append failed: {e}")))?;
+                        file.write_all(content.as_bytes())
+                            .await
+                            .map_err(|e| ToolError::Execution(format!("append failed: {e}")))?;
+                    }
                     Ok(serde_json::json!({
-                        "path": resolved.display().to_string(),
-                        "operation": "write",
+                        "path": display,
+                        "operation": op,
                         "bytes": content.len()
                     }))
                 }
+                "mkdir" => {
+                    fs::create_dir_all(&resolved)
+                        .await
+                        .map_err(|e| ToolError::Execution(format!("mkdir failed: {e}")))?;
+                    Ok(serde_json::json!({"path": display, "operation": "mkdir"}))
+                }
+                "delete" => {
+                    if resolved == self.canonical_root()? {
+                        return Err(ToolError::InvalidArgs("cannot delete sandbox root".into()));
+                    }
+                    let recursive = args
+                        .get("recursive")
+                        .and_then(Value::as_bool)
+                        .unwrap_or(false);
+                    let result = if resolved.is_dir() {
+                        if recursive {
+                            fs::remove_dir_all(&resolved).await
+                        } else {
+                            fs::remove_dir(&resolved).await
+                        }
+                    } else {
+                        fs::remove_file(&resolved).await
+                    };
+                    result.map_err(|e| ToolError::Execution(format!("delete failed: {e}")))?;
+                    Ok(serde_json::json!({"path": display, "operation": "delete"}))
+                }
+                "list" => {
+                    let mut reader = fs::read_dir(&resolved)
+                        .await
+                        .map_err(|e| ToolError::Execution(format!("list failed: {e}")))?;
+                    let mut entries = Vec::new();
+                    while let Some(entry) = reader
+                        .next_entry()
+                        .await
+                        .map_err(|e| ToolError::Execution(format!("list failed: {e}")))?
+                    {
+                        let metadata = entry
+                            .metadata()
+                            .await
+                            .map_err(|e| ToolError::Execution(format!("list failed: {e}")))?;
+                        entries.push(serde_json::json!({
+                            "name": entry.file_name().to_string_lossy(),
+                            "is_dir": metadata.is_dir(),
+                            "size": metadata.len()
+                        }));
+                    }
+                    entries.sort_by(|a, b| a["name"].as_str().cmp(&b["name"].as_str()));
+                    let truncated = entries.len() > self.limits.max_entries;
+                    entries.truncate(self.limits.max_entries);
+                    Ok(serde_json::json!({
+                        "path": display,
+                        "operation": "list",
+                        "entries": entries,
+                        "truncated": truncated
+                    }))
+                }
+                "stat" => {
+                    let metadata = fs::metadata(&resolved)
+                        .await
+                        .map_err(|e| ToolError::Execution(format!("stat failed: {e}")))?;
+                    let modified = metadata
+                        .modified()
+                        .ok()
+                        .map(|time| chrono::DateTime::<chrono::Utc>::from(time).to_rfc3339());
+                    Ok(serde_json::json!({
+                        "path": display,
+                        "operation": "stat",
+                        "is_dir": metadata.is_dir(),
+                        "size": metadata.len(),
+                        "modified": modified
+                    }))
+                }
+                "glob" => {
+                    let pattern = args
+                        .get("pattern")
+                        .and_then(|v| v.as_str())
+                        .ok_or_else(|| ToolError::InvalidArgs("pattern missing for glob".into()))?;
+                    if pattern.split('/').any(|segment| segment == "..") {
+                        return Err(ToolError::InvalidArgs("pattern escapes sandbox".into()));
+                    }
+                    let segments: Vec<&str> = pattern.split('/').collect();
+                    let (matches, truncated) = self.glob(&resolved, &segments)?;
+                    Ok(serde_json::json!({
+                        "path": display,
+                        "operation": "glob",
+                        "matches": matches,
+                        "truncated": truncated
+                    }))
+                }
                 _ => Err(ToolError::InvalidArgs("unsupported operation".into())),
             }
         }
     }
 
+    type Row = serde_json::Map<String, Value>;
+    /// Column names in file order, and the rows.
+    type Table = (Vec<String>, Vec<Row>);
+
+    /// Queries CSV and JSONL files inside a [`FileTool`] sandbox.
+    ///
+    /// Operations: `head` and `sample` (`limit` rows, `sample` reproducible
+    /// through `seed`), `filter` (rows matching every entry of `filters`) and
+    /// `aggregate` (`metrics` such as `{"op": "avg", "column": "price"}`,
+    /// optionally per `group_by` columns). `filters` apply to every operation.
+    /// CSV needs a header row; cells that parse as numbers become numbers and
+    /// empty cells become `null`.
+    pub struct TableTool {
+        files: FileTool,
+    }
+
+    impl TableTool {
+        /// Reads through `files`, sharing its root and read limits.
+        pub fn new(files: FileTool) -> Self {
+            Self { files }
+        }
+
+        async fn load(&self, path: &str, format: Option<&str>) -> Result<Table, ToolError> {
+            let resolved = self.files.resolve(path, false)?;
+            let metadata = fs::metadata(&resolved)
+                .await
+                .map_err(|e| ToolError::Execution(format!("read failed: {e}")))?;
+            if metadata.len() > self.files.limits.max_read_bytes {
+                return Err(ToolError::InvalidArgs(format!(
+                    "file exceeds {} byte read limit",
+                    self.files.limits.max_read_bytes
+                )));
+            }
+            let content = fs::read_to_string(&resolved)
+                .await
+                .map_err(|e| ToolError::Execution(format!("read failed: {e}")))?;
+            let format = match format {
+                Some(format) => format.to_string(),
+                None => resolved
+                    .extension()
+                    .and_then(|ext| ext.to_str())
+                    .unwrap_or_default()
+                    .to_ascii_lowercase(),
+            };
+            match format.as_str() {
+                "csv" => parse_csv(&content),
+                "jsonl" | "ndjson" => parse_jsonl(&content),
+                other => Err(ToolError::InvalidArgs(format!(
+                    "unsupported table format `{other}`; use csv or jsonl"
+                ))),
+            }
+        }
+    }
+
+    fn parse_csv(content: &str) -> Result<Table, ToolError> {
+        let mut records = csv_records(content)?.into_iter();
+        let Some(columns) = records.next() else {
+            return Ok((Vec::new(), Vec::new()));
+        };
+        let rows = records
+            .filter(|record| !(record.len() == 1 && record[0].is_empty()))
+            .enumerate()
+            .map(|(index, record)| {
+                if record.len() != columns.len() {
+                    return Err(ToolError::Execution(format!(
+                        "row {} has {} fields, expected {}",
+                        index + 1,
+                        record.len(),
+                        columns.len()
+                    )));
+                }
+                Ok(columns
+                    .iter()
+                    .cloned()
+                    .zip(record.into_iter().map(|cell| csv_value(&cell)))
+                    .collect())
+            })
+            .collect::<Result<_, _>>()?;
+        Ok((columns, rows))
+    }
+
+    /// Splits RFC 4180 CSV into records, honouring quoted commas, newlines
+    /// and doubled quotes.
+    fn csv_records(content: &str) -> Result<Vec<Vec<String>>, ToolError> {
+        let mut records = Vec::new();
+        let mut record = Vec::new();
+        let mut field = String::new();
+        let mut quoted = false;
+        let mut chars = content.trim_start_matches('\u{feff}').chars().peekable();
+        while let Some(c) = chars.next() {
+            match (quoted, c) {
+                (true, '"') if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                (true, '"') => quoted = false,
+                (true, c) => field.push(c),
+                (false, '"') if field.is_empty() => quoted = true,
+                (false, ',') => record.push(std::mem::take(&mut field)),
+                (false, '\r') if chars.peek() == Some(&'\n') => {}
+                (false, '\n') => {
+                    record.push(std::mem::take(&mut field));
+                    records.push(std::mem::take(&mut record));
+                }
+                (false, c) => field.push(c),
+            }
+        }
+        if quoted {
+            return Err(ToolError::Execution("unterminated quoted CSV field".into()));
+        }
+        if !field.is_empty() || !record.is_empty() {
+            record.push(field);
+            records.push(record);
+        }
+        Ok(records)
+    }
+
+    fn csv_value(cell: &str) -> Value {
+        if cell.is_empty() {
+            return Value::Null;
+        }
+        match cell.parse::<f64>() {
+            Ok(number) if number.is_finite() => serde_json::json!(number),
+            _ => Value::String(cell.to_string()),
+        }
+    }
+
+    fn parse_jsonl(content: &str) -> Result<Table, ToolError> {
+        let mut columns: Vec<String> = Vec::new();
+        let mut rows = Vec::new();
+        for (index, line) in content.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let row = match serde_json::from_str::<Value>(line) {
+                Ok(Value::Object(row)) => row,
+                Ok(_) => {
+                    return Err(ToolError::Execution(format!(
+                        "line {} is not a JSON object",
+                        index + 1
+                    )))
+                }
+                Err(e) => {
+                    return Err(ToolError::Execution(format!(
+                        "line {} is not valid JSON: {e}",
+                        index + 1
+                    )))
+                }
+            };
+            for key in row.keys() {
+                if !columns.contains(key) {
+                    columns.push(key.clone());
+                }
+            }
+            rows.push(row);
+        }
+        Ok((columns, rows))
+    }
+
+    /// Orders a cell against a filter value: numerically when both are
+    /// numbers, otherwise by their text.
+    fn compare_cells(cell: &Value, target: &Value) -> Option<std::cmp::Ordering> {
+        if let (Some(a), Some(b)) = (cell.as_f64(), target.as_f64()) {
+            return a.partial_cmp(&b);
+        }
+        let text = |value: &Value| match value {
+            Value::String(s) => Some(s.clone()),
+            Value::Null => None,
+            other => Some(other.to_string()),
+        };
+        Some(text(cell)?.cmp(&text(target)?))
+    }
+
+    fn row_matches(row: &Row, filter: &Value) -> Result<bool, ToolError> {
+        let column = filter
+            .get("column")
+            .and_then(Value::as_str)
+            .ok_or_else(|| ToolError::InvalidArgs("filter column missing".into()))?;
+        let op = filter.get("op").and_then(Value::as_str).unwrap_or("eq");
+        let target = filter.get("value").unwrap_or(&Value::Null);
+        let cell = row.get(column).unwrap_or(&Value::Null);
+        let ordering = compare_cells(cell, target);
+        use std::cmp::Ordering::*;
+        Ok(match op {
+            "eq" => ordering == Some(Equal) || (cell.is_null() && target.is_null()),
+            "ne" => ordering != Some(Equal) && !(cell.is_null() && target.is_null()),
+            "gt" => ordering == Some(Greater),
+            "gte" => matches!(ordering, Some(Greater | Equal)),
+            "lt" => ordering == Some(Less),
+            "lte" => matches!(ordering, Some(Less | Equal)),
+            "contains" => match (cell, target.as_str()) {
+                (Value::String(text), Some(needle)) => text.contains(needle),
+                _ => false,
+            },
+            other => {
+                return Err(ToolError::InvalidArgs(format!(
+                    "unknown filter op `{other}`"
+                )))
+            }
+        })
+    }
+
+    /// Computes `metrics` over `rows`, once per distinct `group_by` key.
+    fn aggregate(
+        rows: &[Row],
+        group_by: &[String],
+        metrics: &[Value],
+    ) -> Result<Vec<Value>, ToolError> {
+        let mut groups: BTreeMap<String, (Row, Vec<usize>)> = BTreeMap::new();
+        for (index, row) in rows.iter().enumerate() {
+            let key: Row = group_by
+                .iter()
+                .map(|column| {
+                    (
+                        column.clone(),
+                        row.get(column).cloned().unwrap_or(Value::Null),
+                    )
+                })
+                .collect();
+            groups
+                .entry(Value::Object(key.clone()).to_string())
+                .or_insert_with(|| (key, Vec::new()))
+                .1
+                .push(index);
+        }
+        if groups.is_empty() && group_by.is_empty() {
+            groups.insert(String::new(), (serde_json::Map::new(), Vec::new()));
+        }
+
+        groups
+            .into_values()
+            .map(|(mut result, members)| {
+                for metric in metrics {
+                    let op = metric
+                        .get("op")
+                        .and_then(Value::as_str)
+                        .ok_or_else(|| ToolError::InvalidArgs("metric op missing".into()))?;
+                    let column = metric.get("column").and_then(Value::as_str);
+                    let values: Vec<f64> = match column {
+                        Some(column) => members
+                            .iter()
+                            .filter_map(|&i| rows[i].get(column).and_then(Value::as_f64))
+                            .collect(),
+                        None => Vec::new(),
+                    };
+                    if op != "count" && column.is_none() {
+                        return Err(ToolError::InvalidArgs(format!(
+                            "metric {op} needs a column"
+                        )));
+                    }
+                    let value = match op {
+                        "count" => serde_json::json!(members.len()),
+                        "sum" => serde_json::json!(values.iter().sum::<f64>()),
+                        "avg" if values.is_empty() => Value::Null,
+                        "avg" => {
+                            serde_json::json!(values.iter().sum::<f64>() / values.len() as f64)
+                        }
+                        "min" => values
+                            .iter()
+                            .copied()
+                            .reduce(f64::min)
+                            .map_or(Value::Null, |v| serde_json::json!(v)),
+                        "max" => values
+                            .iter()
+                            .copied()
+                            .reduce(f64::max)
+                            .map_or(Value::Null, |v| serde_json::json!(v)),
+                        other => {
+                            return Err(ToolError::InvalidArgs(format!(
+                                "unknown metric op `{other}`"
+                            )))
+                        }
+                    };
+                    let name = match column {
+                        Some(column) => format!("{op}_{column}"),
+                        None => op.to_string(),
+                    };
+                    result.insert(name, value);
+                }
+                Ok(Value::Object(result))
+            })
+            .collect()
+    }
+
+    /// Picks `limit` row indices with reservoir sampling driven by a small
+    /// xorshift generator, so the same `seed` yields the same sample.
+    fn sample_indices(len: usize, limit: usize, seed: u64) -> Vec<usize> {
+        let mut state = seed ^ 0x9E37_79B9_7F4A_7C15;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        let mut picked: Vec<usize> = (0..len.min(limit)).collect();
+        for index in limit..len {
+            let slot = (next() % (index as u64 + 1)) as usize;
+            if slot < limit {
+                picked[slot] = index;
+            }
+        }
+        picked.sort_unstable();
+        picked
+    }
+
+    #[async_trait]
+    impl Tool for TableTool {
+        fn name(&self) -> &'static str {
+            "table"
+        }
+
+        fn input_schema(&self) -> Value {
+            serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "path": {"type": "string"},
+                    "format": {"type": "string", "enum": ["csv", "jsonl"]},
+                    "operation": {
+                        "type": "string",
+                        "enum": ["head", "sample", "filter", "aggregate"]
+                    },
+                    "filters": {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "column": {"type": "string"},
+                                "op": {
+                                    "type": "string",
+                                    "enum": ["eq", "ne", "gt", "gte", "lt", "lte", "contains"]
+                                },
+                                "value": {}
+                            },
+                            "required": ["column"]
+                        }
+                    },
+                    "group_by": {"type": "array", "items": {"type": "string"}},
+                    "metrics": {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "op": {
+                                    "type": "string",
+                                    "enum": ["count", "sum", "avg", "min", "max"]
+                                },
+                                "column": {"type": "string"}
+                            },
+                            "required": ["op"]
+                        }
+                    },
+                    "limit": {"type": "integer", "minimum": 1},
+                    "seed": {"type": "integer", "minimum": 0}
+                },
+                "required": ["path", "operation"],
+                "additionalProperties": false
+            })
+        }
+
+        fn output_schema(&self) -> Value {
+            serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "path": {"type": "string"},
+                    "operation": {"type": "string"},
+                    "columns": {"type": "array", "items": {"type": "string"}},
+                    "total_rows": {"type": "integer"},
+                    "matched_rows": {"type": "integer"},
+                    "rows": {"type": "array", "items": {"type": "object"}}
+                },
+                "required": ["path", "operation", "columns", "rows"]
+            })
+        }
+
+        fn side_effects(&self) -> SideEffects {
+            SideEffects {
+                idempotent: true,
+                reads_external: true,
+                writes_external: false,
+            }
+        }
+
+        async fn execute(&self, args: Value) -> Result<Value, ToolError> {
+            let path = args
+                .get("path")
+                .and_then(Value::as_str)
+                .ok_or_else(|| ToolError::InvalidArgs("path missing".into()))?;
+            let op = args
+                .get("operation")
+                .and_then(Value::as_str)
+                .ok_or_else(|| ToolError::InvalidArgs("operation missing".into()))?;
+            let limit = args.get("limit").and_then(Value::as_u64).unwrap_or(10) as usize;
+
+            let (columns, rows) = self
+                .load(path, args.get("format").and_then(Value::as_str))
+                .await?;
+            let total_rows = rows.len();
+            let filters = args
+                .get("filters")
+                .and_then(Value::as_array)
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            let mut matched = Vec::new();
+            for row in rows {
+                let mut keep = true;
+                for filter in filters {
+                    if !row_matches(&row, filter)? {
+                        keep = false;
+                        break;
+                    }
+                }
+                if keep {
+                    matched.push(row);
+                }
+            }
+            let matched_rows = matched.len();
+
+            let (columns, rows): (Vec<String>, Vec<Value>) = match op {
+                "head" | "filter" => (
+                    columns,
+                    matched.into_iter().take(limit).map(Value::Object).collect(),
+                ),
+                "sample" => {
+                    let seed = args.get("seed").and_then(Value::as_u64).unwrap_or(0);
+                    let rows = sample_indices(matched.len(), limit, seed)
+                        .into_iter()
+                        .map(|index| Value::Object(matched[index].clone()))
+                        .collect();
+                    (columns, rows)
+                }
+                "aggregate" => {
+                    let group_by: Vec<String> = args
+                        .get("group_by")
+                        .and_then(Value::as_array)
+                        .map(|columns| {
+                            columns
+                                .iter()
+                                .filter_map(Value::as_str)
+                                .map(str::to_string)
+                                .collect()
+                        })
+                        .unwrap_or_default();
+                    let metrics = args
+                        .get("metrics")
+                        .and_then(Value::as_array)
+                        .filter(|metrics| !metrics.is_empty())
+                        .ok_or_else(|| ToolError::InvalidArgs("aggregate needs metrics".into()))?;
+                    let rows = aggregate(&matched, &group_by, metrics)?;
+                    let columns = rows
+                        .first()
+                        .and_then(Value::as_object)
+                        .map(|row| row.keys().cloned().collect())
+                        .unwrap_or(group_by);
+                    (columns, rows)
+                }
+                other => {
+                    return Err(ToolError::InvalidArgs(format!(
+                        "unknown table operation `{other}`"
+                    )))
+                }
+            };
+
+            Ok(serde_json::json!({
+                "path": path,
+                "operation": op,
+                "columns": columns,
+                "total_rows": total_rows,
+                "matched_rows": matched_rows,
+                "rows": rows,
+            }))
+        }
+    }
+
     pub struct MathTool;
 
     #[async_trait]
@@ -416,6 +2085,10 @@ pub mod builtins {
             serde_json::json!({"type": "number"})
         }
 
+        fn side_effects(&self) -> SideEffects {
+            SideEffects::pure()
+        }
+
         async fn execute(&self, args: Value) -> Result<Value, ToolError> {
             let expr = args
                 .get("expression")
@@ -434,11 +2107,99 @@ pub mod builtins {
         pub snippet: String,
     }
 
+    /// Maps a provider's raw JSON response into [`SearchResult`]s.
+    ///
+    /// Paths are either JSON pointers (`/data/items`) or dotted paths
+    /// (`data.items`). `results_path` locates the results array in the
+    /// response; the remaining paths are resolved against each result.
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    pub struct SearchResultMapper {
+        pub results_path: String,
+        pub title_path: String,
+        pub url_path: String,
+        pub snippet_path: String,
+    }
+
+    impl Default for SearchResultMapper {
+        fn default() -> Self {
+            Self {
+                results_path: String::new(),
+                title_path: "title".into(),
+                url_path: "url".into(),
+                snippet_path: "snippet".into(),
+            }
+        }
+    }
+
+    impl SearchResultMapper {
+        pub fn new(
+            results_path: impl Into<String>,
+            title_path: impl Into<String>,
+            url_path: impl Into<String>,
+            snippet_path: impl Into<String>,
+        ) -> Self {
+            Self {
+                results_path: results_path.into(),
+                title_path: title_path.into(),
+                url_path: url_path.into(),
+                snippet_path: snippet_path.into(),
+            }
+        }
+
+        pub fn from_json(&self, value: &Value) -> Result<Vec<SearchResult>, ToolError> {
+            let results = lookup(value, &self.results_path)
+                .and_then(Value::as_array)
+                .ok_or_else(|| {
+                    ToolError::Execution(format!(
+                        "no results array at path `{}`",
+                        self.results_path
+                    ))
+                })?;
+
+            results
+                .iter()
+                .enumerate()
+                .map(|(index, item)| {
+                    Ok(SearchResult {
+                        title: Self::field(item, index, &self.title_path)?,
+                        url: Self::field(item, index, &self.url_path)?,
+                        snippet: Self::field(item, index, &self.snippet_path)?,
+                    })
+                })
+                .collect()
+        }
+
+        fn field(item: &Value, index: usize, path: &str) -> Result<String, ToolError> {
+            match lookup(item, path) {
+                Some(Value::String(s)) => Ok(s.clone()),
+                Some(other) if !other.is_null() => Ok(other.to_string()),
+                _ => Err(ToolError::Execution(format!(
+                    "search result {index} missing field at path `{path}`"
+                ))),
+            }
+        }
+    }
+
+    fn lookup<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+        if path.is_empty() || path.starts_with('/') {
+            value.pointer(path)
+        } else {
+            value.pointer(&format!("/{}", path.replace('.', "/")))
+        }
+    }
+
     #[async_trait]
     pub trait SearchProvider: Send + Sync {
         async fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchResult>, ToolError>;
     }
 
+    #[cfg(feature = "bing")]
+    pub use crate::search_providers::BingSearchProvider;
+    #[cfg(feature = "serpapi")]
+    pub use crate::search_providers::SerpApiSearchProvider;
+    #[cfg(feature = "tavily")]
+    pub use crate::search_providers::TavilySearchProvider;
+
     pub struct SearchTool<P: SearchProvider> {
         provider: std::sync::Arc<P>,
     }
@@ -495,113 +2256,2752 @@ pub mod builtins {
             let results = self.provider.search(query, limit).await?;
             Ok(serde_json::to_value(results).map_err(|e| ToolError::Execution(e.to_string()))?)
         }
+
+        fn side_effects(&self) -> SideEffects {
+            SideEffects {
+                idempotent: true,
+                reads_external: true,
+                writes_external: false,
+            }
+        }
+    }
+
+    /// Ranks long-term memory entries by embedding similarity to a query,
+    /// through [`SemanticMemory::search_similar`].
+    ///
+    /// Stores without a vector index are ranked by embedding each entry's
+    /// [text](SemanticMemory::text_of) at query time, and must support
+    /// [`MemoryStore::entries`].
+    pub struct MemorySearchTool {
+        memory: SemanticMemory,
     }
 
-    pub struct LogTool;
+    impl MemorySearchTool {
+        pub fn new(store: Arc<dyn MemoryStore>, embedder: Arc<dyn Embedder>) -> Self {
+            Self {
+                memory: SemanticMemory::new(store, embedder),
+            }
+        }
+    }
 
     #[async_trait]
-    impl Tool for LogTool {
+    impl Tool for MemorySearchTool {
         fn name(&self) -> &'static str {
-            "log"
+            "memory_search"
         }
 
         fn input_schema(&self) -> Value {
-            serde_json::json!({"type": "object", "properties": {"message": {"type": "string"}}, "required": ["message"]})
-        }
-
-        fn output_schema(&self) -> Value {
-            serde_json::json!({"type": "object", "properties": {"ack": {"type": "boolean"}}})
-        }
+            serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "query": {"type": "string"},
+                    "top_k": {"type": "integer", "minimum": 1, "maximum": 50}
+                },
+                "required": ["query"]
+            })
+        }
+
+        fn output_schema(&self) -> Value {
+            serde_json::json!({
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "key": {"type": "string"},
+                        "value": {},
+                        "score": {"type": "number"}
+                    },
+                    "required": ["key", "value", "score"]
+                }
+            })
+        }
+
+        async fn execute(&self, args: Value) -> Result<Value, ToolError> {
+            let query = args
+                .get("query")
+                .and_then(|v| v.as_str())
+                .ok_or_else(|| ToolError::InvalidArgs("query missing".into()))?;
+            let top_k = args
+                .get("top_k")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(5)
+                .clamp(1, 50) as usize;
+
+            let hits = self
+                .memory
+                .search_similar(query, top_k, None)
+                .await
+                .map_err(|e| ToolError::Execution(e.to_string()))?;
+
+            Ok(Value::Array(
+                hits.into_iter()
+                    .filter(|hit| hit.score > 0.0)
+                    .map(|hit| {
+                        serde_json::json!({"key": hit.key, "value": hit.value, "score": hit.score})
+                    })
+                    .collect(),
+            ))
+        }
+
+        fn side_effects(&self) -> SideEffects {
+            SideEffects {
+                idempotent: true,
+                reads_external: true,
+                writes_external: false,
+            }
+        }
+    }
+
+    /// Writes `message` to the `agent-tools::log` tracing target. Secrets
+    /// known to its [`SecretRedactor`] are masked first, as in the registry's
+    /// own logs.
+    #[derive(Debug, Clone, Default)]
+    pub struct LogTool {
+        redactor: Option<SecretRedactor>,
+    }
+
+    impl LogTool {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        pub fn with_secret_redactor(mut self, redactor: SecretRedactor) -> Self {
+            self.redactor = Some(redactor);
+            self
+        }
+    }
+
+    #[async_trait]
+    impl Tool for LogTool {
+        fn name(&self) -> &'static str {
+            "log"
+        }
+
+        fn input_schema(&self) -> Value {
+            serde_json::json!({"type": "object", "properties": {"message": {"type": "string"}}, "required": ["message"]})
+        }
+
+        fn output_schema(&self) -> Value {
+            serde_json::json!({"type": "object", "properties": {"ack": {"type": "boolean"}}})
+        }
+
+        async fn execute(&self, args: Value) -> Result<Value, ToolError> {
+            if let Some(msg) = args.get("message").and_then(|v| v.as_str()) {
+                let msg = match &self.redactor {
+                    Some(redactor) => redactor.redact_str(msg),
+                    None => msg.to_string(),
+                };
+                tracing::info!(target: "agent-tools::log", message = %msg);
+                Ok(serde_json::json!({"ack": true}))
+            } else {
+                Err(ToolError::InvalidArgs("message missing".into()))
+            }
+        }
+    }
+
+    pub struct HttpFetchTool {
+        client: reqwest::Client,
+        policy: Option<HttpFetchPolicy>,
+    }
+
+    /// Restrictions applied by [`HttpFetchTool::with_policy`].
+    ///
+    /// Host patterns match the host itself and any subdomain, and the denylist
+    /// wins over the allowlist. An empty allowlist permits every host.
+    #[derive(Debug, Clone)]
+    pub struct HttpFetchPolicy {
+        pub allowed_hosts: Vec<String>,
+        pub denied_hosts: Vec<String>,
+        /// Responses larger than this are rejected without being fully read.
+        pub max_response_bytes: Option<usize>,
+        /// Refuse hosts resolving to loopback, private, link-local and similar ranges.
+        pub block_private_ips: bool,
+        pub max_redirects: usize,
+        pub timeout: Option<std::time::Duration>,
+    }
+
+    impl Default for HttpFetchPolicy {
+        fn default() -> Self {
+            Self {
+                allowed_hosts: Vec::new(),
+                denied_hosts: Vec::new(),
+                max_response_bytes: Some(1024 * 1024),
+                block_private_ips: true,
+                max_redirects: 5,
+                timeout: Some(std::time::Duration::from_secs(30)),
+            }
+        }
+    }
+
+    impl HttpFetchPolicy {
+        pub fn allow_host(mut self, host: impl Into<String>) -> Self {
+            self.allowed_hosts.push(host.into());
+            self
+        }
+
+        pub fn deny_host(mut self, host: impl Into<String>) -> Self {
+            self.denied_hosts.push(host.into());
+            self
+        }
+
+        fn host_permitted(&self, host: &str) -> bool {
+            let matches = |pattern: &String| {
+                let pattern = pattern.trim_start_matches('.');
+                host.eq_ignore_ascii_case(pattern)
+                    || host
+                        .to_ascii_lowercase()
+                        .ends_with(&format!(".{}", pattern.to_ascii_lowercase()))
+            };
+            !self.denied_hosts.iter().any(matches)
+                && (self.allowed_hosts.is_empty() || self.allowed_hosts.iter().any(matches))
+        }
+
+        /// Sets up `builder` to follow no redirects, time out as configured
+        /// and, when private addresses are blocked, connect only to the
+        /// public addresses a host resolves to. Checking at connect time
+        /// leaves nothing for a DNS server that answers differently the
+        /// second time to exploit; proxies are bypassed for the same reason.
+        fn configure(&self, mut builder: reqwest::ClientBuilder) -> reqwest::ClientBuilder {
+            builder = builder.redirect(reqwest::redirect::Policy::none());
+            if let Some(timeout) = self.timeout {
+                builder = builder.timeout(timeout);
+            }
+            if self.block_private_ips {
+                builder = builder
+                    .dns_resolver(Arc::new(PublicOnlyResolver))
+                    .no_proxy();
+            }
+            builder
+        }
+
+        fn build_client(&self, config: &HttpClientConfig) -> Result<reqwest::Client, ToolError> {
+            let builder = config
+                .client_builder()
+                .map_err(|e| ToolError::Execution(e.to_string()))?;
+            self.configure(builder)
+                .build()
+                .map_err(|e| ToolError::Execution(e.to_string()))
+        }
+
+        /// Checks the host lists and, if enabled, every address the host resolves to.
+        async fn check_url(&self, url: &reqwest::Url) -> Result<(), ToolError> {
+            if !matches!(url.scheme(), "http" | "https") {
+                return Err(ToolError::InvalidArgs(format!(
+                    "unsupported scheme {}",
+                    url.scheme()
+                )));
+            }
+            let host = url
+                .host_str()
+                .ok_or_else(|| ToolError::InvalidArgs("url has no host".into()))?;
+            if !self.host_permitted(host) {
+                return Err(ToolError::InvalidArgs(format!(
+                    "host {host} is not allowed"
+                )));
+            }
+            if self.block_private_ips {
+                let port = url.port_or_known_default().unwrap_or(80);
+                let host = host.trim_start_matches('[').trim_end_matches(']');
+                let addrs = tokio::net::lookup_host((host, port))
+                    .await
+                    .map_err(|e| ToolError::Execution(format!("failed to resolve {host}: {e}")))?;
+                for addr in addrs {
+                    if is_private_ip(addr.ip()) {
+                        return Err(ToolError::InvalidArgs(format!(
+                            "host {host} resolves to a private address"
+                        )));
+                    }
+                }
+            }
+            Ok(())
+        }
+    }
+
+    /// Resolves hosts like the system resolver but fails for any that
+    /// resolve to an address [`is_private_ip`] refuses.
+    struct PublicOnlyResolver;
+
+    impl reqwest::dns::Resolve for PublicOnlyResolver {
+        fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+            let host = name.as_str().to_string();
+            Box::pin(async move {
+                let addrs: Vec<std::net::SocketAddr> =
+                    tokio::net::lookup_host((host.as_str(), 0)).await?.collect();
+                if addrs.iter().any(|addr| is_private_ip(addr.ip())) {
+                    return Err(format!("host {host} resolves to a private address").into());
+                }
+                let addrs: reqwest::dns::Addrs = Box::new(addrs.into_iter());
+                Ok(addrs)
+            })
+        }
+    }
+
+    /// True for addresses an agent should never reach from a fetch tool.
+    pub fn is_private_ip(ip: std::net::IpAddr) -> bool {
+        use std::net::IpAddr;
+        match ip {
+            IpAddr::V4(v4) => {
+                let [a, b, ..] = v4.octets();
+                v4.is_private()
+                    || v4.is_loopback()
+                    || v4.is_link_local()
+                    || v4.is_unspecified()
+                    || v4.is_broadcast()
+                    || v4.is_documentation()
+                    || v4.is_multicast()
+                    || a == 0
+                    || (a == 100 && (64..128).contains(&b)) // carrier-grade NAT
+            }
+            IpAddr::V6(v6) => {
+                if let Some(v4) = v6.to_ipv4_mapped() {
+                    return is_private_ip(IpAddr::V4(v4));
+                }
+                // NAT64 (64:ff9b::/96), IPv4-compatible (::a.b.c.d) and 6to4
+                // (2002::/16) addresses reach the IPv4 address they carry.
+                let embedded = match v6.segments() {
+                    [0x64, 0xff9b, 0, 0, 0, 0, high, low] | [0, 0, 0, 0, 0, 0, high, low] => {
+                        Some((high, low))
+                    }
+                    [0x2002, high, low, ..] => Some((high, low)),
+                    _ => None,
+                };
+                if let Some((high, low)) = embedded {
+                    let v4 = std::net::Ipv4Addr::from((u32::from(high) << 16) | u32::from(low));
+                    return is_private_ip(IpAddr::V4(v4));
+                }
+                let first = v6.segments()[0];
+                v6.is_loopback()
+                    || v6.is_unspecified()
+                    || v6.is_multicast()
+                    || (first & 0xfe00) == 0xfc00 // unique local
+                    || (first & 0xffc0) == 0xfe80 // link local
+            }
+        }
+    }
+
+    impl HttpFetchTool {
+        pub fn new() -> Self {
+            Self {
+                client: reqwest::Client::new(),
+                policy: None,
+            }
+        }
+
+        pub fn with_client_config(config: &HttpClientConfig) -> Result<Self, ToolError> {
+            let client = config
+                .build_client()
+                .map_err(|e| ToolError::Execution(e.to_string()))?;
+            Ok(Self {
+                client,
+                policy: None,
+            })
+        }
+
+        /// Builds a fetch tool that enforces `policy`. Redirects are followed
+        /// manually so every hop is checked, and JSON responses are returned
+        /// as structured values.
+        pub fn with_policy(policy: HttpFetchPolicy) -> Result<Self, ToolError> {
+            Self::with_client_config_and_policy(&HttpClientConfig::default(), policy)
+        }
+
+        /// Like [`with_policy`](Self::with_policy), on a client carrying
+        /// `config`'s headers, user agent and proxy. The policy's redirect,
+        /// timeout and address rules take precedence over `config`'s.
+        pub fn with_client_config_and_policy(
+            config: &HttpClientConfig,
+            policy: HttpFetchPolicy,
+        ) -> Result<Self, ToolError> {
+            Ok(Self {
+                client: policy.build_client(config)?,
+                policy: Some(policy),
+            })
+        }
+
+        async fn fetch_with_policy(
+            &self,
+            policy: &HttpFetchPolicy,
+            url: &str,
+        ) -> Result<Value, ToolError> {
+            let mut url = reqwest::Url::parse(url)
+                .map_err(|e| ToolError::InvalidArgs(format!("invalid url: {e}")))?;
+            let mut redirects = 0;
+            let resp = loop {
+                policy.check_url(&url).await?;
+                let resp = self
+                    .client
+                    .get(url.clone())
+                    .send()
+                    .await
+                    .map_err(|e| ToolError::Execution(e.without_url().to_string()))?;
+                if !resp.status().is_redirection() {
+                    break resp;
+                }
+                let Some(location) = resp
+                    .headers()
+                    .get(reqwest::header::LOCATION)
+                    .and_then(|v| v.to_str().ok())
+                else {
+                    break resp;
+                };
+                redirects += 1;
+                if redirects > policy.max_redirects {
+                    return Err(ToolError::Execution("too many redirects".into()));
+                }
+                url = url
+                    .join(location)
+                    .map_err(|e| ToolError::Execution(format!("invalid redirect: {e}")))?;
+            };
+
+            let status = resp.status().as_u16();
+            let content_type = resp
+                .headers()
+                .get(reqwest::header::CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or_default()
+                .to_string();
+            let text = read_body_limited(resp, policy.max_response_bytes).await?;
+            let body = if content_type.contains("json") {
+                serde_json::from_str(&text).unwrap_or(Value::String(text))
+            } else {
+                Value::String(text)
+            };
+            Ok(serde_json::json!({
+                "status": status,
+                "content_type": content_type,
+                "body": body
+            }))
+        }
+    }
+
+    /// Reads the body of `resp` as text, failing as soon as it exceeds `limit` bytes.
+    async fn read_body_limited(
+        mut resp: reqwest::Response,
+        limit: Option<usize>,
+    ) -> Result<String, ToolError> {
+        let limit = limit.unwrap_or(usize::MAX);
+        if resp
+            .content_length()
+            .is_some_and(|len| len as usize > limit)
+        {
+            return Err(ToolError::Execution(format!(
+                "response exceeds {limit} bytes"
+            )));
+        }
+        let mut bytes = Vec::new();
+        while let Some(chunk) = resp
+            .chunk()
+            .await
+            .map_err(|e| ToolError::Execution(e.to_string()))?
+        {
+            if bytes.len() + chunk.len() > limit {
+                return Err(ToolError::Execution(format!(
+                    "response exceeds {limit} bytes"
+                )));
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    impl Default for HttpFetchTool {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    #[async_trait]
+    impl Tool for HttpFetchTool {
+        fn name(&self) -> &'static str {
+            "http_fetch"
+        }
+
+        fn input_schema(&self) -> Value {
+            serde_json::json!({
+                "type": "object",
+                "properties": {"url": {"type": "string"}},
+                "required": ["url"]
+            })
+        }
+
+        fn output_schema(&self) -> Value {
+            serde_json::json!({"type": "object", "properties": {"status": {"type": "number"}, "body": {"type": "string"}}})
+        }
+
+        async fn execute(&self, args: Value) -> Result<Value, ToolError> {
+            let url = args
+                .get("url")
+                .and_then(|v| v.as_str())
+                .ok_or_else(|| ToolError::InvalidArgs("url missing".into()))?;
+            if let Some(policy) = &self.policy {
+                return self.fetch_with_policy(policy, url).await;
+            }
+            let resp = self
+                .client
+                .get(url)
+                .send()
+                .await
+                .map_err(|e| ToolError::Execution(e.to_string()))?;
+            let status = resp.status().as_u16();
+            let body = resp
+                .text()
+                .await
+                .map_err(|e| ToolError::Execution(e.to_string()))?;
+            Ok(serde_json::json!({"status": status, "body": body}))
+        }
+
+        // GET only reads, so a failed fetch is safe to repeat.
+        fn side_effects(&self) -> SideEffects {
+            SideEffects {
+                idempotent: true,
+                reads_external: true,
+                writes_external: false,
+            }
+        }
+    }
+
+    /// How [`CodeRunnerTool`] builds and runs one language. In commands,
+    /// `{file}` is replaced with the source path and `{dir}` with the temp dir.
+    #[derive(Debug, Clone)]
+    pub struct CodeInterpreter {
+        /// Name the snippet is written to, e.g. `main.py`.
+        pub file_name: String,
+        /// Optional build step; a non-zero exit skips `run`.
+        pub compile: Option<Vec<String>>,
+        pub run: Vec<String>,
+    }
+
+    impl CodeInterpreter {
+        pub fn python3() -> Self {
+            Self {
+                file_name: "main.py".into(),
+                compile: None,
+                run: vec!["python3".into(), "{file}".into()],
+            }
+        }
+
+        pub fn node() -> Self {
+            Self {
+                file_name: "main.js".into(),
+                compile: None,
+                run: vec!["node".into(), "{file}".into()],
+            }
+        }
+
+        pub fn rust() -> Self {
+            Self {
+                file_name: "main.rs".into(),
+                compile: Some(vec![
+                    "rustc".into(),
+                    "--edition".into(),
+                    "2021".into(),
+                    "{file}".into(),
+                    "-o".into(),
+                    "{dir}/main".into(),
+                ]),
+                run: vec!["{dir}/main".into()],
+            }
+        }
+    }
+
+    /// Runs small code snippets in a throwaway temp dir.
+    ///
+    /// Arguments: `language` (a configured interpreter name; `python3`, `node`
+    /// and `rust` by default) and `code`. Each command is killed after the wall
+    /// time, stdout/stderr are cut at the output limit, and on Unix an optional
+    /// CPU-time limit is applied with `ulimit -t`. This is resource limiting,
+    /// not isolation: snippets run with the agent's own permissions.
+    pub struct CodeRunnerTool {
+        interpreters: BTreeMap<String, CodeInterpreter>,
+        wall_time: Duration,
+        cpu_time: Option<Duration>,
+        max_output_bytes: usize,
+    }
+
+    struct CommandOutput {
+        stdout: Vec<u8>,
+        stderr: Vec<u8>,
+        exit_code: Option<i32>,
+        timed_out: bool,
+        truncated: bool,
+    }
+
+    impl CodeRunnerTool {
+        pub fn new() -> Self {
+            Self {
+                interpreters: BTreeMap::from([
+                    ("node".to_string(), CodeInterpreter::node()),
+                    ("python3".to_string(), CodeInterpreter::python3()),
+                    ("rust".to_string(), CodeInterpreter::rust()),
+                ]),
+                wall_time: Duration::from_secs(10),
+                cpu_time: None,
+                max_output_bytes: 64 * 1024,
+            }
+        }
+
+        /// Adds or replaces the interpreter used for `language`.
+        pub fn with_interpreter(
+            mut self,
+            language: impl Into<String>,
+            interpreter: CodeInterpreter,
+        ) -> Self {
+            self.interpreters.insert(language.into(), interpreter);
+            self
+        }
+
+        pub fn with_wall_time(mut self, wall_time: Duration) -> Self {
+            self.wall_time = wall_time;
+            self
+        }
+
+        pub fn with_cpu_time(mut self, cpu_time: Duration) -> Self {
+            self.cpu_time = Some(cpu_time);
+            self
+        }
+
+        pub fn with_max_output_bytes(mut self, max_output_bytes: usize) -> Self {
+            self.max_output_bytes = max_output_bytes;
+            self
+        }
+
+        fn command(&self, argv: &[String], file: &str, dir: &str) -> Result<Command, ToolError> {
+            let mut argv = argv
+                .iter()
+                .map(|arg| arg.replace("{file}", file).replace("{dir}", dir));
+            let program = argv
+                .next()
+                .ok_or_else(|| ToolError::InvalidArgs("interpreter command is empty".into()))?;
+            let mut command = match self.cpu_time {
+                #[cfg(unix)]
+                Some(limit) => {
+                    let mut command = Command::new("sh");
+                    command
+                        .arg("-c")
+                        .arg(r#"ulimit -t "$0" && exec "$@""#)
+                        .arg(limit.as_secs().max(1).to_string())
+                        .arg(program);
+                    command
+                }
+                _ => Command::new(program),
+            };
+            command
+                .args(argv)
+                .current_dir(dir)
+                .stdin(Stdio::null())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .kill_on_drop(true);
+            Ok(command)
+        }
+
+        async fn run_command(&self, mut command: Command) -> Result<CommandOutput, ToolError> {
+            let mut child = command
+                .spawn()
+                .map_err(|e| ToolError::Execution(format!("failed to start interpreter: {e}")))?;
+            let stdout = child.stdout.take();
+            let stderr = child.stderr.take();
+            let limit = self.max_output_bytes;
+            let run = async {
+                let (stdout, stderr, status) = tokio::join!(
+                    read_limited(stdout, limit),
+                    read_limited(stderr, limit),
+                    child.wait()
+                );
+                (stdout, stderr, status)
+            };
+            match tokio::time::timeout(self.wall_time, run).await {
+                Ok(((stdout, out_cut), (stderr, err_cut), status)) => {
+                    let status = status.map_err(|e| ToolError::Execution(e.to_string()))?;
+                    Ok(CommandOutput {
+                        stdout,
+                        stderr,
+                        exit_code: status.code(),
+                        timed_out: false,
+                        truncated: out_cut || err_cut,
+                    })
+                }
+                Err(_) => {
+                    let _ = child.kill().await;
+                    Ok(CommandOutput {
+                        stdout: Vec::new(),
+                        stderr: Vec::new(),
+                        exit_code: None,
+                        timed_out: true,
+                        truncated: false,
+                    })
+                }
+            }
+        }
+    }
+
+    impl Default for CodeRunnerTool {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    /// Reads the whole stream but keeps only the first `limit` bytes, so a
+    /// chatty child never blocks on a full pipe.
+    async fn read_limited<R: AsyncRead + Unpin>(
+        reader: Option<R>,
+        limit: usize,
+    ) -> (Vec<u8>, bool) {
+        let Some(mut reader) = reader else {
+            return (Vec::new(), false);
+        };
+        let mut kept = Vec::new();
+        let mut truncated = false;
+        let mut chunk = [0u8; 8192];
+        while let Ok(read) = reader.read(&mut chunk).await {
+            if read == 0 {
+                break;
+            }
+            let room = limit.saturating_sub(kept.len());
+            kept.extend_from_slice(&chunk[..read.min(room)]);
+            truncated |= read > room;
+        }
+        (kept, truncated)
+    }
+
+    #[async_trait]
+    impl Tool for CodeRunnerTool {
+        fn name(&self) -> &'static str {
+            "code_runner"
+        }
+
+        fn input_schema(&self) -> Value {
+            serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "language": {"type": "string", "enum": self.interpreters.keys().collect::<Vec<_>>()},
+                    "code": {"type": "string"}
+                },
+                "required": ["language", "code"]
+            })
+        }
+
+        fn output_schema(&self) -> Value {
+            serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "stage": {"type": "string", "enum": ["compile", "run"]},
+                    "stdout": {"type": "string"},
+                    "stderr": {"type": "string"},
+                    "exit_code": {"type": ["integer", "null"]},
+                    "timed_out": {"type": "boolean"},
+                    "truncated": {"type": "boolean"}
+                }
+            })
+        }
+
+        async fn execute(&self, args: Value) -> Result<Value, ToolError> {
+            let language = args
+                .get("language")
+                .and_then(Value::as_str)
+                .ok_or_else(|| ToolError::InvalidArgs("language missing".into()))?;
+            let code = args
+                .get("code")
+                .and_then(Value::as_str)
+                .ok_or_else(|| ToolError::InvalidArgs("code missing".into()))?;
+            let interpreter = self.interpreters.get(language).ok_or_else(|| {
+                ToolError::InvalidArgs(format!("unsupported language {language}"))
+            })?;
+
+            let dir = tempfile::tempdir().map_err(|e| ToolError::Execution(e.to_string()))?;
+            let file = dir.path().join(&interpreter.file_name);
+            fs::write(&file, code)
+                .await
+                .map_err(|e| ToolError::Execution(e.to_string()))?;
+            let (file, dir_path) = (file.to_string_lossy(), dir.path().to_string_lossy());
+
+            let mut stage = "run";
+            let mut output = None;
+            if let Some(compile) = &interpreter.compile {
+                let compiled = self
+                    .run_command(self.command(compile, &file, &dir_path)?)
+                    .await?;
+                if compiled.timed_out || compiled.exit_code != Some(0) {
+                    stage = "compile";
+                    output = Some(compiled);
+                }
+            }
+            let output = match output {
+                Some(output) => output,
+                None => {
+                    self.run_command(self.command(&interpreter.run, &file, &dir_path)?)
+                        .await?
+                }
+            };
+
+            Ok(serde_json::json!({
+                "stage": stage,
+                "stdout": String::from_utf8_lossy(&output.stdout),
+                "stderr": String::from_utf8_lossy(&output.stderr),
+                "exit_code": output.exit_code,
+                "timed_out": output.timed_out,
+                "truncated": output.truncated,
+            }))
+        }
+    }
+
+    /// Payload shape expected by the webhook receiving [`NotifyTool`] messages.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum WebhookFormat {
+        /// Microsoft Teams incoming webhook (`MessageCard`).
+        Teams,
+        /// Slack incoming webhook (`{"text": ...}`).
+        Slack,
+        /// Plain JSON with the rendered `text` alongside the raw arguments.
+        Generic,
+    }
+
+    /// Posts a message to a configured webhook so agents can escalate to humans.
+    ///
+    /// Arguments: `title` and `message` (required), `severity` (default `info`)
+    /// and `fields` (object). The text is rendered from a template whose
+    /// `{{name}}` placeholders take those arguments or any key of `fields`.
+    /// Network errors, 429 and 5xx responses are retried with linear backoff.
+    pub struct NotifyTool {
+        client: reqwest::Client,
+        webhook_url: String,
+        format: WebhookFormat,
+        template: String,
+        max_retries: usize,
+        backoff: Duration,
+    }
+
+    impl NotifyTool {
+        pub const DEFAULT_TEMPLATE: &'static str = "[{{severity}}] {{title}}: {{message}}";
+
+        pub fn new(webhook_url: impl Into<String>, format: WebhookFormat) -> Self {
+            Self {
+                client: reqwest::Client::new(),
+                webhook_url: webhook_url.into(),
+                format,
+                template: Self::DEFAULT_TEMPLATE.into(),
+                max_retries: 2,
+                backoff: Duration::from_millis(500),
+            }
+        }
+
+        pub fn with_template(mut self, template: impl Into<String>) -> Self {
+            self.template = template.into();
+            self
+        }
+
+        pub fn with_retry(mut self, max_retries: usize, backoff: Duration) -> Self {
+            self.max_retries = max_retries;
+            self.backoff = backoff;
+            self
+        }
+
+        pub fn with_client_config(mut self, config: &HttpClientConfig) -> Result<Self, ToolError> {
+            self.client = config
+                .build_client()
+                .map_err(|e| ToolError::Execution(e.to_string()))?;
+            Ok(self)
+        }
+
+        /// Replaces `{{name}}` placeholders; unknown names render empty.
+        fn render(&self, values: &serde_json::Map<String, Value>) -> String {
+            let mut rendered = String::with_capacity(self.template.len());
+            let mut rest = self.template.as_str();
+            while let Some(start) = rest.find("{{") {
+                rendered.push_str(&rest[..start]);
+                let Some(end) = rest[start..].find("}}") else {
+                    rest = &rest[start..];
+                    break;
+                };
+                let name = rest[start + 2..start + end].trim();
+                match values.get(name) {
+                    Some(Value::String(text)) => rendered.push_str(text),
+                    Some(Value::Null) | None => {}
+                    Some(other) => rendered.push_str(&other.to_string()),
+                }
+                rest = &rest[start + end + 2..];
+            }
+            rendered.push_str(rest);
+            rendered
+        }
+
+        fn payload(&self, title: &str, text: &str, args: &Value) -> Value {
+            match self.format {
+                WebhookFormat::Teams => serde_json::json!({
+                    "@type": "MessageCard",
+                    "@context": "https://schema.org/extensions",
+                    "summary": title,
+                    "title": title,
+                    "text": text,
+                }),
+                WebhookFormat::Slack => serde_json::json!({"text": text}),
+                WebhookFormat::Generic => serde_json::json!({
+                    "title": title,
+                    "message": args.get("message"),
+                    "severity": args.get("severity").and_then(Value::as_str).unwrap_or("info"),
+                    "fields": args.get("fields").cloned().unwrap_or_else(|| serde_json::json!({})),
+                    "text": text,
+                }),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl Tool for NotifyTool {
+        fn name(&self) -> &'static str {
+            "notify"
+        }
+
+        fn input_schema(&self) -> Value {
+            serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "title": {"type": "string"},
+                    "message": {"type": "string"},
+                    "severity": {"type": "string", "enum": ["info", "warning", "critical"]},
+                    "fields": {"type": "object"}
+                },
+                "required": ["title", "message"]
+            })
+        }
+
+        fn output_schema(&self) -> Value {
+            serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "delivered": {"type": "boolean"},
+                    "status": {"type": "number"},
+                    "attempts": {"type": "number"}
+                }
+            })
+        }
+
+        async fn execute(&self, args: Value) -> Result<Value, ToolError> {
+            let title = args
+                .get("title")
+                .and_then(Value::as_str)
+                .ok_or_else(|| ToolError::InvalidArgs("title missing".into()))?;
+            if args.get("message").and_then(Value::as_str).is_none() {
+                return Err(ToolError::InvalidArgs("message missing".into()));
+            }
+
+            let mut values = args
+                .get("fields")
+                .and_then(Value::as_object)
+                .cloned()
+                .unwrap_or_default();
+            for key in ["title", "message", "severity"] {
+                if let Some(value) = args.get(key) {
+                    values.insert(key.into(), value.clone());
+                }
+            }
+            values
+                .entry("severity")
+                .or_insert_with(|| Value::String("info".into()));
+            let text = self.render(&values);
+            let payload = self.payload(title, &text, &args);
+
+            let mut attempts = 0;
+            loop {
+                attempts += 1;
+                let error = match self
+                    .client
+                    .post(&self.webhook_url)
+                    .json(&payload)
+                    .send()
+                    .await
+                {
+                    Ok(response) if response.status().is_success() => {
+                        return Ok(serde_json::json!({
+                            "delivered": true,
+                            "status": response.status().as_u16(),
+                            "attempts": attempts,
+                        }));
+                    }
+                    Ok(response)
+                        if response.status().is_server_error()
+                            || response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS =>
+                    {
+                        format!("webhook responded with {}", response.status())
+                    }
+                    Ok(response) => {
+                        return Err(ToolError::Execution(format!(
+                            "webhook rejected notification with {}",
+                            response.status()
+                        )));
+                    }
+                    Err(e) => e.to_string(),
+                };
+                if attempts > self.max_retries {
+                    return Err(ToolError::Execution(format!(
+                        "notification failed after {attempts} attempts: {error}"
+                    )));
+                }
+                tokio::time::sleep(self.backoff * attempts as u32).await;
+            }
+        }
+
+        fn side_effects(&self) -> SideEffects {
+            SideEffects {
+                idempotent: false,
+                reads_external: false,
+                writes_external: true,
+            }
+        }
+    }
+
+    /// Credentials attached to requests, resolved from a [`SecretsProvider`].
+    #[derive(Debug, Clone)]
+    pub enum HttpAuth {
+        Bearer {
+            token_secret: String,
+        },
+        Basic {
+            username: String,
+            password_secret: String,
+        },
+    }
+
+    struct ScopedAuth {
+        auth: HttpAuth,
+        secrets: Arc<dyn SecretsProvider>,
+        hosts: Vec<String>,
+    }
+
+    /// General-purpose HTTP client tool supporting any method, headers and JSON bodies.
+    ///
+    /// Arguments: `url` (required), `method` (default `GET`), `headers` (object
+    /// of strings), `body` (any JSON) and `timeout_ms`. Client-wide timeouts and
+    /// redirect limits come from [`HttpClientConfig`], or from the
+    /// [`HttpFetchPolicy`] given to [`with_policy`](Self::with_policy), whose
+    /// checks and size limit then apply to every redirect hop and response,
+    /// and whose redirects drop credential headers once they leave the
+    /// requested origin. Configured credentials are only sent to
+    /// the hosts they were configured for, so a URL chosen by the model cannot
+    /// draw them elsewhere.
+    pub struct HttpRequestTool {
+        client: reqwest::Client,
+        policy: Option<HttpFetchPolicy>,
+        auth: Vec<ScopedAuth>,
+    }
+
+    impl HttpRequestTool {
+        pub fn new() -> Self {
+            Self {
+                client: reqwest::Client::new(),
+                policy: None,
+                auth: Vec::new(),
+            }
+        }
+
+        pub fn with_client_config(config: &HttpClientConfig) -> Result<Self, ToolError> {
+            let client = config
+                .build_client()
+                .map_err(|e| ToolError::Execution(e.to_string()))?;
+            Ok(Self {
+                client,
+                policy: None,
+                auth: Vec::new(),
+            })
+        }
+
+        /// Builds a request tool that enforces `policy`, following redirects
+        /// itself so every hop is checked.
+        pub fn with_policy(policy: HttpFetchPolicy) -> Result<Self, ToolError> {
+            Self::with_client_config_and_policy(&HttpClientConfig::default(), policy)
+        }
+
+        /// Like [`with_policy`](Self::with_policy), on a client carrying
+        /// `config`'s headers, user agent and proxy. The policy's redirect,
+        /// timeout and address rules take precedence over `config`'s.
+        pub fn with_client_config_and_policy(
+            config: &HttpClientConfig,
+            policy: HttpFetchPolicy,
+        ) -> Result<Self, ToolError> {
+            Ok(Self {
+                client: policy.build_client(config)?,
+                policy: Some(policy),
+                auth: Vec::new(),
+            })
+        }
+
+        /// Sends `auth` with requests to any of `hosts`, matched exactly and
+        /// ignoring case; requests to other hosts, including redirect
+        /// targets, go without it. The first matching entry wins.
+        pub fn with_auth(
+            mut self,
+            auth: HttpAuth,
+            secrets: Arc<dyn SecretsProvider>,
+            hosts: impl IntoIterator<Item = impl Into<String>>,
+        ) -> Self {
+            self.auth.push(ScopedAuth {
+                auth,
+                secrets,
+                hosts: hosts.into_iter().map(Into::into).collect(),
+            });
+            self
+        }
+
+        async fn authorize(
+            &self,
+            request: reqwest::RequestBuilder,
+            url: &reqwest::Url,
+        ) -> Result<reqwest::RequestBuilder, ToolError> {
+            let Some(host) = url.host_str() else {
+                return Ok(request);
+            };
+            let Some(scoped) = self.auth.iter().find(|scoped| {
+                scoped
+                    .hosts
+                    .iter()
+                    .any(|allowed| allowed.eq_ignore_ascii_case(host))
+            }) else {
+                return Ok(request);
+            };
+            Ok(match &scoped.auth {
+                HttpAuth::Bearer { token_secret } => {
+                    let token = scoped.secrets.get_secret(token_secret).await?;
+                    request.bearer_auth(token.expose())
+                }
+                HttpAuth::Basic {
+                    username,
+                    password_secret,
+                } => {
+                    let password = scoped.secrets.get_secret(password_secret).await?;
+                    request.basic_auth(username, Some(password.expose()))
+                }
+            })
+        }
+    }
+
+    fn is_sensitive_header(name: &str) -> bool {
+        ["authorization", "proxy-authorization", "cookie", "cookie2"]
+            .iter()
+            .any(|sensitive| name.eq_ignore_ascii_case(sensitive))
+    }
+
+    impl Default for HttpRequestTool {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    #[async_trait]
+    impl Tool for HttpRequestTool {
+        fn name(&self) -> &'static str {
+            "http_request"
+        }
+
+        fn input_schema(&self) -> Value {
+            serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "url": {"type": "string"},
+                    "method": {"type": "string", "enum": ["GET", "POST", "PUT", "PATCH", "DELETE", "HEAD"]},
+                    "headers": {"type": "object", "additionalProperties": {"type": "string"}},
+                    "body": {},
+                    "timeout_ms": {"type": "integer", "minimum": 1}
+                },
+                "required": ["url"]
+            })
+        }
+
+        fn output_schema(&self) -> Value {
+            serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "status": {"type": "number"},
+                    "headers": {"type": "object"},
+                    "body": {}
+                }
+            })
+        }
+
+        async fn execute(&self, args: Value) -> Result<Value, ToolError> {
+            let url = args
+                .get("url")
+                .and_then(|v| v.as_str())
+                .ok_or_else(|| ToolError::InvalidArgs("url missing".into()))?;
+            let method = args
+                .get("method")
+                .and_then(|v| v.as_str())
+                .unwrap_or("GET")
+                .to_uppercase();
+            let mut method = reqwest::Method::from_bytes(method.as_bytes())
+                .map_err(|_| ToolError::InvalidArgs(format!("unsupported method {method}")))?;
+            let mut url = reqwest::Url::parse(url)
+                .map_err(|e| ToolError::InvalidArgs(format!("invalid url: {e}")))?;
+            let mut headers = Vec::new();
+            if let Some(given) = args.get("headers") {
+                let given = given
+                    .as_object()
+                    .ok_or_else(|| ToolError::InvalidArgs("headers must be an object".into()))?;
+                for (name, value) in given {
+                    let value = value.as_str().ok_or_else(|| {
+                        ToolError::InvalidArgs(format!("header {name} must be a string"))
+                    })?;
+                    headers.push((name.as_str(), value));
+                }
+            }
+            let mut body = args.get("body");
+            let timeout = args
+                .get("timeout_ms")
+                .and_then(|v| v.as_u64())
+                .map(std::time::Duration::from_millis);
+
+            let mut redirects = 0;
+            let resp = loop {
+                if let Some(policy) = &self.policy {
+                    policy.check_url(&url).await?;
+                }
+                let mut request = self.client.request(method.clone(), url.clone());
+                for (name, value) in &headers {
+                    request = request.header(*name, *value);
+                }
+                if let Some(body) = body {
+                    request = request.json(body);
+                }
+                if let Some(timeout) = timeout {
+                    request = request.timeout(timeout);
+                }
+                request = self.authorize(request, &url).await?;
+
+                let resp = request.send().await.map_err(|e| {
+                    if e.is_timeout() {
+                        ToolError::Execution("request timed out".into())
+                    } else if e.is_redirect() {
+                        ToolError::Execution("too many redirects".into())
+                    } else {
+                        ToolError::Execution(e.without_url().to_string())
+                    }
+                })?;
+                // Without a policy the client follows redirects itself.
+                let Some(policy) = &self.policy else {
+                    break resp;
+                };
+                if !resp.status().is_redirection() {
+                    break resp;
+                }
+                let Some(location) = resp
+                    .headers()
+                    .get(reqwest::header::LOCATION)
+                    .and_then(|v| v.to_str().ok())
+                else {
+                    break resp;
+                };
+                redirects += 1;
+                if redirects > policy.max_redirects {
+                    return Err(ToolError::Execution("too many redirects".into()));
+                }
+                let status = resp.status();
+                if status == reqwest::StatusCode::SEE_OTHER
+                    || (method == reqwest::Method::POST
+                        && matches!(
+                            status,
+                            reqwest::StatusCode::MOVED_PERMANENTLY | reqwest::StatusCode::FOUND
+                        ))
+                {
+                    method = reqwest::Method::GET;
+                    body = None;
+                }
+                let next = url
+                    .join(location)
+                    .map_err(|e| ToolError::Execution(format!("invalid redirect: {e}")))?;
+                // Like browsers, keep credentials the model supplied to the origin it chose.
+                if next.origin() != url.origin() {
+                    headers.retain(|(name, _)| !is_sensitive_header(name));
+                }
+                url = next;
+            };
+            let status = resp.status().as_u16();
+            let response_headers: serde_json::Map<String, Value> = resp
+                .headers()
+                .iter()
+                .filter_map(|(name, value)| {
+                    value
+                        .to_str()
+                        .ok()
+                        .map(|v| (name.to_string(), Value::String(v.to_string())))
+                })
+                .collect();
+            let limit = self
+                .policy
+                .as_ref()
+                .and_then(|policy| policy.max_response_bytes);
+            let text = read_body_limited(resp, limit).await?;
+            let body = serde_json::from_str(&text).unwrap_or(Value::String(text));
+            Ok(serde_json::json!({"status": status, "headers": response_headers, "body": body}))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::builtins::{
+        is_private_ip, CodeInterpreter, CodeRunnerTool, FileLimits, FileTool, HttpAuth,
+        HttpFetchPolicy, HttpFetchTool, HttpRequestTool, LogTool, MathTool, MemorySearchTool,
+        NotifyTool, SearchProvider, SearchResult, SearchResultMapper, SearchTool, TableTool,
+        TimeTool, WebhookFormat,
+    };
+    use super::{
+        function_name, AuditLogSink, CachePolicy, CancellationToken, FunctionSpecFormat,
+        InvocationContext, OutputRedactor, RateLimitPolicy, SecretRedactor, SecretsProvider,
+        SideEffects, ToolBudget, ToolError, ToolExample, ToolInvocationError, ToolMetadata,
+        ToolMiddleware, ToolRegistry,
+    };
+    use crate::Tool;
+    use agent_core::HttpClientConfig;
+    use agent_telemetry::Secret;
+    use async_trait::async_trait;
+    use serde_json::json;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn file_tool_read_write_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let tool = FileTool::new(dir.path());
+
+        let write_result = tool
+            .execute(
+                json!({"path": "notes/hello.txt", "operation": "write", "content": "hi there"}),
+            )
+            .await
+            .unwrap();
+        assert_eq!(write_result.get("operation").unwrap(), "write");
+
+        let read_result = tool
+            .execute(json!({"path": "notes/hello.txt", "operation": "read"}))
+            .await
+            .unwrap();
+        assert_eq!(read_result.get("operation").unwrap(), "read");
+        assert_eq!(read_result.get("content").unwrap(), "hi there");
+    }
+
+    #[tokio::test]
+    async fn table_tool_filters_and_aggregates_csv() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("sales.csv"),
+            "region,product,amount\nnorth,\"Widget, large\",10\nsouth,Gadget,4.5\nnorth,Gadget,2\nsouth,\"Say \"\"hi\"\"\",\n",
+        )
+        .unwrap();
+        let tool = TableTool::new(FileTool::new(dir.path()));
+
+        let head = tool
+            .execute(json!({"path": "sales.csv", "operation": "head", "limit": 2}))
+            .await
+            .unwrap();
+        assert_eq!(head["columns"], json!(["region", "product", "amount"]));
+        assert_eq!(head["total_rows"], 4);
+        assert_eq!(head["rows"][0]["product"], "Widget, large");
+        assert_eq!(head["rows"][1]["amount"], 4.5);
+
+        let filtered = tool
+            .execute(json!({
+                "path": "sales.csv",
+                "operation": "filter",
+                "filters": [{"column": "amount", "op": "gte", "value": 4}]
+            }))
+            .await
+            .unwrap();
+        assert_eq!(filtered["matched_rows"], 2);
+
+        let grouped = tool
+            .execute(json!({
+                "path": "sales.csv",
+                "operation": "aggregate",
+                "group_by": ["region"],
+                "metrics": [{"op": "sum", "column": "amount"}, {"op": "count"}]
+            }))
+            .await
+            .unwrap();
+        assert_eq!(
+            grouped["rows"],
+            json!([
+                {"region": "north", "sum_amount": 12.0, "count": 2},
+                {"region": "south", "sum_amount": 4.5, "count": 2}
+            ])
+        );
+
+        let escaped = tool
+            .execute(json!({
+                "path": "sales.csv",
+                "operation": "filter",
+                "filters": [{"column": "product", "op": "contains", "value": "hi"}]
+            }))
+            .await
+            .unwrap();
+        assert_eq!(escaped["rows"][0]["product"], "Say \"hi\"");
+        assert_eq!(escaped["rows"][0]["amount"], json!(null));
+    }
+
+    #[tokio::test]
+    async fn table_tool_reads_jsonl_and_samples_reproducibly() {
+        let dir = tempfile::tempdir().unwrap();
+        let lines: Vec<String> = (0..20)
+            .map(|i| json!({"id": i, "score": i * 10}).to_string())
+            .collect();
+        std::fs::write(dir.path().join("scores.jsonl"), lines.join("\n")).unwrap();
+        let tool = TableTool::new(FileTool::new(dir.path()));
+
+        let args = json!({"path": "scores.jsonl", "operation": "sample", "limit": 5, "seed": 7});
+        let first = tool.execute(args.clone()).await.unwrap();
+        let second = tool.execute(args).await.unwrap();
+        assert_eq!(first["rows"].as_array().unwrap().len(), 5);
+        assert_eq!(first["rows"], second["rows"]);
+
+        let avg = tool
+            .execute(json!({
+                "path": "scores.jsonl",
+                "operation": "aggregate",
+                "metrics": [{"op": "avg", "column": "score"}, {"op": "max", "column": "id"}]
+            }))
+            .await
+            .unwrap();
+        assert_eq!(avg["rows"], json!([{"avg_score": 95.0, "max_id": 19.0}]));
+
+        assert!(tool
+            .execute(json!({"path": "../escape.csv", "operation": "head"}))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn file_tool_supports_directory_operations_and_limits() {
+        let dir = tempfile::tempdir().unwrap();
+        let tool = FileTool::new(dir.path()).with_limits(FileLimits {
+            max_read_bytes: 64,
+            max_write_bytes: 32,
+            max_entries: 10,
+        });
+        let run = |args: serde_json::Value| tool.execute(args);
+
+        run(json!({"path": "docs", "operation": "mkdir"}))
+            .await
+            .unwrap();
+        run(json!({"path": "docs/a.md", "operation": "write", "content": "one"}))
+            .await
+            .unwrap();
+        run(json!({"path": "docs/a.md", "operation": "append", "content": ", two"}))
+            .await
+            .unwrap();
+        run(json!({"path": "docs/deep/b.md", "operation": "write", "content": "b"}))
+            .await
+            .unwrap();
+        run(json!({"path": "docs/c.txt", "operation": "write", "content": "c"}))
+            .await
+            .unwrap();
+
+        let read = run(json!({"path": "docs/a.md", "operation": "read"}))
+            .await
+            .unwrap();
+        assert_eq!(read["content"], "one, two");
+        let listed = run(json!({"path": "docs", "operation": "list"}))
+            .await
+            .unwrap();
+        let names: Vec<&str> = listed["entries"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| entry["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, vec!["a.md", "c.txt", "deep"]);
+        let stat = run(json!({"path": "docs/a.md", "operation": "stat"}))
+            .await
+            .unwrap();
+        assert_eq!(stat["size"], 8);
+        assert_eq!(stat["is_dir"], false);
+        let globbed = run(json!({"path": ".", "operation": "glob", "pattern": "**/*.md"}))
+            .await
+            .unwrap();
+        assert_eq!(globbed["matches"], json!(["docs/a.md", "docs/deep/b.md"]));
+
+        let too_big = "x".repeat(33);
+        assert!(
+            run(json!({"path": "big.txt", "operation": "write", "content": too_big}))
+                .await
+                .is_err()
+        );
+        assert!(run(json!({"path": "docs/deep", "operation": "delete"}))
+            .await
+            .is_err());
+        run(json!({"path": "docs/deep", "operation": "delete", "recursive": true}))
+            .await
+            .unwrap();
+        assert!(
+            run(json!({"path": ".", "operation": "delete", "recursive": true}))
+                .await
+                .is_err()
+        );
+
+        let read_only = FileTool::new(dir.path()).with_read_only(true);
+        assert!(read_only.side_effects().idempotent);
+        assert!(matches!(
+            read_only
+                .execute(json!({"path": "docs/a.md", "operation": "delete"}))
+                .await,
+            Err(ToolError::InvalidArgs(_))
+        ));
+        assert!(read_only
+            .execute(json!({"path": "docs/a.md", "operation": "read"}))
+            .await
+            .is_ok());
+    }
+
+    #[test]
+    fn builtin_tools_declare_side_effects() {
+        let dir = tempfile::tempdir().unwrap();
+        let registry = ToolRegistry::new();
+        registry.register(FileTool::new(dir.path()));
+        registry.register(MathTool);
+        registry.register(TimeTool);
+
+        assert_eq!(registry.side_effects("math"), Some(SideEffects::pure()));
+        assert!(registry.side_effects("time").unwrap().idempotent);
+        assert!(!registry.side_effects("file").unwrap().idempotent);
+        assert_eq!(registry.side_effects("missing"), None);
+
+        let file = |operation| registry.side_effects_for("file", &json!({"operation": operation}));
+        assert!(file("read").unwrap().idempotent);
+        assert!(!file("read").unwrap().writes_external);
+        assert!(!file("append").unwrap().idempotent);
+
+        registry.register(HttpFetchTool::new());
+        assert!(registry.side_effects("http_fetch").unwrap().idempotent);
+    }
+
+    struct Recorder {
+        label: &'static str,
+        events: Arc<std::sync::Mutex<Vec<String>>>,
+        cached: Option<serde_json::Value>,
+    }
+
+    #[async_trait]
+    impl ToolMiddleware for Recorder {
+        async fn before_invoke(
+            &self,
+            tool: &str,
+            args: &mut serde_json::Value,
+        ) -> Result<Option<serde_json::Value>, ToolInvocationError> {
+            self.events
+                .lock()
+                .unwrap()
+                .push(format!("{}:before:{tool}", self.label));
+            if let Some(expression) = args.get("expression").and_then(|v| v.as_str()) {
+                args["expression"] = json!(expression.replace('x', "*"));
+            }
+            Ok(self.cached.clone())
+        }
+
+        async fn after_invoke(
+            &self,
+            _tool: &str,
+            _args: &serde_json::Value,
+            output: &mut serde_json::Value,
+        ) -> Result<(), ToolInvocationError> {
+            self.events
+                .lock()
+                .unwrap()
+                .push(format!("{}:after", self.label));
+            *output = json!({"value": output.take(), "seen_by": self.label});
+            Ok(())
+        }
+
+        async fn on_error(
+            &self,
+            _tool: &str,
+            _args: &serde_json::Value,
+            error: &ToolInvocationError,
+        ) {
+            self.events
+                .lock()
+                .unwrap()
+                .push(format!("{}:error:{error}", self.label));
+        }
+    }
+
+    #[tokio::test]
+    async fn middlewares_wrap_invocations_in_order() {
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorder = |label, cached| Recorder {
+            label,
+            events: events.clone(),
+            cached,
+        };
+
+        let registry = ToolRegistry::new()
+            .with_middleware(recorder("outer", None))
+            .with_middleware(recorder("inner", None));
+        registry.register(MathTool);
+        let output = registry
+            .invoke("math", json!({"expression": "6 x 7"}), &[])
+            .await
+            .unwrap();
+        assert_eq!(output["seen_by"], "outer");
+        assert_eq!(output["value"]["seen_by"], "inner");
+        assert_eq!(output["value"]["value"], json!(42.0));
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                "outer:before:math",
+                "inner:before:math",
+                "inner:after",
+                "outer:after"
+            ]
+        );
+
+        events.lock().unwrap().clear();
+        let cached = ToolRegistry::new()
+            .with_middleware(recorder("cache", Some(json!({"result": 1}))))
+            .with_middleware(recorder("never", None));
+        cached.register(MathTool);
+        let output = cached
+            .invoke("math", json!({"expression": "invalid"}), &[])
+            .await
+            .unwrap();
+        assert_eq!(output["value"]["result"], 1);
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec!["cache:before:math", "cache:after"]
+        );
+
+        events.lock().unwrap().clear();
+        let missing = cached.invoke("nope", json!({}), &[]).await;
+        assert!(missing.is_err());
+        assert_eq!(events.lock().unwrap().len(), 2);
+        assert!(events.lock().unwrap()[0].starts_with("cache:error:tool nope not found"));
+    }
+
+    struct FixedSecrets;
+
+    #[async_trait]
+    impl SecretsProvider for FixedSecrets {
+        async fn get_secret(&self, name: &str) -> Result<Secret<String>, ToolError> {
+            match name {
+                "api_token" => Ok(Secret::new("t0ken".into())),
+                _ => Err(ToolError::Execution(format!("secret {name} is not set"))),
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn code_runner_reports_output_exit_code_and_limits() {
+        let shell = CodeInterpreter {
+            file_name: "main.sh".into(),
+            compile: None,
+            run: vec!["sh".into(), "{file}".into()],
+        };
+        let tool = CodeRunnerTool::new()
+            .with_interpreter("sh", shell)
+            .with_wall_time(Duration::from_millis(300))
+            .with_max_output_bytes(16);
+
+        let output = tool
+            .execute(json!({"language": "sh", "code": "echo hello; echo oops >&2; exit 3"}))
+            .await
+            .unwrap();
+        assert_eq!(output["stage"], "run");
+        assert_eq!(output["stdout"], "hello\n");
+        assert_eq!(output["stderr"], "oops\n");
+        assert_eq!(output["exit_code"], 3);
+        assert_eq!(output["truncated"], false);
+
+        let chatty = tool
+            .execute(json!({"language": "sh", "code": "seq 1 1000"}))
+            .await
+            .unwrap();
+        assert_eq!(chatty["truncated"], true);
+        assert_eq!(chatty["stdout"].as_str().unwrap().len(), 16);
+
+        let slow = tool
+            .execute(json!({"language": "sh", "code": "sleep 5"}))
+            .await
+            .unwrap();
+        assert_eq!(slow["timed_out"], true);
+        assert!(slow["exit_code"].is_null());
+
+        assert!(matches!(
+            tool.execute(json!({"language": "cobol", "code": ""})).await,
+            Err(ToolError::InvalidArgs(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn notify_tool_renders_template_and_retries_server_errors() {
+        use wiremock::matchers::{body_json, method};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(body_json(
+                json!({"text": "[critical] Disk full on db-1 (92%): cleanup failed"}),
+            ))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+
+        let tool = NotifyTool::new(server.uri(), WebhookFormat::Slack)
+            .with_template("[{{severity}}] {{title}} ({{usage}}): {{message}}")
+            .with_retry(2, Duration::from_millis(5));
+        let output = tool
+            .execute(json!({
+                "title": "Disk full on db-1",
+                "message": "cleanup failed",
+                "severity": "critical",
+                "fields": {"usage": "92%"}
+            }))
+            .await
+            .unwrap();
+        assert_eq!(
+            output,
+            json!({"delivered": true, "status": 200, "attempts": 2})
+        );
+
+        let teams = NotifyTool::new(format!("{}/missing", server.uri()), WebhookFormat::Teams)
+            .with_retry(0, Duration::from_millis(5));
+        assert!(teams
+            .execute(json!({"title": "t", "message": "m"}))
+            .await
+            .is_err());
+        assert!(!tool.side_effects().idempotent);
+    }
+
+    #[tokio::test]
+    async fn http_request_tool_sends_method_headers_body_and_auth() {
+        use wiremock::matchers::{body_json, header, method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/items"))
+            .and(header("x-trace", "abc"))
+            .and(header("authorization", "Bearer t0ken"))
+            .and(body_json(json!({"name": "widget"})))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!({"id": 9})))
+            .mount(&server)
+            .await;
+        Mock::given(path("/loop"))
+            .respond_with(ResponseTemplate::new(302).insert_header("location", "/loop"))
+            .mount(&server)
+            .await;
+
+        let tool =
+            HttpRequestTool::with_client_config(&HttpClientConfig::default().with_max_redirects(2))
+                .unwrap()
+                .with_auth(
+                    HttpAuth::Bearer {
+                        token_secret: "api_token".into(),
+                    },
+                    Arc::new(FixedSecrets),
+                    ["127.0.0.1"],
+                );
+        let output = tool
+            .execute(json!({
+                "url": format!("{}/items", server.uri()),
+                "method": "post",
+                "headers": {"x-trace": "abc"},
+                "body": {"name": "widget"}
+            }))
+            .await
+            .unwrap();
+        assert_eq!(output["status"], 201);
+        assert_eq!(output["body"]["id"], 9);
+
+        let redirected = tool
+            .execute(json!({"url": format!("{}/loop", server.uri())}))
+            .await;
+        assert!(matches!(redirected, Err(ToolError::Execution(msg)) if msg.contains("redirects")));
+
+        let missing_secret = HttpRequestTool::new()
+            .with_auth(
+                HttpAuth::Basic {
+                    username: "bot".into(),
+                    password_secret: "missing".into(),
+                },
+                Arc::new(FixedSecrets),
+                ["127.0.0.1"],
+            )
+            .execute(json!({"url": format!("{}/items", server.uri())}))
+            .await;
+        assert!(missing_secret.is_err());
+    }
+
+    #[test]
+    fn private_ranges_are_detected() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "192.168.0.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "::1",
+            "fd00::1",
+            "::ffff:10.0.0.1",
+            "64:ff9b::a9fe:a9fe",
+            "::127.0.0.1",
+            "2002:c0a8:0101::1",
+        ] {
+            assert!(is_private_ip(ip.parse().unwrap()), "{ip}");
+        }
+        for ip in ["93.184.216.34", "2606:4700::1111", "64:ff9b::5db8:d822"] {
+            assert!(!is_private_ip(ip.parse().unwrap()), "{ip}");
+        }
+    }
+
+    #[tokio::test]
+    async fn http_fetch_policy_enforces_hosts_size_and_ssrf() {
+        use wiremock::matchers::path;
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(path("/data"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"items": [1, 2]})))
+            .mount(&server)
+            .await;
+        Mock::given(path("/big"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("x".repeat(4096)))
+            .mount(&server)
+            .await;
+        Mock::given(path("/escape"))
+            .respond_with(
+                ResponseTemplate::new(302).insert_header("location", "http://localhost/admin"),
+            )
+            .mount(&server)
+            .await;
+
+        let url = |p: &str| json!({"url": format!("{}{p}", server.uri())});
+
+        let default = HttpFetchTool::with_policy(HttpFetchPolicy::default()).unwrap();
+        let blocked = default.execute(url("/data")).await;
+        assert!(matches!(blocked, Err(ToolError::InvalidArgs(msg)) if msg.contains("private")));
+
+        let local = HttpFetchTool::with_policy(HttpFetchPolicy {
+            block_private_ips: false,
+            max_response_bytes: Some(1024),
+            ..HttpFetchPolicy::default().allow_host("127.0.0.1")
+        })
+        .unwrap();
+        let output = local.execute(url("/data")).await.unwrap();
+        assert_eq!(output["body"]["items"], json!([1, 2]));
+        assert!(local.execute(url("/big")).await.is_err());
+        let escaped = local.execute(url("/escape")).await;
+        assert!(matches!(escaped, Err(ToolError::InvalidArgs(msg)) if msg.contains("localhost")));
+
+        let denied =
+            HttpFetchTool::with_policy(HttpFetchPolicy::default().deny_host("example.com"))
+                .unwrap()
+                .execute(json!({"url": "https://api.example.com/"}))
+                .await;
+        assert!(matches!(denied, Err(ToolError::InvalidArgs(_))));
+    }
+
+    struct CountingTool {
+        calls: Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl Tool for CountingTool {
+        fn name(&self) -> &'static str {
+            "counting"
+        }
+
+        fn input_schema(&self) -> serde_json::Value {
+            json!({"type": "object"})
+        }
+
+        fn output_schema(&self) -> serde_json::Value {
+            json!({"type": "number"})
+        }
+
+        async fn execute(&self, _args: serde_json::Value) -> Result<serde_json::Value, ToolError> {
+            let calls = self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+            Ok(json!(calls))
+        }
+    }
+
+    #[tokio::test]
+    async fn registry_caches_identical_calls() {
+        let calls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let registry = ToolRegistry::new();
+        registry.register_with_metadata(
+            CountingTool {
+                calls: calls.clone(),
+            },
+            ToolMetadata {
+                cache: Some(CachePolicy {
+                    ttl: Duration::from_millis(100),
+                    max_entries: 1,
+                }),
+                ..Default::default()
+            },
+        );
+
+        let first = registry
+            .invoke("counting", json!({"q": "rust", "page": 1}), &[])
+            .await
+            .unwrap();
+        let reordered = registry
+            .invoke("counting", json!({"page": 1, "q": "rust"}), &[])
+            .await
+            .unwrap();
+        assert_eq!(first, reordered);
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+        // A different call evicts the only entry.
+        registry
+            .invoke("counting", json!({"q": "go"}), &[])
+            .await
+            .unwrap();
+        registry
+            .invoke("counting", json!({"q": "rust", "page": 1}), &[])
+            .await
+            .unwrap();
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 3);
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        registry
+            .invoke("counting", json!({"q": "rust", "page": 1}), &[])
+            .await
+            .unwrap();
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 4);
+    }
+
+    /// Counts a few keywords, enough to rank the entries below.
+    struct KeywordEmbedder;
+
+    #[async_trait]
+    impl agent_memory::Embedder for KeywordEmbedder {
+        async fn embed(&self, text: &str) -> Vec<f32> {
+            let text = text.to_lowercase();
+            ["flight", "lisbon", "cat", "budget"]
+                .iter()
+                .map(|word| text.matches(word).count() as f32)
+                .collect()
+        }
+    }
+
+    #[tokio::test]
+    async fn memory_search_ranks_entries_by_similarity() {
+        use agent_memory::{InMemoryStore, MemoryStore};
+
+        let store = Arc::new(InMemoryStore::new());
+        store
+            .put("trip", &json!("Booked a flight to Lisbon in May"))
+            .unwrap();
+        store
+            .put("pet", &json!({"text": "The cat prefers salmon"}))
+            .unwrap();
+        store
+            .put(
+                "work",
+                &json!({"content": "Quarterly flight budget review"}),
+            )
+            .unwrap();
+        let tool = MemorySearchTool::new(store, Arc::new(KeywordEmbedder));
+
+        let results = tool
+            .execute(json!({"query": "flight to Lisbon", "top_k": 2}))
+            .await
+            .unwrap();
+        let keys: Vec<&str> = results
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|hit| hit["key"].as_str().unwrap())
+            .collect();
+        assert_eq!(keys, vec!["trip", "work"]);
+        assert!(results[0]["score"].as_f64().unwrap() > results[1]["score"].as_f64().unwrap());
+        assert!(tool.execute(json!({"top_k": 1})).await.is_err());
+    }
+
+    #[derive(serde::Serialize, schemars::JsonSchema)]
+    struct Forecast {
+        city: String,
+        celsius: i32,
+    }
+
+    #[crate::tool(description = "Looks up the forecast for a city")]
+    async fn weather(city: String, #[serde(default)] days: u8) -> Result<Forecast, String> {
+        if city.is_empty() {
+            return Err("unknown city".into());
+        }
+        Ok(Forecast {
+            city,
+            celsius: 20 + i32::from(days),
+        })
+    }
+
+    #[tokio::test]
+    async fn tool_attribute_generates_schemas_and_execution() {
+        let tool = WeatherTool;
+        assert_eq!(tool.name(), "weather");
+        assert_eq!(
+            WeatherTool::metadata().description.as_deref(),
+            Some("Looks up the forecast for a city")
+        );
+
+        let input = tool.input_schema();
+        assert_eq!(input["type"], "object");
+        assert_eq!(input["description"], "Looks up the forecast for a city");
+        assert_eq!(input["properties"]["city"]["type"], "string");
+        assert_eq!(input["required"], json!(["city"]));
+        assert_eq!(
+            tool.output_schema()["properties"]["celsius"]["type"],
+            "integer"
+        );
+
+        let output = tool
+            .execute(json!({"city": "Oslo", "days": 2}))
+            .await
+            .unwrap();
+        assert_eq!(output, json!({"city": "Oslo", "celsius": 22}));
+        assert!(matches!(
+            tool.execute(json!({"days": 2})).await,
+            Err(ToolError::InvalidArgs(_))
+        ));
+        assert!(matches!(
+            tool.execute(json!({"city": ""})).await,
+            Err(ToolError::Execution(message)) if message == "unknown city"
+        ));
+    }
+
+    struct NamedTool(&'static str);
+
+    #[async_trait]
+    impl Tool for NamedTool {
+        fn name(&self) -> &'static str {
+            self.0
+        }
+
+        fn input_schema(&self) -> serde_json::Value {
+            json!({"type": "object"})
+        }
+
+        fn output_schema(&self) -> serde_json::Value {
+            json!({"type": "string"})
+        }
+
+        async fn execute(&self, _args: serde_json::Value) -> Result<serde_json::Value, ToolError> {
+            Ok(json!(self.0))
+        }
+    }
+
+    #[tokio::test]
+    async fn namespaces_group_tools_and_supply_defaults() {
+        let registry = ToolRegistry::new().with_namespace_defaults(
+            "fs",
+            ToolMetadata {
+                allowed_roles: vec!["ops".into()],
+                timeout: Some(Duration::from_secs(1)),
+                ..Default::default()
+            },
+        );
+        registry.register(NamedTool("fs/read"));
+        registry.register(NamedTool("fs/archive/zip"));
+        registry.register_with_metadata(
+            NamedTool("fs/write"),
+            ToolMetadata {
+                allowed_roles: vec!["admin".into()],
+                ..Default::default()
+            },
+        );
+        registry.register(NamedTool("web/search"));
+
+        assert_eq!(
+            registry.list_namespace("fs"),
+            vec!["fs/archive/zip", "fs/read", "fs/write"]
+        );
+        assert_eq!(registry.list_namespace("web/"), vec!["web/search"]);
+
+        let zip = registry.get_metadata("fs/archive/zip").unwrap();
+        assert_eq!(zip.allowed_roles, vec!["ops"]);
+        assert_eq!(zip.timeout, Some(Duration::from_secs(1)));
+
+        let guest = ["guest".to_string()];
+        let ops = ["ops".to_string()];
+        assert!(matches!(
+            registry.invoke("fs/read", json!({}), &guest).await,
+            Err(ToolInvocationError::AccessDenied { .. })
+        ));
+        assert_eq!(
+            registry.invoke("fs/read", json!({}), &ops).await.unwrap(),
+            json!("fs/read")
+        );
+        // Explicit tool metadata wins over the namespace default.
+        assert!(registry.invoke("fs/write", json!({}), &ops).await.is_err());
+        assert!(registry
+            .invoke("web/search", json!({}), &guest)
+            .await
+            .is_ok());
+    }
+
+    struct GatedTool(Arc<tokio::sync::Notify>);
+
+    #[async_trait]
+    impl Tool for GatedTool {
+        fn name(&self) -> &'static str {
+            "gated"
+        }
+
+        fn input_schema(&self) -> serde_json::Value {
+            json!({"type": "object"})
+        }
+
+        fn output_schema(&self) -> serde_json::Value {
+            json!({"type": "string"})
+        }
+
+        async fn execute(&self, _args: serde_json::Value) -> Result<serde_json::Value, ToolError> {
+            self.0.notified().await;
+            Ok(json!("done"))
+        }
+    }
+
+    #[tokio::test]
+    async fn tools_can_be_swapped_while_invocations_are_in_flight() {
+        let gate = Arc::new(tokio::sync::Notify::new());
+        let registry = Arc::new(ToolRegistry::new());
+        registry.register(GatedTool(gate.clone()));
+
+        let in_flight = tokio::spawn({
+            let registry = registry.clone();
+            async move { registry.invoke("gated", json!({}), &[]).await }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        assert!(registry.unregister("gated").is_some());
+        assert!(registry.unregister("gated").is_none());
+        registry.register(NamedTool("fs/read"));
+        gate.notify_one();
+
+        assert_eq!(in_flight.await.unwrap().unwrap(), json!("done"));
+        assert!(matches!(
+            registry.invoke("gated", json!({}), &[]).await,
+            Err(ToolInvocationError::NotFound(_))
+        ));
+        assert_eq!(registry.list(), vec!["fs/read"]);
+    }
+
+    struct SleepyTool;
+
+    #[async_trait]
+    impl Tool for SleepyTool {
+        fn name(&self) -> &'static str {
+            "sleepy"
+        }
+
+        fn input_schema(&self) -> serde_json::Value {
+            json!({"type": "object"})
+        }
+
+        fn output_schema(&self) -> serde_json::Value {
+            json!({"type": "string"})
+        }
 
-        async fn execute(&self, args: Value) -> Result<Value, ToolError> {
-            if let Some(msg) = args.get("message").and_then(|v| v.as_str()) {
-                tracing::info!(target: "agent-tools::log", message = msg);
-                Ok(serde_json::json!({"ack": true}))
-            } else {
-                Err(ToolError::InvalidArgs("message missing".into()))
-            }
+        async fn execute(&self, _args: serde_json::Value) -> Result<serde_json::Value, ToolError> {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            Ok(json!("awake"))
         }
     }
 
-    pub struct HttpFetchTool {
-        client: reqwest::Client,
-    }
+    #[tokio::test]
+    async fn slow_tools_time_out_or_are_cancelled() {
+        let registry = ToolRegistry::new();
+        registry.register_with_metadata(
+            SleepyTool,
+            ToolMetadata {
+                timeout: Some(Duration::from_millis(20)),
+                ..Default::default()
+            },
+        );
+        let timed_out = registry.invoke("sleepy", json!({}), &[]).await;
+        assert!(matches!(
+            timed_out,
+            Err(ToolInvocationError::TimedOut { timeout_ms: 20, .. })
+        ));
 
-    impl HttpFetchTool {
-        pub fn new() -> Self {
-            Self {
-                client: reqwest::Client::new(),
-            }
-        }
+        let registry = ToolRegistry::new();
+        registry.register(SleepyTool);
+        let token = CancellationToken::new();
+        let ctx = InvocationContext::default().with_cancellation(token.clone());
+        let canceller = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            token.cancel();
+        });
+        let cancelled = registry.invoke_with("sleepy", json!({}), &ctx).await;
+        canceller.await.unwrap();
+        assert!(matches!(
+            cancelled,
+            Err(ToolInvocationError::Cancelled { .. })
+        ));
     }
 
+    struct LeakyTool(Arc<dyn SecretsProvider>);
+
     #[async_trait]
-    impl Tool for HttpFetchTool {
+    impl Tool for LeakyTool {
         fn name(&self) -> &'static str {
-            "http_fetch"
+            "leaky"
         }
 
-        fn input_schema(&self) -> Value {
-            serde_json::json!({
-                "type": "object",
-                "properties": {"url": {"type": "string"}},
-                "required": ["url"]
-            })
+        fn input_schema(&self) -> serde_json::Value {
+            json!({"type": "object"})
         }
 
-        fn output_schema(&self) -> Value {
-            serde_json::json!({"type": "object", "properties": {"status": {"type": "number"}, "body": {"type": "string"}}})
+        fn output_schema(&self) -> serde_json::Value {
+            json!({"type": "string"})
         }
 
-        async fn execute(&self, args: Value) -> Result<Value, ToolError> {
-            let url = args
-                .get("url")
-                .and_then(|v| v.as_str())
-                .ok_or_else(|| ToolError::InvalidArgs("url missing".into()))?;
-            let resp = self
-                .client
-                .get(url)
-                .send()
-                .await
-                .map_err(|e| ToolError::Execution(e.to_string()))?;
-            let status = resp.status().as_u16();
-            let body = resp
-                .text()
-                .await
-                .map_err(|e| ToolError::Execution(e.to_string()))?;
-            Ok(serde_json::json!({"status": status, "body": body}))
+        async fn execute(&self, _args: serde_json::Value) -> Result<serde_json::Value, ToolError> {
+            let token = self.0.get_secret("api_token").await?;
+            Err(ToolError::Execution(format!(
+                "upstream rejected key {}",
+                token.expose()
+            )))
         }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::builtins::{FileTool, SearchProvider, SearchResult, SearchTool};
-    use super::{ToolError, ToolInvocationError, ToolMetadata, ToolRegistry};
-    use crate::Tool;
-    use serde_json::json;
-    use std::sync::Arc;
-    use std::time::Duration;
+    #[tokio::test]
+    async fn registry_redacts_tracked_secrets_from_errors() {
+        let redactor = SecretRedactor::new();
+        let registry = ToolRegistry::new().with_secret_redactor(redactor.clone());
+        registry.register(LeakyTool(Arc::new(redactor.track(Arc::new(FixedSecrets)))));
+
+        let error = registry.invoke("leaky", json!({}), &[]).await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "execution failed: upstream rejected key {}",
+                agent_telemetry::REDACTED
+            )
+        );
+    }
 
     #[tokio::test]
-    async fn file_tool_read_write_roundtrip() {
+    async fn audit_sinks_see_every_invocation() {
         let dir = tempfile::tempdir().unwrap();
-        let tool = FileTool::new(dir.path());
+        let path = dir.path().join("audit.jsonl");
+        let writer = Arc::new(agent_telemetry::AuditLogWriter::new(&path).unwrap());
+        let registry = ToolRegistry::new().with_audit_sink(AuditLogSink::new(writer.clone()));
+        registry.register_with_metadata(
+            MathTool,
+            ToolMetadata {
+                allowed_roles: vec!["analyst".into()],
+                sensitive_arg_paths: vec!["/token".into()],
+                ..Default::default()
+            },
+        );
 
-        let write_result = tool
-            .execute(
-                json!({"path": "notes/hello.txt", "operation": "write", "content": "hi there"}),
+        let analyst = InvocationContext::new(&["analyst".into()]).with_caller_id("planner");
+        registry
+            .invoke_with(
+                "math",
+                json!({"expression": "1 + 2", "token": "s3cret"}),
+                &analyst,
             )
             .await
             .unwrap();
-        assert_eq!(write_result.get("operation").unwrap(), "write");
+        assert!(registry
+            .invoke("math", json!({"expression": "1"}), &[])
+            .await
+            .is_err());
+        writer.flush().unwrap();
 
-        let read_result = tool
-            .execute(json!({"path": "notes/hello.txt", "operation": "read"}))
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert!(!contents.contains("s3cret"));
+        let events: Vec<serde_json::Value> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(events.len(), 2);
+        let ok = &events[0]["payload"];
+        assert_eq!(events[0]["event_name"], "tool_invocation");
+        assert_eq!(ok["tool"], "math");
+        assert_eq!(ok["success"], true);
+        assert_eq!(ok["output"], 3.0);
+        assert_eq!(ok["caller_id"], "planner");
+        assert_eq!(ok["args"]["token"], agent_telemetry::REDACTED);
+        let denied = &events[1]["payload"];
+        assert_eq!(denied["success"], false);
+        assert!(denied["error"].as_str().unwrap().contains("access denied"));
+    }
+
+    struct Snooper(Arc<std::sync::Mutex<Option<serde_json::Value>>>);
+
+    #[async_trait]
+    impl ToolMiddleware for Snooper {
+        async fn after_invoke(
+            &self,
+            _tool: &str,
+            _args: &serde_json::Value,
+            output: &mut serde_json::Value,
+        ) -> Result<(), ToolInvocationError> {
+            *self.0.lock().unwrap() = Some(output.clone());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn output_redactor_masks_results_and_reports_to_the_context() {
+        let seen = Arc::new(std::sync::Mutex::new(None));
+        let registry = ToolRegistry::new()
+            .with_output_redactor(OutputRedactor::new(&["dump"]).unwrap())
+            .with_middleware(Snooper(seen.clone()));
+        registry.register(NamedTool("secrets/dump"));
+
+        let ctx = InvocationContext::new(&[]);
+        let output = registry
+            .invoke_with("secrets/dump", json!({}), &ctx)
             .await
             .unwrap();
-        assert_eq!(read_result.get("operation").unwrap(), "read");
-        assert_eq!(read_result.get("content").unwrap(), "hi there");
+        assert_eq!(output, json!("secrets/[REDACTED]"));
+        // Middlewares only ever see the masked output.
+        assert_eq!(*seen.lock().unwrap(), Some(json!("secrets/[REDACTED]")));
+
+        let report = ctx.take_redactions();
+        assert_eq!(report.len(), 1);
+        assert_eq!(report[0].tool, "secrets/dump");
+        assert_eq!(report[0].rule, "dump");
+        assert_eq!(report[0].path, "");
+        assert!(ctx.take_redactions().is_empty());
+    }
+
+    #[test]
+    fn registry_renders_provider_function_specs() {
+        let registry = ToolRegistry::new();
+        registry.register_with_metadata(
+            MathTool,
+            ToolMetadata {
+                description: Some("Evaluates arithmetic".into()),
+                ..Default::default()
+            },
+        );
+        registry.register(NamedTool("fs/read"));
+
+        let openai = registry.to_function_specs(FunctionSpecFormat::OpenAi);
+        assert_eq!(openai[0]["type"], "function");
+        assert_eq!(openai[0]["function"]["name"], "fs__read");
+        assert!(openai[0]["function"].get("description").is_none());
+        assert_eq!(openai[1]["function"]["name"], "math");
+        assert_eq!(openai[1]["function"]["description"], "Evaluates arithmetic");
+        assert_eq!(openai[1]["function"]["parameters"], MathTool.input_schema());
+        assert_eq!(
+            registry.to_function_specs(FunctionSpecFormat::AzureOpenAi),
+            openai
+        );
+
+        let anthropic = registry.to_function_specs(FunctionSpecFormat::Anthropic);
+        assert_eq!(anthropic[1]["name"], "math");
+        assert_eq!(anthropic[1]["input_schema"], MathTool.input_schema());
+
+        assert_eq!(function_name("web search/v2"), "web_search__v2");
+        assert_eq!(
+            registry.tool_for_function("fs__read").as_deref(),
+            Some("fs/read")
+        );
+        assert!(registry.tool_for_function("fs/read").is_none());
+    }
+
+    #[test]
+    fn function_specs_include_tool_examples() {
+        let registry = ToolRegistry::new();
+        registry.register_with_metadata(
+            MathTool,
+            ToolMetadata {
+                description: Some("Evaluates arithmetic".into()),
+                examples: vec![ToolExample::new(
+                    "Add two numbers",
+                    json!({"expression": "1 + 2"}),
+                    json!(3.0),
+                )],
+                ..Default::default()
+            },
+        );
+        registry.register_with_metadata(
+            NamedTool("fs/read"),
+            ToolMetadata {
+                examples: vec![ToolExample::new(
+                    "Read a config file",
+                    json!({"path": "app.toml"}),
+                    json!({"content": "..."}),
+                )],
+                ..Default::default()
+            },
+        );
+
+        let specs = registry.to_function_specs(FunctionSpecFormat::Anthropic);
+        assert_eq!(
+            specs[0]["description"],
+            "Examples:\n- Read a config file\n  args: {\"path\":\"app.toml\"}\n  output: {\"content\":\"...\"}"
+        );
+        assert_eq!(
+            specs[1]["description"],
+            "Evaluates arithmetic\n\nExamples:\n- Add two numbers\n  args: {\"expression\":\"1 + 2\"}\n  output: 3.0"
+        );
+    }
+
+    #[tokio::test]
+    async fn http_request_tool_keeps_credentials_to_their_hosts() {
+        use wiremock::matchers::path;
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        let port = server.address().port();
+        Mock::given(path("/start"))
+            .respond_with(
+                ResponseTemplate::new(302)
+                    .insert_header("location", format!("http://localhost:{port}/landed")),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(path("/landed"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        let auth = HttpAuth::Bearer {
+            token_secret: "api_token".into(),
+        };
+
+        let elsewhere = HttpRequestTool::new().with_auth(
+            auth.clone(),
+            Arc::new(FixedSecrets),
+            ["api.example.com"],
+        );
+        let output = elsewhere
+            .execute(json!({"url": format!("{}/landed", server.uri())}))
+            .await
+            .unwrap();
+        assert_eq!(output["status"], 200);
+
+        let policy = HttpFetchPolicy {
+            block_private_ips: false,
+            ..HttpFetchPolicy::default()
+                .allow_host("127.0.0.1")
+                .allow_host("localhost")
+        };
+        let redirected = HttpRequestTool::with_policy(policy).unwrap().with_auth(
+            auth,
+            Arc::new(FixedSecrets),
+            ["127.0.0.1"],
+        );
+        let output = redirected
+            .execute(json!({"url": format!("{}/start", server.uri())}))
+            .await
+            .unwrap();
+        assert_eq!(output["status"], 200);
+
+        let requests = server.received_requests().await.unwrap();
+        let authorized: Vec<_> = requests
+            .iter()
+            .map(|r| (r.url.path(), r.headers.contains_key("authorization")))
+            .collect();
+        assert_eq!(
+            authorized,
+            [("/landed", false), ("/start", true), ("/landed", false)]
+        );
+
+        let denied = HttpRequestTool::with_policy(HttpFetchPolicy::default())
+            .unwrap()
+            .execute(json!({"url": format!("{}/landed", server.uri())}))
+            .await;
+        assert!(denied.is_err());
+    }
+
+    #[tokio::test]
+    async fn http_request_tool_guards_redirects_and_body_size_under_a_policy() {
+        use wiremock::matchers::path;
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        let port = server.address().port();
+        Mock::given(path("/start"))
+            .respond_with(
+                ResponseTemplate::new(302)
+                    .insert_header("location", format!("http://localhost:{port}/landed")),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(path("/landed"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        Mock::given(path("/large"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("x".repeat(64)))
+            .mount(&server)
+            .await;
+        let policy = HttpFetchPolicy {
+            block_private_ips: false,
+            max_response_bytes: Some(16),
+            ..HttpFetchPolicy::default()
+                .allow_host("127.0.0.1")
+                .allow_host("localhost")
+        };
+        let tool = HttpRequestTool::with_policy(policy).unwrap();
+
+        tool.execute(json!({
+            "url": format!("{}/start", server.uri()),
+            "headers": {"Authorization": "Bearer model", "Cookie": "a=b", "x-trace": "1"}
+        }))
+        .await
+        .unwrap();
+        let requests = server.received_requests().await.unwrap();
+        let landed = &requests[1];
+        assert_eq!(landed.url.path(), "/landed");
+        assert!(landed.headers.contains_key("x-trace"));
+        assert!(!landed.headers.contains_key("authorization"));
+        assert!(!landed.headers.contains_key("cookie"));
+
+        let large = tool
+            .execute(json!({"url": format!("{}/large", server.uri())}))
+            .await;
+        assert!(matches!(large, Err(ToolError::Execution(msg)) if msg.contains("16 bytes")));
+    }
+
+    #[tokio::test]
+    async fn http_tools_layer_policies_over_client_config() {
+        use wiremock::matchers::{header, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(path("/tenant"))
+            .and(header("x-tenant", "acme"))
+            .and(header("user-agent", "agent-tools-test"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        let config = agent_core::HttpClientConfig::default()
+            .with_header("x-tenant", "acme")
+            .with_user_agent("agent-tools-test");
+        let local = HttpFetchPolicy {
+            block_private_ips: false,
+            ..HttpFetchPolicy::default().allow_host("127.0.0.1")
+        };
+        let url = json!({"url": format!("{}/tenant", server.uri())});
+
+        let fetch = HttpFetchTool::with_client_config_and_policy(&config, local.clone()).unwrap();
+        assert_eq!(fetch.execute(url.clone()).await.unwrap()["status"], 200);
+        let request = HttpRequestTool::with_client_config_and_policy(&config, local).unwrap();
+        assert_eq!(request.execute(url.clone()).await.unwrap()["status"], 200);
+
+        let strict =
+            HttpRequestTool::with_client_config_and_policy(&config, HttpFetchPolicy::default())
+                .unwrap();
+        assert!(strict.execute(url).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn budgets_deny_calls_once_spending_would_exceed_the_limit() {
+        let registry = ToolRegistry::new().with_budget(ToolBudget { limit: 1.0 });
+        registry.register_with_metadata(
+            MathTool,
+            ToolMetadata {
+                cost_per_call: Some(0.4),
+                ..Default::default()
+            },
+        );
+        registry.register(TimeTool);
+        let args = json!({"expression": "2 * 3"});
+        let run = InvocationContext::default().with_run_id("run-1");
+
+        registry
+            .invoke_with("math", args.clone(), &run)
+            .await
+            .unwrap();
+        registry
+            .invoke_with("math", args.clone(), &run)
+            .await
+            .unwrap();
+        match registry.invoke_with("math", args.clone(), &run).await {
+            Err(ToolInvocationError::BudgetExceeded {
+                cost, remaining, ..
+            }) => {
+                assert_eq!(cost, 0.4);
+                assert!((remaining - 0.2).abs() < 1e-9);
+            }
+            other => panic!("expected budget error, got {other:?}"),
+        }
+        // Tools without a cost and other runs are unaffected.
+        registry.invoke_with("time", json!({}), &run).await.unwrap();
+        let other_run = InvocationContext::default().with_run_id("run-2");
+        registry
+            .invoke_with("math", args.clone(), &other_run)
+            .await
+            .unwrap();
+        assert!((registry.spent(&run) - 0.8).abs() < 1e-9);
+
+        registry.reset_budget(&run);
+        assert_eq!(registry.remaining_budget(&run), Some(1.0));
+        registry.invoke_with("math", args, &run).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn rejected_calls_take_neither_budget_nor_rate_limit_slots() {
+        let registry = ToolRegistry::new().with_budget(ToolBudget { limit: 1.0 });
+        registry.register_with_metadata(
+            MathTool,
+            ToolMetadata {
+                cost_per_call: Some(0.6),
+                rate_limit: Some(RateLimitPolicy {
+                    max_calls: 2,
+                    per: Duration::from_secs(60),
+                    per_caller: false,
+                }),
+                ..Default::default()
+            },
+        );
+        let args = json!({"expression": "2 * 3"});
+        let run = |id: &str| InvocationContext::default().with_run_id(id);
+
+        registry
+            .invoke_with("math", args.clone(), &run("run-1"))
+            .await
+            .unwrap();
+        assert!(matches!(
+            registry
+                .invoke_with("math", args.clone(), &run("run-1"))
+                .await,
+            Err(ToolInvocationError::BudgetExceeded { .. })
+        ));
+        // The over-budget call left the second rate-limit slot free.
+        registry
+            .invoke_with("math", args.clone(), &run("run-2"))
+            .await
+            .unwrap();
+        assert!(matches!(
+            registry.invoke_with("math", args, &run("run-3")).await,
+            Err(ToolInvocationError::RateLimited { .. })
+        ));
+        assert_eq!(registry.spent(&run("run-3")), 0.0);
+    }
+
+    #[tokio::test]
+    async fn rate_limits_can_be_keyed_per_caller() {
+        let registry = ToolRegistry::new();
+        registry.register_with_metadata(
+            MathTool,
+            ToolMetadata {
+                rate_limit: Some(RateLimitPolicy {
+                    max_calls: 1,
+                    per: Duration::from_secs(60),
+                    per_caller: true,
+                }),
+                ..Default::default()
+            },
+        );
+        let args = json!({"expression": "1 + 1"});
+        let alpha = InvocationContext::default().with_caller_id("alpha");
+        let beta = InvocationContext::default().with_caller_id("beta");
+
+        registry
+            .invoke_with("math", args.clone(), &alpha)
+            .await
+            .unwrap();
+        let limited = registry.invoke_with("math", args.clone(), &alpha).await;
+        assert!(matches!(
+            limited,
+            Err(ToolInvocationError::RateLimited { .. })
+        ));
+        registry
+            .invoke_with("math", args.clone(), &beta)
+            .await
+            .unwrap();
+
+        // Callers without an id share one window.
+        registry.invoke("math", args.clone(), &[]).await.unwrap();
+        assert!(registry.invoke("math", args, &[]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn tag_rate_limits_are_shared_across_tagged_tools() {
+        let registry = ToolRegistry::new().with_tag_rate_limit(
+            "external_api",
+            RateLimitPolicy {
+                max_calls: 2,
+                per: Duration::from_secs(60),
+                per_caller: false,
+            },
+        );
+        let external = || ToolMetadata {
+            tags: vec!["external_api".into()],
+            ..Default::default()
+        };
+        registry.register_with_metadata(NamedTool("search"), external());
+        registry.register_with_metadata(NamedTool("geocode"), external());
+        registry.register_with_metadata(
+            NamedTool("weather"),
+            ToolMetadata {
+                tags: vec!["external_api".into()],
+                rate_limit: Some(RateLimitPolicy {
+                    max_calls: 0,
+                    per: Duration::from_secs(60),
+                    per_caller: false,
+                }),
+                ..Default::default()
+            },
+        );
+        registry.register(NamedTool("local"));
+
+        // A call denied by the tool's own limit does not use up the tag's window.
+        assert!(registry.invoke("weather", json!({}), &[]).await.is_err());
+        registry.invoke("search", json!({}), &[]).await.unwrap();
+        registry.invoke("geocode", json!({}), &[]).await.unwrap();
+        let limited = registry.invoke("search", json!({}), &[]).await;
+        assert!(matches!(
+            limited,
+            Err(ToolInvocationError::RateLimited { ref tool, retry_after_ms }) if tool == "search" && retry_after_ms > 0
+        ));
+        registry.invoke("local", json!({}), &[]).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn global_rate_limit_covers_every_tool() {
+        let registry = ToolRegistry::new().with_global_rate_limit(RateLimitPolicy {
+            max_calls: 1,
+            per: Duration::from_secs(60),
+            per_caller: true,
+        });
+        registry.register(NamedTool("a"));
+        registry.register(NamedTool("b"));
+        let alpha = InvocationContext::default().with_caller_id("alpha");
+        let beta = InvocationContext::default().with_caller_id("beta");
+
+        registry.invoke_with("a", json!({}), &alpha).await.unwrap();
+        assert!(registry.invoke_with("b", json!({}), &alpha).await.is_err());
+        registry.invoke_with("b", json!({}), &beta).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn registry_attaches_provenance_when_enabled() {
+        let plain = ToolRegistry::new();
+        plain.register(MathTool);
+        let result = plain
+            .invoke_result("math", json!({"expression": "2 * 3"}), &[])
+            .await
+            .unwrap();
+        assert!(result.provenance.is_none());
+
+        let tracked = ToolRegistry::new().with_provenance();
+        tracked.register(MathTool);
+        let first = tracked
+            .invoke_result("math", json!({"expression": "2 * 3"}), &[])
+            .await
+            .unwrap();
+        let second = tracked
+            .invoke_result("math", json!({"expression": "2 * 4"}), &[])
+            .await
+            .unwrap();
+
+        assert_eq!(first.value, result.value);
+        let provenance = first.provenance.unwrap();
+        assert_eq!(provenance.tool, "math");
+        assert_eq!(provenance.args_hash.len(), 64);
+        assert_ne!(provenance.args_hash, second.provenance.unwrap().args_hash);
     }
 
     #[tokio::test]
@@ -647,6 +5047,40 @@ mod tests {
         assert_eq!(output[0]["title"], "Example");
     }
 
+    #[test]
+    fn search_result_mapper_applies_paths() {
+        let mapper = SearchResultMapper::new("webPages.value", "name", "/link/href", "summary");
+        let raw = json!({
+            "webPages": {"value": [
+                {"name": "Rust", "link": {"href": "https://rust-lang.org"}, "summary": "A language"}
+            ]}
+        });
+
+        let results = mapper.from_json(&raw).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].title, "Rust");
+        assert_eq!(results[0].url, "https://rust-lang.org");
+
+        let missing = json!({"webPages": {"value": [{"name": "Rust", "summary": "A language"}]}});
+        let err = mapper.from_json(&missing).unwrap_err();
+        assert!(err.to_string().contains("/link/href"));
+    }
+
+    #[test]
+    fn http_fetch_tool_validates_client_config() {
+        let config = agent_core::HttpClientConfig::default()
+            .with_bearer_token("secret-token")
+            .with_user_agent("agent-tools-test");
+        assert!(HttpFetchTool::with_client_config(&config).is_ok());
+        assert!(!format!("{config:?}").contains("secret-token"));
+
+        let invalid = agent_core::HttpClientConfig::default().with_header("bad header", "x");
+        assert!(matches!(
+            HttpFetchTool::with_client_config(&invalid),
+            Err(ToolError::Execution(_))
+        ));
+    }
+
     #[tokio::test]
     async fn registry_enforces_cooldown_and_access() {
         struct NoopTool;
@@ -673,7 +5107,7 @@ mod tests {
             }
         }
 
-        let mut registry = ToolRegistry::new();
+        let registry = ToolRegistry::new();
         registry.register_with_metadata(
             NoopTool,
             ToolMetadata {
@@ -683,6 +5117,11 @@ mod tests {
                 access_controller: None,
                 rate_limit: None,
                 tags: vec![],
+                sensitive_arg_paths: vec![],
+                cache: None,
+                timeout: None,
+                cost_per_call: None,
+                examples: vec![],
             },
         );
 
@@ -704,4 +5143,73 @@ mod tests {
             .unwrap_err();
         assert!(matches!(cooldown, ToolInvocationError::CoolingDown { .. }));
     }
+
+    #[derive(Clone, Default)]
+    struct CapturedLogs(Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for CapturedLogs {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn registry_redacts_sensitive_args_in_logs() {
+        let logs = CapturedLogs::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::DEBUG)
+            .with_writer(move || writer.clone())
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let registry = ToolRegistry::new();
+        registry.register_with_metadata(
+            MathTool,
+            ToolMetadata {
+                sensitive_arg_paths: vec!["/credentials/api_key".into()],
+                ..Default::default()
+            },
+        );
+        registry
+            .invoke(
+                "math",
+                json!({"expression": "1 + 2", "credentials": {"api_key": "sk-live-123"}}),
+                &[],
+            )
+            .await
+            .unwrap();
+
+        let output = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        assert!(output.contains("invoking tool"));
+        assert!(output.contains("[REDACTED]"));
+        assert!(!output.contains("sk-live-123"));
+    }
+
+    #[tokio::test]
+    async fn log_tool_masks_known_secrets() {
+        let logs = CapturedLogs::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(move || writer.clone())
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let redactor = SecretRedactor::new();
+        redactor.remember("sk-live-123");
+        LogTool::new()
+            .with_secret_redactor(redactor)
+            .execute(json!({"message": "calling the api with sk-live-123"}))
+            .await
+            .unwrap();
+
+        let output = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        assert!(output.contains("calling the api with [REDACTED]"));
+        assert!(!output.contains("sk-live-123"));
+    }
 }
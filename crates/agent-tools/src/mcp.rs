@@ -0,0 +1,863 @@
+//! Client for Model Context Protocol servers.
+//!
+//! [`McpToolProvider`] performs the MCP handshake over an [`McpTransport`],
+//! discovers the server's tools with `tools/list`, and registers each one in a
+//! [`ToolRegistry`] as an [`McpTool`] whose schema is taken verbatim from the
+//! server's `inputSchema`. Servers are reached over stdio
+//! ([`StdioTransport`]), Streamable HTTP ([`HttpTransport`]) or the older
+//! HTTP+SSE transport ([`SseTransport`]).
+
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use thiserror::Error;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::sync::{oneshot, Mutex};
+use tokio::task::JoinHandle;
+
+use crate::{Tool, ToolError, ToolMetadata, ToolRegistry};
+
+pub const PROTOCOL_VERSION: &str = "2024-11-05";
+
+#[derive(Debug, Error)]
+pub enum McpError {
+    #[error("transport failed: {0}")]
+    Transport(String),
+    #[error("protocol error: {0}")]
+    Protocol(String),
+    #[error("server returned error {code}: {message}")]
+    Rpc { code: i64, message: String },
+}
+
+impl From<McpError> for ToolError {
+    fn from(err: McpError) -> Self {
+        ToolError::Execution(err.to_string())
+    }
+}
+
+/// Carries JSON-RPC messages to and from an MCP server.
+#[async_trait]
+pub trait McpTransport: Send + Sync {
+    /// Sends a request and waits for the response with the same id.
+    async fn request(&self, method: &str, params: Value) -> Result<Value, McpError>;
+    /// Sends a notification, which has no response.
+    async fn notify(&self, method: &str, params: Value) -> Result<(), McpError>;
+}
+
+fn request_message(id: u64, method: &str, params: Value) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params})
+}
+
+fn notification_message(method: &str, params: Value) -> Value {
+    json!({"jsonrpc": "2.0", "method": method, "params": params})
+}
+
+/// Extracts the `result` of a JSON-RPC response, surfacing `error` objects.
+fn into_result(response: Value) -> Result<Value, McpError> {
+    if let Some(error) = response.get("error") {
+        return Err(McpError::Rpc {
+            code: error
+                .get("code")
+                .and_then(Value::as_i64)
+                .unwrap_or_default(),
+            message: error
+                .get("message")
+                .and_then(Value::as_str)
+                .unwrap_or("unknown error")
+                .to_string(),
+        });
+    }
+    response
+        .get("result")
+        .cloned()
+        .ok_or_else(|| McpError::Protocol("response has neither result nor error".into()))
+}
+
+/// Talks to a server spawned as a child process over newline-delimited JSON.
+pub struct StdioTransport {
+    io: Mutex<(ChildStdin, BufReader<ChildStdout>)>,
+    next_id: AtomicU64,
+    _child: Child,
+}
+
+impl StdioTransport {
+    pub fn spawn(program: &str, args: &[&str]) -> Result<Self, McpError> {
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| McpError::Transport(format!("failed to spawn {program}: {e}")))?;
+        let stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| McpError::Transport("child stdin unavailable".into()))?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| McpError::Transport("child stdout unavailable".into()))?;
+        Ok(Self {
+            io: Mutex::new((stdin, BufReader::new(stdout))),
+            next_id: AtomicU64::new(1),
+            _child: child,
+        })
+    }
+
+    async fn write_line(stdin: &mut ChildStdin, message: &Value) -> Result<(), McpError> {
+        let mut line = message.to_string();
+        line.push('\n');
+        stdin
+            .write_all(line.as_bytes())
+            .await
+            .map_err(|e| McpError::Transport(e.to_string()))?;
+        stdin
+            .flush()
+            .await
+            .map_err(|e| McpError::Transport(e.to_string()))
+    }
+}
+
+#[async_trait]
+impl McpTransport for StdioTransport {
+    async fn request(&self, method: &str, params: Value) -> Result<Value, McpError> {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let mut io = self.io.lock().await;
+        let (stdin, stdout) = &mut *io;
+        Self::write_line(stdin, &request_message(id, method, params)).await?;
+
+        let mut line = String::new();
+        loop {
+            line.clear();
+            let read = stdout
+                .read_line(&mut line)
+                .await
+                .map_err(|e| McpError::Transport(e.to_string()))?;
+            if read == 0 {
+                return Err(McpError::Transport("server closed stdout".into()));
+            }
+            let Ok(message) = serde_json::from_str::<Value>(line.trim()) else {
+                continue; // servers may log non-JSON lines
+            };
+            // Skip notifications and server-initiated requests.
+            if message.get("id").and_then(Value::as_u64) == Some(id)
+                && message.get("method").is_none()
+            {
+                return into_result(message);
+            }
+        }
+    }
+
+    async fn notify(&self, method: &str, params: Value) -> Result<(), McpError> {
+        let mut io = self.io.lock().await;
+        Self::write_line(&mut io.0, &notification_message(method, params)).await
+    }
+}
+
+/// Header carrying the session a Streamable HTTP server assigns.
+pub const SESSION_ID_HEADER: &str = "mcp-session-id";
+
+/// Talks to a server over the Streamable HTTP transport, POSTing each message
+/// to a single endpoint.
+///
+/// Responses may come back as plain JSON or as a `text/event-stream` whose
+/// `data:` lines carry the JSON-RPC response. The session id the server
+/// returns from `initialize` is sent with every later message. Servers still
+/// on the older HTTP+SSE transport need [`SseTransport`].
+pub struct HttpTransport {
+    client: reqwest::Client,
+    endpoint: String,
+    next_id: AtomicU64,
+    session_id: std::sync::Mutex<Option<String>>,
+}
+
+impl HttpTransport {
+    pub fn new(endpoint: impl Into<String>) -> Self {
+        Self::with_client(reqwest::Client::new(), endpoint)
+    }
+
+    pub fn with_client(client: reqwest::Client, endpoint: impl Into<String>) -> Self {
+        Self {
+            client,
+            endpoint: endpoint.into(),
+            next_id: AtomicU64::new(1),
+            session_id: std::sync::Mutex::new(None),
+        }
+    }
+
+    /// The session the server assigned, once it has assigned one.
+    pub fn session_id(&self) -> Option<String> {
+        self.session().clone()
+    }
+
+    fn session(&self) -> std::sync::MutexGuard<'_, Option<String>> {
+        self.session_id
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    async fn post(&self, message: &Value) -> Result<String, McpError> {
+        let mut request = self
+            .client
+            .post(&self.endpoint)
+            .header("accept", "application/json, text/event-stream")
+            .json(message);
+        if let Some(session_id) = self.session_id() {
+            request = request.header(SESSION_ID_HEADER, session_id);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| McpError::Transport(e.to_string()))?
+            .error_for_status()
+            .map_err(|e| McpError::Transport(e.to_string()))?;
+        if let Some(session_id) = response
+            .headers()
+            .get(SESSION_ID_HEADER)
+            .and_then(|v| v.to_str().ok())
+        {
+            *self.session() = Some(session_id.to_string());
+        }
+        response
+            .text()
+            .await
+            .map_err(|e| McpError::Transport(e.to_string()))
+    }
+}
+
+/// Finds the response with `id` in a JSON body or an SSE stream body.
+fn parse_http_response(body: &str, id: u64) -> Result<Value, McpError> {
+    if let Ok(message) = serde_json::from_str::<Value>(body) {
+        return into_result(message);
+    }
+    body.lines()
+        .filter_map(|line| line.strip_prefix("data:"))
+        .filter_map(|data| serde_json::from_str::<Value>(data.trim()).ok())
+        .find(|message| message.get("id").and_then(Value::as_u64) == Some(id))
+        .ok_or_else(|| McpError::Protocol("no response found in event stream".into()))
+        .and_then(into_result)
+}
+
+#[async_trait]
+impl McpTransport for HttpTransport {
+    async fn request(&self, method: &str, params: Value) -> Result<Value, McpError> {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let body = self.post(&request_message(id, method, params)).await?;
+        parse_http_response(&body, id)
+    }
+
+    async fn notify(&self, method: &str, params: Value) -> Result<(), McpError> {
+        self.post(&notification_message(method, params))
+            .await
+            .map(|_| ())
+    }
+}
+
+/// One event of a `text/event-stream`, with its `data:` lines joined.
+#[derive(Debug, Clone, PartialEq)]
+struct SseEvent {
+    event: String,
+    data: String,
+}
+
+/// Parses one blank-line-terminated event block; the event type defaults to
+/// `message` and comment lines are skipped.
+fn parse_sse_event(block: &str) -> SseEvent {
+    let mut event = String::from("message");
+    let mut data = Vec::new();
+    for line in block.lines() {
+        let (field, value) = line.split_once(':').unwrap_or((line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value);
+        match field {
+            "event" => event = value.to_string(),
+            "data" => data.push(value),
+            _ => {}
+        }
+    }
+    SseEvent {
+        event,
+        data: data.join("\n"),
+    }
+}
+
+type Pending = Arc<std::sync::Mutex<HashMap<u64, oneshot::Sender<Value>>>>;
+
+/// Talks to a server over the HTTP+SSE transport from protocol version
+/// 2024-11-05.
+///
+/// [`SseTransport::connect`] opens the server's GET event stream and waits
+/// for its `endpoint` event, which names the URL messages are POSTed to.
+/// Responses arrive as `message` events on the stream and are matched to
+/// requests by id. The endpoint must share the stream's origin. Dropping the
+/// transport closes the stream.
+pub struct SseTransport {
+    client: reqwest::Client,
+    endpoint: reqwest::Url,
+    next_id: AtomicU64,
+    pending: Pending,
+    reader: JoinHandle<()>,
+}
+
+impl SseTransport {
+    pub async fn connect(url: &str) -> Result<Self, McpError> {
+        Self::connect_with_client(reqwest::Client::new(), url).await
+    }
+
+    pub async fn connect_with_client(client: reqwest::Client, url: &str) -> Result<Self, McpError> {
+        let url = reqwest::Url::parse(url).map_err(|e| McpError::Transport(e.to_string()))?;
+        let response = client
+            .get(url.clone())
+            .header("accept", "text/event-stream")
+            .send()
+            .await
+            .map_err(|e| McpError::Transport(e.to_string()))?
+            .error_for_status()
+            .map_err(|e| McpError::Transport(e.to_string()))?;
+
+        let pending = Pending::default();
+        let (endpoint_tx, endpoint_rx) = oneshot::channel();
+        let reader = tokio::spawn(Self::read_events(response, endpoint_tx, pending.clone()));
+        let Ok(endpoint) = endpoint_rx.await else {
+            return Err(McpError::Protocol(
+                "event stream closed before the endpoint event".into(),
+            ));
+        };
+        let endpoint = url
+            .join(&endpoint)
+            .map_err(|e| McpError::Protocol(format!("invalid endpoint {endpoint}: {e}")))?;
+        if endpoint.origin() != url.origin() {
+            reader.abort();
+            return Err(McpError::Protocol(format!(
+                "endpoint {endpoint} is not on the event stream's origin"
+            )));
+        }
+        Ok(Self {
+            client,
+            endpoint,
+            next_id: AtomicU64::new(1),
+            pending,
+            reader,
+        })
+    }
+
+    /// The URL messages are POSTed to, as announced by the server.
+    pub fn endpoint(&self) -> &reqwest::Url {
+        &self.endpoint
+    }
+
+    /// Reads the event stream until it ends, handing the first `endpoint`
+    /// event to `endpoint` and each response to the request waiting on it.
+    async fn read_events(
+        mut response: reqwest::Response,
+        endpoint: oneshot::Sender<String>,
+        pending: Pending,
+    ) {
+        let mut endpoint = Some(endpoint);
+        // Raw bytes, so a character split across chunks decodes intact once
+        // its event is complete.
+        let mut buffer = Vec::new();
+        while let Ok(Some(chunk)) = response.chunk().await {
+            buffer.extend(chunk.iter().filter(|&&byte| byte != b'\r'));
+            while let Some(end) = buffer.windows(2).position(|pair| pair == b"\n\n") {
+                let block: Vec<u8> = buffer.drain(..end + 2).collect();
+                let event = parse_sse_event(&String::from_utf8_lossy(&block));
+                match event.event.as_str() {
+                    "endpoint" => {
+                        if let Some(endpoint) = endpoint.take() {
+                            let _ = endpoint.send(event.data);
+                        }
+                    }
+                    "message" => {
+                        let Ok(message) = serde_json::from_str::<Value>(&event.data) else {
+                            continue;
+                        };
+                        // Skip notifications and server-initiated requests.
+                        if message.get("method").is_some() {
+                            continue;
+                        }
+                        let Some(id) = message.get("id").and_then(Value::as_u64) else {
+                            continue;
+                        };
+                        let waiter = pending
+                            .lock()
+                            .unwrap_or_else(|poisoned| poisoned.into_inner())
+                            .remove(&id);
+                        if let Some(waiter) = waiter {
+                            let _ = waiter.send(message);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        // Fail anything still waiting now that no response can arrive.
+        pending
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clear();
+    }
+
+    async fn post(&self, message: &Value) -> Result<(), McpError> {
+        self.client
+            .post(self.endpoint.clone())
+            .json(message)
+            .send()
+            .await
+            .map_err(|e| McpError::Transport(e.to_string()))?
+            .error_for_status()
+            .map_err(|e| McpError::Transport(e.to_string()))?;
+        Ok(())
+    }
+}
+
+impl Drop for SseTransport {
+    fn drop(&mut self) {
+        self.reader.abort();
+    }
+}
+
+#[async_trait]
+impl McpTransport for SseTransport {
+    async fn request(&self, method: &str, params: Value) -> Result<Value, McpError> {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let (tx, rx) = oneshot::channel();
+        self.pending
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .insert(id, tx);
+        if let Err(error) = self.post(&request_message(id, method, params)).await {
+            self.pending
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .remove(&id);
+            return Err(error);
+        }
+        let response = rx
+            .await
+            .map_err(|_| McpError::Transport("event stream closed".into()))?;
+        into_result(response)
+    }
+
+    async fn notify(&self, method: &str, params: Value) -> Result<(), McpError> {
+        self.post(&notification_message(method, params)).await
+    }
+}
+
+/// A tool advertised by an MCP server.
+#[derive(Debug, Clone, PartialEq)]
+pub struct McpToolDescriptor {
+    pub name: String,
+    pub description: Option<String>,
+    pub input_schema: Value,
+}
+
+/// Discovers tools on an MCP server and exposes them through [`ToolRegistry`].
+pub struct McpToolProvider {
+    transport: Arc<dyn McpTransport>,
+    server_info: Value,
+}
+
+impl McpToolProvider {
+    /// Runs the `initialize` handshake over `transport`.
+    pub async fn connect(transport: Arc<dyn McpTransport>) -> Result<Self, McpError> {
+        let result = transport
+            .request(
+                "initialize",
+                json!({
+                    "protocolVersion": PROTOCOL_VERSION,
+                    "capabilities": {},
+                    "clientInfo": {"name": "agent-tools", "version": env!("CARGO_PKG_VERSION")},
+                }),
+            )
+            .await?;
+        transport
+            .notify("notifications/initialized", json!({}))
+            .await?;
+        Ok(Self {
+            transport,
+            server_info: result.get("serverInfo").cloned().unwrap_or(Value::Null),
+        })
+    }
+
+    pub fn server_info(&self) -> &Value {
+        &self.server_info
+    }
+
+    /// Lists every tool, following `nextCursor` pagination.
+    pub async fn list_tools(&self) -> Result<Vec<McpToolDescriptor>, McpError> {
+        let mut tools = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let params = match &cursor {
+                Some(cursor) => json!({"cursor": cursor}),
+                None => json!({}),
+            };
+            let result = self.transport.request("tools/list", params).await?;
+            let page = result
+                .get("tools")
+                .and_then(Value::as_array)
+                .ok_or_else(|| McpError::Protocol("tools/list result missing tools".into()))?;
+            for tool in page {
+                let name = tool
+                    .get("name")
+                    .and_then(Value::as_str)
+                    .ok_or_else(|| McpError::Protocol("tool missing name".into()))?;
+                tools.push(McpToolDescriptor {
+                    name: name.to_string(),
+                    description: tool
+                        .get("description")
+                        .and_then(Value::as_str)
+                        .map(ToOwned::to_owned),
+                    input_schema: tool
+                        .get("inputSchema")
+                        .cloned()
+                        .unwrap_or_else(|| json!({"type": "object"})),
+                });
+            }
+            cursor = result
+                .get("nextCursor")
+                .and_then(Value::as_str)
+                .map(ToOwned::to_owned);
+            if cursor.is_none() {
+                return Ok(tools);
+            }
+        }
+    }
+
+    /// Registers every discovered tool and returns their names.
+    pub async fn register_all(&self, registry: &ToolRegistry) -> Result<Vec<String>, McpError> {
+        let mut names = Vec::new();
+        for descriptor in self.list_tools().await? {
+            let metadata = ToolMetadata {
+                description: descriptor.description.clone(),
+                tags: vec!["mcp".into()],
+                ..Default::default()
+            };
+            names.push(descriptor.name.clone());
+            registry
+                .register_with_metadata(McpTool::new(self.transport.clone(), descriptor), metadata);
+        }
+        Ok(names)
+    }
+}
+
+/// A single remote tool, invoked with `tools/call`.
+pub struct McpTool {
+    transport: Arc<dyn McpTransport>,
+    name: String,
+    input_schema: Value,
+}
+
+impl McpTool {
+    pub fn new(transport: Arc<dyn McpTransport>, descriptor: McpToolDescriptor) -> Self {
+        Self {
+            transport,
+            name: descriptor.name,
+            input_schema: descriptor.input_schema,
+        }
+    }
+}
+
+#[async_trait]
+impl Tool for McpTool {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn input_schema(&self) -> Value {
+        self.input_schema.clone()
+    }
+
+    fn output_schema(&self) -> Value {
+        json!({"type": "object", "properties": {"content": {"type": "array"}}})
+    }
+
+    async fn execute(&self, args: Value) -> Result<Value, ToolError> {
+        let result = self
+            .transport
+            .request("tools/call", json!({"name": self.name, "arguments": args}))
+            .await?;
+
+        if result.get("isError").and_then(Value::as_bool) == Some(true) {
+            let message = result
+                .pointer("/content/0/text")
+                .and_then(Value::as_str)
+                .unwrap_or("tool reported an error");
+            return Err(ToolError::Execution(message.to_string()));
+        }
+        // Prefer structured output when the server provides it.
+        Ok(result.get("structuredContent").cloned().unwrap_or_else(
+            || json!({"content": result.get("content").cloned().unwrap_or(json!([]))}),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex as StdMutex;
+
+    /// Server stub answering `initialize`, `tools/list` and `tools/call`.
+    #[derive(Default)]
+    struct FakeServer {
+        calls: StdMutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl McpTransport for FakeServer {
+        async fn request(&self, method: &str, params: Value) -> Result<Value, McpError> {
+            self.calls.lock().unwrap().push(method.to_string());
+            match method {
+                "initialize" => Ok(json!({"serverInfo": {"name": "fake"}})),
+                "tools/list" if params.get("cursor").is_none() => Ok(json!({
+                    "tools": [{
+                        "name": "echo",
+                        "description": "Echo text back",
+                        "inputSchema": {"type": "object", "properties": {"text": {"type": "string"}}}
+                    }],
+                    "nextCursor": "page-2"
+                })),
+                "tools/list" => Ok(json!({"tools": [{"name": "fail"}]})),
+                "tools/call" if params["name"] == "echo" => Ok(json!({
+                    "content": [{"type": "text", "text": params["arguments"]["text"]}]
+                })),
+                "tools/call" => Ok(json!({
+                    "content": [{"type": "text", "text": "boom"}],
+                    "isError": true
+                })),
+                other => Err(McpError::Rpc {
+                    code: -32601,
+                    message: format!("unknown method {other}"),
+                }),
+            }
+        }
+
+        async fn notify(&self, method: &str, _params: Value) -> Result<(), McpError> {
+            self.calls.lock().unwrap().push(method.to_string());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn provider_registers_discovered_tools() {
+        let server = Arc::new(FakeServer::default());
+        let provider = McpToolProvider::connect(server.clone()).await.unwrap();
+        assert_eq!(provider.server_info()["name"], "fake");
+
+        let registry = ToolRegistry::new();
+        let names = provider.register_all(&registry).await.unwrap();
+        assert_eq!(names, vec!["echo", "fail"]);
+        assert_eq!(
+            registry
+                .get_metadata("echo")
+                .unwrap()
+                .description
+                .as_deref(),
+            Some("Echo text back")
+        );
+        assert_eq!(
+            registry.get("echo").unwrap().input_schema()["properties"]["text"]["type"],
+            "string"
+        );
+
+        let output = registry
+            .invoke("echo", json!({"text": "hi"}), &[])
+            .await
+            .unwrap();
+        assert_eq!(output["content"][0]["text"], "hi");
+
+        let failed = registry.invoke("fail", json!({}), &[]).await;
+        assert!(failed.unwrap_err().to_string().contains("boom"));
+        assert_eq!(
+            server.calls.lock().unwrap()[..2],
+            ["initialize", "notifications/initialized"]
+        );
+    }
+
+    #[tokio::test]
+    async fn http_transport_sends_the_session_id_back() {
+        use wiremock::matchers::{body_partial_json, header, method};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(json!({"method": "initialize"})))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header(SESSION_ID_HEADER, "session-1")
+                    .set_body_json(json!({"jsonrpc": "2.0", "id": 1, "result": {}})),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(header(SESSION_ID_HEADER, "session-1"))
+            .respond_with(ResponseTemplate::new(202))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let transport = Arc::new(HttpTransport::new(server.uri()));
+        assert_eq!(transport.session_id(), None);
+        McpToolProvider::connect(transport.clone()).await.unwrap();
+        assert_eq!(transport.session_id().as_deref(), Some("session-1"));
+    }
+
+    #[test]
+    fn sse_events_join_data_lines() {
+        assert_eq!(
+            parse_sse_event(": keep-alive\nevent: endpoint\ndata: /messages\n\n"),
+            SseEvent {
+                event: "endpoint".into(),
+                data: "/messages".into()
+            }
+        );
+        assert_eq!(
+            parse_sse_event("data: {\"a\":\ndata: 1}\n\n"),
+            SseEvent {
+                event: "message".into(),
+                data: "{\"a\":\n1}".into()
+            }
+        );
+    }
+
+    /// Minimal HTTP+SSE server: the GET stream announces `/messages`, and
+    /// each POSTed request is answered on that stream.
+    async fn sse_server() -> String {
+        use tokio::io::AsyncReadExt;
+        use tokio::net::TcpListener;
+        use tokio::sync::mpsc;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let (events_tx, events_rx) = mpsc::unbounded_channel::<Value>();
+        let events_rx = Arc::new(Mutex::new(Some(events_rx)));
+        tokio::spawn(async move {
+            loop {
+                let (socket, _) = listener.accept().await.unwrap();
+                let events_tx = events_tx.clone();
+                let events_rx = events_rx.clone();
+                tokio::spawn(async move {
+                    let mut socket = BufReader::new(socket);
+                    loop {
+                        let mut request_line = String::new();
+                        if socket.read_line(&mut request_line).await.unwrap_or(0) == 0 {
+                            return;
+                        }
+                        let mut length = 0;
+                        loop {
+                            let mut line = String::new();
+                            socket.read_line(&mut line).await.unwrap();
+                            if line.trim().is_empty() {
+                                break;
+                            }
+                            if let Some((name, value)) = line.split_once(':') {
+                                if name.eq_ignore_ascii_case("content-length") {
+                                    length = value.trim().parse().unwrap();
+                                }
+                            }
+                        }
+                        if request_line.starts_with("GET") {
+                            let mut events = events_rx.lock().await.take().unwrap();
+                            let stream = socket.get_mut();
+                            stream
+                                .write_all(
+                                    b"HTTP/1.1 200 OK\r\ncontent-type: text/event-stream\r\n\r\n\
+                                      event: endpoint\r\ndata: /messages?session=1\r\n\r\n",
+                                )
+                                .await
+                                .unwrap();
+                            while let Some(message) = events.recv().await {
+                                let event = format!("event: message\ndata: {message}\n\n");
+                                stream.write_all(event.as_bytes()).await.unwrap();
+                            }
+                            return;
+                        }
+                        assert!(request_line.starts_with("POST /messages?session=1 "));
+                        let mut body = vec![0; length];
+                        socket.read_exact(&mut body).await.unwrap();
+                        let message: Value = serde_json::from_slice(&body).unwrap();
+                        let result = match message["method"].as_str() {
+                            Some("initialize") => json!({"serverInfo": {"name": "sse"}}),
+                            Some("tools/list") => json!({"tools": [{"name": "echo"}]}),
+                            _ => {
+                                json!({"content": [{"type": "text", "text": message["params"]["arguments"]["text"]}]})
+                            }
+                        };
+                        if let Some(id) = message.get("id") {
+                            events_tx
+                                .send(json!({"jsonrpc": "2.0", "id": id, "result": result}))
+                                .unwrap();
+                        }
+                        socket
+                            .get_mut()
+                            .write_all(b"HTTP/1.1 202 Accepted\r\ncontent-length: 0\r\n\r\n")
+                            .await
+                            .unwrap();
+                    }
+                });
+            }
+        });
+        format!("http://{address}/sse")
+    }
+
+    #[tokio::test]
+    async fn sse_transport_posts_to_the_announced_endpoint() {
+        let transport = Arc::new(SseTransport::connect(&sse_server().await).await.unwrap());
+        assert_eq!(transport.endpoint().path(), "/messages");
+
+        let provider = McpToolProvider::connect(transport).await.unwrap();
+        assert_eq!(provider.server_info()["name"], "sse");
+        let registry = ToolRegistry::new();
+        assert_eq!(
+            provider.register_all(&registry).await.unwrap(),
+            vec!["echo"]
+        );
+        let output = registry
+            .invoke("echo", json!({"text": "over sse"}), &[])
+            .await
+            .unwrap();
+        assert_eq!(output["content"][0]["text"], "over sse");
+    }
+
+    #[tokio::test]
+    async fn sse_transport_rejects_endpoints_on_another_origin() {
+        use wiremock::matchers::method;
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(
+                "event: endpoint\ndata: http://elsewhere.example/messages\n\n",
+                "text/event-stream",
+            ))
+            .mount(&server)
+            .await;
+        let error = SseTransport::connect(&server.uri()).await.err().unwrap();
+        assert!(matches!(error, McpError::Protocol(message) if message.contains("origin")));
+
+        let silent = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw("", "text/event-stream"))
+            .mount(&silent)
+            .await;
+        assert!(SseTransport::connect(&silent.uri()).await.is_err());
+    }
+
+    #[test]
+    fn http_response_accepts_event_stream_bodies() {
+        let body =
+            "event: message\ndata: {\"jsonrpc\":\"2.0\",\"id\":7,\"result\":{\"ok\":true}}\n\n";
+        assert_eq!(parse_http_response(body, 7).unwrap(), json!({"ok": true}));
+
+        let error = r#"{"jsonrpc":"2.0","id":3,"error":{"code":-32602,"message":"bad params"}}"#;
+        assert!(matches!(
+            parse_http_response(error, 3),
+            Err(McpError::Rpc { code: -32602, .. })
+        ));
+    }
+}
@@ -0,0 +1,37 @@
+use async_trait::async_trait;
+use serde_json::Value;
+
+use crate::ToolInvocationError;
+
+/// Hooks around every [`crate::ToolRegistry::invoke`] call.
+///
+/// Middlewares run in registration order before the tool executes and in
+/// reverse order afterwards, so the first middleware added wraps all others.
+/// Every hook has a no-op default.
+#[async_trait]
+pub trait ToolMiddleware: Send + Sync {
+    /// Runs before the tool executes. May rewrite `args`, reject the call with
+    /// an error, or return `Some(value)` to skip execution and use `value` as
+    /// the output (e.g. a cache hit).
+    async fn before_invoke(
+        &self,
+        _tool: &str,
+        _args: &mut Value,
+    ) -> Result<Option<Value>, ToolInvocationError> {
+        Ok(None)
+    }
+
+    /// Runs after a successful execution, once the registry's output redactor
+    /// has masked the output, and may rewrite it.
+    async fn after_invoke(
+        &self,
+        _tool: &str,
+        _args: &Value,
+        _output: &mut Value,
+    ) -> Result<(), ToolInvocationError> {
+        Ok(())
+    }
+
+    /// Observes any error returned by the invocation, including policy denials.
+    async fn on_error(&self, _tool: &str, _args: &Value, _error: &ToolInvocationError) {}
+}
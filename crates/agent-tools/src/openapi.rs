@@ -0,0 +1,653 @@
+//! Generates tools from OpenAPI 3 documents.
+//!
+//! [`import_spec`] produces one [`OpenApiTool`] per operation. Path, query and
+//! header parameters become top-level properties of the tool's input schema
+//! and a JSON request body is passed under `body`, so an operation with both
+//! a request body and a parameter named `body` is rejected, as is a `body`
+//! argument to an operation without one. Local `$ref`s are inlined.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use agent_telemetry::REDACTED;
+use async_trait::async_trait;
+use reqwest::Method;
+use serde_json::{json, Map, Value};
+
+use crate::{Tool, ToolError};
+
+const HTTP_METHODS: [&str; 8] = [
+    "get", "put", "post", "delete", "options", "head", "patch", "trace",
+];
+
+/// Credentials applied to every request made by tools from one spec.
+#[derive(Clone, Default)]
+pub enum OpenApiAuth {
+    #[default]
+    None,
+    Bearer(String),
+    ApiKey {
+        name: String,
+        location: ApiKeyLocation,
+        value: String,
+    },
+}
+
+impl fmt::Debug for OpenApiAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::None => f.write_str("None"),
+            Self::Bearer(_) => f.debug_tuple("Bearer").field(&REDACTED).finish(),
+            Self::ApiKey { name, location, .. } => f
+                .debug_struct("ApiKey")
+                .field("name", name)
+                .field("location", location)
+                .field("value", &REDACTED)
+                .finish(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApiKeyLocation {
+    Header,
+    Query,
+}
+
+#[derive(Clone, Default)]
+pub struct OpenApiConfig {
+    /// Overrides the first entry of the spec's `servers`.
+    pub base_url: Option<String>,
+    /// Where the spec was fetched from, against which relative server URLs
+    /// are resolved.
+    pub spec_url: Option<String>,
+    pub auth: OpenApiAuth,
+    pub client: Option<reqwest::Client>,
+}
+
+impl fmt::Debug for OpenApiConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OpenApiConfig")
+            .field("base_url", &self.base_url)
+            .field("spec_url", &self.spec_url)
+            .field("auth", &self.auth)
+            .field("client", &self.client.is_some())
+            .finish()
+    }
+}
+
+impl OpenApiConfig {
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = Some(base_url.into());
+        self
+    }
+
+    pub fn with_spec_url(mut self, spec_url: impl Into<String>) -> Self {
+        self.spec_url = Some(spec_url.into());
+        self
+    }
+
+    pub fn with_auth(mut self, auth: OpenApiAuth) -> Self {
+        self.auth = auth;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ParamLocation {
+    Path,
+    Query,
+    Header,
+}
+
+#[derive(Debug, Clone)]
+struct Parameter {
+    name: String,
+    location: ParamLocation,
+}
+
+/// A single OpenAPI operation exposed as a [`Tool`].
+pub struct OpenApiTool {
+    name: String,
+    description: Option<String>,
+    method: Method,
+    base_url: String,
+    path: String,
+    parameters: Vec<Parameter>,
+    /// Whether the operation takes a JSON request body under `body`.
+    has_body: bool,
+    input_schema: Value,
+    auth: OpenApiAuth,
+    client: reqwest::Client,
+}
+
+impl OpenApiTool {
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+}
+
+/// Parses a JSON or YAML OpenAPI 3 document with default configuration.
+pub fn import_spec(spec: &str) -> Result<Vec<OpenApiTool>, ToolError> {
+    import_spec_with(spec, OpenApiConfig::default())
+}
+
+pub fn import_spec_with(spec: &str, config: OpenApiConfig) -> Result<Vec<OpenApiTool>, ToolError> {
+    let doc: Value = match serde_json::from_str(spec) {
+        Ok(doc) => doc,
+        Err(_) => serde_yaml::from_str(spec)
+            .map_err(|e| ToolError::InvalidArgs(format!("invalid OpenAPI document: {e}")))?,
+    };
+    if !doc
+        .get("openapi")
+        .and_then(Value::as_str)
+        .is_some_and(|version| version.starts_with('3'))
+    {
+        return Err(ToolError::InvalidArgs(
+            "only OpenAPI 3.x documents are supported".into(),
+        ));
+    }
+
+    let base_url = config
+        .base_url
+        .clone()
+        .or_else(|| {
+            doc.pointer("/servers/0/url")
+                .and_then(Value::as_str)
+                .map(ToOwned::to_owned)
+        })
+        .ok_or_else(|| ToolError::InvalidArgs("spec has no servers and no base_url".into()))?;
+    let base_url = resolve_server_url(&base_url, config.spec_url.as_deref())?;
+    let client = config.client.clone().unwrap_or_default();
+    let paths = doc
+        .get("paths")
+        .and_then(Value::as_object)
+        .ok_or_else(|| ToolError::InvalidArgs("spec has no paths".into()))?;
+
+    let mut tools = Vec::new();
+    for (path, item) in paths {
+        let item = resolve(&doc, item)?;
+        let shared_params = item
+            .get("parameters")
+            .and_then(Value::as_array)
+            .cloned()
+            .unwrap_or_default();
+        for method in HTTP_METHODS {
+            let Some(operation) = item.get(method) else {
+                continue;
+            };
+            tools.push(build_tool(
+                &doc,
+                path,
+                method,
+                operation,
+                &shared_params,
+                &base_url,
+                &config.auth,
+                &client,
+            )?);
+        }
+    }
+    Ok(tools)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn build_tool(
+    doc: &Value,
+    path: &str,
+    method: &str,
+    operation: &Value,
+    shared_params: &[Value],
+    base_url: &str,
+    auth: &OpenApiAuth,
+    client: &reqwest::Client,
+) -> Result<OpenApiTool, ToolError> {
+    // Operation-level parameters override path-level ones with the same name and location.
+    let mut merged: BTreeMap<(String, String), Value> = BTreeMap::new();
+    let own_params = operation
+        .get("parameters")
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default();
+    for raw in shared_params.iter().chain(own_params.iter()) {
+        let param = resolve(doc, raw)?;
+        let name = param
+            .get("name")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        let location = param.get("in").and_then(Value::as_str).unwrap_or_default();
+        merged.insert((location.to_string(), name.to_string()), param);
+    }
+
+    let mut properties = Map::new();
+    let mut required = Vec::new();
+    let mut parameters = Vec::new();
+    for ((location, name), param) in merged {
+        let location = match location.as_str() {
+            "path" => ParamLocation::Path,
+            "query" => ParamLocation::Query,
+            "header" => ParamLocation::Header,
+            _ => continue, // cookie parameters are not supported
+        };
+        let mut schema = param
+            .get("schema")
+            .map(|schema| resolve(doc, schema))
+            .transpose()?
+            .unwrap_or_else(|| json!({"type": "string"}));
+        if let (Some(description), Some(object)) =
+            (param.get("description"), schema.as_object_mut())
+        {
+            object.insert("description".into(), description.clone());
+        }
+        if location == ParamLocation::Path || param.get("required") == Some(&Value::Bool(true)) {
+            required.push(Value::String(name.clone()));
+        }
+        properties.insert(name.clone(), schema);
+        parameters.push(Parameter { name, location });
+    }
+
+    let mut has_body = false;
+    if let Some(body) = operation.get("requestBody") {
+        let body = resolve(doc, body)?;
+        if let Some(schema) = body.pointer("/content/application~1json/schema") {
+            if properties.contains_key("body") {
+                return Err(ToolError::InvalidArgs(format!(
+                    "{method} {path} has a parameter named body, which clashes with its request body"
+                )));
+            }
+            properties.insert("body".into(), resolve(doc, schema)?);
+            if body.get("required") == Some(&Value::Bool(true)) {
+                required.push(Value::String("body".into()));
+            }
+            has_body = true;
+        }
+    }
+
+    let name = operation
+        .get("operationId")
+        .and_then(Value::as_str)
+        .map(ToOwned::to_owned)
+        .unwrap_or_else(|| fallback_name(method, path));
+    let description = operation
+        .get("summary")
+        .or_else(|| operation.get("description"))
+        .and_then(Value::as_str)
+        .map(ToOwned::to_owned);
+
+    Ok(OpenApiTool {
+        name,
+        description,
+        method: Method::from_bytes(method.to_uppercase().as_bytes())
+            .map_err(|e| ToolError::InvalidArgs(e.to_string()))?,
+        base_url: base_url.trim_end_matches('/').to_string(),
+        path: path.to_string(),
+        parameters,
+        has_body,
+        input_schema: json!({
+            "type": "object",
+            "properties": properties,
+            "required": required,
+        }),
+        auth: auth.clone(),
+        client: client.clone(),
+    })
+}
+
+fn fallback_name(method: &str, path: &str) -> String {
+    let mut name = method.to_string();
+    for segment in path.split('/').filter(|s| !s.is_empty()) {
+        name.push('_');
+        name.extend(
+            segment
+                .chars()
+                .filter(|c| !matches!(c, '{' | '}'))
+                .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }),
+        );
+    }
+    name
+}
+
+/// Inlines local `$ref`s (`#/...`). A `$ref` back to a schema it is already
+/// inside becomes `{}`, which accepts any value, so recursive schemas import.
+fn resolve(doc: &Value, value: &Value) -> Result<Value, ToolError> {
+    resolve_within(doc, value, &mut Vec::new())
+}
+
+/// Resolves `value` inside the `$ref`s in `enclosing`.
+fn resolve_within<'a>(
+    doc: &'a Value,
+    value: &'a Value,
+    enclosing: &mut Vec<&'a str>,
+) -> Result<Value, ToolError> {
+    match value {
+        Value::Object(object) => {
+            if let Some(reference) = object.get("$ref").and_then(Value::as_str) {
+                if enclosing.contains(&reference) {
+                    return Ok(json!({}));
+                }
+                let pointer = reference.strip_prefix('#').ok_or_else(|| {
+                    ToolError::InvalidArgs(format!("only local $refs are supported: {reference}"))
+                })?;
+                let target = doc.pointer(pointer).ok_or_else(|| {
+                    ToolError::InvalidArgs(format!("unresolved $ref: {reference}"))
+                })?;
+                enclosing.push(reference);
+                let resolved = resolve_within(doc, target, enclosing);
+                enclosing.pop();
+                return resolved;
+            }
+            let mut resolved = Map::new();
+            for (key, child) in object {
+                resolved.insert(key.clone(), resolve_within(doc, child, enclosing)?);
+            }
+            Ok(Value::Object(resolved))
+        }
+        Value::Array(items) => items
+            .iter()
+            .map(|item| resolve_within(doc, item, enclosing))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Value::Array),
+        other => Ok(other.clone()),
+    }
+}
+
+/// Makes a relative server URL such as `/v1` absolute against the URL the
+/// spec was served from.
+fn resolve_server_url(url: &str, spec_url: Option<&str>) -> Result<String, ToolError> {
+    if reqwest::Url::parse(url).is_ok() {
+        return Ok(url.to_string());
+    }
+    let spec_url = spec_url.ok_or_else(|| {
+        ToolError::InvalidArgs(format!(
+            "server url {url} is relative; set base_url or spec_url"
+        ))
+    })?;
+    reqwest::Url::parse(spec_url)
+        .and_then(|spec_url| spec_url.join(url))
+        .map(String::from)
+        .map_err(|e| ToolError::InvalidArgs(format!("invalid server url {url}: {e}")))
+}
+
+fn param_to_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn encode_path_segment(raw: &str) -> String {
+    let mut encoded = String::with_capacity(raw.len());
+    for byte in raw.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.' | b'~') {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    encoded
+}
+
+#[async_trait]
+impl Tool for OpenApiTool {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn input_schema(&self) -> Value {
+        self.input_schema.clone()
+    }
+
+    fn output_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {"status": {"type": "integer"}, "body": {}}
+        })
+    }
+
+    async fn execute(&self, args: Value) -> Result<Value, ToolError> {
+        if !self.has_body
+            && args.get("body").is_some()
+            && !self.parameters.iter().any(|param| param.name == "body")
+        {
+            return Err(ToolError::InvalidArgs(format!(
+                "{} {} takes no request body",
+                self.method, self.path
+            )));
+        }
+        let mut path = self.path.clone();
+        let mut query: Vec<(String, String)> = Vec::new();
+        let mut headers: Vec<(String, String)> = Vec::new();
+        for param in &self.parameters {
+            let Some(value) = args.get(&param.name) else {
+                if param.location == ParamLocation::Path {
+                    return Err(ToolError::InvalidArgs(format!(
+                        "missing path parameter {}",
+                        param.name
+                    )));
+                }
+                continue;
+            };
+            let value = param_to_string(value);
+            match param.location {
+                ParamLocation::Path => {
+                    path =
+                        path.replace(&format!("{{{}}}", param.name), &encode_path_segment(&value))
+                }
+                ParamLocation::Query => query.push((param.name.clone(), value)),
+                ParamLocation::Header => headers.push((param.name.clone(), value)),
+            }
+        }
+
+        let mut request = self
+            .client
+            .request(self.method.clone(), format!("{}{}", self.base_url, path));
+        match &self.auth {
+            OpenApiAuth::None => {}
+            OpenApiAuth::Bearer(token) => request = request.bearer_auth(token),
+            OpenApiAuth::ApiKey {
+                name,
+                location: ApiKeyLocation::Header,
+                value,
+            } => headers.push((name.clone(), value.clone())),
+            OpenApiAuth::ApiKey {
+                name,
+                location: ApiKeyLocation::Query,
+                value,
+            } => query.push((name.clone(), value.clone())),
+        }
+        if !query.is_empty() {
+            request = request.query(&query);
+        }
+        for (name, value) in headers {
+            request = request.header(name, value);
+        }
+        if let Some(body) = args.get("body").filter(|_| self.has_body) {
+            request = request.json(body);
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| ToolError::Execution(format!("request failed: {e}")))?;
+        let status = response.status();
+        let text = response
+            .text()
+            .await
+            .map_err(|e| ToolError::Execution(format!("failed to read body: {e}")))?;
+        if !status.is_success() {
+            return Err(ToolError::Execution(format!(
+                "{} {} returned {status}: {text}",
+                self.method, path
+            )));
+        }
+        let body = serde_json::from_str(&text).unwrap_or(Value::String(text));
+        Ok(json!({"status": status.as_u16(), "body": body}))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{body_json, header, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const SPEC: &str = r##"
+openapi: 3.0.0
+info: {title: Pets, version: "1"}
+servers: [{url: "https://pets.example"}]
+paths:
+  /pets/{petId}:
+    parameters:
+      - {name: petId, in: path, required: true, schema: {type: integer}}
+    get:
+      operationId: getPet
+      summary: Fetch a pet
+      parameters:
+        - {name: verbose, in: query, schema: {type: boolean}}
+    put:
+      requestBody:
+        required: true
+        content:
+          application/json:
+            schema: {$ref: "#/components/schemas/Pet"}
+components:
+  schemas:
+    Pet:
+      type: object
+      properties: {name: {type: string}}
+"##;
+
+    #[test]
+    fn import_maps_operations_to_tools() {
+        let tools = import_spec(SPEC).unwrap();
+        assert_eq!(tools.len(), 2);
+
+        let get = &tools[0];
+        assert_eq!(get.name(), "getPet");
+        assert_eq!(get.description(), Some("Fetch a pet"));
+        let schema = get.input_schema();
+        assert_eq!(schema["properties"]["petId"]["type"], "integer");
+        assert_eq!(schema["properties"]["verbose"]["type"], "boolean");
+        assert_eq!(schema["required"], json!(["petId"]));
+
+        let put = &tools[1];
+        assert_eq!(put.name(), "put_pets_petId");
+        let schema = put.input_schema();
+        assert_eq!(
+            schema["properties"]["body"]["properties"]["name"]["type"],
+            "string"
+        );
+        assert_eq!(schema["required"], json!(["petId", "body"]));
+    }
+
+    #[test]
+    fn import_handles_recursive_schemas_and_relative_servers() {
+        const TREE: &str = r##"
+openapi: 3.0.0
+info: {title: Trees, version: "1"}
+servers: [{url: "/v1"}]
+paths:
+  /nodes:
+    post:
+      requestBody:
+        content:
+          application/json:
+            schema: {$ref: "#/components/schemas/Node"}
+components:
+  schemas:
+    Node:
+      type: object
+      properties:
+        children: {type: array, items: {$ref: "#/components/schemas/Node"}}
+"##;
+        assert!(matches!(
+            import_spec(TREE),
+            Err(ToolError::InvalidArgs(msg)) if msg.contains("relative")
+        ));
+        let tools = import_spec_with(
+            TREE,
+            OpenApiConfig::default().with_spec_url("https://trees.example/specs/openapi.yaml"),
+        )
+        .unwrap();
+        assert_eq!(tools[0].base_url, "https://trees.example/v1");
+        assert_eq!(
+            tools[0].input_schema()["properties"]["body"]["properties"]["children"],
+            json!({"type": "array", "items": {}})
+        );
+    }
+
+    #[test]
+    fn import_rejects_parameters_clashing_with_the_body() {
+        let spec = r#"{
+            "openapi": "3.0.0",
+            "servers": [{"url": "https://notes.example"}],
+            "paths": {"/notes": {"post": {
+                "parameters": [{"name": "body", "in": "query"}],
+                "requestBody": {"content": {"application/json": {"schema": {"type": "object"}}}}
+            }}}
+        }"#;
+        assert!(matches!(
+            import_spec(spec),
+            Err(ToolError::InvalidArgs(msg)) if msg.contains("body")
+        ));
+    }
+
+    #[test]
+    fn import_rejects_swagger_2() {
+        let result = import_spec(r#"{"swagger": "2.0", "paths": {}}"#);
+        assert!(matches!(result, Err(ToolError::InvalidArgs(_))));
+    }
+
+    #[tokio::test]
+    async fn tools_execute_with_configured_auth() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/pets/7"))
+            .and(query_param("verbose", "true"))
+            .and(header("authorization", "Bearer secret"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"name": "Rex"})))
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .and(path("/pets/7"))
+            .and(header("x-api-key", "k"))
+            .and(body_json(json!({"name": "Max"})))
+            .respond_with(ResponseTemplate::new(204))
+            .mount(&server)
+            .await;
+
+        let bearer = import_spec_with(
+            SPEC,
+            OpenApiConfig::default()
+                .with_base_url(server.uri())
+                .with_auth(OpenApiAuth::Bearer("secret".into())),
+        )
+        .unwrap();
+        let output = bearer[0]
+            .execute(json!({"petId": 7, "verbose": true}))
+            .await
+            .unwrap();
+        assert_eq!(output, json!({"status": 200, "body": {"name": "Rex"}}));
+        // GET /pets/{petId} declares no request body.
+        let stray = bearer[0].execute(json!({"petId": 7, "body": {}})).await;
+        assert!(matches!(stray, Err(ToolError::InvalidArgs(_))));
+
+        let config = OpenApiConfig::default()
+            .with_base_url(server.uri())
+            .with_auth(OpenApiAuth::ApiKey {
+                name: "x-api-key".into(),
+                location: ApiKeyLocation::Header,
+                value: "k".into(),
+            });
+        assert!(!format!("{config:?}").contains("\"k\""));
+        let api_key = import_spec_with(SPEC, config).unwrap();
+        let output = api_key[1]
+            .execute(json!({"petId": 7, "body": {"name": "Max"}}))
+            .await
+            .unwrap();
+        assert_eq!(output["status"], 204);
+
+        let missing = api_key[1].execute(json!({"body": {}})).await;
+        assert!(matches!(missing, Err(ToolError::InvalidArgs(_))));
+    }
+}
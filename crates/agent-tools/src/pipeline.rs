@@ -0,0 +1,310 @@
+use std::sync::{Arc, Weak};
+
+use async_trait::async_trait;
+use serde_json::{Map, Value};
+
+use crate::{InvocationContext, Tool, ToolError, ToolInvocationError, ToolRegistry};
+
+/// One step of a [`PipelineTool`]: the registered tool to call and an args
+/// template.
+///
+/// Any string in `args` starting with `$.` is a selector into
+/// `{"input": <pipeline args>, "prev": <previous output>, "stages": [<outputs>]}`,
+/// e.g. `$.input.query`, `$.prev.items[0].url` or `$.stages[0].id`, and is
+/// replaced by the selected value. Everything else is passed through as is.
+#[derive(Debug, Clone)]
+pub struct PipelineStage {
+    pub tool: String,
+    pub args: Value,
+}
+
+impl PipelineStage {
+    pub fn new(tool: impl Into<String>, args: Value) -> Self {
+        Self {
+            tool: tool.into(),
+            args,
+        }
+    }
+}
+
+/// Runs a fixed chain of registered tools as a single tool and returns the
+/// last stage's output.
+///
+/// Stages are invoked through the registry with the pipeline caller's
+/// [`InvocationContext`], so their own policies apply to that caller and a
+/// stage's error, e.g. a rate limit, is returned as is. Called directly
+/// rather than through the registry, stages run with no roles. The input
+/// schema is assembled from the stage parameters fed by `$.input.*`
+/// selectors and the output schema is the last stage's.
+pub struct PipelineTool {
+    name: String,
+    description: Option<String>,
+    registry: Weak<ToolRegistry>,
+    stages: Vec<PipelineStage>,
+}
+
+impl PipelineTool {
+    /// Holds `registry` weakly so the pipeline can be registered in it.
+    pub fn new(
+        name: impl Into<String>,
+        registry: &Arc<ToolRegistry>,
+        stages: Vec<PipelineStage>,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            description: None,
+            registry: Arc::downgrade(registry),
+            stages,
+        }
+    }
+
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    fn registry(&self) -> Result<Arc<ToolRegistry>, ToolError> {
+        self.registry
+            .upgrade()
+            .ok_or_else(|| ToolError::Execution("pipeline registry was dropped".into()))
+    }
+}
+
+#[async_trait]
+impl Tool for PipelineTool {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn input_schema(&self) -> Value {
+        let mut properties = Map::new();
+        let mut required = Vec::new();
+        if let Some(registry) = self.registry.upgrade() {
+            for stage in &self.stages {
+                let Some(tool) = registry.get(&stage.tool) else {
+                    continue;
+                };
+                let schema = tool.input_schema();
+                let stage_required = schema["required"].as_array().cloned().unwrap_or_default();
+                for (param, template) in stage.args.as_object().into_iter().flatten() {
+                    let Some(input) = template.as_str().and_then(|s| s.strip_prefix("$.input."))
+                    else {
+                        continue;
+                    };
+                    // Only direct fields of the input become properties.
+                    if input.contains(['.', '[']) || properties.contains_key(input) {
+                        continue;
+                    }
+                    properties.insert(input.to_string(), schema["properties"][param].clone());
+                    if stage_required.iter().any(|r| r == param) {
+                        required.push(Value::String(input.to_string()));
+                    }
+                }
+            }
+        }
+        let mut schema = serde_json::json!({
+            "type": "object",
+            "properties": properties,
+            "required": required,
+        });
+        if let Some(description) = &self.description {
+            schema["description"] = Value::String(description.clone());
+        }
+        schema
+    }
+
+    fn output_schema(&self) -> Value {
+        self.stages
+            .last()
+            .zip(self.registry.upgrade())
+            .and_then(|(stage, registry)| registry.get(&stage.tool))
+            .map(|tool| tool.output_schema())
+            .unwrap_or_else(|| serde_json::json!({}))
+    }
+
+    async fn execute(&self, args: Value) -> Result<Value, ToolError> {
+        self.execute_with(args, &InvocationContext::new(&[]))
+            .await
+            .map_err(|e| match e {
+                ToolInvocationError::Tool(e) => e,
+                other => ToolError::Execution(other.to_string()),
+            })
+    }
+
+    async fn execute_with(
+        &self,
+        args: Value,
+        ctx: &InvocationContext,
+    ) -> Result<Value, ToolInvocationError> {
+        let registry = self.registry()?;
+        let mut scope = serde_json::json!({"input": args, "prev": Value::Null, "stages": []});
+        for stage in &self.stages {
+            let stage_args = fill_template(&stage.args, &scope)?;
+            let output = registry.invoke_with(&stage.tool, stage_args, ctx).await?;
+            scope["stages"]
+                .as_array_mut()
+                .expect("stages is an array")
+                .push(output.clone());
+            scope["prev"] = output;
+        }
+        Ok(scope["prev"].take())
+    }
+}
+
+fn fill_template(template: &Value, scope: &Value) -> Result<Value, ToolError> {
+    Ok(match template {
+        Value::String(text) if text.starts_with("$.") => select(scope, &text[2..])
+            .cloned()
+            .ok_or_else(|| ToolError::InvalidArgs(format!("selector {text} matched nothing")))?,
+        Value::Array(items) => Value::Array(
+            items
+                .iter()
+                .map(|item| fill_template(item, scope))
+                .collect::<Result<_, _>>()?,
+        ),
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(key, value)| Ok((key.clone(), fill_template(value, scope)?)))
+                .collect::<Result<_, ToolError>>()?,
+        ),
+        other => other.clone(),
+    })
+}
+
+/// Resolves a dotted path with `[index]` suffixes, e.g. `prev.items[0].url`.
+fn select<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    let mut current = value;
+    for part in path.split('.') {
+        let (key, indexes) = part
+            .split_once('[')
+            .map_or((part, ""), |(key, rest)| (key, rest));
+        if !key.is_empty() {
+            current = current.get(key)?;
+        }
+        if !indexes.is_empty() {
+            for index in indexes.trim_end_matches(']').split("][") {
+                current = current.get(index.parse::<usize>().ok()?)?;
+            }
+        }
+    }
+    Some(current)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtins::MathTool;
+    use serde_json::json;
+
+    struct EchoTool;
+
+    #[async_trait]
+    impl Tool for EchoTool {
+        fn name(&self) -> &'static str {
+            "echo"
+        }
+
+        fn input_schema(&self) -> Value {
+            json!({
+                "type": "object",
+                "properties": {"text": {"type": "string"}, "times": {"type": "integer"}},
+                "required": ["text"]
+            })
+        }
+
+        fn output_schema(&self) -> Value {
+            json!({"type": "object"})
+        }
+
+        async fn execute(&self, args: Value) -> Result<Value, ToolError> {
+            let text = args["text"].as_str().unwrap_or_default();
+            let times = args["times"].as_u64().unwrap_or(1) as usize;
+            Ok(json!({"items": [{"expression": format!("{} * 2", text.len() * times)}]}))
+        }
+    }
+
+    #[tokio::test]
+    async fn pipeline_maps_outputs_into_next_stage_args() {
+        let registry = Arc::new(ToolRegistry::new());
+        registry.register(EchoTool);
+        registry.register(MathTool);
+        let pipeline = PipelineTool::new(
+            "echo_then_math",
+            &registry,
+            vec![
+                PipelineStage::new("echo", json!({"text": "$.input.word", "times": 2})),
+                PipelineStage::new("math", json!({"expression": "$.prev.items[0].expression"})),
+            ],
+        )
+        .with_description("Doubles twice the word length");
+        registry.register(pipeline);
+
+        let schema = registry.get("echo_then_math").unwrap().input_schema();
+        assert_eq!(schema["properties"]["word"]["type"], "string");
+        assert_eq!(schema["required"], json!(["word"]));
+        assert_eq!(schema["description"], "Doubles twice the word length");
+
+        let output = registry
+            .invoke("echo_then_math", json!({"word": "abc"}), &[])
+            .await
+            .unwrap();
+        assert_eq!(output, json!(12.0));
+
+        assert!(registry
+            .invoke("echo_then_math", json!({}), &[])
+            .await
+            .is_err());
+        let broken = PipelineTool::new(
+            "broken",
+            &registry,
+            vec![PipelineStage::new(
+                "math",
+                json!({"expression": "$.prev.nothing"}),
+            )],
+        );
+        assert!(matches!(
+            broken.execute(json!({})).await,
+            Err(ToolError::InvalidArgs(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn pipeline_stages_run_as_the_caller() {
+        use crate::{RateLimitPolicy, ToolMetadata};
+        use std::time::Duration;
+
+        let registry = Arc::new(ToolRegistry::new());
+        registry.register_with_metadata(
+            MathTool,
+            ToolMetadata {
+                allowed_roles: vec!["analyst".into()],
+                rate_limit: Some(RateLimitPolicy {
+                    max_calls: 1,
+                    per: Duration::from_secs(60),
+                    per_caller: false,
+                }),
+                ..Default::default()
+            },
+        );
+        registry.register(PipelineTool::new(
+            "double",
+            &registry,
+            vec![PipelineStage::new("math", json!({"expression": "2 * 2"}))],
+        ));
+
+        let denied = registry.invoke("double", json!({}), &[]).await;
+        assert!(matches!(
+            denied,
+            Err(ToolInvocationError::AccessDenied { tool, .. }) if tool == "math"
+        ));
+
+        let analyst = InvocationContext::new(&["analyst".to_string()]);
+        let output = registry.invoke_with("double", json!({}), &analyst).await;
+        assert_eq!(output.unwrap(), json!(4.0));
+        let limited = registry.invoke_with("double", json!({}), &analyst).await;
+        assert!(matches!(
+            limited,
+            Err(ToolInvocationError::RateLimited { tool, .. }) if tool == "math"
+        ));
+    }
+}
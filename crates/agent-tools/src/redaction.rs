@@ -0,0 +1,155 @@
+use agent_core::{Redaction, SafetyPolicy};
+use agent_telemetry::REDACTED;
+use regex::Regex;
+use serde_json::Value;
+
+use crate::ToolError;
+
+/// Masks tool output according to [`SafetyPolicy::redaction_rules`].
+///
+/// A rule starting with `/` is a JSON pointer whose value is replaced
+/// wholesale; any other rule is a regex whose matches are replaced inside
+/// every string of the output.
+#[derive(Debug, Clone, Default)]
+pub struct OutputRedactor {
+    rules: Vec<(String, Rule)>,
+}
+
+#[derive(Debug, Clone)]
+enum Rule {
+    Pointer(String),
+    Pattern(Regex),
+}
+
+impl OutputRedactor {
+    pub fn new<S: AsRef<str>>(rules: &[S]) -> Result<Self, ToolError> {
+        let rules = rules
+            .iter()
+            .map(|rule| {
+                let rule = rule.as_ref();
+                let compiled = if rule.starts_with('/') {
+                    Rule::Pointer(rule.to_string())
+                } else {
+                    Rule::Pattern(Regex::new(rule).map_err(|e| {
+                        ToolError::InvalidArgs(format!("invalid redaction rule `{rule}`: {e}"))
+                    })?)
+                };
+                Ok((rule.to_string(), compiled))
+            })
+            .collect::<Result<_, ToolError>>()?;
+        Ok(Self { rules })
+    }
+
+    pub fn from_policy(policy: &SafetyPolicy) -> Result<Self, ToolError> {
+        Self::new(&policy.redaction_rules)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Masks `output` in place and reports every rule that matched.
+    pub fn redact(&self, tool: &str, output: &mut Value) -> Vec<Redaction> {
+        let mut report = Vec::new();
+        for (text, rule) in &self.rules {
+            let mut record = |path: String, count: usize| {
+                report.push(Redaction {
+                    tool: tool.to_string(),
+                    rule: text.clone(),
+                    path,
+                    count,
+                })
+            };
+            match rule {
+                Rule::Pointer(pointer) => {
+                    if let Some(field) = output.pointer_mut(pointer) {
+                        if field.as_str() != Some(REDACTED) {
+                            *field = Value::String(REDACTED.to_string());
+                            record(pointer.clone(), 1);
+                        }
+                    }
+                }
+                Rule::Pattern(pattern) => {
+                    mask_matches(pattern, output, &mut String::new(), &mut record)
+                }
+            }
+        }
+        report
+    }
+}
+
+fn mask_matches(
+    pattern: &Regex,
+    value: &mut Value,
+    path: &mut String,
+    record: &mut impl FnMut(String, usize),
+) {
+    match value {
+        Value::String(text) => {
+            let count = pattern.find_iter(text).count();
+            if count > 0 {
+                *text = pattern.replace_all(text, REDACTED).into_owned();
+                record(path.clone(), count);
+            }
+        }
+        Value::Array(items) => {
+            for (index, item) in items.iter_mut().enumerate() {
+                let len = path.len();
+                path.push_str(&format!("/{index}"));
+                mask_matches(pattern, item, path, record);
+                path.truncate(len);
+            }
+        }
+        Value::Object(fields) => {
+            for (key, field) in fields.iter_mut() {
+                let len = path.len();
+                path.push('/');
+                path.push_str(&key.replace('~', "~0").replace('/', "~1"));
+                mask_matches(pattern, field, path, record);
+                path.truncate(len);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn applies_pointer_and_pattern_rules_with_a_report() {
+        let redactor = OutputRedactor::new(&["/account/token", r"\d{3}-\d{2}-\d{4}"]).unwrap();
+        let mut output = json!({
+            "account": {"token": "tok_123", "owner": "Ada"},
+            "notes": ["SSN 123-45-6789 and 987-65-4321", "none"],
+            "a/b": "000-00-0000"
+        });
+
+        let report = redactor.redact("crm", &mut output);
+        assert_eq!(output["account"]["token"], REDACTED);
+        assert_eq!(output["account"]["owner"], "Ada");
+        assert_eq!(output["notes"][0], format!("SSN {REDACTED} and {REDACTED}"));
+        assert_eq!(output["a/b"], REDACTED);
+
+        let paths: Vec<(&str, usize)> = report
+            .iter()
+            .map(|entry| (entry.path.as_str(), entry.count))
+            .collect();
+        assert_eq!(
+            paths,
+            vec![("/account/token", 1), ("/a~1b", 1), ("/notes/0", 2)]
+        );
+        assert!(report.iter().all(|entry| entry.tool == "crm"));
+
+        // Already masked values are not reported again.
+        assert!(redactor.redact("crm", &mut output).is_empty());
+    }
+
+    #[test]
+    fn rejects_invalid_patterns() {
+        let err = OutputRedactor::new(&["(unclosed"]).unwrap_err();
+        assert!(matches!(err, ToolError::InvalidArgs(_)));
+    }
+}
@@ -0,0 +1,442 @@
+//! Hosted web search backends for [`SearchTool`](crate::builtins::SearchTool),
+//! each behind its own cargo feature (`bing`, `tavily`, `serpapi`).
+//!
+//! Every provider normalizes its response into [`SearchResult`]s, skipping
+//! entries without a URL, and can throttle itself with
+//! `with_rate_limit` so a shared key stays under the plan's quota.
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use agent_core::HttpClientConfig;
+use agent_telemetry::Secret;
+use async_trait::async_trait;
+use serde_json::Value;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+use crate::builtins::SearchProvider;
+use crate::builtins::SearchResult;
+use crate::ToolError;
+
+/// Sliding-window limiter that delays requests instead of failing them.
+#[derive(Debug)]
+struct Throttle {
+    max_requests: usize,
+    window: Duration,
+    sent: Mutex<VecDeque<Instant>>,
+}
+
+impl Throttle {
+    fn new(max_requests: u32, window: Duration) -> Self {
+        Self {
+            max_requests: max_requests.max(1) as usize,
+            window,
+            sent: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Waits until another request fits in the window, then records it.
+    async fn acquire(&self) {
+        let mut sent = self.sent.lock().await;
+        loop {
+            let now = Instant::now();
+            while sent
+                .front()
+                .is_some_and(|&at| now.duration_since(at) >= self.window)
+            {
+                sent.pop_front();
+            }
+            if sent.len() < self.max_requests {
+                sent.push_back(now);
+                return;
+            }
+            // Holding the lock keeps waiters in arrival order.
+            tokio::time::sleep_until(sent[0] + self.window).await;
+        }
+    }
+}
+
+/// Client, endpoint and limiter shared by every provider.
+#[derive(Debug)]
+struct Backend {
+    client: reqwest::Client,
+    endpoint: String,
+    throttle: Option<Throttle>,
+}
+
+impl Backend {
+    fn new(endpoint: &str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            endpoint: endpoint.to_string(),
+            throttle: None,
+        }
+    }
+
+    fn set_client_config(&mut self, config: &HttpClientConfig) -> Result<(), ToolError> {
+        self.client = config
+            .build_client()
+            .map_err(|e| ToolError::Execution(e.to_string()))?;
+        Ok(())
+    }
+
+    async fn send(
+        &self,
+        provider: &str,
+        request: reqwest::RequestBuilder,
+    ) -> Result<Value, ToolError> {
+        if let Some(throttle) = &self.throttle {
+            throttle.acquire().await;
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| ToolError::Execution(format!("{provider} request failed: {e}")))?;
+        let status = response.status();
+        if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            return Err(ToolError::Execution(format!(
+                "{provider} rate limited the request"
+            )));
+        }
+        if !status.is_success() {
+            return Err(ToolError::Execution(format!(
+                "{provider} returned {status}"
+            )));
+        }
+        response
+            .json()
+            .await
+            .map_err(|e| ToolError::Execution(format!("invalid {provider} response: {e}")))
+    }
+}
+
+/// Field names of one provider's result objects.
+struct ResultFields {
+    items: &'static str,
+    title: &'static str,
+    url: &'static str,
+    snippet: &'static str,
+}
+
+/// Reads up to `limit` results from `body`; a missing results array means no
+/// results, and missing titles or snippets become empty strings.
+fn normalize(body: &Value, fields: &ResultFields, limit: usize) -> Vec<SearchResult> {
+    let text = |item: &Value, key: &str| {
+        item.get(key)
+            .and_then(Value::as_str)
+            .map(|s| s.trim().to_string())
+            .unwrap_or_default()
+    };
+    body.pointer(fields.items)
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|item| {
+            let url = text(item, fields.url);
+            (!url.is_empty()).then(|| SearchResult {
+                title: text(item, fields.title),
+                url,
+                snippet: text(item, fields.snippet),
+            })
+        })
+        .take(limit)
+        .collect()
+}
+
+macro_rules! provider_builders {
+    ($provider:ty) => {
+        impl $provider {
+            /// Sends requests to `endpoint` instead of the public API.
+            pub fn with_endpoint(mut self, endpoint: impl Into<String>) -> Self {
+                self.backend.endpoint = endpoint.into();
+                self
+            }
+
+            pub fn with_client_config(
+                mut self,
+                config: &HttpClientConfig,
+            ) -> Result<Self, ToolError> {
+                self.backend.set_client_config(config)?;
+                Ok(self)
+            }
+
+            /// Allows at most `max_requests` per `window`; extra searches wait.
+            pub fn with_rate_limit(mut self, max_requests: u32, window: Duration) -> Self {
+                self.backend.throttle = Some(Throttle::new(max_requests, window));
+                self
+            }
+        }
+    };
+}
+
+/// Bing Web Search API v7.
+#[cfg(feature = "bing")]
+#[derive(Debug)]
+pub struct BingSearchProvider {
+    backend: Backend,
+    api_key: Secret<String>,
+}
+
+#[cfg(feature = "bing")]
+impl BingSearchProvider {
+    pub const ENDPOINT: &'static str = "https://api.bing.microsoft.com/v7.0/search";
+
+    pub fn new(api_key: Secret<String>) -> Self {
+        Self {
+            backend: Backend::new(Self::ENDPOINT),
+            api_key,
+        }
+    }
+}
+
+#[cfg(feature = "bing")]
+provider_builders!(BingSearchProvider);
+
+#[cfg(feature = "bing")]
+#[async_trait]
+impl SearchProvider for BingSearchProvider {
+    async fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchResult>, ToolError> {
+        let request = self
+            .backend
+            .client
+            .get(&self.backend.endpoint)
+            .header("Ocp-Apim-Subscription-Key", self.api_key.expose())
+            .query(&[("q", query), ("count", &limit.to_string())]);
+        let body = self.backend.send("bing", request).await?;
+        Ok(normalize(
+            &body,
+            &ResultFields {
+                items: "/webPages/value",
+                title: "name",
+                url: "url",
+                snippet: "snippet",
+            },
+            limit,
+        ))
+    }
+}
+
+/// Tavily search API.
+#[cfg(feature = "tavily")]
+#[derive(Debug)]
+pub struct TavilySearchProvider {
+    backend: Backend,
+    api_key: Secret<String>,
+}
+
+#[cfg(feature = "tavily")]
+impl TavilySearchProvider {
+    pub const ENDPOINT: &'static str = "https://api.tavily.com/search";
+
+    pub fn new(api_key: Secret<String>) -> Self {
+        Self {
+            backend: Backend::new(Self::ENDPOINT),
+            api_key,
+        }
+    }
+}
+
+#[cfg(feature = "tavily")]
+provider_builders!(TavilySearchProvider);
+
+#[cfg(feature = "tavily")]
+#[async_trait]
+impl SearchProvider for TavilySearchProvider {
+    async fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchResult>, ToolError> {
+        let request = self
+            .backend
+            .client
+            .post(&self.backend.endpoint)
+            .bearer_auth(self.api_key.expose())
+            .json(&serde_json::json!({"query": query, "max_results": limit}));
+        let body = self.backend.send("tavily", request).await?;
+        Ok(normalize(
+            &body,
+            &ResultFields {
+                items: "/results",
+                title: "title",
+                url: "url",
+                snippet: "content",
+            },
+            limit,
+        ))
+    }
+}
+
+/// SerpApi Google search.
+#[cfg(feature = "serpapi")]
+#[derive(Debug)]
+pub struct SerpApiSearchProvider {
+    backend: Backend,
+    api_key: Secret<String>,
+    engine: String,
+}
+
+#[cfg(feature = "serpapi")]
+impl SerpApiSearchProvider {
+    pub const ENDPOINT: &'static str = "https://serpapi.com/search.json";
+
+    pub fn new(api_key: Secret<String>) -> Self {
+        Self {
+            backend: Backend::new(Self::ENDPOINT),
+            api_key,
+            engine: "google".into(),
+        }
+    }
+
+    /// SerpApi engine to query (`google`, `bing`, `duckduckgo`...).
+    pub fn with_engine(mut self, engine: impl Into<String>) -> Self {
+        self.engine = engine.into();
+        self
+    }
+}
+
+#[cfg(feature = "serpapi")]
+provider_builders!(SerpApiSearchProvider);
+
+#[cfg(feature = "serpapi")]
+#[async_trait]
+impl SearchProvider for SerpApiSearchProvider {
+    async fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchResult>, ToolError> {
+        let request = self.backend.client.get(&self.backend.endpoint).query(&[
+            ("engine", self.engine.as_str()),
+            ("q", query),
+            ("num", &limit.to_string()),
+            ("api_key", self.api_key.expose()),
+        ]);
+        let body = self.backend.send("serpapi", request).await?;
+        Ok(normalize(
+            &body,
+            &ResultFields {
+                items: "/organic_results",
+                title: "title",
+                url: "link",
+                snippet: "snippet",
+            },
+            limit,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn normalize_skips_results_without_urls() {
+        let body = json!({"results": [
+            {"title": " Rust ", "url": "https://rust-lang.org", "content": "A language"},
+            {"title": "No link"},
+            {"url": "https://docs.rs"},
+            {"title": "Over limit", "url": "https://crates.io"}
+        ]});
+        let fields = ResultFields {
+            items: "/results",
+            title: "title",
+            url: "url",
+            snippet: "content",
+        };
+        let results = normalize(&body, &fields, 2);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].title, "Rust");
+        assert_eq!(results[1].url, "https://docs.rs");
+        assert_eq!(results[1].snippet, "");
+        assert!(normalize(&json!({}), &fields, 5).is_empty());
+    }
+
+    #[tokio::test]
+    async fn throttle_delays_requests_beyond_the_window() {
+        let throttle = Throttle::new(2, Duration::from_millis(200));
+        let started = Instant::now();
+        throttle.acquire().await;
+        throttle.acquire().await;
+        assert!(started.elapsed() < Duration::from_millis(100));
+        throttle.acquire().await;
+        assert!(started.elapsed() >= Duration::from_millis(200));
+    }
+
+    #[cfg(feature = "bing")]
+    #[tokio::test]
+    async fn bing_provider_sends_key_and_maps_web_pages() {
+        use wiremock::matchers::{header, method, path, query_param};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v7.0/search"))
+            .and(query_param("q", "rust"))
+            .and(query_param("count", "3"))
+            .and(header("Ocp-Apim-Subscription-Key", "bing-key"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "webPages": {"value": [
+                    {"name": "Rust", "url": "https://rust-lang.org", "snippet": "A language"}
+                ]}
+            })))
+            .mount(&server)
+            .await;
+
+        let provider = BingSearchProvider::new(Secret::new("bing-key".to_string()))
+            .with_endpoint(format!("{}/v7.0/search", server.uri()));
+        let results = provider.search("rust", 3).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].title, "Rust");
+        assert_eq!(results[0].snippet, "A language");
+    }
+
+    #[cfg(feature = "tavily")]
+    #[tokio::test]
+    async fn tavily_provider_posts_query_and_maps_results() {
+        use wiremock::matchers::{body_json, header, method};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(header("authorization", "Bearer tvly-key"))
+            .and(body_json(json!({"query": "rust", "max_results": 2})))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "results": [{"title": "Rust", "url": "https://rust-lang.org", "content": "A language"}]
+            })))
+            .mount(&server)
+            .await;
+
+        let provider = TavilySearchProvider::new(Secret::new("tvly-key".to_string()))
+            .with_endpoint(server.uri());
+        let results = provider.search("rust", 2).await.unwrap();
+        assert_eq!(results[0].snippet, "A language");
+    }
+
+    #[cfg(feature = "serpapi")]
+    #[tokio::test]
+    async fn serpapi_provider_maps_organic_results_and_rate_limits() {
+        use wiremock::matchers::{method, query_param};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(query_param("engine", "google"))
+            .and(query_param("api_key", "serp-key"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "organic_results": [{"title": "Rust", "link": "https://rust-lang.org"}]
+            })))
+            .mount(&server)
+            .await;
+
+        let provider = SerpApiSearchProvider::new(Secret::new("serp-key".to_string()))
+            .with_endpoint(server.uri());
+        let results = provider.search("rust", 5).await.unwrap();
+        assert_eq!(results[0].url, "https://rust-lang.org");
+        assert_eq!(results[0].snippet, "");
+
+        let limited = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(429))
+            .mount(&limited)
+            .await;
+        let provider = SerpApiSearchProvider::new(Secret::new("serp-key".to_string()))
+            .with_endpoint(limited.uri());
+        let err = provider.search("rust", 5).await.unwrap_err();
+        assert!(err.to_string().contains("rate limited"));
+    }
+}
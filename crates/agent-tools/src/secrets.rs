@@ -0,0 +1,328 @@
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+
+use agent_core::HttpClientConfig;
+use agent_telemetry::{Secret, REDACTED};
+use async_trait::async_trait;
+use serde_json::Value;
+
+use crate::ToolError;
+
+/// Source of credentials that tools resolve by name at execute time.
+#[async_trait]
+pub trait SecretsProvider: Send + Sync {
+    async fn get_secret(&self, name: &str) -> Result<Secret<String>, ToolError>;
+}
+
+/// Reads secrets from environment variables, optionally under a prefix.
+#[derive(Debug, Clone, Default)]
+pub struct EnvSecrets {
+    prefix: String,
+}
+
+impl EnvSecrets {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Looks up `name` as `<prefix><name>`.
+    pub fn with_prefix(prefix: impl Into<String>) -> Self {
+        Self {
+            prefix: prefix.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl SecretsProvider for EnvSecrets {
+    async fn get_secret(&self, name: &str) -> Result<Secret<String>, ToolError> {
+        let key = format!("{}{name}", self.prefix);
+        std::env::var(&key)
+            .map(Secret::new)
+            .map_err(|_| ToolError::Execution(format!("secret {key} is not set")))
+    }
+}
+
+/// Reads each secret from a file named after it, as mounted by Docker or
+/// Kubernetes secrets. A single trailing newline is stripped.
+#[derive(Debug, Clone)]
+pub struct FileSecrets {
+    dir: PathBuf,
+}
+
+impl FileSecrets {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+}
+
+#[async_trait]
+impl SecretsProvider for FileSecrets {
+    async fn get_secret(&self, name: &str) -> Result<Secret<String>, ToolError> {
+        if name.is_empty() || name.contains(['/', '\\']) || name == "." || name == ".." {
+            return Err(ToolError::InvalidArgs(format!(
+                "invalid secret name {name}"
+            )));
+        }
+        let mut value = tokio::fs::read_to_string(self.dir.join(name))
+            .await
+            .map_err(|_| ToolError::Execution(format!("secret {name} is not readable")))?;
+        if value.ends_with('\n') {
+            value.pop();
+            if value.ends_with('\r') {
+                value.pop();
+            }
+        }
+        Ok(Secret::new(value))
+    }
+}
+
+/// Fetches secrets from Azure Key Vault's REST API (`GET
+/// {vault_url}/secrets/{name}`) using a pre-acquired bearer token for the
+/// `https://vault.azure.net` resource.
+pub struct AzureKeyVaultSecrets {
+    client: reqwest::Client,
+    vault_url: String,
+    access_token: Secret<String>,
+}
+
+impl AzureKeyVaultSecrets {
+    pub const API_VERSION: &'static str = "7.4";
+
+    pub fn new(vault_url: impl Into<String>, access_token: Secret<String>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            vault_url: vault_url.into().trim_end_matches('/').to_string(),
+            access_token,
+        }
+    }
+
+    pub fn with_client_config(mut self, config: &HttpClientConfig) -> Result<Self, ToolError> {
+        self.client = config
+            .build_client()
+            .map_err(|e| ToolError::Execution(e.to_string()))?;
+        Ok(self)
+    }
+}
+
+#[async_trait]
+impl SecretsProvider for AzureKeyVaultSecrets {
+    async fn get_secret(&self, name: &str) -> Result<Secret<String>, ToolError> {
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            return Err(ToolError::InvalidArgs(format!(
+                "invalid Key Vault secret name {name}"
+            )));
+        }
+        let response = self
+            .client
+            .get(format!("{}/secrets/{name}", self.vault_url))
+            .query(&[("api-version", Self::API_VERSION)])
+            .bearer_auth(self.access_token.expose())
+            .send()
+            .await
+            .map_err(|e| ToolError::Execution(format!("key vault request failed: {e}")))?;
+        if !response.status().is_success() {
+            return Err(ToolError::Execution(format!(
+                "key vault returned {} for secret {name}",
+                response.status()
+            )));
+        }
+        let body: Value = response
+            .json()
+            .await
+            .map_err(|e| ToolError::Execution(format!("invalid key vault response: {e}")))?;
+        body.get("value")
+            .and_then(Value::as_str)
+            .map(|value| Secret::new(value.to_string()))
+            .ok_or_else(|| ToolError::Execution(format!("key vault secret {name} has no value")))
+    }
+}
+
+/// Wraps `inner` in an [`EncryptedStore`](agent_memory::EncryptedStore)
+/// keyed by the secret `name`, which holds 32 bytes as base64 or hex.
+#[cfg(feature = "encryption")]
+pub async fn encrypted_store(
+    inner: Arc<dyn agent_memory::MemoryStore>,
+    secrets: &dyn SecretsProvider,
+    name: &str,
+) -> Result<agent_memory::EncryptedStore, ToolError> {
+    let secret = secrets.get_secret(name).await?;
+    let key = agent_memory::EncryptionKey::parse(secret.expose())
+        .map_err(|e| ToolError::Execution(format!("secret {name}: {e}")))?;
+    Ok(agent_memory::EncryptedStore::new(inner, &key))
+}
+
+/// Remembers secret values handed out by [`SecretRedactor::track`]ed
+/// providers and masks them wherever they reappear in text or JSON.
+///
+/// Values shorter than [`SecretRedactor::MIN_LEN`] are not tracked, since
+/// masking them would mangle unrelated text.
+#[derive(Debug, Clone, Default)]
+pub struct SecretRedactor {
+    values: Arc<RwLock<BTreeSet<String>>>,
+}
+
+impl SecretRedactor {
+    pub const MIN_LEN: usize = 4;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wraps `provider` so every secret it returns is remembered.
+    pub fn track(&self, provider: Arc<dyn SecretsProvider>) -> RedactingSecrets {
+        RedactingSecrets {
+            inner: provider,
+            redactor: self.clone(),
+        }
+    }
+
+    pub fn remember(&self, value: &str) {
+        if value.len() >= Self::MIN_LEN {
+            self.values
+                .write()
+                .expect("secret redactor lock poisoned")
+                .insert(value.to_string());
+        }
+    }
+
+    pub fn redact_str(&self, text: &str) -> String {
+        let values = self.values.read().expect("secret redactor lock poisoned");
+        // Longest first so a secret containing another is masked whole.
+        let mut ordered: Vec<&String> = values.iter().collect();
+        ordered.sort_by_key(|value| std::cmp::Reverse(value.len()));
+        ordered.into_iter().fold(text.to_string(), |text, value| {
+            text.replace(value.as_str(), REDACTED)
+        })
+    }
+
+    pub fn redact_value(&self, value: &Value) -> Value {
+        match value {
+            Value::String(text) => Value::String(self.redact_str(text)),
+            Value::Array(items) => {
+                Value::Array(items.iter().map(|v| self.redact_value(v)).collect())
+            }
+            Value::Object(map) => Value::Object(
+                map.iter()
+                    .map(|(key, v)| (key.clone(), self.redact_value(v)))
+                    .collect(),
+            ),
+            other => other.clone(),
+        }
+    }
+}
+
+/// A [`SecretsProvider`] that reports resolved values to a [`SecretRedactor`].
+pub struct RedactingSecrets {
+    inner: Arc<dyn SecretsProvider>,
+    redactor: SecretRedactor,
+}
+
+#[async_trait]
+impl SecretsProvider for RedactingSecrets {
+    async fn get_secret(&self, name: &str) -> Result<Secret<String>, ToolError> {
+        let secret = self.inner.get_secret(name).await?;
+        self.redactor.remember(secret.expose());
+        Ok(secret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn file_secrets_read_mounted_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("db_password"), "hunter22\n").unwrap();
+        let secrets = FileSecrets::new(dir.path());
+
+        let secret = secrets.get_secret("db_password").await.unwrap();
+        assert_eq!(secret.expose(), "hunter22");
+        assert!(secrets.get_secret("missing").await.is_err());
+        assert!(matches!(
+            secrets.get_secret("../etc/passwd").await,
+            Err(ToolError::InvalidArgs(_))
+        ));
+    }
+
+    #[cfg(feature = "encryption")]
+    #[tokio::test]
+    async fn memory_encryption_keys_come_from_secrets() {
+        use agent_memory::{EncryptionKey, InMemoryStore, MemoryStore};
+
+        let dir = tempfile::tempdir().unwrap();
+        let key = EncryptionKey::generate();
+        std::fs::write(dir.path().join("memory_key"), key.to_base64()).unwrap();
+        std::fs::write(dir.path().join("short_key"), "c2hvcnQ=").unwrap();
+        let secrets = FileSecrets::new(dir.path());
+        let inner = Arc::new(InMemoryStore::new());
+
+        let store = encrypted_store(inner.clone(), &secrets, "memory_key")
+            .await
+            .unwrap();
+        store.put("note", &json!("remember the milk")).unwrap();
+        assert!(!inner
+            .get("note")
+            .unwrap()
+            .unwrap()
+            .to_string()
+            .contains("milk"));
+        assert_eq!(store.get("note").unwrap(), Some(json!("remember the milk")));
+        assert!(matches!(
+            encrypted_store(inner, &secrets, "short_key").await,
+            Err(ToolError::Execution(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn key_vault_secrets_use_the_rest_api() {
+        use wiremock::matchers::{header, method, path, query_param};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/secrets/api-key"))
+            .and(query_param(
+                "api-version",
+                AzureKeyVaultSecrets::API_VERSION,
+            ))
+            .and(header("authorization", "Bearer aad-token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"value": "kv-value"})))
+            .mount(&server)
+            .await;
+
+        let vault = AzureKeyVaultSecrets::new(
+            format!("{}/", server.uri()),
+            Secret::new("aad-token".to_string()),
+        );
+        assert_eq!(
+            vault.get_secret("api-key").await.unwrap().expose(),
+            "kv-value"
+        );
+        assert!(vault.get_secret("other").await.is_err());
+        assert!(vault.get_secret("bad/name").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn redactor_masks_values_from_tracked_providers() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("token"), "abc123secret").unwrap();
+        std::fs::write(dir.path().join("pin"), "42").unwrap();
+        let redactor = SecretRedactor::new();
+        let secrets = redactor.track(Arc::new(FileSecrets::new(dir.path())));
+
+        secrets.get_secret("token").await.unwrap();
+        secrets.get_secret("pin").await.unwrap();
+        assert_eq!(
+            redactor.redact_str("401 for token abc123secret (pin 42)"),
+            format!("401 for token {REDACTED} (pin 42)")
+        );
+        assert_eq!(
+            redactor.redact_value(&json!({"auth": ["Bearer abc123secret"], "n": 1})),
+            json!({"auth": [format!("Bearer {REDACTED}")], "n": 1})
+        );
+    }
+}
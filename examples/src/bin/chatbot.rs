@@ -2,7 +2,7 @@ use agent_core::{Agent, AgentContext, AgentError, Plan, Step, StepOutcome};
 use agent_examples::common::{
     base_context, default_policies, deterministic_loop, shared_tools_arc,
 };
-use agent_models::{LLMModel, StubModel};
+use agent_models::{GenerationOptions, LLMModel, StubModel};
 use agent_runtime::ControlLoop;
 use serde_json::json;
 use std::fmt;
@@ -35,6 +35,11 @@ impl Agent for ChatbotAgent {
                 subtasks: vec![],
                 policies: default_policies(),
                 chain_of_thought: None,
+                sub_plan: None,
+                depends_on: Vec::new(),
+                condition: None,
+                for_each: None,
+                delegate: None,
             }],
             metadata: json!({"agent": self.system_prompt}),
         })
@@ -59,6 +64,9 @@ impl Agent for ChatbotAgent {
                 retries: 0,
                 fallback_used: false,
                 control_notes: vec![],
+                fallback_applied: None,
+                failure_kind: None,
+                redactions: Vec::new(),
             });
         }
 
@@ -70,7 +78,10 @@ impl Agent for ChatbotAgent {
                 .and_then(|v| v.as_str())
                 .unwrap_or("Hello")
         );
-        let reply = self.model.generate(&prompt).await;
+        let reply = self
+            .model
+            .generate(&prompt, &GenerationOptions::default())
+            .await?;
         Ok(StepOutcome {
             step_id: step.id.clone(),
             output: json!({
@@ -82,6 +93,9 @@ impl Agent for ChatbotAgent {
             retries: 0,
             fallback_used: false,
             control_notes: vec!["chatbot".into()],
+            fallback_applied: None,
+            failure_kind: None,
+            redactions: Vec::new(),
         })
     }
 }
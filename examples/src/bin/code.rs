@@ -1,6 +1,6 @@
 use agent_core::{Agent, AgentContext, AgentError, Plan, Step, StepOutcome};
 use agent_examples::common::{base_context, default_policies, deterministic_loop};
-use agent_models::{LLMModel, StubModel};
+use agent_models::{GenerationOptions, LLMModel, StubModel};
 use agent_runtime::ControlLoop;
 use serde_json::json;
 use std::fmt;
@@ -31,6 +31,11 @@ impl Agent for CodeAgent {
                     subtasks: vec![],
                     policies: default_policies(),
                     chain_of_thought: None,
+                    sub_plan: None,
+                    depends_on: Vec::new(),
+                    condition: None,
+                    for_each: None,
+                    delegate: None,
                 },
                 Step {
                     id: "draft".into(),
@@ -40,6 +45,11 @@ impl Agent for CodeAgent {
                     subtasks: vec![],
                     policies: default_policies(),
                     chain_of_thought: None,
+                    sub_plan: None,
+                    depends_on: Vec::new(),
+                    condition: None,
+                    for_each: None,
+                    delegate: None,
                 },
                 Step {
                     id: "write".into(),
@@ -53,6 +63,11 @@ impl Agent for CodeAgent {
                     subtasks: vec![],
                     policies: default_policies(),
                     chain_of_thought: None,
+                    sub_plan: None,
+                    depends_on: Vec::new(),
+                    condition: None,
+                    for_each: None,
+                    delegate: None,
                 },
             ],
             metadata: json!({}),
@@ -78,6 +93,9 @@ impl Agent for CodeAgent {
                 retries: 0,
                 fallback_used: false,
                 control_notes: vec!["codegen".into()],
+                fallback_applied: None,
+                failure_kind: None,
+                redactions: Vec::new(),
             });
         }
 
@@ -86,7 +104,10 @@ impl Agent for CodeAgent {
             .get("goal")
             .and_then(|v| v.as_str())
             .unwrap_or("Write code");
-        let completion = self.model.generate(prompt).await;
+        let completion = self
+            .model
+            .generate(prompt, &GenerationOptions::default())
+            .await?;
         Ok(StepOutcome {
             step_id: step.id.clone(),
             output: json!({"draft": completion.content}),
@@ -95,6 +116,9 @@ impl Agent for CodeAgent {
             retries: 0,
             fallback_used: false,
             control_notes: vec!["codegen".into()],
+            fallback_applied: None,
+            failure_kind: None,
+            redactions: Vec::new(),
         })
     }
 }
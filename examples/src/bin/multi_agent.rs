@@ -1,6 +1,6 @@
 use agent_core::{Agent, AgentContext, AgentError, Plan, Step, StepOutcome};
 use agent_examples::common::{base_context, default_policies, deterministic_loop};
-use agent_models::{LLMModel, StubModel};
+use agent_models::{GenerationOptions, LLMModel, ModelError, StubModel};
 use agent_runtime::ControlLoop;
 use serde_json::json;
 use std::fmt;
@@ -11,11 +11,14 @@ struct ResearchPartner {
 }
 
 impl ResearchPartner {
-    async fn investigate(&self, topic: &str) -> String {
+    async fn investigate(&self, topic: &str) -> Result<String, ModelError> {
         self.model
-            .generate(&format!("Collect findings about {topic}"))
+            .generate(
+                &format!("Collect findings about {topic}"),
+                &GenerationOptions::default(),
+            )
             .await
-            .content
+            .map(|response| response.content)
     }
 }
 
@@ -24,11 +27,14 @@ struct BuilderPartner {
 }
 
 impl BuilderPartner {
-    async fn propose(&self, idea: &str) -> String {
+    async fn propose(&self, idea: &str) -> Result<String, ModelError> {
         self.model
-            .generate(&format!("Draft an implementation for {idea}"))
+            .generate(
+                &format!("Draft an implementation for {idea}"),
+                &GenerationOptions::default(),
+            )
             .await
-            .content
+            .map(|response| response.content)
     }
 }
 
@@ -58,6 +64,11 @@ impl Agent for MultiAgentCoordinator {
                     subtasks: vec![],
                     policies: default_policies(),
                     chain_of_thought: None,
+                    sub_plan: None,
+                    depends_on: Vec::new(),
+                    condition: None,
+                    for_each: None,
+                    delegate: None,
                 },
                 Step {
                     id: "research".into(),
@@ -67,6 +78,11 @@ impl Agent for MultiAgentCoordinator {
                     subtasks: vec![],
                     policies: default_policies(),
                     chain_of_thought: None,
+                    sub_plan: None,
+                    depends_on: Vec::new(),
+                    condition: None,
+                    for_each: None,
+                    delegate: None,
                 },
                 Step {
                     id: "build".into(),
@@ -76,6 +92,11 @@ impl Agent for MultiAgentCoordinator {
                     subtasks: vec![],
                     policies: default_policies(),
                     chain_of_thought: None,
+                    sub_plan: None,
+                    depends_on: Vec::new(),
+                    condition: None,
+                    for_each: None,
+                    delegate: None,
                 },
                 Step {
                     id: "debrief".into(),
@@ -85,6 +106,11 @@ impl Agent for MultiAgentCoordinator {
                     subtasks: vec![],
                     policies: default_policies(),
                     chain_of_thought: None,
+                    sub_plan: None,
+                    depends_on: Vec::new(),
+                    condition: None,
+                    for_each: None,
+                    delegate: None,
                 },
             ],
             metadata: json!({}),
@@ -110,6 +136,9 @@ impl Agent for MultiAgentCoordinator {
                 retries: 0,
                 fallback_used: false,
                 control_notes: vec!["multi-agent".into()],
+                fallback_applied: None,
+                failure_kind: None,
+                redactions: Vec::new(),
             });
         }
 
@@ -121,7 +150,7 @@ impl Agent for MultiAgentCoordinator {
                     .and_then(|v| v.as_str())
                     .unwrap_or("unknown topic");
                 (
-                    self.researcher.investigate(topic).await,
+                    self.researcher.investigate(topic).await?,
                     "researcher".to_string(),
                 )
             }
@@ -131,7 +160,7 @@ impl Agent for MultiAgentCoordinator {
                     .get("idea")
                     .and_then(|v| v.as_str())
                     .unwrap_or("unknown idea");
-                (self.builder.propose(idea).await, "builder".to_string())
+                (self.builder.propose(idea).await?, "builder".to_string())
             }
             _ => ("noop".into(), "noop".into()),
         };
@@ -144,6 +173,9 @@ impl Agent for MultiAgentCoordinator {
             retries: 0,
             fallback_used: false,
             control_notes: vec![control_note],
+            fallback_applied: None,
+            failure_kind: None,
+            redactions: Vec::new(),
         })
     }
 }
@@ -2,7 +2,7 @@ use agent_core::{
     Agent, AgentContext, AgentError, FallbackPolicy, FallbackStrategy, Plan, Step, StepOutcome,
 };
 use agent_examples::common::{base_context, default_policies, deterministic_loop};
-use agent_models::{LLMModel, StubModel};
+use agent_models::{GenerationOptions, LLMModel, StubModel};
 use agent_runtime::ControlLoop;
 use serde_json::json;
 use std::fmt;
@@ -35,6 +35,11 @@ fn planned_step(id: &str, description: &str, tool: Option<&str>, args: serde_jso
         subtasks: vec![],
         policies,
         chain_of_thought: None,
+        sub_plan: None,
+        depends_on: Vec::new(),
+        condition: None,
+        for_each: None,
+        delegate: None,
     }
 }
 
@@ -92,6 +97,9 @@ impl Agent for PlanningExecutionAgent {
                 retries: 0,
                 fallback_used: false,
                 control_notes: vec!["plan+execute".into()],
+                fallback_applied: None,
+                failure_kind: None,
+                redactions: Vec::new(),
             });
         }
 
@@ -100,7 +108,10 @@ impl Agent for PlanningExecutionAgent {
             .get("prompt")
             .and_then(|v| v.as_str())
             .unwrap_or("Summarize plan");
-        let response = self.model.generate(prompt).await;
+        let response = self
+            .model
+            .generate(prompt, &GenerationOptions::default())
+            .await?;
         Ok(StepOutcome {
             step_id: step.id.clone(),
             output: json!({"message": response.content}),
@@ -109,6 +120,9 @@ impl Agent for PlanningExecutionAgent {
             retries: 0,
             fallback_used: false,
             control_notes: vec!["plan+execute".into()],
+            fallback_applied: None,
+            failure_kind: None,
+            redactions: Vec::new(),
         })
     }
 }
@@ -1,6 +1,6 @@
 use agent_core::{Agent, AgentContext, AgentError, Plan, Step, StepOutcome};
 use agent_examples::common::{base_context, default_policies, reactive_loop};
-use agent_models::{LLMModel, StubModel};
+use agent_models::{GenerationOptions, LLMModel, StubModel};
 use agent_runtime::ControlLoop;
 use serde_json::json;
 use std::fmt;
@@ -34,6 +34,11 @@ impl Agent for ReactAgent {
                     cot.push("Need context before acting");
                     cot
                 }),
+                sub_plan: None,
+                depends_on: Vec::new(),
+                condition: None,
+                for_each: None,
+                delegate: None,
             },
             1 => Step {
                 id: "action".into(),
@@ -43,6 +48,11 @@ impl Agent for ReactAgent {
                 subtasks: vec![],
                 policies: default_policies(),
                 chain_of_thought: None,
+                sub_plan: None,
+                depends_on: Vec::new(),
+                condition: None,
+                for_each: None,
+                delegate: None,
             },
             _ => Step {
                 id: "answer".into(),
@@ -52,6 +62,11 @@ impl Agent for ReactAgent {
                 subtasks: vec![],
                 policies: default_policies(),
                 chain_of_thought: None,
+                sub_plan: None,
+                depends_on: Vec::new(),
+                condition: None,
+                for_each: None,
+                delegate: None,
             },
         };
 
@@ -81,6 +96,9 @@ impl Agent for ReactAgent {
                 retries: 0,
                 fallback_used: false,
                 control_notes: vec!["react".into()],
+                fallback_applied: None,
+                failure_kind: None,
+                redactions: Vec::new(),
             });
         }
 
@@ -89,7 +107,10 @@ impl Agent for ReactAgent {
             .get("prompt")
             .and_then(|v| v.as_str())
             .unwrap_or("Reflect");
-        let response = self.model.generate(prompt).await;
+        let response = self
+            .model
+            .generate(prompt, &GenerationOptions::default())
+            .await?;
         Ok(StepOutcome {
             step_id: step.id.clone(),
             output: json!({"message": response.content}),
@@ -98,6 +119,9 @@ impl Agent for ReactAgent {
             retries: 0,
             fallback_used: false,
             control_notes: vec!["react".into()],
+            fallback_applied: None,
+            failure_kind: None,
+            redactions: Vec::new(),
         })
     }
 }
@@ -56,6 +56,11 @@ impl Agent for ResearchAgent {
                     subtasks: vec![],
                     policies: default_policies(),
                     chain_of_thought: None,
+                    sub_plan: None,
+                    depends_on: Vec::new(),
+                    condition: None,
+                    for_each: None,
+                    delegate: None,
                 },
                 Step {
                     id: "synthesize".into(),
@@ -71,6 +76,11 @@ impl Agent for ResearchAgent {
                     subtasks: vec![],
                     policies: default_policies(),
                     chain_of_thought: None,
+                    sub_plan: None,
+                    depends_on: Vec::new(),
+                    condition: None,
+                    for_each: None,
+                    delegate: None,
                 },
             ],
             metadata: json!({}),
@@ -96,6 +106,9 @@ impl Agent for ResearchAgent {
                 retries: 0,
                 fallback_used: false,
                 control_notes: vec!["research".into()],
+                fallback_applied: None,
+                failure_kind: None,
+                redactions: Vec::new(),
             });
         }
 
@@ -114,6 +127,9 @@ impl Agent for ResearchAgent {
             retries: 0,
             fallback_used: false,
             control_notes: vec!["research".into()],
+            fallback_applied: None,
+            failure_kind: None,
+            redactions: Vec::new(),
         })
     }
 }
@@ -121,7 +137,7 @@ impl Agent for ResearchAgent {
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     println!("Running research example...");
-    let mut registry = agent_examples::common::default_tools();
+    let registry = agent_examples::common::default_tools();
     let search = SearchTool::new(Arc::new(StaticSearchProvider));
     registry.register(search);
     let tools = Arc::new(registry);
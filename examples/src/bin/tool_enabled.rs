@@ -1,6 +1,6 @@
 use agent_core::{Agent, AgentContext, AgentError, Plan, Step, StepOutcome};
 use agent_examples::common::{base_context, default_policies, deterministic_loop};
-use agent_models::{LLMModel, StubModel};
+use agent_models::{GenerationOptions, LLMModel, StubModel};
 use agent_runtime::ControlLoop;
 use serde_json::json;
 use std::fmt;
@@ -31,6 +31,11 @@ impl Agent for ToolEnabledAgent {
                     subtasks: vec![],
                     policies: default_policies(),
                     chain_of_thought: None,
+                    sub_plan: None,
+                    depends_on: Vec::new(),
+                    condition: None,
+                    for_each: None,
+                    delegate: None,
                 },
                 Step {
                     id: "compute".into(),
@@ -40,6 +45,11 @@ impl Agent for ToolEnabledAgent {
                     subtasks: vec![],
                     policies: default_policies(),
                     chain_of_thought: None,
+                    sub_plan: None,
+                    depends_on: Vec::new(),
+                    condition: None,
+                    for_each: None,
+                    delegate: None,
                 },
                 Step {
                     id: "respond".into(),
@@ -49,6 +59,11 @@ impl Agent for ToolEnabledAgent {
                     subtasks: vec![],
                     policies: default_policies(),
                     chain_of_thought: None,
+                    sub_plan: None,
+                    depends_on: Vec::new(),
+                    condition: None,
+                    for_each: None,
+                    delegate: None,
                 },
             ],
             metadata: json!({}),
@@ -74,6 +89,9 @@ impl Agent for ToolEnabledAgent {
                 retries: 0,
                 fallback_used: false,
                 control_notes: vec!["tool-enabled".into()],
+                fallback_applied: None,
+                failure_kind: None,
+                redactions: Vec::new(),
             });
         }
 
@@ -82,7 +100,10 @@ impl Agent for ToolEnabledAgent {
             .get("prompt")
             .and_then(|v| v.as_str())
             .unwrap_or("Summarize");
-        let content = self.model.generate(prompt).await;
+        let content = self
+            .model
+            .generate(prompt, &GenerationOptions::default())
+            .await?;
         Ok(StepOutcome {
             step_id: step.id.clone(),
             output: json!({"message": content.content}),
@@ -91,6 +112,9 @@ impl Agent for ToolEnabledAgent {
             retries: 0,
             fallback_used: false,
             control_notes: vec!["tool-enabled".into()],
+            fallback_applied: None,
+            failure_kind: None,
+            redactions: Vec::new(),
         })
     }
 }
@@ -1,6 +1,6 @@
 use agent_core::{Agent, AgentContext, AgentError, Plan, Step, StepOutcome};
 use agent_examples::common::{base_context, default_policies, deterministic_loop};
-use agent_models::{LLMModel, StubModel};
+use agent_models::{GenerationOptions, LLMModel, StubModel};
 use agent_runtime::ControlLoop;
 use agent_tools::builtins::{SearchProvider, SearchResult, SearchTool};
 use serde_json::json;
@@ -52,6 +52,11 @@ impl Agent for WebSearchAgent {
                     subtasks: vec![],
                     policies: default_policies(),
                     chain_of_thought: None,
+                    sub_plan: None,
+                    depends_on: Vec::new(),
+                    condition: None,
+                    for_each: None,
+                    delegate: None,
                 },
                 Step {
                     id: "summarize".into(),
@@ -61,6 +66,11 @@ impl Agent for WebSearchAgent {
                     subtasks: vec![],
                     policies: default_policies(),
                     chain_of_thought: None,
+                    sub_plan: None,
+                    depends_on: Vec::new(),
+                    condition: None,
+                    for_each: None,
+                    delegate: None,
                 },
             ],
             metadata: json!({}),
@@ -86,6 +96,9 @@ impl Agent for WebSearchAgent {
                 retries: 0,
                 fallback_used: false,
                 control_notes: vec!["web".into()],
+                fallback_applied: None,
+                failure_kind: None,
+                redactions: Vec::new(),
             });
         }
 
@@ -101,8 +114,11 @@ impl Agent for WebSearchAgent {
             .unwrap_or("https://example.com");
         let summary = self
             .model
-            .generate(&format!("Summarize {title} at {url}"))
-            .await;
+            .generate(
+                &format!("Summarize {title} at {url}"),
+                &GenerationOptions::default(),
+            )
+            .await?;
         Ok(StepOutcome {
             step_id: step.id.clone(),
             output: json!({"summary": summary.content, "source": url}),
@@ -111,6 +127,9 @@ impl Agent for WebSearchAgent {
             retries: 0,
             fallback_used: false,
             control_notes: vec!["web".into()],
+            fallback_applied: None,
+            failure_kind: None,
+            redactions: Vec::new(),
         })
     }
 }
@@ -118,7 +137,7 @@ impl Agent for WebSearchAgent {
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     println!("Running web-search example...");
-    let mut registry = agent_examples::common::default_tools();
+    let registry = agent_examples::common::default_tools();
     registry.register(SearchTool::new(Arc::new(DemoSearchProvider)));
     let tools = Arc::new(registry);
 
@@ -22,13 +22,14 @@ pub fn base_context(name: &str) -> AgentContext {
         metadata: json!({}),
         memory: None,
         tool_permissions: ToolPermissions::default(),
+        cancellation: None,
     }
 }
 
 pub fn default_tools() -> ToolRegistry {
-    let mut registry = ToolRegistry::new();
+    let registry = ToolRegistry::new();
     registry.register(TimeTool);
-    registry.register(LogTool);
+    registry.register(LogTool::new());
     registry.register(MathTool);
     registry.register(HttpFetchTool::new());
     let root = std::env::current_dir().expect("cwd");
@@ -41,6 +42,7 @@ pub fn deterministic_loop(iterations: usize) -> ControlLoop {
         max_iterations: iterations,
         delay: Duration::from_millis(0),
         mode: ControlMode::Deterministic,
+        ..Default::default()
     }
 }
 
@@ -49,6 +51,7 @@ pub fn reactive_loop(iterations: usize) -> ControlLoop {
         max_iterations: iterations,
         delay: Duration::from_millis(0),
         mode: ControlMode::Reactive,
+        ..Default::default()
     }
 }
 
@@ -60,6 +63,7 @@ pub fn default_policies() -> StepPolicies {
             allow_tool_execution: true,
             ..SafetyPolicy::default()
         },
+        timeout_ms: None,
     }
 }
 